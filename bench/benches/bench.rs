@@ -16,6 +16,8 @@ criterion_group!(benches,
 	lightning::routing::router::benches::generate_routes_with_nonlinear_probabilistic_scorer,
 	lightning::routing::router::benches::generate_mpp_routes_with_nonlinear_probabilistic_scorer,
 	lightning::routing::router::benches::generate_large_mpp_routes_with_nonlinear_probabilistic_scorer,
+	lightning::routing::router::benches::generate_mpp_routes_with_min_cost_flow,
+	lightning::routing::router::benches::generate_large_mpp_routes_with_min_cost_flow,
 	lightning::sign::benches::bench_get_secure_random_bytes,
 	lightning::ln::channelmanager::bench::bench_sends,
 	lightning_persister::fs_store::bench::bench_sends,
@@ -79,6 +79,10 @@ cargo test --verbose --color always --features rpc-client,rest-client
 cargo check --verbose --color always --features rpc-client,rest-client
 cargo test --verbose --color always --features rpc-client,rest-client,tokio
 cargo check --verbose --color always --features rpc-client,rest-client,tokio
+cargo test --verbose --color always --features bip157-client
+cargo check --verbose --color always --features bip157-client
+cargo test --verbose --color always --features bip157-client,tokio
+cargo check --verbose --color always --features bip157-client,tokio
 popd
 
 if [[ "$HOST_PLATFORM" != *windows* ]]; then
@@ -102,6 +106,10 @@ if [[ "$HOST_PLATFORM" != *windows* ]]; then
 	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo check --verbose --color always --features esplora-async-https
 	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo test --verbose --color always --features electrum
 	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo check --verbose --color always --features electrum
+	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo test --verbose --color always --features electrum-async
+	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo check --verbose --color always --features electrum-async
+	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo test --verbose --color always --features electrum-async-tls
+	RUSTFLAGS="$RUSTFLAGS --cfg no_download" cargo check --verbose --color always --features electrum-async-tls
 
 	popd
 fi
@@ -25,7 +25,7 @@ use lightning::chain;
 use lightning::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use lightning::chain::chainmonitor::{ChainMonitor, Persist};
 use lightning::sign::{EntropySource, NodeSigner, SignerProvider};
-use lightning::events::{Event, PathFailure};
+use lightning::events::{Event, HTLCDestination, PathFailure};
 #[cfg(feature = "std")]
 use lightning::events::EventHandler;
 #[cfg(any(feature = "std", feature = "futures"))]
@@ -34,11 +34,13 @@ use lightning::events::EventsProvider;
 use lightning::ln::channelmanager::ChannelManager;
 use lightning::ln::msgs::OnionMessageHandler;
 use lightning::ln::peer_handler::APeerManager;
+use lightning::offers::offer::CurrencyConversion;
 use lightning::routing::gossip::{NetworkGraph, P2PGossipSync};
 use lightning::routing::utxo::UtxoLookup;
 use lightning::routing::router::Router;
 use lightning::routing::scoring::{ScoreUpdate, WriteableScore};
 use lightning::util::logger::Logger;
+use lightning::util::metrics::{self, MetricsRecorder};
 use lightning::util::persist::Persister;
 #[cfg(feature = "std")]
 use lightning::util::wakers::Sleeper;
@@ -130,6 +132,11 @@ const REBROADCAST_TIMER: u64 = 30;
 #[cfg(test)]
 const REBROADCAST_TIMER: u64 = 1;
 
+#[cfg(all(not(test), any(feature = "std", feature = "futures")))]
+const TX_SYNC_TIMER: u64 = 30;
+#[cfg(test)]
+const TX_SYNC_TIMER: u64 = 1;
+
 #[cfg(feature = "futures")]
 /// core::cmp::min is not currently const, so we define a trivial (and equivalent) replacement
 const fn min_u64(a: u64, b: u64) -> u64 { if a < b { a } else { b } }
@@ -277,12 +284,50 @@ fn update_scorer<'a, S: 'static + Deref<Target = SC> + Send + Sync, SC: 'a + Wri
 	true
 }
 
+#[cfg(any(feature = "std", feature = "futures"))]
+fn log_sync_result<L: Deref>(logger: &L, result: Result<(), chain::TxSyncError>) where L::Target: Logger {
+	match result {
+		Ok(()) => log_trace!(logger, "Synced transactions via TransactionSync"),
+		Err(e) => log_error!(logger, "Error: Failed to sync transactions: {}, retrying in {} seconds", e, TX_SYNC_TIMER),
+	}
+}
+
+/// Records the time spent persisting the given `object`, if the wall clock time is available.
+fn record_persistence_duration<MR: Deref>(
+	metrics_recorder: &MR, object: &'static str, start: Option<Duration>, end: Option<Duration>
+) where MR::Target: MetricsRecorder {
+	if let (Some(start), Some(end)) = (start, end) {
+		let duration = end.saturating_sub(start);
+		metrics_recorder.record_histogram(metrics::PERSISTENCE_DURATION, &[("object", object)], duration.as_secs_f64());
+	}
+}
+
+/// Records the forwarded and failed HTLCs reported by the given `event`.
+fn record_event_metrics<MR: Deref>(metrics_recorder: &MR, event: &Event) where MR::Target: MetricsRecorder {
+	match event {
+		Event::PaymentForwarded { .. } => {
+			metrics_recorder.increment_counter(metrics::HTLCS_FORWARDED, &[], 1);
+		},
+		Event::HTLCHandlingFailed { failed_next_destination, .. } => {
+			let reason = match failed_next_destination {
+				HTLCDestination::NextHopChannel { .. } => "next_hop_channel",
+				HTLCDestination::UnknownNextHop { .. } => "unknown_next_hop",
+				HTLCDestination::InvalidForward { .. } => "invalid_forward",
+				HTLCDestination::FailedPayment { .. } => "failed_payment",
+				HTLCDestination::NextTrampoline { .. } => "next_trampoline",
+			};
+			metrics_recorder.increment_counter(metrics::HTLCS_FAILED, &[("reason", reason)], 1);
+		},
+		_ => {},
+	}
+}
+
 macro_rules! define_run_body {
 	(
 		$persister: ident, $chain_monitor: ident, $process_chain_monitor_events: expr,
 		$channel_manager: ident, $process_channel_manager_events: expr,
 		$peer_manager: ident, $process_onion_message_handler_events: expr, $gossip_sync: ident,
-		$logger: ident, $scorer: ident, $loop_exit_check: expr, $await: expr, $get_timer: expr,
+		$logger: ident, $metrics_recorder: ident, $scorer: ident, $loop_exit_check: expr, $await: expr, $get_timer: expr,
 		$timer_elapsed: expr, $check_slow_await: expr, $time_fetch: expr,
 	) => { {
 		log_trace!($logger, "Calling ChannelManager's timer_tick_occurred on startup");
@@ -338,7 +383,9 @@ macro_rules! define_run_body {
 
 			if $channel_manager.get_and_clear_needs_persistence() {
 				log_trace!($logger, "Persisting ChannelManager...");
+				let persist_start = $time_fetch();
 				$persister.persist_manager(&*$channel_manager)?;
+				record_persistence_duration(&$metrics_recorder, "channel_manager", persist_start, $time_fetch());
 				log_trace!($logger, "Done persisting ChannelManager.");
 			}
 			if $timer_elapsed(&mut last_freshness_call, FRESHNESS_TIMER) {
@@ -395,9 +442,11 @@ macro_rules! define_run_body {
 						log_trace!($logger, "Persisting network graph.");
 					}
 
+					let persist_start = $time_fetch();
 					if let Err(e) = $persister.persist_graph(network_graph) {
 						log_error!($logger, "Error: Failed to persist network graph, check your disk and permissions {}", e)
 					}
+					record_persistence_duration(&$metrics_recorder, "network_graph", persist_start, $time_fetch());
 
 					have_pruned = true;
 				}
@@ -423,9 +472,11 @@ macro_rules! define_run_body {
 					} else {
 						log_trace!($logger, "Persisting scorer");
 					}
+					let persist_start = $time_fetch();
 					if let Err(e) = $persister.persist_scorer(&scorer) {
 						log_error!($logger, "Error: Failed to persist scorer, check your disk and permissions {}", e)
 					}
+					record_persistence_duration(&$metrics_recorder, "scorer", persist_start, $time_fetch());
 				}
 				last_scorer_persist_call = $get_timer(SCORER_PERSIST_TIMER);
 			}
@@ -562,14 +613,15 @@ use core::task;
 /// # type MyUtxoLookup = dyn lightning::routing::utxo::UtxoLookup + Send + Sync;
 /// # type MyFilter = dyn lightning::chain::Filter + Send + Sync;
 /// # type MyLogger = dyn lightning::util::logger::Logger + Send + Sync;
-/// # type MyChainMonitor = lightning::chain::chainmonitor::ChainMonitor<lightning::sign::InMemorySigner, Arc<MyFilter>, Arc<MyBroadcaster>, Arc<MyFeeEstimator>, Arc<MyLogger>, Arc<MyStore>>;
+/// # type MyMetricsRecorder = dyn lightning::util::metrics::MetricsRecorder + Send + Sync;
+/// # type MyChainMonitor = lightning::chain::chainmonitor::ChainMonitor<lightning::sign::InMemorySigner, Arc<MyFilter>, Arc<MyBroadcaster>, Arc<MyFeeEstimator>, Arc<MyLogger>, Arc<MyStore>, Arc<MyMetricsRecorder>>;
 /// # type MyPeerManager = lightning::ln::peer_handler::SimpleArcPeerManager<MySocketDescriptor, MyChainMonitor, MyBroadcaster, MyFeeEstimator, Arc<MyUtxoLookup>, MyLogger>;
 /// # type MyNetworkGraph = lightning::routing::gossip::NetworkGraph<Arc<MyLogger>>;
 /// # type MyGossipSync = lightning::routing::gossip::P2PGossipSync<Arc<MyNetworkGraph>, Arc<MyUtxoLookup>, Arc<MyLogger>>;
 /// # type MyChannelManager = lightning::ln::channelmanager::SimpleArcChannelManager<MyChainMonitor, MyBroadcaster, MyFeeEstimator, MyLogger>;
 /// # type MyScorer = RwLock<lightning::routing::scoring::ProbabilisticScorer<Arc<MyNetworkGraph>, Arc<MyLogger>>>;
 ///
-/// # async fn setup_background_processing(my_persister: Arc<MyStore>, my_event_handler: Arc<MyEventHandler>, my_chain_monitor: Arc<MyChainMonitor>, my_channel_manager: Arc<MyChannelManager>, my_gossip_sync: Arc<MyGossipSync>, my_logger: Arc<MyLogger>, my_scorer: Arc<MyScorer>, my_peer_manager: Arc<MyPeerManager>) {
+/// # async fn setup_background_processing(my_persister: Arc<MyStore>, my_event_handler: Arc<MyEventHandler>, my_chain_monitor: Arc<MyChainMonitor>, my_channel_manager: Arc<MyChannelManager>, my_gossip_sync: Arc<MyGossipSync>, my_logger: Arc<MyLogger>, my_metrics_recorder: Arc<MyMetricsRecorder>, my_scorer: Arc<MyScorer>, my_peer_manager: Arc<MyPeerManager>) {
 ///	let background_persister = Arc::clone(&my_persister);
 ///	let background_event_handler = Arc::clone(&my_event_handler);
 ///	let background_chain_mon = Arc::clone(&my_chain_monitor);
@@ -577,6 +629,7 @@ use core::task;
 ///	let background_gossip_sync = GossipSync::p2p(Arc::clone(&my_gossip_sync));
 ///	let background_peer_man = Arc::clone(&my_peer_manager);
 ///	let background_logger = Arc::clone(&my_logger);
+///	let background_metrics_recorder = Arc::clone(&my_metrics_recorder);
 ///	let background_scorer = Arc::clone(&my_scorer);
 ///
 ///	// Setup the sleeper.
@@ -603,6 +656,7 @@ use core::task;
 ///			background_gossip_sync,
 ///			background_peer_man,
 ///			background_logger,
+///			background_metrics_recorder,
 ///			Some(background_scorer),
 ///			sleeper,
 ///			mobile_interruptable_platform,
@@ -631,12 +685,14 @@ pub async fn process_events_async<
 	R: 'static + Deref + Send + Sync,
 	G: 'static + Deref<Target = NetworkGraph<L>> + Send + Sync,
 	L: 'static + Deref + Send + Sync,
+	CC: 'static + Deref + Send + Sync,
+	MR: 'static + Deref + Send + Sync,
 	P: 'static + Deref + Send + Sync,
 	EventHandlerFuture: core::future::Future<Output = ()>,
 	EventHandler: Fn(Event) -> EventHandlerFuture,
 	PS: 'static + Deref + Send,
-	M: 'static + Deref<Target = ChainMonitor<<SP::Target as SignerProvider>::EcdsaSigner, CF, T, F, L, P>> + Send + Sync,
-	CM: 'static + Deref<Target = ChannelManager<CW, T, ES, NS, SP, F, R, L>> + Send + Sync,
+	M: 'static + Deref<Target = ChainMonitor<<SP::Target as SignerProvider>::EcdsaSigner, CF, T, F, L, P, MR>> + Send + Sync,
+	CM: 'static + Deref<Target = ChannelManager<CW, T, ES, NS, SP, F, R, L, CC>> + Send + Sync,
 	PGS: 'static + Deref<Target = P2PGossipSync<G, UL, L>> + Send + Sync,
 	RGS: 'static + Deref<Target = RapidGossipSync<G, L>> + Send,
 	PM: 'static + Deref + Send + Sync,
@@ -647,8 +703,8 @@ pub async fn process_events_async<
 	FetchTime: Fn() -> Option<Duration>,
 >(
 	persister: PS, event_handler: EventHandler, chain_monitor: M, channel_manager: CM,
-	gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM, logger: L, scorer: Option<S>,
-	sleeper: Sleeper, mobile_interruptable_platform: bool, fetch_time: FetchTime,
+	gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM, logger: L, metrics_recorder: MR,
+	scorer: Option<S>, sleeper: Sleeper, mobile_interruptable_platform: bool, fetch_time: FetchTime,
 ) -> Result<(), lightning::io::Error>
 where
 	UL::Target: 'static + UtxoLookup,
@@ -661,8 +717,10 @@ where
 	F::Target: 'static + FeeEstimator,
 	R::Target: 'static + Router,
 	L::Target: 'static + Logger,
+	CC::Target: 'static + CurrencyConversion,
+	MR::Target: 'static + MetricsRecorder,
 	P::Target: 'static + Persist<<SP::Target as SignerProvider>::EcdsaSigner>,
-	PS::Target: 'static + Persister<'a, CW, T, ES, NS, SP, F, R, L, SC>,
+	PS::Target: 'static + Persister<'a, CW, T, ES, NS, SP, F, R, L, CC, SC>,
 	PM::Target: APeerManager + Send + Sync,
 {
 	let mut should_break = false;
@@ -671,12 +729,14 @@ where
 		let event_handler = &event_handler;
 		let scorer = &scorer;
 		let logger = &logger;
+		let metrics_recorder = &metrics_recorder;
 		let persister = &persister;
 		let fetch_time = &fetch_time;
 		async move {
 			if let Some(network_graph) = network_graph {
 				handle_network_graph_update(network_graph, &event)
 			}
+			record_event_metrics(metrics_recorder, &event);
 			if let Some(ref scorer) = scorer {
 				if let Some(duration_since_epoch) = fetch_time() {
 					if update_scorer(scorer, &event, duration_since_epoch) {
@@ -695,7 +755,7 @@ where
 		chain_monitor.process_pending_events_async(async_event_handler).await,
 		channel_manager, channel_manager.process_pending_events_async(async_event_handler).await,
 		peer_manager, process_onion_message_handler_events_async(&peer_manager, async_event_handler).await,
-		gossip_sync, logger, scorer, should_break, {
+		gossip_sync, logger, metrics_recorder, scorer, should_break, {
 			let fut = Selector {
 				a: channel_manager.get_event_or_persistence_needed_future(),
 				b: chain_monitor.get_update_future(),
@@ -719,6 +779,38 @@ where
 	)
 }
 
+/// Keeps the given `confirmables`, usually a [`ChannelManager`] and a [`ChainMonitor`], in sync
+/// with the chain via an [`AsyncTransactionSync`] implementation, on startup and every 30 seconds
+/// thereafter until `sleeper` returns `true`.
+///
+/// This should be run on its own task, alongside [`process_events_async`], so that a slow chain
+/// source never delays event processing and persistence. The [`AsyncTransactionSync`] needs to be
+/// registered as the [`ChainMonitor`]'s [`Filter`]. Failed syncs are logged and retried at the
+/// next interval.
+///
+/// [`AsyncTransactionSync`]: lightning::chain::AsyncTransactionSync
+/// [`Filter`]: lightning::chain::Filter
+#[cfg(feature = "futures")]
+pub async fn sync_transactions_async<
+	TS: Deref,
+	C: Deref<Target = dyn chain::Confirm + Sync + Send>,
+	L: Deref,
+	SleepFuture: core::future::Future<Output = bool> + core::marker::Unpin,
+	Sleeper: Fn(Duration) -> SleepFuture,
+>(tx_sync: TS, confirmables: Vec<C>, logger: L, sleeper: Sleeper)
+where
+	TS::Target: chain::AsyncTransactionSync,
+	L::Target: Logger,
+{
+	loop {
+		let confirmables_refs = confirmables.iter().map(|confirmable| &**confirmable).collect();
+		log_sync_result(&logger, chain::AsyncTransactionSync::sync(&*tx_sync, confirmables_refs).await);
+		if sleeper(Duration::from_secs(TX_SYNC_TIMER)).await {
+			break;
+		}
+	}
+}
+
 #[cfg(feature = "futures")]
 async fn process_onion_message_handler_events_async<
 	EventHandlerFuture: core::future::Future<Output = ()>,
@@ -775,6 +867,12 @@ impl BackgroundProcessor {
 	/// to indicate that the [`BackgroundProcessor`] should not prune the [`NetworkGraph`] instance
 	/// until the [`RapidGossipSync`] instance completes its first sync.
 	///
+	/// # Metrics
+	///
+	/// The time spent persisting as well as the HTLCs reported as forwarded or failed via
+	/// [`Event::PaymentForwarded`] and [`Event::HTLCHandlingFailed`] are reported to the given
+	/// `metrics_recorder`. Use an [`IgnoringMetricsRecorder`] if metrics are not needed.
+	///
 	/// [top-level documentation]: BackgroundProcessor
 	/// [`join`]: Self::join
 	/// [`stop`]: Self::stop
@@ -784,6 +882,7 @@ impl BackgroundProcessor {
 	/// [`Persister::persist_graph`]: lightning::util::persist::Persister::persist_graph
 	/// [`NetworkGraph`]: lightning::routing::gossip::NetworkGraph
 	/// [`NetworkGraph::write`]: lightning::routing::gossip::NetworkGraph#impl-Writeable
+	/// [`IgnoringMetricsRecorder`]: lightning::util::metrics::IgnoringMetricsRecorder
 	pub fn start<
 		'a,
 		UL: 'static + Deref + Send + Sync,
@@ -797,11 +896,13 @@ impl BackgroundProcessor {
 		R: 'static + Deref + Send + Sync,
 		G: 'static + Deref<Target = NetworkGraph<L>> + Send + Sync,
 		L: 'static + Deref + Send + Sync,
+		CC: 'static + Deref + Send + Sync,
+		MR: 'static + Deref + Send + Sync,
 		P: 'static + Deref + Send + Sync,
 		EH: 'static + EventHandler + Send,
 		PS: 'static + Deref + Send,
-		M: 'static + Deref<Target = ChainMonitor<<SP::Target as SignerProvider>::EcdsaSigner, CF, T, F, L, P>> + Send + Sync,
-		CM: 'static + Deref<Target = ChannelManager<CW, T, ES, NS, SP, F, R, L>> + Send + Sync,
+		M: 'static + Deref<Target = ChainMonitor<<SP::Target as SignerProvider>::EcdsaSigner, CF, T, F, L, P, MR>> + Send + Sync,
+		CM: 'static + Deref<Target = ChannelManager<CW, T, ES, NS, SP, F, R, L, CC>> + Send + Sync,
 		PGS: 'static + Deref<Target = P2PGossipSync<G, UL, L>> + Send + Sync,
 		RGS: 'static + Deref<Target = RapidGossipSync<G, L>> + Send,
 		PM: 'static + Deref + Send + Sync,
@@ -809,7 +910,8 @@ impl BackgroundProcessor {
 		SC: for <'b> WriteableScore<'b>,
 	>(
 		persister: PS, event_handler: EH, chain_monitor: M, channel_manager: CM,
-		gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM, logger: L, scorer: Option<S>,
+		gossip_sync: GossipSync<PGS, RGS, G, UL, L>, peer_manager: PM, logger: L, metrics_recorder: MR,
+		scorer: Option<S>,
 	) -> Self
 	where
 		UL::Target: 'static + UtxoLookup,
@@ -822,8 +924,10 @@ impl BackgroundProcessor {
 		F::Target: 'static + FeeEstimator,
 		R::Target: 'static + Router,
 		L::Target: 'static + Logger,
+		CC::Target: 'static + CurrencyConversion,
+		MR::Target: 'static + MetricsRecorder,
 		P::Target: 'static + Persist<<SP::Target as SignerProvider>::EcdsaSigner>,
-		PS::Target: 'static + Persister<'a, CW, T, ES, NS, SP, F, R, L, SC>,
+		PS::Target: 'static + Persister<'a, CW, T, ES, NS, SP, F, R, L, CC, SC>,
 		PM::Target: APeerManager + Send + Sync,
 	{
 		let stop_thread = Arc::new(AtomicBool::new(false));
@@ -834,6 +938,7 @@ impl BackgroundProcessor {
 				if let Some(network_graph) = network_graph {
 					handle_network_graph_update(network_graph, &event)
 				}
+				record_event_metrics(&metrics_recorder, &event);
 				if let Some(ref scorer) = scorer {
 					use std::time::SystemTime;
 					let duration_since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
@@ -852,7 +957,7 @@ impl BackgroundProcessor {
 				channel_manager, channel_manager.process_pending_events(&event_handler),
 				peer_manager,
 				peer_manager.onion_message_handler().process_pending_events(&event_handler),
-				gossip_sync, logger, scorer, stop_thread.load(Ordering::Acquire),
+				gossip_sync, logger, metrics_recorder, scorer, stop_thread.load(Ordering::Acquire),
 				{ Sleeper::from_two_futures(
 					channel_manager.get_event_or_persistence_needed_future(),
 					chain_monitor.get_update_future()
@@ -916,32 +1021,116 @@ impl Drop for BackgroundProcessor {
 	}
 }
 
+/// `TransactionSyncProcessor` keeps [`Confirm`] implementations, usually a [`ChannelManager`] and
+/// a [`ChainMonitor`], in sync with the chain via a [`TransactionSync`] implementation.
+///
+/// Syncing happens on a dedicated thread, on startup and every 30 seconds thereafter, so that a
+/// slow chain source never delays the event processing and persistence done by the
+/// [`BackgroundProcessor`]. The [`TransactionSync`] needs to be registered as the
+/// [`ChainMonitor`]'s [`Filter`]. Failed syncs are logged and retried at the next interval.
+///
+/// See `sync_transactions_async` for an async variant.
+///
+/// [`Confirm`]: lightning::chain::Confirm
+/// [`TransactionSync`]: lightning::chain::TransactionSync
+/// [`Filter`]: lightning::chain::Filter
+#[cfg(feature = "std")]
+#[must_use = "TransactionSyncProcessor will immediately stop on drop. It should be stored until shutdown."]
+pub struct TransactionSyncProcessor {
+	stop_thread: Arc<AtomicBool>,
+	thread_handle: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "std")]
+impl TransactionSyncProcessor {
+	/// Start a background thread that syncs the given `confirmables` via `tx_sync` until
+	/// [`stop`] is called or the `TransactionSyncProcessor` is dropped.
+	///
+	/// [`stop`]: Self::stop
+	pub fn start<
+		TS: 'static + Deref + Send,
+		C: 'static + Deref<Target = dyn chain::Confirm + Sync + Send> + Send,
+		L: 'static + Deref + Send,
+	>(tx_sync: TS, confirmables: Vec<C>, logger: L) -> Self
+	where
+		TS::Target: chain::TransactionSync,
+		L::Target: Logger,
+	{
+		let stop_thread = Arc::new(AtomicBool::new(false));
+		let stop_thread_clone = stop_thread.clone();
+		let handle = thread::spawn(move || {
+			let sync_interval = Duration::from_secs(TX_SYNC_TIMER);
+			loop {
+				let confirmables = confirmables.iter().map(|confirmable| &**confirmable).collect();
+				log_sync_result(&logger, chain::TransactionSync::sync(&*tx_sync, confirmables));
+
+				let sync_time = Instant::now();
+				while !stop_thread.load(Ordering::Acquire) && sync_time.elapsed() < sync_interval {
+					thread::park_timeout(sync_interval.saturating_sub(sync_time.elapsed()));
+				}
+				if stop_thread.load(Ordering::Acquire) {
+					break;
+				}
+			}
+		});
+		Self { stop_thread: stop_thread_clone, thread_handle: Some(handle) }
+	}
+
+	/// Stop `TransactionSyncProcessor`'s thread, waiting for any sync in progress to complete.
+	///
+	/// # Panics
+	///
+	/// This function panics if the background thread has panicked while syncing.
+	pub fn stop(mut self) {
+		assert!(self.thread_handle.is_some());
+		self.stop_and_join_thread();
+	}
+
+	fn stop_and_join_thread(&mut self) {
+		self.stop_thread.store(true, Ordering::Release);
+		if let Some(handle) = self.thread_handle.take() {
+			handle.thread().unpark();
+			handle.join().unwrap();
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Drop for TransactionSyncProcessor {
+	fn drop(&mut self) {
+		self.stop_and_join_thread();
+	}
+}
+
 #[cfg(all(feature = "std", test))]
 mod tests {
 	use bitcoin::blockdata::constants::{genesis_block, ChainHash};
 	use bitcoin::blockdata::locktime::absolute::LockTime;
+	use bitcoin::blockdata::script::Script;
 	use bitcoin::blockdata::transaction::{Transaction, TxOut};
+	use bitcoin::hash_types::Txid;
 	use bitcoin::network::constants::Network;
 	use bitcoin::secp256k1::{SecretKey, PublicKey, Secp256k1};
-	use lightning::chain::{BestBlock, Confirm, chainmonitor};
+	use lightning::chain::{AsyncTransactionSync, AsyncTransactionSyncResult, BestBlock, Confirm, Filter, TransactionSync, TxSyncError, WatchedOutput, chainmonitor};
 	use lightning::chain::channelmonitor::ANTI_REORG_DELAY;
 	use lightning::sign::{InMemorySigner, KeysManager};
 	use lightning::chain::transaction::OutPoint;
-	use lightning::events::{Event, PathFailure, MessageSendEventsProvider, MessageSendEvent};
+	use lightning::events::{Event, HTLCDestination, PathFailure, MessageSendEventsProvider, MessageSendEvent};
 	use lightning::{get_event_msg, get_event};
-	use lightning::ln::PaymentHash;
+	use lightning::ln::{ChannelId, PaymentHash};
 	use lightning::ln::channelmanager;
 	use lightning::ln::channelmanager::{BREAKDOWN_TIMEOUT, ChainParameters, MIN_CLTV_EXPIRY_DELTA, PaymentId};
 	use lightning::ln::features::{ChannelFeatures, NodeFeatures};
 	use lightning::ln::functional_test_utils::*;
 	use lightning::ln::msgs::{ChannelMessageHandler, Init};
 	use lightning::ln::peer_handler::{PeerManager, MessageHandler, SocketDescriptor, IgnoringMessageHandler};
+	use lightning::offers::offer::NoCurrencyConversion;
 	use lightning::routing::gossip::{NetworkGraph, P2PGossipSync};
 	use lightning::routing::scoring::{ChannelUsage, ScoreUpdate, ScoreLookUp, LockableScore};
 	use lightning::routing::router::{DefaultRouter, Path, RouteHop, CandidateRouteHop};
 	use lightning::util::config::UserConfig;
 	use lightning::util::ser::Writeable;
-	use lightning::util::test_utils;
+	use lightning::util::{metrics, test_utils};
 	use lightning::util::persist::{KVStore,
 		CHANNEL_MANAGER_PERSISTENCE_PRIMARY_NAMESPACE, CHANNEL_MANAGER_PERSISTENCE_SECONDARY_NAMESPACE, CHANNEL_MANAGER_PERSISTENCE_KEY,
 		NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE, NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE, NETWORK_GRAPH_PERSISTENCE_KEY,
@@ -951,10 +1140,11 @@ mod tests {
 	use std::{fs, env};
 	use std::path::PathBuf;
 	use std::sync::{Arc, Mutex};
+	use std::sync::atomic::{AtomicUsize, Ordering};
 	use std::sync::mpsc::SyncSender;
 	use std::time::Duration;
 	use lightning_rapid_gossip_sync::RapidGossipSync;
-	use super::{BackgroundProcessor, GossipSync, FRESHNESS_TIMER};
+	use super::{BackgroundProcessor, GossipSync, TransactionSyncProcessor, FRESHNESS_TIMER};
 
 	const EVENT_DEADLINE: u64 = 5 * FRESHNESS_TIMER;
 
@@ -986,11 +1176,13 @@ mod tests {
 				Arc<test_utils::TestLogger>,
 				Arc<LockingWrapper<TestScorer>>,
 				(),
-				TestScorer>
+				TestScorer,
+				Arc<test_utils::TestMetricsRecorder>>
 			>,
-			Arc<test_utils::TestLogger>>;
+			Arc<test_utils::TestLogger>,
+			NoCurrencyConversion>;
 
-	type ChainMonitor = chainmonitor::ChainMonitor<InMemorySigner, Arc<test_utils::TestChainSource>, Arc<test_utils::TestBroadcaster>, Arc<test_utils::TestFeeEstimator>, Arc<test_utils::TestLogger>, Arc<FilesystemStore>>;
+	type ChainMonitor = chainmonitor::ChainMonitor<InMemorySigner, Arc<test_utils::TestChainSource>, Arc<test_utils::TestBroadcaster>, Arc<test_utils::TestFeeEstimator>, Arc<test_utils::TestLogger>, Arc<FilesystemStore>, Arc<test_utils::TestMetricsRecorder>>;
 
 	type PGS = Arc<P2PGossipSync<Arc<NetworkGraph<Arc<test_utils::TestLogger>>>, Arc<test_utils::TestChainSource>, Arc<test_utils::TestLogger>>>;
 	type RGS = Arc<RapidGossipSync<Arc<NetworkGraph<Arc<test_utils::TestLogger>>>, Arc<test_utils::TestLogger>>>;
@@ -999,12 +1191,13 @@ mod tests {
 		node: Arc<ChannelManager>,
 		p2p_gossip_sync: PGS,
 		rapid_gossip_sync: RGS,
-		peer_manager: Arc<PeerManager<TestDescriptor, Arc<test_utils::TestChannelMessageHandler>, Arc<test_utils::TestRoutingMessageHandler>, IgnoringMessageHandler, Arc<test_utils::TestLogger>, IgnoringMessageHandler, Arc<KeysManager>>>,
+		peer_manager: Arc<PeerManager<TestDescriptor, Arc<test_utils::TestChannelMessageHandler>, Arc<test_utils::TestRoutingMessageHandler>, IgnoringMessageHandler, Arc<test_utils::TestLogger>, IgnoringMessageHandler, Arc<KeysManager>, Arc<test_utils::TestMetricsRecorder>>>,
 		chain_monitor: Arc<ChainMonitor>,
 		kv_store: Arc<FilesystemStore>,
 		tx_broadcaster: Arc<test_utils::TestBroadcaster>,
 		network_graph: Arc<NetworkGraph<Arc<test_utils::TestLogger>>>,
 		logger: Arc<test_utils::TestLogger>,
+		metrics: Arc<test_utils::TestMetricsRecorder>,
 		best_block: BestBlock,
 		scorer: Arc<LockingWrapper<TestScorer>>,
 	}
@@ -1245,6 +1438,41 @@ mod tests {
 		}
 	}
 
+	struct TestTxSync {
+		sync_calls: AtomicUsize,
+		should_fail: bool,
+	}
+
+	impl TestTxSync {
+		fn new(should_fail: bool) -> Self {
+			Self { sync_calls: AtomicUsize::new(0), should_fail }
+		}
+
+		fn do_sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+			assert_eq!(confirmables.len(), 2);
+			self.sync_calls.fetch_add(1, Ordering::AcqRel);
+			if self.should_fail { Err(TxSyncError::Failed) } else { Ok(()) }
+		}
+	}
+
+	impl Filter for TestTxSync {
+		fn register_tx(&self, _txid: &Txid, _script_pubkey: &Script) {}
+		fn register_output(&self, _output: WatchedOutput) {}
+	}
+
+	impl TransactionSync for TestTxSync {
+		fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+			self.do_sync(confirmables)
+		}
+	}
+
+	impl AsyncTransactionSync for TestTxSync {
+		fn sync<'a>(&'a self, confirmables: Vec<&'a (dyn Confirm + Sync + Send)>) -> AsyncTransactionSyncResult<'a> {
+			let res = self.do_sync(confirmables);
+			Box::pin(async move { res })
+		}
+	}
+
 	fn get_full_filepath(filepath: String, filename: String) -> String {
 		let mut path = PathBuf::from(filepath);
 		path.push(filename);
@@ -1260,19 +1488,20 @@ mod tests {
 			let tx_broadcaster = Arc::new(test_utils::TestBroadcaster::new(network));
 			let fee_estimator = Arc::new(test_utils::TestFeeEstimator { sat_per_kw: Mutex::new(253) });
 			let logger = Arc::new(test_utils::TestLogger::with_id(format!("node {}", i)));
+			let metrics = Arc::new(test_utils::TestMetricsRecorder::new());
 			let genesis_block = genesis_block(network);
 			let network_graph = Arc::new(NetworkGraph::new(network, logger.clone()));
 			let scorer = Arc::new(LockingWrapper::new(TestScorer::new()));
 			let seed = [i as u8; 32];
-			let router = Arc::new(DefaultRouter::new(network_graph.clone(), logger.clone(), seed, scorer.clone(), Default::default()));
+			let router = Arc::new(DefaultRouter::new(network_graph.clone(), logger.clone(), seed, scorer.clone(), Default::default(), metrics.clone()));
 			let chain_source = Arc::new(test_utils::TestChainSource::new(Network::Bitcoin));
 			let kv_store = Arc::new(FilesystemStore::new(format!("{}_persister_{}", &persist_dir, i).into()));
 			let now = Duration::from_secs(genesis_block.header.time as u64);
 			let keys_manager = Arc::new(KeysManager::new(&seed, now.as_secs(), now.subsec_nanos()));
-			let chain_monitor = Arc::new(chainmonitor::ChainMonitor::new(Some(chain_source.clone()), tx_broadcaster.clone(), logger.clone(), fee_estimator.clone(), kv_store.clone()));
+			let chain_monitor = Arc::new(chainmonitor::ChainMonitor::new(Some(chain_source.clone()), tx_broadcaster.clone(), logger.clone(), fee_estimator.clone(), kv_store.clone(), metrics.clone()));
 			let best_block = BestBlock::from_network(network);
 			let params = ChainParameters { network, best_block };
-			let manager = Arc::new(ChannelManager::new(fee_estimator.clone(), chain_monitor.clone(), tx_broadcaster.clone(), router.clone(), logger.clone(), keys_manager.clone(), keys_manager.clone(), keys_manager.clone(), UserConfig::default(), params, genesis_block.header.time));
+			let manager = Arc::new(ChannelManager::new(fee_estimator.clone(), chain_monitor.clone(), tx_broadcaster.clone(), router.clone(), logger.clone(), keys_manager.clone(), keys_manager.clone(), keys_manager.clone(), NoCurrencyConversion {}, UserConfig::default(), params, genesis_block.header.time));
 			let p2p_gossip_sync = Arc::new(P2PGossipSync::new(network_graph.clone(), Some(chain_source.clone()), logger.clone()));
 			let rapid_gossip_sync = Arc::new(RapidGossipSync::new(network_graph.clone(), logger.clone()));
 			let msg_handler = MessageHandler {
@@ -1280,8 +1509,8 @@ mod tests {
 				route_handler: Arc::new(test_utils::TestRoutingMessageHandler::new()),
 				onion_message_handler: IgnoringMessageHandler{}, custom_message_handler: IgnoringMessageHandler{}
 			};
-			let peer_manager = Arc::new(PeerManager::new(msg_handler, 0, &seed, logger.clone(), keys_manager.clone()));
-			let node = Node { node: manager, p2p_gossip_sync, rapid_gossip_sync, peer_manager, chain_monitor, kv_store, tx_broadcaster, network_graph, logger, best_block, scorer };
+			let peer_manager = Arc::new(PeerManager::new(msg_handler, 0, &seed, logger.clone(), keys_manager.clone(), metrics.clone()));
+			let node = Node { node: manager, p2p_gossip_sync, rapid_gossip_sync, peer_manager, chain_monitor, kv_store, tx_broadcaster, network_graph, logger, metrics, best_block, scorer };
 			nodes.push(node);
 		}
 
@@ -1379,7 +1608,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].p2p_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].p2p_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		macro_rules! check_persisted_data {
 			($node: expr, $filepath: expr) => {
@@ -1423,7 +1652,7 @@ mod tests {
 		}
 
 		// Check network graph is persisted
-		let filepath = get_full_filepath(format!("{}_persister_0", &persist_dir), "network_graph".to_string());
+		let filepath = get_full_filepath(format!("{}_persister_0", &persist_dir), NETWORK_GRAPH_PERSISTENCE_KEY.to_string());
 		check_persisted_data!(nodes[0].network_graph, filepath.clone());
 
 		// Check scorer is persisted
@@ -1446,7 +1675,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 		loop {
 			let log_entries = nodes[0].logger.lines.lock().unwrap();
 			let desired_log_1 = "Calling ChannelManager's timer_tick_occurred".to_string();
@@ -1475,7 +1704,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir).with_manager_error(std::io::ErrorKind::Other, "test"));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 		match bg_processor.join() {
 			Ok(_) => panic!("Expected error persisting manager"),
 			Err(e) => {
@@ -1497,7 +1726,7 @@ mod tests {
 
 		let bp_future = super::process_events_async(
 			persister, |_: _| {async {}}, nodes[0].chain_monitor.clone(), nodes[0].node.clone(),
-			nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(),
+			nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(),
 			Some(nodes[0].scorer.clone()), move |dur: Duration| {
 				Box::pin(async move {
 					tokio::time::sleep(dur).await;
@@ -1521,7 +1750,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir).with_graph_error(std::io::ErrorKind::Other, "test"));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].p2p_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].p2p_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		match bg_processor.stop() {
 			Ok(_) => panic!("Expected error persisting network graph"),
@@ -1539,7 +1768,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir).with_scorer_error(std::io::ErrorKind::Other, "test"));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(),  nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(),  nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		match bg_processor.stop() {
 			Ok(_) => panic!("Expected error persisting scorer"),
@@ -1567,7 +1796,7 @@ mod tests {
 			_ => panic!("Unexpected event: {:?}", event),
 		};
 
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		// Open a channel and check that the FundingGenerationReady event was handled.
 		begin_open_channel!(nodes[0], nodes[1], channel_value);
@@ -1604,7 +1833,7 @@ mod tests {
 			_ => panic!("Unexpected event: {:?}", event),
 		};
 		let persister = Arc::new(Persister::new(data_dir));
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		// Force close the channel and check that the SpendableOutputs event was handled.
 		nodes[0].node.force_close_broadcasting_latest_txn(&nodes[0].node.list_channels()[0].channel_id, &nodes[1].node.get_our_node_id()).unwrap();
@@ -1630,7 +1859,7 @@ mod tests {
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir));
 		let event_handler = |_: _| {};
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		loop {
 			let log_entries = nodes[0].logger.lines.lock().unwrap();
@@ -1645,6 +1874,83 @@ mod tests {
 		}
 	}
 
+	fn tx_sync_confirmables(node: &Node) -> Vec<Arc<dyn Confirm + Sync + Send>> {
+		vec![Arc::clone(&node.node) as Arc<dyn Confirm + Sync + Send>, Arc::clone(&node.chain_monitor) as _]
+	}
+
+	#[test]
+	fn test_transaction_sync() {
+		let (_, nodes) = create_nodes(1, "test_transaction_sync");
+		let tx_sync = Arc::new(TestTxSync::new(false));
+		let tx_sync_processor = TransactionSyncProcessor::start(Arc::clone(&tx_sync),
+			tx_sync_confirmables(&nodes[0]), nodes[0].logger.clone());
+
+		// Wait until we synced both on startup and once the timer elapsed.
+		loop {
+			let log_entries = nodes[0].logger.lines.lock().unwrap();
+			let expected_log = "Synced transactions via TransactionSync".to_string();
+			if *log_entries.get(&("lightning_background_processor", expected_log)).unwrap_or(&0) > 1 {
+				break
+			}
+		}
+		assert!(tx_sync.sync_calls.load(Ordering::Acquire) > 1);
+
+		// Stopping doesn't wait for the next sync interval to elapse.
+		let stop_time = std::time::Instant::now();
+		tx_sync_processor.stop();
+		assert!(stop_time.elapsed() < Duration::from_secs(super::TX_SYNC_TIMER));
+	}
+
+	#[test]
+	fn test_transaction_sync_error() {
+		// Test that sync failures are logged and retried.
+		let (_, nodes) = create_nodes(1, "test_transaction_sync_error");
+		let tx_sync = Arc::new(TestTxSync::new(true));
+		let _tx_sync_processor = TransactionSyncProcessor::start(Arc::clone(&tx_sync),
+			tx_sync_confirmables(&nodes[0]), nodes[0].logger.clone());
+
+		loop {
+			let log_entries = nodes[0].logger.lines.lock().unwrap();
+			let expected_log = format!("Error: Failed to sync transactions: {}, retrying in {} seconds",
+				TxSyncError::Failed, super::TX_SYNC_TIMER);
+			if *log_entries.get(&("lightning_background_processor", expected_log)).unwrap_or(&0) > 1 {
+				break
+			}
+		}
+	}
+
+	#[tokio::test]
+	#[cfg(feature = "futures")]
+	async fn test_transaction_sync_async() {
+		let (_, nodes) = create_nodes(1, "test_transaction_sync_async");
+		let tx_sync = Arc::new(TestTxSync::new(false));
+
+		let (exit_sender, exit_receiver) = tokio::sync::watch::channel(());
+		let tx_sync_future = super::sync_transactions_async(
+			Arc::clone(&tx_sync), tx_sync_confirmables(&nodes[0]), nodes[0].logger.clone(),
+			move |dur: Duration| {
+				let mut exit_receiver = exit_receiver.clone();
+				Box::pin(async move {
+					tokio::select! {
+						_ = tokio::time::sleep(dur) => false,
+						_ = exit_receiver.changed() => true,
+					}
+				})
+			},
+		);
+
+		let t1 = tokio::spawn(tx_sync_future);
+		let t2 = tokio::spawn(async move {
+			while tx_sync.sync_calls.load(Ordering::Acquire) < 2 {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+			exit_sender.send(()).unwrap();
+		});
+		let (r1, r2) = tokio::join!(t1, t2);
+		r1.unwrap();
+		r2.unwrap()
+	}
+
 	macro_rules! do_test_not_pruning_network_graph_until_graph_sync_completion {
 		($nodes: expr, $receive: expr, $sleep: expr) => {
 			let features = ChannelFeatures::empty();
@@ -1703,7 +2009,7 @@ mod tests {
 		let persister = Arc::new(Persister::new(data_dir).with_graph_persistence_notifier(sender));
 
 		let event_handler = |_: _| {};
-		let background_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let background_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		do_test_not_pruning_network_graph_until_graph_sync_completion!(nodes,
 			receiver.recv_timeout(Duration::from_secs(super::FIRST_NETWORK_PRUNE_TIMER * 5)),
@@ -1724,7 +2030,7 @@ mod tests {
 		let (exit_sender, exit_receiver) = tokio::sync::watch::channel(());
 		let bp_future = super::process_events_async(
 			persister, |_: _| {async {}}, nodes[0].chain_monitor.clone(), nodes[0].node.clone(),
-			nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(),
+			nodes[0].rapid_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(),
 			Some(nodes[0].scorer.clone()), move |dur: Duration| {
 				let mut exit_receiver = exit_receiver.clone();
 				Box::pin(async move {
@@ -1774,7 +2080,7 @@ mod tests {
 				fee_msat: 0,
 				cltv_expiry_delta: MIN_CLTV_EXPIRY_DELTA as u32,
 				maybe_announced_channel: true,
-			}], blinded_tail: None };
+			}], blinded_tail: None, trampoline_hops: vec![] };
 
 			$nodes[0].scorer.write_lock().expect(TestResult::PaymentFailure { path: path.clone(), short_channel_id: scored_scid });
 			$nodes[0].node.push_pending_event(Event::PaymentPathFailed {
@@ -1861,7 +2167,7 @@ mod tests {
 		let (_, nodes) = create_nodes(1, "test_payment_path_scoring");
 		let data_dir = nodes[0].kv_store.get_data_dir();
 		let persister = Arc::new(Persister::new(data_dir));
-		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), Some(nodes[0].scorer.clone()));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
 
 		do_test_payment_path_scoring!(nodes, receiver.recv_timeout(Duration::from_secs(EVENT_DEADLINE)));
 
@@ -1874,6 +2180,55 @@ mod tests {
 		assert_eq!(*log_entries.get(&("lightning_background_processor", expected_log)).unwrap(), 5);
 	}
 
+	#[test]
+	fn test_metrics() {
+		// Test that HTLC events and the time spent persisting are reported to the `MetricsRecorder`.
+		let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+		let event_handler = move |event: Event| match event {
+			Event::PaymentForwarded { .. } => sender.send(event).unwrap(),
+			Event::HTLCHandlingFailed { .. } => sender.send(event).unwrap(),
+			_ => panic!("Unexpected event: {:?}", event),
+		};
+
+		let (_, nodes) = create_nodes(1, "test_metrics");
+		let data_dir = nodes[0].kv_store.get_data_dir();
+		let persister = Arc::new(Persister::new(data_dir));
+		let bg_processor = BackgroundProcessor::start(persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(), nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(), Some(nodes[0].scorer.clone()));
+
+		nodes[0].node.push_pending_event(Event::PaymentForwarded {
+			prev_channel_id: None,
+			next_channel_id: None,
+			fee_earned_msat: Some(1_000),
+			claim_from_onchain_tx: false,
+			outbound_amount_forwarded_msat: Some(100_000),
+		});
+		receiver.recv_timeout(Duration::from_secs(EVENT_DEADLINE)).expect("PaymentForwarded not handled within deadline");
+
+		nodes[0].node.push_pending_event(Event::HTLCHandlingFailed {
+			prev_channel_id: ChannelId::new_zero(),
+			failed_next_destination: HTLCDestination::UnknownNextHop { requested_forward_scid: 42 },
+		});
+		receiver.recv_timeout(Duration::from_secs(EVENT_DEADLINE)).expect("HTLCHandlingFailed not handled within deadline");
+
+		loop {
+			let log_entries = nodes[0].logger.lines.lock().unwrap();
+			let expected_log = "Calling time_passed and persisting scorer".to_string();
+			if log_entries.get(&("lightning_background_processor", expected_log)).is_some() {
+				break
+			}
+		}
+
+		if !std::thread::panicking() {
+			bg_processor.stop().unwrap();
+		}
+
+		let recorder = &nodes[0].metrics;
+		assert_eq!(recorder.counter(metrics::HTLCS_FORWARDED, &[]), 1);
+		assert_eq!(recorder.counter(metrics::HTLCS_FAILED, &[("reason", "unknown_next_hop")]), 1);
+		assert_eq!(recorder.counter(metrics::HTLCS_FAILED, &[("reason", "next_hop_channel")]), 0);
+		assert!(recorder.histogram_observations(metrics::PERSISTENCE_DURATION, &[("object", "scorer")]) > 0);
+	}
+
 	#[tokio::test]
 	#[cfg(feature = "futures")]
 	async fn test_payment_path_scoring_async() {
@@ -1899,7 +2254,7 @@ mod tests {
 
 		let bp_future = super::process_events_async(
 			persister, event_handler, nodes[0].chain_monitor.clone(), nodes[0].node.clone(),
-			nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(),
+			nodes[0].no_gossip_sync(), nodes[0].peer_manager.clone(), nodes[0].logger.clone(), nodes[0].metrics.clone(),
 			Some(nodes[0].scorer.clone()), move |dur: Duration| {
 				let mut exit_receiver = exit_receiver.clone();
 				Box::pin(async move {
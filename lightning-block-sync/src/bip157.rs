@@ -0,0 +1,721 @@
+//! A [`BlockSource`] which syncs the chain from a Bitcoin P2P peer using compact block filters
+//! (BIP 157/158), only downloading the blocks which contain transactions relevant to us.
+//!
+//! [`Bip157Client`] downloads block headers and the basic filter header chain from the peer. When
+//! asked for a block, it fetches and verifies the block's filter and matches it against all
+//! scripts registered via [`Filter`]. Only if a script matches is the full block downloaded,
+//! otherwise [`BlockData::HeaderOnly`] is returned. Driving the client with a [`ChainPoller`] and
+//! [`SpvClient`] thus handles reorgs like any other [`BlockSource`].
+//!
+//! Note that the peer is trusted to serve the best chain and filters matching it. While headers
+//! are checked for proof of work, filters are checked against the filter header chain and blocks
+//! against their headers, a single malicious peer may still withhold blocks or filters. Thus, you
+//! should only connect to a peer you trust, e.g., your own node with `-blockfilterindex=1` and
+//! `-peerblockfilters=1`.
+//!
+//! [`ChainPoller`]: crate::poll::ChainPoller
+//! [`SpvClient`]: crate::SpvClient
+
+use crate::{AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource, BlockSourceError};
+
+use bitcoin::bip158::BlockFilter;
+use bitcoin::blockdata::block::{Block, Header};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode;
+use bitcoin::hash_types::{BlockHash, FilterHeader, Txid};
+use bitcoin::hashes::Hash;
+use bitcoin::network::address::Address;
+use bitcoin::network::constants::{Magic, Network, ServiceFlags};
+use bitcoin::network::message::{NetworkMessage, RawNetworkMessage};
+use bitcoin::network::message_blockdata::{GetHeadersMessage, Inventory};
+use bitcoin::network::message_filter::{GetCFHeaders, GetCFilters};
+use bitcoin::network::message_network::VersionMessage;
+use bitcoin::{Script, ScriptBuf};
+
+use lightning::chain::{Filter, WatchedOutput};
+
+use std::collections::{HashMap, HashSet};
+#[cfg(not(feature = "tokio"))]
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+use tokio::net::TcpStream;
+
+#[cfg(not(feature = "tokio"))]
+use std::net::TcpStream;
+
+/// Timeout for operations on TCP streams.
+const TCP_STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The maximum size of a P2P message we're willing to read, matching Bitcoin Core's `MAX_SIZE`.
+const MAX_MESSAGE_SIZE: usize = 0x0200_0000;
+
+/// The size of the header preceding each P2P message's payload.
+const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// The maximum number of messages we read while waiting for a response to a request before giving
+/// up, to avoid waiting forever on a peer which keeps sending us unrelated messages.
+const MAX_MESSAGES_PER_REQUEST: usize = 1000;
+
+/// The maximum number of headers a peer returns in response to a `getheaders` message.
+const MAX_HEADERS_RESULTS: usize = 2000;
+
+/// The maximum number of filter hashes a peer returns in response to a `getcfheaders` message.
+const MAX_CFHEADERS_RESULTS: u32 = 2000;
+
+/// The basic filter type defined in BIP 158.
+const BASIC_FILTER_TYPE: u8 = 0;
+
+/// The oldest P2P protocol version supporting compact block filters.
+const PROTOCOL_VERSION: u32 = 70016;
+
+/// A single connection to a Bitcoin P2P peer which has completed the `version` handshake.
+struct PeerConnection {
+	stream: TcpStream,
+	magic: Magic,
+}
+
+impl PeerConnection {
+	/// Connects to the peer at `address`, failing if it does not serve compact block filters.
+	async fn connect(address: SocketAddr, network: Network) -> std::io::Result<Self> {
+		#[cfg(feature = "tokio")]
+		let stream = match tokio::time::timeout(TCP_STREAM_TIMEOUT, TcpStream::connect(address)).await {
+			Ok(stream) => stream?,
+			Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out")),
+		};
+		#[cfg(not(feature = "tokio"))]
+		let stream = {
+			let stream = TcpStream::connect_timeout(&address, TCP_STREAM_TIMEOUT)?;
+			stream.set_read_timeout(Some(TCP_STREAM_TIMEOUT))?;
+			stream.set_write_timeout(Some(TCP_STREAM_TIMEOUT))?;
+			stream
+		};
+
+		let mut connection = Self { stream, magic: network.magic() };
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+		let unspecified_address = Address::new(&([0u8; 4], 0).into(), ServiceFlags::NONE);
+		let mut version = VersionMessage::new(
+			ServiceFlags::NONE, timestamp as i64, Address::new(&address, ServiceFlags::NONE),
+			unspecified_address, 0, "/lightning-block-sync/".to_string(), 0
+		);
+		version.version = PROTOCOL_VERSION;
+		version.relay = false;
+		connection.send(NetworkMessage::Version(version)).await?;
+
+		let mut version_received = false;
+		let mut verack_received = false;
+		for _ in 0..MAX_MESSAGES_PER_REQUEST {
+			match connection.receive().await? {
+				NetworkMessage::Version(version) => {
+					let required_services = ServiceFlags::COMPACT_FILTERS | ServiceFlags::WITNESS;
+					if version.version < PROTOCOL_VERSION || !version.services.has(required_services) {
+						return Err(std::io::Error::new(std::io::ErrorKind::Unsupported,
+							"peer does not serve compact block filters"));
+					}
+					version_received = true;
+					connection.send(NetworkMessage::Verack).await?;
+				},
+				NetworkMessage::Verack => verack_received = true,
+				NetworkMessage::Ping(nonce) => connection.send(NetworkMessage::Pong(nonce)).await?,
+				_ => {},
+			}
+			if version_received && verack_received {
+				return Ok(connection);
+			}
+		}
+		Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "peer did not complete handshake"))
+	}
+
+	/// Sends a P2P message to the peer.
+	async fn send(&mut self, payload: NetworkMessage) -> std::io::Result<()> {
+		let message = encode::serialize(&RawNetworkMessage { magic: self.magic, payload });
+		#[cfg(feature = "tokio")]
+		{
+			self.stream.write_all(&message).await?;
+			self.stream.flush().await
+		}
+		#[cfg(not(feature = "tokio"))]
+		{
+			self.stream.write_all(&message)?;
+			self.stream.flush()
+		}
+	}
+
+	/// Reads exactly enough bytes to fill `buf` from the peer.
+	async fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+		#[cfg(feature = "tokio")]
+		{
+			match tokio::time::timeout(TCP_STREAM_TIMEOUT, self.stream.read_exact(buf)).await {
+				Ok(res) => res.map(|_| ()),
+				Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "read timed out")),
+			}
+		}
+		#[cfg(not(feature = "tokio"))]
+		{
+			self.stream.read_exact(buf)
+		}
+	}
+
+	/// Reads the next P2P message from the peer.
+	async fn receive(&mut self) -> std::io::Result<NetworkMessage> {
+		let mut message = vec![0; MESSAGE_HEADER_SIZE];
+		self.read_exact(&mut message).await?;
+		let mut payload_len = [0; 4];
+		payload_len.copy_from_slice(&message[16..20]);
+		let payload_len = u32::from_le_bytes(payload_len) as usize;
+		if payload_len > MAX_MESSAGE_SIZE {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "message too large"));
+		}
+		message.resize(MESSAGE_HEADER_SIZE + payload_len, 0);
+		self.read_exact(&mut message[MESSAGE_HEADER_SIZE..]).await?;
+
+		let message: RawNetworkMessage = encode::deserialize(&message)
+			.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+		if message.magic != self.magic {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected network magic"));
+		}
+		Ok(message.payload)
+	}
+
+	/// Sends `request` to the peer and returns the first response for which `handle_response`
+	/// returns `Some`, answering any pings in the meantime.
+	async fn request<T, F>(&mut self, request: NetworkMessage, mut handle_response: F) -> std::io::Result<T>
+	where F: FnMut(NetworkMessage) -> Option<std::io::Result<T>> {
+		self.send(request).await?;
+		for _ in 0..MAX_MESSAGES_PER_REQUEST {
+			match self.receive().await? {
+				NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(nonce)).await?,
+				message => if let Some(res) = handle_response(message) { return res; },
+			}
+		}
+		Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "peer did not respond to request"))
+	}
+}
+
+/// The headers and filter headers we've learned about from the peer, starting at our checkpoint.
+struct HeaderChain {
+	headers: HashMap<BlockHash, BlockHeaderData>,
+	filter_headers: HashMap<BlockHash, FilterHeader>,
+	/// The hashes of the blocks on the best chain, indexed by their height minus `base_height`.
+	best_chain: Vec<BlockHash>,
+	base_height: u32,
+}
+
+impl HeaderChain {
+	fn new(checkpoint: BlockHeaderData) -> Self {
+		let hash = checkpoint.header.block_hash();
+		let mut headers = HashMap::new();
+		headers.insert(hash, checkpoint);
+		Self { headers, filter_headers: HashMap::new(), best_chain: vec![hash], base_height: checkpoint.height }
+	}
+
+	fn tip(&self) -> &BlockHeaderData {
+		&self.headers[self.best_chain.last().unwrap()]
+	}
+
+	fn best_chain_hash(&self, height: u32) -> Option<&BlockHash> {
+		height.checked_sub(self.base_height).and_then(|idx| self.best_chain.get(idx as usize))
+	}
+
+	/// Returns a block locator for the best chain, i.e., the hashes of the last few blocks followed
+	/// by exponentially sparser ones back to our checkpoint.
+	fn locator(&self) -> Vec<BlockHash> {
+		let mut locator = Vec::new();
+		let mut idx = self.best_chain.len() - 1;
+		let mut step = 1;
+		while idx > 0 {
+			locator.push(self.best_chain[idx]);
+			if locator.len() >= 10 {
+				step *= 2;
+			}
+			idx = idx.saturating_sub(step);
+		}
+		locator.push(self.best_chain[0]);
+		locator
+	}
+
+	/// Adds `headers` received from the peer, switching our best chain to them if they build the
+	/// chain with the most work.
+	fn connect_headers(&mut self, headers: &[Header]) -> Result<(), BlockSourceError> {
+		let mut prev = match headers.first().and_then(|header| self.headers.get(&header.prev_blockhash)) {
+			Some(prev) => *prev,
+			None => return Err(BlockSourceError::persistent("headers do not connect to known chain")),
+		};
+		for header in headers {
+			if header.prev_blockhash != prev.header.block_hash() {
+				return Err(BlockSourceError::persistent("headers are not continuous"));
+			}
+			let hash = header.validate_pow(header.target())
+				.map_err(|_| BlockSourceError::persistent("header has invalid proof of work"))?;
+			let header_data = BlockHeaderData {
+				header: *header,
+				height: prev.height + 1,
+				chainwork: prev.chainwork + header.work(),
+			};
+			self.headers.insert(hash, header_data);
+			prev = header_data;
+		}
+
+		if prev.chainwork <= self.tip().chainwork {
+			return Ok(());
+		}
+		// Walk back from the new tip until we find the fork point on our current best chain.
+		let mut new_blocks = Vec::new();
+		let mut block = prev;
+		while self.best_chain_hash(block.height) != Some(&block.header.block_hash()) {
+			if block.height <= self.base_height {
+				return Err(BlockSourceError::persistent("chain forks below checkpoint"));
+			}
+			new_blocks.push(block.header.block_hash());
+			block = self.headers[&block.header.prev_blockhash];
+		}
+		self.best_chain.truncate((block.height - self.base_height + 1) as usize);
+		self.best_chain.extend(new_blocks.drain(..).rev());
+		Ok(())
+	}
+}
+
+/// A [`BlockSource`] backed by a single Bitcoin P2P peer serving compact block filters (BIP 157).
+///
+/// Scripts to watch for are registered via the [`Filter`] implementation, which should be passed
+/// to the [`ChainMonitor`] and used when registering outputs during [`ChannelMonitor`] loading.
+/// See the [module-level documentation](self) for the trust assumptions this entails.
+///
+/// Headers are kept in memory from the checkpoint the client was created with onwards.
+///
+/// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
+/// [`ChannelMonitor`]: lightning::chain::channelmonitor::ChannelMonitor
+pub struct Bip157Client {
+	address: SocketAddr,
+	network: Network,
+	connection: Mutex<Option<PeerConnection>>,
+	chain: Mutex<HeaderChain>,
+	watched_scripts: Mutex<HashSet<ScriptBuf>>,
+}
+
+impl Bip157Client {
+	/// Creates a new client syncing the chain of the given `network` from the genesis block via
+	/// the peer at `address`. The connection is established on first use.
+	pub fn new(address: SocketAddr, network: Network) -> Self {
+		let genesis = genesis_block(network).header;
+		let checkpoint = BlockHeaderData { header: genesis, height: 0, chainwork: genesis.work() };
+		Self::with_checkpoint(address, network, checkpoint)
+	}
+
+	/// Creates a new client which trusts `checkpoint` to be part of the best chain and only syncs
+	/// headers building on it, avoiding the download of all headers since genesis.
+	///
+	/// The checkpoint must not be more recent than the best block of any listener synced via this
+	/// client. If its chainwork is unknown, the checkpoint header's own work may be used, as long as
+	/// the client is not combined with other block sources.
+	pub fn with_checkpoint(address: SocketAddr, network: Network, checkpoint: BlockHeaderData) -> Self {
+		Self {
+			address,
+			network,
+			connection: Mutex::new(None),
+			chain: Mutex::new(HeaderChain::new(checkpoint)),
+			watched_scripts: Mutex::new(HashSet::new()),
+		}
+	}
+
+	/// Sends `request` to the peer, connecting first if needed. The connection is dropped on error,
+	/// causing the next request to reconnect.
+	async fn request<T, F>(&self, request: NetworkMessage, handle_response: F) -> Result<T, BlockSourceError>
+	where F: FnMut(NetworkMessage) -> Option<std::io::Result<T>> {
+		let connection_opt = self.connection.lock().unwrap().take();
+		let mut connection = match connection_opt {
+			Some(connection) => connection,
+			None => PeerConnection::connect(self.address, self.network).await
+				.map_err(BlockSourceError::transient)?,
+		};
+		let res = connection.request(request, handle_response).await
+			.map_err(BlockSourceError::transient)?;
+		*self.connection.lock().unwrap() = Some(connection);
+		Ok(res)
+	}
+
+	/// Downloads any new headers and filter headers from the peer.
+	async fn sync(&self) -> Result<(), BlockSourceError> {
+		loop {
+			let locator = self.chain.lock().unwrap().locator();
+			let get_headers = GetHeadersMessage::new(locator, BlockHash::all_zeros());
+			let headers = self.request(NetworkMessage::GetHeaders(get_headers), |message| match message {
+				NetworkMessage::Headers(headers) => Some(Ok(headers)),
+				_ => None,
+			}).await?;
+			if headers.is_empty() {
+				break;
+			}
+			self.chain.lock().unwrap().connect_headers(&headers)?;
+			if headers.len() < MAX_HEADERS_RESULTS {
+				break;
+			}
+		}
+
+		loop {
+			let (start_height, stop_hash, previous_filter_header) = {
+				let chain = self.chain.lock().unwrap();
+				let tip_height = chain.tip().height;
+				let mut start_height = tip_height + 1;
+				while start_height > chain.base_height + 1 &&
+					!chain.filter_headers.contains_key(chain.best_chain_hash(start_height - 1).unwrap())
+				{
+					start_height -= 1;
+				}
+				if start_height > tip_height {
+					break;
+				}
+				let stop_height = std::cmp::min(tip_height, start_height + MAX_CFHEADERS_RESULTS - 1);
+				let previous_block_hash = chain.best_chain_hash(start_height - 1).unwrap();
+				(start_height, *chain.best_chain_hash(stop_height).unwrap(),
+					chain.filter_headers.get(previous_block_hash).copied())
+			};
+
+			let get_cfheaders = GetCFHeaders { filter_type: BASIC_FILTER_TYPE, start_height, stop_hash };
+			let cfheaders = self.request(NetworkMessage::GetCFHeaders(get_cfheaders), |message| match message {
+				NetworkMessage::CFHeaders(cfheaders) if cfheaders.stop_hash == stop_hash => Some(Ok(cfheaders)),
+				_ => None,
+			}).await?;
+
+			let mut chain = self.chain.lock().unwrap();
+			// Our best chain may have changed while we were waiting on the peer.
+			let stop_height = match chain.headers.get(&stop_hash) {
+				Some(header) if chain.best_chain_hash(header.height) == Some(&stop_hash) => header.height,
+				_ => continue,
+			};
+			if cfheaders.filter_type != BASIC_FILTER_TYPE ||
+				cfheaders.filter_hashes.len() != (stop_height + 1 - start_height) as usize
+			{
+				return Err(BlockSourceError::persistent("unexpected number of filter hashes"));
+			}
+			match previous_filter_header {
+				Some(previous_filter_header) if previous_filter_header != cfheaders.previous_filter_header => {
+					return Err(BlockSourceError::persistent("filter headers do not connect"));
+				},
+				Some(_) => {},
+				None => {
+					// We trust the peer for the filter header of our checkpoint.
+					let base_hash = chain.best_chain[0];
+					chain.filter_headers.insert(base_hash, cfheaders.previous_filter_header);
+				},
+			}
+			let mut filter_header = cfheaders.previous_filter_header;
+			for (height, filter_hash) in (start_height..=stop_height).zip(cfheaders.filter_hashes.iter()) {
+				filter_header = filter_hash.filter_header(&filter_header);
+				let block_hash = *chain.best_chain_hash(height).unwrap();
+				chain.filter_headers.insert(block_hash, filter_header);
+			}
+		}
+		Ok(())
+	}
+
+	/// Fetches the filter for the block with `block_hash` and checks it against our filter headers.
+	async fn get_filter(&self, block_hash: BlockHash) -> Result<BlockFilter, BlockSourceError> {
+		let (height, filter_header, previous_filter_header) = {
+			let chain = self.chain.lock().unwrap();
+			let header = chain.headers.get(&block_hash)
+				.ok_or_else(|| BlockSourceError::transient("unknown block"))?;
+			match (chain.filter_headers.get(&block_hash), chain.filter_headers.get(&header.header.prev_blockhash)) {
+				(Some(filter_header), Some(previous_filter_header)) =>
+					(header.height, *filter_header, *previous_filter_header),
+				_ => return Err(BlockSourceError::transient("missing filter header")),
+			}
+		};
+
+		let get_cfilters = GetCFilters { filter_type: BASIC_FILTER_TYPE, start_height: height, stop_hash: block_hash };
+		let cfilter = self.request(NetworkMessage::GetCFilters(get_cfilters), |message| match message {
+			NetworkMessage::CFilter(cfilter) if cfilter.block_hash == block_hash => Some(Ok(cfilter)),
+			_ => None,
+		}).await?;
+		let filter = BlockFilter::new(&cfilter.filter);
+		if cfilter.filter_type != BASIC_FILTER_TYPE || filter.filter_header(&previous_filter_header) != filter_header {
+			return Err(BlockSourceError::persistent("filter does not match filter header"));
+		}
+		Ok(filter)
+	}
+
+	/// Downloads the full block with `block_hash` and checks it against its header.
+	async fn get_full_block(&self, block_hash: BlockHash) -> Result<Block, BlockSourceError> {
+		let get_data = vec![Inventory::WitnessBlock(block_hash)];
+		let block = self.request(NetworkMessage::GetData(get_data), |message| match message {
+			NetworkMessage::Block(block) if block.block_hash() == block_hash => Some(Ok(block)),
+			NetworkMessage::NotFound(inventory) if inventory.contains(&Inventory::WitnessBlock(block_hash)) =>
+				Some(Err(std::io::Error::new(std::io::ErrorKind::NotFound, "block not found"))),
+			_ => None,
+		}).await?;
+		if !block.check_merkle_root() || !block.check_witness_commitment() {
+			return Err(BlockSourceError::persistent("block does not match its header"));
+		}
+		Ok(block)
+	}
+}
+
+impl BlockSource for Bip157Client {
+	fn get_header<'a>(&'a self, header_hash: &'a BlockHash, _height_hint: Option<u32>) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+		Box::pin(async move {
+			if let Some(header) = self.chain.lock().unwrap().headers.get(header_hash) {
+				return Ok(*header);
+			}
+			self.sync().await?;
+			self.chain.lock().unwrap().headers.get(header_hash).copied()
+				.ok_or_else(|| BlockSourceError::transient("header not found"))
+		})
+	}
+
+	fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, BlockData> {
+		Box::pin(async move {
+			let header = match self.chain.lock().unwrap().headers.get(header_hash) {
+				Some(header) => header.header,
+				None => return Err(BlockSourceError::transient("block not found")),
+			};
+			let scripts: Vec<ScriptBuf> = self.watched_scripts.lock().unwrap().iter().cloned().collect();
+			if scripts.is_empty() {
+				return Ok(BlockData::HeaderOnly(header));
+			}
+
+			let filter = self.get_filter(*header_hash).await?;
+			let matches = filter.match_any(header_hash, scripts.iter().map(|script| script.as_bytes()))
+				.map_err(|_| BlockSourceError::persistent("malformed filter"))?;
+			if matches {
+				Ok(BlockData::FullBlock(self.get_full_block(*header_hash).await?))
+			} else {
+				Ok(BlockData::HeaderOnly(header))
+			}
+		})
+	}
+
+	fn get_best_block<'a>(&'a self) -> AsyncBlockSourceResult<'a, (BlockHash, Option<u32>)> {
+		Box::pin(async move {
+			self.sync().await?;
+			let chain = self.chain.lock().unwrap();
+			let tip = chain.tip();
+			Ok((tip.header.block_hash(), Some(tip.height)))
+		})
+	}
+}
+
+impl Filter for Bip157Client {
+	fn register_tx(&self, _txid: &Txid, script_pubkey: &Script) {
+		self.watched_scripts.lock().unwrap().insert(script_pubkey.into());
+	}
+
+	fn register_output(&self, output: WatchedOutput) {
+		self.watched_scripts.lock().unwrap().insert(output.script_pubkey);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ChainTip, SpvClient};
+	use crate::poll::ChainPoller;
+	use crate::test_utils::{Blockchain, MockChainListener};
+
+	use bitcoin::bip158;
+	use bitcoin::blockdata::locktime::absolute::LockTime;
+	use bitcoin::hash_types::FilterHash;
+	use bitcoin::network::message_filter::{CFHeaders, CFilter};
+	use bitcoin::{Transaction, TxOut};
+
+	use std::io::{Read, Write};
+	use std::net::TcpListener;
+	use std::sync::Arc;
+
+	/// A Bitcoin P2P peer serving headers, compact block filters and blocks from `blocks`, which
+	/// may be changed by tests to simulate reorgs.
+	struct MockPeer {
+		address: SocketAddr,
+		blocks: Arc<Mutex<Vec<Block>>>,
+	}
+
+	impl MockPeer {
+		fn spawn(blocks: Vec<Block>, network: Network, services: ServiceFlags) -> Self {
+			let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+			let address = listener.local_addr().unwrap();
+			let blocks = Arc::new(Mutex::new(blocks));
+			let peer_blocks = Arc::clone(&blocks);
+			std::thread::spawn(move || {
+				for stream in listener.incoming() {
+					Self::serve(stream.unwrap(), &peer_blocks, network.magic(), services);
+				}
+			});
+			Self { address, blocks }
+		}
+
+		fn serve(mut stream: std::net::TcpStream, blocks: &Mutex<Vec<Block>>, magic: Magic, services: ServiceFlags) {
+			let mut send = |stream: &mut std::net::TcpStream, payload| {
+				stream.write_all(&encode::serialize(&RawNetworkMessage { magic, payload })).is_ok()
+			};
+			loop {
+				let mut message = vec![0; MESSAGE_HEADER_SIZE];
+				if stream.read_exact(&mut message).is_err() { return; }
+				let payload_len = u32::from_le_bytes(message[16..20].try_into().unwrap()) as usize;
+				message.resize(MESSAGE_HEADER_SIZE + payload_len, 0);
+				if stream.read_exact(&mut message[MESSAGE_HEADER_SIZE..]).is_err() { return; }
+				let message: RawNetworkMessage = encode::deserialize(&message).unwrap();
+
+				let blocks = blocks.lock().unwrap().clone();
+				let height_of = |block_hash: &BlockHash| blocks.iter().position(|block| block.block_hash() == *block_hash);
+				let filters: Vec<_> = blocks.iter().map(|block| BlockFilter::new_script_filter(block,
+					|outpoint| Err::<ScriptBuf, _>(bip158::Error::UtxoMissing(*outpoint))).unwrap()).collect();
+				let mut responses = Vec::new();
+				match message.payload {
+					NetworkMessage::Version(_) => {
+						let address = Address::new(&([0u8; 4], 0).into(), ServiceFlags::NONE);
+						let mut version = VersionMessage::new(services, 0, address.clone(), address, 0,
+							"/mock/".to_string(), blocks.len() as i32 - 1);
+						version.version = PROTOCOL_VERSION;
+						responses.push(NetworkMessage::Version(version));
+						responses.push(NetworkMessage::Verack);
+					},
+					NetworkMessage::GetHeaders(get_headers) => {
+						let fork_height = get_headers.locator_hashes.iter().find_map(height_of).unwrap_or(0);
+						let headers = blocks.iter().skip(fork_height + 1).take(MAX_HEADERS_RESULTS)
+							.map(|block| block.header).collect();
+						responses.push(NetworkMessage::Headers(headers));
+					},
+					NetworkMessage::GetCFHeaders(get_cfheaders) => {
+						let start_height = get_cfheaders.start_height as usize;
+						let stop_height = height_of(&get_cfheaders.stop_hash).unwrap();
+						let mut previous_filter_header = FilterHeader::all_zeros();
+						for filter in &filters[..start_height] {
+							previous_filter_header = filter.filter_header(&previous_filter_header);
+						}
+						let filter_hashes = filters[start_height..=stop_height].iter()
+							.map(|filter| FilterHash::hash(&filter.content)).collect();
+						responses.push(NetworkMessage::CFHeaders(CFHeaders {
+							filter_type: BASIC_FILTER_TYPE, stop_hash: get_cfheaders.stop_hash,
+							previous_filter_header, filter_hashes,
+						}));
+					},
+					NetworkMessage::GetCFilters(get_cfilters) => {
+						let stop_height = height_of(&get_cfilters.stop_hash).unwrap();
+						for height in get_cfilters.start_height as usize..=stop_height {
+							responses.push(NetworkMessage::CFilter(CFilter {
+								filter_type: BASIC_FILTER_TYPE, block_hash: blocks[height].block_hash(),
+								filter: filters[height].content.clone(),
+							}));
+						}
+					},
+					NetworkMessage::GetData(inventory) => {
+						for inv in inventory {
+							match inv {
+								Inventory::WitnessBlock(block_hash) => match height_of(&block_hash) {
+									Some(height) => responses.push(NetworkMessage::Block(blocks[height].clone())),
+									None => responses.push(NetworkMessage::NotFound(vec![inv])),
+								},
+								_ => responses.push(NetworkMessage::NotFound(vec![inv])),
+							}
+						}
+					},
+					_ => {},
+				}
+				for response in responses {
+					if !send(&mut stream, response) { return; }
+				}
+			}
+		}
+	}
+
+	fn full_services() -> ServiceFlags {
+		ServiceFlags::NETWORK | ServiceFlags::WITNESS | ServiceFlags::COMPACT_FILTERS
+	}
+
+	/// Adds a transaction paying to `script_pubkey` to the block at `height`, relinking all blocks
+	/// after it.
+	fn pay_to_script_in_block(blocks: &mut [Block], height: usize, script_pubkey: ScriptBuf) {
+		blocks[height].txdata.push(Transaction {
+			version: 2,
+			lock_time: LockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut { value: 1000, script_pubkey }],
+		});
+		blocks[height].header.merkle_root = blocks[height].compute_merkle_root().unwrap();
+		for height in height + 1..blocks.len() {
+			blocks[height].header.prev_blockhash = blocks[height - 1].block_hash();
+		}
+	}
+
+	#[tokio::test]
+	async fn only_fetches_blocks_matching_watched_scripts() {
+		let mut chain = Blockchain::with_network(Network::Testnet).with_height(3);
+		let script_pubkey = ScriptBuf::from(vec![0x00, 0x14, 0x42, 0x42]);
+		pay_to_script_in_block(&mut chain.blocks, 2, script_pubkey.clone());
+		let peer = MockPeer::spawn(chain.blocks.clone(), Network::Testnet, full_services());
+		let client = Bip157Client::new(peer.address, Network::Testnet);
+
+		let tip = chain.tip();
+		assert_eq!(client.get_best_block().await.unwrap(), (tip.header.block_hash(), Some(3)));
+		assert_eq!(client.get_header(&tip.header.block_hash(), None).await.unwrap(), *tip);
+
+		// Without any watched scripts, no filters or blocks need to be fetched.
+		let block_hash = chain.blocks[2].block_hash();
+		match client.get_block(&block_hash).await.unwrap() {
+			BlockData::HeaderOnly(header) => assert_eq!(header, chain.blocks[2].header),
+			BlockData::FullBlock(_) => panic!("Unexpected full block"),
+		}
+
+		client.register_tx(&chain.blocks[2].txdata[1].txid(), &script_pubkey);
+		for (height, block) in chain.blocks.iter().enumerate().skip(1) {
+			match client.get_block(&block.block_hash()).await.unwrap() {
+				BlockData::FullBlock(full_block) => {
+					assert_eq!(height, 2);
+					assert_eq!(full_block, *block);
+				},
+				BlockData::HeaderOnly(header) => {
+					assert_ne!(height, 2);
+					assert_eq!(header, block.header);
+				},
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn notifies_listener_of_reorg() {
+		let old_chain = Blockchain::with_network(Network::Testnet).with_height(3);
+		let new_chain = Blockchain::with_network(Network::Testnet).with_height(4);
+		assert_ne!(old_chain.blocks[1], new_chain.blocks[1]);
+		let peer = MockPeer::spawn(old_chain.blocks.clone(), Network::Testnet, full_services());
+		let client = Bip157Client::new(peer.address, Network::Testnet);
+		assert_eq!(client.get_best_block().await.unwrap().1, Some(3));
+
+		*peer.blocks.lock().unwrap() = new_chain.blocks.clone();
+		let listener = MockChainListener::new()
+			.expect_block_disconnected(*old_chain.at_height(3))
+			.expect_block_disconnected(*old_chain.at_height(2))
+			.expect_block_disconnected(*old_chain.at_height(1))
+			.expect_filtered_block_connected(*new_chain.at_height(1))
+			.expect_filtered_block_connected(*new_chain.at_height(2))
+			.expect_filtered_block_connected(*new_chain.at_height(3))
+			.expect_filtered_block_connected(*new_chain.at_height(4));
+		let mut cache = old_chain.header_cache(0..=3);
+		let poller = ChainPoller::new(&client, Network::Testnet);
+		let mut spv_client = SpvClient::new(old_chain.tip(), poller, &mut cache, &listener);
+		match spv_client.poll_best_tip().await {
+			Err(e) => panic!("Unexpected error: {:?}", e),
+			Ok((chain_tip, blocks_connected)) => {
+				assert_eq!(chain_tip, ChainTip::Better(new_chain.tip()));
+				assert!(blocks_connected);
+			},
+		}
+	}
+
+	#[tokio::test]
+	async fn rejects_peer_without_compact_filters() {
+		let chain = Blockchain::with_network(Network::Testnet).with_height(1);
+		let peer = MockPeer::spawn(chain.blocks, Network::Testnet, ServiceFlags::NETWORK | ServiceFlags::WITNESS);
+		let client = Bip157Client::new(peer.address, Network::Testnet);
+		match client.get_best_block().await {
+			Err(e) => {
+				assert_eq!(e.kind(), crate::BlockSourceErrorKind::Transient);
+				assert_eq!(e.into_inner().to_string(), "peer does not serve compact block filters");
+			},
+			Ok(_) => panic!("Expected error"),
+		}
+	}
+}
@@ -50,9 +50,11 @@ BlockSourceResult<ValidatedBlockHeader> where B::Target: BlockSource {
 /// use lightning::sign;
 /// use lightning::sign::{EntropySource, NodeSigner, SignerProvider};
 /// use lightning::ln::channelmanager::{ChannelManager, ChannelManagerReadArgs};
+/// use lightning::offers::offer::CurrencyConversion;
 /// use lightning::routing::router::Router;
 /// use lightning::util::config::UserConfig;
 /// use lightning::util::logger::Logger;
+/// use lightning::util::metrics::MetricsRecorder;
 /// use lightning::util::ser::ReadableArgs;
 ///
 /// use lightning_block_sync::*;
@@ -70,9 +72,11 @@ BlockSourceResult<ValidatedBlockHeader> where B::Target: BlockSource {
 /// 	L: Logger,
 /// 	C: chain::Filter,
 /// 	P: chainmonitor::Persist<SP::EcdsaSigner>,
+/// 	MR: MetricsRecorder,
+/// 	CC: CurrencyConversion,
 /// >(
 /// 	block_source: &B,
-/// 	chain_monitor: &ChainMonitor<SP::EcdsaSigner, &C, &T, &F, &L, &P>,
+/// 	chain_monitor: &ChainMonitor<SP::EcdsaSigner, &C, &T, &F, &L, &P, &MR>,
 /// 	config: UserConfig,
 /// 	entropy_source: &ES,
 /// 	node_signer: &NS,
@@ -82,6 +86,7 @@ BlockSourceResult<ValidatedBlockHeader> where B::Target: BlockSource {
 /// 	router: &R,
 /// 	logger: &L,
 /// 	persister: &P,
+/// 	currency_conversion: &CC,
 /// ) {
 /// 	// Read a serialized channel monitor paired with the block hash when it was persisted.
 /// 	let serialized_monitor = "...";
@@ -100,10 +105,11 @@ BlockSourceResult<ValidatedBlockHeader> where B::Target: BlockSource {
 /// 			tx_broadcaster,
 /// 			router,
 /// 			logger,
+/// 			currency_conversion,
 /// 			config,
 /// 			vec![&mut monitor],
 /// 		);
-/// 		<(BlockHash, ChannelManager<&ChainMonitor<SP::EcdsaSigner, &C, &T, &F, &L, &P>, &T, &ES, &NS, &SP, &F, &R, &L>)>::read(
+/// 		<(BlockHash, ChannelManager<&ChainMonitor<SP::EcdsaSigner, &C, &T, &F, &L, &P, &MR>, &T, &ES, &NS, &SP, &F, &R, &L, &CC>)>::read(
 /// 			&mut Cursor::new(&serialized_manager), read_args).unwrap()
 /// 	};
 ///
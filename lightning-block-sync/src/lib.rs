@@ -8,9 +8,11 @@
 //! and data.
 //!
 //! Enabling feature `rest-client` or `rpc-client` allows configuring the client to fetch blocks
-//! using Bitcoin Core's REST or RPC interface, respectively.
+//! using Bitcoin Core's REST or RPC interface, respectively. Enabling feature `bip157-client`
+//! allows fetching headers, compact block filters and only relevant blocks from a Bitcoin P2P peer
+//! instead (see BIP 157 and 158).
 //!
-//! Both features support either blocking I/O using `std::net::TcpStream` or, with feature `tokio`,
+//! All three features support either blocking I/O using `std::net::TcpStream` or, with feature `tokio`,
 //! non-blocking I/O using `tokio::net::TcpStream` from inside a Tokio runtime.
 
 #![deny(rustdoc::broken_intra_doc_links)]
@@ -35,6 +37,9 @@ pub mod rest;
 #[cfg(feature = "rpc-client")]
 pub mod rpc;
 
+#[cfg(feature = "bip157-client")]
+pub mod bip157;
+
 #[cfg(any(feature = "rest-client", feature = "rpc-client"))]
 mod convert;
 
@@ -18,7 +18,9 @@
 //! `SocketDescriptor` implementation.
 //!
 //! Three methods are exposed to register a new connection for handling in [`tokio::spawn`] calls;
-//! see their individual docs for details.
+//! see their individual docs for details. Outbound connections may also be routed through a
+//! SOCKS5 proxy such as Tor via [`tor_connect_outbound`], allowing connections to peers which only
+//! announce [`SocketAddress::OnionV3`] addresses.
 //!
 //! [`PeerManager`]: lightning::ln::peer_handler::PeerManager
 
@@ -30,6 +32,7 @@
 
 use bitcoin::secp256k1::PublicKey;
 
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time;
 use tokio::sync::mpsc;
@@ -38,6 +41,7 @@ use lightning::ln::peer_handler;
 use lightning::ln::peer_handler::SocketDescriptor as LnSocketTrait;
 use lightning::ln::peer_handler::APeerManager;
 use lightning::ln::msgs::SocketAddress;
+use lightning::sign::EntropySource;
 
 use std::ops::Deref;
 use std::task::{self, Poll};
@@ -412,11 +416,136 @@ pub async fn connect_outbound<PM: Deref + 'static + Send + Sync + Clone>(
 	addr: SocketAddr,
 ) -> Option<impl std::future::Future<Output=()>>
 where PM::Target: APeerManager<Descriptor = SocketDescriptor> {
-	if let Ok(Ok(stream)) = time::timeout(Duration::from_secs(10), async { TcpStream::connect(&addr).await.map(|s| s.into_std().unwrap()) }).await {
+	if let Ok(Ok(stream)) = time::timeout(Duration::from_secs(CONNECT_OUTBOUND_TIMEOUT), async { TcpStream::connect(&addr).await.map(|s| s.into_std().unwrap()) }).await {
 		Some(setup_outbound(peer_manager, their_node_id, stream))
 	} else { None }
 }
 
+/// Routes [`connect_outbound`] through a SOCKS5 proxy such as Tor, allowing connections to peers
+/// announcing [`SocketAddress::OnionV3`] or [`SocketAddress::Hostname`] addresses, which are
+/// resolved by the proxy.
+///
+/// Each connection authenticates to the proxy with a fresh random password sourced from
+/// [`EntropySource::get_secure_random_bytes`], which Tor uses to isolate the connection on its
+/// own circuit (see `IsolateSOCKSAuth` in the Tor manual).
+///
+/// Returns `None` if the proxy could not be reached, refused the connection or the connection
+/// could not be established within ten seconds. [`SocketAddress::OnionV2`] addresses are not
+/// supported as Tor no longer supports them.
+///
+/// Returns a future (as the fn is async) which yields another future, see [`connect_outbound`]
+/// for details on this return value.
+pub async fn tor_connect_outbound<PM: Deref + 'static + Send + Sync + Clone, ES: Deref>(
+	peer_manager: PM,
+	their_node_id: PublicKey,
+	addr: SocketAddress,
+	tor_proxy_addr: SocketAddr,
+	entropy_source: ES,
+) -> Option<impl std::future::Future<Output=()>>
+where PM::Target: APeerManager<Descriptor = SocketDescriptor>, ES::Target: EntropySource {
+	let connect_fut = async {
+		socks5_connect(addr, tor_proxy_addr, entropy_source).await.map(|s| s.into_std().unwrap())
+	};
+	if let Ok(Ok(stream)) = time::timeout(Duration::from_secs(CONNECT_OUTBOUND_TIMEOUT), connect_fut).await {
+		Some(setup_outbound(peer_manager, their_node_id, stream))
+	} else { None }
+}
+
+const CONNECT_OUTBOUND_TIMEOUT: u64 = 10;
+
+// Constants defined in RFC 1928 and RFC 1929.
+const SOCKS5_VERSION: u8 = 5;
+const SOCKS5_USERNAME_PASSWORD_AUTH: u8 = 2;
+const SOCKS5_USERNAME_PASSWORD_VERSION: u8 = 1;
+const SOCKS5_CMD_CONNECT: u8 = 1;
+const SOCKS5_RSV: u8 = 0;
+const SOCKS5_ATYP_IPV4: u8 = 1;
+const SOCKS5_ATYP_DOMAINNAME: u8 = 3;
+const SOCKS5_ATYP_IPV6: u8 = 4;
+const SOCKS5_SUCCESS: u8 = 0;
+
+// Tor accepts any username, we use the one suggested in Tor's SOCKS extensions spec for stream
+// isolation. The password is random entropy, hex-encoded.
+const SOCKS5_USERNAME: &[u8] = b"<torS0X>0";
+const SOCKS5_PASSWORD_ENTROPY_LEN: usize = 32;
+
+/// Opens a connection to `addr` through the SOCKS5 proxy at `proxy_addr`, authenticating with a
+/// random password for stream isolation.
+async fn socks5_connect<ES: Deref>(
+	addr: SocketAddress, proxy_addr: SocketAddr, entropy_source: ES,
+) -> Result<TcpStream, ()> where ES::Target: EntropySource {
+	let mut request = Vec::with_capacity(1 /* VER */ + 1 /* CMD */ + 1 /* RSV */ + 1 /* ATYP */ +
+		1 /* hostname len */ + u8::MAX as usize /* hostname */ + 2 /* port */);
+	request.extend_from_slice(&[SOCKS5_VERSION, SOCKS5_CMD_CONNECT, SOCKS5_RSV]);
+	match addr {
+		SocketAddress::TcpIpV4 { addr, port } => {
+			request.push(SOCKS5_ATYP_IPV4);
+			request.extend_from_slice(&addr);
+			request.extend_from_slice(&port.to_be_bytes());
+		},
+		SocketAddress::TcpIpV6 { addr, port } => {
+			request.push(SOCKS5_ATYP_IPV6);
+			request.extend_from_slice(&addr);
+			request.extend_from_slice(&port.to_be_bytes());
+		},
+		ref onion_v3 @ SocketAddress::OnionV3 { port, .. } => {
+			let onion_v3_url = onion_v3.to_string();
+			let hostname = onion_v3_url.split_once(':').ok_or(())?.0.as_bytes();
+			request.extend_from_slice(&[SOCKS5_ATYP_DOMAINNAME, hostname.len() as u8]);
+			request.extend_from_slice(hostname);
+			request.extend_from_slice(&port.to_be_bytes());
+		},
+		SocketAddress::Hostname { hostname, port } => {
+			request.extend_from_slice(&[SOCKS5_ATYP_DOMAINNAME, hostname.len()]);
+			request.extend_from_slice(hostname.as_bytes());
+			request.extend_from_slice(&port.to_be_bytes());
+		},
+		SocketAddress::OnionV2(_) => return Err(()),
+	}
+
+	let mut stream = TcpStream::connect(&proxy_addr).await.map_err(|_| ())?;
+
+	stream.write_all(&[SOCKS5_VERSION, 1 /* NMETHODS */, SOCKS5_USERNAME_PASSWORD_AUTH]).await
+		.map_err(|_| ())?;
+	let mut method_selection_reply = [0u8; 2];
+	stream.read_exact(&mut method_selection_reply).await.map_err(|_| ())?;
+	if method_selection_reply != [SOCKS5_VERSION, SOCKS5_USERNAME_PASSWORD_AUTH] {
+		return Err(());
+	}
+
+	let password_entropy: [u8; SOCKS5_PASSWORD_ENTROPY_LEN] = entropy_source.get_secure_random_bytes();
+	let password = password_entropy.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+	let mut auth_request = Vec::with_capacity(3 + SOCKS5_USERNAME.len() + password.len());
+	auth_request.extend_from_slice(&[SOCKS5_USERNAME_PASSWORD_VERSION, SOCKS5_USERNAME.len() as u8]);
+	auth_request.extend_from_slice(SOCKS5_USERNAME);
+	auth_request.push(password.len() as u8);
+	auth_request.extend_from_slice(password.as_bytes());
+	stream.write_all(&auth_request).await.map_err(|_| ())?;
+	let mut auth_reply = [0u8; 2];
+	stream.read_exact(&mut auth_reply).await.map_err(|_| ())?;
+	if auth_reply != [SOCKS5_USERNAME_PASSWORD_VERSION, SOCKS5_SUCCESS] {
+		return Err(());
+	}
+
+	stream.write_all(&request).await.map_err(|_| ())?;
+	let mut reply_header = [0u8; 4];
+	stream.read_exact(&mut reply_header).await.map_err(|_| ())?;
+	if reply_header[..3] != [SOCKS5_VERSION, SOCKS5_SUCCESS, SOCKS5_RSV] {
+		return Err(());
+	}
+	// Skip over the bound address and port, which we have no use for.
+	let bound_addr_len = match reply_header[3] {
+		SOCKS5_ATYP_IPV4 => 4,
+		SOCKS5_ATYP_IPV6 => 16,
+		SOCKS5_ATYP_DOMAINNAME => stream.read_u8().await.map_err(|_| ())? as usize,
+		_ => return Err(()),
+	};
+	let mut bound_addr = [0u8; u8::MAX as usize + 2];
+	stream.read_exact(&mut bound_addr[..bound_addr_len + 2]).await.map_err(|_| ())?;
+
+	Ok(stream)
+}
+
 const SOCK_WAKER_VTABLE: task::RawWakerVTable =
 	task::RawWakerVTable::new(clone_socket_waker, wake_socket_waker, wake_socket_waker_by_ref, drop_socket_waker);
 
@@ -559,14 +688,18 @@ mod tests {
 	use lightning::ln::features::NodeFeatures;
 	use lightning::routing::gossip::NodeId;
 	use lightning::events::*;
+	use lightning::sign::KeysManager;
+	use lightning::util::metrics::IgnoringMetricsRecorder;
 	use lightning::util::test_utils::TestNodeSigner;
 	use bitcoin::Network;
 	use bitcoin::blockdata::constants::ChainHash;
 	use bitcoin::secp256k1::{Secp256k1, SecretKey, PublicKey};
 
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
 	use tokio::sync::mpsc;
 
 	use std::mem;
+	use std::net::SocketAddr;
 	use std::sync::atomic::{AtomicBool, Ordering};
 	use std::sync::{Arc, Mutex};
 	use std::time::Duration;
@@ -592,10 +725,12 @@ mod tests {
 		fn get_next_channel_announcement(&self, _starting_point: u64) -> Option<(ChannelAnnouncement, Option<ChannelUpdate>, Option<ChannelUpdate>)> { None }
 		fn get_next_node_announcement(&self, _starting_point: Option<&NodeId>) -> Option<NodeAnnouncement> { None }
 		fn peer_connected(&self, _their_node_id: &PublicKey, _init_msg: &Init, _inbound: bool) -> Result<(), ()> { Ok(()) }
+		fn peer_disconnected(&self, _their_node_id: &PublicKey) {}
 		fn handle_reply_channel_range(&self, _their_node_id: &PublicKey, _msg: ReplyChannelRange) -> Result<(), LightningError> { Ok(()) }
 		fn handle_reply_short_channel_ids_end(&self, _their_node_id: &PublicKey, _msg: ReplyShortChannelIdsEnd) -> Result<(), LightningError> { Ok(()) }
 		fn handle_query_channel_range(&self, _their_node_id: &PublicKey, _msg: QueryChannelRange) -> Result<(), LightningError> { Ok(()) }
 		fn handle_query_short_channel_ids(&self, _their_node_id: &PublicKey, _msg: QueryShortChannelIds) -> Result<(), LightningError> { Ok(()) }
+		fn get_next_short_channel_ids_reply(&self, _their_node_id: &PublicKey) -> Option<ShortChannelIdsReply> { None }
 		fn provided_node_features(&self) -> NodeFeatures { NodeFeatures::empty() }
 		fn provided_init_features(&self, _their_node_id: &PublicKey) -> InitFeatures { InitFeatures::empty() }
 		fn processing_queue_high(&self) -> bool { false }
@@ -619,6 +754,8 @@ mod tests {
 		fn handle_channel_update(&self, _their_node_id: &PublicKey, _msg: &ChannelUpdate) {}
 		fn handle_open_channel_v2(&self, _their_node_id: &PublicKey, _msg: &OpenChannelV2) {}
 		fn handle_accept_channel_v2(&self, _their_node_id: &PublicKey, _msg: &AcceptChannelV2) {}
+		fn handle_peer_storage(&self, _their_node_id: &PublicKey, _msg: &PeerStorage) {}
+		fn handle_peer_storage_retrieval(&self, _their_node_id: &PublicKey, _msg: &PeerStorageRetrieval) {}
 		fn handle_stfu(&self, _their_node_id: &PublicKey, _msg: &Stfu) {}
 		fn handle_splice(&self, _their_node_id: &PublicKey, _msg: &Splice) {}
 		fn handle_splice_ack(&self, _their_node_id: &PublicKey, _msg: &SpliceAck) {}
@@ -651,6 +788,7 @@ mod tests {
 		fn get_chain_hashes(&self) -> Option<Vec<ChainHash>> {
 			Some(vec![ChainHash::using_genesis_block(Network::Testnet)])
 		}
+		fn peer_has_channels(&self, _their_node_id: &PublicKey) -> bool { false }
 	}
 	impl MessageSendEventsProvider for MsgHandler {
 		fn get_and_clear_pending_msg_events(&self) -> Vec<MessageSendEvent> {
@@ -697,7 +835,7 @@ mod tests {
 			route_handler: Arc::clone(&a_handler),
 			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
 			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
-		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key))));
+		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key)), IgnoringMetricsRecorder {}));
 
 		let (b_connected_sender, mut b_connected) = mpsc::channel(1);
 		let (b_disconnected_sender, mut b_disconnected) = mpsc::channel(1);
@@ -713,7 +851,7 @@ mod tests {
 			route_handler: Arc::clone(&b_handler),
 			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
 			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
-		}, 0, &[2; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(b_key))));
+		}, 0, &[2; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(b_key)), IgnoringMetricsRecorder {}));
 
 		// We bind on localhost, hoping the environment is properly configured with a local
 		// address. This may not always be the case in containers and the like, so if this test is
@@ -767,7 +905,7 @@ mod tests {
 			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
 			route_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
 			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
-		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key))));
+		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key)), IgnoringMetricsRecorder {}));
 
 		// Make two connections, one for an inbound and one for an outbound connection
 		let conn_a = {
@@ -798,4 +936,166 @@ mod tests {
 	async fn unthreaded_race_disconnect_accept() {
 		race_disconnect_accept().await;
 	}
+
+	/// The stream isolation password and destination (including its address type) of each
+	/// connection request received by a [`spawn_socks5_stub`].
+	type Socks5Requests = Arc<Mutex<Vec<(Vec<u8>, Vec<u8>)>>>;
+
+	/// Spawns a minimal SOCKS5 proxy on localhost which records all connection requests. If a
+	/// `target` is provided, all connections are forwarded to it regardless of the requested
+	/// destination, otherwise they are refused with a "host unreachable" reply.
+	async fn spawn_socks5_stub(target: Option<SocketAddr>) -> (SocketAddr, Socks5Requests) {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let proxy_addr = listener.local_addr().unwrap();
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let stub_requests = Arc::clone(&requests);
+		tokio::spawn(async move {
+			loop {
+				let (mut stream, _) = listener.accept().await.unwrap();
+				let requests = Arc::clone(&stub_requests);
+				tokio::spawn(async move {
+					let mut method_selection = [0u8; 3];
+					stream.read_exact(&mut method_selection).await.unwrap();
+					assert_eq!(method_selection, [5, 1, 2]);
+					stream.write_all(&[5, 2]).await.unwrap();
+
+					assert_eq!(stream.read_u8().await.unwrap(), 1);
+					let mut username = vec![0u8; stream.read_u8().await.unwrap() as usize];
+					stream.read_exact(&mut username).await.unwrap();
+					let mut password = vec![0u8; stream.read_u8().await.unwrap() as usize];
+					stream.read_exact(&mut password).await.unwrap();
+					stream.write_all(&[1, 0]).await.unwrap();
+
+					let mut request_header = [0u8; 4];
+					stream.read_exact(&mut request_header).await.unwrap();
+					assert_eq!(request_header[..3], [5, 1, 0]);
+					let addr_len = match request_header[3] {
+						1 => 4,
+						3 => stream.read_u8().await.unwrap() as usize,
+						4 => 16,
+						_ => panic!("Unexpected address type"),
+					};
+					let mut destination = vec![0u8; addr_len + 2];
+					stream.read_exact(&mut destination).await.unwrap();
+					destination.insert(0, request_header[3]);
+					requests.lock().unwrap().push((password, destination));
+
+					if let Some(target) = target {
+						let mut target_stream = tokio::net::TcpStream::connect(target).await.unwrap();
+						stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).await.unwrap();
+						let _ = tokio::io::copy_bidirectional(&mut stream, &mut target_stream).await;
+					} else {
+						stream.write_all(&[5, 4, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+					}
+				});
+			}
+		});
+		(proxy_addr, requests)
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn socks5_proxy_connection_test() {
+		let secp_ctx = Secp256k1::new();
+		let a_key = SecretKey::from_slice(&[1; 32]).unwrap();
+		let b_key = SecretKey::from_slice(&[2; 32]).unwrap();
+		let a_pub = PublicKey::from_secret_key(&secp_ctx, &a_key);
+		let b_pub = PublicKey::from_secret_key(&secp_ctx, &b_key);
+
+		let (a_connected_sender, mut a_connected) = mpsc::channel(1);
+		let (a_disconnected_sender, _a_disconnected) = mpsc::channel(1);
+		let a_handler = Arc::new(MsgHandler {
+			expected_pubkey: b_pub,
+			pubkey_connected: a_connected_sender,
+			pubkey_disconnected: a_disconnected_sender,
+			disconnected_flag: AtomicBool::new(false),
+			msg_events: Mutex::new(Vec::new()),
+		});
+		let a_manager = Arc::new(PeerManager::new(MessageHandler {
+			chan_handler: Arc::clone(&a_handler),
+			route_handler: Arc::clone(&a_handler),
+			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key)), IgnoringMetricsRecorder {}));
+
+		let (b_connected_sender, mut b_connected) = mpsc::channel(1);
+		let (b_disconnected_sender, _b_disconnected) = mpsc::channel(1);
+		let b_handler = Arc::new(MsgHandler {
+			expected_pubkey: a_pub,
+			pubkey_connected: b_connected_sender,
+			pubkey_disconnected: b_disconnected_sender,
+			disconnected_flag: AtomicBool::new(false),
+			msg_events: Mutex::new(Vec::new()),
+		});
+		let b_manager = Arc::new(PeerManager::new(MessageHandler {
+			chan_handler: Arc::clone(&b_handler),
+			route_handler: Arc::clone(&b_handler),
+			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+		}, 0, &[2; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(b_key)), IgnoringMetricsRecorder {}));
+
+		let b_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let b_addr = b_listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			let (stream, _) = b_listener.accept().await.unwrap();
+			super::setup_inbound(b_manager, stream.into_std().unwrap()).await;
+		});
+
+		let (proxy_addr, requests) = spawn_socks5_stub(Some(b_addr)).await;
+		let keys_manager = Arc::new(KeysManager::new(&[42; 32], 42, 42));
+		let peer_addr = SocketAddress::TcpIpV4 { addr: [127, 0, 0, 1], port: b_addr.port() };
+		let _fut_a = super::tor_connect_outbound(Arc::clone(&a_manager), b_pub, peer_addr, proxy_addr, keys_manager)
+			.await.unwrap();
+
+		tokio::time::timeout(Duration::from_secs(10), a_connected.recv()).await.unwrap();
+		tokio::time::timeout(Duration::from_secs(1), b_connected.recv()).await.unwrap();
+
+		let requests = requests.lock().unwrap();
+		assert_eq!(requests.len(), 1);
+		let mut expected_destination = vec![1, 127, 0, 0, 1];
+		expected_destination.extend_from_slice(&b_addr.port().to_be_bytes());
+		assert_eq!(requests[0].1, expected_destination);
+		assert_eq!(requests[0].0.len(), 64);
+	}
+
+	#[tokio::test]
+	async fn socks5_proxy_onion_v3_test() {
+		let secp_ctx = Secp256k1::new();
+		let a_key = SecretKey::from_slice(&[1; 32]).unwrap();
+		let b_key = SecretKey::from_slice(&[2; 32]).unwrap();
+		let b_pub = PublicKey::from_secret_key(&secp_ctx, &b_key);
+
+		let a_manager = Arc::new(PeerManager::new(MessageHandler {
+			chan_handler: Arc::new(lightning::ln::peer_handler::ErroringMessageHandler::new()),
+			onion_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+			route_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+			custom_message_handler: Arc::new(lightning::ln::peer_handler::IgnoringMessageHandler{}),
+		}, 0, &[1; 32], Arc::new(TestLogger()), Arc::new(TestNodeSigner::new(a_key)), IgnoringMetricsRecorder {}));
+
+		let (proxy_addr, requests) = spawn_socks5_stub(None).await;
+		let keys_manager = Arc::new(KeysManager::new(&[42; 32], 42, 42));
+		let onion_addr = SocketAddress::OnionV3 {
+			ed25519_pubkey: [42; 32], checksum: 0x4242, version: 3, port: 9735,
+		};
+
+		// The stub refuses all connections, but should have been asked to resolve the onion address
+		// on a fresh isolated stream each time.
+		for _ in 0..2 {
+			assert!(super::tor_connect_outbound(Arc::clone(&a_manager), b_pub, onion_addr.clone(),
+				proxy_addr, Arc::clone(&keys_manager)).await.is_none());
+		}
+		assert!(super::tor_connect_outbound(Arc::clone(&a_manager), b_pub,
+			SocketAddress::OnionV2([42; 12]), proxy_addr, Arc::clone(&keys_manager)).await.is_none());
+
+		let requests = requests.lock().unwrap();
+		assert_eq!(requests.len(), 2);
+		let onion_addr_string = onion_addr.to_string();
+		let hostname = onion_addr_string.split_once(':').unwrap().0;
+		assert!(hostname.ends_with(".onion"));
+		let mut expected_destination = vec![3];
+		expected_destination.extend_from_slice(hostname.as_bytes());
+		expected_destination.extend_from_slice(&9735u16.to_be_bytes());
+		assert_eq!(requests[0].1, expected_destination);
+		assert_eq!(requests[1].1, expected_destination);
+		assert_ne!(requests[0].0, requests[1].0);
+	}
 }
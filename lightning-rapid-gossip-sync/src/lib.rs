@@ -60,6 +60,10 @@
 //! let current_time_unix = 0;
 //! let new_last_sync_timestamp_result = rapid_sync.update_network_graph_no_std(snapshot_contents, Some(current_time_unix));
 //! ```
+//!
+//! # Serving Snapshots
+//! Rather than relying on a third-party server, snapshots may also be generated from a local
+//! [`NetworkGraph`] via the [`SnapshotGenerator`], e.g., to serve a fleet of mobile clients.
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
@@ -78,6 +82,9 @@ use lightning::routing::gossip::NetworkGraph;
 use lightning::util::logger::Logger;
 
 pub use crate::error::GraphSyncError;
+pub use crate::snapshot::{GossipHistory, SnapshotGenerator};
+#[cfg(feature = "std")]
+pub use crate::snapshot::InMemoryGossipHistory;
 
 /// Error types that these functions can return
 mod error;
@@ -85,6 +92,9 @@ mod error;
 /// Core functionality of this crate
 mod processing;
 
+/// Generation of snapshots for serving rapid gossip sync clients
+mod snapshot;
+
 /// The main Rapid Gossip Sync object.
 ///
 /// See [crate-level documentation] for usage.
@@ -27,7 +27,7 @@ use alloc::{vec::Vec, borrow::ToOwned};
 /// sync formats arise in the future.
 ///
 /// The fourth byte is the protocol version in case our format gets updated.
-const GOSSIP_PREFIX: [u8; 4] = [76, 68, 75, 1];
+pub(crate) const GOSSIP_PREFIX: [u8; 4] = [76, 68, 75, 1];
 
 /// Maximum vector allocation capacity for distinct node IDs. This constraint is necessary to
 /// avoid malicious updates being able to trigger excessive memory allocation.
@@ -0,0 +1,479 @@
+use core::ops::Deref;
+
+use lightning::ln::msgs::UnsignedChannelUpdate;
+use lightning::routing::gossip::{ChannelInfo, ChannelUpdateInfo, NetworkGraph, NodeId};
+use lightning::util::logger::Logger;
+use lightning::util::ser::{BigSize, Writeable};
+use lightning::log_debug;
+
+use crate::processing::GOSSIP_PREFIX;
+
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+
+const INCREMENTAL_UPDATE_FLAG: u8 = 0b_1000_0000;
+const CLTV_EXPIRY_DELTA_FLAG: u8 = 0b_0100_0000;
+const HTLC_MINIMUM_MSAT_FLAG: u8 = 0b_0010_0000;
+const FEE_BASE_MSAT_FLAG: u8 = 0b_0001_0000;
+const FEE_PROPORTIONAL_MILLIONTHS_FLAG: u8 = 0b_0000_1000;
+const HTLC_MAXIMUM_MSAT_FLAG: u8 = 0b_0000_0100;
+const DISABLED_FLAG: u8 = 0b_0000_0010;
+
+/// A store of the gossip a [`SnapshotGenerator`] has seen over time.
+///
+/// As the [`NetworkGraph`] only holds the latest state of each channel, this is used to determine
+/// which data a client which last synced at a given timestamp already has, allowing to only
+/// include any changes since in delta snapshots.
+pub trait GossipHistory {
+	/// Returns the time at which we first saw the announcement of the channel with the given
+	/// `short_channel_id`, if known.
+	fn channel_announcement_seen_at(&self, short_channel_id: u64) -> Option<u32>;
+
+	/// Returns the latest channel update for the given `direction` (i.e., the direction bit of the
+	/// update's `flags`) of the channel with the given `short_channel_id` which we had seen at
+	/// `timestamp`, if any.
+	fn latest_channel_update_seen_by(
+		&self, short_channel_id: u64, direction: u8, timestamp: u32
+	) -> Option<UnsignedChannelUpdate>;
+}
+
+/// A simple in-memory [`GossipHistory`], to be fed with any gossip as it is applied to the
+/// [`NetworkGraph`].
+///
+/// Note that this keeps every channel update ever recorded, so users serving snapshots over a long
+/// time may want to back their [`GossipHistory`] by a database instead.
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct InMemoryGossipHistory {
+	announcements: Mutex<HashMap<u64, u32>>,
+	/// The updates seen per channel direction, along with the time they were seen at, ordered by
+	/// the latter.
+	updates: Mutex<HashMap<(u64, u8), UpdatesSeen>>,
+}
+
+#[cfg(feature = "std")]
+type UpdatesSeen = Vec<(u32, UnsignedChannelUpdate)>;
+
+#[cfg(feature = "std")]
+impl InMemoryGossipHistory {
+	/// Creates a new, empty [`InMemoryGossipHistory`].
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Records that we saw the announcement of the channel with the given `short_channel_id` at
+	/// `seen_at`. Only the first time a channel's announcement is seen is retained.
+	pub fn record_channel_announcement(&self, short_channel_id: u64, seen_at: u32) {
+		let mut announcements = self.announcements.lock().unwrap();
+		let first_seen = announcements.entry(short_channel_id).or_insert(seen_at);
+		*first_seen = core::cmp::min(*first_seen, seen_at);
+	}
+
+	/// Records that we saw the given channel update at `seen_at`.
+	pub fn record_channel_update(&self, update: &UnsignedChannelUpdate, seen_at: u32) {
+		let mut updates = self.updates.lock().unwrap();
+		let direction_updates = updates.entry((update.short_channel_id, update.flags & 1))
+			.or_default();
+		let idx = direction_updates.partition_point(|(update_seen_at, _)| *update_seen_at <= seen_at);
+		direction_updates.insert(idx, (seen_at, update.clone()));
+	}
+}
+
+#[cfg(feature = "std")]
+impl GossipHistory for InMemoryGossipHistory {
+	fn channel_announcement_seen_at(&self, short_channel_id: u64) -> Option<u32> {
+		self.announcements.lock().unwrap().get(&short_channel_id).copied()
+	}
+
+	fn latest_channel_update_seen_by(
+		&self, short_channel_id: u64, direction: u8, timestamp: u32
+	) -> Option<UnsignedChannelUpdate> {
+		let updates = self.updates.lock().unwrap();
+		updates.get(&(short_channel_id, direction)).and_then(|direction_updates| {
+			direction_updates.iter().rev()
+				.find(|(seen_at, _)| *seen_at <= timestamp)
+				.map(|(_, update)| update.clone())
+		})
+	}
+}
+
+/// Generates rapid gossip sync snapshots from a [`NetworkGraph`], to be consumed by clients via
+/// [`RapidGossipSync::update_network_graph`].
+///
+/// This allows to self-host the server side of rapid gossip sync. Snapshots may either contain
+/// the full graph or only the changes since the time a client last synced, relying on the given
+/// [`GossipHistory`] to tell which data the client already has.
+///
+/// [`RapidGossipSync::update_network_graph`]: crate::RapidGossipSync::update_network_graph
+pub struct SnapshotGenerator<NG: Deref<Target=NetworkGraph<L>>, GH: Deref, L: Deref>
+where GH::Target: GossipHistory, L::Target: Logger {
+	network_graph: NG,
+	gossip_history: GH,
+	logger: L,
+}
+
+/// The values of a channel direction's routing policy which are included in a snapshot.
+#[derive(Clone, Copy, PartialEq)]
+struct ChannelPolicy {
+	cltv_expiry_delta: u16,
+	htlc_minimum_msat: u64,
+	fee_base_msat: u32,
+	fee_proportional_millionths: u32,
+	htlc_maximum_msat: u64,
+}
+
+impl ChannelPolicy {
+	fn from_update_info(info: &ChannelUpdateInfo) -> Self {
+		Self {
+			cltv_expiry_delta: info.cltv_expiry_delta,
+			htlc_minimum_msat: info.htlc_minimum_msat,
+			fee_base_msat: info.fees.base_msat,
+			fee_proportional_millionths: info.fees.proportional_millionths,
+			htlc_maximum_msat: info.htlc_maximum_msat,
+		}
+	}
+
+	fn from_update(update: &UnsignedChannelUpdate) -> Self {
+		Self {
+			cltv_expiry_delta: update.cltv_expiry_delta,
+			htlc_minimum_msat: update.htlc_minimum_msat,
+			fee_base_msat: update.fee_base_msat,
+			fee_proportional_millionths: update.fee_proportional_millionths,
+			htlc_maximum_msat: update.htlc_maximum_msat,
+		}
+	}
+
+	/// Writes the flags byte and any fields of `self` differing from `base` to `buffer`.
+	fn write_update(&self, base: &ChannelPolicy, mut flags: u8, buffer: &mut Vec<u8>) {
+		if self.cltv_expiry_delta != base.cltv_expiry_delta { flags |= CLTV_EXPIRY_DELTA_FLAG; }
+		if self.htlc_minimum_msat != base.htlc_minimum_msat { flags |= HTLC_MINIMUM_MSAT_FLAG; }
+		if self.fee_base_msat != base.fee_base_msat { flags |= FEE_BASE_MSAT_FLAG; }
+		if self.fee_proportional_millionths != base.fee_proportional_millionths {
+			flags |= FEE_PROPORTIONAL_MILLIONTHS_FLAG;
+		}
+		if self.htlc_maximum_msat != base.htlc_maximum_msat { flags |= HTLC_MAXIMUM_MSAT_FLAG; }
+
+		write_to_buffer(&flags, buffer);
+		if flags & CLTV_EXPIRY_DELTA_FLAG != 0 { write_to_buffer(&self.cltv_expiry_delta, buffer); }
+		if flags & HTLC_MINIMUM_MSAT_FLAG != 0 { write_to_buffer(&self.htlc_minimum_msat, buffer); }
+		if flags & FEE_BASE_MSAT_FLAG != 0 { write_to_buffer(&self.fee_base_msat, buffer); }
+		if flags & FEE_PROPORTIONAL_MILLIONTHS_FLAG != 0 {
+			write_to_buffer(&self.fee_proportional_millionths, buffer);
+		}
+		if flags & HTLC_MAXIMUM_MSAT_FLAG != 0 { write_to_buffer(&self.htlc_maximum_msat, buffer); }
+	}
+}
+
+/// A channel update to be included in a snapshot.
+struct SnapshotUpdate {
+	short_channel_id: u64,
+	direction: u8,
+	enabled: bool,
+	policy: ChannelPolicy,
+	/// The policy the client already knows for this channel direction, if any, in which case an
+	/// incremental update is sent.
+	known_policy: Option<ChannelPolicy>,
+}
+
+fn write_to_buffer<W: Writeable>(value: &W, buffer: &mut Vec<u8>) {
+	value.write(buffer).expect("Writing to a Vec cannot fail");
+}
+
+/// Returns the most common of the given values, preferring the smallest one in case of ties.
+fn most_common_value<T: Ord + Copy + Default, I: Iterator<Item = T>>(values: I) -> T {
+	let mut counts = BTreeMap::new();
+	for value in values {
+		*counts.entry(value).or_insert(0usize) += 1;
+	}
+	let mut most_common: Option<(T, usize)> = None;
+	for (value, count) in counts {
+		if most_common.map_or(true, |(_, max_count)| count > max_count) {
+			most_common = Some((value, count));
+		}
+	}
+	most_common.map(|(value, _)| value).unwrap_or_default()
+}
+
+impl<NG: Deref<Target=NetworkGraph<L>>, GH: Deref, L: Deref> SnapshotGenerator<NG, GH, L>
+where GH::Target: GossipHistory, L::Target: Logger {
+	/// Instantiate a new [`SnapshotGenerator`] serving snapshots of the given `network_graph`.
+	pub fn new(network_graph: NG, gossip_history: GH, logger: L) -> Self {
+		Self { network_graph, gossip_history, logger }
+	}
+
+	/// Generates a snapshot of the [`NetworkGraph`] as of `snapshot_timestamp`, for a client which
+	/// last synced at `last_sync_timestamp`.
+	///
+	/// If `last_sync_timestamp` is `0`, a full snapshot is generated. Otherwise, the snapshot only
+	/// contains the channels announced and the channel updates seen since `last_sync_timestamp`,
+	/// with updates to channel directions known to the client only including the changed fields.
+	///
+	/// `snapshot_timestamp` should generally be the current time and is returned to the client by
+	/// [`RapidGossipSync::update_network_graph`], to be used as `last_sync_timestamp` for its next
+	/// sync.
+	///
+	/// [`RapidGossipSync::update_network_graph`]: crate::RapidGossipSync::update_network_graph
+	pub fn generate_snapshot(&self, last_sync_timestamp: u32, snapshot_timestamp: u32) -> Vec<u8> {
+		let read_only_graph = self.network_graph.read_only();
+
+		let mut channels: Vec<(&u64, &ChannelInfo)> = read_only_graph.channels().unordered_iter()
+			.filter(|(_, channel)| channel.one_to_two.is_some() || channel.two_to_one.is_some())
+			.collect();
+		channels.sort_unstable_by_key(|(short_channel_id, _)| **short_channel_id);
+
+		let mut announced_channels = Vec::new();
+		let mut updates = Vec::new();
+		for (short_channel_id, channel) in channels {
+			let is_new_channel = last_sync_timestamp == 0 ||
+				self.gossip_history.channel_announcement_seen_at(*short_channel_id)
+					.map_or(true, |seen_at| seen_at > last_sync_timestamp);
+			if is_new_channel {
+				announced_channels.push((*short_channel_id, channel));
+			}
+
+			for (direction, update_info) in [(0, &channel.one_to_two), (1, &channel.two_to_one)] {
+				let update_info = match update_info {
+					Some(update_info) => update_info,
+					None => continue,
+				};
+				let known_update = if is_new_channel { None } else {
+					self.gossip_history.latest_channel_update_seen_by(
+						*short_channel_id, direction, last_sync_timestamp
+					)
+				};
+				if let Some(ref known_update) = known_update {
+					if known_update.timestamp >= update_info.last_update {
+						// The client already has the latest update for this direction.
+						continue;
+					}
+				}
+				updates.push(SnapshotUpdate {
+					short_channel_id: *short_channel_id,
+					direction,
+					enabled: update_info.enabled,
+					policy: ChannelPolicy::from_update_info(update_info),
+					known_policy: known_update.as_ref().map(ChannelPolicy::from_update),
+				});
+			}
+		}
+
+		let mut node_ids: Vec<NodeId> = Vec::new();
+		let mut node_id_indices: BTreeMap<NodeId, usize> = BTreeMap::new();
+		let mut node_id_index = |node_id: &NodeId| {
+			*node_id_indices.entry(*node_id).or_insert_with(|| {
+				node_ids.push(*node_id);
+				node_ids.len() - 1
+			})
+		};
+		let announcement_node_indices: Vec<(usize, usize)> = announced_channels.iter()
+			.map(|(_, channel)| (node_id_index(&channel.node_one), node_id_index(&channel.node_two)))
+			.collect();
+
+		let mut snapshot = Vec::new();
+		snapshot.extend_from_slice(&GOSSIP_PREFIX);
+		write_to_buffer(&self.network_graph.get_chain_hash(), &mut snapshot);
+		write_to_buffer(&snapshot_timestamp, &mut snapshot);
+
+		write_to_buffer(&(node_ids.len() as u32), &mut snapshot);
+		for node_id in node_ids.iter() {
+			write_to_buffer(node_id, &mut snapshot);
+		}
+
+		write_to_buffer(&(announced_channels.len() as u32), &mut snapshot);
+		let mut previous_scid = 0;
+		for ((short_channel_id, channel), (node_one_index, node_two_index)) in
+			announced_channels.iter().zip(announcement_node_indices.iter())
+		{
+			write_to_buffer(&channel.features, &mut snapshot);
+			write_to_buffer(&BigSize(short_channel_id - previous_scid), &mut snapshot);
+			write_to_buffer(&BigSize(*node_one_index as u64), &mut snapshot);
+			write_to_buffer(&BigSize(*node_two_index as u64), &mut snapshot);
+			previous_scid = *short_channel_id;
+		}
+
+		write_to_buffer(&(updates.len() as u32), &mut snapshot);
+		if !updates.is_empty() {
+			// Full updates only need to include the fields differing from the defaults, which we
+			// pick as the most common value of each field.
+			let full_update_policies = || updates.iter()
+				.filter(|update| update.known_policy.is_none())
+				.map(|update| update.policy);
+			let default_policy = ChannelPolicy {
+				cltv_expiry_delta: most_common_value(full_update_policies().map(|p| p.cltv_expiry_delta)),
+				htlc_minimum_msat: most_common_value(full_update_policies().map(|p| p.htlc_minimum_msat)),
+				fee_base_msat: most_common_value(full_update_policies().map(|p| p.fee_base_msat)),
+				fee_proportional_millionths:
+					most_common_value(full_update_policies().map(|p| p.fee_proportional_millionths)),
+				htlc_maximum_msat: most_common_value(full_update_policies().map(|p| p.htlc_maximum_msat)),
+			};
+			write_to_buffer(&default_policy.cltv_expiry_delta, &mut snapshot);
+			write_to_buffer(&default_policy.htlc_minimum_msat, &mut snapshot);
+			write_to_buffer(&default_policy.fee_base_msat, &mut snapshot);
+			write_to_buffer(&default_policy.fee_proportional_millionths, &mut snapshot);
+			write_to_buffer(&default_policy.htlc_maximum_msat, &mut snapshot);
+
+			let mut previous_scid = 0;
+			for update in updates.iter() {
+				write_to_buffer(&BigSize(update.short_channel_id - previous_scid), &mut snapshot);
+				previous_scid = update.short_channel_id;
+
+				let mut flags = update.direction;
+				if !update.enabled { flags |= DISABLED_FLAG; }
+				match update.known_policy {
+					Some(ref known_policy) => {
+						update.policy.write_update(known_policy, flags | INCREMENTAL_UPDATE_FLAG, &mut snapshot);
+					},
+					None => update.policy.write_update(&default_policy, flags, &mut snapshot),
+				}
+			}
+		}
+
+		log_debug!(self.logger, "Generated RGS snapshot at {} since {} with {} nodes, {} channel announcements and {} channel updates.",
+			snapshot_timestamp, last_sync_timestamp, node_ids.len(), announced_channels.len(), updates.len());
+		snapshot
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bitcoin::Network;
+	use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+	use lightning::ln::features::ChannelFeatures;
+	use lightning::ln::msgs::UnsignedChannelUpdate;
+	use lightning::routing::gossip::NetworkGraph;
+	use lightning::util::test_utils::TestLogger;
+
+	use crate::RapidGossipSync;
+	use crate::snapshot::{InMemoryGossipHistory, SnapshotGenerator};
+
+	const FULL_SNAPSHOT_TIME: u32 = 1_700_000_000;
+	const DELTA_SNAPSHOT_TIME: u32 = FULL_SNAPSHOT_TIME + 3600;
+
+	fn node_id(idx: u8) -> PublicKey {
+		PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[idx; 32]).unwrap())
+	}
+
+	fn channel_update(short_channel_id: u64, flags: u8, timestamp: u32, fee_base_msat: u32) -> UnsignedChannelUpdate {
+		UnsignedChannelUpdate {
+			chain_hash: bitcoin::blockdata::constants::ChainHash::using_genesis_block(Network::Bitcoin),
+			short_channel_id,
+			timestamp,
+			flags,
+			cltv_expiry_delta: 40 + (short_channel_id % 2) as u16 * 104,
+			htlc_minimum_msat: 1000,
+			htlc_maximum_msat: 100_000_000 * short_channel_id,
+			fee_base_msat,
+			fee_proportional_millionths: 100,
+			excess_data: Vec::new(),
+		}
+	}
+
+	fn apply_update(graph: &NetworkGraph<&TestLogger>, history: &InMemoryGossipHistory, update: UnsignedChannelUpdate, seen_at: u32) {
+		graph.update_channel_unsigned(&update).unwrap();
+		history.record_channel_update(&update, seen_at);
+	}
+
+	fn add_channel(graph: &NetworkGraph<&TestLogger>, history: &InMemoryGossipHistory, short_channel_id: u64, nodes: (u8, u8), seen_at: u32) {
+		let (node_a, node_b) = (node_id(nodes.0), node_id(nodes.1));
+		let (node_one, node_two) = if node_a.serialize() < node_b.serialize() { (node_a, node_b) } else { (node_b, node_a) };
+		graph.add_channel_from_partial_announcement(short_channel_id, seen_at as u64, ChannelFeatures::empty(), node_one, node_two).unwrap();
+		history.record_channel_announcement(short_channel_id, seen_at);
+	}
+
+	/// Asserts that the channels in `client_graph` match those of `server_graph`, ignoring the
+	/// update timestamps which are backdated by the client.
+	fn assert_graphs_match(server_graph: &NetworkGraph<&TestLogger>, client_graph: &NetworkGraph<&TestLogger>) {
+		let server_graph = server_graph.read_only();
+		let client_graph = client_graph.read_only();
+		assert_eq!(server_graph.channels().len(), client_graph.channels().len());
+		for (short_channel_id, server_channel) in server_graph.channels().unordered_iter() {
+			let client_channel = client_graph.channels().get(short_channel_id).unwrap();
+			assert_eq!(server_channel.features, client_channel.features);
+			assert_eq!(server_channel.node_one, client_channel.node_one);
+			assert_eq!(server_channel.node_two, client_channel.node_two);
+			for (server_info, client_info) in [
+				(&server_channel.one_to_two, &client_channel.one_to_two),
+				(&server_channel.two_to_one, &client_channel.two_to_one),
+			] {
+				assert_eq!(server_info.is_some(), client_info.is_some());
+				if let (Some(server_info), Some(client_info)) = (server_info, client_info) {
+					assert_eq!(server_info.enabled, client_info.enabled);
+					assert_eq!(server_info.cltv_expiry_delta, client_info.cltv_expiry_delta);
+					assert_eq!(server_info.htlc_minimum_msat, client_info.htlc_minimum_msat);
+					assert_eq!(server_info.htlc_maximum_msat, client_info.htlc_maximum_msat);
+					assert_eq!(server_info.fees, client_info.fees);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn full_and_delta_snapshots_round_trip() {
+		let logger = TestLogger::new();
+		let server_graph = NetworkGraph::new(Network::Bitcoin, &logger);
+		let history = InMemoryGossipHistory::new();
+		let initial_time = FULL_SNAPSHOT_TIME - 1000;
+		for short_channel_id in 1..=5 {
+			add_channel(&server_graph, &history, short_channel_id, (short_channel_id as u8, 42), initial_time);
+			apply_update(&server_graph, &history, channel_update(short_channel_id, 0, initial_time, 1000), initial_time);
+			if short_channel_id != 3 {
+				apply_update(&server_graph, &history, channel_update(short_channel_id, 1, initial_time, 0), initial_time);
+			}
+		}
+
+		let generator = SnapshotGenerator::new(&server_graph, &history, &logger);
+		let full_snapshot = generator.generate_snapshot(0, FULL_SNAPSHOT_TIME);
+
+		let client_graph = NetworkGraph::new(Network::Bitcoin, &logger);
+		let rapid_sync = RapidGossipSync::new(&client_graph, &logger);
+		assert_eq!(rapid_sync.update_network_graph_no_std(&full_snapshot, None).unwrap(), FULL_SNAPSHOT_TIME);
+		assert_graphs_match(&server_graph, &client_graph);
+
+		// Change the fee of one direction, disable another one, add the missing direction of
+		// channel 3 and announce a new channel.
+		let update_time = FULL_SNAPSHOT_TIME + 10;
+		apply_update(&server_graph, &history, channel_update(1, 0, update_time, 2000), update_time);
+		apply_update(&server_graph, &history, channel_update(2, 1 | 2, update_time, 0), update_time);
+		apply_update(&server_graph, &history, channel_update(3, 1, update_time, 5), update_time);
+		add_channel(&server_graph, &history, 6, (6, 7), update_time);
+		apply_update(&server_graph, &history, channel_update(6, 0, update_time, 1000), update_time);
+
+		let delta_snapshot = generator.generate_snapshot(FULL_SNAPSHOT_TIME, DELTA_SNAPSHOT_TIME);
+		assert!(delta_snapshot.len() < full_snapshot.len());
+		assert_eq!(rapid_sync.update_network_graph_no_std(&delta_snapshot, None).unwrap(), DELTA_SNAPSHOT_TIME);
+		assert_graphs_match(&server_graph, &client_graph);
+
+		// A delta snapshot to a client which already synced the delta doesn't contain any updates.
+		let empty_snapshot = generator.generate_snapshot(DELTA_SNAPSHOT_TIME, DELTA_SNAPSHOT_TIME + 3600);
+		let update_count_offset = empty_snapshot.len() - 4;
+		assert_eq!(&empty_snapshot[update_count_offset..], &[0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn full_snapshot_omits_default_values() {
+		let logger = TestLogger::new();
+		let server_graph = NetworkGraph::new(Network::Bitcoin, &logger);
+		let history = InMemoryGossipHistory::new();
+		let generator = SnapshotGenerator::new(&server_graph, &history, &logger);
+
+		add_channel(&server_graph, &history, 2, (1, 2), FULL_SNAPSHOT_TIME);
+		apply_update(&server_graph, &history, channel_update(2, 0, FULL_SNAPSHOT_TIME, 1000), FULL_SNAPSHOT_TIME);
+		let single_update_snapshot = generator.generate_snapshot(0, FULL_SNAPSHOT_TIME);
+
+		// An update matching the defaults only consists of its scid delta and flags.
+		apply_update(&server_graph, &history, channel_update(2, 1, FULL_SNAPSHOT_TIME, 1000), FULL_SNAPSHOT_TIME);
+		let two_update_snapshot = generator.generate_snapshot(0, FULL_SNAPSHOT_TIME);
+		assert_eq!(two_update_snapshot.len(), single_update_snapshot.len() + 2);
+
+		let client_graph = NetworkGraph::new(Network::Bitcoin, &logger);
+		let rapid_sync = RapidGossipSync::new(&client_graph, &logger);
+		rapid_sync.update_network_graph_no_std(&two_update_snapshot, None).unwrap();
+		assert_graphs_match(&server_graph, &client_graph);
+	}
+}
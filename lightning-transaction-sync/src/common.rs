@@ -1,6 +1,12 @@
 use lightning::chain::{Confirm, WatchedOutput};
 use bitcoin::{Txid, BlockHash, Transaction, OutPoint};
 use bitcoin::block::Header;
+#[cfg(any(feature = "electrum", feature = "electrum-async"))]
+use bitcoin::hash_types::TxMerkleNode;
+#[cfg(any(feature = "electrum", feature = "electrum-async"))]
+use bitcoin::hashes::Hash;
+#[cfg(any(feature = "electrum", feature = "electrum-async"))]
+use bitcoin::hashes::sha256d::Hash as Sha256d;
 
 use std::collections::{HashSet, HashMap};
 
@@ -108,3 +114,30 @@ pub(crate) struct ConfirmedTx {
 	pub block_height: u32,
 	pub pos: usize,
 }
+
+// Checks the merkle proof of `txid` being included at position `pos` of the block with the given
+// `merkle_root`, with the proof's hashes given in the byte order used by Electrum servers.
+#[cfg(any(feature = "electrum", feature = "electrum-async"))]
+pub(crate) fn validate_electrum_merkle_proof(
+	txid: &Txid, merkle_root: &TxMerkleNode, pos: usize, merkle: &[[u8; 32]],
+) -> bool {
+	let mut index = pos;
+	let mut cur = txid.to_raw_hash();
+	for bytes in merkle {
+		let mut bytes = *bytes;
+		bytes.reverse();
+		// unwrap() safety: `bytes` has len 32 so `from_slice` can never fail.
+		let next_hash = Sha256d::from_slice(&bytes).unwrap();
+		let (left, right) = if index % 2 == 0 {
+			(cur, next_hash)
+		} else {
+			(next_hash, cur)
+		};
+
+		let data = [&left[..], &right[..]].concat();
+		cur = Sha256d::hash(&data);
+		index /= 2;
+	}
+
+	cur == merkle_root.to_raw_hash()
+}
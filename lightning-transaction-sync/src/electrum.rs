@@ -1,20 +1,16 @@
-use crate::common::{ConfirmedTx, SyncState, FilterQueue};
+use crate::common::{ConfirmedTx, SyncState, FilterQueue, validate_electrum_merkle_proof};
 use crate::error::{TxSyncError, InternalError};
 
 use electrum_client::Client as ElectrumClient;
 use electrum_client::ElectrumApi;
-use electrum_client::GetMerkleRes;
 
 use lightning::util::logger::Logger;
 use lightning::{log_error, log_debug, log_trace};
 use lightning::chain::WatchedOutput;
-use lightning::chain::{Confirm, Filter};
+use lightning::chain::{Confirm, Filter, TransactionSync};
 
 use bitcoin::{BlockHash, Script, Transaction, Txid};
 use bitcoin::block::Header;
-use bitcoin::hash_types::TxMerkleNode;
-use bitcoin::hashes::Hash;
-use bitcoin::hashes::sha256d::Hash as Sha256d;
 
 use std::ops::Deref;
 use std::sync::Mutex;
@@ -51,7 +47,7 @@ where
 	pub fn new(server_url: String, logger: L) -> Result<Self, TxSyncError> {
 		let client = ElectrumClient::new(&server_url).map_err(|e| {
 			log_error!(logger, "Failed to connect to electrum server '{}': {}", server_url, e);
-			e
+			TxSyncError::Failed
 		})?;
 
 		Self::from_client(client, logger)
@@ -92,9 +88,9 @@ where
 		let mut num_unconfirmed = 0;
 
 		// Clear any header notifications we might have gotten to keep the queue count low.
-		while let Some(_) = self.client.block_headers_pop()? {}
+		while let Some(_) = self.client.block_headers_pop().map_err(InternalError::from)? {}
 
-		let tip_notification = self.client.block_headers_subscribe()?;
+		let tip_notification = self.client.block_headers_subscribe().map_err(InternalError::from)?;
 		let mut tip_header = tip_notification.header;
 		let mut tip_height = tip_notification.height as u32;
 
@@ -406,8 +402,8 @@ where
 				match self.client.block_header(prob_conf_height as usize) {
 					Ok(block_header) => {
 						let pos = merkle_res.pos;
-						if !self.validate_merkle_proof(&txid,
-							&block_header.merkle_root, merkle_res)?
+						if !validate_electrum_merkle_proof(&txid, &block_header.merkle_root,
+							merkle_res.pos, &merkle_res.merkle)
 						{
 							log_trace!(self.logger,
 								"Inconsistency: Block {} was unconfirmed during syncing.",
@@ -442,29 +438,6 @@ where
 	pub fn client(&self) -> &ElectrumClient {
 		&self.client
 	}
-
-	fn validate_merkle_proof(&self, txid: &Txid, merkle_root: &TxMerkleNode,
-		merkle_res: GetMerkleRes) -> Result<bool, InternalError>
-	{
-		let mut index = merkle_res.pos;
-		let mut cur = txid.to_raw_hash();
-		for mut bytes in merkle_res.merkle {
-			bytes.reverse();
-			// unwrap() safety: `bytes` has len 32 so `from_slice` can never fail.
-			let next_hash = Sha256d::from_slice(&bytes).unwrap();
-			let (left, right) = if index % 2 == 0 {
-				(cur, next_hash)
-			} else {
-				(next_hash, cur)
-			};
-
-			let data = [&left[..], &right[..]].concat();
-			cur = Sha256d::hash(&data);
-			index /= 2;
-		}
-
-		Ok(cur == merkle_root.to_raw_hash())
-	}
 }
 
 impl<L: Deref> Filter for ElectrumSyncClient<L>
@@ -481,3 +454,12 @@ where
 		locked_queue.outputs.insert(output.outpoint.into_bitcoin_outpoint(), output);
 	}
 }
+
+impl<L: Deref> TransactionSync for ElectrumSyncClient<L>
+where
+	L::Target: Logger,
+{
+	fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+		ElectrumSyncClient::sync(self, confirmables)
+	}
+}
@@ -0,0 +1,871 @@
+use crate::common::{ConfirmedTx, SyncState, FilterQueue, validate_electrum_merkle_proof};
+use crate::error::{TxSyncError, InternalError};
+
+use lightning::util::logger::Logger;
+use lightning::{log_error, log_debug, log_trace};
+use lightning::chain::WatchedOutput;
+use lightning::chain::{AsyncTransactionSync, AsyncTransactionSyncResult, Confirm, Filter};
+
+use bitcoin::{BlockHash, Script, ScriptBuf, Transaction, Txid};
+use bitcoin::block::Header;
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::sha256;
+
+use serde_json::{json, Value};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{oneshot, Notify};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The Electrum protocol version we require the server to support.
+const PROTOCOL_VERSION: &str = "1.4";
+
+/// The time we wait for the server to respond to a batch of requests before giving up.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The maximum length of a single message we accept from the server. Any response to a single
+/// request, e.g., a transaction or a script history, is expected to fit well within this limit.
+const MAX_MESSAGE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// Synchronizes LDK with a given Electrum server using an async client.
+///
+/// Needs to be registered with a [`ChainMonitor`] via the [`Filter`] interface to be informed of
+/// transactions and outputs to monitor for on-chain confirmation, unconfirmation, and
+/// reconfirmation.
+///
+/// Note that registration via [`Filter`] needs to happen before any calls to
+/// [`Watch::watch_channel`] to ensure we get notified of the items to monitor.
+///
+/// Other than [`ElectrumSyncClient`], this client runs on a Tokio runtime. It subscribes to block
+/// header notifications once per connection rather than polling for the chain tip, allowing
+/// [`Self::wait_for_new_tip`] to be used to only sync when a new block was found. Script histories
+/// and transactions are requested in batches.
+///
+/// The server URL needs to be of the form `tcp://host:port` or `host:port` for plaintext TCP
+/// connections. If the `electrum-async-tls` feature is enabled, `ssl://host:port` may be used to
+/// connect via TLS, verifying the server's certificate against the Mozilla root certificates.
+///
+/// If the connection to the server is lost, it is re-established on the next call to
+/// [`Self::sync`].
+///
+/// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
+/// [`Watch::watch_channel`]: lightning::chain::Watch::watch_channel
+/// [`Filter`]: lightning::chain::Filter
+/// [`ElectrumSyncClient`]: crate::ElectrumSyncClient
+pub struct AsyncElectrumSyncClient<L: Deref>
+where
+	L::Target: Logger,
+{
+	sync_state: tokio::sync::Mutex<SyncState>,
+	queue: Mutex<FilterQueue>,
+	server_url: String,
+	connection: tokio::sync::Mutex<Option<Arc<ElectrumConnection>>>,
+	logger: L,
+}
+
+impl<L: Deref> AsyncElectrumSyncClient<L>
+where
+	L::Target: Logger,
+{
+	/// Returns a new [`AsyncElectrumSyncClient`] object, connecting to the given Electrum server.
+	pub async fn new(server_url: String, logger: L) -> Result<Self, TxSyncError> {
+		let connection = ElectrumConnection::connect(&server_url).await.map_err(|e| {
+			log_error!(logger, "Failed to connect to electrum server '{}': {}", server_url, e);
+			e
+		})?;
+
+		Ok(Self {
+			sync_state: tokio::sync::Mutex::new(SyncState::new()),
+			queue: Mutex::new(FilterQueue::new()),
+			server_url,
+			connection: tokio::sync::Mutex::new(Some(Arc::new(connection))),
+			logger,
+		})
+	}
+
+	/// Synchronizes the given `confirmables` via their [`Confirm`] interface implementations. This
+	/// method should be called regularly to keep LDK up-to-date with current chain data.
+	///
+	/// For example, instances of [`ChannelManager`] and [`ChainMonitor`] can be informed about the
+	/// newest on-chain activity related to the items previously registered via the [`Filter`]
+	/// interface.
+	///
+	/// [`Confirm`]: lightning::chain::Confirm
+	/// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
+	/// [`ChannelManager`]: lightning::ln::channelmanager::ChannelManager
+	/// [`Filter`]: lightning::chain::Filter
+	pub async fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+		// This lock makes sure we're syncing once at a time.
+		let mut sync_state = self.sync_state.lock().await;
+
+		log_trace!(self.logger, "Starting transaction sync.");
+		#[cfg(feature = "time")]
+		let start_time = std::time::Instant::now();
+		let mut num_confirmed = 0;
+		let mut num_unconfirmed = 0;
+
+		let connection = self.connection().await.map_err(|e| {
+			sync_state.pending_sync = true;
+			e
+		})?;
+		let (mut tip_header, mut tip_height) = connection.tip();
+
+		loop {
+			let pending_registrations = self.queue.lock().unwrap().process_queues(&mut sync_state);
+			let tip_is_new = Some(tip_header.block_hash()) != sync_state.last_sync_hash;
+
+			// We loop until any registered transactions have been processed at least once, or the
+			// tip hasn't been updated during the last iteration.
+			if !sync_state.pending_sync && !pending_registrations && !tip_is_new {
+				// Nothing to do.
+				break;
+			} else {
+				// Update the known tip to the newest one.
+				if tip_is_new {
+					// First check for any unconfirmed transactions and act on it immediately.
+					match self.get_unconfirmed_transactions(&connection, &confirmables).await {
+						Ok(unconfirmed_txs) => {
+							// Double-check the tip hash. If it changed, a reorg happened since
+							// we started syncing and we need to restart last-minute.
+							match Self::check_update_tip(&connection, &mut tip_header, &mut tip_height).await {
+								Ok(false) => {
+									num_unconfirmed += unconfirmed_txs.len();
+									sync_state.sync_unconfirmed_transactions(
+										&confirmables,
+										unconfirmed_txs
+									);
+								}
+								Ok(true) => {
+									log_debug!(self.logger,
+										"Encountered inconsistency during transaction sync, restarting.");
+									sync_state.pending_sync = true;
+									continue;
+								}
+								Err(err) => {
+									// (Semi-)permanent failure, retry later.
+									log_error!(self.logger,
+										"Failed during transaction sync, aborting. Synced so far: {} confirmed, {} unconfirmed.",
+										num_confirmed,
+										num_unconfirmed
+									);
+									sync_state.pending_sync = true;
+									return Err(TxSyncError::from(err));
+								}
+							}
+						},
+						Err(err) => {
+							// (Semi-)permanent failure, retry later.
+							log_error!(self.logger,
+								"Failed during transaction sync, aborting. Synced so far: {} confirmed, {} unconfirmed.",
+								num_confirmed,
+								num_unconfirmed
+							);
+							sync_state.pending_sync = true;
+							return Err(TxSyncError::from(err));
+						}
+					}
+
+					// Update the best block.
+					for c in &confirmables {
+						c.best_block_updated(&tip_header, tip_height);
+					}
+				}
+
+				match self.get_confirmed_transactions(&connection, &sync_state).await {
+					Ok(confirmed_txs) => {
+						// Double-check the tip hash. If it changed, a reorg happened since
+						// we started syncing and we need to restart last-minute.
+						match Self::check_update_tip(&connection, &mut tip_header, &mut tip_height).await {
+							Ok(false) => {
+								num_confirmed += confirmed_txs.len();
+								sync_state.sync_confirmed_transactions(
+									&confirmables,
+									confirmed_txs
+								);
+							}
+							Ok(true) => {
+								log_debug!(self.logger,
+									"Encountered inconsistency during transaction sync, restarting.");
+								sync_state.pending_sync = true;
+								continue;
+							}
+							Err(err) => {
+								// (Semi-)permanent failure, retry later.
+								log_error!(self.logger,
+									"Failed during transaction sync, aborting. Synced so far: {} confirmed, {} unconfirmed.",
+									num_confirmed,
+									num_unconfirmed
+								);
+								sync_state.pending_sync = true;
+								return Err(TxSyncError::from(err));
+							}
+						}
+					}
+					Err(InternalError::Inconsistency) => {
+						// Immediately restart syncing when we encounter any inconsistencies.
+						log_debug!(self.logger,
+							"Encountered inconsistency during transaction sync, restarting.");
+						sync_state.pending_sync = true;
+						continue;
+					}
+					Err(err) => {
+						// (Semi-)permanent failure, retry later.
+						log_error!(self.logger,
+							"Failed during transaction sync, aborting. Synced so far: {} confirmed, {} unconfirmed.",
+							num_confirmed,
+							num_unconfirmed
+						);
+						sync_state.pending_sync = true;
+						return Err(TxSyncError::from(err));
+					}
+				}
+				sync_state.last_sync_hash = Some(tip_header.block_hash());
+				sync_state.pending_sync = false;
+			}
+		}
+		#[cfg(feature = "time")]
+		log_debug!(self.logger,
+			"Finished transaction sync at tip {} in {}ms: {} confirmed, {} unconfirmed.",
+			tip_header.block_hash(), start_time.elapsed().as_millis(), num_confirmed,
+			num_unconfirmed);
+		#[cfg(not(feature = "time"))]
+		log_debug!(self.logger,
+			"Finished transaction sync at tip {}: {} confirmed, {} unconfirmed.",
+			tip_header.block_hash(), num_confirmed, num_unconfirmed);
+		Ok(())
+	}
+
+	/// Waits until the Electrum server notifies us of a chain tip other than the one we last
+	/// synced to, returning immediately if we haven't synced to the current tip yet.
+	///
+	/// This allows to only call [`Self::sync`] once a new block was found rather than on a timer.
+	/// Note that transactions and outputs registered via [`Filter`] in the meantime will only be
+	/// picked up on the next call to [`Self::sync`] nonetheless.
+	///
+	/// Returns an error if the connection to the server is lost.
+	///
+	/// [`Filter`]: lightning::chain::Filter
+	pub async fn wait_for_new_tip(&self) -> Result<(), TxSyncError> {
+		let connection = self.connection().await?;
+		loop {
+			// Register for notifications before checking the tip to make sure we don't miss any.
+			let tip_notified = connection.tip_notifier.notified();
+			if connection.is_closed() {
+				log_error!(self.logger, "Lost connection to electrum server '{}'.", self.server_url);
+				return Err(TxSyncError::Failed);
+			}
+			let tip_hash = connection.tip().0.block_hash();
+			if Some(tip_hash) != self.sync_state.lock().await.last_sync_hash {
+				return Ok(());
+			}
+			tip_notified.await;
+		}
+	}
+
+	/// Returns the current connection to the server, reconnecting if it was lost.
+	async fn connection(&self) -> Result<Arc<ElectrumConnection>, InternalError> {
+		let mut connection_lock = self.connection.lock().await;
+		if let Some(connection) = &*connection_lock {
+			if !connection.is_closed() {
+				return Ok(Arc::clone(connection));
+			}
+		}
+
+		log_debug!(self.logger, "Reconnecting to electrum server '{}'.", self.server_url);
+		let connection = Arc::new(ElectrumConnection::connect(&self.server_url).await.map_err(|e| {
+			log_error!(self.logger, "Failed to connect to electrum server '{}': {}", self.server_url, e);
+			InternalError::Failed
+		})?);
+		*connection_lock = Some(Arc::clone(&connection));
+		Ok(connection)
+	}
+
+	async fn check_update_tip(
+		connection: &ElectrumConnection, cur_tip_header: &mut Header, cur_tip_height: &mut u32,
+	) -> Result<bool, InternalError> {
+		// Any header notifications the server sent before responding to our ping have been
+		// processed once we get the response, so we're up-to-date with its view of the chain.
+		connection.request("server.ping", vec![]).await?;
+		let (check_tip_header, check_tip_height) = connection.tip();
+
+		if check_tip_header.block_hash() != cur_tip_header.block_hash() {
+			*cur_tip_header = check_tip_header;
+			*cur_tip_height = check_tip_height;
+			Ok(true)
+		} else {
+			Ok(false)
+		}
+	}
+
+	async fn get_confirmed_transactions(
+		&self, connection: &ElectrumConnection, sync_state: &SyncState,
+	) -> Result<Vec<ConfirmedTx>, InternalError> {
+
+		// First, check the confirmation status of registered transactions as well as the
+		// status of dependent transactions of registered outputs.
+		let mut confirmed_txs = Vec::new();
+		let mut watched_script_pubkeys = Vec::with_capacity(
+			sync_state.watched_transactions.len() + sync_state.watched_outputs.len());
+		let mut watched_txs = Vec::with_capacity(sync_state.watched_transactions.len());
+
+		let watched_txids: Vec<Txid> = sync_state.watched_transactions.iter().cloned().collect();
+		let tx_results = connection.batch_transaction_get(&watched_txids).await.map_err(|e| {
+			log_error!(self.logger, "Failed to look up transactions: {}.", e);
+			InternalError::Failed
+		})?;
+		for (txid, tx_res) in watched_txids.iter().zip(tx_results) {
+			match tx_res {
+				Ok(tx) => {
+					if let Some(tx_out) = tx.output.first() {
+						// We watch an arbitrary output of the transaction of interest in order to
+						// retrieve the associated script history, before narrowing down our search
+						// through `filter`ing by `txid` below.
+						watched_script_pubkeys.push(tx_out.script_pubkey.clone());
+						watched_txs.push((txid, tx));
+					} else {
+						debug_assert!(false, "Failed due to retrieving invalid tx data.");
+						log_error!(self.logger, "Failed due to retrieving invalid tx data.");
+						return Err(InternalError::Failed);
+					}
+				}
+				Err(ElectrumError::Protocol(_)) => {
+					// We couldn't find the tx, do nothing.
+				}
+				Err(e) => {
+					log_error!(self.logger, "Failed to look up transaction {}: {}.", txid, e);
+					return Err(InternalError::Failed);
+				}
+			}
+		}
+
+		let num_tx_lookups = watched_script_pubkeys.len();
+		debug_assert_eq!(num_tx_lookups, watched_txs.len());
+
+		for output in sync_state.watched_outputs.values() {
+			watched_script_pubkeys.push(output.script_pubkey.clone());
+		}
+
+		let num_output_spend_lookups = watched_script_pubkeys.len() - num_tx_lookups;
+		debug_assert_eq!(num_output_spend_lookups, sync_state.watched_outputs.len());
+
+		let results = connection.batch_script_get_history(&watched_script_pubkeys).await.map_err(|e| {
+			log_error!(self.logger, "Failed to look up script histories: {}.", e);
+			InternalError::Failed
+		})?;
+		let (tx_results, output_results) = results.split_at(num_tx_lookups);
+		debug_assert_eq!(num_output_spend_lookups, output_results.len());
+
+		for (i, script_history) in tx_results.iter().enumerate() {
+			let (txid, tx) = &watched_txs[i];
+			let mut filtered_history = script_history.iter().filter(|h| h.tx_hash == **txid);
+			if let Some(history) = filtered_history.next()
+			{
+				let prob_conf_height = history.height as u32;
+				let confirmed_tx = self.get_confirmed_tx(connection, tx, prob_conf_height).await?;
+				confirmed_txs.push(confirmed_tx);
+			}
+			debug_assert!(filtered_history.next().is_none());
+		}
+
+		for (watched_output, script_history) in sync_state.watched_outputs.values()
+			.zip(output_results)
+		{
+			let possible_output_spends: Vec<&HistoryEntry> =
+				script_history.iter().filter(|h| h.height > 0).collect();
+			let possible_spend_txids: Vec<Txid> =
+				possible_output_spends.iter().map(|h| h.tx_hash).collect();
+			let spend_tx_results = connection.batch_transaction_get(&possible_spend_txids).await
+				.map_err(|e| {
+					log_error!(self.logger, "Failed to look up transactions: {}.", e);
+					InternalError::Failed
+				})?;
+
+			for (possible_output_spend, tx_res) in possible_output_spends.iter().zip(spend_tx_results) {
+				let txid = possible_output_spend.tx_hash;
+				match tx_res {
+					Ok(tx) => {
+						let watched_outpoint = watched_output.outpoint.into_bitcoin_outpoint();
+						if !tx.input.iter().any(|txin| txin.previous_output == watched_outpoint) {
+							continue;
+						}
+
+						let prob_conf_height = possible_output_spend.height as u32;
+						let confirmed_tx = self.get_confirmed_tx(connection, &tx, prob_conf_height).await?;
+						confirmed_txs.push(confirmed_tx);
+					}
+					Err(e) => {
+						log_trace!(self.logger,
+							"Inconsistency: Tx {} was unconfirmed during syncing: {}",
+							txid, e);
+						return Err(InternalError::Inconsistency);
+					}
+				}
+			}
+		}
+
+		// Sort all confirmed transactions first by block height, then by in-block
+		// position, and finally feed them to the interface in order.
+		confirmed_txs.sort_unstable_by(|tx1, tx2| {
+			tx1.block_height.cmp(&tx2.block_height).then_with(|| tx1.pos.cmp(&tx2.pos))
+		});
+
+		Ok(confirmed_txs)
+	}
+
+	async fn get_unconfirmed_transactions(
+		&self, connection: &ElectrumConnection, confirmables: &Vec<&(dyn Confirm + Sync + Send)>,
+	) -> Result<Vec<Txid>, InternalError> {
+		// Query the interface for relevant txids and check whether the relevant blocks are still
+		// in the best chain, mark them unconfirmed otherwise
+		let relevant_txids = confirmables
+			.iter()
+			.flat_map(|c| c.get_relevant_txids())
+			.collect::<HashSet<(Txid, u32, Option<BlockHash>)>>();
+
+		let mut unconfirmed_txs = Vec::new();
+
+		for (txid, conf_height, block_hash_opt) in relevant_txids {
+			if let Some(block_hash) = block_hash_opt {
+				let block_header = connection.block_header(conf_height).await?;
+				if block_header.block_hash() == block_hash {
+					// Skip if the tx is still confirmed in the block in question.
+					continue;
+				}
+
+				unconfirmed_txs.push(txid);
+			} else {
+				log_error!(self.logger,
+					"Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
+				panic!("Untracked confirmation of funding transaction. Please ensure none of your channels had been created with LDK prior to version 0.0.113!");
+			}
+		}
+		Ok(unconfirmed_txs)
+	}
+
+	async fn get_confirmed_tx(&self, connection: &ElectrumConnection, tx: &Transaction,
+		prob_conf_height: u32) -> Result<ConfirmedTx, InternalError>
+	{
+		let txid = tx.txid();
+		match connection.transaction_get_merkle(&txid, prob_conf_height).await {
+			Ok(merkle_res) => {
+				debug_assert_eq!(prob_conf_height, merkle_res.block_height);
+				match connection.block_header(prob_conf_height).await {
+					Ok(block_header) => {
+						let pos = merkle_res.pos;
+						if !validate_electrum_merkle_proof(&txid, &block_header.merkle_root,
+							merkle_res.pos, &merkle_res.merkle)
+						{
+							log_trace!(self.logger,
+								"Inconsistency: Block {} was unconfirmed during syncing.",
+								block_header.block_hash());
+							return Err(InternalError::Inconsistency);
+						}
+						let confirmed_tx = ConfirmedTx {
+							tx: tx.clone(),
+							block_header, block_height: prob_conf_height,
+							pos,
+						};
+						Ok(confirmed_tx)
+					}
+					Err(e) => {
+						log_error!(self.logger,
+							"Failed to retrieve block header for height {}: {}.",
+							prob_conf_height, e);
+						Err(InternalError::Failed)
+					}
+				}
+			}
+			Err(e) => {
+				log_trace!(self.logger,
+					"Inconsistency: Tx {} was unconfirmed during syncing: {}",
+					txid, e);
+				Err(InternalError::Inconsistency)
+			}
+		}
+	}
+}
+
+impl<L: Deref> Filter for AsyncElectrumSyncClient<L>
+where
+	L::Target: Logger,
+{
+	fn register_tx(&self, txid: &Txid, _script_pubkey: &Script) {
+		let mut locked_queue = self.queue.lock().unwrap();
+		locked_queue.transactions.insert(*txid);
+	}
+
+	fn register_output(&self, output: WatchedOutput) {
+		let mut locked_queue = self.queue.lock().unwrap();
+		locked_queue.outputs.insert(output.outpoint.into_bitcoin_outpoint(), output);
+	}
+}
+
+impl<L: Deref + Send + Sync> AsyncTransactionSync for AsyncElectrumSyncClient<L>
+where
+	L::Target: Logger,
+{
+	fn sync<'a>(&'a self, confirmables: Vec<&'a (dyn Confirm + Sync + Send)>) -> AsyncTransactionSyncResult<'a> {
+		Box::pin(async move { AsyncElectrumSyncClient::sync(self, confirmables).await })
+	}
+}
+
+/// An error encountered while talking to the Electrum server.
+#[derive(Debug)]
+pub(crate) enum ElectrumError {
+	/// The connection to the server failed or was closed.
+	Io(std::io::Error),
+	/// The server responded to a request with an error, e.g., as it doesn't know the requested
+	/// transaction.
+	Protocol(Value),
+	/// The server's response could not be parsed.
+	InvalidResponse,
+}
+
+impl fmt::Display for ElectrumError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "I/O error: {}", e),
+			Self::Protocol(e) => write!(f, "Electrum server error: {}", e),
+			Self::InvalidResponse => write!(f, "Invalid response from Electrum server"),
+		}
+	}
+}
+
+impl std::error::Error for ElectrumError {}
+
+impl From<std::io::Error> for ElectrumError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// An entry of a script's history as returned by `blockchain.scripthash.get_history`.
+struct HistoryEntry {
+	tx_hash: Txid,
+	/// The confirmation height, or a value `<= 0` if the transaction is unconfirmed.
+	height: i32,
+}
+
+/// The merkle proof of a transaction's inclusion in a block as returned by
+/// `blockchain.transaction.get_merkle`.
+struct MerkleProof {
+	block_height: u32,
+	pos: usize,
+	merkle: Vec<[u8; 32]>,
+}
+
+/// The state shared between an [`ElectrumConnection`] and the task reading from it.
+struct ConnectionState {
+	pending_requests: HashMap<u64, oneshot::Sender<Result<Value, ElectrumError>>>,
+	/// The latest chain tip, only `None` until we subscribed to header notifications.
+	tip: Option<(Header, u32)>,
+	closed: bool,
+}
+
+/// A bidirectional byte stream to an Electrum server, either plaintext or TLS-encrypted.
+trait ElectrumStream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> ElectrumStream for T {}
+
+/// A connection to an Electrum server, subscribed to block header notifications.
+///
+/// Responses and notifications are read by a background task, allowing any number of requests to
+/// be in flight at once.
+struct ElectrumConnection {
+	writer: tokio::sync::Mutex<WriteHalf<Box<dyn ElectrumStream>>>,
+	state: Arc<Mutex<ConnectionState>>,
+	tip_notifier: Arc<Notify>,
+	next_request_id: AtomicU64,
+	reader_task: tokio::task::JoinHandle<()>,
+}
+
+impl ElectrumConnection {
+	async fn connect(server_url: &str) -> Result<Self, ElectrumError> {
+		let stream: Box<dyn ElectrumStream> = if let Some(address) = server_url.strip_prefix("ssl://") {
+			Box::new(Self::connect_tls(address).await?)
+		} else {
+			let address = server_url.strip_prefix("tcp://").unwrap_or(server_url);
+			if address.contains("://") {
+				return Err(ElectrumError::Io(std::io::Error::new(std::io::ErrorKind::Unsupported,
+					"unsupported URL scheme, expected tcp:// or ssl://")));
+			}
+			Box::new(TcpStream::connect(address).await?)
+		};
+		let (reader, writer) = tokio::io::split(stream);
+
+		let state = Arc::new(Mutex::new(ConnectionState {
+			pending_requests: HashMap::new(),
+			tip: None,
+			closed: false,
+		}));
+		let tip_notifier = Arc::new(Notify::new());
+		let reader_task = tokio::spawn(
+			Self::read_messages(reader, Arc::clone(&state), Arc::clone(&tip_notifier)));
+		let connection = Self {
+			writer: tokio::sync::Mutex::new(writer),
+			state,
+			tip_notifier,
+			next_request_id: AtomicU64::new(0),
+			reader_task,
+		};
+
+		connection.request("server.version", vec![json!("LDK"), json!(PROTOCOL_VERSION)]).await?;
+		let tip = connection.request("blockchain.headers.subscribe", vec![]).await?;
+		connection.state.lock().unwrap().tip = Some(parse_header_notification(&tip)?);
+		Ok(connection)
+	}
+
+	#[cfg(feature = "electrum-async-tls")]
+	async fn connect_tls(address: &str) -> Result<tokio_rustls::client::TlsStream<TcpStream>, ElectrumError> {
+		use tokio_rustls::rustls;
+
+		let host = address.rsplit_once(':').map_or(address, |(host, _port)| host);
+		let server_name = rustls::ServerName::try_from(host).map_err(|_| {
+			ElectrumError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid server name"))
+		})?;
+
+		let mut root_store = rustls::RootCertStore::empty();
+		root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+			rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+				anchor.subject, anchor.spki, anchor.name_constraints)
+		}));
+		let config = rustls::ClientConfig::builder()
+			.with_safe_defaults()
+			.with_root_certificates(root_store)
+			.with_no_client_auth();
+		let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+
+		let stream = TcpStream::connect(address).await?;
+		Ok(connector.connect(server_name, stream).await?)
+	}
+
+	#[cfg(not(feature = "electrum-async-tls"))]
+	async fn connect_tls(_address: &str) -> Result<TcpStream, ElectrumError> {
+		Err(ElectrumError::Io(std::io::Error::new(std::io::ErrorKind::Unsupported,
+			"TLS connections require the electrum-async-tls feature")))
+	}
+
+	/// Reads responses and notifications from the server until the connection is closed.
+	///
+	/// The connection is closed if the server sends a message longer than [`MAX_MESSAGE_LENGTH`].
+	async fn read_messages(
+		reader: ReadHalf<Box<dyn ElectrumStream>>, state: Arc<Mutex<ConnectionState>>,
+		tip_notifier: Arc<Notify>,
+	) {
+		let mut reader = BufReader::new(reader);
+		let mut line = Vec::new();
+		loop {
+			line.clear();
+			let mut limited_reader = (&mut reader).take(MAX_MESSAGE_LENGTH + 1);
+			match limited_reader.read_until(b'\n', &mut line).await {
+				Ok(0) | Err(_) => break,
+				Ok(_) => {},
+			}
+			// Without a trailing newline, the message either exceeded our limit or the connection
+			// was closed in the middle of it.
+			if line.last() != Some(&b'\n') {
+				break;
+			}
+
+			let message: Value = match serde_json::from_slice(&line) {
+				Ok(message) => message,
+				Err(_) => break,
+			};
+
+			if let Some(id) = message["id"].as_u64() {
+				let response = match message.get("error") {
+					Some(error) if !error.is_null() => Err(ElectrumError::Protocol(error.clone())),
+					_ => Ok(message["result"].clone()),
+				};
+				if let Some(sender) = state.lock().unwrap().pending_requests.remove(&id) {
+					let _ = sender.send(response);
+				}
+			} else if message["method"] == "blockchain.headers.subscribe" {
+				match parse_header_notification(&message["params"][0]) {
+					Ok(tip) => state.lock().unwrap().tip = Some(tip),
+					Err(_) => break,
+				}
+				tip_notifier.notify_waiters();
+			}
+		}
+
+		// Fail any requests still waiting on a response, dropping their senders.
+		let mut state = state.lock().unwrap();
+		state.closed = true;
+		state.pending_requests.clear();
+		tip_notifier.notify_waiters();
+	}
+
+	fn is_closed(&self) -> bool {
+		self.state.lock().unwrap().closed
+	}
+
+	/// Returns the latest chain tip the server notified us of.
+	fn tip(&self) -> (Header, u32) {
+		self.state.lock().unwrap().tip.expect("We subscribe to header notifications when connecting")
+	}
+
+	/// Sends all `requests` at once and waits for their responses, which are returned in order.
+	///
+	/// The outer `Result` fails if the connection to the server failed, the inner ones if the
+	/// server returned an error for the respective request.
+	async fn batch_request(&self, requests: Vec<(&str, Vec<Value>)>)
+		-> Result<Vec<Result<Value, ElectrumError>>, ElectrumError>
+	{
+		let mut message = Vec::new();
+		let mut ids = Vec::with_capacity(requests.len());
+		let mut receivers = Vec::with_capacity(requests.len());
+		{
+			let mut state = self.state.lock().unwrap();
+			if state.closed {
+				return Err(ElectrumError::Io(std::io::ErrorKind::NotConnected.into()));
+			}
+			for (method, params) in requests {
+				let id = self.next_request_id.fetch_add(1, Ordering::AcqRel);
+				let (sender, receiver) = oneshot::channel();
+				state.pending_requests.insert(id, sender);
+				ids.push(id);
+				receivers.push(receiver);
+
+				let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+				message.extend_from_slice(request.to_string().as_bytes());
+				message.push(b'\n');
+			}
+		}
+		if receivers.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let write_res = async {
+			let mut writer = self.writer.lock().await;
+			writer.write_all(&message).await?;
+			writer.flush().await
+		}.await;
+		if let Err(e) = write_res {
+			self.remove_pending_requests(&ids);
+			return Err(e.into());
+		}
+
+		let responses = async {
+			let mut responses = Vec::with_capacity(receivers.len());
+			for receiver in receivers {
+				// The sender is dropped without a response if the connection is closed.
+				responses.push(receiver.await
+					.map_err(|_| ElectrumError::Io(std::io::ErrorKind::ConnectionAborted.into()))?);
+			}
+			Ok(responses)
+		};
+		match tokio::time::timeout(REQUEST_TIMEOUT, responses).await {
+			Ok(responses) => responses,
+			Err(_) => {
+				// Don't keep the senders around in case the server never responds.
+				self.remove_pending_requests(&ids);
+				Err(ElectrumError::Io(std::io::ErrorKind::TimedOut.into()))
+			},
+		}
+	}
+
+	fn remove_pending_requests(&self, ids: &[u64]) {
+		let mut state = self.state.lock().unwrap();
+		for id in ids {
+			state.pending_requests.remove(id);
+		}
+	}
+
+	async fn request(&self, method: &str, params: Vec<Value>) -> Result<Value, ElectrumError> {
+		let mut responses = self.batch_request(vec![(method, params)]).await?;
+		responses.pop().ok_or(ElectrumError::InvalidResponse)?
+	}
+
+	async fn block_header(&self, height: u32) -> Result<Header, ElectrumError> {
+		let header_hex = self.request("blockchain.block.header", vec![json!(height)]).await?;
+		deserialize_hex(&header_hex)
+	}
+
+	async fn batch_transaction_get(&self, txids: &[Txid])
+		-> Result<Vec<Result<Transaction, ElectrumError>>, ElectrumError>
+	{
+		let requests = txids.iter()
+			.map(|txid| ("blockchain.transaction.get", vec![json!(txid.to_string())]))
+			.collect();
+		let responses = self.batch_request(requests).await?;
+		Ok(responses.into_iter().map(|res| res.and_then(|tx_hex| deserialize_hex(&tx_hex))).collect())
+	}
+
+	async fn batch_script_get_history(&self, scripts: &[ScriptBuf])
+		-> Result<Vec<Vec<HistoryEntry>>, ElectrumError>
+	{
+		let requests = scripts.iter()
+			.map(|script| ("blockchain.scripthash.get_history", vec![json!(script_hash(script))]))
+			.collect();
+		let mut histories = Vec::with_capacity(scripts.len());
+		for response in self.batch_request(requests).await? {
+			let history = response?.as_array().ok_or(ElectrumError::InvalidResponse)?
+				.iter()
+				.map(|entry| {
+					let tx_hash = entry["tx_hash"].as_str()
+						.and_then(|txid| txid.parse().ok())
+						.ok_or(ElectrumError::InvalidResponse)?;
+					let height = entry["height"].as_i64()
+						.and_then(|height| i32::try_from(height).ok())
+						.ok_or(ElectrumError::InvalidResponse)?;
+					Ok(HistoryEntry { tx_hash, height })
+				})
+				.collect::<Result<Vec<_>, ElectrumError>>()?;
+			histories.push(history);
+		}
+		Ok(histories)
+	}
+
+	async fn transaction_get_merkle(&self, txid: &Txid, height: u32) -> Result<MerkleProof, ElectrumError> {
+		let res = self.request("blockchain.transaction.get_merkle",
+			vec![json!(txid.to_string()), json!(height)]).await?;
+		let block_height = res["block_height"].as_u64()
+			.and_then(|height| u32::try_from(height).ok())
+			.ok_or(ElectrumError::InvalidResponse)?;
+		let pos = res["pos"].as_u64().ok_or(ElectrumError::InvalidResponse)? as usize;
+		let merkle = res["merkle"].as_array().ok_or(ElectrumError::InvalidResponse)?
+			.iter()
+			.map(|hash| hash.as_str()
+				.and_then(|hash| <[u8; 32]>::from_hex(hash).ok())
+				.ok_or(ElectrumError::InvalidResponse))
+			.collect::<Result<Vec<_>, ElectrumError>>()?;
+		Ok(MerkleProof { block_height, pos, merkle })
+	}
+}
+
+impl Drop for ElectrumConnection {
+	fn drop(&mut self) {
+		self.reader_task.abort();
+	}
+}
+
+/// Returns the hash of the given script as used by the Electrum protocol to identify it, i.e., its
+/// SHA256 hash in reversed byte order, hex-encoded.
+fn script_hash(script: &Script) -> String {
+	let hash = sha256::Hash::hash(script.as_bytes());
+	hash.to_byte_array().iter().rev().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn deserialize_hex<T: encode::Decodable>(value: &Value) -> Result<T, ElectrumError> {
+	value.as_str()
+		.and_then(|hex| Vec::<u8>::from_hex(hex).ok())
+		.and_then(|bytes| encode::deserialize(&bytes).ok())
+		.ok_or(ElectrumError::InvalidResponse)
+}
+
+fn parse_header_notification(notification: &Value) -> Result<(Header, u32), ElectrumError> {
+	let header = deserialize_hex(&notification["hex"])?;
+	let height = notification["height"].as_u64()
+		.and_then(|height| u32::try_from(height).ok())
+		.ok_or(ElectrumError::InvalidResponse)?;
+	Ok((header, height))
+}
@@ -1,21 +1,6 @@
 use std::fmt;
 
-#[derive(Debug)]
-/// An error that possibly needs to be handled by the user.
-pub enum TxSyncError {
-	/// A transaction sync failed and needs to be retried eventually.
-	Failed,
-}
-
-impl std::error::Error for TxSyncError {}
-
-impl fmt::Display for TxSyncError {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		match *self {
-			Self::Failed => write!(f, "Failed to conduct transaction sync."),
-		}
-	}
-}
+pub use lightning::chain::TxSyncError;
 
 #[derive(Debug)]
 pub(crate) enum InternalError {
@@ -44,13 +29,6 @@ impl From<InternalError> for TxSyncError {
 	}
 }
 
-#[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
-impl From<esplora_client::Error> for TxSyncError {
-	fn from(_e: esplora_client::Error) -> Self {
-		Self::Failed
-	}
-}
-
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
 impl From<esplora_client::Error> for InternalError {
 	fn from(_e: esplora_client::Error) -> Self {
@@ -65,9 +43,17 @@ impl From<electrum_client::Error> for InternalError {
 	}
 }
 
-#[cfg(feature = "electrum")]
-impl From<electrum_client::Error> for TxSyncError {
-	fn from(_e: electrum_client::Error) -> Self {
+#[cfg(feature = "electrum-async")]
+impl From<crate::electrum_async::ElectrumError> for InternalError {
+	fn from(_e: crate::electrum_async::ElectrumError) -> Self {
+		Self::Failed
+	}
+}
+
+
+#[cfg(feature = "electrum-async")]
+impl From<crate::electrum_async::ElectrumError> for TxSyncError {
+	fn from(_e: crate::electrum_async::ElectrumError) -> Self {
 		Self::Failed
 	}
 }
@@ -5,6 +5,10 @@ use lightning::util::logger::Logger;
 use lightning::{log_error, log_debug, log_trace};
 use lightning::chain::WatchedOutput;
 use lightning::chain::{Confirm, Filter};
+#[cfg(not(feature = "async-interface"))]
+use lightning::chain::TransactionSync;
+#[cfg(feature = "async-interface")]
+use lightning::chain::{AsyncTransactionSync, AsyncTransactionSyncResult};
 
 use bitcoin::{BlockHash, Script, Txid};
 
@@ -95,7 +99,7 @@ where
 		let mut num_confirmed = 0;
 		let mut num_unconfirmed = 0;
 
-		let mut tip_hash = maybe_await!(self.client.get_tip_hash())?;
+		let mut tip_hash = maybe_await!(self.client.get_tip_hash()).map_err(InternalError::from)?;
 
 		loop {
 			let pending_registrations = self.queue.lock().unwrap().process_queues(&mut sync_state);
@@ -407,3 +411,23 @@ where
 		locked_queue.outputs.insert(output.outpoint.into_bitcoin_outpoint(), output);
 	}
 }
+
+#[cfg(not(feature = "async-interface"))]
+impl<L: Deref> TransactionSync for EsploraSyncClient<L>
+where
+	L::Target: Logger,
+{
+	fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+		EsploraSyncClient::sync(self, confirmables)
+	}
+}
+
+#[cfg(feature = "async-interface")]
+impl<L: Deref + Send + Sync> AsyncTransactionSync for EsploraSyncClient<L>
+where
+	L::Target: Logger,
+{
+	fn sync<'a>(&'a self, confirmables: Vec<&'a (dyn Confirm + Sync + Send)>) -> AsyncTransactionSyncResult<'a> {
+		Box::pin(async move { EsploraSyncClient::sync(self, confirmables).await })
+	}
+}
@@ -0,0 +1,133 @@
+use lightning::util::logger::Logger;
+use lightning::{log_error, log_info};
+use lightning::chain::{AsyncTransactionSync, AsyncTransactionSyncResult, TransactionSync, TxSyncError};
+use lightning::chain::WatchedOutput;
+use lightning::chain::{Confirm, Filter};
+
+use bitcoin::{Script, Txid};
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Synchronizes LDK via one of multiple chain backends, failing over to the next backend whenever
+/// syncing via the current one fails.
+///
+/// Any of the provided clients may be used as backends, either via the blocking
+/// [`TransactionSync`] or the async [`AsyncTransactionSync`] interface, which are in turn
+/// implemented by [`FailoverSyncClient`]. To mix different kinds of backends, wrap them in a
+/// `Box<dyn TransactionSync + Send + Sync>` or `Box<dyn AsyncTransactionSync + Send + Sync>`.
+///
+/// As with the individual clients, the [`FailoverSyncClient`] needs to be registered with a
+/// [`ChainMonitor`] via the [`Filter`] interface, which forwards any registrations to all
+/// backends. This allows each backend to pick up syncing where a failed one left off, as it keeps
+/// track of the same transactions and outputs.
+///
+/// Backends are tried in the given order, starting with the one which last synced successfully.
+///
+/// [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
+pub struct FailoverSyncClient<TS: Deref, L: Deref>
+where
+	L::Target: Logger,
+{
+	backends: Vec<TS>,
+	active_backend: AtomicUsize,
+	logger: L,
+}
+
+impl<TS: Deref, L: Deref> FailoverSyncClient<TS, L>
+where
+	L::Target: Logger,
+{
+	/// Returns a new [`FailoverSyncClient`] object syncing via the given `backends`.
+	///
+	/// Panics if no backends are given.
+	pub fn new(backends: Vec<TS>, logger: L) -> Self {
+		assert!(!backends.is_empty(), "At least one backend is required");
+		Self { backends, active_backend: AtomicUsize::new(0), logger }
+	}
+
+	/// Returns the backend which is used for the next sync, i.e., the one which last synced
+	/// successfully.
+	pub fn active_backend(&self) -> &TS::Target {
+		&self.backends[self.active_backend.load(Ordering::Acquire)]
+	}
+
+	/// Returns the order in which backends are tried, starting with the active one.
+	fn backend_order(&self) -> impl Iterator<Item = usize> {
+		let num_backends = self.backends.len();
+		let active_backend = self.active_backend.load(Ordering::Acquire);
+		(0..num_backends).map(move |i| (active_backend + i) % num_backends)
+	}
+
+	fn backend_succeeded(&self, backend_idx: usize) {
+		let previous_backend = self.active_backend.swap(backend_idx, Ordering::AcqRel);
+		if previous_backend != backend_idx {
+			log_info!(self.logger, "Failed over from chain backend {} to backend {}.",
+				previous_backend, backend_idx);
+		}
+	}
+}
+
+impl<TS: Deref, L: Deref> TransactionSync for FailoverSyncClient<TS, L>
+where
+	TS::Target: TransactionSync,
+	L::Target: Logger,
+{
+	fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError> {
+		for backend_idx in self.backend_order() {
+			match self.backends[backend_idx].sync(confirmables.clone()) {
+				Ok(()) => {
+					self.backend_succeeded(backend_idx);
+					return Ok(());
+				},
+				Err(e) => {
+					log_error!(self.logger, "Failed to sync via chain backend {}: {}", backend_idx, e);
+				},
+			}
+		}
+		log_error!(self.logger, "Failed to sync via any of the {} chain backends.", self.backends.len());
+		Err(TxSyncError::Failed)
+	}
+}
+
+impl<TS: Deref + Send + Sync, L: Deref + Send + Sync> AsyncTransactionSync for FailoverSyncClient<TS, L>
+where
+	TS::Target: AsyncTransactionSync,
+	L::Target: Logger,
+{
+	fn sync<'a>(&'a self, confirmables: Vec<&'a (dyn Confirm + Sync + Send)>) -> AsyncTransactionSyncResult<'a> {
+		Box::pin(async move {
+			for backend_idx in self.backend_order() {
+				match self.backends[backend_idx].sync(confirmables.clone()).await {
+					Ok(()) => {
+						self.backend_succeeded(backend_idx);
+						return Ok(());
+					},
+					Err(e) => {
+						log_error!(self.logger, "Failed to sync via chain backend {}: {}", backend_idx, e);
+					},
+				}
+			}
+			log_error!(self.logger, "Failed to sync via any of the {} chain backends.", self.backends.len());
+			Err(TxSyncError::Failed)
+		})
+	}
+}
+
+impl<TS: Deref, L: Deref> Filter for FailoverSyncClient<TS, L>
+where
+	TS::Target: Filter,
+	L::Target: Logger,
+{
+	fn register_tx(&self, txid: &Txid, script_pubkey: &Script) {
+		for backend in &self.backends {
+			backend.register_tx(txid, script_pubkey);
+		}
+	}
+
+	fn register_output(&self, output: WatchedOutput) {
+		for backend in &self.backends {
+			backend.register_output(output.clone());
+		}
+	}
+}
@@ -5,11 +5,20 @@
 //! implementations to be synchronized, i.e., usually instances of [`ChannelManager`] and
 //! [`ChainMonitor`].
 //!
+//! All clients implement either the [`TransactionSync`] or [`AsyncTransactionSync`] interface,
+//! allowing them to be used interchangeably, e.g., to have `lightning-background-processor` drive
+//! syncing. Multiple clients may be combined into a [`FailoverSyncClient`], which falls back to the
+//! next client whenever syncing via the current one fails.
+//!
 //! ## Features and Backend Support
 //!
 //!- `esplora-blocking` enables syncing against an Esplora backend based on a blocking client.
 //!- `esplora-async` enables syncing against an Esplora backend based on an async client.
 //!- `esplora-async-https` enables the async Esplora client with support for HTTPS.
+//!- `electrum` enables syncing against an Electrum backend based on a blocking client.
+//!- `electrum-async` enables syncing against an Electrum backend based on an async client running
+//!  on Tokio.
+//!- `electrum-async-tls` enables the async Electrum client with support for TLS connections.
 //!
 //! ## Version Compatibility
 //!
@@ -30,6 +39,7 @@
 //! 	Arc::clone(&some_logger),
 //! 	Arc::clone(&some_fee_estimator),
 //! 	Arc::clone(&some_persister),
+//! 	Arc::clone(&some_metrics_recorder),
 //! ));
 //!
 //! let channel_manager = Arc::new(ChannelManager::new(
@@ -41,6 +51,7 @@
 //! 	Arc::clone(&some_entropy_source),
 //! 	Arc::clone(&some_node_signer),
 //! 	Arc::clone(&some_signer_provider),
+//! 	Arc::clone(&some_currency_conversion),
 //! 	user_config,
 //! 	chain_params,
 //! ));
@@ -55,6 +66,8 @@
 //!
 //! [`Confirm`]: lightning::chain::Confirm
 //! [`Filter`]: lightning::chain::Filter
+//! [`TransactionSync`]: lightning::chain::TransactionSync
+//! [`AsyncTransactionSync`]: lightning::chain::AsyncTransactionSync
 //! [`ChainMonitor`]: lightning::chain::chainmonitor::ChainMonitor
 //! [`ChannelManager`]: lightning::ln::channelmanager::ChannelManager
 
@@ -76,14 +89,22 @@ mod esplora;
 #[cfg(any(feature = "electrum"))]
 mod electrum;
 
-#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum"))]
+#[cfg(feature = "electrum-async")]
+mod electrum_async;
+
+mod failover;
+
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum", feature = "electrum-async"))]
 mod common;
-#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum"))]
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum", feature = "electrum-async"))]
 mod error;
-#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum"))]
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum", feature = "electrum-async"))]
 pub use error::TxSyncError;
 
+pub use failover::FailoverSyncClient;
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
 pub use esplora::EsploraSyncClient;
 #[cfg(feature = "electrum")]
 pub use electrum::ElectrumSyncClient;
+#[cfg(feature = "electrum-async")]
+pub use electrum_async::AsyncElectrumSyncClient;
@@ -1,9 +1,11 @@
-#![cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum"))]
+#![cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum", feature = "electrum-async"))]
 
 #[cfg(any(feature = "esplora-blocking", feature = "esplora-async"))]
 use lightning_transaction_sync::EsploraSyncClient;
 #[cfg(feature = "electrum")]
 use lightning_transaction_sync::ElectrumSyncClient;
+#[cfg(feature = "electrum-async")]
+use lightning_transaction_sync::AsyncElectrumSyncClient;
 use lightning::chain::{Confirm, Filter, WatchedOutput};
 use lightning::chain::transaction::{OutPoint, TransactionData};
 use lightning::util::test_utils::TestLogger;
@@ -15,6 +17,7 @@ use bitcoin::blockdata::constants::genesis_block;
 use bitcoin::network::constants::Network;
 use electrsd::bitcoind::bitcoincore_rpc::bitcoincore_rpc_json::AddressType;
 use bitcoind::bitcoincore_rpc::RpcApi;
+#[cfg(any(feature = "esplora-blocking", feature = "esplora-async", feature = "electrum"))]
 use bdk_macros::maybe_await;
 
 use std::env;
@@ -153,12 +156,22 @@ impl Confirm for TestConfirmable {
 	}
 }
 
+// The async Electrum client doesn't depend on the `async-interface` feature, so we can't rely on
+// `maybe_await` to await its syncs.
+#[cfg(feature = "electrum-async")]
+macro_rules! await_sync {
+	($sync: expr) => { $sync.await };
+}
+
 macro_rules! test_syncing {
-	($tx_sync: expr, $confirmable: expr, $bitcoind: expr, $electrsd: expr) => {{
+	($tx_sync: expr, $confirmable: expr, $bitcoind: expr, $electrsd: expr) => {
+		test_syncing!($tx_sync, $confirmable, $bitcoind, $electrsd, maybe_await)
+	};
+	($tx_sync: expr, $confirmable: expr, $bitcoind: expr, $electrsd: expr, $await_sync: ident) => {{
 		// Check we pick up on new best blocks
 		assert_eq!($confirmable.best_block.lock().unwrap().1, 0);
 
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 		assert_eq!($confirmable.best_block.lock().unwrap().1, 102);
 
 		let events = std::mem::take(&mut *$confirmable.events.lock().unwrap());
@@ -173,7 +186,7 @@ macro_rules! test_syncing {
 		None, None, None, None, None).unwrap();
 		$tx_sync.register_tx(&txid, &new_address.payload.script_pubkey());
 
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 
 		let events = std::mem::take(&mut *$confirmable.events.lock().unwrap());
 		assert_eq!(events.len(), 0);
@@ -181,7 +194,7 @@ macro_rules! test_syncing {
 		assert!($confirmable.unconfirmed_txs.lock().unwrap().is_empty());
 
 		generate_blocks_and_wait(&$bitcoind, &$electrsd, 1);
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 
 		let events = std::mem::take(&mut *$confirmable.events.lock().unwrap());
 		assert_eq!(events.len(), 2);
@@ -203,7 +216,7 @@ macro_rules! test_syncing {
 		};
 
 		$tx_sync.register_output(output);
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 
 		let events = std::mem::take(&mut *$confirmable.events.lock().unwrap());
 		assert_eq!(events.len(), 1);
@@ -218,14 +231,14 @@ macro_rules! test_syncing {
 		// We're getting back to the previous height with a new tip, but best block shouldn't change.
 		generate_blocks_and_wait(&$bitcoind, &$electrsd, 1);
 		assert_ne!($bitcoind.client.get_best_block_hash().unwrap(), best_block_hash);
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 		let events = std::mem::take(&mut *$confirmable.events.lock().unwrap());
 		assert_eq!(events.len(), 0);
 
 		// Now we're surpassing previous height, getting new tip.
 		generate_blocks_and_wait(&$bitcoind, &$electrsd, 1);
 		assert_ne!($bitcoind.client.get_best_block_hash().unwrap(), best_block_hash);
-		maybe_await!($tx_sync.sync(vec![&$confirmable])).unwrap();
+		$await_sync!($tx_sync.sync(vec![&$confirmable])).unwrap();
 
 		// Transactions still confirmed but under new tip.
 		assert!($confirmable.confirmed_txs.lock().unwrap().contains_key(&txid));
@@ -315,3 +328,23 @@ fn test_electrum_syncs() {
 	let confirmable = TestConfirmable::new();
 	test_syncing!(tx_sync, confirmable, bitcoind, electrsd);
 }
+
+#[tokio::test]
+#[cfg(feature = "electrum-async")]
+async fn test_electrum_async_syncs() {
+	let (bitcoind, electrsd) = setup_bitcoind_and_electrsd();
+	generate_blocks_and_wait(&bitcoind, &electrsd, 101);
+	let mut logger = TestLogger::new();
+	let electrum_url = format!("tcp://{}", electrsd.electrum_url);
+	let tx_sync = AsyncElectrumSyncClient::new(electrum_url, &mut logger).await.unwrap();
+	let confirmable = TestConfirmable::new();
+
+	// We haven't synced yet, so we don't have to wait for a new tip.
+	tx_sync.wait_for_new_tip().await.unwrap();
+	test_syncing!(tx_sync, confirmable, bitcoind, electrsd, await_sync);
+
+	// Once synced, we only return once a new block was found.
+	assert!(tokio::time::timeout(Duration::from_millis(100), tx_sync.wait_for_new_tip()).await.is_err());
+	generate_blocks_and_wait(&bitcoind, &electrsd, 1);
+	tokio::time::timeout(Duration::from_secs(10), tx_sync.wait_for_new_tip()).await.unwrap().unwrap();
+}
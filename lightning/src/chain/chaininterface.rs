@@ -123,6 +123,18 @@ pub enum ConfirmationTarget {
 	///
 	/// [`ChannelManager::close_channel_with_feerate_and_script`]: crate::ln::channelmanager::ChannelManager::close_channel_with_feerate_and_script
 	ChannelCloseMinimum,
+	/// The feerate [`OutputSweeper`] will use on transactions spending
+	/// [`SpendableOutputDescriptor`]s after a channel closure.
+	///
+	/// Generally spending these outputs is safe as long as they eventually confirm, so a value
+	/// (slightly above) the mempool minimum should suffice. However, as this value will influence
+	/// how long funds will be unavailable after channel closure, [`FeeEstimator`] implementors
+	/// might want to choose a higher feerate to regain control over funds faster. If the spend
+	/// doesn't confirm, [`OutputSweeper`] will bump its feerate over time.
+	///
+	/// [`OutputSweeper`]: crate::util::sweep::OutputSweeper
+	/// [`SpendableOutputDescriptor`]: crate::sign::SpendableOutputDescriptor
+	OutputSpendingFee,
 }
 
 /// A trait which should be implemented to provide feerate information on a number of time
@@ -154,6 +166,14 @@ pub const MIN_RELAY_FEE_SAT_PER_1000_WEIGHT: u64 = 4000;
 /// <https://github.com/ElementsProject/lightning/commit/2e687b9b352c9092b5e8bd4a688916ac50b44af0>
 pub const FEERATE_FLOOR_SATS_PER_KW: u32 = 253;
 
+/// The maximum weight of a version 3 (TRUC) transaction as allowed by bitcoin network mempool
+/// policy, i.e. 10,000 vbytes.
+pub const TRUC_MAX_WEIGHT: u64 = 40_000;
+/// The maximum weight of a version 3 (TRUC) transaction spending an unconfirmed version 3 parent,
+/// as allowed by bitcoin network mempool policy, i.e. 1,000 vbytes. A TRUC parent may only have a
+/// single unconfirmed child.
+pub const TRUC_CHILD_MAX_WEIGHT: u64 = 4_000;
+
 /// Wraps a `Deref` to a `FeeEstimator` so that any fee estimations provided by it
 /// are bounded below by `FEERATE_FLOOR_SATS_PER_KW` (253 sats/KW).
 ///
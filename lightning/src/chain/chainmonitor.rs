@@ -29,19 +29,21 @@ use bitcoin::hash_types::{Txid, BlockHash};
 use crate::chain;
 use crate::chain::{ChannelMonitorUpdateStatus, Filter, WatchedOutput};
 use crate::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
-use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, Balance, MonitorEvent, TransactionOutputs, WithChannelMonitor, LATENCY_GRACE_PERIOD_BLOCKS};
+use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, ChannelMonitorUpdateStep, Balance, MonitorEvent, TransactionOutputs, WithChannelMonitor, LATENCY_GRACE_PERIOD_BLOCKS};
 use crate::chain::transaction::{OutPoint, TransactionData};
 use crate::sign::ecdsa::WriteableEcdsaChannelSigner;
 use crate::events;
 use crate::events::{Event, EventHandler};
 use crate::util::atomic_counter::AtomicCounter;
 use crate::util::logger::{Logger, WithContext};
+use crate::util::metrics::{self, LatencyTimer, MetricsRecorder};
 use crate::util::errors::APIError;
 use crate::util::wakers::{Future, Notifier};
 use crate::ln::channelmanager::ChannelDetails;
+use crate::ln::our_peer_storage::PeerStorageMonitorHolder;
 
 use crate::prelude::*;
-use crate::sync::{RwLock, RwLockReadGuard, Mutex, MutexGuard};
+use crate::sync::{Arc, RwLock, RwLockReadGuard, Mutex, MutexGuard};
 use core::iter::FromIterator;
 use core::ops::Deref;
 use core::sync::atomic::{AtomicUsize, Ordering};
@@ -194,6 +196,41 @@ pub trait Persist<ChannelSigner: WriteableEcdsaChannelSigner> {
 	///
 	/// [`Writeable::write`]: crate::util::ser::Writeable::write
 	fn update_persisted_channel(&self, channel_id: OutPoint, update: Option<&ChannelMonitorUpdate>, data: &ChannelMonitor<ChannelSigner>, update_id: MonitorUpdateId) -> ChannelMonitorUpdateStatus;
+
+	/// Returns the updates for which [`ChannelMonitorUpdateStatus::InProgress`] was previously
+	/// returned and which have since finished persisting, along with their final status.
+	///
+	/// The status of each update must be either [`ChannelMonitorUpdateStatus::Completed`] or
+	/// [`ChannelMonitorUpdateStatus::UnrecoverableError`], with the same semantics as if it had
+	/// been returned from the original call. In particular, the latter results in an immediate
+	/// panic.
+	///
+	/// This is polled by [`ChainMonitor`] whenever it releases pending monitor events, and allows
+	/// asynchronous persisters to complete updates without holding a reference to the
+	/// [`ChainMonitor`] to call [`ChainMonitor::channel_monitor_updated`] on. Implementations which
+	/// call [`ChainMonitor::channel_monitor_updated`] themselves may rely on the default, which
+	/// never returns any updates.
+	fn get_and_clear_completed_updates(&self) -> Vec<(OutPoint, MonitorUpdateId, ChannelMonitorUpdateStatus)> {
+		Vec::new()
+	}
+
+	/// Called once by [`ChainMonitor::new`] with a [`PersistCompletionNotifier`] which should be
+	/// notified whenever new updates are available via [`Self::get_and_clear_completed_updates`],
+	/// ensuring they're handed to the [`ChainMonitor`] promptly.
+	fn register_completion_notifier(&self, _notifier: PersistCompletionNotifier) {}
+}
+
+/// Wakes the [`ChainMonitor`] a [`Persist`] implementation was registered with via
+/// [`Persist::register_completion_notifier`], causing any updates returned by
+/// [`Persist::get_and_clear_completed_updates`] to be processed.
+#[derive(Clone)]
+pub struct PersistCompletionNotifier(Arc<Notifier>);
+
+impl PersistCompletionNotifier {
+	/// Wakes the [`ChainMonitor`], see the struct-level documentation for more details.
+	pub fn notify(&self) {
+		self.0.notify();
+	}
 }
 
 struct MonitorHolder<ChannelSigner: WriteableEcdsaChannelSigner> {
@@ -268,12 +305,13 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> Deref for LockedChannelMonitor<
 /// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
 /// [module-level documentation]: crate::chain::chainmonitor
 /// [`rebroadcast_pending_claims`]: Self::rebroadcast_pending_claims
-pub struct ChainMonitor<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref>
+pub struct ChainMonitor<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref, MR: Deref>
 	where C::Target: chain::Filter,
         T::Target: BroadcasterInterface,
         F::Target: FeeEstimator,
         L::Target: Logger,
         P::Target: Persist<ChannelSigner>,
+        MR::Target: MetricsRecorder,
 {
 	monitors: RwLock<HashMap<OutPoint, MonitorHolder<ChannelSigner>>>,
 	/// When we generate a [`MonitorUpdateId`] for a chain-event monitor persistence, we need a
@@ -285,21 +323,23 @@ pub struct ChainMonitor<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T:
 	logger: L,
 	fee_estimator: F,
 	persister: P,
+	metrics_recorder: MR,
 	/// "User-provided" (ie persistence-completion/-failed) [`MonitorEvent`]s. These came directly
 	/// from the user and not from a [`ChannelMonitor`].
 	pending_monitor_events: Mutex<Vec<(OutPoint, Vec<MonitorEvent>, Option<PublicKey>)>>,
 	/// The best block height seen, used as a proxy for the passage of time.
 	highest_chain_height: AtomicUsize,
 
-	event_notifier: Notifier,
+	event_notifier: Arc<Notifier>,
 }
 
-impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref> ChainMonitor<ChannelSigner, C, T, F, L, P>
+impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref, MR: Deref> ChainMonitor<ChannelSigner, C, T, F, L, P, MR>
 where C::Target: chain::Filter,
 	    T::Target: BroadcasterInterface,
 	    F::Target: FeeEstimator,
 	    L::Target: Logger,
 	    P::Target: Persist<ChannelSigner>,
+	    MR::Target: MetricsRecorder,
 {
 	/// Dispatches to per-channel monitors, which are responsible for updating their on-chain view
 	/// of a channel and reacting accordingly based on transactions in the given chain data. See
@@ -377,11 +417,15 @@ where C::Target: chain::Filter,
 			}
 
 			log_trace!(logger, "Syncing Channel Monitor for channel {}", log_funding_info!(monitor));
-			match self.persister.update_persisted_channel(*funding_outpoint, None, monitor, update_id) {
+			let persist_timer = LatencyTimer::start();
+			let persist_res = self.persister.update_persisted_channel(*funding_outpoint, None, monitor, update_id);
+			persist_timer.record(&*self.metrics_recorder, metrics::MONITOR_UPDATE_DURATION, &[("kind", "chain_sync")]);
+			match persist_res {
 				ChannelMonitorUpdateStatus::Completed =>
 					log_trace!(logger, "Finished syncing Channel Monitor for channel {}", log_funding_info!(monitor)),
 				ChannelMonitorUpdateStatus::InProgress => {
 					log_debug!(logger, "Channel Monitor sync for channel {} in progress, holding events until completion!", log_funding_info!(monitor));
+					self.metrics_recorder.increment_counter(metrics::MONITOR_UPDATES_IN_PROGRESS, &[("kind", "chain_sync")], 1);
 					pending_monitor_updates.push(update_id);
 				},
 				ChannelMonitorUpdateStatus::UnrecoverableError => {
@@ -417,7 +461,14 @@ where C::Target: chain::Filter,
 	/// pre-filter blocks or only fetch blocks matching a compact filter. Otherwise, clients may
 	/// always need to fetch full blocks absent another means for determining which blocks contain
 	/// transactions relevant to the watched channels.
-	pub fn new(chain_source: Option<C>, broadcaster: T, logger: L, feeest: F, persister: P) -> Self {
+	///
+	/// Metrics about the persistence of [`ChannelMonitor`]s are reported to the given
+	/// `metrics_recorder`, see the [`metrics`] module for details.
+	///
+	/// [`metrics`]: crate::util::metrics
+	pub fn new(chain_source: Option<C>, broadcaster: T, logger: L, feeest: F, persister: P, metrics_recorder: MR) -> Self {
+		let event_notifier = Arc::new(Notifier::new());
+		persister.register_completion_notifier(PersistCompletionNotifier(Arc::clone(&event_notifier)));
 		Self {
 			monitors: RwLock::new(HashMap::new()),
 			sync_persistence_id: AtomicCounter::new(),
@@ -426,9 +477,10 @@ where C::Target: chain::Filter,
 			logger,
 			fee_estimator: feeest,
 			persister,
+			metrics_recorder,
 			pending_monitor_events: Mutex::new(Vec::new()),
 			highest_chain_height: AtomicUsize::new(0),
-			event_notifier: Notifier::new(),
+			event_notifier,
 		}
 	}
 
@@ -628,14 +680,15 @@ where C::Target: chain::Filter,
 	}
 }
 
-impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref>
-chain::Listen for ChainMonitor<ChannelSigner, C, T, F, L, P>
+impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref, MR: Deref>
+chain::Listen for ChainMonitor<ChannelSigner, C, T, F, L, P, MR>
 where
 	C::Target: chain::Filter,
 	T::Target: BroadcasterInterface,
 	F::Target: FeeEstimator,
 	L::Target: Logger,
 	P::Target: Persist<ChannelSigner>,
+	MR::Target: MetricsRecorder,
 {
 	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		log_debug!(self.logger, "New best block {} at height {} provided via block_connected", header.block_hash(), height);
@@ -655,14 +708,15 @@ where
 	}
 }
 
-impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref>
-chain::Confirm for ChainMonitor<ChannelSigner, C, T, F, L, P>
+impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref, MR: Deref>
+chain::Confirm for ChainMonitor<ChannelSigner, C, T, F, L, P, MR>
 where
 	C::Target: chain::Filter,
 	T::Target: BroadcasterInterface,
 	F::Target: FeeEstimator,
 	L::Target: Logger,
 	P::Target: Persist<ChannelSigner>,
+	MR::Target: MetricsRecorder,
 {
 	fn transactions_confirmed(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		log_debug!(self.logger, "{} provided transactions confirmed at height {} in block {}", txdata.len(), height, header.block_hash());
@@ -705,13 +759,14 @@ where
 	}
 }
 
-impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref , T: Deref , F: Deref , L: Deref , P: Deref >
-chain::Watch<ChannelSigner> for ChainMonitor<ChannelSigner, C, T, F, L, P>
+impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref , T: Deref , F: Deref , L: Deref , P: Deref , MR: Deref >
+chain::Watch<ChannelSigner> for ChainMonitor<ChannelSigner, C, T, F, L, P, MR>
 where C::Target: chain::Filter,
 	    T::Target: BroadcasterInterface,
 	    F::Target: FeeEstimator,
 	    L::Target: Logger,
 	    P::Target: Persist<ChannelSigner>,
+	    MR::Target: MetricsRecorder,
 {
 	fn watch_channel(&self, funding_outpoint: OutPoint, monitor: ChannelMonitor<ChannelSigner>) -> Result<ChannelMonitorUpdateStatus, ()> {
 		let logger = WithChannelMonitor::from(&self.logger, &monitor);
@@ -726,10 +781,13 @@ where C::Target: chain::Filter,
 		log_trace!(logger, "Got new ChannelMonitor for channel {}", log_funding_info!(monitor));
 		let update_id = MonitorUpdateId::from_new_monitor(&monitor);
 		let mut pending_monitor_updates = Vec::new();
+		let persist_timer = LatencyTimer::start();
 		let persist_res = self.persister.persist_new_channel(funding_outpoint, &monitor, update_id);
+		persist_timer.record(&*self.metrics_recorder, metrics::MONITOR_UPDATE_DURATION, &[("kind", "new_channel")]);
 		match persist_res {
 			ChannelMonitorUpdateStatus::InProgress => {
 				log_info!(logger, "Persistence of new ChannelMonitor for channel {} in progress", log_funding_info!(monitor));
+				self.metrics_recorder.increment_counter(metrics::MONITOR_UPDATES_IN_PROGRESS, &[("kind", "new_channel")], 1);
 				pending_monitor_updates.push(update_id);
 			},
 			ChannelMonitorUpdateStatus::Completed => {
@@ -757,7 +815,7 @@ where C::Target: chain::Filter,
 		let monitors = self.monitors.read().unwrap();
 		match monitors.get(&funding_txo) {
 			None => {
-				let logger = WithContext::from(&self.logger, update.counterparty_node_id, Some(funding_txo.to_channel_id()));
+				let logger = WithContext::from(&self.logger, update.counterparty_node_id, None);
 				log_error!(logger, "Failed to update channel monitor: no such monitor registered");
 
 				// We should never ever trigger this from within ChannelManager. Technically a
@@ -773,9 +831,19 @@ where C::Target: chain::Filter,
 				let logger = WithChannelMonitor::from(&self.logger, &monitor);
 				log_trace!(logger, "Updating ChannelMonitor for channel {}", log_funding_info!(monitor));
 				let update_res = monitor.update_monitor(update, &self.broadcaster, &self.fee_estimator, &self.logger);
+				if let Some(ref chain_source) = self.chain_source {
+					// A splice's funding transaction needs to be watched for until it's locked, and
+					// its funding output once it is.
+					if update.updates.iter().any(|step| matches!(step,
+						ChannelMonitorUpdateStep::RenegotiatedFunding { .. } | ChannelMonitorUpdateStep::SpliceLocked { .. }))
+					{
+						monitor.load_outputs_to_watch(chain_source, &self.logger);
+					}
+				}
 
 				let update_id = MonitorUpdateId::from_monitor_update(update);
 				let mut pending_monitor_updates = monitor_state.pending_monitor_updates.lock().unwrap();
+				let persist_timer = LatencyTimer::start();
 				let persist_res = if update_res.is_err() {
 					// Even if updating the monitor returns an error, the monitor's state will
 					// still be changed. Therefore, we should persist the updated monitor despite the error.
@@ -787,8 +855,10 @@ where C::Target: chain::Filter,
 				} else {
 					self.persister.update_persisted_channel(funding_txo, Some(update), monitor, update_id)
 				};
+				persist_timer.record(&*self.metrics_recorder, metrics::MONITOR_UPDATE_DURATION, &[("kind", "update")]);
 				match persist_res {
 					ChannelMonitorUpdateStatus::InProgress => {
+						self.metrics_recorder.increment_counter(metrics::MONITOR_UPDATES_IN_PROGRESS, &[("kind", "update")], 1);
 						pending_monitor_updates.push(update_id);
 						log_debug!(logger, "Persistence of ChannelMonitorUpdate for channel {} in progress", log_funding_info!(monitor));
 					},
@@ -816,6 +886,26 @@ where C::Target: chain::Filter,
 	}
 
 	fn release_pending_monitor_events(&self) -> Vec<(OutPoint, Vec<MonitorEvent>, Option<PublicKey>)> {
+		for (funding_txo, update_id, status) in self.persister.get_and_clear_completed_updates() {
+			match status {
+				ChannelMonitorUpdateStatus::Completed => {
+					// This only fails if we no longer know about the monitor, in which case
+					// there's nothing left to complete.
+					let _ = self.channel_monitor_updated(funding_txo, update_id);
+				},
+				ChannelMonitorUpdateStatus::InProgress => {
+					debug_assert!(false, "Persist::get_and_clear_completed_updates must only return finished updates");
+				},
+				ChannelMonitorUpdateStatus::UnrecoverableError => {
+					// Take the monitors lock for writing so that we poison it and any future
+					// operations going forward fail immediately.
+					let _poison = self.monitors.write().unwrap();
+					let err_str = "ChannelMonitor[Update] persistence failed unrecoverably. This indicates we cannot continue normal operation and must shut down.";
+					log_error!(self.logger, "{}", err_str);
+					panic!("{}", err_str);
+				},
+			}
+		}
 		let mut pending_monitor_events = self.pending_monitor_events.lock().unwrap().split_off(0);
 		for monitor_state in self.monitors.read().unwrap().values() {
 			let logger = WithChannelMonitor::from(&self.logger, &monitor_state.monitor);
@@ -836,14 +926,30 @@ where C::Target: chain::Filter,
 		}
 		pending_monitor_events
 	}
+
+	fn get_peer_storage_snapshot(&self) -> Vec<PeerStorageMonitorHolder> {
+		let mut snapshot: Vec<_> = self.monitors.read().unwrap().iter().map(|(funding_outpoint, monitor_state)| {
+			let monitor = &monitor_state.monitor;
+			PeerStorageMonitorHolder {
+				counterparty_node_id: monitor.get_counterparty_node_id(),
+				funding_outpoint: *funding_outpoint,
+				channel_keys_id: monitor.channel_keys_id(),
+				latest_commitment_number: monitor.get_cur_holder_commitment_number(),
+			}
+		}).collect();
+		// Keep the snapshot stable across calls so that it only changes with the monitors.
+		snapshot.sort_unstable_by_key(|holder| (holder.funding_outpoint.txid, holder.funding_outpoint.index));
+		snapshot
+	}
 }
 
-impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref> events::EventsProvider for ChainMonitor<ChannelSigner, C, T, F, L, P>
+impl<ChannelSigner: WriteableEcdsaChannelSigner, C: Deref, T: Deref, F: Deref, L: Deref, P: Deref, MR: Deref> events::EventsProvider for ChainMonitor<ChannelSigner, C, T, F, L, P, MR>
 	where C::Target: chain::Filter,
 	      T::Target: BroadcasterInterface,
 	      F::Target: FeeEstimator,
 	      L::Target: Logger,
 	      P::Target: Persist<ChannelSigner>,
+	      MR::Target: MetricsRecorder,
 {
 	/// Processes [`SpendableOutputs`] events produced from each [`ChannelMonitor`] upon maturity.
 	///
@@ -533,6 +533,20 @@ pub(crate) enum ChannelMonitorUpdateStep {
 	ShutdownScript {
 		scriptpubkey: ScriptBuf,
 	},
+	/// Provides a funding output negotiated by a splice, along with the holder commitment
+	/// transaction spending it, which replaces the current funding output once the splice has been
+	/// locked and its transaction confirmed.
+	RenegotiatedFunding {
+		funding_outpoint: OutPoint,
+		channel_value_satoshis: u64,
+		holder_commitment_tx: HolderCommitmentTransaction,
+		counterparty_commitment_txid: Txid,
+	},
+	/// Indicates that both parties have sent `splice_locked` for the funding transaction `txid`
+	/// previously provided via [`ChannelMonitorUpdateStep::RenegotiatedFunding`].
+	SpliceLocked {
+		funding_txid: Txid,
+	},
 }
 
 impl ChannelMonitorUpdateStep {
@@ -544,6 +558,8 @@ impl ChannelMonitorUpdateStep {
 			ChannelMonitorUpdateStep::CommitmentSecret { .. } => "CommitmentSecret",
 			ChannelMonitorUpdateStep::ChannelForceClosed { .. } => "ChannelForceClosed",
 			ChannelMonitorUpdateStep::ShutdownScript { .. } => "ShutdownScript",
+			ChannelMonitorUpdateStep::RenegotiatedFunding { .. } => "RenegotiatedFunding",
+			ChannelMonitorUpdateStep::SpliceLocked { .. } => "SpliceLocked",
 		}
 	}
 }
@@ -577,8 +593,56 @@ impl_writeable_tlv_based_enum_upgradable!(ChannelMonitorUpdateStep,
 	(5, ShutdownScript) => {
 		(0, scriptpubkey, required),
 	},
+	(6, RenegotiatedFunding) => {
+		(0, funding_outpoint, required),
+		(2, channel_value_satoshis, required),
+		(4, holder_commitment_tx, required),
+		(6, counterparty_commitment_txid, required),
+	},
+	(7, SpliceLocked) => {
+		(0, funding_txid, required),
+	},
 );
 
+/// A funding output negotiated by a splice which has yet to be locked by both parties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PendingFunding {
+	funding_outpoint: OutPoint,
+	channel_value_satoshis: u64,
+	holder_commitment_tx: HolderCommitmentTransaction,
+	counterparty_commitment_txid: Txid,
+	/// The height at which the funding transaction confirmed, if it currently is.
+	confirmation_height: Option<u32>,
+	/// Whether both parties have sent `splice_locked`.
+	locked: bool,
+}
+
+impl_writeable_tlv_based!(PendingFunding, {
+	(0, funding_outpoint, required),
+	(2, channel_value_satoshis, required),
+	(4, holder_commitment_tx, required),
+	(6, counterparty_commitment_txid, required),
+	(8, confirmation_height, option),
+	(10, locked, (default_value, false)),
+});
+
+/// A funding output which has been replaced by a splice. Until the splice transaction has
+/// [`ANTI_REORG_DELAY`] confirmations it may still be reorged out, so we keep watching for
+/// (revoked) commitment transactions spending the replaced funding output until then.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SplicedFunding {
+	funding_outpoint: OutPoint,
+	splice_txid: Txid,
+	/// The height at which the splice transaction confirmed, if it currently is.
+	splice_confirmation_height: Option<u32>,
+}
+
+impl_writeable_tlv_based!(SplicedFunding, {
+	(0, funding_outpoint, required),
+	(2, splice_txid, required),
+	(4, splice_confirmation_height, option),
+});
+
 /// Details about the balance(s) available for spending once the channel appears on chain.
 ///
 /// See [`ChannelMonitor::get_claimable_balances`] for more details on when these will or will not
@@ -906,6 +970,22 @@ pub(crate) struct ChannelMonitorImpl<Signer: WriteableEcdsaChannelSigner> {
 	/// Ordering of tuple data: (their_per_commitment_point, feerate_per_kw, to_broadcaster_sats,
 	/// to_countersignatory_sats)
 	initial_counterparty_commitment_info: Option<(PublicKey, u32, u64, u64)>,
+
+	/// The funding outpoint the channel was opened with, if it has since been replaced by a
+	/// splice. The original funding outpoint continues to identify the channel and this monitor.
+	original_funding_txo: Option<OutPoint>,
+
+	/// The funding output negotiated by a splice which has yet to be locked, if any. Once the
+	/// splice is locked and its transaction confirmed it replaces `funding_info`.
+	pending_funding: Option<PendingFunding>,
+
+	/// The funding output replaced by the latest splice, if that splice's transaction may still be
+	/// reorged out.
+	spliced_funding: Option<SplicedFunding>,
+
+	/// The ID of the channel this monitor is for. Unlike for V1 channels, the ID of a channel
+	/// opened using V2 channel establishment cannot be derived from its funding outpoint.
+	channel_id: ChannelId,
 }
 
 /// Transaction outputs to watch for on-chain spends.
@@ -1097,6 +1177,10 @@ impl<Signer: WriteableEcdsaChannelSigner> Writeable for ChannelMonitorImpl<Signe
 			(13, self.spendable_txids_confirmed, required_vec),
 			(15, self.counterparty_fulfilled_htlcs, required),
 			(17, self.initial_counterparty_commitment_info, option),
+			(18, self.original_funding_txo, option),
+			(20, self.pending_funding, option),
+			(22, self.spliced_funding, option),
+			(23, self.channel_id, required),
 		});
 
 		Ok(())
@@ -1160,7 +1244,7 @@ impl<'a, L: Deref> WithChannelMonitor<'a, L> where L::Target: Logger {
 
 	pub(crate) fn from_impl<S: WriteableEcdsaChannelSigner>(logger: &'a L, monitor_impl: &ChannelMonitorImpl<S>) -> Self {
 		let peer_id = monitor_impl.counterparty_node_id;
-		let channel_id = Some(monitor_impl.funding_info.0.to_channel_id());
+		let channel_id = Some(monitor_impl.channel_id());
 		WithChannelMonitor {
 			logger, peer_id, channel_id,
 		}
@@ -1181,7 +1265,8 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 	                  funding_redeemscript: ScriptBuf, channel_value_satoshis: u64,
 	                  commitment_transaction_number_obscure_factor: u64,
 	                  initial_holder_commitment_tx: HolderCommitmentTransaction,
-	                  best_block: BestBlock, counterparty_node_id: PublicKey) -> ChannelMonitor<Signer> {
+	                  best_block: BestBlock, counterparty_node_id: PublicKey, channel_id: ChannelId,
+	) -> ChannelMonitor<Signer> {
 
 		assert!(commitment_transaction_number_obscure_factor <= (1 << 48));
 		let counterparty_payment_script = chan_utils::get_counterparty_payment_script(
@@ -1278,6 +1363,10 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 			best_block,
 			counterparty_node_id: Some(counterparty_node_id),
 			initial_counterparty_commitment_info: None,
+			original_funding_txo: None,
+			pending_funding: None,
+			spliced_funding: None,
+			channel_id,
 		})
 	}
 
@@ -1382,8 +1471,12 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 	}
 
 	/// Gets the funding transaction outpoint of the channel this ChannelMonitor is monitoring for.
+	///
+	/// If the channel has been spliced, this is still the outpoint the channel was originally
+	/// funded with, which continues to identify the channel.
 	pub fn get_funding_txo(&self) -> (OutPoint, ScriptBuf) {
-		self.inner.lock().unwrap().get_funding_txo().clone()
+		let inner = self.inner.lock().unwrap();
+		(inner.original_funding_outpoint(), inner.get_funding_txo().1.clone())
 	}
 
 	/// Gets a list of txids, with their output scripts (in the order they appear in the
@@ -1404,6 +1497,15 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 		let logger = WithChannelMonitor::from_impl(logger, &*lock);
 		log_trace!(&logger, "Registering funding outpoint {}", &lock.get_funding_txo().0);
 		filter.register_tx(&lock.get_funding_txo().0.txid, &lock.get_funding_txo().1);
+		// After a splice, the original and any pending funding transactions are watched as well so
+		// that their confirmations continue to be tracked.
+		if lock.original_funding_outpoint() != lock.get_funding_txo().0 {
+			filter.register_tx(&lock.original_funding_outpoint().txid, &lock.get_funding_txo().1);
+		}
+		if let Some(pending_funding) = &lock.pending_funding {
+			log_trace!(&logger, "Registering pending funding outpoint {}", pending_funding.funding_outpoint);
+			filter.register_tx(&pending_funding.funding_outpoint.txid, &lock.get_funding_txo().1);
+		}
 		for (txid, outputs) in lock.get_outputs_to_watch().iter() {
 			for (index, script_pubkey) in outputs.iter() {
 				assert!(*index <= u16::max_value() as u32);
@@ -1534,6 +1636,10 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 		self.inner.lock().unwrap().get_cur_holder_commitment_number()
 	}
 
+	pub(crate) fn channel_keys_id(&self) -> [u8; 32] {
+		self.inner.lock().unwrap().channel_keys_id
+	}
+
 	/// Gets the `node_id` of the counterparty for this channel.
 	///
 	/// Will be `None` for channels constructed on LDK versions prior to 0.0.110 and always `Some`
@@ -1542,6 +1648,11 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitor<Signer> {
 		self.inner.lock().unwrap().counterparty_node_id
 	}
 
+	/// Gets the ID of the channel this monitor is for.
+	pub fn channel_id(&self) -> ChannelId {
+		self.inner.lock().unwrap().channel_id()
+	}
+
 	/// Used by [`ChannelManager`] deserialization to broadcast the latest holder state if its copy
 	/// of the channel state was out-of-date.
 	///
@@ -2692,7 +2803,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			self.best_block.height(), self.best_block.height()
 		);
 		let mut claimable_outpoints = vec![commitment_package];
-		self.pending_monitor_events.push(MonitorEvent::HolderForceClosed(self.funding_info.0));
+		self.pending_monitor_events.push(MonitorEvent::HolderForceClosed(self.original_funding_outpoint()));
 		// Although we aren't signing the transaction directly here, the transaction will be signed
 		// in the claim that is queued to OnchainTxHandler. We set holder_tx_signed here to reject
 		// new channel updates.
@@ -2834,7 +2945,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 						self.queue_latest_holder_commitment_txn_for_broadcast(broadcaster, &bounded_fee_estimator, logger);
 					} else if !self.holder_tx_signed {
 						log_error!(logger, "WARNING: You have a potentially-unsafe holder commitment transaction available to broadcast");
-						log_error!(logger, "    in channel monitor for channel {}!", &self.funding_info.0.to_channel_id());
+						log_error!(logger, "    in channel monitor for channel {}!", &self.channel_id());
 						log_error!(logger, "    Read the docs for ChannelMonitor::get_latest_holder_commitment_txn and take manual action!");
 					} else {
 						// If we generated a MonitorEvent::HolderForceClosed, the ChannelManager
@@ -2849,6 +2960,37 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 						panic!("Attempted to replace shutdown script {} with {}", shutdown_script, scriptpubkey);
 					}
 				},
+				ChannelMonitorUpdateStep::RenegotiatedFunding { funding_outpoint, channel_value_satoshis, holder_commitment_tx, counterparty_commitment_txid } => {
+					log_trace!(logger, "Updating ChannelMonitor with renegotiated funding output {}", funding_outpoint);
+					if self.lockdown_from_offchain { panic!(); }
+					// Splices are only negotiated while no HTLCs are pending.
+					debug_assert!(holder_commitment_tx.htlcs().is_empty());
+					self.pending_funding = Some(PendingFunding {
+						funding_outpoint: *funding_outpoint,
+						channel_value_satoshis: *channel_value_satoshis,
+						holder_commitment_tx: holder_commitment_tx.clone(),
+						counterparty_commitment_txid: *counterparty_commitment_txid,
+						confirmation_height: None,
+						locked: false,
+					});
+				},
+				ChannelMonitorUpdateStep::SpliceLocked { funding_txid } => {
+					log_trace!(logger, "Updating ChannelMonitor with splice_locked for funding transaction {}", funding_txid);
+					match self.pending_funding.as_mut() {
+						Some(pending_funding) if pending_funding.funding_outpoint.txid == *funding_txid => {
+							pending_funding.locked = true;
+							if let Some((txid, outputs)) = self.promote_pending_funding(logger) {
+								let idx_and_scripts = outputs.iter().map(|o| (o.0, o.1.script_pubkey.clone())).collect();
+								self.outputs_to_watch.insert(txid, idx_and_scripts);
+							}
+						},
+						_ => {
+							debug_assert!(false, "Got splice_locked for an unknown funding transaction");
+							log_error!(logger, "Got splice_locked for unknown funding transaction {}", funding_txid);
+							ret = Err(());
+						},
+					}
+				},
 			}
 		}
 
@@ -2880,6 +3022,68 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		&self.funding_info
 	}
 
+	fn original_funding_outpoint(&self) -> OutPoint {
+		self.original_funding_txo.unwrap_or(self.funding_info.0)
+	}
+
+	fn channel_id(&self) -> ChannelId {
+		self.channel_id
+	}
+
+	/// Switches to the funding output negotiated by a splice once it has been locked by both
+	/// parties and its transaction has confirmed, returning the new funding output to watch for
+	/// spends.
+	///
+	/// Until the splice transaction has [`ANTI_REORG_DELAY`] confirmations, the previous funding
+	/// output is still tracked via `spliced_funding` so that a revoked commitment transaction
+	/// spending it can be punished should the splice be reorged out.
+	fn promote_pending_funding<L: Deref>(&mut self, logger: &WithChannelMonitor<L>) -> Option<TransactionOutputs>
+	where L::Target: Logger {
+		match self.pending_funding.as_ref() {
+			Some(pending_funding) if pending_funding.locked && pending_funding.confirmation_height.is_some() => {},
+			_ => return None,
+		}
+		let pending_funding = self.pending_funding.take().unwrap();
+		let txid = pending_funding.funding_outpoint.txid;
+		log_info!(logger, "Channel {} spliced into new funding output {}",
+			&self.channel_id(), pending_funding.funding_outpoint);
+
+		let holder_commitment_tx = pending_funding.holder_commitment_tx;
+		let trusted_tx = holder_commitment_tx.trust();
+		let tx_keys = trusted_tx.keys();
+		self.current_holder_commitment_tx = HolderSignedTx {
+			txid: trusted_tx.txid(),
+			revocation_key: tx_keys.revocation_key,
+			a_htlc_key: tx_keys.broadcaster_htlc_key,
+			b_htlc_key: tx_keys.countersignatory_htlc_key,
+			delayed_payment_key: tx_keys.broadcaster_delayed_payment_key,
+			per_commitment_point: tx_keys.per_commitment_point,
+			htlc_outputs: Vec::new(),
+			to_self_value_sat: holder_commitment_tx.to_broadcaster_value_sat(),
+			feerate_per_kw: trusted_tx.feerate_per_kw(),
+		};
+		self.prev_holder_signed_commitment_tx = None;
+		self.onchain_tx_handler.provide_renegotiated_funding(
+			pending_funding.funding_outpoint, pending_funding.channel_value_satoshis, holder_commitment_tx);
+
+		self.counterparty_claimable_outpoints.insert(pending_funding.counterparty_commitment_txid, Vec::new());
+		self.current_counterparty_commitment_txid = Some(pending_funding.counterparty_commitment_txid);
+		self.prev_counterparty_commitment_txid = None;
+
+		self.original_funding_txo.get_or_insert(self.funding_info.0);
+		self.spliced_funding = Some(SplicedFunding {
+			funding_outpoint: self.funding_info.0,
+			splice_txid: txid,
+			splice_confirmation_height: pending_funding.confirmation_height,
+		});
+		self.funding_info.0 = pending_funding.funding_outpoint;
+		self.channel_value_satoshis = pending_funding.channel_value_satoshis;
+		let funding_output_index = pending_funding.funding_outpoint.index as u32;
+		Some((txid, vec![(funding_output_index, TxOut {
+			value: pending_funding.channel_value_satoshis, script_pubkey: self.funding_info.1.clone(),
+		})]))
+	}
+
 	fn get_outputs_to_watch(&self) -> &HashMap<Txid, Vec<(u32, ScriptBuf)>> {
 		// If we've detected a counterparty commitment tx on chain, we must include it in the set
 		// of outputs to watch for spends of, otherwise we're likely to lose user funds. Because
@@ -2912,6 +3116,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 					let pending_htlcs = self.current_holder_commitment_tx.non_dust_htlcs();
 					let commitment_tx_fee_satoshis = self.channel_value_satoshis -
 						commitment_tx.output.iter().fold(0u64, |sum, output| sum + output.value);
+					let anchor_value_satoshis = commitment_tx.output[anchor_output_idx as usize].value;
 					ret.push(Event::BumpTransaction(BumpTransactionEvent::ChannelClose {
 						claim_id,
 						package_target_feerate_sat_per_1000_weight,
@@ -2927,6 +3132,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 								txid: commitment_txid,
 								vout: anchor_output_idx,
 							},
+							value_satoshis: anchor_value_satoshis,
 						},
 						pending_htlcs,
 					}));
@@ -2993,7 +3199,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		let channel_parameters =
 			&self.onchain_tx_handler.channel_transaction_parameters.as_counterparty_broadcastable();
 
-		CommitmentTransaction::new_with_auxiliary_htlc_data(commitment_number,
+		CommitmentTransaction::new_with_channel_value_and_auxiliary_htlc_data(commitment_number, self.channel_value_satoshis,
 			to_broadcaster_value, to_countersignatory_value, broadcaster_funding_key,
 			countersignatory_funding_key, keys, feerate_per_kw, &mut nondust_htlcs,
 			channel_parameters)
@@ -3568,6 +3774,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			self.best_block = BestBlock::new(block_hash, height);
 			log_trace!(logger, "Best block re-orged, replaced with new block {} at height {}", block_hash, height);
 			self.onchain_events_awaiting_threshold_conf.retain(|ref entry| entry.height <= height);
+			self.funding_confirmations_disconnected(height + 1, logger);
 			self.onchain_tx_handler.block_disconnected(height + 1, broadcaster, fee_estimator, logger);
 			Vec::new()
 		} else { Vec::new() }
@@ -3633,16 +3840,42 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 				}
 			}
 
+			if let Some(pending_funding) = self.pending_funding.as_mut() {
+				if pending_funding.funding_outpoint.txid == txid {
+					pending_funding.confirmation_height = Some(height);
+					// A fee-bumped funding transaction conflicts with the one it replaces, rather than
+					// spending it as a splice does, so there's no `splice_locked` to wait for.
+					let funding_outpoint = self.funding_info.0.into_bitcoin_outpoint();
+					if !tx.input.iter().any(|input| input.previous_output == funding_outpoint) {
+						pending_funding.locked = true;
+					}
+					if let Some(new_funding_output) = self.promote_pending_funding(&logger) {
+						watch_outputs.push(new_funding_output);
+					}
+					continue 'tx_iter;
+				}
+			}
+			if let Some(spliced_funding) = self.spliced_funding.as_mut() {
+				if spliced_funding.splice_txid == txid {
+					spliced_funding.splice_confirmation_height = Some(height);
+					continue 'tx_iter;
+				}
+			}
+
 			if tx.input.len() == 1 {
 				// Assuming our keys were not leaked (in which case we're screwed no matter what),
 				// commitment transactions and HTLC transactions will all only ever have one input
 				// (except for HTLC transactions for channels with anchor outputs), which is an easy
 				// way to filter out any potential non-matching txn for lazy filters.
 				let prevout = &tx.input[0].previous_output;
-				if prevout.txid == self.funding_info.0.txid && prevout.vout == self.funding_info.0.index as u32 {
+				let spends_funding = |funding_outpoint: &OutPoint|
+					prevout.txid == funding_outpoint.txid && prevout.vout == funding_outpoint.index as u32;
+				if spends_funding(&self.funding_info.0) ||
+					self.spliced_funding.as_ref().map_or(false, |spliced| spends_funding(&spliced.funding_outpoint))
+				{
 					let mut balance_spendable_csv = None;
 					log_info!(logger, "Channel {} closed by funding output spend in txid {}.",
-						&self.funding_info.0.to_channel_id(), txid);
+						&self.channel_id(), txid);
 					self.funding_spend_seen = true;
 					let mut commitment_tx_to_counterparty_output = None;
 					if (tx.input[0].sequence.0 >> 8*3) as u8 == 0x80 && (tx.lock_time.to_consensus_u32() >> 8*3) as u8 == 0x20 {
@@ -3812,7 +4045,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 					log_debug!(logger, "Descriptor {} has got enough confirmations to be passed upstream", log_spendable!(descriptor));
 					self.pending_events.push(Event::SpendableOutputs {
 						outputs: vec![descriptor],
-						channel_id: Some(self.funding_info.0.to_channel_id()),
+						channel_id: Some(self.channel_id()),
 					});
 					self.spendable_txids_confirmed.push(entry.txid);
 				},
@@ -3831,6 +4064,15 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 			}
 		}
 
+		let splice_buried = self.spliced_funding.as_ref()
+			.and_then(|spliced| spliced.splice_confirmation_height)
+			.map_or(false, |height| self.best_block.height() >= height + ANTI_REORG_DELAY - 1);
+		if splice_buried {
+			let spliced_funding = self.spliced_funding.take().unwrap();
+			log_debug!(logger, "Splice transaction {} has got enough confirmations, no longer watching funding output {}",
+				spliced_funding.splice_txid, spliced_funding.funding_outpoint);
+		}
+
 		self.onchain_tx_handler.update_claims_view_from_requests(claimable_outpoints, conf_height, self.best_block.height(), broadcaster, fee_estimator, logger);
 		self.onchain_tx_handler.update_claims_view_from_matched_txn(&txn_matched, conf_height, conf_hash, self.best_block.height(), broadcaster, fee_estimator, logger);
 
@@ -3869,6 +4111,7 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		//- htlc update there as failure-trigger tx (revoked commitment tx, non-revoked commitment tx, HTLC-timeout tx) has been disconnected
 		//- maturing spendable output has transaction paying us has been disconnected
 		self.onchain_events_awaiting_threshold_conf.retain(|ref entry| entry.height < height);
+		self.funding_confirmations_disconnected(height, logger);
 
 		let bounded_fee_estimator = LowerBoundedFeeEstimator::new(fee_estimator);
 		self.onchain_tx_handler.block_disconnected(height, broadcaster, &bounded_fee_estimator, logger);
@@ -3876,6 +4119,24 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		self.best_block = BestBlock::new(header.prev_blockhash, height - 1);
 	}
 
+	/// Forgets any confirmation of a splice's funding transaction at or above `height`.
+	fn funding_confirmations_disconnected<L: Deref>(&mut self, height: u32, logger: &WithChannelMonitor<L>)
+	where L::Target: Logger {
+		if let Some(pending_funding) = self.pending_funding.as_mut() {
+			if pending_funding.confirmation_height.map_or(false, |conf_height| conf_height >= height) {
+				log_info!(logger, "Splice funding transaction {} was reorged out", pending_funding.funding_outpoint.txid);
+				pending_funding.confirmation_height = None;
+			}
+		}
+		if let Some(spliced_funding) = self.spliced_funding.as_mut() {
+			if spliced_funding.splice_confirmation_height.map_or(false, |conf_height| conf_height >= height) {
+				log_info!(logger, "Splice transaction {} was reorged out, watching funding output {} again",
+					spliced_funding.splice_txid, spliced_funding.funding_outpoint);
+				spliced_funding.splice_confirmation_height = None;
+			}
+		}
+	}
+
 	fn transaction_unconfirmed<B: Deref, F: Deref, L: Deref>(
 		&mut self,
 		txid: &Txid,
@@ -3887,6 +4148,18 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 		F::Target: FeeEstimator,
 		L::Target: Logger,
 	{
+		if let Some(pending_funding) = self.pending_funding.as_mut() {
+			if pending_funding.funding_outpoint.txid == *txid && pending_funding.confirmation_height.take().is_some() {
+				log_info!(logger, "Splice funding transaction {} was unconfirmed", txid);
+			}
+		}
+		if let Some(spliced_funding) = self.spliced_funding.as_mut() {
+			if spliced_funding.splice_txid == *txid && spliced_funding.splice_confirmation_height.take().is_some() {
+				log_info!(logger, "Splice transaction {} was unconfirmed, watching funding output {} again",
+					txid, spliced_funding.funding_outpoint);
+			}
+		}
+
 		let mut removed_height = None;
 		for entry in self.onchain_events_awaiting_threshold_conf.iter() {
 			if entry.txid == *txid {
@@ -3912,8 +4185,11 @@ impl<Signer: WriteableEcdsaChannelSigner> ChannelMonitorImpl<Signer> {
 	/// transactions thereof.
 	fn filter_block<'a>(&self, txdata: &TransactionData<'a>) -> Vec<&'a Transaction> {
 		let mut matched_txn = HashSet::new();
+		let pending_funding_txid = self.pending_funding.as_ref().map(|pending| pending.funding_outpoint.txid);
 		txdata.iter().filter(|&&(_, tx)| {
-			let mut matches = self.spends_watched_output(tx);
+			// A fee-bumped dual-funded open doesn't spend any output we watch, so match on the
+			// pending funding transaction itself to learn when it replaces the original.
+			let mut matches = self.spends_watched_output(tx) || Some(tx.txid()) == pending_funding_txid;
 			for input in tx.input.iter() {
 				if matches { break; }
 				if matched_txn.contains(&input.previous_output.txid) {
@@ -4557,6 +4833,10 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 		let mut spendable_txids_confirmed = Some(Vec::new());
 		let mut counterparty_fulfilled_htlcs = Some(HashMap::new());
 		let mut initial_counterparty_commitment_info = None;
+		let mut original_funding_txo = None;
+		let mut pending_funding = None;
+		let mut spliced_funding = None;
+		let mut channel_id = None;
 		read_tlv_fields!(reader, {
 			(1, funding_spend_confirmed, option),
 			(3, htlcs_resolved_on_chain, optional_vec),
@@ -4567,6 +4847,10 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 			(13, spendable_txids_confirmed, optional_vec),
 			(15, counterparty_fulfilled_htlcs, option),
 			(17, initial_counterparty_commitment_info, option),
+			(18, original_funding_txo, option),
+			(20, pending_funding, option),
+			(22, spliced_funding, option),
+			(23, channel_id, option),
 		});
 
 		// Monitors for anchor outputs channels opened in v0.0.116 suffered from a bug in which the
@@ -4580,6 +4864,11 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 				chan_utils::get_to_countersignatory_with_anchors_redeemscript(&payment_point).to_v0_p2wsh();
 		}
 
+		// Monitors written prior to V2 channel establishment being supported are always for V1
+		// channels, whose ID is derived from the funding outpoint they were opened with.
+		let channel_id = channel_id
+			.unwrap_or_else(|| original_funding_txo.unwrap_or(funding_info.0).to_channel_id());
+
 		Ok((best_block.block_hash(), ChannelMonitor::from_impl(ChannelMonitorImpl {
 			latest_update_id,
 			commitment_transaction_number_obscure_factor,
@@ -4634,6 +4923,10 @@ impl<'a, 'b, ES: EntropySource, SP: SignerProvider> ReadableArgs<(&'a ES, &'b SP
 			best_block,
 			counterparty_node_id,
 			initial_counterparty_commitment_info,
+			original_funding_txo,
+			pending_funding,
+			spliced_funding,
+			channel_id,
 		})))
 	}
 }
@@ -4860,7 +5153,7 @@ mod tests {
 			Some(ShutdownScript::new_p2wpkh_from_pubkey(shutdown_pubkey).into_inner()), 0, &ScriptBuf::new(),
 			(OutPoint { txid: Txid::from_slice(&[43; 32]).unwrap(), index: 0 }, ScriptBuf::new()),
 			&channel_parameters, ScriptBuf::new(), 46, 0, HolderCommitmentTransaction::dummy(&mut Vec::new()),
-			best_block, dummy_key);
+			best_block, dummy_key, ChannelId::v1_from_funding_txid(&[43; 32], 0));
 
 		let mut htlcs = preimages_slice_to_htlcs!(preimages[0..10]);
 		let dummy_commitment_tx = HolderCommitmentTransaction::dummy(&mut htlcs);
@@ -5107,9 +5400,9 @@ mod tests {
 			Some(ShutdownScript::new_p2wpkh_from_pubkey(shutdown_pubkey).into_inner()), 0, &ScriptBuf::new(),
 			(OutPoint { txid: Txid::from_slice(&[43; 32]).unwrap(), index: 0 }, ScriptBuf::new()),
 			&channel_parameters, ScriptBuf::new(), 46, 0, HolderCommitmentTransaction::dummy(&mut Vec::new()),
-			best_block, dummy_key);
+			best_block, dummy_key, ChannelId::v1_from_funding_txid(&[43; 32], 0));
 
-		let chan_id = monitor.inner.lock().unwrap().funding_info.0.to_channel_id().clone();
+		let chan_id = monitor.inner.lock().unwrap().channel_id();
 		let context_logger = WithChannelMonitor::from(&logger, &monitor);
 		log_error!(context_logger, "This is an error");
 		log_warn!(context_logger, "This is an error");
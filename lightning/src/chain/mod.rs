@@ -19,18 +19,23 @@ use bitcoin::secp256k1::PublicKey;
 use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, MonitorEvent};
 use crate::sign::ecdsa::WriteableEcdsaChannelSigner;
 use crate::chain::transaction::{OutPoint, TransactionData};
+use crate::ln::our_peer_storage::PeerStorageMonitorHolder;
 
 use crate::prelude::*;
 
+use core::future::Future;
+use core::pin::Pin;
+
 pub mod chaininterface;
 pub mod chainmonitor;
 pub mod channelmonitor;
 pub mod transaction;
+pub mod watchtower;
 pub(crate) mod onchaintx;
 pub(crate) mod package;
 
 /// The best known block as identified by its hash and height.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BestBlock {
 	block_hash: BlockHash,
 	height: u32,
@@ -58,6 +63,11 @@ impl BestBlock {
 	pub fn height(&self) -> u32 { self.height }
 }
 
+impl_writeable_tlv_based!(BestBlock, {
+	(0, block_hash, required),
+	(2, height, required),
+});
+
 
 /// The `Listen` trait is used to notify when blocks have been connected or disconnected from the
 /// chain.
@@ -298,6 +308,18 @@ pub trait Watch<ChannelSigner: WriteableEcdsaChannelSigner> {
 	/// For details on asynchronous [`ChannelMonitor`] updating and returning
 	/// [`MonitorEvent::Completed`] here, see [`ChannelMonitorUpdateStatus::InProgress`].
 	fn release_pending_monitor_events(&self) -> Vec<(OutPoint, Vec<MonitorEvent>, Option<PublicKey>)>;
+
+	/// Returns a compact summary of each [`ChannelMonitor`] being watched, which the
+	/// [`ChannelManager`] encrypts and hands to our peers as a backup via [`msgs::PeerStorage`].
+	///
+	/// Implementations which don't wish to back up their channel state with peers may return an
+	/// empty list, as the default implementation does.
+	///
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	/// [`msgs::PeerStorage`]: crate::ln::msgs::PeerStorage
+	fn get_peer_storage_snapshot(&self) -> Vec<PeerStorageMonitorHolder> {
+		Vec::new()
+	}
 }
 
 /// The `Filter` trait defines behavior for indicating chain activity of interest pertaining to
@@ -357,6 +379,55 @@ pub struct WatchedOutput {
 	pub script_pubkey: ScriptBuf,
 }
 
+/// An error returned by a [`TransactionSync`] or [`AsyncTransactionSync`] implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxSyncError {
+	/// A transaction sync failed and needs to be retried eventually.
+	Failed,
+}
+
+impl core::fmt::Display for TxSyncError {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match *self {
+			Self::Failed => write!(f, "Failed to conduct transaction sync."),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TxSyncError {}
+
+/// A chain source which keeps [`Confirm`] implementations, usually a [`ChannelManager`] and a
+/// [`ChainMonitor`], in sync with the best chain, e.g., by querying an Esplora or Electrum server
+/// for the transactions and outputs registered via its [`Filter`] implementation.
+///
+/// Implementing this trait allows different chain sources to be used interchangeably and to be
+/// driven periodically by the `lightning-background-processor` crate. See
+/// [`AsyncTransactionSync`] for a variant which does not block the calling thread on I/O.
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+pub trait TransactionSync: Filter {
+	/// Synchronizes the given `confirmables` via their [`Confirm`] interface implementations with
+	/// the current best chain.
+	///
+	/// If the sync failed it should be retried later.
+	fn sync(&self, confirmables: Vec<&(dyn Confirm + Sync + Send)>) -> Result<(), TxSyncError>;
+}
+
+/// The result of an [`AsyncTransactionSync::sync`] call, which resolves once the sync has
+/// completed.
+pub type AsyncTransactionSyncResult<'a> = Pin<Box<dyn Future<Output = Result<(), TxSyncError>> + Send + 'a>>;
+
+/// An asynchronous variant of [`TransactionSync`].
+pub trait AsyncTransactionSync: Filter {
+	/// Synchronizes the given `confirmables` via their [`Confirm`] interface implementations with
+	/// the current best chain.
+	///
+	/// If the sync failed it should be retried later.
+	fn sync<'a>(&'a self, confirmables: Vec<&'a (dyn Confirm + Sync + Send)>) -> AsyncTransactionSyncResult<'a>;
+}
+
 impl<T: Listen> Listen for dyn core::ops::Deref<Target = T> {
 	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		(**self).filtered_block_connected(header, txdata, height);
@@ -28,6 +28,7 @@ use crate::ln::msgs::DecodeError;
 use crate::ln::PaymentPreimage;
 use crate::ln::chan_utils::{self, ChannelTransactionParameters, HTLCOutputInCommitment, HolderCommitmentTransaction};
 use crate::chain::ClaimId;
+use crate::chain::transaction::OutPoint;
 use crate::chain::chaininterface::{ConfirmationTarget, FeeEstimator, BroadcasterInterface, LowerBoundedFeeEstimator};
 use crate::chain::channelmonitor::{ANTI_REORG_DELAY, CLTV_SHARED_CLAIM_BUFFER};
 use crate::chain::package::{PackageSolvingData, PackageTemplate};
@@ -645,7 +646,12 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> OnchainTxHandler<ChannelSigner>
 
 					// We'll locate an anchor output we can spend within the commitment transaction.
 					let funding_pubkey = &self.channel_transaction_parameters.holder_pubkeys.funding_pubkey;
-					match chan_utils::get_anchor_output(&tx, funding_pubkey) {
+					let anchor_output = if self.channel_type_features().supports_anchor_zero_fee_commitments() {
+						chan_utils::get_shared_anchor_output(&tx)
+					} else {
+						chan_utils::get_anchor_output(&tx, funding_pubkey)
+					};
+					match anchor_output {
 						// An anchor output was found, so we should yield a funding event externally.
 						Some((idx, _)) => {
 							// TODO: Use a lower confirmation target when both our and the
@@ -746,6 +752,9 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> OnchainTxHandler<ChannelSigner>
 					preprocessed_requests.push(req);
 				} else if aggregated_request.is_none() {
 					aggregated_request = Some(req);
+				} else if !aggregated_request.as_ref().unwrap().can_merge_within_truc_limits(&req) {
+					// Merging would exceed the TRUC transaction size limit, so start a new package.
+					preprocessed_requests.push(aggregated_request.replace(req).unwrap());
 				} else {
 					aggregated_request.as_mut().unwrap().merge_package(req);
 				}
@@ -1102,6 +1111,19 @@ impl<ChannelSigner: WriteableEcdsaChannelSigner> OnchainTxHandler<ChannelSigner>
 		self.prev_holder_commitment = Some(replace(&mut self.holder_commitment, tx));
 	}
 
+	/// Switches to a new funding output once a splice transaction has confirmed. Holder commitment
+	/// transactions spending the previous funding output can no longer confirm, so they are
+	/// forgotten.
+	pub(crate) fn provide_renegotiated_funding(
+		&mut self, funding_outpoint: OutPoint, channel_value_satoshis: u64, tx: HolderCommitmentTransaction
+	) {
+		self.channel_transaction_parameters.funding_outpoint = Some(funding_outpoint);
+		self.channel_value_satoshis = channel_value_satoshis;
+		self.signer.provide_renegotiated_funding(funding_outpoint, channel_value_satoshis);
+		self.holder_commitment = tx;
+		self.prev_holder_commitment = None;
+	}
+
 	pub(crate) fn get_unsigned_holder_commitment_tx(&self) -> &Transaction {
 		&self.holder_commitment.trust().built_transaction().transaction
 	}
@@ -23,11 +23,11 @@ use bitcoin::secp256k1::{SecretKey,PublicKey};
 use bitcoin::sighash::EcdsaSighashType;
 
 use crate::ln::PaymentPreimage;
-use crate::ln::chan_utils::{self, TxCreationKeys, HTLCOutputInCommitment};
+use crate::ln::chan_utils::{self, TxCreationKeys, HTLCOutputInCommitment, htlc_success_tx_weight, htlc_timeout_tx_weight};
 use crate::ln::features::ChannelTypeFeatures;
 use crate::ln::channel_keys::{DelayedPaymentBasepoint, HtlcBasepoint};
 use crate::ln::msgs::DecodeError;
-use crate::chain::chaininterface::{FeeEstimator, ConfirmationTarget, MIN_RELAY_FEE_SAT_PER_1000_WEIGHT, compute_feerate_sat_per_1000_weight, FEERATE_FLOOR_SATS_PER_KW};
+use crate::chain::chaininterface::{FeeEstimator, ConfirmationTarget, MIN_RELAY_FEE_SAT_PER_1000_WEIGHT, compute_feerate_sat_per_1000_weight, FEERATE_FLOOR_SATS_PER_KW, TRUC_MAX_WEIGHT};
 use crate::sign::ecdsa::WriteableEcdsaChannelSigner;
 use crate::chain::onchaintx::{ExternalHTLCClaim, OnchainTxHandler};
 use crate::util::logger::Logger;
@@ -44,6 +44,11 @@ use super::chaininterface::LowerBoundedFeeEstimator;
 
 const MAX_ALLOC_SIZE: usize = 64*1024;
 
+/// The maximum weight of the HTLC claims we aggregate within a single TRUC (version 3) HTLC
+/// transaction, leaving a quarter of [`TRUC_MAX_WEIGHT`] for the inputs and change output
+/// attached to fund it.
+const TRUC_MAX_HTLC_CLAIMS_WEIGHT: u64 = TRUC_MAX_WEIGHT * 3 / 4;
+
 
 pub(crate) fn weight_revoked_offered_htlc(channel_type_features: &ChannelTypeFeatures) -> u64 {
 	// number_of_witness_elements + sig_length + revocation_sig + pubkey_length + revocationpubkey + witness_script_length + witness_script
@@ -80,7 +85,7 @@ pub(crate) fn verify_channel_type_features(channel_type_features: &Option<Channe
 			return Err(DecodeError::UnknownRequiredFeature);
 		}
 
-		let mut supported_feature_set = ChannelTypeFeatures::anchors_zero_htlc_fee_and_dependencies();
+		let mut supported_feature_set = ChannelTypeFeatures::anchors_zero_fee_commitments_and_dependencies();
 		supported_feature_set.set_scid_privacy_required();
 		supported_feature_set.set_zero_conf_required();
 
@@ -811,6 +816,34 @@ impl PackageTemplate {
 			}
 		}
 	}
+	/// Returns the weight of the TRUC (version 3) HTLC transaction resulting from this package,
+	/// excluding any inputs and outputs attached to fund it, or `None` if it doesn't result in one.
+	fn truc_htlc_claims_weight(&self) -> Option<u64> {
+		let mut weight = None;
+		for (_, outp) in self.inputs.iter() {
+			if let PackageSolvingData::HolderHTLCOutput(ref outp) = outp {
+				if outp.channel_type_features.supports_anchor_zero_fee_commitments() {
+					*weight.get_or_insert(0) += if outp.preimage.is_some() {
+						htlc_success_tx_weight(&outp.channel_type_features)
+					} else {
+						htlc_timeout_tx_weight(&outp.channel_type_features)
+					};
+				}
+			}
+		}
+		weight
+	}
+	/// Returns whether `merge_from` can be merged into this package without the resulting TRUC
+	/// (version 3) HTLC transaction exceeding the policy size limit, leaving room for the inputs
+	/// and change output attached to fund it. Packages which don't result in a TRUC transaction
+	/// can always be merged.
+	pub(crate) fn can_merge_within_truc_limits(&self, merge_from: &PackageTemplate) -> bool {
+		match (self.truc_htlc_claims_weight(), merge_from.truc_htlc_claims_weight()) {
+			(None, None) => true,
+			(weight, merge_from_weight) =>
+				weight.unwrap_or(0) + merge_from_weight.unwrap_or(0) <= TRUC_MAX_HTLC_CLAIMS_WEIGHT,
+		}
+	}
 	pub(crate) fn merge_package(&mut self, mut merge_from: PackageTemplate) {
 		assert_eq!(self.height_original, merge_from.height_original);
 		if self.malleability == PackageMalleability::Untractable || merge_from.malleability == PackageMalleability::Untractable {
@@ -0,0 +1,952 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Utilities for outsourcing the punishment of revoked counterparty commitment transactions to
+//! watchtowers.
+//!
+//! On the client side, [`WatchtowerClient`] wraps a [`Persist`] implementation, pre-signs a
+//! justice transaction for each counterparty commitment transaction once it has been revoked and
+//! hands it to the tower(s) as an encrypted [`Appointment`] via a [`WatchtowerTransport`].
+//!
+//! On the tower side, [`Watchtower`] stores the [`Appointment`]s it receives and broadcasts the
+//! matching justice transaction once a revoked commitment transaction appears on chain.
+//!
+//! Following the BOLT 13 draft, each [`Appointment`] is identified by a [`Locator`], consisting of
+//! the first half of the revoked commitment transaction's txid, and the justice transaction is
+//! encrypted using the full txid as key. Thus, a tower learns nothing about a channel unless one of
+//! its revoked commitment transactions is confirmed.
+
+use bitcoin::blockdata::block::Header;
+use bitcoin::blockdata::script::ScriptBuf;
+use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::hash_types::{BlockHash, Txid};
+use bitcoin::hashes::Hash;
+
+use crate::chain::{Confirm, Listen};
+use crate::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator, FEERATE_FLOOR_SATS_PER_KW};
+use crate::chain::chainmonitor::{MonitorUpdateId, Persist, PersistCompletionNotifier};
+use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, ANTI_REORG_DELAY, CLOSED_CHANNEL_UPDATE_ID};
+use crate::chain::transaction::{OutPoint, TransactionData};
+use crate::chain::ChannelMonitorUpdateStatus;
+use crate::crypto::chacha20poly1305rfc::ChaCha20Poly1305RFC;
+use crate::io;
+use crate::ln::chan_utils::CommitmentTransaction;
+use crate::ln::msgs::DecodeError;
+use crate::sign::ecdsa::WriteableEcdsaChannelSigner;
+use crate::sync::Mutex;
+use crate::util::logger::Logger;
+use crate::util::persist::{
+	KVStore, WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE,
+	WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE, WATCHTOWER_CLIENT_PERSISTENCE_KEY,
+	WATCHTOWER_CLIENT_PERSISTENCE_PRIMARY_NAMESPACE, WATCHTOWER_CLIENT_PERSISTENCE_SECONDARY_NAMESPACE,
+	WATCHTOWER_PERSISTENCE_KEY, WATCHTOWER_PERSISTENCE_PRIMARY_NAMESPACE,
+	WATCHTOWER_PERSISTENCE_SECONDARY_NAMESPACE,
+};
+use crate::util::ser::{Readable, Writeable, Writer};
+
+use hex::DisplayHex;
+
+use crate::prelude::*;
+use core::cmp;
+use core::ops::Deref;
+
+const TAG_LEN: usize = 16;
+
+/// The maximum size of an encrypted justice transaction a [`Watchtower`] will accept.
+pub const MAX_APPOINTMENT_BLOB_SIZE: usize = 1024;
+
+/// The maximum number of [`Appointment`]s with the same [`Locator`] a [`Watchtower`] will store.
+///
+/// As the [`Locator`] only covers half of the commitment txid, multiple clients may (maliciously
+/// or not) hand us appointments with the same [`Locator`].
+pub const MAX_APPOINTMENTS_PER_LOCATOR: usize = 8;
+
+/// Identifies an [`Appointment`], consisting of the first 16 bytes of the txid of the revoked
+/// commitment transaction it punishes.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Locator(pub [u8; 16]);
+
+impl Locator {
+	/// Builds the [`Locator`] for the commitment transaction with the given txid.
+	pub fn from_txid(txid: &Txid) -> Self {
+		let mut locator = [0; 16];
+		locator.copy_from_slice(&txid.as_byte_array()[..16]);
+		Self(locator)
+	}
+}
+
+impl Writeable for Locator {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.0.write(w)
+	}
+}
+
+impl Readable for Locator {
+	fn read<R: io::Read>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self(Readable::read(r)?))
+	}
+}
+
+/// A justice transaction for a revoked counterparty commitment transaction, encrypted such that
+/// it can only be decrypted once the commitment transaction is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Appointment {
+	/// The [`Locator`] derived from the txid of the revoked commitment transaction.
+	pub locator: Locator,
+	/// The justice transaction, encrypted using the txid of the revoked commitment transaction as
+	/// key.
+	pub encrypted_blob: Vec<u8>,
+}
+
+impl_writeable_tlv_based!(Appointment, {
+	(0, locator, required),
+	(2, encrypted_blob, required),
+});
+
+impl Appointment {
+	/// Builds an [`Appointment`] for the given justice transaction, spending an output of the
+	/// revoked commitment transaction with the given txid.
+	pub fn new(commitment_txid: &Txid, justice_tx: &Transaction) -> Self {
+		let plaintext = justice_tx.encode();
+		let mut encrypted_blob = vec![0; plaintext.len() + TAG_LEN];
+		let (ciphertext, tag) = encrypted_blob.split_at_mut(plaintext.len());
+		// Each key is only ever used for a single commitment transaction, so we can use a fixed
+		// nonce.
+		let mut chacha = ChaCha20Poly1305RFC::new(commitment_txid.as_byte_array(), &[0; 12], b"");
+		chacha.encrypt(&plaintext, ciphertext, tag);
+		Self { locator: Locator::from_txid(commitment_txid), encrypted_blob }
+	}
+
+	/// Decrypts the justice transaction using the txid of the revoked commitment transaction,
+	/// failing if it doesn't match or the result doesn't spend the commitment transaction.
+	pub fn decrypt(&self, commitment_txid: &Txid) -> Result<Transaction, DecodeError> {
+		if self.locator != Locator::from_txid(commitment_txid) {
+			return Err(DecodeError::InvalidValue);
+		}
+		decrypt_justice_tx(commitment_txid, &self.encrypted_blob)
+	}
+}
+
+fn decrypt_justice_tx(commitment_txid: &Txid, encrypted_blob: &[u8]) -> Result<Transaction, DecodeError> {
+	if encrypted_blob.len() < TAG_LEN {
+		return Err(DecodeError::InvalidValue);
+	}
+	let (ciphertext, tag) = encrypted_blob.split_at(encrypted_blob.len() - TAG_LEN);
+	let mut plaintext = vec![0; ciphertext.len()];
+	let mut chacha = ChaCha20Poly1305RFC::new(commitment_txid.as_byte_array(), &[0; 12], b"");
+	chacha.variable_time_decrypt(ciphertext, &mut plaintext, tag)
+		.map_err(|()| DecodeError::InvalidValue)?;
+	let justice_tx: Transaction = Readable::read(&mut &plaintext[..])?;
+	if !justice_tx.input.iter().any(|input| input.previous_output.txid == *commitment_txid) {
+		return Err(DecodeError::InvalidValue);
+	}
+	Ok(justice_tx)
+}
+
+/// Delivers [`Appointment`]s from a [`WatchtowerClient`] to one or more [`Watchtower`]s.
+pub trait WatchtowerTransport {
+	/// Hands the given [`Appointment`] to the tower(s).
+	///
+	/// If `Err` is returned, the [`Appointment`] is kept and delivery will be retried on the next
+	/// call to [`WatchtowerClient::retry_pending_appointments`].
+	fn send_appointment(&self, appointment: &Appointment) -> Result<(), ()>;
+}
+
+/// The data needed to sign a justice transaction once its commitment transaction is revoked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct UnsignedJusticeTx {
+	funding_outpoint: OutPoint,
+	justice_tx: Transaction,
+	value: u64,
+	commitment_number: u64,
+}
+
+impl_writeable_tlv_based!(UnsignedJusticeTx, {
+	(0, funding_outpoint, required),
+	(2, justice_tx, required),
+	(4, value, required),
+	(6, commitment_number, required),
+});
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ClientState {
+	/// Justice transactions for counterparty commitment transactions which haven't been revoked
+	/// yet, in the order we learned about them.
+	unsigned_justice_txs: Vec<UnsignedJusticeTx>,
+	/// Appointments the [`WatchtowerTransport`] failed to deliver.
+	pending_appointments: Vec<Appointment>,
+}
+
+impl_writeable_tlv_based!(ClientState, {
+	(0, unsigned_justice_txs, required_vec),
+	(2, pending_appointments, required_vec),
+});
+
+/// A [`Persist`] implementation which wraps another one, handing a pre-signed justice transaction
+/// for each revoked counterparty commitment transaction to a watchtower.
+///
+/// Justice transactions only claim the revoked counterparty's `to_local` output, paying the
+/// [`ConfirmationTarget::OnChainSweep`] feerate at the time the counterparty commitment
+/// transaction was created to the given `destination_script`. Revoked HTLC outputs are left for
+/// our own [`ChannelMonitor`] to claim.
+///
+/// Its state, including [`Appointment`]s the [`WatchtowerTransport`] failed to deliver, is
+/// persisted in the given [`KVStore`] and read back in [`Self::new`]. If persisting it fails
+/// alongside a [`ChannelMonitor`] update, [`ChannelMonitorUpdateStatus::UnrecoverableError`] is
+/// returned for the update.
+pub struct WatchtowerClient<P: Deref, T: Deref, F: Deref, K: Deref, L: Deref>
+where
+	T::Target: WatchtowerTransport,
+	F::Target: FeeEstimator,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	persister: P,
+	transport: T,
+	fee_estimator: F,
+	kv_store: K,
+	logger: L,
+	destination_script: ScriptBuf,
+	state: Mutex<ClientState>,
+}
+
+impl<P: Deref, T: Deref, F: Deref, K: Deref, L: Deref> WatchtowerClient<P, T, F, K, L>
+where
+	T::Target: WatchtowerTransport,
+	F::Target: FeeEstimator,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	/// Constructs a new [`WatchtowerClient`], wrapping the given `persister`, and reads any
+	/// previously persisted state from `kv_store`.
+	pub fn new(
+		persister: P, transport: T, fee_estimator: F, kv_store: K, logger: L,
+		destination_script: ScriptBuf,
+	) -> Result<Self, io::Error> {
+		let state = match kv_store.read(WATCHTOWER_CLIENT_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_CLIENT_PERSISTENCE_SECONDARY_NAMESPACE, WATCHTOWER_CLIENT_PERSISTENCE_KEY)
+		{
+			Ok(data) => ClientState::read(&mut io::Cursor::new(data))
+				.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to read WatchtowerClient state"))?,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				ClientState { unsigned_justice_txs: Vec::new(), pending_appointments: Vec::new() }
+			},
+			Err(e) => return Err(e),
+		};
+		Ok(Self {
+			persister, transport, fee_estimator, kv_store, logger, destination_script,
+			state: Mutex::new(state),
+		})
+	}
+
+	/// Retries delivering any [`Appointment`]s the [`WatchtowerTransport`] previously failed to
+	/// deliver.
+	///
+	/// This should be called regularly, e.g., once a minute.
+	///
+	/// Returns `Err` on persistence failure, in which case the call may be safely retried.
+	pub fn retry_pending_appointments(&self) -> Result<(), io::Error> {
+		let mut state = self.state.lock().unwrap();
+		if state.pending_appointments.is_empty() {
+			return Ok(());
+		}
+		self.send_pending_appointments(&mut state);
+		self.persist_state(&state).map_err(|e| {
+			log_error!(self.logger, "Error persisting WatchtowerClient: {:?}", e);
+			e
+		})
+	}
+
+	/// Returns the number of [`Appointment`]s the [`WatchtowerTransport`] failed to deliver which
+	/// are pending a retry.
+	pub fn pending_appointment_count(&self) -> usize {
+		self.state.lock().unwrap().pending_appointments.len()
+	}
+
+	fn build_unsigned_justice_tx(
+		&self, funding_outpoint: OutPoint, commitment_tx: &CommitmentTransaction,
+	) -> Option<UnsignedJusticeTx> {
+		let trusted_tx = commitment_tx.trust();
+		let output_idx = trusted_tx.revokeable_output_index()?;
+		let value = trusted_tx.built_transaction().transaction.output[output_idx].value;
+		let feerate_per_kw = cmp::max(FEERATE_FLOOR_SATS_PER_KW,
+			self.fee_estimator.get_est_sat_per_1000_weight(ConfirmationTarget::OnChainSweep));
+		match trusted_tx.build_to_local_justice_tx(feerate_per_kw as u64, self.destination_script.clone()) {
+			Ok(justice_tx) => Some(UnsignedJusticeTx {
+				funding_outpoint, justice_tx, value, commitment_number: commitment_tx.commitment_number(),
+			}),
+			Err(()) => {
+				log_debug!(self.logger, "Not building a justice transaction for counterparty commitment {} of channel {} as its to_local output is too small",
+					commitment_tx.commitment_number(), funding_outpoint);
+				None
+			},
+		}
+	}
+
+	/// Signs the justice transactions for any counterparty commitment transactions which were
+	/// revoked, queueing them for delivery. Returns whether our state changed.
+	fn sign_revoked_justice_txs<ChannelSigner: WriteableEcdsaChannelSigner>(
+		&self, state: &mut ClientState, funding_outpoint: OutPoint, monitor: &ChannelMonitor<ChannelSigner>,
+	) -> bool {
+		let mut changed = false;
+		let mut idx = 0;
+		while idx < state.unsigned_justice_txs.len() {
+			let unsigned = &state.unsigned_justice_txs[idx];
+			if unsigned.funding_outpoint != funding_outpoint {
+				idx += 1;
+				continue;
+			}
+			// Counterparty commitment transactions are revoked in order, so once we fail to sign
+			// one, none of the following ones are revoked yet either.
+			let commitment_txid = unsigned.justice_tx.input[0].previous_output.txid;
+			match monitor.sign_to_local_justice_tx(unsigned.justice_tx.clone(), 0, unsigned.value, unsigned.commitment_number) {
+				Ok(signed_justice_tx) => {
+					log_debug!(self.logger, "Handing justice transaction {} for revoked commitment transaction {} of channel {} to our watchtower",
+						signed_justice_tx.txid(), commitment_txid, funding_outpoint);
+					state.pending_appointments.push(Appointment::new(&commitment_txid, &signed_justice_tx));
+					state.unsigned_justice_txs.remove(idx);
+					changed = true;
+				},
+				Err(()) => break,
+			}
+		}
+		changed
+	}
+
+	fn send_pending_appointments(&self, state: &mut ClientState) {
+		state.pending_appointments.retain(|appointment| {
+			match self.transport.send_appointment(appointment) {
+				Ok(()) => false,
+				Err(()) => {
+					log_warn!(self.logger, "Failed to deliver appointment {} to our watchtower, will retry",
+						appointment.locator.0.as_hex());
+					true
+				},
+			}
+		});
+	}
+
+	fn persist_state(&self, state: &ClientState) -> Result<(), io::Error> {
+		self.kv_store.write(WATCHTOWER_CLIENT_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_CLIENT_PERSISTENCE_SECONDARY_NAMESPACE, WATCHTOWER_CLIENT_PERSISTENCE_KEY,
+			&state.encode())
+	}
+
+	/// Persists our state after it changed alongside a [`ChannelMonitor`] update with the given
+	/// status, failing the update if we fail to persist as we'd otherwise lose track of the
+	/// justice transactions for it.
+	fn persist_state_for_update(
+		&self, state: &ClientState, status: ChannelMonitorUpdateStatus,
+	) -> ChannelMonitorUpdateStatus {
+		match self.persist_state(state) {
+			Ok(()) => status,
+			Err(e) => {
+				log_error!(self.logger, "Error persisting WatchtowerClient: {:?}", e);
+				ChannelMonitorUpdateStatus::UnrecoverableError
+			},
+		}
+	}
+}
+
+impl<ChannelSigner: WriteableEcdsaChannelSigner, P: Deref, T: Deref, F: Deref, K: Deref, L: Deref>
+	Persist<ChannelSigner> for WatchtowerClient<P, T, F, K, L>
+where
+	P::Target: Persist<ChannelSigner>,
+	T::Target: WatchtowerTransport,
+	F::Target: FeeEstimator,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	fn persist_new_channel(
+		&self, funding_outpoint: OutPoint, monitor: &ChannelMonitor<ChannelSigner>, update_id: MonitorUpdateId,
+	) -> ChannelMonitorUpdateStatus {
+		let res = self.persister.persist_new_channel(funding_outpoint, monitor, update_id);
+
+		if let Some(commitment_tx) = monitor.initial_counterparty_commitment_tx() {
+			if let Some(unsigned) = self.build_unsigned_justice_tx(funding_outpoint, &commitment_tx) {
+				let mut state = self.state.lock().unwrap();
+				state.unsigned_justice_txs.push(unsigned);
+				return self.persist_state_for_update(&state, res);
+			}
+		}
+		res
+	}
+
+	fn update_persisted_channel(
+		&self, funding_outpoint: OutPoint, update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<ChannelSigner>, update_id: MonitorUpdateId,
+	) -> ChannelMonitorUpdateStatus {
+		let res = self.persister.update_persisted_channel(funding_outpoint, update, monitor, update_id);
+
+		let update = match update {
+			Some(update) => update,
+			None => return res,
+		};
+		let mut state = self.state.lock().unwrap();
+		let mut changed = false;
+		for commitment_tx in monitor.counterparty_commitment_txs_from_update(update) {
+			if let Some(unsigned) = self.build_unsigned_justice_tx(funding_outpoint, &commitment_tx) {
+				state.unsigned_justice_txs.push(unsigned);
+				changed = true;
+			}
+		}
+		changed |= self.sign_revoked_justice_txs(&mut state, funding_outpoint, monitor);
+		if monitor.get_latest_update_id() == CLOSED_CHANNEL_UPDATE_ID {
+			// Once the channel is closed no further commitment transactions will be revoked.
+			let prev_len = state.unsigned_justice_txs.len();
+			state.unsigned_justice_txs.retain(|unsigned| unsigned.funding_outpoint != funding_outpoint);
+			changed |= state.unsigned_justice_txs.len() != prev_len;
+		}
+		if !state.pending_appointments.is_empty() {
+			self.send_pending_appointments(&mut state);
+			changed = true;
+		}
+		if changed {
+			return self.persist_state_for_update(&state, res);
+		}
+		res
+	}
+
+	fn get_and_clear_completed_updates(&self) -> Vec<(OutPoint, MonitorUpdateId, ChannelMonitorUpdateStatus)> {
+		self.persister.get_and_clear_completed_updates()
+	}
+
+	fn register_completion_notifier(&self, notifier: PersistCompletionNotifier) {
+		self.persister.register_completion_notifier(notifier)
+	}
+}
+
+/// How a justice transaction's claim on the revoked output was resolved on chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct JusticeResolution {
+	spending_txid: Txid,
+	confirmation_height: u32,
+	confirmation_hash: BlockHash,
+}
+
+impl_writeable_tlv_based!(JusticeResolution, {
+	(0, spending_txid, required),
+	(2, confirmation_height, required),
+	(4, confirmation_hash, required),
+});
+
+/// A justice transaction we broadcast after its revoked commitment transaction confirmed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PendingJusticeTx {
+	commitment_txid: Txid,
+	justice_tx: Transaction,
+	confirmation_height: u32,
+	confirmation_hash: BlockHash,
+	/// Set once the revoked output was spent, either by our justice transaction or a conflicting
+	/// one.
+	resolution: Option<JusticeResolution>,
+}
+
+impl_writeable_tlv_based!(PendingJusticeTx, {
+	(0, commitment_txid, required),
+	(2, justice_tx, required),
+	(4, confirmation_height, required),
+	(6, confirmation_hash, required),
+	(8, resolution, option),
+});
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TowerState {
+	pending_justice_txs: Vec<PendingJusticeTx>,
+}
+
+impl_writeable_tlv_based!(TowerState, {
+	(0, pending_justice_txs, required_vec),
+});
+
+/// The [`Appointment`]s with a given [`Locator`], as persisted by a [`Watchtower`].
+struct LocatorAppointments {
+	locator: Locator,
+	encrypted_blobs: Vec<Vec<u8>>,
+}
+
+impl_writeable_tlv_based!(LocatorAppointments, {
+	(0, locator, required),
+	(2, encrypted_blobs, required_vec),
+});
+
+/// A watchtower which stores [`Appointment`]s handed to it by [`WatchtowerClient`]s and
+/// broadcasts the matching justice transaction once a revoked commitment transaction confirms.
+///
+/// The justice transaction is rebroadcast on every block until the revoked output has been spent
+/// and that spend reached [`ANTI_REORG_DELAY`] confirmations, at which point the [`Appointment`]s
+/// for the commitment transaction are dropped.
+///
+/// As revoked commitment transactions can't be known in advance, the [`Watchtower`] needs to be
+/// provided with all transactions of each block, either via [`Listen::block_connected`] or
+/// [`Confirm::transactions_confirmed`]. Appointments are persisted in the given [`KVStore`] as
+/// they are received and read back in [`Self::new`].
+pub struct Watchtower<B: Deref, K: Deref, L: Deref>
+where
+	B::Target: BroadcasterInterface,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	appointments: Mutex<HashMap<Locator, Vec<Vec<u8>>>>,
+	state: Mutex<TowerState>,
+	broadcaster: B,
+	kv_store: K,
+	logger: L,
+}
+
+impl<B: Deref, K: Deref, L: Deref> Watchtower<B, K, L>
+where
+	B::Target: BroadcasterInterface,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	/// Constructs a new [`Watchtower`], reading any previously persisted appointments and state
+	/// from `kv_store`.
+	pub fn new(broadcaster: B, kv_store: K, logger: L) -> Result<Self, io::Error> {
+		let invalid_data = || io::Error::new(io::ErrorKind::InvalidData, "Failed to read Watchtower state");
+		let state = match kv_store.read(WATCHTOWER_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_PERSISTENCE_SECONDARY_NAMESPACE, WATCHTOWER_PERSISTENCE_KEY)
+		{
+			Ok(data) => TowerState::read(&mut io::Cursor::new(data)).map_err(|_| invalid_data())?,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => TowerState { pending_justice_txs: Vec::new() },
+			Err(e) => return Err(e),
+		};
+
+		let mut appointments = HashMap::new();
+		for key in kv_store.list(WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE)?
+		{
+			let data = kv_store.read(WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE,
+				WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE, &key)?;
+			let stored = LocatorAppointments::read(&mut io::Cursor::new(data)).map_err(|_| invalid_data())?;
+			appointments.insert(stored.locator, stored.encrypted_blobs);
+		}
+
+		Ok(Self {
+			appointments: Mutex::new(appointments),
+			state: Mutex::new(state),
+			broadcaster,
+			kv_store,
+			logger,
+		})
+	}
+
+	/// Stores the given [`Appointment`], to be acted upon once the commitment transaction it
+	/// refers to confirms.
+	///
+	/// Returns an [`io::ErrorKind::InvalidInput`] error if the [`Appointment`] exceeds
+	/// [`MAX_APPOINTMENT_BLOB_SIZE`] or we already hold [`MAX_APPOINTMENTS_PER_LOCATOR`]
+	/// appointments for its [`Locator`]. Any other error indicates a persistence failure, in which
+	/// case the call may be safely retried.
+	pub fn add_appointment(&self, appointment: Appointment) -> Result<(), io::Error> {
+		if appointment.encrypted_blob.len() > MAX_APPOINTMENT_BLOB_SIZE {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "Appointment exceeds the maximum size"));
+		}
+		let mut appointments = self.appointments.lock().unwrap();
+		let encrypted_blobs = appointments.entry(appointment.locator).or_insert_with(Vec::new);
+		if encrypted_blobs.contains(&appointment.encrypted_blob) {
+			return Ok(());
+		}
+		if encrypted_blobs.len() >= MAX_APPOINTMENTS_PER_LOCATOR {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "Too many appointments for the locator"));
+		}
+		encrypted_blobs.push(appointment.encrypted_blob);
+
+		let stored = LocatorAppointments { locator: appointment.locator, encrypted_blobs: encrypted_blobs.clone() };
+		self.kv_store.write(WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE,
+			&appointment.locator.0.as_hex().to_string(), &stored.encode()
+		).map_err(|e| {
+			log_error!(self.logger, "Error persisting Watchtower appointment: {:?}", e);
+			encrypted_blobs.pop();
+			e
+		})
+	}
+
+	/// Returns the number of [`Appointment`]s we currently hold.
+	pub fn appointment_count(&self) -> usize {
+		self.appointments.lock().unwrap().values().map(|blobs| blobs.len()).sum()
+	}
+
+	/// Returns the justice transactions we broadcast which didn't reach [`ANTI_REORG_DELAY`]
+	/// confirmations yet.
+	pub fn pending_justice_txs(&self) -> Vec<Transaction> {
+		self.state.lock().unwrap().pending_justice_txs.iter().map(|p| p.justice_tx.clone()).collect()
+	}
+
+	fn transactions_confirmed_internal(
+		&self, state: &mut TowerState, header: &Header, txdata: &TransactionData, height: u32,
+	) -> bool {
+		let confirmation_hash = header.block_hash();
+		let appointments = self.appointments.lock().unwrap();
+		let mut new_justice_txs = Vec::new();
+		for (_, tx) in txdata.iter() {
+			let txid = tx.txid();
+			for pending in state.pending_justice_txs.iter_mut() {
+				if pending.resolution.is_some() {
+					continue;
+				}
+				let revoked_outpoint = pending.justice_tx.input[0].previous_output;
+				if tx.input.iter().any(|input| input.previous_output == revoked_outpoint) {
+					pending.resolution = Some(JusticeResolution {
+						spending_txid: txid, confirmation_height: height, confirmation_hash,
+					});
+				}
+			}
+
+			let encrypted_blobs = match appointments.get(&Locator::from_txid(&txid)) {
+				Some(encrypted_blobs) => encrypted_blobs,
+				None => continue,
+			};
+			if state.pending_justice_txs.iter().any(|pending| pending.commitment_txid == txid) {
+				continue;
+			}
+			if let Some(justice_tx) = encrypted_blobs.iter()
+				.find_map(|blob| decrypt_justice_tx(&txid, blob).ok())
+			{
+				log_info!(self.logger, "Revoked commitment transaction {} confirmed, broadcasting justice transaction {}",
+					txid, justice_tx.txid());
+				new_justice_txs.push(justice_tx.clone());
+				state.pending_justice_txs.push(PendingJusticeTx {
+					commitment_txid: txid, justice_tx, confirmation_height: height, confirmation_hash,
+					resolution: None,
+				});
+			}
+		}
+
+		if !new_justice_txs.is_empty() {
+			self.broadcaster.broadcast_transactions(&new_justice_txs.iter().collect::<Vec<_>>());
+		}
+		!new_justice_txs.is_empty() ||
+			state.pending_justice_txs.iter().any(|p| p.resolution.as_ref().map_or(false, |r| r.confirmation_hash == confirmation_hash))
+	}
+
+	fn best_block_updated_internal(&self, state: &mut TowerState, height: u32) {
+		let mut resolved_commitment_txids = Vec::new();
+		state.pending_justice_txs.retain(|pending| {
+			if let Some(resolution) = &pending.resolution {
+				if height >= resolution.confirmation_height + ANTI_REORG_DELAY - 1 {
+					log_debug!(self.logger, "Revoked output of commitment transaction {} was irrevocably spent by {}",
+						pending.commitment_txid, resolution.spending_txid);
+					resolved_commitment_txids.push(pending.commitment_txid);
+					return false;
+				}
+			}
+			true
+		});
+
+		if !resolved_commitment_txids.is_empty() {
+			let mut appointments = self.appointments.lock().unwrap();
+			for commitment_txid in resolved_commitment_txids {
+				let locator = Locator::from_txid(&commitment_txid);
+				appointments.remove(&locator);
+				self.kv_store.remove(WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE,
+					WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE,
+					&locator.0.as_hex().to_string(), true
+				).unwrap_or_else(|e| {
+					log_error!(self.logger, "Error removing Watchtower appointment: {:?}", e);
+				});
+			}
+		}
+
+		let unresolved_justice_txs: Vec<&Transaction> = state.pending_justice_txs.iter()
+			.filter(|pending| pending.resolution.is_none())
+			.map(|pending| &pending.justice_tx)
+			.collect();
+		if !unresolved_justice_txs.is_empty() {
+			self.broadcaster.broadcast_transactions(&unresolved_justice_txs);
+		}
+	}
+
+	fn unconfirm_transactions(&self, state: &mut TowerState, unconfirmed: impl Fn(&Txid, &BlockHash) -> bool) {
+		// If the revoked commitment transaction was reorged out, the appointment is still around
+		// in case it confirms again.
+		state.pending_justice_txs.retain(|pending| !unconfirmed(&pending.commitment_txid, &pending.confirmation_hash));
+		for pending in state.pending_justice_txs.iter_mut() {
+			if pending.resolution.as_ref().map_or(false, |r| unconfirmed(&r.spending_txid, &r.confirmation_hash)) {
+				pending.resolution = None;
+			}
+		}
+	}
+
+	fn persist_state(&self, state: &TowerState) -> Result<(), io::Error> {
+		self.kv_store.write(WATCHTOWER_PERSISTENCE_PRIMARY_NAMESPACE,
+			WATCHTOWER_PERSISTENCE_SECONDARY_NAMESPACE, WATCHTOWER_PERSISTENCE_KEY, &state.encode())
+	}
+}
+
+impl<B: Deref, K: Deref, L: Deref> Listen for Watchtower<B, K, L>
+where
+	B::Target: BroadcasterInterface,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
+		let mut state = self.state.lock().unwrap();
+		self.transactions_confirmed_internal(&mut state, header, txdata, height);
+		self.best_block_updated_internal(&mut state, height);
+		self.persist_state(&state).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting Watchtower: {:?}", e);
+		});
+	}
+
+	fn block_disconnected(&self, header: &Header, _height: u32) {
+		let mut state = self.state.lock().unwrap();
+		let block_hash = header.block_hash();
+		self.unconfirm_transactions(&mut state, |_, confirmation_hash| *confirmation_hash == block_hash);
+		self.persist_state(&state).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting Watchtower: {:?}", e);
+		});
+	}
+}
+
+impl<B: Deref, K: Deref, L: Deref> Confirm for Watchtower<B, K, L>
+where
+	B::Target: BroadcasterInterface,
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	fn transactions_confirmed(&self, header: &Header, txdata: &TransactionData, height: u32) {
+		let mut state = self.state.lock().unwrap();
+		if self.transactions_confirmed_internal(&mut state, header, txdata, height) {
+			self.persist_state(&state).unwrap_or_else(|e| {
+				log_error!(self.logger, "Error persisting Watchtower: {:?}", e);
+			});
+		}
+	}
+
+	fn transaction_unconfirmed(&self, txid: &Txid) {
+		let mut state = self.state.lock().unwrap();
+		self.unconfirm_transactions(&mut state, |unconfirmed_txid, _| unconfirmed_txid == txid);
+		self.persist_state(&state).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting Watchtower: {:?}", e);
+		});
+	}
+
+	fn best_block_updated(&self, _header: &Header, height: u32) {
+		let mut state = self.state.lock().unwrap();
+		self.best_block_updated_internal(&mut state, height);
+		self.persist_state(&state).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting Watchtower: {:?}", e);
+		});
+	}
+
+	fn get_relevant_txids(&self) -> Vec<(Txid, u32, Option<BlockHash>)> {
+		let state = self.state.lock().unwrap();
+		let mut relevant_txids = Vec::new();
+		for pending in state.pending_justice_txs.iter() {
+			relevant_txids.push((pending.commitment_txid, pending.confirmation_height, Some(pending.confirmation_hash)));
+			if let Some(resolution) = &pending.resolution {
+				relevant_txids.push((resolution.spending_txid, resolution.confirmation_height, Some(resolution.confirmation_hash)));
+			}
+		}
+		relevant_txids
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bitcoin::blockdata::constants::genesis_block;
+	use bitcoin::network::constants::Network;
+
+	use crate::chain::Confirm;
+	use crate::chain::channelmonitor::ANTI_REORG_DELAY;
+	use crate::io;
+	use crate::ln::functional_test_utils::*;
+	use crate::sign::SignerProvider;
+	use crate::sync::Mutex;
+	use crate::util::test_utils::{TestBroadcaster, TestLogger, TestStore};
+	use crate::{check_spends, get_local_commitment_txn};
+	use crate::prelude::*;
+
+	use super::{Appointment, Locator, MAX_APPOINTMENT_BLOB_SIZE, Watchtower, WatchtowerClient, WatchtowerTransport};
+
+	use core::sync::atomic::{AtomicBool, Ordering};
+
+	struct TestTransport {
+		appointments: Mutex<Vec<Appointment>>,
+		fail: AtomicBool,
+	}
+
+	impl TestTransport {
+		fn new() -> Self {
+			Self { appointments: Mutex::new(Vec::new()), fail: AtomicBool::new(false) }
+		}
+	}
+
+	impl WatchtowerTransport for TestTransport {
+		fn send_appointment(&self, appointment: &Appointment) -> Result<(), ()> {
+			if self.fail.load(Ordering::Acquire) {
+				return Err(());
+			}
+			self.appointments.lock().unwrap().push(appointment.clone());
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_appointment_encryption() {
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let (_, _, channel_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+		let commitment_tx = get_local_commitment_txn!(nodes[0], channel_id).remove(0);
+
+		// A "justice transaction" only needs to spend the commitment transaction to be accepted.
+		let mut justice_tx = commitment_tx.clone();
+		justice_tx.input[0].previous_output.txid = commitment_tx.txid();
+		let appointment = Appointment::new(&commitment_tx.txid(), &justice_tx);
+		assert_eq!(appointment.locator, Locator::from_txid(&commitment_tx.txid()));
+		assert_eq!(appointment.decrypt(&commitment_tx.txid()).unwrap(), justice_tx);
+		assert!(appointment.decrypt(&funding_tx.txid()).is_err());
+
+		// Transactions which don't spend the commitment transaction are rejected.
+		let appointment = Appointment::new(&commitment_tx.txid(), &commitment_tx);
+		assert!(appointment.decrypt(&commitment_tx.txid()).is_err());
+	}
+
+	#[test]
+	fn test_watchtower_broadcasts_justice_tx() {
+		// nodes[1] hands justice transactions to a tower via a WatchtowerClient. Once nodes[0]
+		// broadcasts a revoked commitment transaction, the tower broadcasts the justice transaction.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let transports = [TestTransport::new(), TestTransport::new()];
+		let client_stores = [TestStore::new(false), TestStore::new(false)];
+		let clients: Vec<_> = (0..2).map(|i| {
+			let destination_script = chanmon_cfgs[i].keys_manager.get_destination_script([0; 32]).unwrap();
+			WatchtowerClient::new(&chanmon_cfgs[i].persister, &transports[i], &chanmon_cfgs[i].fee_estimator,
+				&client_stores[i], &chanmon_cfgs[i].logger, destination_script).unwrap()
+		}).collect();
+		let (client, transport) = (&clients[1], &transports[1]);
+		let node_cfgs = create_node_cfgs_with_persisters(2, &chanmon_cfgs, clients.iter().collect());
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let (_, _, channel_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+		let revoked_commitment_tx = get_local_commitment_txn!(nodes[0], channel_id).remove(0);
+		assert!(transport.appointments.lock().unwrap().iter()
+			.all(|appointment| appointment.locator != Locator::from_txid(&revoked_commitment_tx.txid())));
+
+		// If the transport fails, the appointments are retried later. The payment revokes two of
+		// nodes[0]'s commitment transactions, one when adding and one when claiming the HTLC.
+		transport.fail.store(true, Ordering::Release);
+		send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+		assert_eq!(client.pending_appointment_count(), 2);
+		transport.fail.store(false, Ordering::Release);
+		client.retry_pending_appointments().unwrap();
+		assert_eq!(client.pending_appointment_count(), 0);
+
+		let tower_broadcaster = TestBroadcaster::new(Network::Testnet);
+		let tower_store = TestStore::new(false);
+		let tower_logger = TestLogger::new();
+		let tower = Watchtower::new(&tower_broadcaster, &tower_store, &tower_logger).unwrap();
+		let appointments = transport.appointments.lock().unwrap().clone();
+		assert!(!appointments.is_empty());
+		for appointment in appointments.iter() {
+			tower.add_appointment(appointment.clone()).unwrap();
+		}
+
+		// Invalid appointments are rejected, as are appointments we fail to persist.
+		let oversized_appointment = Appointment {
+			locator: appointments[0].locator, encrypted_blob: vec![0; MAX_APPOINTMENT_BLOB_SIZE + 1],
+		};
+		assert_eq!(tower.add_appointment(oversized_appointment).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+		let read_only_store = TestStore::new(true);
+		let read_only_tower = Watchtower::new(&tower_broadcaster, &read_only_store, &tower_logger).unwrap();
+		assert!(read_only_tower.add_appointment(appointments[0].clone()).is_err());
+		assert_eq!(read_only_tower.appointment_count(), 0);
+
+		// Appointments survive a restart of the tower.
+		let tower = Watchtower::new(&tower_broadcaster, &tower_store, &tower_logger).unwrap();
+		let appointment_count = tower.appointment_count();
+
+		let genesis_hash = genesis_block(Network::Testnet).header.block_hash();
+		let header = create_dummy_header(genesis_hash, 42);
+		tower.transactions_confirmed(&header, &[(0, &funding_tx)], 1);
+		assert!(tower_broadcaster.txn_broadcasted.lock().unwrap().is_empty());
+
+		let header = create_dummy_header(header.block_hash(), 42);
+		tower.transactions_confirmed(&header, &[(0, &revoked_commitment_tx)], 2);
+		let justice_tx = {
+			let mut txn = tower_broadcaster.txn_broadcasted.lock().unwrap();
+			assert_eq!(txn.len(), 1);
+			txn.pop().unwrap()
+		};
+		check_spends!(justice_tx, revoked_commitment_tx);
+		assert_eq!(tower.pending_justice_txs(), vec![justice_tx.clone()]);
+
+		// The justice transaction is rebroadcast until it confirms.
+		tower.best_block_updated(&header, 2);
+		assert_eq!(*tower_broadcaster.txn_broadcasted.lock().unwrap(), vec![justice_tx.clone()]);
+		tower_broadcaster.txn_broadcasted.lock().unwrap().clear();
+
+		let header = create_dummy_header(header.block_hash(), 42);
+		tower.transactions_confirmed(&header, &[(0, &justice_tx)], 3);
+		tower.best_block_updated(&header, 3);
+		assert!(tower_broadcaster.txn_broadcasted.lock().unwrap().is_empty());
+		assert_eq!(tower.pending_justice_txs().len(), 1);
+
+		// Once the justice transaction is irrevocably confirmed we drop the appointment.
+		tower.best_block_updated(&header, 3 + ANTI_REORG_DELAY - 1);
+		assert!(tower.pending_justice_txs().is_empty());
+		assert_eq!(tower.appointment_count(), appointment_count - 1);
+		let tower = Watchtower::new(&tower_broadcaster, &tower_store, &tower_logger).unwrap();
+		assert_eq!(tower.appointment_count(), appointment_count - 1);
+	}
+
+	#[test]
+	fn test_watchtower_unconfirmed_commitment() {
+		// If the revoked commitment transaction is reorged out, we stop broadcasting the justice
+		// transaction but keep the appointment in case it confirms again.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let transports = [TestTransport::new(), TestTransport::new()];
+		let client_stores = [TestStore::new(false), TestStore::new(false)];
+		let clients: Vec<_> = (0..2).map(|i| {
+			let destination_script = chanmon_cfgs[i].keys_manager.get_destination_script([0; 32]).unwrap();
+			WatchtowerClient::new(&chanmon_cfgs[i].persister, &transports[i], &chanmon_cfgs[i].fee_estimator,
+				&client_stores[i], &chanmon_cfgs[i].logger, destination_script).unwrap()
+		}).collect();
+		let transport = &transports[1];
+		let node_cfgs = create_node_cfgs_with_persisters(2, &chanmon_cfgs, clients.iter().collect());
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let (_, _, channel_id, _) = create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+		let revoked_commitment_tx = get_local_commitment_txn!(nodes[0], channel_id).remove(0);
+		send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+
+		let tower_broadcaster = TestBroadcaster::new(Network::Testnet);
+		let tower_store = TestStore::new(false);
+		let tower_logger = TestLogger::new();
+		let tower = Watchtower::new(&tower_broadcaster, &tower_store, &tower_logger).unwrap();
+		for appointment in transport.appointments.lock().unwrap().drain(..) {
+			tower.add_appointment(appointment).unwrap();
+		}
+		let appointment_count = tower.appointment_count();
+
+		let genesis_hash = genesis_block(Network::Testnet).header.block_hash();
+		let header = create_dummy_header(genesis_hash, 42);
+		tower.transactions_confirmed(&header, &[(0, &revoked_commitment_tx)], 1);
+		assert_eq!(tower.pending_justice_txs().len(), 1);
+		assert_eq!(tower.get_relevant_txids(), vec![(revoked_commitment_tx.txid(), 1, Some(header.block_hash()))]);
+
+		tower.transaction_unconfirmed(&revoked_commitment_tx.txid());
+		assert!(tower.pending_justice_txs().is_empty());
+		assert_eq!(tower.appointment_count(), appointment_count);
+		tower_broadcaster.txn_broadcasted.lock().unwrap().clear();
+		tower.best_block_updated(&header, 10);
+		assert!(tower_broadcaster.txn_broadcasted.lock().unwrap().is_empty());
+
+		tower.transactions_confirmed(&header, &[(0, &revoked_commitment_tx)], 1);
+		assert_eq!(tower.pending_justice_txs().len(), 1);
+	}
+}
@@ -14,14 +14,15 @@
 use alloc::collections::BTreeMap;
 use core::ops::Deref;
 
-use crate::chain::chaininterface::{BroadcasterInterface, fee_for_weight};
+use crate::chain::chaininterface::{
+	BroadcasterInterface, TRUC_CHILD_MAX_WEIGHT, TRUC_MAX_WEIGHT, fee_for_weight
+};
 use crate::chain::ClaimId;
 use crate::io_extras::sink;
-use crate::ln::channel::ANCHOR_OUTPUT_VALUE_SATOSHI;
 use crate::ln::chan_utils;
 use crate::ln::chan_utils::{
 	ANCHOR_INPUT_WITNESS_WEIGHT, HTLC_SUCCESS_INPUT_ANCHOR_WITNESS_WEIGHT,
-	HTLC_TIMEOUT_INPUT_ANCHOR_WITNESS_WEIGHT, HTLCOutputInCommitment
+	HTLC_TIMEOUT_INPUT_ANCHOR_WITNESS_WEIGHT, HTLCOutputInCommitment, SHARED_ANCHOR_INPUT_WITNESS_WEIGHT
 };
 use crate::prelude::*;
 use crate::sign::{
@@ -46,6 +47,11 @@ const BASE_INPUT_SIZE: u64 = 32 /* txid */ + 4 /* vout */ + 4 /* sequence */;
 
 const BASE_INPUT_WEIGHT: u64 = BASE_INPUT_SIZE * WITNESS_SCALE_FACTOR as u64;
 
+/// The weight of a P2WSH or P2TR output, the largest of the standard outputs a change output may
+/// be.
+const MAX_CHANGE_OUTPUT_WEIGHT: u64 =
+	(8 /* value */ + 1 /* script len */ + 34 /* script */) * WITNESS_SCALE_FACTOR as u64;
+
 /// A descriptor used to sign for a commitment transaction's anchor output.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AnchorDescriptor {
@@ -54,16 +60,33 @@ pub struct AnchorDescriptor {
 	/// The transaction input's outpoint corresponding to the commitment transaction's anchor
 	/// output.
 	pub outpoint: OutPoint,
+	/// The value of the commitment transaction's anchor output. This is always
+	/// [`ANCHOR_OUTPUT_VALUE_SATOSHI`] unless the channel uses zero-fee commitments, whose shared
+	/// anchor output carries any value not assigned to the other outputs.
+	///
+	/// [`ANCHOR_OUTPUT_VALUE_SATOSHI`]: crate::ln::channel::ANCHOR_OUTPUT_VALUE_SATOSHI
+	pub value_satoshis: u64,
 }
 
 impl AnchorDescriptor {
+	/// Returns whether the anchor output is the shared anchor output of a zero-fee commitment
+	/// transaction. Such outputs don't require a signature to be spent, i.e.
+	/// [`Self::tx_input_witness`] must not be used, and their spending transaction must be a
+	/// version 3 (TRUC) transaction.
+	pub fn is_shared_anchor(&self) -> bool {
+		self.channel_derivation_parameters.transaction_parameters.channel_type_features
+			.supports_anchor_zero_fee_commitments()
+	}
+
 	/// Returns the UTXO to be spent by the anchor input, which can be obtained via
 	/// [`Self::unsigned_tx_input`].
 	pub fn previous_utxo(&self) -> TxOut {
-		TxOut {
-			script_pubkey: self.witness_script().to_v0_p2wsh(),
-			value: ANCHOR_OUTPUT_VALUE_SATOSHI,
-		}
+		let script_pubkey = if self.is_shared_anchor() {
+			chan_utils::get_shared_anchor_script_pubkey()
+		} else {
+			self.witness_script().to_v0_p2wsh()
+		};
+		TxOut { script_pubkey, value: self.value_satoshis }
 	}
 
 	/// Returns the unsigned transaction input spending the anchor output in the commitment
@@ -126,6 +149,13 @@ pub enum BumpTransactionEvent {
 	/// provided to [`build_anchor_input_witness`] along with the `funding_pubkey` to obtain the
 	/// full witness required to spend.
 	///
+	/// For channels using zero-fee commitments (see
+	/// [`AnchorDescriptor::is_shared_anchor`]), the commitment transaction pays no fee at all and
+	/// has a single shared anchor output which is spent with an empty witness, so no signature is
+	/// required. Both the commitment and child anchor transactions are version 3 (TRUC)
+	/// transactions, so the child must not exceed [`TRUC_CHILD_MAX_WEIGHT`] and the package must
+	/// be submitted to a node supporting package relay.
+	///
 	/// It is possible to receive more than one instance of this event if a valid child anchor
 	/// transaction is never broadcast or is but not with a sufficient fee to be mined. Care should
 	/// be taken by the consumer of the event to ensure any future iterations of the child anchor
@@ -325,6 +355,10 @@ pub trait CoinSelectionSource {
 	///    provided, in which case a zero-value empty OP_RETURN output can be used instead.
 	/// 3. Enough inputs must be selected/contributed for the resulting transaction (including the
 	///    inputs and outputs noted above) to meet `target_feerate_sat_per_1000_weight`.
+	/// 4. The resulting transaction, including any change output and with the weight of the
+	///    `must_spend` inputs based on their [`Input::satisfaction_weight`], must not exceed
+	///    `max_tx_weight`. This is used to keep version 3 (TRUC) transactions within the weight
+	///    allowed by mempool policy, and is [`u64::MAX`] if the transaction isn't limited.
 	///
 	/// Implementations must take note that [`Input::satisfaction_weight`] only tracks the weight of
 	/// the input's `script_sig` and `witness`. Some wallets, like Bitcoin Core's, may require
@@ -340,7 +374,7 @@ pub trait CoinSelectionSource {
 	/// set of other claims being double spent to a minimum.
 	fn select_confirmed_utxos(
 		&self, claim_id: ClaimId, must_spend: Vec<Input>, must_pay_to: &[TxOut],
-		target_feerate_sat_per_1000_weight: u32,
+		target_feerate_sat_per_1000_weight: u32, max_tx_weight: u64,
 	) -> Result<CoinSelection, ()>;
 	/// Signs and provides the full witness for all inputs within the transaction known to the
 	/// trait (i.e., any provided via [`CoinSelectionSource::select_confirmed_utxos`]).
@@ -402,10 +436,15 @@ where
 	/// `tolerate_high_network_feerates` is set, we'll attempt to spend UTXOs that contribute at
 	/// least 1 satoshi at the current feerate, otherwise, we'll only attempt to spend those which
 	/// contribute at least twice their fee.
+	///
+	/// UTXOs are skipped if spending them would exceed `max_tx_weight`, leaving room for a change
+	/// output. If this leaves us short of the target amount, we retry preferring the largest UTXOs
+	/// to require as few inputs as possible.
 	fn select_confirmed_utxos_internal(
 		&self, utxos: &[Utxo], claim_id: ClaimId, force_conflicting_utxo_spend: bool,
 		tolerate_high_network_feerates: bool, target_feerate_sat_per_1000_weight: u32,
 		preexisting_tx_weight: u64, input_amount_sat: u64, target_amount_sat: u64,
+		max_tx_weight: u64,
 	) -> Result<CoinSelection, ()> {
 		let mut locked_utxos = self.locked_utxos.lock().unwrap();
 		let mut eligible_utxos = utxos.iter().filter_map(|utxo| {
@@ -432,16 +471,36 @@ where
 		}).collect::<Vec<_>>();
 		eligible_utxos.sort_unstable_by_key(|(utxo, _)| utxo.output.value);
 
-		let mut selected_amount = input_amount_sat;
-		let mut total_fees = fee_for_weight(target_feerate_sat_per_1000_weight, preexisting_tx_weight);
-		let mut selected_utxos = Vec::new();
-		for (utxo, fee_to_spend_utxo) in eligible_utxos {
-			if selected_amount >= target_amount_sat + total_fees {
-				break;
+		let max_selected_weight = max_tx_weight
+			.saturating_sub(preexisting_tx_weight.saturating_add(MAX_CHANGE_OUTPUT_WEIGHT));
+		let select_utxos = |eligible_utxos: &mut dyn Iterator<Item = &(&Utxo, u64)>| {
+			let mut selected_amount = input_amount_sat;
+			let mut total_fees = fee_for_weight(target_feerate_sat_per_1000_weight, preexisting_tx_weight);
+			let mut selected_weight = 0;
+			let mut selected_utxos = Vec::new();
+			let mut skipped_for_weight = false;
+			for (utxo, fee_to_spend_utxo) in eligible_utxos {
+				if selected_amount >= target_amount_sat + total_fees {
+					break;
+				}
+				let utxo_weight = BASE_INPUT_WEIGHT + utxo.satisfaction_weight;
+				if selected_weight + utxo_weight > max_selected_weight {
+					skipped_for_weight = true;
+					continue;
+				}
+				selected_amount += utxo.output.value;
+				total_fees += fee_to_spend_utxo;
+				selected_weight += utxo_weight;
+				selected_utxos.push((*utxo).clone());
 			}
-			selected_amount += utxo.output.value;
-			total_fees += fee_to_spend_utxo;
-			selected_utxos.push(utxo.clone());
+			(selected_amount, total_fees, selected_utxos, skipped_for_weight)
+		};
+		let (mut selected_amount, mut total_fees, mut selected_utxos, skipped_for_weight) =
+			select_utxos(&mut eligible_utxos.iter());
+		if selected_amount < target_amount_sat + total_fees && skipped_for_weight {
+			log_trace!(self.logger, "Retrying coin selection with the largest UTXOs first to stay within {} WU",
+				max_tx_weight);
+			(selected_amount, total_fees, selected_utxos, _) = select_utxos(&mut eligible_utxos.iter().rev());
 		}
 		if selected_amount < target_amount_sat + total_fees {
 			log_debug!(self.logger, "Insufficient funds to meet target feerate {} sat/kW",
@@ -482,7 +541,7 @@ where
 {
 	fn select_confirmed_utxos(
 		&self, claim_id: ClaimId, must_spend: Vec<Input>, must_pay_to: &[TxOut],
-		target_feerate_sat_per_1000_weight: u32,
+		target_feerate_sat_per_1000_weight: u32, max_tx_weight: u64,
 	) -> Result<CoinSelection, ()> {
 		let utxos = self.source.list_confirmed_utxos()?;
 		// TODO: Use fee estimation utils when we upgrade to bitcoin v0.30.0.
@@ -503,6 +562,7 @@ where
 			self.select_confirmed_utxos_internal(
 				&utxos, claim_id, force_conflicting_utxo_spend, tolerate_high_network_feerates,
 				target_feerate_sat_per_1000_weight, preexisting_tx_weight, input_amount_sat, target_amount_sat,
+				max_tx_weight,
 			)
 		};
 		do_coin_selection(false, false)
@@ -590,31 +650,44 @@ where
 		// Our commitment transaction already has fees allocated to it, so we should take them into
 		// account. We do so by pretending the commitment tranasction's fee and weight are part of
 		// the anchor input.
+		let anchor_input_witness_weight = if anchor_descriptor.is_shared_anchor() {
+			SHARED_ANCHOR_INPUT_WITNESS_WEIGHT
+		} else {
+			ANCHOR_INPUT_WITNESS_WEIGHT
+		};
 		let mut anchor_utxo = anchor_descriptor.previous_utxo();
 		anchor_utxo.value += commitment_tx_fee_sat;
 		let must_spend = vec![Input {
 			outpoint: anchor_descriptor.outpoint,
 			previous_utxo: anchor_utxo,
-			satisfaction_weight: commitment_tx.weight().to_wu() + ANCHOR_INPUT_WITNESS_WEIGHT + EMPTY_SCRIPT_SIG_WEIGHT,
+			satisfaction_weight: commitment_tx.weight().to_wu() + anchor_input_witness_weight + EMPTY_SCRIPT_SIG_WEIGHT,
 		}];
 		#[cfg(debug_assertions)]
 		let must_spend_amount =	must_spend.iter().map(|input| input.previous_utxo.value).sum::<u64>();
 
+		// As the anchor input's weight includes the commitment transaction's, so must the limit on
+		// the anchor transaction's weight.
+		let max_tx_weight = if anchor_descriptor.is_shared_anchor() {
+			TRUC_CHILD_MAX_WEIGHT + commitment_tx.weight().to_wu()
+		} else {
+			u64::MAX
+		};
+
 		log_debug!(self.logger, "Peforming coin selection for commitment package (commitment and anchor transaction) targeting {} sat/kW",
 			package_target_feerate_sat_per_1000_weight);
 		let coin_selection: CoinSelection = self.utxo_source.select_confirmed_utxos(
-			claim_id, must_spend, &[], package_target_feerate_sat_per_1000_weight,
+			claim_id, must_spend, &[], package_target_feerate_sat_per_1000_weight, max_tx_weight,
 		)?;
 
+		let channel_type = &anchor_descriptor.channel_derivation_parameters.transaction_parameters.channel_type_features;
 		let mut anchor_tx = Transaction {
-			version: 2,
+			version: chan_utils::transaction_version(channel_type),
 			lock_time: LockTime::ZERO, // TODO: Use next best height.
 			input: vec![anchor_descriptor.unsigned_tx_input()],
 			output: vec![],
 		};
 
-		#[cfg(debug_assertions)]
-		let total_satisfaction_weight = ANCHOR_INPUT_WITNESS_WEIGHT + EMPTY_SCRIPT_SIG_WEIGHT +
+		let total_satisfaction_weight = anchor_input_witness_weight + EMPTY_SCRIPT_SIG_WEIGHT +
 			coin_selection.confirmed_utxos.iter().map(|utxo| utxo.satisfaction_weight).sum::<u64>();
 		#[cfg(debug_assertions)]
 		let total_input_amount = must_spend_amount +
@@ -638,23 +711,34 @@ where
 		}
 
 		debug_assert_eq!(anchor_psbt.unsigned_tx.output.len(), 1);
-		#[cfg(debug_assertions)]
 		let unsigned_tx_weight = anchor_psbt.unsigned_tx.weight().to_wu() - (anchor_psbt.unsigned_tx.input.len() as u64 * EMPTY_SCRIPT_SIG_WEIGHT);
+		if anchor_descriptor.is_shared_anchor() && unsigned_tx_weight + total_satisfaction_weight > TRUC_CHILD_MAX_WEIGHT {
+			log_error!(self.logger, "Anchor transaction {} exceeds the maximum weight of a TRUC child transaction",
+				anchor_txid);
+			return Err(());
+		}
 
 		log_debug!(self.logger, "Signing anchor transaction {}", anchor_txid);
 		anchor_tx = self.utxo_source.sign_psbt(anchor_psbt)?;
 
-		let signer = anchor_descriptor.derive_channel_signer(&self.signer_provider);
-		let anchor_sig = signer.sign_holder_anchor_input(&anchor_tx, 0, &self.secp)?;
-		anchor_tx.input[0].witness = anchor_descriptor.tx_input_witness(&anchor_sig);
+		// Shared anchors are spent with an empty witness, so only legacy anchors need a signature.
+		if !anchor_descriptor.is_shared_anchor() {
+			let signer = anchor_descriptor.derive_channel_signer(&self.signer_provider);
+			let anchor_sig = signer.sign_holder_anchor_input(&anchor_tx, 0, &self.secp)?;
+			anchor_tx.input[0].witness = anchor_descriptor.tx_input_witness(&anchor_sig);
+		}
 
 		#[cfg(debug_assertions)] {
 			let signed_tx_weight = anchor_tx.weight().to_wu();
 			let expected_signed_tx_weight = unsigned_tx_weight + total_satisfaction_weight;
 			// Our estimate should be within a 1% error margin of the actual weight and we should
-			// never underestimate.
+			// never underestimate. Anchor transactions spending a shared anchor are small enough
+			// that a few ECDSA signatures being shorter than estimated can exceed that margin, so
+			// we allow for up to two bytes of slack per input as well.
+			let weight_error_margin = core::cmp::max(expected_signed_tx_weight / 100,
+				anchor_tx.input.len() as u64 * 2 * WITNESS_SCALE_FACTOR as u64);
 			assert!(expected_signed_tx_weight >= signed_tx_weight &&
-				expected_signed_tx_weight - (expected_signed_tx_weight / 100) <= signed_tx_weight);
+				expected_signed_tx_weight - weight_error_margin <= signed_tx_weight);
 
 			let expected_package_fee = fee_for_weight(package_target_feerate_sat_per_1000_weight,
 				signed_tx_weight + commitment_tx.weight().to_wu());
@@ -679,8 +763,12 @@ where
 		&self, claim_id: ClaimId, target_feerate_sat_per_1000_weight: u32,
 		htlc_descriptors: &[HTLCDescriptor], tx_lock_time: LockTime,
 	) -> Result<(), ()> {
+		let version = htlc_descriptors.first().map_or(2, |htlc_descriptor| chan_utils::transaction_version(
+			&htlc_descriptor.channel_derivation_parameters.transaction_parameters.channel_type_features
+		));
+		let is_truc = version == 3;
 		let mut htlc_tx = Transaction {
-			version: 2,
+			version,
 			lock_time: tx_lock_time,
 			input: vec![],
 			output: vec![],
@@ -705,17 +793,16 @@ where
 		log_debug!(self.logger, "Peforming coin selection for HTLC transaction targeting {} sat/kW",
 			target_feerate_sat_per_1000_weight);
 
-		#[cfg(debug_assertions)]
 		let must_spend_satisfaction_weight =
 			must_spend.iter().map(|input| input.satisfaction_weight).sum::<u64>();
 		#[cfg(debug_assertions)]
 		let must_spend_amount =	must_spend.iter().map(|input| input.previous_utxo.value).sum::<u64>();
 
+		let max_tx_weight = if is_truc { TRUC_MAX_WEIGHT } else { u64::MAX };
 		let coin_selection: CoinSelection = self.utxo_source.select_confirmed_utxos(
-			claim_id, must_spend, &htlc_tx.output, target_feerate_sat_per_1000_weight,
+			claim_id, must_spend, &htlc_tx.output, target_feerate_sat_per_1000_weight, max_tx_weight,
 		)?;
 
-		#[cfg(debug_assertions)]
 		let total_satisfaction_weight = must_spend_satisfaction_weight +
 			coin_selection.confirmed_utxos.iter().map(|utxo| utxo.satisfaction_weight).sum::<u64>();
 		#[cfg(debug_assertions)]
@@ -741,8 +828,12 @@ where
 			}
 		}
 
-		#[cfg(debug_assertions)]
 		let unsigned_tx_weight = htlc_psbt.unsigned_tx.weight().to_wu() - (htlc_psbt.unsigned_tx.input.len() as u64 * EMPTY_SCRIPT_SIG_WEIGHT);
+		if is_truc && unsigned_tx_weight + total_satisfaction_weight > TRUC_MAX_WEIGHT {
+			log_error!(self.logger, "HTLC transaction {} exceeds the maximum weight of a TRUC transaction",
+				htlc_psbt.unsigned_tx.txid());
+			return Err(());
+		}
 
 		log_debug!(self.logger, "Signing HTLC transaction {}", htlc_psbt.unsigned_tx.txid());
 		htlc_tx = self.utxo_source.sign_psbt(htlc_psbt)?;
@@ -811,3 +902,57 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::util::test_utils::{TestLogger, TestWalletSource};
+
+	use bitcoin::Txid;
+	use bitcoin::hashes::Hash;
+	use bitcoin::secp256k1::SecretKey;
+
+	#[test]
+	fn test_coin_selection_respects_max_tx_weight() {
+		let wallet_source = TestWalletSource::new(SecretKey::from_slice(&[42; 32]).unwrap());
+		let logger = TestLogger::new();
+		// Smaller UTXOs are preferred, but spending enough of them to meet the target feerate
+		// would exceed the transaction's weight limit, unlike spending the single large one.
+		for vout in 0..4 {
+			wallet_source.add_utxo(OutPoint { txid: Txid::all_zeros(), vout }, 5_000);
+		}
+		let large_utxo = OutPoint { txid: Txid::all_zeros(), vout: 4 };
+		wallet_source.add_utxo(large_utxo, 1_000_000);
+		let wallet = Wallet::new(&wallet_source, &logger);
+
+		let must_spend = vec![Input {
+			outpoint: OutPoint { txid: Txid::all_zeros(), vout: 5 },
+			previous_utxo: TxOut { value: 0, script_pubkey: ScriptBuf::new() },
+			satisfaction_weight: 4_000,
+		}];
+		let preexisting_tx_weight = 2 /* segwit marker & flag */ + BASE_INPUT_WEIGHT + 4_000 +
+			(4 /* version */ + 1 /* input count */ + 1 /* output count */ + 4 /* locktime */) *
+			WITNESS_SCALE_FACTOR as u64;
+		let claim_id = ClaimId([0; 32]);
+
+		let coin_selection = wallet.select_confirmed_utxos(
+			claim_id, must_spend.clone(), &[], 2_500, u64::MAX,
+		).unwrap();
+		assert!(coin_selection.confirmed_utxos.len() > 2);
+		assert!(coin_selection.confirmed_utxos.iter().all(|utxo| utxo.outpoint != large_utxo));
+
+		// Only leave room for two inputs besides the change output.
+		let max_tx_weight = preexisting_tx_weight + MAX_CHANGE_OUTPUT_WEIGHT +
+			2 * (BASE_INPUT_WEIGHT + coin_selection.confirmed_utxos[0].satisfaction_weight);
+		let coin_selection = wallet.select_confirmed_utxos(
+			claim_id, must_spend.clone(), &[], 2_500, max_tx_weight,
+		).unwrap();
+		assert_eq!(coin_selection.confirmed_utxos.len(), 1);
+		assert_eq!(coin_selection.confirmed_utxos[0].outpoint, large_utxo);
+
+		// If the large UTXO doesn't fit either, coin selection fails rather than exceeding the limit.
+		let max_tx_weight = preexisting_tx_weight + MAX_CHANGE_OUTPUT_WEIGHT;
+		assert!(wallet.select_confirmed_utxos(claim_id, must_spend, &[], 2_500, max_tx_weight).is_err());
+	}
+}
@@ -29,7 +29,7 @@ use crate::routing::gossip::NetworkUpdate;
 use crate::util::errors::APIError;
 use crate::util::ser::{BigSize, FixedLengthReader, Writeable, Writer, MaybeReadable, Readable, RequiredWrapper, UpgradableRequired, WithoutLength};
 use crate::util::string::UntrustedString;
-use crate::routing::router::{BlindedTail, Path, RouteHop, RouteParameters};
+use crate::routing::router::{BlindedTail, Path, RouteHop, RouteParameters, TrampolineHop};
 
 use bitcoin::{Transaction, OutPoint};
 use bitcoin::blockdata::locktime::absolute::LockTime;
@@ -296,6 +296,12 @@ pub enum HTLCDestination {
 		/// The payment hash of the payment we attempted to process.
 		payment_hash: PaymentHash
 	},
+	/// We acted as a trampoline node but were unable to route the HTLC on to the next trampoline
+	/// node (or the recipient).
+	NextTrampoline {
+		/// The `node_id` of the next trampoline node we were instructed to reach.
+		node_id: PublicKey,
+	},
 }
 
 impl_writeable_tlv_based_enum_upgradable!(HTLCDestination,
@@ -312,6 +318,9 @@ impl_writeable_tlv_based_enum_upgradable!(HTLCDestination,
 	(4, FailedPayment) => {
 		(0, payment_hash, required),
 	},
+	(5, NextTrampoline) => {
+		(0, node_id, required),
+	},
 );
 
 /// Will be used in [`Event::HTLCIntercepted`] to identify the next hop in the HTLC's path.
@@ -330,6 +339,26 @@ impl_writeable_tlv_based_enum!(InterceptNextHop,
 	};
 );
 
+/// How our starting balance in an inbound channel is determined. Used in
+/// [`Event::OpenChannelRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InboundChannelFunds {
+	/// For channels opened using V1 channel establishment, our counterparty funds the channel and
+	/// may push some amount, in milli-satoshis, to us.
+	PushMsat(u64),
+	/// For channels opened using V2 channel establishment, we may contribute funds of our own. To
+	/// do so, select inputs from the wallet covering the contribution and the fee for them at the
+	/// given feerate, along with a change script, and pass them to
+	/// [`ChannelManager::accept_inbound_channel_with_contribution`].
+	///
+	/// [`ChannelManager::accept_inbound_channel_with_contribution`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel_with_contribution
+	DualFunded {
+		/// The feerate, in satoshis per 1000 weight units, the funding transaction will pay,
+		/// including for any inputs and outputs we contribute.
+		funding_feerate_sat_per_1000_weight: u32,
+	},
+}
+
 /// The reason the payment failed. Used in [`Event::PaymentFailed`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PaymentFailureReason {
@@ -766,6 +795,11 @@ pub enum Event {
 	/// Such an output will *not* ever be spent by rust-lightning, and are not at risk of your
 	/// counterparty spending them due to some kind of timeout. Thus, you need to store them
 	/// somewhere and spend them when you create on-chain transactions.
+	///
+	/// You may hand them to the [`OutputSweeper`] utility which will store and (re-)generate spending
+	/// transactions for you.
+	///
+	/// [`OutputSweeper`]: crate::util::sweep::OutputSweeper
 	SpendableOutputs {
 		/// The outputs which you should store as spendable by you.
 		outputs: Vec<SpendableOutputDescriptor>,
@@ -832,6 +866,120 @@ pub enum Event {
 		/// The outpoint of the channel's funding transaction.
 		funding_txo: OutPoint,
 	},
+	/// Indicates that the interactive construction of a splice's new funding transaction has
+	/// completed and the inputs we contributed must now be signed.
+	///
+	/// The witnesses for each input spending one of the UTXOs passed to
+	/// [`ChannelManager::splice_channel`] should be filled in and the signed transaction handed
+	/// back via [`ChannelManager::funding_transaction_signed`]. All other inputs, including the
+	/// input spending the channel's current funding output, must be left untouched.
+	///
+	/// This event is not generated if we did not contribute any inputs, and is not persisted: if
+	/// we disconnect from our peer before signing, the splice is aborted and an
+	/// [`Event::SpliceFailed`] is generated instead.
+	///
+	/// [`ChannelManager::splice_channel`]: crate::ln::channelmanager::ChannelManager::splice_channel
+	/// [`ChannelManager::funding_transaction_signed`]: crate::ln::channelmanager::ChannelManager::funding_transaction_signed
+	FundingTransactionReadyForSigning {
+		/// The `channel_id` of the channel being spliced.
+		channel_id: ChannelId,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The `user_channel_id` value passed in to [`ChannelManager::create_channel`] for outbound
+		/// channels, or to [`ChannelManager::accept_inbound_channel`] for inbound channels.
+		///
+		/// [`ChannelManager::create_channel`]: crate::ln::channelmanager::ChannelManager::create_channel
+		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+		user_channel_id: u128,
+		/// The negotiated transaction, without any witnesses.
+		unsigned_transaction: Transaction,
+	},
+	/// Used to indicate that a splice of the channel with the given `channel_id` has been fully
+	/// signed and its new funding transaction broadcast, pending confirmation on-chain.
+	///
+	/// Until the splice is locked (see [`Event::SpliceLocked`]), the channel keeps operating on
+	/// its current funding output and the channel's previous capacity.
+	SplicePending {
+		/// The `channel_id` of the channel being spliced.
+		channel_id: ChannelId,
+		/// The `user_channel_id` value passed in to [`ChannelManager::create_channel`] for outbound
+		/// channels, or to [`ChannelManager::accept_inbound_channel`] for inbound channels.
+		///
+		/// [`ChannelManager::create_channel`]: crate::ln::channelmanager::ChannelManager::create_channel
+		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+		user_channel_id: u128,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The outpoint of the new funding output created by the splice transaction.
+		new_funding_txo: OutPoint,
+		/// The channel's capacity, in satoshis, once the splice locks.
+		channel_value_satoshis: u64,
+	},
+	/// Used to indicate that a splice of the channel with the given `channel_id` has been
+	/// confirmed and locked by both parties. The channel now operates on the new funding output.
+	///
+	/// Note that the channel keeps its `channel_id`, but will be announced with a new
+	/// `short_channel_id` once the new funding transaction has enough confirmations.
+	SpliceLocked {
+		/// The `channel_id` of the channel that was spliced.
+		channel_id: ChannelId,
+		/// The `user_channel_id` value passed in to [`ChannelManager::create_channel`] for outbound
+		/// channels, or to [`ChannelManager::accept_inbound_channel`] for inbound channels.
+		///
+		/// [`ChannelManager::create_channel`]: crate::ln::channelmanager::ChannelManager::create_channel
+		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+		user_channel_id: u128,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The outpoint of the channel's new funding output.
+		funding_txo: OutPoint,
+		/// The channel's new capacity, in satoshis.
+		channel_value_satoshis: u64,
+	},
+	/// Used to indicate that a splice of the channel with the given `channel_id` was aborted
+	/// before its new funding transaction was signed by both parties. The channel keeps operating
+	/// on its current funding output and any inputs contributed to the splice may be reused.
+	SpliceFailed {
+		/// The `channel_id` of the channel whose splice failed.
+		channel_id: ChannelId,
+		/// The `user_channel_id` value passed in to [`ChannelManager::create_channel`] for outbound
+		/// channels, or to [`ChannelManager::accept_inbound_channel`] for inbound channels.
+		///
+		/// [`ChannelManager::create_channel`]: crate::ln::channelmanager::ChannelManager::create_channel
+		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+		user_channel_id: u128,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// The outpoint of the abandoned new funding output, if the splice transaction had already
+		/// been negotiated.
+		abandoned_funding_txo: Option<OutPoint>,
+	},
+	/// Used to indicate that the channel with the given `channel_id` has become quiescent, i.e.
+	/// both parties have exchanged `stfu` and neither may propose any further updates to it until
+	/// quiescence terminates, either via [`ChannelManager::exit_quiescence`] or upon disconnection.
+	///
+	/// Quiescence is requested via [`ChannelManager::propose_quiescence`], though our counterparty
+	/// may also request it, in which case `is_initiator` is `false`.
+	///
+	/// This event is not persisted, as quiescence always terminates upon restart.
+	///
+	/// [`ChannelManager::exit_quiescence`]: crate::ln::channelmanager::ChannelManager::exit_quiescence
+	/// [`ChannelManager::propose_quiescence`]: crate::ln::channelmanager::ChannelManager::propose_quiescence
+	ChannelQuiescent {
+		/// The `channel_id` of the channel which became quiescent.
+		channel_id: ChannelId,
+		/// The `user_channel_id` value passed in to [`ChannelManager::create_channel`] for outbound
+		/// channels, or to [`ChannelManager::accept_inbound_channel`] for inbound channels.
+		///
+		/// [`ChannelManager::create_channel`]: crate::ln::channelmanager::ChannelManager::create_channel
+		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+		user_channel_id: u128,
+		/// The `node_id` of the channel counterparty.
+		counterparty_node_id: PublicKey,
+		/// Whether we initiated quiescence, as decided by the `stfu` exchange. If both sides requested
+		/// quiescence at the same time, the channel funder is the initiator.
+		is_initiator: bool,
+	},
 	/// Used to indicate that a channel with the given `channel_id` is ready to
 	/// be used. This event is emitted either when the funding transaction has been confirmed
 	/// on-chain, or, in case of a 0conf channel, when both parties have confirmed the channel
@@ -908,10 +1056,14 @@ pub enum Event {
 	/// call [`ChannelManager::force_close_without_broadcasting_txn`]. Note that a ['ChannelClosed`]
 	/// event will _not_ be triggered if the channel is rejected.
 	///
+	/// Requests to open a dual-funded channel may also be accepted with a contribution of our own
+	/// via [`ChannelManager::accept_inbound_channel_with_contribution`].
+	///
 	/// The event is only triggered when a new open channel request is received and the
 	/// [`UserConfig::manually_accept_inbound_channels`] config flag is set to true.
 	///
 	/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+	/// [`ChannelManager::accept_inbound_channel_with_contribution`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel_with_contribution
 	/// [`ChannelManager::force_close_without_broadcasting_txn`]: crate::ln::channelmanager::ChannelManager::force_close_without_broadcasting_txn
 	/// [`UserConfig::manually_accept_inbound_channels`]: crate::util::config::UserConfig::manually_accept_inbound_channels
 	OpenChannelRequest {
@@ -934,10 +1086,11 @@ pub enum Event {
 		/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
 		/// [`ChannelManager::force_close_without_broadcasting_txn`]: crate::ln::channelmanager::ChannelManager::force_close_without_broadcasting_txn
 		counterparty_node_id: PublicKey,
-		/// The channel value of the requested channel.
+		/// The channel value of the requested channel, or for dual-funded channels, the amount our
+		/// counterparty is contributing to it.
 		funding_satoshis: u64,
-		/// Our starting balance in the channel if the request is accepted, in milli-satoshi.
-		push_msat: u64,
+		/// How our starting balance in the channel is negotiated if the request is accepted.
+		channel_negotiation_type: InboundChannelFunds,
 		/// The features that this channel will operate with. If you reject the channel, a
 		/// well-behaved counterparty may automatically re-attempt the channel with a new set of
 		/// feature flags.
@@ -1053,6 +1206,7 @@ impl Writeable for Event {
 					(3, false, required), // all_paths_failed in LDK versions prior to 0.0.114
 					(4, path.blinded_tail, option),
 					(5, path.hops, required_vec),
+					(6, path.trampoline_hops, optional_vec),
 					(7, short_channel_id, option),
 					(9, None::<RouteParameters>, option), // retry in LDK versions prior to 0.0.115
 					(11, payment_id, option),
@@ -1128,6 +1282,7 @@ impl Writeable for Event {
 					(2, payment_hash, option),
 					(4, path.hops, required_vec),
 					(6, path.blinded_tail, option),
+					(8, path.trampoline_hops, optional_vec),
 				})
 			},
 			&Event::PaymentFailed { ref payment_id, ref payment_hash, ref reason } => {
@@ -1161,6 +1316,7 @@ impl Writeable for Event {
 					(2, payment_hash, required),
 					(4, path.hops, required_vec),
 					(6, path.blinded_tail, option),
+					(8, path.trampoline_hops, optional_vec),
 				})
 			},
 			&Event::ProbeFailed { ref payment_id, ref payment_hash, ref path, ref short_channel_id } => {
@@ -1171,6 +1327,7 @@ impl Writeable for Event {
 					(4, path.hops, required_vec),
 					(6, short_channel_id, option),
 					(8, path.blinded_tail, option),
+					(10, path.trampoline_hops, optional_vec),
 				})
 			},
 			&Event::HTLCHandlingFailed { ref prev_channel_id, ref failed_next_destination } => {
@@ -1219,6 +1376,45 @@ impl Writeable for Event {
 				35u8.write(writer)?;
 				// Never write ConnectionNeeded events as buffered onion messages aren't serialized.
 			},
+			&Event::FundingTransactionReadyForSigning { .. } => {
+				37u8.write(writer)?;
+				// We never write out FundingTransactionReadyForSigning events as, upon
+				// disconnection, any splice which has not yet exchanged tx_signatures is aborted.
+			},
+			&Event::SplicePending { ref channel_id, ref user_channel_id, ref counterparty_node_id, ref new_funding_txo, ref channel_value_satoshis } => {
+				39u8.write(writer)?;
+				write_tlv_fields!(writer, {
+					(0, channel_id, required),
+					(2, user_channel_id, required),
+					(4, counterparty_node_id, required),
+					(6, new_funding_txo, required),
+					(8, channel_value_satoshis, required),
+				});
+			},
+			&Event::SpliceLocked { ref channel_id, ref user_channel_id, ref counterparty_node_id, ref funding_txo, ref channel_value_satoshis } => {
+				41u8.write(writer)?;
+				write_tlv_fields!(writer, {
+					(0, channel_id, required),
+					(2, user_channel_id, required),
+					(4, counterparty_node_id, required),
+					(6, funding_txo, required),
+					(8, channel_value_satoshis, required),
+				});
+			},
+			&Event::SpliceFailed { ref channel_id, ref user_channel_id, ref counterparty_node_id, ref abandoned_funding_txo } => {
+				43u8.write(writer)?;
+				write_tlv_fields!(writer, {
+					(0, channel_id, required),
+					(2, user_channel_id, required),
+					(4, counterparty_node_id, required),
+					(6, abandoned_funding_txo, option),
+				});
+			},
+			&Event::ChannelQuiescent { .. } => {
+				45u8.write(writer)?;
+				// We never write out ChannelQuiescent events as quiescence terminates upon
+				// disconnection.
+			},
 			// Note that, going forward, all new events must only write data inside of
 			// `write_tlv_fields`. Versions 0.0.101+ will ignore odd-numbered events that write
 			// data via `write_tlv_fields`.
@@ -1314,6 +1510,7 @@ impl MaybeReadable for Event {
 					let mut network_update = None;
 					let mut blinded_tail: Option<BlindedTail> = None;
 					let mut path: Option<Vec<RouteHop>> = Some(vec![]);
+					let mut trampoline_hops: Option<Vec<TrampolineHop>> = Some(vec![]);
 					let mut short_channel_id = None;
 					let mut payment_id = None;
 					let mut failure_opt = None;
@@ -1325,6 +1522,7 @@ impl MaybeReadable for Event {
 						// Added as a part of LDK 0.0.101 and always filled in since.
 						// Defaults to an empty Vec, though likely should have been `Option`al.
 						(5, path, optional_vec),
+						(6, trampoline_hops, optional_vec),
 						(7, short_channel_id, option),
 						(11, payment_id, option),
 						(13, failure_opt, upgradable_option),
@@ -1335,7 +1533,7 @@ impl MaybeReadable for Event {
 						payment_hash,
 						payment_failed_permanently,
 						failure,
-						path: Path { hops: path.unwrap(), blinded_tail },
+						path: Path { hops: path.unwrap(), blinded_tail, trampoline_hops: trampoline_hops.unwrap() },
 						short_channel_id,
 						#[cfg(test)]
 						error_code,
@@ -1452,11 +1650,12 @@ impl MaybeReadable for Event {
 						(2, payment_hash, option),
 						(4, path, required_vec),
 						(6, blinded_tail, option),
+						(8, trampoline_hops, optional_vec),
 					});
 					Ok(Some(Event::PaymentPathSuccessful {
 						payment_id: payment_id.0.unwrap(),
 						payment_hash,
-						path: Path { hops: path, blinded_tail },
+						path: Path { hops: path, blinded_tail, trampoline_hops: trampoline_hops.unwrap() },
 					}))
 				};
 				f()
@@ -1517,11 +1716,12 @@ impl MaybeReadable for Event {
 						(2, payment_hash, required),
 						(4, path, required_vec),
 						(6, blinded_tail, option),
+						(8, trampoline_hops, optional_vec),
 					});
 					Ok(Some(Event::ProbeSuccessful {
 						payment_id: payment_id.0.unwrap(),
 						payment_hash: payment_hash.0.unwrap(),
-						path: Path { hops: path, blinded_tail },
+						path: Path { hops: path, blinded_tail, trampoline_hops: trampoline_hops.unwrap() },
 					}))
 				};
 				f()
@@ -1534,11 +1734,12 @@ impl MaybeReadable for Event {
 						(4, path, required_vec),
 						(6, short_channel_id, option),
 						(8, blinded_tail, option),
+						(10, trampoline_hops, optional_vec),
 					});
 					Ok(Some(Event::ProbeFailed {
 						payment_id: payment_id.0.unwrap(),
 						payment_hash: payment_hash.0.unwrap(),
-						path: Path { hops: path, blinded_tail },
+						path: Path { hops: path, blinded_tail, trampoline_hops: trampoline_hops.unwrap() },
 						short_channel_id,
 					}))
 				};
@@ -1620,6 +1821,66 @@ impl MaybeReadable for Event {
 			},
 			// Note that we do not write a length-prefixed TLV for ConnectionNeeded events.
 			35u8 => Ok(None),
+			// Note that we do not write a length-prefixed TLV for FundingTransactionReadyForSigning
+			// events.
+			37u8 => Ok(None),
+			39u8 => {
+				let f = || {
+					_init_and_read_len_prefixed_tlv_fields!(reader, {
+						(0, channel_id, required),
+						(2, user_channel_id, required),
+						(4, counterparty_node_id, required),
+						(6, new_funding_txo, required),
+						(8, channel_value_satoshis, required),
+					});
+					Ok(Some(Event::SplicePending {
+						channel_id: channel_id.0.unwrap(),
+						user_channel_id: user_channel_id.0.unwrap(),
+						counterparty_node_id: counterparty_node_id.0.unwrap(),
+						new_funding_txo: new_funding_txo.0.unwrap(),
+						channel_value_satoshis: channel_value_satoshis.0.unwrap(),
+					}))
+				};
+				f()
+			},
+			41u8 => {
+				let f = || {
+					_init_and_read_len_prefixed_tlv_fields!(reader, {
+						(0, channel_id, required),
+						(2, user_channel_id, required),
+						(4, counterparty_node_id, required),
+						(6, funding_txo, required),
+						(8, channel_value_satoshis, required),
+					});
+					Ok(Some(Event::SpliceLocked {
+						channel_id: channel_id.0.unwrap(),
+						user_channel_id: user_channel_id.0.unwrap(),
+						counterparty_node_id: counterparty_node_id.0.unwrap(),
+						funding_txo: funding_txo.0.unwrap(),
+						channel_value_satoshis: channel_value_satoshis.0.unwrap(),
+					}))
+				};
+				f()
+			},
+			43u8 => {
+				let f = || {
+					_init_and_read_len_prefixed_tlv_fields!(reader, {
+						(0, channel_id, required),
+						(2, user_channel_id, required),
+						(4, counterparty_node_id, required),
+						(6, abandoned_funding_txo, option),
+					});
+					Ok(Some(Event::SpliceFailed {
+						channel_id: channel_id.0.unwrap(),
+						user_channel_id: user_channel_id.0.unwrap(),
+						counterparty_node_id: counterparty_node_id.0.unwrap(),
+						abandoned_funding_txo,
+					}))
+				};
+				f()
+			},
+			// Note that we do not write a length-prefixed TLV for ChannelQuiescent events.
+			45u8 => Ok(None),
 			// Versions prior to 0.0.100 did not ignore odd types, instead returning InvalidValue.
 			// Version 0.0.100 failed to properly ignore odd types, possibly resulting in corrupt
 			// reads.
@@ -1691,6 +1952,22 @@ pub enum MessageSendEvent {
 		/// The message which should be sent.
 		msg: msgs::FundingSigned,
 	},
+	/// Used to indicate that a peer_storage message should be sent to the peer with the given node
+	/// id.
+	SendPeerStorage {
+		/// The node_id of the node which should receive this message
+		node_id: PublicKey,
+		/// The message which should be sent.
+		msg: msgs::PeerStorage,
+	},
+	/// Used to indicate that a peer_storage_retrieval message should be sent to the peer with the
+	/// given node id.
+	SendPeerStorageRetrieval {
+		/// The node_id of the node which should receive this message
+		node_id: PublicKey,
+		/// The message which should be sent.
+		msg: msgs::PeerStorageRetrieval,
+	},
 	/// Used to indicate that a stfu message should be sent to the peer with the given node id.
 	SendStfu {
 		/// The node_id of the node which should receive this message
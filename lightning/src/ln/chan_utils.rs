@@ -50,6 +50,9 @@ use super::channel_keys::{DelayedPaymentBasepoint, DelayedPaymentKey, HtlcKey, H
 
 /// Maximum number of one-way in-flight HTLC (protocol-level value).
 pub const MAX_HTLCS: u16 = 483;
+/// Maximum number of HTLCs in each direction on zero-fee commitment channels, keeping commitment
+/// transactions within the TRUC transaction size limit.
+pub const MAX_ZERO_FEE_COMMITMENT_HTLCS: u16 = 114;
 /// The weight of a BIP141 witnessScript for a BOLT3's "offered HTLC output" on a commitment transaction, non-anchor variant.
 pub const OFFERED_HTLC_SCRIPT_WEIGHT: usize = 133;
 /// The weight of a BIP141 witnessScript for a BOLT3's "offered HTLC output" on a commitment transaction, anchor variant.
@@ -65,6 +68,9 @@ pub const MAX_ACCEPTED_HTLC_SCRIPT_WEIGHT: usize = 143;
 
 /// The upper bound weight of an anchor input.
 pub const ANCHOR_INPUT_WITNESS_WEIGHT: u64 = 116;
+/// The weight of a shared anchor input of a zero-fee commitment transaction, which only consists of
+/// an empty witness.
+pub const SHARED_ANCHOR_INPUT_WITNESS_WEIGHT: u64 = 1;
 /// The upper bound weight of an HTLC timeout input from a commitment transaction with anchor
 /// outputs.
 pub const HTLC_TIMEOUT_INPUT_ANCHOR_WITNESS_WEIGHT: u64 = 288;
@@ -650,6 +656,12 @@ pub(crate) fn make_funding_redeemscript_from_slices(broadcaster_funding_key: &[u
 	}.push_opcode(opcodes::all::OP_PUSHNUM_2).push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script()
 }
 
+/// The version of pre-signed commitment and HTLC transactions. Zero-fee commitments use version 3
+/// (TRUC) transactions so that they can be fee-bumped via package relay.
+pub(crate) fn transaction_version(channel_type_features: &ChannelTypeFeatures) -> i32 {
+	if channel_type_features.supports_anchor_zero_fee_commitments() { 3 } else { 2 }
+}
+
 /// Builds an unsigned HTLC-Success or HTLC-Timeout transaction from the given channel and HTLC
 /// parameters. This is used by [`TrustedCommitmentTransaction::get_htlc_sigs`] to fetch the
 /// transaction which needs signing, and can be used to construct an HTLC transaction which is
@@ -668,7 +680,7 @@ pub fn build_htlc_transaction(commitment_txid: &Txid, feerate_per_kw: u32, conte
 	));
 
 	Transaction {
-		version: 2,
+		version: transaction_version(channel_type_features),
 		lock_time: LockTime::from_consensus(if htlc.offered { htlc.cltv_expiry } else { 0 }),
 		input: txins,
 		output: txouts,
@@ -802,6 +814,27 @@ pub(crate) fn get_anchor_output<'a>(commitment_tx: &'a Transaction, funding_pubk
 		.map(|(idx, txout)| (idx as u32, txout))
 }
 
+/// The maximum value of the shared anchor output of zero-fee commitment transactions. Any value not
+/// assigned to other outputs of the commitment transaction, such as trimmed HTLCs, is added to the
+/// shared anchor up to this amount, above which it is no longer considered dust.
+pub const SHARED_ANCHOR_OUTPUT_MAX_VALUE_SATOSHI: u64 = 240;
+
+/// Gets the script_pubkey of the shared anchor output of zero-fee commitment transactions, a
+/// pay-to-anchor (P2A) output which anyone can spend with an empty witness.
+pub fn get_shared_anchor_script_pubkey() -> ScriptBuf {
+	Builder::new().push_int(1)
+		.push_slice(&[0x4e, 0x73])
+		.into_script()
+}
+
+/// Locates the shared anchor output of a zero-fee `commitment_tx`.
+pub(crate) fn get_shared_anchor_output<'a>(commitment_tx: &'a Transaction) -> Option<(u32, &'a TxOut)> {
+	let anchor_script = get_shared_anchor_script_pubkey();
+	commitment_tx.output.iter().enumerate()
+		.find(|(_, txout)| txout.script_pubkey == anchor_script)
+		.map(|(idx, txout)| (idx as u32, txout))
+}
+
 /// Returns the witness required to satisfy and spend an anchor input.
 pub fn build_anchor_input_witness(funding_key: &PublicKey, funding_sig: &Signature) -> Witness {
 	let anchor_redeem_script = chan_utils::get_anchor_redeemscript(funding_key);
@@ -1274,6 +1307,8 @@ pub struct CommitmentTransaction {
 	to_broadcaster_value_sat: u64,
 	to_countersignatory_value_sat: u64,
 	to_broadcaster_delay: Option<u16>, // Added in 0.0.117
+	// The value of the shared anchor output, only set for zero-fee commitments.
+	shared_anchor_value_sat: u64,
 	feerate_per_kw: u32,
 	htlcs: Vec<HTLCOutputInCommitment>,
 	// Note that on upgrades, some features of existing outputs may be missed.
@@ -1290,6 +1325,7 @@ impl PartialEq for CommitmentTransaction {
 		let eq = self.commitment_number == o.commitment_number &&
 			self.to_broadcaster_value_sat == o.to_broadcaster_value_sat &&
 			self.to_countersignatory_value_sat == o.to_countersignatory_value_sat &&
+			self.shared_anchor_value_sat == o.shared_anchor_value_sat &&
 			self.feerate_per_kw == o.feerate_per_kw &&
 			self.htlcs == o.htlcs &&
 			self.channel_type_features == o.channel_type_features &&
@@ -1316,6 +1352,7 @@ impl Writeable for CommitmentTransaction {
 			(12, self.htlcs, required_vec),
 			(14, legacy_deserialization_prevention_marker, option),
 			(15, self.channel_type_features, required),
+			(17, self.shared_anchor_value_sat, required),
 		});
 		Ok(())
 	}
@@ -1334,6 +1371,7 @@ impl Readable for CommitmentTransaction {
 			(12, htlcs, required_vec),
 			(14, _legacy_deserialization_prevention_marker, option),
 			(15, channel_type_features, option),
+			(17, shared_anchor_value_sat, (default_value, 0)),
 		});
 
 		let mut additional_features = ChannelTypeFeatures::empty();
@@ -1345,6 +1383,7 @@ impl Readable for CommitmentTransaction {
 			to_broadcaster_value_sat: to_broadcaster_value_sat.0.unwrap(),
 			to_countersignatory_value_sat: to_countersignatory_value_sat.0.unwrap(),
 			to_broadcaster_delay,
+			shared_anchor_value_sat: shared_anchor_value_sat.0.unwrap(),
 			feerate_per_kw: feerate_per_kw.0.unwrap(),
 			keys: keys.0.unwrap(),
 			built: built.0.unwrap(),
@@ -1364,19 +1403,46 @@ impl CommitmentTransaction {
 	///
 	/// Only include HTLCs that are above the dust limit for the channel.
 	///
+	/// For zero-fee commitments, the shared anchor output built by this method carries no value. Use
+	/// [`Self::new_with_channel_value_and_auxiliary_htlc_data`] to have any value not assigned to
+	/// the other outputs, e.g. trimmed HTLCs, added to it instead.
+	///
 	/// This is not exported to bindings users due to the generic though we likely should expose a version without
 	pub fn new_with_auxiliary_htlc_data<T>(commitment_number: u64, to_broadcaster_value_sat: u64, to_countersignatory_value_sat: u64, broadcaster_funding_key: PublicKey, countersignatory_funding_key: PublicKey, keys: TxCreationKeys, feerate_per_kw: u32, htlcs_with_aux: &mut Vec<(HTLCOutputInCommitment, T)>, channel_parameters: &DirectedChannelTransactionParameters) -> CommitmentTransaction {
+		let htlcs_value_sat: u64 = htlcs_with_aux.iter().map(|(htlc, _)| htlc.amount_msat / 1000).sum();
+		let channel_value_satoshis = to_broadcaster_value_sat + to_countersignatory_value_sat + htlcs_value_sat;
+		Self::new_with_channel_value_and_auxiliary_htlc_data(commitment_number, channel_value_satoshis,
+			to_broadcaster_value_sat, to_countersignatory_value_sat, broadcaster_funding_key,
+			countersignatory_funding_key, keys, feerate_per_kw, htlcs_with_aux, channel_parameters)
+	}
+
+	/// Construct an object of the class while assigning transaction output indices to HTLCs, as
+	/// [`Self::new_with_auxiliary_htlc_data`] does.
+	///
+	/// The `channel_value_satoshis` is only used for zero-fee commitments, where any value not
+	/// assigned to the other outputs is added to the shared anchor output, up to
+	/// [`SHARED_ANCHOR_OUTPUT_MAX_VALUE_SATOSHI`].
+	///
+	/// This is not exported to bindings users due to the generic though we likely should expose a version without
+	pub fn new_with_channel_value_and_auxiliary_htlc_data<T>(commitment_number: u64, channel_value_satoshis: u64, to_broadcaster_value_sat: u64, to_countersignatory_value_sat: u64, broadcaster_funding_key: PublicKey, countersignatory_funding_key: PublicKey, keys: TxCreationKeys, feerate_per_kw: u32, htlcs_with_aux: &mut Vec<(HTLCOutputInCommitment, T)>, channel_parameters: &DirectedChannelTransactionParameters) -> CommitmentTransaction {
+		let shared_anchor_value_sat = if channel_parameters.channel_type_features().supports_anchor_zero_fee_commitments() {
+			let htlcs_value_sat: u64 = htlcs_with_aux.iter().map(|(htlc, _)| htlc.amount_msat / 1000).sum();
+			let outputs_value_sat = to_broadcaster_value_sat + to_countersignatory_value_sat + htlcs_value_sat;
+			cmp::min(channel_value_satoshis.saturating_sub(outputs_value_sat), SHARED_ANCHOR_OUTPUT_MAX_VALUE_SATOSHI)
+		} else { 0 };
+
 		// Sort outputs and populate output indices while keeping track of the auxiliary data
-		let (outputs, htlcs) = Self::internal_build_outputs(&keys, to_broadcaster_value_sat, to_countersignatory_value_sat, htlcs_with_aux, channel_parameters, &broadcaster_funding_key, &countersignatory_funding_key).unwrap();
+		let (outputs, htlcs) = Self::internal_build_outputs(&keys, to_broadcaster_value_sat, to_countersignatory_value_sat, shared_anchor_value_sat, htlcs_with_aux, channel_parameters, &broadcaster_funding_key, &countersignatory_funding_key).unwrap();
 
 		let (obscured_commitment_transaction_number, txins) = Self::internal_build_inputs(commitment_number, channel_parameters);
-		let transaction = Self::make_transaction(obscured_commitment_transaction_number, txins, outputs);
+		let transaction = Self::make_transaction(obscured_commitment_transaction_number, txins, outputs, channel_parameters);
 		let txid = transaction.txid();
 		CommitmentTransaction {
 			commitment_number,
 			to_broadcaster_value_sat,
 			to_countersignatory_value_sat,
 			to_broadcaster_delay: Some(channel_parameters.contest_delay()),
+			shared_anchor_value_sat,
 			feerate_per_kw,
 			htlcs,
 			channel_type_features: channel_parameters.channel_type_features().clone(),
@@ -1400,9 +1466,9 @@ impl CommitmentTransaction {
 		let (obscured_commitment_transaction_number, txins) = Self::internal_build_inputs(self.commitment_number, channel_parameters);
 
 		let mut htlcs_with_aux = self.htlcs.iter().map(|h| (h.clone(), ())).collect();
-		let (outputs, _) = Self::internal_build_outputs(keys, self.to_broadcaster_value_sat, self.to_countersignatory_value_sat, &mut htlcs_with_aux, channel_parameters, broadcaster_funding_key, countersignatory_funding_key)?;
+		let (outputs, _) = Self::internal_build_outputs(keys, self.to_broadcaster_value_sat, self.to_countersignatory_value_sat, self.shared_anchor_value_sat, &mut htlcs_with_aux, channel_parameters, broadcaster_funding_key, countersignatory_funding_key)?;
 
-		let transaction = Self::make_transaction(obscured_commitment_transaction_number, txins, outputs);
+		let transaction = Self::make_transaction(obscured_commitment_transaction_number, txins, outputs, channel_parameters);
 		let txid = transaction.txid();
 		let built_transaction = BuiltCommitmentTransaction {
 			transaction,
@@ -1411,9 +1477,9 @@ impl CommitmentTransaction {
 		Ok(built_transaction)
 	}
 
-	fn make_transaction(obscured_commitment_transaction_number: u64, txins: Vec<TxIn>, outputs: Vec<TxOut>) -> Transaction {
+	fn make_transaction(obscured_commitment_transaction_number: u64, txins: Vec<TxIn>, outputs: Vec<TxOut>, channel_parameters: &DirectedChannelTransactionParameters) -> Transaction {
 		Transaction {
-			version: 2,
+			version: transaction_version(channel_parameters.channel_type_features()),
 			lock_time: LockTime::from_consensus(((0x20 as u32) << 8 * 3) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32)),
 			input: txins,
 			output: outputs,
@@ -1424,7 +1490,7 @@ impl CommitmentTransaction {
 	// - initial sorting of outputs / HTLCs in the constructor, in which case T is auxiliary data the
 	//   caller needs to have sorted together with the HTLCs so it can keep track of the output index
 	// - building of a bitcoin transaction during a verify() call, in which case T is just ()
-	fn internal_build_outputs<T>(keys: &TxCreationKeys, to_broadcaster_value_sat: u64, to_countersignatory_value_sat: u64, htlcs_with_aux: &mut Vec<(HTLCOutputInCommitment, T)>, channel_parameters: &DirectedChannelTransactionParameters, broadcaster_funding_key: &PublicKey, countersignatory_funding_key: &PublicKey) -> Result<(Vec<TxOut>, Vec<HTLCOutputInCommitment>), ()> {
+	fn internal_build_outputs<T>(keys: &TxCreationKeys, to_broadcaster_value_sat: u64, to_countersignatory_value_sat: u64, shared_anchor_value_sat: u64, htlcs_with_aux: &mut Vec<(HTLCOutputInCommitment, T)>, channel_parameters: &DirectedChannelTransactionParameters, broadcaster_funding_key: &PublicKey, countersignatory_funding_key: &PublicKey) -> Result<(Vec<TxOut>, Vec<HTLCOutputInCommitment>), ()> {
 		let countersignatory_pubkeys = channel_parameters.countersignatory_pubkeys();
 		let contest_delay = channel_parameters.contest_delay();
 
@@ -1460,7 +1526,16 @@ impl CommitmentTransaction {
 			));
 		}
 
-		if channel_parameters.channel_type_features().supports_anchors_zero_fee_htlc_tx() {
+		if channel_parameters.channel_type_features().supports_anchor_zero_fee_commitments() {
+			// Zero-fee commitments always have a single anchor output, which either party may spend.
+			txouts.push((
+				TxOut {
+					script_pubkey: get_shared_anchor_script_pubkey(),
+					value: shared_anchor_value_sat,
+				},
+				None,
+			));
+		} else if channel_parameters.channel_type_features().supports_anchors_zero_fee_htlc_tx() {
 			if to_broadcaster_value_sat > 0 || !htlcs_with_aux.is_empty() {
 				let anchor_script = get_anchor_redeemscript(broadcaster_funding_key);
 				txouts.push((
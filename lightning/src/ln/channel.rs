@@ -9,7 +9,9 @@
 
 use bitcoin::blockdata::constants::ChainHash;
 use bitcoin::blockdata::script::{Script, ScriptBuf, Builder};
-use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::blockdata::transaction::{Transaction, TxIn, TxOut};
+use bitcoin::blockdata::witness::Witness;
 use bitcoin::sighash;
 use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::consensus::encode;
@@ -17,7 +19,7 @@ use bitcoin::consensus::encode;
 use bitcoin::hashes::Hash;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::sha256d::Hash as Sha256d;
-use bitcoin::hash_types::{Txid, BlockHash};
+use bitcoin::hash_types::{Txid, BlockHash, WScriptHash};
 
 use bitcoin::secp256k1::constants::PUBLIC_KEY_SIZE;
 use bitcoin::secp256k1::{PublicKey,SecretKey};
@@ -29,19 +31,20 @@ use crate::ln::features::{ChannelTypeFeatures, InitFeatures};
 use crate::ln::msgs;
 use crate::ln::msgs::DecodeError;
 use crate::ln::script::{self, ShutdownScript};
-use crate::ln::channelmanager::{self, CounterpartyForwardingInfo, PendingHTLCStatus, HTLCSource, SentHTLCId, HTLCFailureMsg, PendingHTLCInfo, RAACommitmentOrder, BREAKDOWN_TIMEOUT, MIN_CLTV_EXPIRY_DELTA, MAX_LOCAL_BREAKDOWN_TIMEOUT, ChannelShutdownState};
-use crate::ln::chan_utils::{CounterpartyCommitmentSecrets, TxCreationKeys, HTLCOutputInCommitment, htlc_success_tx_weight, htlc_timeout_tx_weight, make_funding_redeemscript, ChannelPublicKeys, CommitmentTransaction, HolderCommitmentTransaction, ChannelTransactionParameters, CounterpartyChannelTransactionParameters, MAX_HTLCS, get_commitment_transaction_number_obscure_factor, ClosingTransaction};
+use crate::ln::channelmanager::{self, CounterpartyForwardingInfo, PendingHTLCStatus, SpliceContribution, FundingContribution, HTLCSource, SentHTLCId, HTLCFailureMsg, PendingHTLCInfo, RAACommitmentOrder, BREAKDOWN_TIMEOUT, MIN_CLTV_EXPIRY_DELTA, MAX_LOCAL_BREAKDOWN_TIMEOUT, ChannelShutdownState};
+use crate::ln::chan_utils::{CounterpartyCommitmentSecrets, TxCreationKeys, HTLCOutputInCommitment, htlc_success_tx_weight, htlc_timeout_tx_weight, make_funding_redeemscript, ChannelPublicKeys, CommitmentTransaction, HolderCommitmentTransaction, ChannelTransactionParameters, CounterpartyChannelTransactionParameters, MAX_HTLCS, MAX_ZERO_FEE_COMMITMENT_HTLCS, get_commitment_transaction_number_obscure_factor, ClosingTransaction};
 use crate::ln::chan_utils;
+use crate::ln::interactivetxs::{self, InteractiveTxConstructor, InteractiveTxMessageSend, InteractiveTxParameters, SharedFundingInput};
 use crate::ln::onion_utils::HTLCFailReason;
 use crate::chain::BestBlock;
-use crate::chain::chaininterface::{FeeEstimator, ConfirmationTarget, LowerBoundedFeeEstimator};
+use crate::chain::chaininterface::{FeeEstimator, ConfirmationTarget, LowerBoundedFeeEstimator, FEERATE_FLOOR_SATS_PER_KW, fee_for_weight};
 use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, ChannelMonitorUpdateStep, LATENCY_GRACE_PERIOD_BLOCKS, CLOSED_CHANNEL_UPDATE_ID};
 use crate::chain::transaction::{OutPoint, TransactionData};
 use crate::sign::ecdsa::{EcdsaChannelSigner, WriteableEcdsaChannelSigner};
 use crate::sign::{EntropySource, ChannelSigner, SignerProvider, NodeSigner, Recipient};
 use crate::events::ClosureReason;
 use crate::routing::gossip::NodeId;
-use crate::util::ser::{Readable, ReadableArgs, Writeable, Writer};
+use crate::util::ser::{Readable, ReadableArgs, TransactionU16LenLimited, Writeable, Writer};
 use crate::util::logger::{Logger, Record, WithContext};
 use crate::util::errors::APIError;
 use crate::util::config::{UserConfig, ChannelConfig, LegacyChannelConfig, ChannelHandshakeConfig, ChannelHandshakeLimits, MaxDustHTLCExposure};
@@ -235,6 +238,8 @@ struct OutboundHTLCOutput {
 	source: HTLCSource,
 	blinding_point: Option<PublicKey>,
 	skimmed_fee_msat: Option<u64>,
+	// Whether we endorsed this HTLC to our counterparty, see `msgs::UpdateAddHTLC::endorsed`.
+	endorsed: Option<bool>,
 }
 
 /// See AwaitingRemoteRevoke ChannelState for more info
@@ -250,6 +255,7 @@ enum HTLCUpdateAwaitingACK {
 		// The extra fee we're skimming off the top of this HTLC.
 		skimmed_fee_msat: Option<u64>,
 		blinding_point: Option<PublicKey>,
+		endorsed: Option<bool>,
 	},
 	ClaimHTLC {
 		payment_preimage: PaymentPreimage,
@@ -365,6 +371,10 @@ mod state_flags {
 	pub const LOCAL_SHUTDOWN_SENT: u32 = 1 << 11;
 	pub const SHUTDOWN_COMPLETE: u32 = 1 << 12;
 	pub const WAITING_FOR_BATCH: u32 = 1 << 13;
+	pub const AWAITING_QUIESCENCE: u32 = 1 << 14;
+	pub const LOCAL_STFU_SENT: u32 = 1 << 15;
+	pub const REMOTE_STFU_SENT: u32 = 1 << 16;
+	pub const QUIESCENT: u32 = 1 << 17;
 }
 
 define_state_flags!(
@@ -415,7 +425,17 @@ define_state_flags!(
 			`revoke_and_ack` message. During this period, we can't generate new `commitment_signed` \
 			messages as we'd be unable to determine which HTLCs they included in their `revoke_and_ack` \
 			implicit ACK, so instead we have to hold them away temporarily to be sent later.",
-			AWAITING_REMOTE_REVOKE, state_flags::AWAITING_REMOTE_REVOKE)
+			AWAITING_REMOTE_REVOKE, state_flags::AWAITING_REMOTE_REVOKE),
+		("Indicates we have been asked to make the channel quiescent and will send `stfu` once all \
+			pending updates have been irrevocably committed.",
+			AWAITING_QUIESCENCE, state_flags::AWAITING_QUIESCENCE),
+		("Indicates we have sent `stfu` and are awaiting theirs. We may not send any further \
+			updates until quiescence is terminated.", LOCAL_STFU_SENT, state_flags::LOCAL_STFU_SENT),
+		("Indicates we have received `stfu` but have yet to send ours. They may not send any \
+			further updates until quiescence is terminated.", REMOTE_STFU_SENT, state_flags::REMOTE_STFU_SENT),
+		("Indicates both sides have sent `stfu` and the channel is now quiescent. Neither side may \
+			send any updates until quiescence is terminated, which happens either upon disconnection \
+			or once the protocol which required quiescence completes.", QUIESCENT, state_flags::QUIESCENT)
 	]
 );
 
@@ -528,7 +548,9 @@ impl ChannelState {
 			ChannelState::ChannelReady(flags) =>
 				flags.is_set(ChannelReadyFlags::AWAITING_REMOTE_REVOKE) ||
 					flags.is_set(FundedStateFlags::MONITOR_UPDATE_IN_PROGRESS.into()) ||
-					flags.is_set(FundedStateFlags::PEER_DISCONNECTED.into()),
+					flags.is_set(FundedStateFlags::PEER_DISCONNECTED.into()) ||
+					flags.is_set(ChannelReadyFlags::LOCAL_STFU_SENT) ||
+					flags.is_set(ChannelReadyFlags::QUIESCENT),
 			_ => {
 				debug_assert!(false, "The holding cell is only valid within ChannelReady");
 				false
@@ -552,6 +574,14 @@ impl ChannelState {
 		AwaitingChannelReadyFlags::WAITING_FOR_BATCH, AwaitingChannelReady);
 	impl_state_flag!(is_awaiting_remote_revoke, set_awaiting_remote_revoke, clear_awaiting_remote_revoke,
 		ChannelReadyFlags::AWAITING_REMOTE_REVOKE, ChannelReady);
+	impl_state_flag!(is_awaiting_quiescence, set_awaiting_quiescence, clear_awaiting_quiescence,
+		ChannelReadyFlags::AWAITING_QUIESCENCE, ChannelReady);
+	impl_state_flag!(is_local_stfu_sent, set_local_stfu_sent, clear_local_stfu_sent,
+		ChannelReadyFlags::LOCAL_STFU_SENT, ChannelReady);
+	impl_state_flag!(is_remote_stfu_sent, set_remote_stfu_sent, clear_remote_stfu_sent,
+		ChannelReadyFlags::REMOTE_STFU_SENT, ChannelReady);
+	impl_state_flag!(is_quiescent, set_quiescent, clear_quiescent,
+		ChannelReadyFlags::QUIESCENT, ChannelReady);
 }
 
 pub const INITIAL_COMMITMENT_NUMBER: u64 = (1 << 48) - 1;
@@ -571,6 +601,28 @@ pub const COMMITMENT_TX_WEIGHT_PER_HTLC: u64 = 172;
 
 pub const ANCHOR_OUTPUT_VALUE_SATOSHI: u64 = 330;
 
+/// The value of the anchor outputs the funder pays for out of its balance on each commitment
+/// transaction. For zero-fee commitments, the shared anchor is instead funded by any value which
+/// didn't make it into the other outputs, e.g. trimmed HTLCs.
+pub(crate) fn anchor_outputs_value_sat(channel_type_features: &ChannelTypeFeatures) -> u64 {
+	if channel_type_features.supports_anchor_zero_fee_commitments() {
+		0
+	} else if channel_type_features.supports_anchors_zero_fee_htlc_tx() {
+		ANCHOR_OUTPUT_VALUE_SATOSHI * 2
+	} else {
+		0
+	}
+}
+
+/// The maximum number of HTLCs either side may accept on a channel of the given type.
+pub(crate) fn max_accepted_htlcs_limit(channel_type_features: &ChannelTypeFeatures) -> u16 {
+	if channel_type_features.supports_anchor_zero_fee_commitments() {
+		MAX_ZERO_FEE_COMMITMENT_HTLCS
+	} else {
+		MAX_HTLCS
+	}
+}
+
 /// The percentage of the channel value `holder_max_htlc_value_in_flight_msat` used to be set to,
 /// before this was made configurable. The percentage was made configurable in LDK 0.0.107,
 /// although LDK 0.0.104+ enabled serialization of channels with a different value set for
@@ -613,6 +665,7 @@ pub const MIN_THEIR_CHAN_RESERVE_SATOSHIS: u64 = 1000;
 pub(super) enum ChannelError {
 	Ignore(String),
 	Warn(String),
+	WarnAndDisconnect(String),
 	Close(String),
 }
 
@@ -621,6 +674,7 @@ impl fmt::Debug for ChannelError {
 		match self {
 			&ChannelError::Ignore(ref e) => write!(f, "Ignore : {}", e),
 			&ChannelError::Warn(ref e) => write!(f, "Warn : {}", e),
+			&ChannelError::WarnAndDisconnect(ref e) => write!(f, "Disconnecting with warning: {}", e),
 			&ChannelError::Close(ref e) => write!(f, "Close : {}", e),
 		}
 	}
@@ -631,6 +685,7 @@ impl fmt::Display for ChannelError {
 		match self {
 			&ChannelError::Ignore(ref e) => write!(f, "{}", e),
 			&ChannelError::Warn(ref e) => write!(f, "{}", e),
+			&ChannelError::WarnAndDisconnect(ref e) => write!(f, "{}", e),
 			&ChannelError::Close(ref e) => write!(f, "{}", e),
 		}
 	}
@@ -791,6 +846,8 @@ pub(super) struct MonitorRestoreUpdates {
 	pub funding_broadcastable: Option<Transaction>,
 	pub channel_ready: Option<msgs::ChannelReady>,
 	pub announcement_sigs: Option<msgs::AnnouncementSignatures>,
+	pub tx_signatures: Option<msgs::TxSignatures>,
+	pub splice_funding_broadcastable: Option<Transaction>,
 }
 
 /// The return value of `signer_maybe_unblocked`
@@ -809,6 +866,28 @@ pub(super) struct ReestablishResponses {
 	pub order: RAACommitmentOrder,
 	pub announcement_sigs: Option<msgs::AnnouncementSignatures>,
 	pub shutdown_msg: Option<msgs::Shutdown>,
+	pub splice_commitment_signed: Option<msgs::CommitmentSigned>,
+	pub tx_signatures: Option<msgs::TxSignatures>,
+	pub splice_funding_broadcastable: Option<Transaction>,
+	pub tx_abort: Option<msgs::TxAbort>,
+	pub splice_locked: Option<msgs::SpliceLocked>,
+}
+
+/// A splice which was aborted before its funding transaction was fully signed.
+pub(super) struct AbortedSplice {
+	/// The funding outpoint of the aborted splice, if its transaction had been negotiated.
+	pub funding_txo: Option<OutPoint>,
+	/// Whether this was a fee-bump of a dual-funded channel's funding transaction rather than a
+	/// splice.
+	pub is_funding_rbf: bool,
+}
+
+/// The return value of `tx_complete` once a funding transaction has been negotiated, either for a
+/// splice or for a dual-funded channel.
+pub(super) struct FundingTxNegotiated {
+	pub commitment_signed: msgs::CommitmentSigned,
+	/// The negotiated funding transaction, if we contributed inputs which need to be signed.
+	pub unsigned_transaction: Option<Transaction>,
 }
 
 /// The result of a shutdown that should be handled.
@@ -900,11 +979,86 @@ impl_writeable_tlv_based!(PendingChannelMonitorUpdate, {
 	(0, update, required),
 });
 
+/// The state of an interactive funding transaction negotiation, either for a splice, from the
+/// `splice` message until both parties have exchanged `commitment_signed` for the new funding
+/// transaction, or for the funding transaction of a dual-funded channel or a fee-bump of it.
+///
+/// This is not persisted - a negotiation which has not completed is aborted if we disconnect.
+struct PendingSplice {
+	is_initiator: bool,
+	our_funding_contribution_satoshis: i64,
+	their_funding_contribution_satoshis: i64,
+	funding_feerate_per_kw: u32,
+	locktime: u32,
+	our_funding_inputs: Vec<(TxIn, TransactionU16LenLimited)>,
+	our_funding_outputs: Vec<TxOut>,
+	interactive_tx_constructor: Option<InteractiveTxConstructor>,
+}
+
+/// A funding transaction negotiated by a splice, which replaces the channel's current funding
+/// output once both parties have sent `splice_locked`.
+///
+/// The initial funding transaction of a dual-funded channel and any fee-bumps of it are tracked
+/// in the same way until both parties have exchanged `tx_signatures`. These spend no shared input.
+struct SpliceFunding {
+	/// The negotiated transaction, with our witnesses once we've signed our inputs and all
+	/// witnesses once both parties have exchanged `tx_signatures`.
+	funding_transaction: Transaction,
+	funding_output_index: u16,
+	/// The index of the input spending the current funding output, if any.
+	shared_input_index: Option<u16>,
+	/// The number of inputs (besides the shared input) which we contributed.
+	holder_input_count: u16,
+	holder_inputs_signed: bool,
+	holder_sends_tx_signatures_first: bool,
+	channel_value_satoshis: u64,
+	value_to_self_msat: u64,
+	received_commitment_signed: bool,
+	holder_tx_signatures: Option<msgs::TxSignatures>,
+	counterparty_tx_signatures: Option<msgs::TxSignatures>,
+	confirmation_height: u32,
+	confirmed_in: Option<BlockHash>,
+	short_channel_id: Option<u64>,
+	sent_splice_locked: bool,
+	received_splice_locked: bool,
+}
+
+impl SpliceFunding {
+	fn funding_outpoint(&self) -> OutPoint {
+		OutPoint { txid: self.funding_transaction.txid(), index: self.funding_output_index }
+	}
+
+	fn is_fully_signed(&self) -> bool {
+		self.holder_tx_signatures.is_some() && self.counterparty_tx_signatures.is_some()
+	}
+}
+
+impl_writeable_tlv_based!(SpliceFunding, {
+	(0, funding_transaction, required),
+	(2, funding_output_index, required),
+	(4, shared_input_index, option),
+	(6, holder_input_count, required),
+	(8, holder_inputs_signed, required),
+	(10, holder_sends_tx_signatures_first, required),
+	(12, channel_value_satoshis, required),
+	(14, value_to_self_msat, required),
+	(16, received_commitment_signed, required),
+	(18, holder_tx_signatures, option),
+	(20, counterparty_tx_signatures, option),
+	(22, confirmation_height, required),
+	(24, confirmed_in, option),
+	(26, sent_splice_locked, required),
+	(28, received_splice_locked, required),
+	(30, short_channel_id, option),
+});
+
 /// The `ChannelPhase` enum describes the current phase in life of a lightning channel with each of
 /// its variants containing an appropriate channel struct.
 pub(super) enum ChannelPhase<SP: Deref> where SP::Target: SignerProvider {
 	UnfundedOutboundV1(OutboundV1Channel<SP>),
 	UnfundedInboundV1(InboundV1Channel<SP>),
+	UnfundedOutboundV2(OutboundV2Channel<SP>),
+	UnfundedInboundV2(InboundV2Channel<SP>),
 	Funded(Channel<SP>),
 }
 
@@ -917,6 +1071,8 @@ impl<'a, SP: Deref> ChannelPhase<SP> where
 			ChannelPhase::Funded(chan) => &chan.context,
 			ChannelPhase::UnfundedOutboundV1(chan) => &chan.context,
 			ChannelPhase::UnfundedInboundV1(chan) => &chan.context,
+			ChannelPhase::UnfundedOutboundV2(chan) => &chan.context,
+			ChannelPhase::UnfundedInboundV2(chan) => &chan.context,
 		}
 	}
 
@@ -925,6 +1081,8 @@ impl<'a, SP: Deref> ChannelPhase<SP> where
 			ChannelPhase::Funded(ref mut chan) => &mut chan.context,
 			ChannelPhase::UnfundedOutboundV1(ref mut chan) => &mut chan.context,
 			ChannelPhase::UnfundedInboundV1(ref mut chan) => &mut chan.context,
+			ChannelPhase::UnfundedOutboundV2(ref mut chan) => &mut chan.context,
+			ChannelPhase::UnfundedInboundV2(ref mut chan) => &mut chan.context,
 		}
 	}
 }
@@ -951,6 +1109,18 @@ impl UnfundedChannelContext {
 	}
 }
 
+/// Contains all state common to unfunded inbound/outbound channels using V2 channel establishment.
+pub(super) struct DualFundingChannelContext {
+	/// The amount in satoshis we will be contributing to the channel.
+	pub our_funding_satoshis: u64,
+	/// The amount in satoshis our counterparty will be contributing to the channel.
+	pub their_funding_satoshis: u64,
+	/// The funding transaction locktime suggested by the initiator.
+	pub funding_tx_locktime: u32,
+	/// The feerate set by the initiator to be used for the funding transaction.
+	pub funding_feerate_sat_per_1000_weight: u32,
+}
+
 /// Contains everything about the channel including state, and various flags.
 pub(super) struct ChannelContext<SP: Deref> where SP::Target: SignerProvider {
 	config: LegacyChannelConfig,
@@ -1015,6 +1185,13 @@ pub(super) struct ChannelContext<SP: Deref> where SP::Target: SignerProvider {
 	monitor_pending_channel_ready: bool,
 	monitor_pending_revoke_and_ack: bool,
 	monitor_pending_commitment_signed: bool,
+	/// Set when we've received the counterparty's `commitment_signed` for a splice's funding
+	/// transaction, as our `tx_signatures` may only be sent once the new commitment transaction
+	/// has been persisted in the [`ChannelMonitor`].
+	monitor_pending_tx_signatures: bool,
+	/// Set to a splice's funding txid once both parties have sent `splice_locked`, until we've
+	/// generated the [`ChannelMonitorUpdate`] informing the [`ChannelMonitor`] of it.
+	monitor_pending_splice_locked: Option<Txid>,
 
 	// TODO: If a channel is drop'd, we don't know whether the `ChannelMonitor` is ultimately
 	// responsible for some of the HTLCs here or not - we don't know whether the update in question
@@ -1191,10 +1368,15 @@ pub(super) struct ChannelContext<SP: Deref> where SP::Target: SignerProvider {
 	/// to in a timely manner, which may lead to channels becoming unusable and/or force-closed. An
 	/// example of such can be found at <https://github.com/lightningnetwork/lnd/issues/7682>.
 	///
-	/// This is currently only used when waiting for a [`msgs::ChannelReestablish`] or
-	/// [`msgs::RevokeAndACK`] message from the counterparty.
+	/// This is currently only used when waiting for a [`msgs::ChannelReestablish`],
+	/// [`msgs::RevokeAndACK`] or [`msgs::Stfu`] message from the counterparty, as well as while
+	/// the counterparty holds the channel quiescent.
 	sent_message_awaiting_response: Option<usize>,
 
+	/// Whether we initiated the current quiescence session, as decided by the `stfu` exchange. Only
+	/// set while the `stfu` exchange is ongoing or the channel is quiescent.
+	is_holder_quiescence_initiator: Option<bool>,
+
 	#[cfg(any(test, fuzzing))]
 	// When we receive an HTLC fulfill on an outbound path, we may immediately fulfill the
 	// corresponding HTLC on the inbound path. If, then, the outbound path channel is
@@ -1238,6 +1420,24 @@ pub(super) struct ChannelContext<SP: Deref> where SP::Target: SignerProvider {
 	/// If we can't release a [`ChannelMonitorUpdate`] until some external action completes, we
 	/// store it here and only release it to the `ChannelManager` once it asks for it.
 	blocked_monitor_updates: Vec<PendingChannelMonitorUpdate>,
+
+	/// The funding outpoint the channel was opened with, if it has since been spliced. This
+	/// continues to identify the channel (e.g. as the key of its [`ChannelMonitor`]) while
+	/// `channel_transaction_parameters` tracks the current funding outpoint.
+	original_funding_outpoint: Option<OutPoint>,
+	/// A splice (or a dual-funded channel's funding transaction) which is currently being
+	/// negotiated, if any.
+	pending_splice: Option<PendingSplice>,
+	/// A funding transaction negotiated by a splice which has yet to be locked by both parties, or
+	/// the funding transaction of a dual-funded channel (or a fee-bump of it) which has yet to be
+	/// fully signed or confirmed.
+	pending_splice_funding: Option<SpliceFunding>,
+	/// The feerate of the latest funding transaction of a channel opened using V2 channel
+	/// establishment, which any fee-bump of it must exceed. `None` for V1 channels.
+	dual_funding_feerate_sat_per_1000_weight: Option<u32>,
+	/// The fully signed funding transaction of a dual-funded channel, kept until it confirms so that
+	/// we can check that any fee-bump of it double-spends it.
+	dual_funding_transaction: Option<Transaction>,
 }
 
 impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
@@ -1378,10 +1578,87 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 
 	/// Returns the funding_txo we either got from our peer, or were given by
 	/// get_funding_created.
+	///
+	/// If the channel has been spliced, this is still the original funding outpoint, which
+	/// continues to identify the channel and its [`ChannelMonitor`].
 	pub fn get_funding_txo(&self) -> Option<OutPoint> {
+		self.original_funding_outpoint.or(self.channel_transaction_parameters.funding_outpoint)
+	}
+
+	/// Returns the funding outpoint currently backing the channel, which differs from
+	/// [`Self::get_funding_txo`] once the channel has been spliced.
+	pub fn get_current_funding_txo(&self) -> Option<OutPoint> {
 		self.channel_transaction_parameters.funding_outpoint
 	}
 
+	/// Returns the funding transaction negotiated by a splice which has yet to be locked, if any.
+	pub fn get_pending_splice_funding_txo(&self) -> Option<OutPoint> {
+		self.pending_splice_funding.as_ref().map(|funding| funding.funding_outpoint())
+	}
+
+	/// Returns the channel value once the splice which has yet to be locked does so, if any.
+	pub fn get_pending_splice_channel_value_satoshis(&self) -> Option<u64> {
+		self.pending_splice_funding.as_ref().map(|funding| funding.channel_value_satoshis)
+	}
+
+	/// Returns true if a splice is being negotiated or its funding transaction has yet to be locked.
+	/// No HTLC or fee updates are allowed in the meantime.
+	pub fn is_splice_pending(&self) -> bool {
+		self.pending_splice.is_some() || self.pending_splice_funding.is_some()
+	}
+
+	/// Returns true if the channel was opened using V2 channel establishment.
+	pub fn is_dual_funded(&self) -> bool {
+		self.dual_funding_feerate_sat_per_1000_weight.is_some()
+	}
+
+	/// Attaches our witnesses to the pending funding transaction, in response to
+	/// [`Event::FundingTransactionReadyForSigning`].
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: crate::events::Event::FundingTransactionReadyForSigning
+	pub fn funding_transaction_signed(&mut self, transaction: Transaction) -> Result<(), APIError> {
+		let splice_funding = match self.pending_splice_funding.as_mut() {
+			Some(splice_funding) if !splice_funding.holder_inputs_signed => splice_funding,
+			_ => return Err(APIError::APIMisuseError { err: "No funding transaction is awaiting signatures".to_owned() }),
+		};
+		if transaction.txid() != splice_funding.funding_transaction.txid() {
+			return Err(APIError::APIMisuseError { err: "Transaction does not match the negotiated funding transaction".to_owned() });
+		}
+		let shared_input_index = splice_funding.shared_input_index.map(|idx| idx as usize);
+		let signed_input_count = transaction.input.iter().enumerate()
+			.filter(|(idx, input)| Some(*idx) != shared_input_index && !input.witness.is_empty())
+			.count();
+		if signed_input_count != splice_funding.holder_input_count as usize {
+			return Err(APIError::APIMisuseError {
+				err: format!("Expected witnesses for {} inputs, got {}", splice_funding.holder_input_count, signed_input_count),
+			});
+		}
+		let mut transaction = transaction;
+		if let Some(shared_input_index) = shared_input_index {
+			transaction.input[shared_input_index].witness = Witness::new();
+		}
+		splice_funding.funding_transaction = transaction;
+		splice_funding.holder_inputs_signed = true;
+		Ok(())
+	}
+
+	/// Returns true if the initial funding transaction of a dual-funded channel has been negotiated
+	/// but has yet to be signed by both parties, in which case it must not be broadcast.
+	fn is_initial_funding_unsigned(&self) -> bool {
+		self.pending_splice_funding.as_ref()
+			.map(|funding| Some(funding.funding_outpoint()) == self.channel_transaction_parameters.funding_outpoint)
+			.unwrap_or(false)
+	}
+
+	/// Returns true if the pending funding transaction is a fee-bump of a dual-funded channel's
+	/// funding transaction rather than a splice. Fee-bumps are only possible before the funding
+	/// transaction has confirmed, while splices require the channel to be ready.
+	pub fn is_funding_rbf_pending(&self) -> bool {
+		self.is_dual_funded() && !self.is_initial_funding_unsigned() &&
+			matches!(self.channel_state, ChannelState::AwaitingChannelReady(_)) &&
+			(self.pending_splice.is_some() || self.pending_splice_funding.is_some())
+	}
+
 	/// Returns the height in which our funding transaction was confirmed.
 	pub fn get_funding_tx_confirmation_height(&self) -> Option<u32> {
 		let conf_height = self.funding_tx_confirmation_height;
@@ -1567,7 +1844,8 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 	/// funding transaction has been broadcast if necessary.
 	pub fn is_funding_broadcast(&self) -> bool {
 		!self.channel_state.is_pre_funded_state() &&
-			!matches!(self.channel_state, ChannelState::AwaitingChannelReady(flags) if flags.is_set(AwaitingChannelReadyFlags::WAITING_FOR_BATCH))
+			!matches!(self.channel_state, ChannelState::AwaitingChannelReady(flags) if flags.is_set(AwaitingChannelReadyFlags::WAITING_FOR_BATCH)) &&
+			!self.is_initial_funding_unsigned()
 	}
 
 	/// Transaction nomenclature is somewhat confusing here as there are many different cases - a
@@ -1758,7 +2036,7 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 		}
 
 		let total_fee_sat = commit_tx_fee_sat(feerate_per_kw, included_non_dust_htlcs.len(), &self.channel_transaction_parameters.channel_type_features);
-		let anchors_val = if self.channel_transaction_parameters.channel_type_features.supports_anchors_zero_fee_htlc_tx() { ANCHOR_OUTPUT_VALUE_SATOSHI * 2 } else { 0 } as i64;
+		let anchors_val = anchor_outputs_value_sat(&self.channel_transaction_parameters.channel_type_features) as i64;
 		let (value_to_self, value_to_remote) = if self.is_outbound() {
 			(value_to_self_msat / 1000 - anchors_val - total_fee_sat as i64, value_to_remote_msat / 1000)
 		} else {
@@ -1790,7 +2068,8 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 		let channel_parameters =
 			if local { self.channel_transaction_parameters.as_holder_broadcastable() }
 			else { self.channel_transaction_parameters.as_counterparty_broadcastable() };
-		let tx = CommitmentTransaction::new_with_auxiliary_htlc_data(commitment_number,
+		let tx = CommitmentTransaction::new_with_channel_value_and_auxiliary_htlc_data(commitment_number,
+		                                                             self.channel_value_satoshis,
 		                                                             value_to_a as u64,
 		                                                             value_to_b as u64,
 		                                                             funding_pubkey_a,
@@ -1994,11 +2273,7 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 
 		let mut available_capacity_msat = outbound_capacity_msat;
 
-		let anchor_outputs_value_msat = if context.get_channel_type().supports_anchors_zero_fee_htlc_tx() {
-			ANCHOR_OUTPUT_VALUE_SATOSHI * 2 * 1000
-		} else {
-			0
-		};
+		let anchor_outputs_value_msat = anchor_outputs_value_sat(context.get_channel_type()) * 1000;
 		if context.is_outbound() {
 			// We should mind channel commit tx fee when computing how much of the available capacity
 			// can be used in the next htlc. Mirrors the logic in send_htlc.
@@ -2104,6 +2379,11 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 			available_capacity_msat = 0;
 		}
 
+		// No HTLCs may be added until a pending splice has been locked.
+		if context.is_splice_pending() {
+			available_capacity_msat = 0;
+		}
+
 		AvailableBalances {
 			inbound_capacity_msat: cmp::max(context.channel_value_satoshis as i64 * 1000
 					- context.value_to_self_msat as i64
@@ -2463,6 +2743,348 @@ impl<SP: Deref> ChannelContext<SP> where SP::Target: SignerProvider  {
 			_ => todo!()
 		}
 	}
+
+	/// Gets the parameters for the interactive construction of a funding transaction with the given
+	/// channel value, which spends the current funding output if this is a splice.
+	fn get_interactive_tx_parameters(
+		&self, holder_node_id: PublicKey, pending_splice: &PendingSplice, channel_value_satoshis: u64,
+		spends_funding_output: bool,
+	) -> InteractiveTxParameters {
+		let funding_script = self.get_funding_redeemscript().to_v0_p2wsh();
+		let shared_input = if spends_funding_output {
+			Some(SharedFundingInput {
+				outpoint: self.get_current_funding_txo().unwrap().into_bitcoin_outpoint(),
+				prev_output: TxOut { value: self.channel_value_satoshis, script_pubkey: funding_script.clone() },
+			})
+		} else { None };
+		InteractiveTxParameters {
+			channel_id: self.channel_id,
+			holder_is_initiator: pending_splice.is_initiator,
+			holder_node_id,
+			counterparty_node_id: self.counterparty_node_id,
+			feerate_sat_per_kw: pending_splice.funding_feerate_per_kw,
+			tx_locktime: LockTime::from_consensus(pending_splice.locktime),
+			shared_input,
+			shared_output: TxOut { value: channel_value_satoshis, script_pubkey: funding_script },
+			counterparty_funding_contribution_satoshis: pending_splice.their_funding_contribution_satoshis,
+		}
+	}
+
+	/// Passes a message of an interactive transaction negotiation to the pending negotiation's
+	/// constructor, aborting the negotiation if it fails.
+	fn with_interactive_tx_constructor<R, F>(&mut self, f: F) -> Result<R, msgs::TxAbort>
+	where F: FnOnce(&mut InteractiveTxConstructor) -> Result<R, interactivetxs::AbortReason> {
+		let channel_id = self.channel_id;
+		let constructor = self.pending_splice.as_mut()
+			.and_then(|pending_splice| pending_splice.interactive_tx_constructor.as_mut())
+			.ok_or(interactivetxs::AbortReason::UnexpectedCounterpartyMessage.into_tx_abort_msg(channel_id))?;
+		f(constructor).map_err(|reason| {
+			self.pending_splice = None;
+			reason.into_tx_abort_msg(channel_id)
+		})
+	}
+
+	pub fn tx_add_input(&mut self, msg: &msgs::TxAddInput) -> Result<InteractiveTxMessageSend, msgs::TxAbort> {
+		self.with_interactive_tx_constructor(|constructor| constructor.handle_tx_add_input(msg))
+	}
+
+	pub fn tx_add_output(&mut self, msg: &msgs::TxAddOutput) -> Result<InteractiveTxMessageSend, msgs::TxAbort> {
+		self.with_interactive_tx_constructor(|constructor| constructor.handle_tx_add_output(msg))
+	}
+
+	pub fn tx_remove_input(&mut self, msg: &msgs::TxRemoveInput) -> Result<InteractiveTxMessageSend, msgs::TxAbort> {
+		self.with_interactive_tx_constructor(|constructor| constructor.handle_tx_remove_input(msg))
+	}
+
+	pub fn tx_remove_output(&mut self, msg: &msgs::TxRemoveOutput) -> Result<InteractiveTxMessageSend, msgs::TxAbort> {
+		self.with_interactive_tx_constructor(|constructor| constructor.handle_tx_remove_output(msg))
+	}
+
+	/// Handles a `tx_complete` while negotiating the funding transaction of a dual-funded channel,
+	/// returning our response, if any, and once the negotiation completes, our `commitment_signed`
+	/// for the initial commitment transaction.
+	fn dual_funding_tx_complete<L: Deref>(
+		&mut self, msg: &msgs::TxComplete, logger: &L,
+	) -> Result<(Option<InteractiveTxMessageSend>, Option<FundingTxNegotiated>), msgs::TxAbort> where L::Target: Logger {
+		let (msg_send, constructed_tx) = self.with_interactive_tx_constructor(|constructor| constructor.handle_tx_complete(msg))?;
+		let constructed_tx = match constructed_tx {
+			Some(constructed_tx) => constructed_tx,
+			None => return Ok((msg_send, None)),
+		};
+
+		let pending_splice = self.pending_splice.take().expect("Negotiation completed without a pending negotiation");
+		let funding_transaction = constructed_tx.build_unsigned_tx();
+		// Negotiations are limited to `MAX_INPUTS_OUTPUTS_COUNT` inputs and outputs.
+		let funding_output_index = constructed_tx.shared_output_index() as u16;
+		let holder_input_count = constructed_tx.holder_owned_input_count() as u16;
+		let funding_txo = OutPoint { txid: funding_transaction.txid(), index: funding_output_index };
+		debug_assert_eq!(funding_transaction.output[funding_output_index as usize].value, self.channel_value_satoshis);
+
+		self.channel_transaction_parameters.funding_outpoint = Some(funding_txo);
+		self.holder_signer.as_mut().provide_channel_parameters(&self.channel_transaction_parameters);
+		// Our signer was derived before our counterparty's contribution was known.
+		self.holder_signer.as_mut().provide_renegotiated_funding(funding_txo, self.channel_value_satoshis);
+
+		let counterparty_keys = self.build_remote_transaction_keys();
+		let counterparty_initial_commitment_tx = self.build_commitment_transaction(
+			self.cur_counterparty_commitment_transaction_number, &counterparty_keys, false, false, logger).tx;
+		let signature = match &self.holder_signer {
+			// TODO (taproot|arik): move match into calling method for Taproot
+			ChannelSignerType::Ecdsa(ecdsa) => {
+				ecdsa.sign_counterparty_commitment(&counterparty_initial_commitment_tx, Vec::new(), Vec::new(), &self.secp_ctx)
+					.map(|(sig, _)| sig)
+			},
+			// TODO (taproot|arik)
+			#[cfg(taproot)]
+			_ => todo!()
+		}.map_err(|_| {
+			log_error!(logger, "Failed to sign the initial commitment transaction of channel {}", &self.channel_id);
+			msgs::TxAbort { channel_id: self.channel_id, data: b"Failed to sign commitment transaction".to_vec() }
+		})?;
+
+		self.channel_state = ChannelState::FundingNegotiated;
+		let splice_funding = SpliceFunding {
+			channel_value_satoshis: self.channel_value_satoshis,
+			value_to_self_msat: pending_splice.our_funding_contribution_satoshis as u64 * 1000,
+			funding_transaction,
+			funding_output_index,
+			shared_input_index: None,
+			holder_input_count,
+			holder_inputs_signed: holder_input_count == 0,
+			holder_sends_tx_signatures_first: constructed_tx.holder_sends_tx_signatures_first(),
+			received_commitment_signed: false,
+			holder_tx_signatures: None,
+			counterparty_tx_signatures: None,
+			confirmation_height: 0,
+			confirmed_in: None,
+			short_channel_id: None,
+			sent_splice_locked: false,
+			received_splice_locked: false,
+		};
+		log_info!(logger, "Negotiated funding transaction {} for channel {}", funding_txo.txid, &self.channel_id);
+		let unsigned_transaction = if splice_funding.holder_inputs_signed { None } else {
+			Some(splice_funding.funding_transaction.clone())
+		};
+		self.pending_splice_funding = Some(splice_funding);
+		let commitment_signed = msgs::CommitmentSigned {
+			channel_id: self.channel_id,
+			signature,
+			htlc_signatures: Vec::new(),
+			#[cfg(taproot)]
+			partial_signature_with_nonce: None,
+		};
+		Ok((msg_send, Some(FundingTxNegotiated { commitment_signed, unsigned_transaction })))
+	}
+
+	/// Handles our counterparty's `commitment_signed` for the initial commitment transaction of a
+	/// dual-funded channel, returning the [`ChannelMonitor`] for the channel.
+	fn dual_funding_commitment_signed<L: Deref>(
+		&mut self, msg: &msgs::CommitmentSigned, best_block: BestBlock, signer_provider: &SP, logger: &L
+	) -> Result<ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>, ChannelError> where L::Target: Logger {
+		if !matches!(self.channel_state, ChannelState::FundingNegotiated) {
+			return Err(ChannelError::Close("Received commitment_signed before the funding transaction was negotiated".to_owned()));
+		}
+		if self.commitment_secrets.get_min_seen_secret() != (1 << 48) ||
+				self.cur_counterparty_commitment_transaction_number != INITIAL_COMMITMENT_NUMBER ||
+				self.cur_holder_commitment_transaction_number != INITIAL_COMMITMENT_NUMBER {
+			panic!("Should not have advanced channel commitment tx numbers prior to commitment_signed");
+		}
+		if !msg.htlc_signatures.is_empty() {
+			return Err(ChannelError::Close("Got HTLC signatures for the initial commitment transaction".to_owned()));
+		}
+
+		let funding_script = self.get_funding_redeemscript();
+		let keys = self.build_holder_transaction_keys(self.cur_holder_commitment_transaction_number);
+		let initial_commitment_tx = self.build_commitment_transaction(self.cur_holder_commitment_transaction_number, &keys, true, false, logger).tx;
+		{
+			let trusted_tx = initial_commitment_tx.trust();
+			let initial_commitment_bitcoin_tx = trusted_tx.built_transaction();
+			let sighash = initial_commitment_bitcoin_tx.get_sighash_all(&funding_script, self.channel_value_satoshis);
+			// They sign our commitment transaction, allowing us to broadcast the tx if we wish.
+			if let Err(_) = self.secp_ctx.verify_ecdsa(&sighash, &msg.signature, self.counterparty_funding_pubkey()) {
+				return Err(ChannelError::Close("Invalid commitment_signed signature from peer".to_owned()));
+			}
+		}
+
+		let holder_commitment_tx = HolderCommitmentTransaction::new(
+			initial_commitment_tx,
+			msg.signature,
+			Vec::new(),
+			&self.get_holder_pubkeys().funding_pubkey,
+			self.counterparty_funding_pubkey()
+		);
+		if let Err(_) = self.holder_signer.as_ref().validate_holder_commitment(&holder_commitment_tx, Vec::new()) {
+			return Err(ChannelError::Close("Failed to validate our commitment".to_owned()));
+		}
+
+		let counterparty_keys = self.build_remote_transaction_keys();
+		let counterparty_initial_commitment_tx = self.build_commitment_transaction(
+			self.cur_counterparty_commitment_transaction_number, &counterparty_keys, false, false, logger).tx;
+		let funding_redeemscript = self.get_funding_redeemscript();
+		let funding_txo = self.get_funding_txo().unwrap();
+		let funding_txo_script = funding_redeemscript.to_v0_p2wsh();
+		let obscure_factor = get_commitment_transaction_number_obscure_factor(&self.get_holder_pubkeys().payment_point, &self.get_counterparty_pubkeys().payment_point, self.is_outbound());
+		let shutdown_script = self.shutdown_scriptpubkey.clone().map(|script| script.into_inner());
+		let mut monitor_signer = signer_provider.derive_channel_signer(self.channel_value_satoshis, self.channel_keys_id);
+		monitor_signer.provide_channel_parameters(&self.channel_transaction_parameters);
+		let channel_monitor = ChannelMonitor::new(self.secp_ctx.clone(), monitor_signer,
+		                                          shutdown_script, self.get_holder_selected_contest_delay(),
+		                                          &self.destination_script, (funding_txo, funding_txo_script),
+		                                          &self.channel_transaction_parameters,
+		                                          funding_redeemscript.clone(), self.channel_value_satoshis,
+		                                          obscure_factor,
+		                                          holder_commitment_tx, best_block, self.counterparty_node_id, self.channel_id());
+		channel_monitor.provide_initial_counterparty_commitment_tx(
+			counterparty_initial_commitment_tx.trust().txid(), Vec::new(),
+			self.cur_counterparty_commitment_transaction_number,
+			self.counterparty_cur_commitment_point.unwrap(), self.feerate_per_kw,
+			counterparty_initial_commitment_tx.to_broadcaster_value_sat(),
+			counterparty_initial_commitment_tx.to_countersignatory_value_sat(), logger);
+
+		self.channel_state = ChannelState::AwaitingChannelReady(AwaitingChannelReadyFlags::new());
+		self.cur_holder_commitment_transaction_number -= 1;
+		self.cur_counterparty_commitment_transaction_number -= 1;
+		self.pending_splice_funding.as_mut().unwrap().received_commitment_signed = true;
+		log_info!(logger, "Received commitment_signed from peer for dual-funded channel {}", &self.channel_id);
+		Ok(channel_monitor)
+	}
+
+	/// Checks an `accept_channel` (or the V1-equivalent of an `accept_channel2`) message from our
+	/// counterparty and updates our state with the parameters it sets.
+	fn do_accept_channel_checks(
+		&mut self, default_limits: &ChannelHandshakeLimits, their_features: &InitFeatures, msg: &msgs::AcceptChannel,
+	) -> Result<(), ChannelError> {
+		let peer_limits = if let Some(ref limits) = self.inbound_handshake_limits_override { limits } else { default_limits };
+
+		// Check sanity of message fields:
+		if !self.is_outbound() {
+			return Err(ChannelError::Close("Got an accept_channel message from an inbound peer".to_owned()));
+		}
+		if !matches!(self.channel_state, ChannelState::NegotiatingFunding(flags) if flags == NegotiatingFundingFlags::OUR_INIT_SENT) {
+			return Err(ChannelError::Close("Got an accept_channel message at a strange time".to_owned()));
+		}
+		if msg.dust_limit_satoshis > 21000000 * 100000000 {
+			return Err(ChannelError::Close(format!("Peer never wants payout outputs? dust_limit_satoshis was {}", msg.dust_limit_satoshis)));
+		}
+		if msg.channel_reserve_satoshis > self.channel_value_satoshis {
+			return Err(ChannelError::Close(format!("Bogus channel_reserve_satoshis ({}). Must not be greater than ({})", msg.channel_reserve_satoshis, self.channel_value_satoshis)));
+		}
+		if msg.dust_limit_satoshis > self.holder_selected_channel_reserve_satoshis {
+			return Err(ChannelError::Close(format!("Dust limit ({}) is bigger than our channel reserve ({})", msg.dust_limit_satoshis, self.holder_selected_channel_reserve_satoshis)));
+		}
+		if msg.channel_reserve_satoshis > self.channel_value_satoshis - self.holder_selected_channel_reserve_satoshis {
+			return Err(ChannelError::Close(format!("Bogus channel_reserve_satoshis ({}). Must not be greater than channel value minus our reserve ({})",
+				msg.channel_reserve_satoshis, self.channel_value_satoshis - self.holder_selected_channel_reserve_satoshis)));
+		}
+		let full_channel_value_msat = (self.channel_value_satoshis - msg.channel_reserve_satoshis) * 1000;
+		if msg.htlc_minimum_msat >= full_channel_value_msat {
+			return Err(ChannelError::Close(format!("Minimum htlc value ({}) is full channel value ({})", msg.htlc_minimum_msat, full_channel_value_msat)));
+		}
+		let max_delay_acceptable = u16::min(peer_limits.their_to_self_delay, MAX_LOCAL_BREAKDOWN_TIMEOUT);
+		if msg.to_self_delay > max_delay_acceptable {
+			return Err(ChannelError::Close(format!("They wanted our payments to be delayed by a needlessly long period. Upper limit: {}. Actual: {}", max_delay_acceptable, msg.to_self_delay)));
+		}
+		if msg.max_accepted_htlcs < 1 {
+			return Err(ChannelError::Close("0 max_accepted_htlcs makes for a useless channel".to_owned()));
+		}
+		let max_htlcs = max_accepted_htlcs_limit(&self.channel_type);
+		if msg.max_accepted_htlcs > max_htlcs {
+			return Err(ChannelError::Close(format!("max_accepted_htlcs was {}. It must not be larger than {}", msg.max_accepted_htlcs, max_htlcs)));
+		}
+
+		// Now check against optional parameters as set by config...
+		if msg.htlc_minimum_msat > peer_limits.max_htlc_minimum_msat {
+			return Err(ChannelError::Close(format!("htlc_minimum_msat ({}) is higher than the user specified limit ({})", msg.htlc_minimum_msat, peer_limits.max_htlc_minimum_msat)));
+		}
+		if msg.max_htlc_value_in_flight_msat < peer_limits.min_max_htlc_value_in_flight_msat {
+			return Err(ChannelError::Close(format!("max_htlc_value_in_flight_msat ({}) is less than the user specified limit ({})", msg.max_htlc_value_in_flight_msat, peer_limits.min_max_htlc_value_in_flight_msat)));
+		}
+		if msg.channel_reserve_satoshis > peer_limits.max_channel_reserve_satoshis {
+			return Err(ChannelError::Close(format!("channel_reserve_satoshis ({}) is higher than the user specified limit ({})", msg.channel_reserve_satoshis, peer_limits.max_channel_reserve_satoshis)));
+		}
+		if msg.max_accepted_htlcs < peer_limits.min_max_accepted_htlcs {
+			return Err(ChannelError::Close(format!("max_accepted_htlcs ({}) is less than the user specified limit ({})", msg.max_accepted_htlcs, peer_limits.min_max_accepted_htlcs)));
+		}
+		if msg.dust_limit_satoshis < MIN_CHAN_DUST_LIMIT_SATOSHIS {
+			return Err(ChannelError::Close(format!("dust_limit_satoshis ({}) is less than the implementation limit ({})", msg.dust_limit_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS)));
+		}
+		if msg.dust_limit_satoshis > MAX_CHAN_DUST_LIMIT_SATOSHIS {
+			return Err(ChannelError::Close(format!("dust_limit_satoshis ({}) is greater than the implementation limit ({})", msg.dust_limit_satoshis, MAX_CHAN_DUST_LIMIT_SATOSHIS)));
+		}
+		if msg.minimum_depth > peer_limits.max_minimum_depth {
+			return Err(ChannelError::Close(format!("We consider the minimum depth to be unreasonably large. Expected minimum: ({}). Actual: ({})", peer_limits.max_minimum_depth, msg.minimum_depth)));
+		}
+
+		if let Some(ty) = &msg.channel_type {
+			if *ty != self.channel_type {
+				return Err(ChannelError::Close("Channel Type in accept_channel didn't match the one sent in open_channel.".to_owned()));
+			}
+		} else if their_features.supports_channel_type() {
+			// Assume they've accepted the channel type as they said they understand it.
+		} else {
+			let channel_type = ChannelTypeFeatures::from_init(&their_features);
+			if channel_type != ChannelTypeFeatures::only_static_remote_key() {
+				return Err(ChannelError::Close("Only static_remote_key is supported for non-negotiated channel types".to_owned()));
+			}
+			self.channel_type = channel_type.clone();
+			self.channel_transaction_parameters.channel_type_features = channel_type;
+		}
+
+		let counterparty_shutdown_scriptpubkey = if their_features.supports_upfront_shutdown_script() {
+			match &msg.shutdown_scriptpubkey {
+				&Some(ref script) => {
+					// Peer is signaling upfront_shutdown and has opt-out with a 0-length script. We don't enforce anything
+					if script.len() == 0 {
+						None
+					} else {
+						if !script::is_bolt2_compliant(&script, their_features) {
+							return Err(ChannelError::Close(format!("Peer is signaling upfront_shutdown but has provided an unacceptable scriptpubkey format: {}", script)));
+						}
+						Some(script.clone())
+					}
+				},
+				// Peer is signaling upfront shutdown but don't opt-out with correct mechanism (a.k.a 0-length script). Peer looks buggy, we fail the channel
+				&None => {
+					return Err(ChannelError::Close("Peer is signaling upfront_shutdown but we don't get any script. Use 0-length script to opt-out".to_owned()));
+				}
+			}
+		} else { None };
+
+		self.counterparty_dust_limit_satoshis = msg.dust_limit_satoshis;
+		self.counterparty_max_htlc_value_in_flight_msat = cmp::min(msg.max_htlc_value_in_flight_msat, self.channel_value_satoshis * 1000);
+		self.counterparty_selected_channel_reserve_satoshis = Some(msg.channel_reserve_satoshis);
+		self.counterparty_htlc_minimum_msat = msg.htlc_minimum_msat;
+		self.counterparty_max_accepted_htlcs = msg.max_accepted_htlcs;
+
+		if peer_limits.trust_own_funding_0conf {
+			self.minimum_depth = Some(msg.minimum_depth);
+		} else {
+			self.minimum_depth = Some(cmp::max(1, msg.minimum_depth));
+		}
+
+		let counterparty_pubkeys = ChannelPublicKeys {
+			funding_pubkey: msg.funding_pubkey,
+			revocation_basepoint: RevocationBasepoint::from(msg.revocation_basepoint),
+			payment_point: msg.payment_point,
+			delayed_payment_basepoint: DelayedPaymentBasepoint::from(msg.delayed_payment_basepoint),
+			htlc_basepoint: HtlcBasepoint::from(msg.htlc_basepoint)
+		};
+
+		self.channel_transaction_parameters.counterparty_parameters = Some(CounterpartyChannelTransactionParameters {
+			selected_contest_delay: msg.to_self_delay,
+			pubkeys: counterparty_pubkeys,
+		});
+
+		self.counterparty_cur_commitment_point = Some(msg.first_per_commitment_point);
+		self.counterparty_shutdown_scriptpubkey = counterparty_shutdown_scriptpubkey;
+
+		self.channel_state = ChannelState::NegotiatingFunding(
+			NegotiatingFundingFlags::OUR_INIT_SENT | NegotiatingFundingFlags::THEIR_INIT_SENT
+		);
+		self.inbound_handshake_limits_override = None; // We're done enforcing limits on our peer's handshake now.
+
+		Ok(())
+	}
 }
 
 // Internal utility functions for channels
@@ -2507,6 +3129,62 @@ pub(crate) fn get_legacy_default_holder_selected_channel_reserve_satoshis(channe
 	cmp::min(channel_value_satoshis, cmp::max(q, 1000))
 }
 
+/// Returns the channel reserve a party to a dual-funded channel must keep, as fixed by the spec to
+/// 1% of the channel value, but no less than the dust limit of the other party.
+///
+/// Guaranteed to return a value no larger than channel_value_satoshis
+pub(crate) fn get_v2_channel_reserve_satoshis(channel_value_satoshis: u64, dust_limit_satoshis: u64) -> u64 {
+	let channel_reserve_satoshis = channel_value_satoshis / 100;
+	cmp::min(channel_value_satoshis, cmp::max(channel_reserve_satoshis, dust_limit_satoshis))
+}
+
+/// Checks the inputs we contribute to an interactively constructed funding transaction, returning
+/// them along with a change output for any value left over after contributing `value_satoshis` and
+/// paying the fee for our inputs and outputs, unless it would be dust.
+fn get_funding_inputs_and_change(
+	value_satoshis: u64, inputs: Vec<(TxIn, Transaction)>, change_script: Option<ScriptBuf>,
+	is_initiator: bool, has_shared_input: bool, shared_output_script: &Script, funding_feerate_per_kw: u32,
+) -> Result<(Vec<(TxIn, TransactionU16LenLimited)>, Vec<TxOut>), APIError> {
+	let mut input_value_satoshis = 0;
+	let mut prev_outputs = Vec::with_capacity(inputs.len());
+	let mut funding_inputs = Vec::with_capacity(inputs.len());
+	for (txin, prevtx) in inputs {
+		if prevtx.txid() != txin.previous_output.txid {
+			return Err(APIError::APIMisuseError { err: "Funding input does not spend its given previous transaction".to_owned() });
+		}
+		let prev_output = match prevtx.output.get(txin.previous_output.vout as usize) {
+			Some(prev_output) if prev_output.script_pubkey.is_witness_program() => prev_output.clone(),
+			_ => return Err(APIError::APIMisuseError { err: "Funding inputs must spend existing segwit outputs".to_owned() }),
+		};
+		input_value_satoshis += prev_output.value;
+		prev_outputs.push(prev_output);
+		let prevtx = TransactionU16LenLimited::new(prevtx).map_err(|_| APIError::APIMisuseError {
+			err: "Funding input previous transactions must be smaller than 65535 bytes".to_owned(),
+		})?;
+		funding_inputs.push((txin, prevtx));
+	}
+
+	let weight = interactivetxs::estimate_contribution_weight(is_initiator, has_shared_input,
+		shared_output_script, prev_outputs.iter(), &[]);
+	let fee_satoshis = fee_for_weight(funding_feerate_per_kw, weight);
+	if input_value_satoshis < value_satoshis + fee_satoshis {
+		return Err(APIError::APIMisuseError {
+			err: format!("Funding inputs worth {} sats cannot cover contributing {} sats and a fee of {} sats",
+				input_value_satoshis, value_satoshis, fee_satoshis),
+		});
+	}
+	let mut funding_outputs = Vec::new();
+	if let Some(change_script) = change_script {
+		let change_fee_satoshis = fee_for_weight(funding_feerate_per_kw,
+			weight + interactivetxs::get_output_weight(&change_script));
+		let change_value_satoshis = input_value_satoshis.saturating_sub(value_satoshis + change_fee_satoshis);
+		if change_value_satoshis >= change_script.dust_value().to_sat() {
+			funding_outputs.push(TxOut { value: change_value_satoshis, script_pubkey: change_script });
+		}
+	}
+	Ok((funding_inputs, funding_outputs))
+}
+
 // Get the fee cost in SATS of a commitment tx with a given number of HTLC outputs.
 // Note that num_htlcs should not include dust HTLCs.
 #[inline]
@@ -2602,6 +3280,13 @@ impl<SP: Deref> Channel<SP> where
 		feerate_per_kw: u32, cur_feerate_per_kw: Option<u32>, logger: &L
 	) -> Result<(), ChannelError> where F::Target: FeeEstimator, L::Target: Logger,
 	{
+		if channel_type.supports_anchor_zero_fee_commitments() {
+			// Zero-fee commitment transactions are always bumped via their shared anchor instead.
+			if feerate_per_kw != 0 {
+				return Err(ChannelError::Close(format!("Peer's feerate must be zero on zero-fee commitment channels. Actual: {}", feerate_per_kw)));
+			}
+			return Ok(());
+		}
 		let lower_limit_conf_target = if channel_type.supports_anchors_zero_fee_htlc_tx() {
 			ConfirmationTarget::MinAllowedAnchorChannelRemoteFee
 		} else {
@@ -3123,6 +3808,12 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent update_add_htlc when we needed a channel_reestablish".to_owned()));
 		}
+		if self.context.is_splice_pending() {
+			return Err(ChannelError::Close("Peer sent update_add_htlc while a splice was pending".to_owned()));
+		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Got add HTLC message while quiescent".to_owned()));
+		}
 		if msg.amount_msat > self.context.channel_value_satoshis * 1000 {
 			return Err(ChannelError::Close("Remote side tried to send more than the total value of the channel".to_owned()));
 		}
@@ -3206,8 +3897,8 @@ impl<SP: Deref> Channel<SP> where
 				let htlc_candidate = HTLCCandidate::new(msg.amount_msat, HTLCInitiator::RemoteOffered);
 				self.context.next_remote_commit_tx_fee_msat(htlc_candidate, None) // Don't include the extra fee spike buffer HTLC in calculations
 			};
-			let anchor_outputs_value_msat = if !self.context.is_outbound() && self.context.get_channel_type().supports_anchors_zero_fee_htlc_tx() {
-				ANCHOR_OUTPUT_VALUE_SATOSHI * 2 * 1000
+			let anchor_outputs_value_msat = if !self.context.is_outbound() {
+				anchor_outputs_value_sat(self.context.get_channel_type()) * 1000
 			} else {
 				0
 			};
@@ -3219,11 +3910,7 @@ impl<SP: Deref> Channel<SP> where
 			}
 		}
 
-		let anchor_outputs_value_msat = if self.context.get_channel_type().supports_anchors_zero_fee_htlc_tx() {
-			ANCHOR_OUTPUT_VALUE_SATOSHI * 2 * 1000
-		} else {
-			0
-		};
+		let anchor_outputs_value_msat = anchor_outputs_value_sat(self.context.get_channel_type()) * 1000;
 		if !self.context.is_outbound() {
 			// `Some(())` is for the fee spike buffer we keep for the remote. This deviates from
 			// the spec because the fee spike buffer requirement doesn't exist on the receiver's
@@ -3311,6 +3998,9 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent update_fulfill_htlc when we needed a channel_reestablish".to_owned()));
 		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent update_fulfill_htlc while quiescent".to_owned()));
+		}
 
 		self.mark_outbound_htlc_removed(msg.htlc_id, Some(msg.payment_preimage), None).map(|htlc| (htlc.source.clone(), htlc.amount_msat))
 	}
@@ -3322,6 +4012,9 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent update_fail_htlc when we needed a channel_reestablish".to_owned()));
 		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent update_fail_htlc while quiescent".to_owned()));
+		}
 
 		self.mark_outbound_htlc_removed(msg.htlc_id, None, Some(fail_reason))?;
 		Ok(())
@@ -3334,6 +4027,9 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent update_fail_malformed_htlc when we needed a channel_reestablish".to_owned()));
 		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent update_fail_malformed_htlc while quiescent".to_owned()));
+		}
 
 		self.mark_outbound_htlc_removed(msg.htlc_id, None, Some(fail_reason))?;
 		Ok(())
@@ -3342,16 +4038,22 @@ impl<SP: Deref> Channel<SP> where
 	pub fn commitment_signed<L: Deref>(&mut self, msg: &msgs::CommitmentSigned, logger: &L) -> Result<Option<ChannelMonitorUpdate>, ChannelError>
 		where L::Target: Logger
 	{
-		if !matches!(self.context.channel_state, ChannelState::ChannelReady(_)) {
-			return Err(ChannelError::Close("Got commitment signed message when channel was not in an operational state".to_owned()));
-		}
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent commitment_signed when we needed a channel_reestablish".to_owned()));
 		}
-		if self.context.channel_state.is_both_sides_shutdown() && self.context.last_sent_closing_fee.is_some() {
-			return Err(ChannelError::Close("Peer sent commitment_signed after we'd started exchanging closing_signeds".to_owned()));
+		// No updates may be made to the channel while a splice is pending, so this can only be for
+		// the splice's funding transaction. Fee-bumps of a dual-funded channel's funding transaction
+		// are negotiated before the channel is ready.
+		if self.context.pending_splice_funding.is_some() {
+			return self.splice_commitment_signed(msg, logger);
 		}
-
+		if !matches!(self.context.channel_state, ChannelState::ChannelReady(_)) {
+			return Err(ChannelError::Close("Got commitment signed message when channel was not in an operational state".to_owned()));
+		}
+		if self.context.channel_state.is_both_sides_shutdown() && self.context.last_sent_closing_fee.is_some() {
+			return Err(ChannelError::Close("Peer sent commitment_signed after we'd started exchanging closing_signeds".to_owned()));
+		}
+
 		let funding_script = self.context.get_funding_redeemscript();
 
 		let keys = self.context.build_holder_transaction_keys(self.context.cur_holder_commitment_transaction_number);
@@ -3608,11 +4310,11 @@ impl<SP: Deref> Channel<SP> where
 				let fail_htlc_res = match &htlc_update {
 					&HTLCUpdateAwaitingACK::AddHTLC {
 						amount_msat, cltv_expiry, ref payment_hash, ref source, ref onion_routing_packet,
-						skimmed_fee_msat, blinding_point, ..
+						skimmed_fee_msat, blinding_point, endorsed, ..
 					} => {
 						match self.send_htlc(
 							amount_msat, *payment_hash, cltv_expiry, source.clone(), onion_routing_packet.clone(),
-							false, skimmed_fee_msat, blinding_point, fee_estimator, logger
+							false, skimmed_fee_msat, blinding_point, endorsed, fee_estimator, logger
 						) {
 							Ok(_) => update_add_count += 1,
 							Err(e) => {
@@ -3776,7 +4478,10 @@ impl<SP: Deref> Channel<SP> where
 		// OK, we step the channel here and *then* if the new generation fails we can fail the
 		// channel based on that, but stepping stuff here should be safe either way.
 		self.context.channel_state.clear_awaiting_remote_revoke();
-		self.context.sent_message_awaiting_response = None;
+		if !self.context.channel_state.is_local_stfu_sent() {
+			// If we've sent `stfu` we're still waiting on theirs.
+			self.context.sent_message_awaiting_response = None;
+		}
 		self.context.counterparty_prev_commitment_point = self.context.counterparty_cur_commitment_point;
 		self.context.counterparty_cur_commitment_point = Some(msg.next_per_commitment_point);
 		self.context.cur_counterparty_commitment_transaction_number -= 1;
@@ -3939,7 +4644,14 @@ impl<SP: Deref> Channel<SP> where
 			return_with_htlcs_to_fail!(Vec::new());
 		}
 
-		match self.free_holding_cell_htlcs(fee_estimator, logger) {
+		// We can't send any new updates once we've sent `stfu`, so they'll stay in the holding cell
+		// until quiescence is terminated.
+		let holding_cell_res = if self.context.channel_state.is_local_stfu_sent() || self.context.channel_state.is_quiescent() {
+			(None, Vec::new())
+		} else {
+			self.free_holding_cell_htlcs(fee_estimator, logger)
+		};
+		match holding_cell_res {
 			(Some(mut additional_update), htlcs_to_fail) => {
 				// free_holding_cell_htlcs may bump latest_monitor_id multiple times but we want them to be
 				// strictly increasing by one, so decrement it here.
@@ -4012,6 +4724,10 @@ impl<SP: Deref> Channel<SP> where
 		if !self.context.is_live() {
 			panic!("Cannot update fee while peer is disconnected/we're awaiting a monitor update (ChannelManager should have caught this)");
 		}
+		if self.context.is_splice_pending() {
+			log_debug!(logger, "Cannot send new feerate at {} while a splice is pending", feerate_per_kw);
+			return None;
+		}
 
 		// Before proposing a feerate update, check that we can actually afford the new fee.
 		let inbound_stats = self.context.get_inbound_pending_htlc_stats(Some(feerate_per_kw));
@@ -4131,6 +4847,18 @@ impl<SP: Deref> Channel<SP> where
 
 		self.context.sent_message_awaiting_response = None;
 
+		// Quiescence is terminated upon disconnection. If we had requested it but never made it to
+		// quiescence, we'll try again once the channel is reestablished.
+		if matches!(self.context.channel_state, ChannelState::ChannelReady(_)) {
+			if self.context.channel_state.is_local_stfu_sent() {
+				self.context.channel_state.clear_local_stfu_sent();
+				self.context.channel_state.set_awaiting_quiescence();
+			}
+			self.context.channel_state.clear_remote_stfu_sent();
+			self.context.channel_state.clear_quiescent();
+		}
+		self.context.is_holder_quiescence_initiator = None;
+
 		self.context.channel_state.set_peer_disconnected();
 		log_trace!(logger, "Peer disconnection resulted in {} remote-announced HTLC drops on channel {}", inbound_drop_count, &self.context.channel_id());
 		Ok(())
@@ -4221,9 +4949,11 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			self.context.monitor_pending_revoke_and_ack = false;
 			self.context.monitor_pending_commitment_signed = false;
+			self.context.monitor_pending_tx_signatures = false;
 			return MonitorRestoreUpdates {
 				raa: None, commitment_update: None, order: RAACommitmentOrder::RevokeAndACKFirst,
-				accepted_htlcs, failed_htlcs, finalized_claimed_htlcs, funding_broadcastable, channel_ready, announcement_sigs
+				accepted_htlcs, failed_htlcs, finalized_claimed_htlcs, funding_broadcastable, channel_ready, announcement_sigs,
+				tx_signatures: None, splice_funding_broadcastable: None,
 			};
 		}
 
@@ -4239,13 +4969,16 @@ impl<SP: Deref> Channel<SP> where
 
 		self.context.monitor_pending_revoke_and_ack = false;
 		self.context.monitor_pending_commitment_signed = false;
+		self.context.monitor_pending_tx_signatures = false;
+		let (tx_signatures, splice_funding_broadcastable) = self.maybe_get_splice_tx_signatures(logger);
 		let order = self.context.resend_order.clone();
 		log_debug!(logger, "Restored monitor updating in channel {} resulting in {}{} commitment update and {} RAA, with {} first",
 			&self.context.channel_id(), if funding_broadcastable.is_some() { "a funding broadcastable, " } else { "" },
 			if commitment_update.is_some() { "a" } else { "no" }, if raa.is_some() { "an" } else { "no" },
 			match order { RAACommitmentOrder::CommitmentFirst => "commitment", RAACommitmentOrder::RevokeAndACKFirst => "RAA"});
 		MonitorRestoreUpdates {
-			raa, commitment_update, order, accepted_htlcs, failed_htlcs, finalized_claimed_htlcs, funding_broadcastable, channel_ready, announcement_sigs
+			raa, commitment_update, order, accepted_htlcs, failed_htlcs, finalized_claimed_htlcs, funding_broadcastable, channel_ready, announcement_sigs,
+			tx_signatures, splice_funding_broadcastable,
 		}
 	}
 
@@ -4258,6 +4991,15 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() {
 			return Err(ChannelError::Close("Peer sent update_fee when we needed a channel_reestablish".to_owned()));
 		}
+		if self.context.is_splice_pending() {
+			return Err(ChannelError::Close("Peer sent update_fee while a splice was pending".to_owned()));
+		}
+		if self.context.channel_type.supports_anchor_zero_fee_commitments() {
+			return Err(ChannelError::Close("Peer sent update_fee on a zero-fee commitment channel".to_owned()));
+		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent update_fee while quiescent".to_owned()));
+		}
 		Channel::<SP>::check_remote_fee(&self.context.channel_type, fee_estimator, msg.feerate_per_kw, Some(self.context.feerate_per_kw), logger)?;
 
 		self.context.pending_update_fee = Some((msg.feerate_per_kw, FeeUpdateState::RemoteAnnounced));
@@ -4337,6 +5079,7 @@ impl<SP: Deref> Channel<SP> where
 					onion_routing_packet: (**onion_packet).clone(),
 					skimmed_fee_msat: htlc.skimmed_fee_msat,
 					blinding_point: htlc.blinding_point,
+					endorsed: htlc.endorsed,
 				});
 			}
 		}
@@ -4487,6 +5230,9 @@ impl<SP: Deref> Channel<SP> where
 
 		let announcement_sigs = self.get_announcement_sigs(node_signer, chain_hash, user_config, best_block.height(), logger);
 
+		let (splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked) =
+			self.get_splice_reestablish_responses(msg, logger);
+
 		if matches!(self.context.channel_state, ChannelState::AwaitingChannelReady(_)) {
 			// If we're waiting on a monitor update, we shouldn't re-send any channel_ready's.
 			if !self.context.channel_state.is_our_channel_ready() ||
@@ -4500,6 +5246,7 @@ impl<SP: Deref> Channel<SP> where
 					raa: None, commitment_update: None,
 					order: RAACommitmentOrder::CommitmentFirst,
 					shutdown_msg, announcement_sigs,
+					splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked,
 				});
 			}
 
@@ -4514,6 +5261,7 @@ impl<SP: Deref> Channel<SP> where
 				raa: None, commitment_update: None,
 				order: RAACommitmentOrder::CommitmentFirst,
 				shutdown_msg, announcement_sigs,
+				splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked,
 			});
 		}
 
@@ -4569,6 +5317,7 @@ impl<SP: Deref> Channel<SP> where
 				raa: required_revoke,
 				commitment_update: None,
 				order: self.context.resend_order.clone(),
+				splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked,
 			})
 		} else if msg.next_local_commitment_number == next_counterparty_commitment_number - 1 {
 			if required_revoke.is_some() {
@@ -4583,6 +5332,7 @@ impl<SP: Deref> Channel<SP> where
 					channel_ready, shutdown_msg, announcement_sigs,
 					commitment_update: None, raa: None,
 					order: self.context.resend_order.clone(),
+					splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked,
 				})
 			} else {
 				Ok(ReestablishResponses {
@@ -4590,6 +5340,7 @@ impl<SP: Deref> Channel<SP> where
 					raa: required_revoke,
 					commitment_update: self.get_last_commitment_update_for_send(logger).ok(),
 					order: self.context.resend_order.clone(),
+					splice_commitment_signed, tx_signatures, splice_funding_broadcastable, tx_abort, splice_locked,
 				})
 			}
 		} else if msg.next_local_commitment_number < next_counterparty_commitment_number {
@@ -4760,6 +5511,164 @@ impl<SP: Deref> Channel<SP> where
 		*ticks_elapsed >= DISCONNECT_PEER_AWAITING_RESPONSE_TICKS
 	}
 
+	/// Returns whether there are any updates to the channel which have yet to be irrevocably
+	/// committed by both sides. These must all be resolved before we can send `stfu`.
+	fn has_pending_channel_update(&self) -> bool {
+		if self.context.channel_state.is_awaiting_remote_revoke() || self.context.expecting_peer_commitment_signed ||
+			self.context.monitor_pending_revoke_and_ack || self.context.monitor_pending_commitment_signed ||
+			self.context.signer_pending_commitment_update || self.context.pending_update_fee.is_some()
+		{
+			return true;
+		}
+		self.context.pending_inbound_htlcs.iter().any(|htlc| !matches!(htlc.state, InboundHTLCState::Committed)) ||
+			self.context.pending_outbound_htlcs.iter().any(|htlc| !matches!(htlc.state, OutboundHTLCState::Committed))
+	}
+
+	/// Requests that the channel be made quiescent. Any pending updates will first be irrevocably
+	/// committed, after which we'll send `stfu` to the counterparty. Returns the `stfu` message if
+	/// it can be sent immediately, otherwise it will be returned later by [`Self::try_send_stfu`].
+	pub fn propose_quiescence<L: Deref>(
+		&mut self, logger: &L,
+	) -> Result<Option<msgs::Stfu>, ChannelError> where L::Target: Logger {
+		log_debug!(logger, "Attempting to initiate quiescence for channel {}", &self.context.channel_id());
+
+		if !self.context.is_live() {
+			return Err(ChannelError::Ignore("Channel is not in a live state to propose quiescence".to_owned()));
+		}
+		if self.context.channel_state.is_awaiting_quiescence() || self.context.channel_state.is_local_stfu_sent() {
+			return Err(ChannelError::Ignore("Channel is already awaiting quiescence".to_owned()));
+		}
+		if self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::Ignore("Channel is already quiescent".to_owned()));
+		}
+
+		self.context.channel_state.set_awaiting_quiescence();
+		Ok(self.try_send_stfu(logger))
+	}
+
+	// Builds our `stfu` message, marking it as sent. Must only be called once all pending updates
+	// have been irrevocably committed.
+	fn send_stfu<L: Deref>(&mut self, logger: &L) -> msgs::Stfu where L::Target: Logger {
+		debug_assert!(!self.context.channel_state.is_local_stfu_sent());
+		debug_assert!(!self.has_pending_channel_update());
+
+		let initiator = if self.context.channel_state.is_remote_stfu_sent() {
+			// They've already sent `stfu`, so this is our response to their request.
+			self.context.channel_state.clear_remote_stfu_sent();
+			self.context.channel_state.clear_awaiting_quiescence();
+			self.context.channel_state.set_quiescent();
+			self.context.is_holder_quiescence_initiator = Some(false);
+			// Give the initiator a bounded amount of time to complete whatever they needed the
+			// channel to be quiescent for.
+			self.mark_awaiting_response();
+			log_debug!(logger, "Responding to quiescence request for channel {}, channel is now quiescent", &self.context.channel_id());
+			false
+		} else {
+			debug_assert!(self.context.channel_state.is_awaiting_quiescence());
+			self.context.channel_state.set_local_stfu_sent();
+			self.mark_awaiting_response();
+			log_debug!(logger, "Sending stfu as quiescence initiator for channel {}", &self.context.channel_id());
+			true
+		};
+
+		msgs::Stfu { channel_id: self.context.channel_id, initiator: initiator as u8 }
+	}
+
+	/// Handles an `stfu` message from our counterparty, returning our own `stfu` in response if it
+	/// can be sent immediately.
+	pub fn stfu<L: Deref>(
+		&mut self, msg: &msgs::Stfu, logger: &L,
+	) -> Result<Option<msgs::Stfu>, ChannelError> where L::Target: Logger {
+		if !matches!(self.context.channel_state, ChannelState::ChannelReady(_)) {
+			return Err(ChannelError::Warn("Peer sent stfu when the channel was not ready".to_owned()));
+		}
+		if self.context.channel_state.is_peer_disconnected() {
+			return Err(ChannelError::Close("Peer sent stfu when we needed a channel_reestablish".to_owned()));
+		}
+		if self.context.channel_state.is_remote_stfu_sent() || self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent duplicate stfu".to_owned()));
+		}
+		if msg.initiator > 1 {
+			return Err(ChannelError::WarnAndDisconnect("Peer sent stfu with an invalid initiator value".to_owned()));
+		}
+
+		if self.context.channel_state.is_local_stfu_sent() {
+			// We've already sent `stfu` (either as the initiator or we're racing with theirs), so
+			// the channel is now quiescent. If we both claim to be the initiator, the channel funder
+			// wins.
+			let is_holder_initiator = msg.initiator == 0 || self.context.is_outbound();
+			self.context.channel_state.clear_local_stfu_sent();
+			self.context.channel_state.clear_awaiting_quiescence();
+			self.context.channel_state.set_quiescent();
+			self.context.is_holder_quiescence_initiator = Some(is_holder_initiator);
+			if is_holder_initiator {
+				self.context.sent_message_awaiting_response = None;
+			} else {
+				self.mark_awaiting_response();
+			}
+			log_debug!(logger, "Received stfu for channel {}, channel is now quiescent with {} as the initiator",
+				&self.context.channel_id(), if is_holder_initiator { "us" } else { "our counterparty" });
+			return Ok(None);
+		}
+
+		// Quiescence requires that the sender has no pending updates of its own, which we can only
+		// partially check here as the rest are ours to resolve before we respond.
+		let has_remote_pending_update =
+			self.context.pending_inbound_htlcs.iter().any(|htlc| matches!(htlc.state, InboundHTLCState::RemoteAnnounced(_))) ||
+			self.context.pending_outbound_htlcs.iter().any(|htlc| matches!(htlc.state, OutboundHTLCState::RemoteRemoved(_))) ||
+			matches!(self.context.pending_update_fee, Some((_, FeeUpdateState::RemoteAnnounced)));
+		if has_remote_pending_update {
+			return Err(ChannelError::WarnAndDisconnect(
+				"Peer sent stfu while it still had pending updates which were not yet committed".to_owned()));
+		}
+
+		self.context.channel_state.set_remote_stfu_sent();
+		log_debug!(logger, "Received stfu for channel {} from the quiescence initiator", &self.context.channel_id());
+		Ok(self.try_send_stfu(logger))
+	}
+
+	/// Returns our `stfu` message if we're awaiting quiescence (either because we requested it or
+	/// our counterparty did) and all pending updates have since been irrevocably committed.
+	pub fn try_send_stfu<L: Deref>(&mut self, logger: &L) -> Option<msgs::Stfu> where L::Target: Logger {
+		let awaiting_quiescence = self.context.channel_state.is_awaiting_quiescence() ||
+			self.context.channel_state.is_remote_stfu_sent();
+		if !awaiting_quiescence || self.context.channel_state.is_local_stfu_sent() ||
+			self.context.channel_state.is_quiescent()
+		{
+			return None;
+		}
+		if !self.context.is_live() || self.has_pending_channel_update() {
+			return None;
+		}
+		Some(self.send_stfu(logger))
+	}
+
+	/// Returns whether the channel is currently quiescent, i.e. both sides have exchanged `stfu`
+	/// and no further updates may be sent.
+	pub fn is_quiescent(&self) -> bool {
+		self.context.channel_state.is_quiescent()
+	}
+
+	/// Returns whether we initiated the current quiescence session, or `None` if the channel is not
+	/// quiescent.
+	pub fn quiescence_initiator(&self) -> Option<bool> {
+		if !self.context.channel_state.is_quiescent() {
+			return None;
+		}
+		Some(self.context.is_holder_quiescence_initiator.unwrap_or(false))
+	}
+
+	/// Terminates quiescence, allowing updates to flow again. Returns whether we were the initiator
+	/// of the quiescence session.
+	pub fn exit_quiescence(&mut self) -> Result<bool, ChannelError> {
+		if !self.context.channel_state.is_quiescent() {
+			return Err(ChannelError::Ignore("Channel is not quiescent".to_owned()));
+		}
+		self.context.channel_state.clear_quiescent();
+		self.context.sent_message_awaiting_response = None;
+		Ok(self.context.is_holder_quiescence_initiator.take().unwrap_or(false))
+	}
+
 	pub fn shutdown(
 		&mut self, signer_provider: &SP, their_features: &InitFeatures, msg: &msgs::Shutdown
 	) -> Result<(Option<msgs::Shutdown>, Option<ChannelMonitorUpdate>, Vec<(HTLCSource, PaymentHash)>), ChannelError>
@@ -5275,6 +6184,11 @@ impl<SP: Deref> Channel<SP> where
 			return None;
 		}
 
+		// Similarly, a dual-funded channel's funding transaction must be fully signed first.
+		if self.context.is_initial_funding_unsigned() {
+			return None;
+		}
+
 		// Note that we don't include ChannelState::WaitingForBatch as we don't want to send
 		// channel_ready until the entire batch is ready.
 		let need_commitment_update = if matches!(self.context.channel_state, ChannelState::AwaitingChannelReady(f) if (f & !FundedStateFlags::ALL).is_empty()) {
@@ -5334,11 +6248,27 @@ impl<SP: Deref> Channel<SP> where
 		L::Target: Logger
 	{
 		let mut msgs = (None, None);
-		if let Some(funding_txo) = self.context.get_funding_txo() {
+		if let Some(mut funding_txo) = self.context.get_current_funding_txo() {
 			for &(index_in_block, tx) in txdata.iter() {
 				// Check if the transaction is the expected funding transaction, and if it is,
 				// check that it pays the right amount to the right script.
 				if self.context.funding_tx_confirmation_height == 0 {
+					// A dual-funded channel's funding transaction may have been fee-bumped, in which
+					// case either version may confirm.
+					if let Some(splice_funding) = self.context.pending_splice_funding.as_ref() {
+						let pending_txid = splice_funding.funding_transaction.txid();
+						if splice_funding.shared_input_index.is_none() && splice_funding.is_fully_signed() &&
+							tx.txid() == pending_txid && pending_txid != funding_txo.txid
+						{
+							self.promote_splice_funding(logger);
+							funding_txo = self.context.get_current_funding_txo().unwrap();
+						} else if tx.txid() == funding_txo.txid {
+							log_info!(logger, "Funding transaction {} for channel {} confirmed, dropping pending funding transaction {}",
+								funding_txo.txid, &self.context.channel_id, pending_txid);
+							self.context.pending_splice = None;
+							self.context.pending_splice_funding = None;
+						}
+					}
 					if tx.txid() == funding_txo.txid {
 						let txo_idx = funding_txo.index as usize;
 						if txo_idx >= tx.output.len() || tx.output[txo_idx].script_pubkey != self.context.get_funding_redeemscript().to_v0_p2wsh() ||
@@ -5370,6 +6300,7 @@ impl<SP: Deref> Channel<SP> where
 							}
 							self.context.funding_tx_confirmation_height = height;
 							self.context.funding_tx_confirmed_in = Some(*block_hash);
+							self.context.dual_funding_transaction = None;
 							self.context.short_channel_id = match scid_from_parts(height as u64, index_in_block as u64, txo_idx as u64) {
 								Ok(scid) => Some(scid),
 								Err(_) => panic!("Block was bogus - either height was > 16 million, had > 16 million transactions, or had > 65k outputs"),
@@ -5392,6 +6323,23 @@ impl<SP: Deref> Channel<SP> where
 						msgs = (Some(channel_ready), announcement_sigs);
 					}
 				}
+				// A splice's funding transaction spends the current funding output without closing
+				// the channel.
+				if let Some(splice_funding) = self.context.pending_splice_funding.as_mut() {
+					if splice_funding.is_fully_signed() && tx.txid() == splice_funding.funding_transaction.txid() {
+						if splice_funding.confirmation_height == 0 {
+							log_info!(logger, "Splice transaction {} for channel {} confirmed at height {}",
+								tx.txid(), &self.context.channel_id, height);
+							splice_funding.confirmation_height = height;
+							splice_funding.confirmed_in = Some(*block_hash);
+							splice_funding.short_channel_id = Some(
+								scid_from_parts(height as u64, index_in_block as u64, splice_funding.funding_output_index as u64)
+									.expect("Block was bogus - either height was > 16 million, had > 16 million transactions, or had > 65k outputs")
+							);
+						}
+						continue;
+					}
+				}
 				for inp in tx.input.iter() {
 					if inp.previous_output == funding_txo.into_bitcoin_outpoint() {
 						log_info!(logger, "Detected channel-closing tx {} spending {}:{}, closing channel {}", tx.txid(), inp.previous_output.txid, inp.previous_output.vout, &self.context.channel_id());
@@ -5571,7 +6519,7 @@ impl<SP: Deref> Channel<SP> where
 		Ok(msg)
 	}
 
-	fn get_announcement_sigs<NS: Deref, L: Deref>(
+	pub fn get_announcement_sigs<NS: Deref, L: Deref>(
 		&mut self, node_signer: &NS, chain_hash: ChainHash, user_config: &UserConfig,
 		best_block_height: u32, logger: &L
 	) -> Option<msgs::AnnouncementSignatures>
@@ -5766,16 +6714,52 @@ impl<SP: Deref> Channel<SP> where
 			next_remote_commitment_number: INITIAL_COMMITMENT_NUMBER - self.context.cur_counterparty_commitment_transaction_number - 1,
 			your_last_per_commitment_secret: remote_last_secret,
 			my_current_per_commitment_point: dummy_pubkey,
-			// TODO(dual_funding): If we've sent `commtiment_signed` for an interactive transaction
-			// construction but have not received `tx_signatures` we MUST set `next_funding_txid` to the
-			// txid of that interactive transaction, else we MUST NOT set it.
-			next_funding_txid: None,
+			// If we've sent `tx_signatures` for a splice's funding transaction but have not received
+			// theirs we need them to retransmit. Splices which we had yet to sign are aborted on
+			// disconnect. A dual-funded channel's initial funding transaction can never be aborted
+			// once both commitments have been exchanged, so we always ask for it until it is signed.
+			next_funding_txid: self.context.pending_splice_funding.as_ref()
+				.filter(|funding| funding.counterparty_tx_signatures.is_none() &&
+					(funding.holder_tx_signatures.is_some() || self.context.is_initial_funding_unsigned()))
+				.map(|funding| funding.funding_transaction.txid()),
 		}
 	}
 
 
 	// Send stuff to our remote peers:
 
+	/// Returns whether an outbound HTLC of `amount_msat` which we do not endorse fits within the
+	/// share of our counterparty's HTLC slots and in-flight value limit we allow unendorsed HTLCs
+	/// to occupy, see [`ChannelConfig::unendorsed_htlc_slot_share_percentage`] and
+	/// [`ChannelConfig::unendorsed_htlc_liquidity_share_percentage`].
+	pub fn can_add_unendorsed_htlc(&self, amount_msat: u64) -> bool {
+		let config = self.context.config();
+		let mut htlc_count = 1;
+		let mut htlc_value_msat = amount_msat;
+		for htlc in self.context.pending_outbound_htlcs.iter() {
+			if htlc.endorsed != Some(true) {
+				htlc_count += 1;
+				htlc_value_msat += htlc.amount_msat;
+			}
+		}
+		for update in self.context.holding_cell_htlc_updates.iter() {
+			if let &HTLCUpdateAwaitingACK::AddHTLC { amount_msat, endorsed, .. } = update {
+				if endorsed != Some(true) {
+					htlc_count += 1;
+					htlc_value_msat += amount_msat;
+				}
+			}
+		}
+
+		let max_htlc_count = self.context.counterparty_max_accepted_htlcs as u64
+			* cmp::min(config.unendorsed_htlc_slot_share_percentage, 100) as u64 / 100;
+		let max_in_flight_msat = (cmp::min(
+			self.context.counterparty_max_htlc_value_in_flight_msat,
+			self.context.channel_value_satoshis * 1000
+		) as u128 * cmp::min(config.unendorsed_htlc_liquidity_share_percentage, 100) as u128 / 100) as u64;
+		htlc_count <= max_htlc_count && htlc_value_msat <= max_in_flight_msat
+	}
+
 	/// Queues up an outbound HTLC to send by placing it in the holding cell. You should call
 	/// [`Self::maybe_free_holding_cell_htlcs`] in order to actually generate and send the
 	/// commitment update.
@@ -5784,13 +6768,14 @@ impl<SP: Deref> Channel<SP> where
 	pub fn queue_add_htlc<F: Deref, L: Deref>(
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32, source: HTLCSource,
 		onion_routing_packet: msgs::OnionPacket, skimmed_fee_msat: Option<u64>,
-		blinding_point: Option<PublicKey>, fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
+		blinding_point: Option<PublicKey>, endorsed: Option<bool>,
+		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
 	) -> Result<(), ChannelError>
 	where F::Target: FeeEstimator, L::Target: Logger
 	{
 		self
 			.send_htlc(amount_msat, payment_hash, cltv_expiry, source, onion_routing_packet, true,
-				skimmed_fee_msat, blinding_point, fee_estimator, logger)
+				skimmed_fee_msat, blinding_point, endorsed, fee_estimator, logger)
 			.map(|msg_opt| assert!(msg_opt.is_none(), "We forced holding cell?"))
 			.map_err(|err| {
 				if let ChannelError::Ignore(_) = err { /* fine */ }
@@ -5818,7 +6803,7 @@ impl<SP: Deref> Channel<SP> where
 	fn send_htlc<F: Deref, L: Deref>(
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32, source: HTLCSource,
 		onion_routing_packet: msgs::OnionPacket, mut force_holding_cell: bool,
-		skimmed_fee_msat: Option<u64>, blinding_point: Option<PublicKey>,
+		skimmed_fee_msat: Option<u64>, blinding_point: Option<PublicKey>, endorsed: Option<bool>,
 		fee_estimator: &LowerBoundedFeeEstimator<F>, logger: &L
 	) -> Result<Option<msgs::UpdateAddHTLC>, ChannelError>
 	where F::Target: FeeEstimator, L::Target: Logger
@@ -5829,6 +6814,9 @@ impl<SP: Deref> Channel<SP> where
 		{
 			return Err(ChannelError::Ignore("Cannot send HTLC until channel is fully established and we haven't started shutting down".to_owned()));
 		}
+		if self.context.is_splice_pending() {
+			return Err(ChannelError::Ignore("Cannot send HTLC while a splice is pending".to_owned()));
+		}
 		let channel_total_msat = self.context.channel_value_satoshis * 1000;
 		if amount_msat > channel_total_msat {
 			return Err(ChannelError::Ignore(format!("Cannot send amount {}, because it is more than the total value of the channel {}", amount_msat, channel_total_msat)));
@@ -5880,6 +6868,7 @@ impl<SP: Deref> Channel<SP> where
 				onion_routing_packet,
 				skimmed_fee_msat,
 				blinding_point,
+				endorsed,
 			});
 			return Ok(None);
 		}
@@ -5893,6 +6882,7 @@ impl<SP: Deref> Channel<SP> where
 			source,
 			blinding_point,
 			skimmed_fee_msat,
+			endorsed,
 		});
 
 		let res = msgs::UpdateAddHTLC {
@@ -5904,6 +6894,7 @@ impl<SP: Deref> Channel<SP> where
 			onion_routing_packet,
 			skimmed_fee_msat,
 			blinding_point,
+			endorsed,
 		};
 		self.context.next_holder_htlc_id += 1;
 
@@ -6063,6 +7054,8 @@ impl<SP: Deref> Channel<SP> where
 	///
 	/// Shorthand for calling [`Self::send_htlc`] followed by a commitment update, see docs on
 	/// [`Self::send_htlc`] and [`Self::build_commitment_no_state_update`] for more info.
+	///
+	/// As the HTLC originates with us, it is always sent endorsed.
 	pub fn send_htlc_and_commit<F: Deref, L: Deref>(
 		&mut self, amount_msat: u64, payment_hash: PaymentHash, cltv_expiry: u32,
 		source: HTLCSource, onion_routing_packet: msgs::OnionPacket, skimmed_fee_msat: Option<u64>,
@@ -6071,7 +7064,7 @@ impl<SP: Deref> Channel<SP> where
 	where F::Target: FeeEstimator, L::Target: Logger
 	{
 		let send_res = self.send_htlc(amount_msat, payment_hash, cltv_expiry, source,
-			onion_routing_packet, false, skimmed_fee_msat, None, fee_estimator, logger);
+			onion_routing_packet, false, skimmed_fee_msat, None, Some(true), fee_estimator, logger);
 		if let Err(e) = &send_res { if let ChannelError::Ignore(_) = e {} else { debug_assert!(false, "Sending cannot trigger channel failure"); } }
 		match send_res? {
 			Some(_) => {
@@ -6123,6 +7116,9 @@ impl<SP: Deref> Channel<SP> where
 		if self.context.channel_state.is_peer_disconnected() || self.context.channel_state.is_monitor_update_in_progress() {
 			return Err(APIError::ChannelUnavailable{err: "Cannot begin shutdown while peer is disconnected or we're waiting on a monitor update, maybe force-close instead?".to_owned()});
 		}
+		if self.context.is_splice_pending() {
+			return Err(APIError::ChannelUnavailable{err: "Cannot begin shutdown while a splice is pending".to_owned()});
+		}
 
 		let update_shutdown_script = match self.context.shutdown_scriptpubkey {
 			Some(_) => false,
@@ -6188,116 +7184,1073 @@ impl<SP: Deref> Channel<SP> where
 		Ok((shutdown, monitor_update, dropped_outbound_htlcs))
 	}
 
-	pub fn inflight_htlc_sources(&self) -> impl Iterator<Item=(&HTLCSource, &PaymentHash)> {
-		self.context.holding_cell_htlc_updates.iter()
-			.flat_map(|htlc_update| {
-				match htlc_update {
-					HTLCUpdateAwaitingACK::AddHTLC { source, payment_hash, .. }
-						=> Some((source, payment_hash)),
-					_ => None,
-				}
-			})
-			.chain(self.context.pending_outbound_htlcs.iter().map(|htlc| (&htlc.source, &htlc.payment_hash)))
-	}
-}
-
-/// A not-yet-funded outbound (from holder) channel using V1 channel establishment.
-pub(super) struct OutboundV1Channel<SP: Deref> where SP::Target: SignerProvider {
-	pub context: ChannelContext<SP>,
-	pub unfunded_context: UnfundedChannelContext,
-}
-
-impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
-	pub fn new<ES: Deref, F: Deref>(
-		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP, counterparty_node_id: PublicKey, their_features: &InitFeatures,
-		channel_value_satoshis: u64, push_msat: u64, user_id: u128, config: &UserConfig, current_chain_height: u32,
-		outbound_scid_alias: u64, temporary_channel_id: Option<ChannelId>
-	) -> Result<OutboundV1Channel<SP>, APIError>
-	where ES::Target: EntropySource,
-	      F::Target: FeeEstimator
-	{
-		let holder_selected_contest_delay = config.channel_handshake_config.our_to_self_delay;
-		let channel_keys_id = signer_provider.generate_channel_keys_id(false, channel_value_satoshis, user_id);
-		let holder_signer = signer_provider.derive_channel_signer(channel_value_satoshis, channel_keys_id);
-		let pubkeys = holder_signer.pubkeys().clone();
-
-		if !their_features.supports_wumbo() && channel_value_satoshis > MAX_FUNDING_SATOSHIS_NO_WUMBO {
-			return Err(APIError::APIMisuseError{err: format!("funding_value must not exceed {}, it was {}", MAX_FUNDING_SATOSHIS_NO_WUMBO, channel_value_satoshis)});
+	/// Checks that the channel is quiescent, i.e. has no pending HTLC or fee updates, and can thus
+	/// begin a splice.
+	fn check_can_splice(&self) -> Result<(), String> {
+		if !self.context.is_live() {
+			return Err("Cannot splice a channel which is not live or is shutting down".to_owned());
 		}
-		if channel_value_satoshis >= TOTAL_BITCOIN_SUPPLY_SATOSHIS {
-			return Err(APIError::APIMisuseError{err: format!("funding_value must be smaller than the total bitcoin supply, it was {}", channel_value_satoshis)});
+		if self.context.channel_state.is_monitor_update_in_progress() {
+			return Err("Cannot splice a channel while awaiting a monitor update".to_owned());
 		}
-		let channel_value_msat = channel_value_satoshis * 1000;
-		if push_msat > channel_value_msat {
-			return Err(APIError::APIMisuseError { err: format!("Push value ({}) was larger than channel_value ({})", push_msat, channel_value_msat) });
+		if self.context.is_splice_pending() {
+			return Err("A splice is already pending".to_owned());
 		}
-		if holder_selected_contest_delay < BREAKDOWN_TIMEOUT {
-			return Err(APIError::APIMisuseError {err: format!("Configured with an unreasonable our_to_self_delay ({}) putting user funds at risks", holder_selected_contest_delay)});
+		if !self.context.pending_inbound_htlcs.is_empty() || !self.context.pending_outbound_htlcs.is_empty() ||
+			!self.context.holding_cell_htlc_updates.is_empty() || self.context.pending_update_fee.is_some() ||
+			self.context.channel_state.is_awaiting_remote_revoke()
+		{
+			return Err("Cannot splice a channel with pending HTLC or fee updates".to_owned());
 		}
-		let holder_selected_channel_reserve_satoshis = get_holder_selected_channel_reserve_satoshis(channel_value_satoshis, config);
-		if holder_selected_channel_reserve_satoshis < MIN_CHAN_DUST_LIMIT_SATOSHIS {
-			// Protocol level safety check in place, although it should never happen because
-			// of `MIN_THEIR_CHAN_RESERVE_SATOSHIS`
-			return Err(APIError::APIMisuseError { err: format!("Holder selected channel  reserve below implemention limit dust_limit_satoshis {}", holder_selected_channel_reserve_satoshis) });
+		Ok(())
+	}
+
+	/// Calculates the channel value and our balance after a splice with the given contributions,
+	/// checking that both parties retain their reserve and that the funder can still pay for the
+	/// commitment transaction.
+	///
+	/// Note that `their_funding_contribution_satoshis` is provided by our counterparty, so all
+	/// arithmetic on it is checked.
+	fn get_splice_balances(
+		&self, our_funding_contribution_satoshis: i64, their_funding_contribution_satoshis: i64,
+	) -> Result<(u64, u64), ChannelError> {
+		let overflow = || ChannelError::Warn(format!(
+			"Splice contributions of {} sats (ours) and {} sats (theirs) overflow the channel value",
+			our_funding_contribution_satoshis, their_funding_contribution_satoshis));
+		let channel_value_satoshis = (self.context.channel_value_satoshis as i64)
+			.checked_add(our_funding_contribution_satoshis)
+			.and_then(|value| value.checked_add(their_funding_contribution_satoshis))
+			.ok_or_else(overflow)?;
+		if channel_value_satoshis <= 0 {
+			return Err(ChannelError::Warn(format!("Splice would result in a channel value of {} sats",
+				channel_value_satoshis)));
+		}
+		let value_to_self_msat = our_funding_contribution_satoshis.checked_mul(1000)
+			.and_then(|contribution_msat| (self.context.value_to_self_msat as i64).checked_add(contribution_msat))
+			.ok_or_else(overflow)?;
+		let value_to_remote_msat = channel_value_satoshis.checked_mul(1000)
+			.and_then(|channel_value_msat| channel_value_msat.checked_sub(value_to_self_msat))
+			.ok_or_else(overflow)?;
+		if value_to_self_msat < 0 || value_to_remote_msat < 0 {
+			return Err(ChannelError::Warn(format!("Splice would result in a negative balance (ours: {} msat, theirs: {} msat)",
+				value_to_self_msat, value_to_remote_msat)));
+		}
+		// Whoever splices funds out must retain the reserve their counterparty requires.
+		let holder_reserve_msat = self.context.counterparty_selected_channel_reserve_satoshis.unwrap_or(0) as i64 * 1000;
+		if our_funding_contribution_satoshis < 0 && value_to_self_msat < holder_reserve_msat {
+			return Err(ChannelError::Warn(format!("Splice would leave our balance of {} msat below our reserve of {} msat",
+				value_to_self_msat, holder_reserve_msat)));
+		}
+		let counterparty_reserve_msat = self.context.holder_selected_channel_reserve_satoshis as i64 * 1000;
+		if their_funding_contribution_satoshis < 0 && value_to_remote_msat < counterparty_reserve_msat {
+			return Err(ChannelError::Warn(format!("Splice would leave their balance of {} msat below their reserve of {} msat",
+				value_to_remote_msat, counterparty_reserve_msat)));
+		}
+		let anchors_val = anchor_outputs_value_sat(&self.context.channel_type);
+		let commit_tx_fee_msat = (commit_tx_fee_sat(self.context.feerate_per_kw, 0, &self.context.channel_type) + anchors_val) as i64 * 1000;
+		let funder_balance_msat = if self.context.is_outbound() { value_to_self_msat } else { value_to_remote_msat };
+		if funder_balance_msat < commit_tx_fee_msat {
+			return Err(ChannelError::Warn(format!("Splice would leave the channel funder unable to pay the commitment transaction fee of {} msat",
+				commit_tx_fee_msat)));
+		}
+		Ok((channel_value_satoshis as u64, value_to_self_msat as u64))
+	}
+
+	/// Begins splicing funds into or out of the channel, returning the `splice` message to send to
+	/// our counterparty.
+	///
+	/// For a splice-in, the fees for the new funding transaction are paid out of the given inputs
+	/// with any remainder sent to the change script. For a splice-out, they're paid out of our
+	/// channel balance in addition to the spliced-out outputs.
+	pub fn splice_channel<L: Deref>(
+		&mut self, contribution: SpliceContribution, funding_feerate_per_kw: u32, locktime: u32,
+		chain_hash: ChainHash, logger: &L,
+	) -> Result<msgs::Splice, APIError> where L::Target: Logger {
+		self.check_can_splice().map_err(|err| APIError::ChannelUnavailable { err })?;
+		if funding_feerate_per_kw < FEERATE_FLOOR_SATS_PER_KW {
+			return Err(APIError::APIMisuseError {
+				err: format!("Splice feerate of {} sat/kW is below the minimum of {} sat/kW", funding_feerate_per_kw, FEERATE_FLOOR_SATS_PER_KW),
+			});
 		}
 
-		let channel_type = Self::get_initial_channel_type(&config, their_features);
-		debug_assert!(channel_type.is_subset(&channelmanager::provided_channel_type_features(&config)));
+		let funding_script = self.context.get_funding_redeemscript().to_v0_p2wsh();
+		let (our_funding_contribution_satoshis, our_funding_inputs, our_funding_outputs) = match contribution {
+			SpliceContribution::SpliceIn { value_satoshis, inputs, change_script } => {
+				if value_satoshis == 0 || inputs.is_empty() {
+					return Err(APIError::APIMisuseError { err: "Splicing in requires a non-zero value and at least one input".to_owned() });
+				}
+				let (funding_inputs, funding_outputs) = get_funding_inputs_and_change(value_satoshis, inputs,
+					change_script, true, true, &funding_script, funding_feerate_per_kw)?;
+				(value_satoshis as i64, funding_inputs, funding_outputs)
+			},
+			SpliceContribution::SpliceOut { outputs } => {
+				if outputs.is_empty() {
+					return Err(APIError::APIMisuseError { err: "Splicing out requires at least one output".to_owned() });
+				}
+				for output in outputs.iter() {
+					if output.value < output.script_pubkey.dust_value().to_sat() {
+						return Err(APIError::APIMisuseError { err: "Splice outputs must not be dust".to_owned() });
+					}
+				}
+				let weight = interactivetxs::estimate_contribution_weight(true, true, &funding_script, core::iter::empty(), &outputs);
+				let fee_satoshis = fee_for_weight(funding_feerate_per_kw, weight);
+				let output_value_satoshis = outputs.iter().map(|output| output.value).sum::<u64>();
+				(-((output_value_satoshis + fee_satoshis) as i64), Vec::new(), outputs)
+			},
+		};
+		self.get_splice_balances(our_funding_contribution_satoshis, 0)
+			.map_err(|err| APIError::APIMisuseError { err: err.to_string() })?;
+
+		log_info!(logger, "Initiating splice of channel {} contributing {} sats", &self.context.channel_id, our_funding_contribution_satoshis);
+		self.context.pending_splice = Some(PendingSplice {
+			is_initiator: true,
+			our_funding_contribution_satoshis,
+			their_funding_contribution_satoshis: 0,
+			funding_feerate_per_kw,
+			locktime,
+			our_funding_inputs,
+			our_funding_outputs,
+			interactive_tx_constructor: None,
+		});
+		Ok(msgs::Splice {
+			channel_id: self.context.channel_id,
+			chain_hash,
+			relative_satoshis: our_funding_contribution_satoshis,
+			funding_feerate_perkw: funding_feerate_per_kw,
+			locktime,
+			funding_pubkey: self.context.get_holder_pubkeys().funding_pubkey,
+		})
+	}
 
-		let (commitment_conf_target, anchor_outputs_value_msat)  = if channel_type.supports_anchors_zero_fee_htlc_tx() {
-			(ConfirmationTarget::AnchorChannelFee, ANCHOR_OUTPUT_VALUE_SATOSHI * 2 * 1000)
-		} else {
-			(ConfirmationTarget::NonAnchorChannelFee, 0)
+	/// Handles a `splice` from our counterparty, accepting it without contributing any funds of
+	/// our own. Rejected splices are answered with a `tx_abort`.
+	pub fn splice<ES: Deref, L: Deref>(
+		&mut self, msg: &msgs::Splice, chain_hash: ChainHash, entropy_source: &ES,
+		holder_node_id: PublicKey, logger: &L,
+	) -> Result<msgs::SpliceAck, msgs::TxAbort> where ES::Target: EntropySource, L::Target: Logger {
+		let channel_id = self.context.channel_id;
+		let reject = |err: String| {
+			log_debug!(logger, "Rejecting splice of channel {}: {}", channel_id, err);
+			msgs::TxAbort { channel_id, data: err.into_bytes() }
+		};
+		if msg.chain_hash != chain_hash {
+			return Err(reject("Splice is for a different chain".to_owned()));
+		}
+		self.check_can_splice().map_err(reject)?;
+		if msg.funding_pubkey != *self.context.counterparty_funding_pubkey() {
+			return Err(reject("Changing the funding pubkey in a splice is not supported".to_owned()));
+		}
+		if msg.funding_feerate_perkw < FEERATE_FLOOR_SATS_PER_KW {
+			return Err(reject(format!("Splice feerate of {} sat/kW is too low", msg.funding_feerate_perkw)));
+		}
+		let (channel_value_satoshis, _) = self.get_splice_balances(0, msg.relative_satoshis)
+			.map_err(|err| reject(err.to_string()))?;
+
+		log_info!(logger, "Accepting splice of channel {} with counterparty contribution of {} sats",
+			&self.context.channel_id, msg.relative_satoshis);
+		let mut pending_splice = PendingSplice {
+			is_initiator: false,
+			our_funding_contribution_satoshis: 0,
+			their_funding_contribution_satoshis: msg.relative_satoshis,
+			funding_feerate_per_kw: msg.funding_feerate_perkw,
+			locktime: msg.locktime,
+			our_funding_inputs: Vec::new(),
+			our_funding_outputs: Vec::new(),
+			interactive_tx_constructor: None,
 		};
-		let commitment_feerate = fee_estimator.bounded_sat_per_1000_weight(commitment_conf_target);
+		let params = self.context.get_interactive_tx_parameters(holder_node_id, &pending_splice, channel_value_satoshis, true);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params, Vec::new(), Vec::new());
+		debug_assert!(first_msg.is_none());
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		self.context.pending_splice = Some(pending_splice);
+		Ok(msgs::SpliceAck {
+			channel_id: self.context.channel_id,
+			chain_hash,
+			relative_satoshis: 0,
+			funding_pubkey: self.context.get_holder_pubkeys().funding_pubkey,
+		})
+	}
 
-		let value_to_self_msat = channel_value_satoshis * 1000 - push_msat;
-		let commitment_tx_fee = commit_tx_fee_msat(commitment_feerate, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
-		if value_to_self_msat.saturating_sub(anchor_outputs_value_msat) < commitment_tx_fee {
-			return Err(APIError::APIMisuseError{ err: format!("Funding amount ({}) can't even pay fee for initial commitment transaction fee of {}.", value_to_self_msat / 1000, commitment_tx_fee / 1000) });
+	/// Handles a `splice_ack` for a splice we initiated, returning the first message of the
+	/// interactive transaction negotiation.
+	pub fn splice_ack<ES: Deref, L: Deref>(
+		&mut self, msg: &msgs::SpliceAck, entropy_source: &ES, holder_node_id: PublicKey, logger: &L,
+	) -> Result<InteractiveTxMessageSend, msgs::TxAbort> where ES::Target: EntropySource, L::Target: Logger {
+		let channel_id = self.context.channel_id;
+		let mut pending_splice = match self.context.pending_splice.take() {
+			Some(pending_splice) if pending_splice.is_initiator && pending_splice.interactive_tx_constructor.is_none() &&
+				matches!(self.context.channel_state, ChannelState::ChannelReady(_)) => pending_splice,
+			pending_splice => {
+				self.context.pending_splice = pending_splice;
+				return Err(interactivetxs::AbortReason::UnexpectedCounterpartyMessage.into_tx_abort_msg(channel_id));
+			},
+		};
+		let abort = |err: String| {
+			log_debug!(logger, "Aborting splice of channel {}: {}", channel_id, err);
+			msgs::TxAbort { channel_id, data: err.into_bytes() }
+		};
+		if msg.funding_pubkey != *self.context.counterparty_funding_pubkey() {
+			return Err(abort("Changing the funding pubkey in a splice is not supported".to_owned()));
 		}
+		pending_splice.their_funding_contribution_satoshis = msg.relative_satoshis;
+		let (channel_value_satoshis, _) = self.get_splice_balances(
+			pending_splice.our_funding_contribution_satoshis, pending_splice.their_funding_contribution_satoshis
+		).map_err(|err| abort(err.to_string()))?;
 
-		let mut secp_ctx = Secp256k1::new();
-		secp_ctx.seeded_randomize(&entropy_source.get_secure_random_bytes());
+		let params = self.context.get_interactive_tx_parameters(holder_node_id, &pending_splice, channel_value_satoshis, true);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params,
+			mem::take(&mut pending_splice.our_funding_inputs), mem::take(&mut pending_splice.our_funding_outputs));
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		self.context.pending_splice = Some(pending_splice);
+		Ok(first_msg.expect("The initiator always sends the first message"))
+	}
 
-		let shutdown_scriptpubkey = if config.channel_handshake_config.commit_upfront_shutdown_pubkey {
-			match signer_provider.get_shutdown_scriptpubkey() {
-				Ok(scriptpubkey) => Some(scriptpubkey),
-				Err(_) => return Err(APIError::ChannelUnavailable { err: "Failed to get shutdown scriptpubkey".to_owned()}),
-			}
-		} else { None };
+	fn check_can_bump_funding_fee(&self, funding_feerate_per_kw: u32) -> Result<(), String> {
+		if !self.context.is_dual_funded() {
+			return Err("Only the funding transaction of a dual-funded channel can be fee-bumped".to_owned());
+		}
+		if !matches!(self.context.channel_state, ChannelState::AwaitingChannelReady(_)) ||
+			self.context.funding_tx_confirmation_height != 0
+		{
+			return Err("Cannot fee-bump a funding transaction which has confirmed".to_owned());
+		}
+		if self.context.channel_state.is_peer_disconnected() || self.context.channel_state.is_monitor_update_in_progress() {
+			return Err("Cannot fee-bump the funding transaction while disconnected or awaiting a monitor update".to_owned());
+		}
+		if self.context.is_splice_pending() || self.context.dual_funding_transaction.is_none() {
+			return Err("Cannot fee-bump the funding transaction while another funding transaction is pending".to_owned());
+		}
+		// As with any replacement, the new feerate has to exceed the previous one by at least 25/24.
+		let min_feerate_per_kw = self.context.dual_funding_feerate_sat_per_1000_weight.unwrap_or(0) as u64 * 25 / 24;
+		if (funding_feerate_per_kw as u64) < min_feerate_per_kw {
+			return Err(format!("Funding feerate of {} sat/kW must be at least {} sat/kW", funding_feerate_per_kw, min_feerate_per_kw));
+		}
+		Ok(())
+	}
 
-		if let Some(shutdown_scriptpubkey) = &shutdown_scriptpubkey {
-			if !shutdown_scriptpubkey.is_compatible(&their_features) {
-				return Err(APIError::IncompatibleShutdownScript { script: shutdown_scriptpubkey.clone() });
-			}
+	/// Begins replacing the unconfirmed funding transaction of a dual-funded channel we opened
+	/// with one paying a higher feerate, returning the `tx_init_rbf` message to send to our
+	/// counterparty.
+	///
+	/// Our contribution replaces our contribution to the previous funding transaction. At least one
+	/// of its inputs must be an input of the previous funding transaction so that only one of them
+	/// can confirm.
+	pub fn bump_funding_fee<L: Deref>(
+		&mut self, contribution: FundingContribution, funding_feerate_per_kw: u32, locktime: u32, logger: &L,
+	) -> Result<msgs::TxInitRbf, APIError> where L::Target: Logger {
+		if !self.context.is_outbound() {
+			return Err(APIError::APIMisuseError { err: "Only the opener of a channel may fee-bump its funding transaction".to_owned() });
+		}
+		self.check_can_bump_funding_fee(funding_feerate_per_kw).map_err(|err| APIError::ChannelUnavailable { err })?;
+		let previous_inputs = &self.context.dual_funding_transaction.as_ref().unwrap().input;
+		if !contribution.inputs.iter().any(|(txin, _)| previous_inputs.iter().any(|input| input.previous_output == txin.previous_output)) {
+			return Err(APIError::APIMisuseError {
+				err: "A fee-bump must spend at least one input of the previous funding transaction".to_owned(),
+			});
+		}
+		let anchors_val = anchor_outputs_value_sat(&self.context.channel_type);
+		let commit_tx_fee_satoshis = commit_tx_fee_sat(self.context.feerate_per_kw, 0, &self.context.channel_type) + anchors_val;
+		if contribution.value_satoshis < commit_tx_fee_satoshis {
+			return Err(APIError::APIMisuseError {
+				err: format!("Funding contribution must cover the commitment transaction fee of {} sats", commit_tx_fee_satoshis),
+			});
 		}
 
-		let destination_script = match signer_provider.get_destination_script(channel_keys_id) {
-			Ok(script) => script,
-			Err(_) => return Err(APIError::ChannelUnavailable { err: "Failed to get destination script".to_owned()}),
-		};
+		let funding_script = self.context.get_funding_redeemscript().to_v0_p2wsh();
+		let (our_funding_inputs, our_funding_outputs) = get_funding_inputs_and_change(contribution.value_satoshis,
+			contribution.inputs, contribution.change_script, true, false, &funding_script, funding_feerate_per_kw)?;
+		log_info!(logger, "Initiating fee-bump of the funding transaction of channel {} at {} sat/kW",
+			&self.context.channel_id, funding_feerate_per_kw);
+		self.context.pending_splice = Some(PendingSplice {
+			is_initiator: true,
+			our_funding_contribution_satoshis: contribution.value_satoshis as i64,
+			their_funding_contribution_satoshis: 0,
+			funding_feerate_per_kw,
+			locktime,
+			our_funding_inputs,
+			our_funding_outputs,
+			interactive_tx_constructor: None,
+		});
+		Ok(msgs::TxInitRbf {
+			channel_id: self.context.channel_id,
+			locktime,
+			feerate_sat_per_1000_weight: funding_feerate_per_kw,
+			funding_output_contribution: Some(contribution.value_satoshis as i64),
+		})
+	}
 
-		let temporary_channel_id = temporary_channel_id.unwrap_or_else(|| ChannelId::temporary_from_entropy_source(entropy_source));
+	/// Handles a `tx_init_rbf` from our counterparty, accepting the fee-bump of the funding
+	/// transaction without contributing any funds of our own. As we have no way to contribute our
+	/// previous inputs again, fee-bumps of funding transactions we contributed to are rejected with
+	/// a `tx_abort`.
+	pub fn tx_init_rbf<ES: Deref, L: Deref>(
+		&mut self, msg: &msgs::TxInitRbf, entropy_source: &ES, holder_node_id: PublicKey, logger: &L,
+	) -> Result<msgs::TxAckRbf, msgs::TxAbort> where ES::Target: EntropySource, L::Target: Logger {
+		let channel_id = self.context.channel_id;
+		let reject = |err: String| {
+			log_debug!(logger, "Rejecting fee-bump of the funding transaction of channel {}: {}", channel_id, err);
+			msgs::TxAbort { channel_id, data: err.into_bytes() }
+		};
+		if self.context.is_outbound() {
+			return Err(reject("Only the opener of a channel may fee-bump its funding transaction".to_owned()));
+		}
+		self.check_can_bump_funding_fee(msg.feerate_sat_per_1000_weight).map_err(reject)?;
+		if self.context.value_to_self_msat != 0 {
+			return Err(reject("Fee-bumping a funding transaction we contributed to is not supported".to_owned()));
+		}
+		let their_funding_contribution_satoshis = match msg.funding_output_contribution {
+			Some(contribution) if contribution > 0 => contribution,
+			_ => return Err(reject("Fee-bump does not contribute to the funding output".to_owned())),
+		};
 
-		Ok(Self {
-			context: ChannelContext {
-				user_id,
+		log_info!(logger, "Accepting fee-bump of the funding transaction of channel {} at {} sat/kW",
+			&self.context.channel_id, msg.feerate_sat_per_1000_weight);
+		let mut pending_splice = PendingSplice {
+			is_initiator: false,
+			our_funding_contribution_satoshis: 0,
+			their_funding_contribution_satoshis,
+			funding_feerate_per_kw: msg.feerate_sat_per_1000_weight,
+			locktime: msg.locktime,
+			our_funding_inputs: Vec::new(),
+			our_funding_outputs: Vec::new(),
+			interactive_tx_constructor: None,
+		};
+		let params = self.context.get_interactive_tx_parameters(holder_node_id, &pending_splice,
+			their_funding_contribution_satoshis as u64, false);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params, Vec::new(), Vec::new());
+		debug_assert!(first_msg.is_none());
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		self.context.pending_splice = Some(pending_splice);
+		Ok(msgs::TxAckRbf {
+			channel_id: self.context.channel_id,
+			funding_output_contribution: Some(0),
+		})
+	}
 
-				config: LegacyChannelConfig {
-					options: config.channel_config.clone(),
-					announced_channel: config.channel_handshake_config.announced_channel,
-					commit_upfront_shutdown_pubkey: config.channel_handshake_config.commit_upfront_shutdown_pubkey,
-				},
+	/// Handles a `tx_ack_rbf` for a fee-bump of the funding transaction we initiated, returning the
+	/// first message of the interactive transaction negotiation.
+	pub fn tx_ack_rbf<ES: Deref, L: Deref>(
+		&mut self, msg: &msgs::TxAckRbf, entropy_source: &ES, holder_node_id: PublicKey, logger: &L,
+	) -> Result<InteractiveTxMessageSend, msgs::TxAbort> where ES::Target: EntropySource, L::Target: Logger {
+		let channel_id = self.context.channel_id;
+		let mut pending_splice = match self.context.pending_splice.take() {
+			Some(pending_splice) if pending_splice.is_initiator && pending_splice.interactive_tx_constructor.is_none() &&
+				matches!(self.context.channel_state, ChannelState::AwaitingChannelReady(_)) => pending_splice,
+			pending_splice => {
+				self.context.pending_splice = pending_splice;
+				return Err(interactivetxs::AbortReason::UnexpectedCounterpartyMessage.into_tx_abort_msg(channel_id));
+			},
+		};
+		let their_funding_contribution_satoshis = msg.funding_output_contribution.unwrap_or(0);
+		if their_funding_contribution_satoshis < 0 {
+			log_debug!(logger, "Aborting fee-bump of the funding transaction of channel {}: negative contribution", channel_id);
+			return Err(msgs::TxAbort { channel_id, data: b"Negative funding contribution".to_vec() });
+		}
+		pending_splice.their_funding_contribution_satoshis = their_funding_contribution_satoshis;
+		let channel_value_satoshis = match pending_splice.our_funding_contribution_satoshis
+			.checked_add(their_funding_contribution_satoshis)
+		{
+			Some(value) => value as u64,
+			None => {
+				log_debug!(logger, "Aborting fee-bump of the funding transaction of channel {}: funding contribution overflow", channel_id);
+				return Err(msgs::TxAbort { channel_id, data: b"Funding contribution overflow".to_vec() });
+			},
+		};
+		let params = self.context.get_interactive_tx_parameters(holder_node_id, &pending_splice, channel_value_satoshis, false);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params,
+			mem::take(&mut pending_splice.our_funding_inputs), mem::take(&mut pending_splice.our_funding_outputs));
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		self.context.pending_splice = Some(pending_splice);
+		Ok(first_msg.expect("The initiator always sends the first message"))
+	}
+
+	/// Handles a `tx_complete` from our counterparty, returning our response, if any, and once the
+	/// negotiation completes, our `commitment_signed` for the new funding transaction.
+	pub fn tx_complete<L: Deref>(
+		&mut self, msg: &msgs::TxComplete, logger: &L,
+	) -> Result<(Option<InteractiveTxMessageSend>, Option<FundingTxNegotiated>), msgs::TxAbort> where L::Target: Logger {
+		let (msg_send, constructed_tx) = self.context.with_interactive_tx_constructor(|constructor| constructor.handle_tx_complete(msg))?;
+		let constructed_tx = match constructed_tx {
+			Some(constructed_tx) => constructed_tx,
+			None => return Ok((msg_send, None)),
+		};
 
-				prev_config: None,
+		let pending_splice = self.context.pending_splice.take().expect("Negotiation completed without a pending splice");
+		let funding_transaction = constructed_tx.build_unsigned_tx();
+		let funding_output_index = constructed_tx.shared_output_index();
+		let holder_input_count = constructed_tx.holder_owned_input_count() as u16;
+		let shared_input_index = constructed_tx.shared_input_index().map(|idx| idx as u16);
+		if shared_input_index.is_none() {
+			// Both the previous funding transaction and its fee-bump could otherwise confirm.
+			let previous_inputs = self.context.dual_funding_transaction.as_ref().map(|tx| &tx.input[..]).unwrap_or(&[]);
+			if !funding_transaction.input.iter().any(|input| previous_inputs.iter().any(|prev| prev.previous_output == input.previous_output)) {
+				log_info!(logger, "Aborting fee-bump of the funding transaction of channel {} which does not double-spend it",
+					&self.context.channel_id);
+				return Err(msgs::TxAbort { channel_id: self.context.channel_id, data: b"Fee-bump does not double-spend the funding transaction".to_vec() });
+			}
+			self.context.dual_funding_feerate_sat_per_1000_weight = Some(pending_splice.funding_feerate_per_kw);
+		}
+		// A fee-bump of a dual-funded channel's funding transaction spends no shared input and our
+		// contribution makes up our entire balance.
+		let value_to_self_msat = if shared_input_index.is_some() {
+			(self.context.value_to_self_msat as i64 + pending_splice.our_funding_contribution_satoshis * 1000) as u64
+		} else {
+			pending_splice.our_funding_contribution_satoshis as u64 * 1000
+		};
+		let splice_funding = SpliceFunding {
+			channel_value_satoshis: funding_transaction.output[funding_output_index as usize].value,
+			value_to_self_msat,
+			funding_transaction,
+			// Negotiations are limited to `MAX_INPUTS_OUTPUTS_COUNT` inputs and outputs.
+			funding_output_index: funding_output_index as u16,
+			shared_input_index,
+			holder_input_count,
+			holder_inputs_signed: holder_input_count == 0,
+			holder_sends_tx_signatures_first: constructed_tx.holder_sends_tx_signatures_first(),
+			received_commitment_signed: false,
+			holder_tx_signatures: None,
+			counterparty_tx_signatures: None,
+			confirmation_height: 0,
+			confirmed_in: None,
+			short_channel_id: None,
+			sent_splice_locked: false,
+			received_splice_locked: false,
+		};
+		let commitment_signed = self.get_splice_commitment_signed(&splice_funding, logger).map_err(|_| {
+			log_error!(logger, "Failed to sign the commitment transaction for the new funding transaction of channel {}", &self.context.channel_id);
+			msgs::TxAbort { channel_id: self.context.channel_id, data: b"Failed to sign commitment transaction".to_vec() }
+		})?;
+		log_info!(logger, "Negotiated {} transaction {} for channel {}", if shared_input_index.is_some() { "splice" } else { "funding fee-bump" },
+			splice_funding.funding_transaction.txid(), &self.context.channel_id);
+		let unsigned_transaction = if splice_funding.holder_inputs_signed { None } else {
+			Some(splice_funding.funding_transaction.clone())
+		};
+		self.context.pending_splice_funding = Some(splice_funding);
+		Ok((msg_send, Some(FundingTxNegotiated { commitment_signed, unsigned_transaction })))
+	}
+
+	/// Builds a commitment transaction spending a splice's funding output. As splices may only
+	/// happen while no HTLCs are pending, it replaces the current commitment transaction of the
+	/// broadcaster and has no HTLC outputs.
+	fn build_splice_commitment_transaction<L: Deref>(
+		&self, splice_funding: &SpliceFunding, local: bool, logger: &L,
+	) -> CommitmentTransaction where L::Target: Logger {
+		let context = &self.context;
+		let (commitment_number, keys) = if local {
+			let commitment_number = context.cur_holder_commitment_transaction_number + 1;
+			(commitment_number, context.build_holder_transaction_keys(commitment_number))
+		} else {
+			let holder_pubkeys = context.get_holder_pubkeys();
+			let counterparty_pubkeys = context.get_counterparty_pubkeys();
+			(context.cur_counterparty_commitment_transaction_number + 1, TxCreationKeys::derive_new(
+				// Until they've sent `channel_ready`, their latest commitment uses their first point.
+				&context.secp_ctx, &context.counterparty_prev_commitment_point.or(context.counterparty_cur_commitment_point).unwrap(),
+				&counterparty_pubkeys.delayed_payment_basepoint, &counterparty_pubkeys.htlc_basepoint,
+				&holder_pubkeys.revocation_basepoint, &holder_pubkeys.htlc_basepoint))
+		};
 
-				inbound_handshake_limits_override: Some(config.channel_handshake_limits.clone()),
+		let total_fee_sat = commit_tx_fee_sat(context.feerate_per_kw, 0, &context.channel_type);
+		let anchors_val = anchor_outputs_value_sat(&context.channel_type) as i64;
+		let value_to_self_msat = splice_funding.value_to_self_msat as i64;
+		let value_to_remote_msat = (splice_funding.channel_value_satoshis * 1000) as i64 - value_to_self_msat;
+		let (value_to_self, value_to_remote) = if context.is_outbound() {
+			(value_to_self_msat / 1000 - anchors_val - total_fee_sat as i64, value_to_remote_msat / 1000)
+		} else {
+			(value_to_self_msat / 1000, value_to_remote_msat / 1000 - anchors_val - total_fee_sat as i64)
+		};
 
-				channel_id: temporary_channel_id,
-				temporary_channel_id: Some(temporary_channel_id),
-				channel_state: ChannelState::NegotiatingFunding(NegotiatingFundingFlags::OUR_INIT_SENT),
-				announcement_sigs_state: AnnouncementSigsState::NotSent,
+		let broadcaster_dust_limit_satoshis = if local { context.holder_dust_limit_satoshis } else { context.counterparty_dust_limit_satoshis } as i64;
+		let mut value_to_a = if local { value_to_self } else { value_to_remote };
+		let mut value_to_b = if local { value_to_remote } else { value_to_self };
+		if value_to_a < broadcaster_dust_limit_satoshis { value_to_a = 0; }
+		if value_to_b < broadcaster_dust_limit_satoshis { value_to_b = 0; }
+		let (funding_pubkey_a, funding_pubkey_b) = if local {
+			(context.get_holder_pubkeys().funding_pubkey, context.get_counterparty_pubkeys().funding_pubkey)
+		} else {
+			(context.get_counterparty_pubkeys().funding_pubkey, context.get_holder_pubkeys().funding_pubkey)
+		};
+		log_trace!(logger, "Building splice commitment transaction {} for {} with values {} and {}",
+			commitment_number, if local { "us" } else { "our counterparty" }, value_to_a, value_to_b);
+
+		let mut channel_transaction_parameters = context.channel_transaction_parameters.clone();
+		channel_transaction_parameters.funding_outpoint = Some(splice_funding.funding_outpoint());
+		let channel_parameters = if local { channel_transaction_parameters.as_holder_broadcastable() }
+			else { channel_transaction_parameters.as_counterparty_broadcastable() };
+		CommitmentTransaction::new_with_channel_value_and_auxiliary_htlc_data(commitment_number, splice_funding.channel_value_satoshis,
+			value_to_a as u64, value_to_b as u64, funding_pubkey_a, funding_pubkey_b, keys, context.feerate_per_kw,
+			&mut Vec::<(HTLCOutputInCommitment, ())>::new(), &channel_parameters)
+	}
+
+	fn get_splice_commitment_signed<L: Deref>(
+		&self, splice_funding: &SpliceFunding, logger: &L,
+	) -> Result<msgs::CommitmentSigned, ()> where L::Target: Logger {
+		let counterparty_commitment_tx = self.build_splice_commitment_transaction(splice_funding, false, logger);
+		let signature = match &self.context.holder_signer {
+			ChannelSignerType::Ecdsa(ecdsa) => ecdsa.sign_splice_counterparty_commitment(
+				&counterparty_commitment_tx, splice_funding.channel_value_satoshis, &self.context.secp_ctx)?,
+			// TODO (taproot|arik)
+			#[cfg(taproot)]
+			_ => todo!()
+		};
+		Ok(msgs::CommitmentSigned {
+			channel_id: self.context.channel_id,
+			signature,
+			htlc_signatures: Vec::new(),
+			#[cfg(taproot)]
+			partial_signature_with_nonce: None,
+		})
+	}
+
+	/// Handles our counterparty's `commitment_signed` for a splice's funding transaction,
+	/// returning the [`ChannelMonitorUpdate`] which starts tracking the new funding output.
+	fn splice_commitment_signed<L: Deref>(
+		&mut self, msg: &msgs::CommitmentSigned, logger: &L,
+	) -> Result<Option<ChannelMonitorUpdate>, ChannelError> where L::Target: Logger {
+		let splice_funding = self.context.pending_splice_funding.as_ref().unwrap();
+		if !msg.htlc_signatures.is_empty() {
+			return Err(ChannelError::Close("Got HTLC signatures for a splice commitment transaction".to_owned()));
+		}
+		let commitment_tx = self.build_splice_commitment_transaction(splice_funding, true, logger);
+		let funding_script = self.context.get_funding_redeemscript();
+		let sighash = commitment_tx.trust().built_transaction().get_sighash_all(&funding_script, splice_funding.channel_value_satoshis);
+		if let Err(_) = self.context.secp_ctx.verify_ecdsa(&sighash, &msg.signature, &self.context.counterparty_funding_pubkey()) {
+			return Err(ChannelError::Close("Invalid splice commitment tx signature from peer".to_owned()));
+		}
+		if splice_funding.received_commitment_signed {
+			// Retransmitted on reconnection - we've already handled it.
+			return Ok(None);
+		}
+
+		let holder_commitment_tx = HolderCommitmentTransaction::new(
+			commitment_tx,
+			msg.signature,
+			Vec::new(),
+			&self.context.get_holder_pubkeys().funding_pubkey,
+			self.context.counterparty_funding_pubkey()
+		);
+		self.context.holder_signer.as_ref().validate_holder_commitment(&holder_commitment_tx, Vec::new())
+			.map_err(|_| ChannelError::Close("Failed to validate our splice commitment".to_owned()))?;
+		let counterparty_commitment_txid = self.build_splice_commitment_transaction(splice_funding, false, logger).trust().txid();
+		let funding_outpoint = splice_funding.funding_outpoint();
+		let channel_value_satoshis = splice_funding.channel_value_satoshis;
+
+		log_debug!(logger, "Received splice commitment_signed for funding outpoint {} in channel {}",
+			funding_outpoint, &self.context.channel_id);
+		self.context.pending_splice_funding.as_mut().unwrap().received_commitment_signed = true;
+		self.context.latest_monitor_update_id += 1;
+		let monitor_update = ChannelMonitorUpdate {
+			update_id: self.context.latest_monitor_update_id,
+			counterparty_node_id: Some(self.context.counterparty_node_id),
+			updates: vec![ChannelMonitorUpdateStep::RenegotiatedFunding {
+				funding_outpoint,
+				channel_value_satoshis,
+				holder_commitment_tx,
+				counterparty_commitment_txid,
+			}],
+		};
+		self.monitor_updating_paused(false, false, false, Vec::new(), Vec::new(), Vec::new());
+		self.context.monitor_pending_tx_signatures = true;
+		Ok(self.push_ret_blockable_mon_update(monitor_update))
+	}
+
+	/// Returns our `tx_signatures` for a splice's funding transaction if we're ready to send them,
+	/// along with the fully signed transaction to broadcast if we already have our counterparty's.
+	fn maybe_get_splice_tx_signatures<L: Deref>(&mut self, logger: &L) -> (Option<msgs::TxSignatures>, Option<Transaction>)
+	where L::Target: Logger {
+		let splice_funding = match self.context.pending_splice_funding.as_mut() {
+			Some(splice_funding) => splice_funding,
+			None => return (None, None),
+		};
+		if splice_funding.holder_tx_signatures.is_some() || !splice_funding.received_commitment_signed ||
+			!splice_funding.holder_inputs_signed || self.context.monitor_pending_tx_signatures ||
+			self.context.channel_state.is_peer_disconnected() || self.context.channel_state.is_monitor_update_in_progress()
+		{
+			return (None, None);
+		}
+		if !splice_funding.holder_sends_tx_signatures_first && splice_funding.counterparty_tx_signatures.is_none() {
+			return (None, None);
+		}
+
+		let shared_input_signature = match splice_funding.shared_input_index {
+			Some(shared_input_index) => {
+				let shared_input_signature = match &self.context.holder_signer {
+					ChannelSignerType::Ecdsa(ecdsa) => ecdsa.sign_splice_shared_input(
+						&splice_funding.funding_transaction, shared_input_index as usize, &self.context.secp_ctx),
+					// TODO (taproot|arik)
+					#[cfg(taproot)]
+					_ => todo!()
+				};
+				match shared_input_signature {
+					Ok(signature) => Some(signature),
+					Err(_) => {
+						log_error!(logger, "Failed to sign the funding input of the splice transaction for channel {}", &self.context.channel_id);
+						return (None, None);
+					},
+				}
+			},
+			None => None,
+		};
+		// Only the inputs we contributed have been signed so far.
+		let shared_input_index = splice_funding.shared_input_index.map(|idx| idx as usize);
+		let witnesses = splice_funding.funding_transaction.input.iter().enumerate()
+			.filter(|(idx, input)| Some(*idx) != shared_input_index && !input.witness.is_empty())
+			.map(|(_, input)| input.witness.clone())
+			.collect();
+		let tx_signatures = msgs::TxSignatures {
+			channel_id: self.context.channel_id,
+			tx_hash: splice_funding.funding_transaction.txid(),
+			witnesses,
+			shared_input_signature,
+		};
+		splice_funding.holder_tx_signatures = Some(tx_signatures.clone());
+		let funding_transaction = if splice_funding.counterparty_tx_signatures.is_some() {
+			Some(self.finalize_splice_funding_transaction())
+		} else { None };
+		(Some(tx_signatures), funding_transaction)
+	}
+
+	/// Adds both parties' signatures to a splice's funding transaction once `tx_signatures` have
+	/// been exchanged, returning the transaction to broadcast.
+	///
+	/// The initial funding transaction of a dual-funded channel needs no further tracking once it
+	/// is fully signed, so it is dropped here.
+	fn finalize_splice_funding_transaction(&mut self) -> Transaction {
+		let holder_funding_key = self.context.get_holder_pubkeys().funding_pubkey.serialize();
+		let counterparty_funding_key = self.context.counterparty_funding_pubkey().serialize();
+		let funding_redeemscript = self.context.get_funding_redeemscript();
+		let is_initial_funding = self.context.is_initial_funding_unsigned();
+		let splice_funding = self.context.pending_splice_funding.as_mut().unwrap();
+		let holder_tx_signatures = splice_funding.holder_tx_signatures.as_ref().unwrap();
+		let counterparty_tx_signatures = splice_funding.counterparty_tx_signatures.as_ref().unwrap();
+
+		let shared_input_sigs = holder_tx_signatures.shared_input_signature.zip(counterparty_tx_signatures.shared_input_signature);
+		let mut counterparty_witnesses = counterparty_tx_signatures.witnesses.iter();
+		let shared_input_index = splice_funding.shared_input_index.map(|idx| idx as usize);
+		for (idx, input) in splice_funding.funding_transaction.input.iter_mut().enumerate() {
+			if Some(idx) == shared_input_index {
+				let (holder_sig, counterparty_sig) = shared_input_sigs.expect("Signatures for the shared input were checked");
+				let mut holder_sig = holder_sig.serialize_der().to_vec();
+				holder_sig.push(EcdsaSighashType::All as u8);
+				let mut counterparty_sig = counterparty_sig.serialize_der().to_vec();
+				counterparty_sig.push(EcdsaSighashType::All as u8);
+				let mut witness = Witness::new();
+				witness.push(Vec::new()); // First is the multisig dummy
+				if holder_funding_key[..] < counterparty_funding_key[..] {
+					witness.push(&holder_sig);
+					witness.push(&counterparty_sig);
+				} else {
+					witness.push(&counterparty_sig);
+					witness.push(&holder_sig);
+				}
+				witness.push(funding_redeemscript.as_bytes());
+				input.witness = witness;
+			} else if input.witness.is_empty() {
+				input.witness = counterparty_witnesses.next()
+					.expect("The number of counterparty witnesses was checked").clone();
+			}
+		}
+		let funding_transaction = splice_funding.funding_transaction.clone();
+		if is_initial_funding {
+			self.context.pending_splice_funding = None;
+			self.context.dual_funding_transaction = Some(funding_transaction.clone());
+		}
+		funding_transaction
+	}
+
+	/// Provides the splice's funding transaction with our inputs signed, in response to
+	/// [`Event::FundingTransactionReadyForSigning`].
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: crate::events::Event::FundingTransactionReadyForSigning
+	pub fn funding_transaction_signed<L: Deref>(
+		&mut self, transaction: Transaction, logger: &L,
+	) -> Result<(Option<msgs::TxSignatures>, Option<Transaction>), APIError> where L::Target: Logger {
+		self.context.funding_transaction_signed(transaction)?;
+		Ok(self.maybe_get_splice_tx_signatures(logger))
+	}
+
+	/// Handles our counterparty's `tx_signatures` for a splice's funding transaction, returning our
+	/// own if we're now ready to send them and the fully signed transaction to broadcast, if any.
+	pub fn tx_signatures<L: Deref>(
+		&mut self, msg: &msgs::TxSignatures, logger: &L,
+	) -> Result<(Option<msgs::TxSignatures>, Option<Transaction>), ChannelError> where L::Target: Logger {
+		if self.context.channel_state.is_peer_disconnected() {
+			return Err(ChannelError::Close("Peer sent tx_signatures when we needed a channel_reestablish".to_owned()));
+		}
+		let funding_redeemscript = self.context.get_funding_redeemscript();
+		let counterparty_funding_pubkey = *self.context.counterparty_funding_pubkey();
+		let splice_funding = match self.context.pending_splice_funding.as_mut() {
+			Some(splice_funding) if splice_funding.funding_transaction.txid() == msg.tx_hash => splice_funding,
+			_ => return Err(ChannelError::Warn("Got tx_signatures for an unknown transaction".to_owned())),
+		};
+		if let Some(counterparty_tx_signatures) = &splice_funding.counterparty_tx_signatures {
+			if counterparty_tx_signatures == msg {
+				return Ok((None, None));
+			}
+			return Err(ChannelError::Close("Got conflicting tx_signatures".to_owned()));
+		}
+		if !splice_funding.received_commitment_signed {
+			return Err(ChannelError::Close("Got tx_signatures before commitment_signed".to_owned()));
+		}
+		if splice_funding.holder_sends_tx_signatures_first && splice_funding.holder_tx_signatures.is_none() {
+			return Err(ChannelError::Close("Got tx_signatures before we sent ours".to_owned()));
+		}
+		let shared_input_count = if splice_funding.shared_input_index.is_some() { 1 } else { 0 };
+		let expected_witnesses = splice_funding.funding_transaction.input.len() - shared_input_count - splice_funding.holder_input_count as usize;
+		if msg.witnesses.len() != expected_witnesses || msg.witnesses.iter().any(|witness| witness.is_empty()) {
+			return Err(ChannelError::Close(format!("Got {} witnesses in tx_signatures, expected {}",
+				msg.witnesses.len(), expected_witnesses)));
+		}
+		match (splice_funding.shared_input_index, msg.shared_input_signature) {
+			(Some(shared_input_index), Some(shared_input_signature)) => {
+				let sighash = sighash::SighashCache::new(&splice_funding.funding_transaction).segwit_signature_hash(
+					shared_input_index as usize, &funding_redeemscript, self.context.channel_value_satoshis,
+					EcdsaSighashType::All
+				).map_err(|_| ChannelError::Close("Failed to compute the funding input sighash".to_owned()))?;
+				if let Err(_) = self.context.secp_ctx.verify_ecdsa(&hash_to_message!(&sighash[..]), &shared_input_signature,
+					&counterparty_funding_pubkey)
+				{
+					return Err(ChannelError::Close("Invalid funding input signature from peer".to_owned()));
+				}
+			},
+			(Some(_), None) => return Err(ChannelError::Close("Got tx_signatures without a signature for the funding input".to_owned())),
+			(None, Some(_)) => return Err(ChannelError::Close("Got a funding input signature for a transaction without a funding input".to_owned())),
+			(None, None) => {},
+		}
+
+		splice_funding.counterparty_tx_signatures = Some(msg.clone());
+		if splice_funding.holder_tx_signatures.is_some() {
+			log_info!(logger, "Funding transaction {} for channel {} is fully signed", msg.tx_hash, &self.context.channel_id);
+			return Ok((None, Some(self.finalize_splice_funding_transaction())));
+		}
+		Ok(self.maybe_get_splice_tx_signatures(logger))
+	}
+
+	/// Drops any splice which has yet to be fully signed.
+	///
+	/// The initial funding transaction of a dual-funded channel is never dropped here, as the
+	/// channel cannot exist without it.
+	fn abort_splice(&mut self) -> Option<AbortedSplice> {
+		let splice_funding_abandoned = !self.context.is_initial_funding_unsigned() &&
+			self.context.pending_splice_funding.as_ref()
+				.map(|splice_funding| !splice_funding.is_fully_signed()).unwrap_or(false);
+		let is_funding_rbf = self.context.is_funding_rbf_pending();
+		if self.context.pending_splice.take().is_none() && !splice_funding_abandoned {
+			return None;
+		}
+		self.context.monitor_pending_tx_signatures = false;
+		let funding_txo = if splice_funding_abandoned {
+			self.context.pending_splice_funding.take().map(|splice_funding| splice_funding.funding_outpoint())
+		} else { None };
+		Some(AbortedSplice { funding_txo, is_funding_rbf })
+	}
+
+	/// Handles a `tx_abort` from our counterparty, aborting any splice which has yet to be fully
+	/// signed. Returns the `tx_abort` to echo back if a splice was aborted.
+	pub fn tx_abort<L: Deref>(&mut self, msg: &msgs::TxAbort, logger: &L) -> Option<(msgs::TxAbort, AbortedSplice)>
+	where L::Target: Logger {
+		let aborted_splice = self.abort_splice()?;
+		log_info!(logger, "Splice of channel {} aborted by our counterparty: {}",
+			&self.context.channel_id, String::from_utf8_lossy(&msg.data));
+		Some((msgs::TxAbort { channel_id: self.context.channel_id, data: msg.data.clone() }, aborted_splice))
+	}
+
+	/// Aborts a splice on disconnection unless we've already sent our `tx_signatures`, in which
+	/// case our counterparty may still broadcast it and we'll retransmit them on reconnection.
+	pub fn abort_splice_on_disconnect<L: Deref>(&mut self, logger: &L) -> Option<AbortedSplice> where L::Target: Logger {
+		if self.context.pending_splice_funding.as_ref().map(|funding| funding.holder_tx_signatures.is_some()).unwrap_or(false) {
+			return None;
+		}
+		let aborted_splice = self.abort_splice()?;
+		log_info!(logger, "Splice of channel {} aborted due to peer disconnection", &self.context.channel_id);
+		Some(aborted_splice)
+	}
+
+	fn get_splice_reestablish_responses<L: Deref>(
+		&mut self, msg: &msgs::ChannelReestablish, logger: &L,
+	) -> (Option<msgs::CommitmentSigned>, Option<msgs::TxSignatures>, Option<Transaction>, Option<msgs::TxAbort>, Option<msgs::SpliceLocked>)
+	where L::Target: Logger {
+		let (mut commitment_signed, mut tx_signatures, mut funding_broadcastable, mut tx_abort) = (None, None, None, None);
+		if let Some(next_funding_txid) = msg.next_funding_txid {
+			let pending_funding_txid = self.context.pending_splice_funding.as_ref()
+				.map(|splice_funding| splice_funding.funding_transaction.txid());
+			if pending_funding_txid == Some(next_funding_txid) {
+				let splice_funding = self.context.pending_splice_funding.as_ref().unwrap();
+				if let Some(holder_tx_signatures) = &splice_funding.holder_tx_signatures {
+					log_debug!(logger, "Retransmitting tx_signatures for funding transaction {}", next_funding_txid);
+					commitment_signed = self.get_splice_commitment_signed(splice_funding, logger).ok();
+					tx_signatures = Some(holder_tx_signatures.clone());
+				} else if self.context.is_initial_funding_unsigned() {
+					// The initial funding transaction of a dual-funded channel survives disconnection
+					// even if we have yet to sign it, so we may be able to send our `tx_signatures` now.
+					commitment_signed = self.get_splice_commitment_signed(splice_funding, logger).ok();
+					let (holder_tx_signatures, broadcastable) = self.maybe_get_splice_tx_signatures(logger);
+					tx_signatures = holder_tx_signatures;
+					funding_broadcastable = broadcastable;
+				}
+			} else if pending_funding_txid.is_none() && self.context.get_funding_txo().map(|txo| txo.txid) == Some(next_funding_txid) {
+				// We've already seen the initial funding transaction fully signed and broadcast it, so
+				// our counterparty will learn of it once it confirms.
+				log_debug!(logger, "Ignoring request to retransmit tx_signatures for broadcast funding transaction {}",
+					next_funding_txid);
+			} else {
+				tx_abort = Some(msgs::TxAbort {
+					channel_id: self.context.channel_id,
+					data: b"Unknown funding transaction".to_vec(),
+				});
+			}
+		}
+		let splice_locked = self.context.pending_splice_funding.as_ref()
+			.filter(|splice_funding| splice_funding.sent_splice_locked)
+			.map(|_| msgs::SpliceLocked { channel_id: self.context.channel_id });
+		(commitment_signed, tx_signatures, funding_broadcastable, tx_abort, splice_locked)
+	}
+
+	/// Returns the splice's funding transaction and the block it was confirmed in, if any, so that
+	/// its confirmation can be tracked across reorgs.
+	pub fn get_splice_funding_confirmation(&self) -> Option<(Txid, u32, BlockHash)> {
+		self.context.pending_splice_funding.as_ref().and_then(|splice_funding| {
+			splice_funding.confirmed_in.map(|block_hash|
+				(splice_funding.funding_transaction.txid(), splice_funding.confirmation_height, block_hash))
+		})
+	}
+
+	/// Forgets the confirmation of a splice's funding transaction after it was reorged out.
+	pub fn splice_funding_unconfirmed(&mut self, txid: &Txid) {
+		if let Some(splice_funding) = self.context.pending_splice_funding.as_mut() {
+			if splice_funding.funding_transaction.txid() == *txid {
+				splice_funding.confirmation_height = 0;
+				splice_funding.confirmed_in = None;
+				splice_funding.short_channel_id = None;
+			}
+		}
+	}
+
+	/// Checks whether a splice's funding transaction has reached our required depth, returning the
+	/// `splice_locked` to send if so and whether the splice is now locked by both parties.
+	pub fn check_get_splice_locked<L: Deref>(&mut self, height: u32, logger: &L) -> Option<(msgs::SpliceLocked, bool)>
+	where L::Target: Logger {
+		let minimum_depth = cmp::max(self.context.minimum_depth.unwrap_or(0), 1) as i64;
+		let splice_funding = self.context.pending_splice_funding.as_mut()?;
+		if splice_funding.sent_splice_locked || splice_funding.confirmation_height == 0 {
+			return None;
+		}
+		let confirmations = height as i64 - splice_funding.confirmation_height as i64 + 1;
+		if confirmations <= 0 {
+			splice_funding.confirmation_height = 0;
+			splice_funding.confirmed_in = None;
+			splice_funding.short_channel_id = None;
+			return None;
+		}
+		if confirmations < minimum_depth || self.context.channel_state.is_peer_disconnected() {
+			return None;
+		}
+		splice_funding.sent_splice_locked = true;
+		let locked = splice_funding.received_splice_locked;
+		log_info!(logger, "Sending splice_locked for channel {}", &self.context.channel_id);
+		if locked {
+			self.promote_splice_funding(logger);
+		}
+		Some((msgs::SpliceLocked { channel_id: self.context.channel_id }, locked))
+	}
+
+	/// Handles a `splice_locked` from our counterparty, returning whether the splice is now locked
+	/// by both parties.
+	pub fn splice_locked<L: Deref>(&mut self, _msg: &msgs::SpliceLocked, logger: &L) -> Result<bool, ChannelError>
+	where L::Target: Logger {
+		let splice_funding = match self.context.pending_splice_funding.as_mut() {
+			Some(splice_funding) => splice_funding,
+			// Retransmitted after we've already switched to the new funding output.
+			None if self.context.original_funding_outpoint.is_some() => return Ok(false),
+			None => return Err(ChannelError::Warn("Got splice_locked without a pending splice".to_owned())),
+		};
+		if !splice_funding.is_fully_signed() {
+			return Err(ChannelError::Close("Got splice_locked before tx_signatures were exchanged".to_owned()));
+		}
+		splice_funding.received_splice_locked = true;
+		if splice_funding.sent_splice_locked {
+			self.promote_splice_funding(logger);
+			return Ok(true);
+		}
+		Ok(false)
+	}
+
+	/// Switches the channel to a splice's funding output once both parties have sent
+	/// `splice_locked`, or to a fee-bumped funding transaction once it confirms.
+	///
+	/// The [`ChannelMonitor`] switches to a splice's funding output once it learns the splice has
+	/// been locked via [`Self::get_splice_locked_monitor_update`]. A fee-bumped funding transaction
+	/// conflicts with the one it replaces, so the [`ChannelMonitor`] switches to it as soon as it
+	/// confirms.
+	fn promote_splice_funding<L: Deref>(&mut self, logger: &L) where L::Target: Logger {
+		let splice_funding = self.context.pending_splice_funding.take().unwrap();
+		let is_splice = splice_funding.shared_input_index.is_some();
+		let funding_outpoint = splice_funding.funding_outpoint();
+		log_info!(logger, "Channel {} switched to funding outpoint {}, new channel value is {} sats",
+			&self.context.channel_id, funding_outpoint, splice_funding.channel_value_satoshis);
+
+		if self.context.original_funding_outpoint.is_none() {
+			self.context.original_funding_outpoint = self.context.channel_transaction_parameters.funding_outpoint;
+		}
+		self.context.channel_transaction_parameters.funding_outpoint = Some(funding_outpoint);
+		self.context.channel_value_satoshis = splice_funding.channel_value_satoshis;
+		self.context.value_to_self_msat = splice_funding.value_to_self_msat;
+		self.context.funding_tx_confirmation_height = splice_funding.confirmation_height;
+		self.context.funding_tx_confirmed_in = splice_funding.confirmed_in;
+		self.context.short_channel_id = splice_funding.short_channel_id;
+		self.context.funding_transaction = None;
+		// The new funding output has to be announced under its new short channel id.
+		self.context.announcement_sigs_state = AnnouncementSigsState::NotSent;
+		self.context.announcement_sigs = None;
+		self.context.holder_signer.as_mut().provide_renegotiated_funding(funding_outpoint, splice_funding.channel_value_satoshis);
+		self.context.update_time_counter += 1;
+		#[cfg(debug_assertions)]
+		{
+			let value_to_remote_msat = splice_funding.channel_value_satoshis * 1000 - splice_funding.value_to_self_msat;
+			let balances = (splice_funding.value_to_self_msat, value_to_remote_msat);
+			*self.context.holder_max_commitment_tx_output.lock().unwrap() = balances;
+			*self.context.counterparty_max_commitment_tx_output.lock().unwrap() = balances;
+		}
+
+		if is_splice {
+			self.context.monitor_pending_splice_locked = Some(funding_outpoint.txid);
+		}
+	}
+
+	/// Returns whether we have yet to inform the [`ChannelMonitor`] that a splice has been locked
+	/// by both parties.
+	pub fn is_monitor_pending_splice_locked(&self) -> bool {
+		self.context.monitor_pending_splice_locked.is_some()
+	}
+
+	/// Returns the [`ChannelMonitorUpdate`] informing the [`ChannelMonitor`] that a splice has been
+	/// locked by both parties, allowing it to switch to the splice's funding output, if we have yet
+	/// to generate it.
+	///
+	/// This is generated separately from the promotion of the splice's funding output as the latter
+	/// may happen while processing a block, when no [`ChannelMonitorUpdate`]s can be applied.
+	pub fn get_splice_locked_monitor_update(&mut self) -> Option<ChannelMonitorUpdate> {
+		let funding_txid = self.context.monitor_pending_splice_locked.take()?;
+		self.context.latest_monitor_update_id += 1;
+		let monitor_update = ChannelMonitorUpdate {
+			update_id: self.context.latest_monitor_update_id,
+			counterparty_node_id: Some(self.context.counterparty_node_id),
+			updates: vec![ChannelMonitorUpdateStep::SpliceLocked { funding_txid }],
+		};
+		self.push_ret_blockable_mon_update(monitor_update)
+	}
+
+	pub fn inflight_htlc_sources(&self) -> impl Iterator<Item=(&HTLCSource, &PaymentHash)> {
+		self.context.holding_cell_htlc_updates.iter()
+			.flat_map(|htlc_update| {
+				match htlc_update {
+					HTLCUpdateAwaitingACK::AddHTLC { source, payment_hash, .. }
+						=> Some((source, payment_hash)),
+					_ => None,
+				}
+			})
+			.chain(self.context.pending_outbound_htlcs.iter().map(|htlc| (&htlc.source, &htlc.payment_hash)))
+	}
+}
+
+/// A not-yet-funded outbound (from holder) channel using V1 channel establishment.
+pub(super) struct OutboundV1Channel<SP: Deref> where SP::Target: SignerProvider {
+	pub context: ChannelContext<SP>,
+	pub unfunded_context: UnfundedChannelContext,
+}
+
+impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
+	pub fn new<ES: Deref, F: Deref>(
+		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP, counterparty_node_id: PublicKey, their_features: &InitFeatures,
+		channel_value_satoshis: u64, push_msat: u64, user_id: u128, config: &UserConfig, current_chain_height: u32,
+		outbound_scid_alias: u64, temporary_channel_id: Option<ChannelId>
+	) -> Result<OutboundV1Channel<SP>, APIError>
+	where ES::Target: EntropySource,
+	      F::Target: FeeEstimator
+	{
+		let holder_selected_contest_delay = config.channel_handshake_config.our_to_self_delay;
+		let channel_keys_id = signer_provider.generate_channel_keys_id(false, channel_value_satoshis, user_id);
+		let holder_signer = signer_provider.derive_channel_signer(channel_value_satoshis, channel_keys_id);
+		let pubkeys = holder_signer.pubkeys().clone();
+
+		if !their_features.supports_wumbo() && channel_value_satoshis > MAX_FUNDING_SATOSHIS_NO_WUMBO {
+			return Err(APIError::APIMisuseError{err: format!("funding_value must not exceed {}, it was {}", MAX_FUNDING_SATOSHIS_NO_WUMBO, channel_value_satoshis)});
+		}
+		if channel_value_satoshis >= TOTAL_BITCOIN_SUPPLY_SATOSHIS {
+			return Err(APIError::APIMisuseError{err: format!("funding_value must be smaller than the total bitcoin supply, it was {}", channel_value_satoshis)});
+		}
+		let channel_value_msat = channel_value_satoshis * 1000;
+		if push_msat > channel_value_msat {
+			return Err(APIError::APIMisuseError { err: format!("Push value ({}) was larger than channel_value ({})", push_msat, channel_value_msat) });
+		}
+		if holder_selected_contest_delay < BREAKDOWN_TIMEOUT {
+			return Err(APIError::APIMisuseError {err: format!("Configured with an unreasonable our_to_self_delay ({}) putting user funds at risks", holder_selected_contest_delay)});
+		}
+		let holder_selected_channel_reserve_satoshis = get_holder_selected_channel_reserve_satoshis(channel_value_satoshis, config);
+		if holder_selected_channel_reserve_satoshis < MIN_CHAN_DUST_LIMIT_SATOSHIS {
+			// Protocol level safety check in place, although it should never happen because
+			// of `MIN_THEIR_CHAN_RESERVE_SATOSHIS`
+			return Err(APIError::APIMisuseError { err: format!("Holder selected channel  reserve below implemention limit dust_limit_satoshis {}", holder_selected_channel_reserve_satoshis) });
+		}
+
+		let channel_type = Self::get_initial_channel_type(&config, their_features);
+		debug_assert!(channel_type.is_subset(&channelmanager::provided_channel_type_features(&config)));
+
+		let anchor_outputs_value_msat = anchor_outputs_value_sat(&channel_type) * 1000;
+		let commitment_feerate = if channel_type.supports_anchor_zero_fee_commitments() {
+			0
+		} else if channel_type.supports_anchors_zero_fee_htlc_tx() {
+			fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::AnchorChannelFee)
+		} else {
+			fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::NonAnchorChannelFee)
+		};
+
+		let value_to_self_msat = channel_value_satoshis * 1000 - push_msat;
+		let commitment_tx_fee = commit_tx_fee_msat(commitment_feerate, MIN_AFFORDABLE_HTLC_COUNT, &channel_type);
+		if value_to_self_msat.saturating_sub(anchor_outputs_value_msat) < commitment_tx_fee {
+			return Err(APIError::APIMisuseError{ err: format!("Funding amount ({}) can't even pay fee for initial commitment transaction fee of {}.", value_to_self_msat / 1000, commitment_tx_fee / 1000) });
+		}
+
+		let mut secp_ctx = Secp256k1::new();
+		secp_ctx.seeded_randomize(&entropy_source.get_secure_random_bytes());
+
+		let shutdown_scriptpubkey = if config.channel_handshake_config.commit_upfront_shutdown_pubkey {
+			match signer_provider.get_shutdown_scriptpubkey() {
+				Ok(scriptpubkey) => Some(scriptpubkey),
+				Err(_) => return Err(APIError::ChannelUnavailable { err: "Failed to get shutdown scriptpubkey".to_owned()}),
+			}
+		} else { None };
+
+		if let Some(shutdown_scriptpubkey) = &shutdown_scriptpubkey {
+			if !shutdown_scriptpubkey.is_compatible(&their_features) {
+				return Err(APIError::IncompatibleShutdownScript { script: shutdown_scriptpubkey.clone() });
+			}
+		}
+
+		let destination_script = match signer_provider.get_destination_script(channel_keys_id) {
+			Ok(script) => script,
+			Err(_) => return Err(APIError::ChannelUnavailable { err: "Failed to get destination script".to_owned()}),
+		};
+
+		let temporary_channel_id = temporary_channel_id.unwrap_or_else(|| ChannelId::temporary_from_entropy_source(entropy_source));
+
+		Ok(Self {
+			context: ChannelContext {
+				user_id,
+
+				config: LegacyChannelConfig {
+					options: config.channel_config.clone(),
+					announced_channel: config.channel_handshake_config.announced_channel,
+					commit_upfront_shutdown_pubkey: config.channel_handshake_config.commit_upfront_shutdown_pubkey,
+				},
+
+				prev_config: None,
+
+				inbound_handshake_limits_override: Some(config.channel_handshake_limits.clone()),
+
+				channel_id: temporary_channel_id,
+				temporary_channel_id: Some(temporary_channel_id),
+				channel_state: ChannelState::NegotiatingFunding(NegotiatingFundingFlags::OUR_INIT_SENT),
+				announcement_sigs_state: AnnouncementSigsState::NotSent,
 				secp_ctx,
 				channel_value_satoshis,
 
@@ -6325,6 +8278,8 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 				monitor_pending_channel_ready: false,
 				monitor_pending_revoke_and_ack: false,
 				monitor_pending_commitment_signed: false,
+				monitor_pending_tx_signatures: false,
+				monitor_pending_splice_locked: None,
 				monitor_pending_forwards: Vec::new(),
 				monitor_pending_failures: Vec::new(),
 				monitor_pending_finalized_fulfills: Vec::new(),
@@ -6358,7 +8313,7 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 				counterparty_htlc_minimum_msat: 0,
 				holder_htlc_minimum_msat: if config.channel_handshake_config.our_htlc_minimum_msat == 0 { 1 } else { config.channel_handshake_config.our_htlc_minimum_msat },
 				counterparty_max_accepted_htlcs: 0,
-				holder_max_accepted_htlcs: cmp::min(config.channel_handshake_config.our_max_accepted_htlcs, MAX_HTLCS),
+				holder_max_accepted_htlcs: cmp::min(config.channel_handshake_config.our_max_accepted_htlcs, max_accepted_htlcs_limit(&channel_type)),
 				minimum_depth: None, // Filled in in accept_channel
 
 				counterparty_forwarding_info: None,
@@ -6394,6 +8349,7 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 
 				workaround_lnd_bug_4006: None,
 				sent_message_awaiting_response: None,
+				is_holder_quiescence_initiator: None,
 
 				latest_inbound_scid_alias: None,
 				outbound_scid_alias,
@@ -6408,6 +8364,12 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 				channel_keys_id,
 
 				blocked_monitor_updates: Vec::new(),
+
+				original_funding_outpoint: None,
+				pending_splice: None,
+				pending_splice_funding: None,
+				dual_funding_feerate_sat_per_1000_weight: None,
+				dual_funding_transaction: None,
 			},
 			unfunded_context: UnfundedChannelContext { unfunded_channel_age_ticks: 0 }
 		})
@@ -6518,10 +8480,15 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 
 		// Optionally, if the user would like to negotiate the `anchors_zero_fee_htlc_tx` option, we
 		// set it now. If they don't understand it, we'll fall back to our default of
-		// `only_static_remotekey`.
-		if config.channel_handshake_config.negotiate_anchors_zero_fee_htlc_tx &&
+		// `only_static_remotekey`. `zero_fee_commitments` builds on top of it, so we only try it if
+		// they understand both.
+		let negotiate_zero_fee_commitments = config.channel_handshake_config.negotiate_anchor_zero_fee_commitments;
+		if (config.channel_handshake_config.negotiate_anchors_zero_fee_htlc_tx || negotiate_zero_fee_commitments) &&
 			their_features.supports_anchors_zero_fee_htlc_tx() {
 			ret.set_anchors_zero_fee_htlc_tx_required();
+			if negotiate_zero_fee_commitments && their_features.supports_anchor_zero_fee_commitments() {
+				ret.set_anchor_zero_fee_commitments_required();
+			}
 		}
 
 		ret
@@ -6557,7 +8524,10 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 		// checks whether the counterparty supports every feature, this would only happen if the
 		// counterparty is advertising the feature, but rejecting channels proposing the feature for
 		// whatever reason.
-		if self.context.channel_type.supports_anchors_zero_fee_htlc_tx() {
+		if self.context.channel_type.supports_anchor_zero_fee_commitments() {
+			self.context.channel_type.clear_anchor_zero_fee_commitments();
+			self.context.feerate_per_kw = fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::AnchorChannelFee);
+		} else if self.context.channel_type.supports_anchors_zero_fee_htlc_tx() {
 			self.context.channel_type.clear_anchors_zero_fee_htlc_tx();
 			self.context.feerate_per_kw = fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::NonAnchorChannelFee);
 			assert!(!self.context.channel_transaction_parameters.channel_type_features.supports_anchors_nonzero_fee_htlc_tx());
@@ -6614,135 +8584,7 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 
 	// Message handlers
 	pub fn accept_channel(&mut self, msg: &msgs::AcceptChannel, default_limits: &ChannelHandshakeLimits, their_features: &InitFeatures) -> Result<(), ChannelError> {
-		let peer_limits = if let Some(ref limits) = self.context.inbound_handshake_limits_override { limits } else { default_limits };
-
-		// Check sanity of message fields:
-		if !self.context.is_outbound() {
-			return Err(ChannelError::Close("Got an accept_channel message from an inbound peer".to_owned()));
-		}
-		if !matches!(self.context.channel_state, ChannelState::NegotiatingFunding(flags) if flags == NegotiatingFundingFlags::OUR_INIT_SENT) {
-			return Err(ChannelError::Close("Got an accept_channel message at a strange time".to_owned()));
-		}
-		if msg.dust_limit_satoshis > 21000000 * 100000000 {
-			return Err(ChannelError::Close(format!("Peer never wants payout outputs? dust_limit_satoshis was {}", msg.dust_limit_satoshis)));
-		}
-		if msg.channel_reserve_satoshis > self.context.channel_value_satoshis {
-			return Err(ChannelError::Close(format!("Bogus channel_reserve_satoshis ({}). Must not be greater than ({})", msg.channel_reserve_satoshis, self.context.channel_value_satoshis)));
-		}
-		if msg.dust_limit_satoshis > self.context.holder_selected_channel_reserve_satoshis {
-			return Err(ChannelError::Close(format!("Dust limit ({}) is bigger than our channel reserve ({})", msg.dust_limit_satoshis, self.context.holder_selected_channel_reserve_satoshis)));
-		}
-		if msg.channel_reserve_satoshis > self.context.channel_value_satoshis - self.context.holder_selected_channel_reserve_satoshis {
-			return Err(ChannelError::Close(format!("Bogus channel_reserve_satoshis ({}). Must not be greater than channel value minus our reserve ({})",
-				msg.channel_reserve_satoshis, self.context.channel_value_satoshis - self.context.holder_selected_channel_reserve_satoshis)));
-		}
-		let full_channel_value_msat = (self.context.channel_value_satoshis - msg.channel_reserve_satoshis) * 1000;
-		if msg.htlc_minimum_msat >= full_channel_value_msat {
-			return Err(ChannelError::Close(format!("Minimum htlc value ({}) is full channel value ({})", msg.htlc_minimum_msat, full_channel_value_msat)));
-		}
-		let max_delay_acceptable = u16::min(peer_limits.their_to_self_delay, MAX_LOCAL_BREAKDOWN_TIMEOUT);
-		if msg.to_self_delay > max_delay_acceptable {
-			return Err(ChannelError::Close(format!("They wanted our payments to be delayed by a needlessly long period. Upper limit: {}. Actual: {}", max_delay_acceptable, msg.to_self_delay)));
-		}
-		if msg.max_accepted_htlcs < 1 {
-			return Err(ChannelError::Close("0 max_accepted_htlcs makes for a useless channel".to_owned()));
-		}
-		if msg.max_accepted_htlcs > MAX_HTLCS {
-			return Err(ChannelError::Close(format!("max_accepted_htlcs was {}. It must not be larger than {}", msg.max_accepted_htlcs, MAX_HTLCS)));
-		}
-
-		// Now check against optional parameters as set by config...
-		if msg.htlc_minimum_msat > peer_limits.max_htlc_minimum_msat {
-			return Err(ChannelError::Close(format!("htlc_minimum_msat ({}) is higher than the user specified limit ({})", msg.htlc_minimum_msat, peer_limits.max_htlc_minimum_msat)));
-		}
-		if msg.max_htlc_value_in_flight_msat < peer_limits.min_max_htlc_value_in_flight_msat {
-			return Err(ChannelError::Close(format!("max_htlc_value_in_flight_msat ({}) is less than the user specified limit ({})", msg.max_htlc_value_in_flight_msat, peer_limits.min_max_htlc_value_in_flight_msat)));
-		}
-		if msg.channel_reserve_satoshis > peer_limits.max_channel_reserve_satoshis {
-			return Err(ChannelError::Close(format!("channel_reserve_satoshis ({}) is higher than the user specified limit ({})", msg.channel_reserve_satoshis, peer_limits.max_channel_reserve_satoshis)));
-		}
-		if msg.max_accepted_htlcs < peer_limits.min_max_accepted_htlcs {
-			return Err(ChannelError::Close(format!("max_accepted_htlcs ({}) is less than the user specified limit ({})", msg.max_accepted_htlcs, peer_limits.min_max_accepted_htlcs)));
-		}
-		if msg.dust_limit_satoshis < MIN_CHAN_DUST_LIMIT_SATOSHIS {
-			return Err(ChannelError::Close(format!("dust_limit_satoshis ({}) is less than the implementation limit ({})", msg.dust_limit_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS)));
-		}
-		if msg.dust_limit_satoshis > MAX_CHAN_DUST_LIMIT_SATOSHIS {
-			return Err(ChannelError::Close(format!("dust_limit_satoshis ({}) is greater than the implementation limit ({})", msg.dust_limit_satoshis, MAX_CHAN_DUST_LIMIT_SATOSHIS)));
-		}
-		if msg.minimum_depth > peer_limits.max_minimum_depth {
-			return Err(ChannelError::Close(format!("We consider the minimum depth to be unreasonably large. Expected minimum: ({}). Actual: ({})", peer_limits.max_minimum_depth, msg.minimum_depth)));
-		}
-
-		if let Some(ty) = &msg.channel_type {
-			if *ty != self.context.channel_type {
-				return Err(ChannelError::Close("Channel Type in accept_channel didn't match the one sent in open_channel.".to_owned()));
-			}
-		} else if their_features.supports_channel_type() {
-			// Assume they've accepted the channel type as they said they understand it.
-		} else {
-			let channel_type = ChannelTypeFeatures::from_init(&their_features);
-			if channel_type != ChannelTypeFeatures::only_static_remote_key() {
-				return Err(ChannelError::Close("Only static_remote_key is supported for non-negotiated channel types".to_owned()));
-			}
-			self.context.channel_type = channel_type.clone();
-			self.context.channel_transaction_parameters.channel_type_features = channel_type;
-		}
-
-		let counterparty_shutdown_scriptpubkey = if their_features.supports_upfront_shutdown_script() {
-			match &msg.shutdown_scriptpubkey {
-				&Some(ref script) => {
-					// Peer is signaling upfront_shutdown and has opt-out with a 0-length script. We don't enforce anything
-					if script.len() == 0 {
-						None
-					} else {
-						if !script::is_bolt2_compliant(&script, their_features) {
-							return Err(ChannelError::Close(format!("Peer is signaling upfront_shutdown but has provided an unacceptable scriptpubkey format: {}", script)));
-						}
-						Some(script.clone())
-					}
-				},
-				// Peer is signaling upfront shutdown but don't opt-out with correct mechanism (a.k.a 0-length script). Peer looks buggy, we fail the channel
-				&None => {
-					return Err(ChannelError::Close("Peer is signaling upfront_shutdown but we don't get any script. Use 0-length script to opt-out".to_owned()));
-				}
-			}
-		} else { None };
-
-		self.context.counterparty_dust_limit_satoshis = msg.dust_limit_satoshis;
-		self.context.counterparty_max_htlc_value_in_flight_msat = cmp::min(msg.max_htlc_value_in_flight_msat, self.context.channel_value_satoshis * 1000);
-		self.context.counterparty_selected_channel_reserve_satoshis = Some(msg.channel_reserve_satoshis);
-		self.context.counterparty_htlc_minimum_msat = msg.htlc_minimum_msat;
-		self.context.counterparty_max_accepted_htlcs = msg.max_accepted_htlcs;
-
-		if peer_limits.trust_own_funding_0conf {
-			self.context.minimum_depth = Some(msg.minimum_depth);
-		} else {
-			self.context.minimum_depth = Some(cmp::max(1, msg.minimum_depth));
-		}
-
-		let counterparty_pubkeys = ChannelPublicKeys {
-			funding_pubkey: msg.funding_pubkey,
-			revocation_basepoint: RevocationBasepoint::from(msg.revocation_basepoint),
-			payment_point: msg.payment_point,
-			delayed_payment_basepoint: DelayedPaymentBasepoint::from(msg.delayed_payment_basepoint),
-			htlc_basepoint: HtlcBasepoint::from(msg.htlc_basepoint)
-		};
-
-		self.context.channel_transaction_parameters.counterparty_parameters = Some(CounterpartyChannelTransactionParameters {
-			selected_contest_delay: msg.to_self_delay,
-			pubkeys: counterparty_pubkeys,
-		});
-
-		self.context.counterparty_cur_commitment_point = Some(msg.first_per_commitment_point);
-		self.context.counterparty_shutdown_scriptpubkey = counterparty_shutdown_scriptpubkey;
-
-		self.context.channel_state = ChannelState::NegotiatingFunding(
-			NegotiatingFundingFlags::OUR_INIT_SENT | NegotiatingFundingFlags::THEIR_INIT_SENT
-		);
-		self.context.inbound_handshake_limits_override = None; // We're done enforcing limits on our peer's handshake now.
-
-		Ok(())
+		self.context.do_accept_channel_checks(default_limits, their_features, msg)
 	}
 
 	/// Handles a funding_signed message from the remote end.
@@ -6814,7 +8656,7 @@ impl<SP: Deref> OutboundV1Channel<SP> where SP::Target: SignerProvider {
 		                                          &self.context.channel_transaction_parameters,
 		                                          funding_redeemscript.clone(), self.context.channel_value_satoshis,
 		                                          obscure_factor,
-		                                          holder_commitment_tx, best_block, self.context.counterparty_node_id);
+		                                          holder_commitment_tx, best_block, self.context.counterparty_node_id, self.context.channel_id());
 		channel_monitor.provide_initial_counterparty_commitment_tx(
 			counterparty_initial_bitcoin_tx.txid, Vec::new(),
 			self.context.cur_counterparty_commitment_transaction_number,
@@ -6860,6 +8702,35 @@ pub(super) struct InboundV1Channel<SP: Deref> where SP::Target: SignerProvider {
 
 /// Fetches the [`ChannelTypeFeatures`] that will be used for a channel built from a given
 /// [`msgs::OpenChannel`].
+/// Converts an `open_channel2` message to the equivalent `open_channel` message, with our
+/// contribution as the amount pushed to us, so that checks of V1 channel establishment can be
+/// reused for dual-funded channels.
+pub(super) fn open_channel_v1_from_v2(msg: &msgs::OpenChannelV2, our_funding_satoshis: u64) -> msgs::OpenChannel {
+	let channel_value_satoshis = msg.funding_satoshis.saturating_add(our_funding_satoshis);
+	msgs::OpenChannel {
+		chain_hash: msg.chain_hash,
+		temporary_channel_id: msg.temporary_channel_id,
+		funding_satoshis: channel_value_satoshis,
+		push_msat: our_funding_satoshis.saturating_mul(1000),
+		dust_limit_satoshis: msg.dust_limit_satoshis,
+		max_htlc_value_in_flight_msat: msg.max_htlc_value_in_flight_msat,
+		channel_reserve_satoshis: get_v2_channel_reserve_satoshis(channel_value_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS),
+		htlc_minimum_msat: msg.htlc_minimum_msat,
+		feerate_per_kw: msg.commitment_feerate_sat_per_1000_weight,
+		to_self_delay: msg.to_self_delay,
+		max_accepted_htlcs: msg.max_accepted_htlcs,
+		funding_pubkey: msg.funding_pubkey,
+		revocation_basepoint: msg.revocation_basepoint,
+		payment_point: msg.payment_basepoint,
+		delayed_payment_basepoint: msg.delayed_payment_basepoint,
+		htlc_basepoint: msg.htlc_basepoint,
+		first_per_commitment_point: msg.first_per_commitment_point,
+		channel_flags: msg.channel_flags,
+		shutdown_scriptpubkey: msg.shutdown_scriptpubkey.clone(),
+		channel_type: msg.channel_type.clone(),
+	}
+}
+
 pub(super) fn channel_type_from_open_channel(
 	msg: &msgs::OpenChannel, their_features: &InitFeatures,
 	our_supported_features: &ChannelTypeFeatures
@@ -6883,6 +8754,9 @@ pub(super) fn channel_type_from_open_channel(
 		if channel_type.requires_scid_privacy() && announced_channel {
 			return Err(ChannelError::Close("SCID Alias/Privacy Channel Type cannot be set on a public channel".to_owned()));
 		}
+		if channel_type.requires_anchor_zero_fee_commitments() && !channel_type.requires_anchors_zero_fee_htlc_tx() {
+			return Err(ChannelError::Close("Zero-fee commitments Channel Type requires anchors_zero_fee_htlc_tx".to_owned()));
+		}
 		Ok(channel_type.clone())
 	} else {
 		let channel_type = ChannelTypeFeatures::from_init(&their_features);
@@ -6957,8 +8831,9 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 		if msg.max_accepted_htlcs < 1 {
 			return Err(ChannelError::Close("0 max_accepted_htlcs makes for a useless channel".to_owned()));
 		}
-		if msg.max_accepted_htlcs > MAX_HTLCS {
-			return Err(ChannelError::Close(format!("max_accepted_htlcs was {}. It must not be larger than {}", msg.max_accepted_htlcs, MAX_HTLCS)));
+		let max_htlcs = max_accepted_htlcs_limit(&channel_type);
+		if msg.max_accepted_htlcs > max_htlcs {
+			return Err(ChannelError::Close(format!("max_accepted_htlcs was {}. It must not be larger than {}", msg.max_accepted_htlcs, max_htlcs)));
 		}
 
 		// Now check against optional parameters as set by config...
@@ -7011,11 +8886,7 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 
 		// check if the funder's amount for the initial commitment tx is sufficient
 		// for full fee payment plus a few HTLCs to ensure the channel will be useful.
-		let anchor_outputs_value = if channel_type.supports_anchors_zero_fee_htlc_tx() {
-			ANCHOR_OUTPUT_VALUE_SATOSHI * 2
-		} else {
-			0
-		};
+		let anchor_outputs_value = anchor_outputs_value_sat(&channel_type);
 		let funders_amount_msat = msg.funding_satoshis * 1000 - msg.push_msat;
 		let commitment_tx_fee = commit_tx_fee_msat(msg.feerate_per_kw, MIN_AFFORDABLE_HTLC_COUNT, &channel_type) / 1000;
 		if (funders_amount_msat / 1000).saturating_sub(anchor_outputs_value) < commitment_tx_fee {
@@ -7122,6 +8993,8 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 				monitor_pending_channel_ready: false,
 				monitor_pending_revoke_and_ack: false,
 				monitor_pending_commitment_signed: false,
+				monitor_pending_tx_signatures: false,
+				monitor_pending_splice_locked: None,
 				monitor_pending_forwards: Vec::new(),
 				monitor_pending_failures: Vec::new(),
 				monitor_pending_finalized_fulfills: Vec::new(),
@@ -7156,7 +9029,7 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 				counterparty_htlc_minimum_msat: msg.htlc_minimum_msat,
 				holder_htlc_minimum_msat: if config.channel_handshake_config.our_htlc_minimum_msat == 0 { 1 } else { config.channel_handshake_config.our_htlc_minimum_msat },
 				counterparty_max_accepted_htlcs: msg.max_accepted_htlcs,
-				holder_max_accepted_htlcs: cmp::min(config.channel_handshake_config.our_max_accepted_htlcs, MAX_HTLCS),
+				holder_max_accepted_htlcs: cmp::min(config.channel_handshake_config.our_max_accepted_htlcs, max_accepted_htlcs_limit(&channel_type)),
 				minimum_depth,
 
 				counterparty_forwarding_info: None,
@@ -7195,6 +9068,7 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 
 				workaround_lnd_bug_4006: None,
 				sent_message_awaiting_response: None,
+				is_holder_quiescence_initiator: None,
 
 				latest_inbound_scid_alias: None,
 				outbound_scid_alias: 0,
@@ -7209,6 +9083,12 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 				channel_keys_id,
 
 				blocked_monitor_updates: Vec::new(),
+
+				original_funding_outpoint: None,
+				pending_splice: None,
+				pending_splice_funding: None,
+				dual_funding_feerate_sat_per_1000_weight: None,
+				dual_funding_transaction: None,
 			},
 			unfunded_context: UnfundedChannelContext { unfunded_channel_age_ticks: 0 }
 		};
@@ -7374,7 +9254,7 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 		                                          &self.context.channel_transaction_parameters,
 		                                          funding_redeemscript.clone(), self.context.channel_value_satoshis,
 		                                          obscure_factor,
-		                                          holder_commitment_tx, best_block, self.context.counterparty_node_id);
+		                                          holder_commitment_tx, best_block, self.context.counterparty_node_id, self.context.channel_id());
 		channel_monitor.provide_initial_counterparty_commitment_tx(
 			counterparty_initial_commitment_tx.trust().txid(), Vec::new(),
 			self.context.cur_counterparty_commitment_transaction_number + 1,
@@ -7397,6 +9277,355 @@ impl<SP: Deref> InboundV1Channel<SP> where SP::Target: SignerProvider {
 	}
 }
 
+/// A not-yet-funded outbound (from holder) channel using V2 channel establishment.
+pub(super) struct OutboundV2Channel<SP: Deref> where SP::Target: SignerProvider {
+	pub context: ChannelContext<SP>,
+	pub unfunded_context: UnfundedChannelContext,
+	pub dual_funding_context: DualFundingChannelContext,
+}
+
+impl<SP: Deref> OutboundV2Channel<SP> where SP::Target: SignerProvider {
+	pub fn new<ES: Deref, F: Deref>(
+		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP,
+		counterparty_node_id: PublicKey, their_features: &InitFeatures, contribution: FundingContribution,
+		funding_feerate_sat_per_1000_weight: u32, funding_tx_locktime: u32, user_id: u128, config: &UserConfig,
+		current_chain_height: u32, outbound_scid_alias: u64,
+	) -> Result<OutboundV2Channel<SP>, APIError>
+	where ES::Target: EntropySource,
+	      F::Target: FeeEstimator,
+	{
+		if !their_features.supports_dual_fund() {
+			return Err(APIError::APIMisuseError { err: "Peer does not support dual-funded channels".to_owned() });
+		}
+		if contribution.value_satoshis == 0 || contribution.inputs.is_empty() {
+			return Err(APIError::APIMisuseError { err: "Opening a dual-funded channel requires a non-zero contribution and at least one input".to_owned() });
+		}
+		if funding_feerate_sat_per_1000_weight < FEERATE_FLOOR_SATS_PER_KW {
+			return Err(APIError::APIMisuseError {
+				err: format!("Funding feerate of {} sat/kW is below the minimum of {} sat/kW", funding_feerate_sat_per_1000_weight, FEERATE_FLOOR_SATS_PER_KW),
+			});
+		}
+		// Our counterparty's contribution will be added once they accept the channel.
+		let mut context = OutboundV1Channel::new(fee_estimator, entropy_source, signer_provider, counterparty_node_id,
+			their_features, contribution.value_satoshis, 0, user_id, config, current_chain_height, outbound_scid_alias,
+			None)?.context;
+		let temporary_channel_id = ChannelId::temporary_v2_from_revocation_basepoint(&context.get_holder_pubkeys().revocation_basepoint);
+		context.channel_id = temporary_channel_id;
+		context.temporary_channel_id = Some(temporary_channel_id);
+		context.dual_funding_feerate_sat_per_1000_weight = Some(funding_feerate_sat_per_1000_weight);
+
+		// We don't know our counterparty's funding pubkey yet, but only the size of the funding
+		// output's script matters to estimate the weight of our contribution.
+		let funding_script = ScriptBuf::new_v0_p2wsh(&WScriptHash::all_zeros());
+		let (our_funding_inputs, our_funding_outputs) = get_funding_inputs_and_change(contribution.value_satoshis,
+			contribution.inputs, contribution.change_script, true, false, &funding_script, funding_feerate_sat_per_1000_weight)?;
+		context.pending_splice = Some(PendingSplice {
+			is_initiator: true,
+			our_funding_contribution_satoshis: contribution.value_satoshis as i64,
+			their_funding_contribution_satoshis: 0,
+			funding_feerate_per_kw: funding_feerate_sat_per_1000_weight,
+			locktime: funding_tx_locktime,
+			our_funding_inputs,
+			our_funding_outputs,
+			interactive_tx_constructor: None,
+		});
+
+		Ok(Self {
+			context,
+			unfunded_context: UnfundedChannelContext { unfunded_channel_age_ticks: 0 },
+			dual_funding_context: DualFundingChannelContext {
+				our_funding_satoshis: contribution.value_satoshis,
+				their_funding_satoshis: 0,
+				funding_tx_locktime,
+				funding_feerate_sat_per_1000_weight,
+			},
+		})
+	}
+
+	pub fn get_open_channel_v2(&self, chain_hash: ChainHash) -> msgs::OpenChannelV2 {
+		if !self.context.is_outbound() {
+			panic!("Tried to open a channel for an inbound channel?");
+		}
+		if self.context.have_received_message() {
+			panic!("Cannot generate an open_channel2 after we've moved forward");
+		}
+
+		if self.context.cur_holder_commitment_transaction_number != INITIAL_COMMITMENT_NUMBER {
+			panic!("Tried to send an open_channel2 for a channel that has already advanced");
+		}
+
+		let first_per_commitment_point = self.context.holder_signer.as_ref()
+			.get_per_commitment_point(self.context.cur_holder_commitment_transaction_number, &self.context.secp_ctx);
+		let second_per_commitment_point = self.context.holder_signer.as_ref()
+			.get_per_commitment_point(self.context.cur_holder_commitment_transaction_number - 1, &self.context.secp_ctx);
+		let keys = self.context.get_holder_pubkeys();
+
+		msgs::OpenChannelV2 {
+			chain_hash,
+			temporary_channel_id: self.context.channel_id,
+			funding_feerate_sat_per_1000_weight: self.dual_funding_context.funding_feerate_sat_per_1000_weight,
+			commitment_feerate_sat_per_1000_weight: self.context.feerate_per_kw,
+			funding_satoshis: self.dual_funding_context.our_funding_satoshis,
+			dust_limit_satoshis: self.context.holder_dust_limit_satoshis,
+			max_htlc_value_in_flight_msat: self.context.holder_max_htlc_value_in_flight_msat,
+			htlc_minimum_msat: self.context.holder_htlc_minimum_msat,
+			to_self_delay: self.context.get_holder_selected_contest_delay(),
+			max_accepted_htlcs: self.context.holder_max_accepted_htlcs,
+			locktime: self.dual_funding_context.funding_tx_locktime,
+			funding_pubkey: keys.funding_pubkey,
+			revocation_basepoint: keys.revocation_basepoint.to_public_key(),
+			payment_basepoint: keys.payment_point,
+			delayed_payment_basepoint: keys.delayed_payment_basepoint.to_public_key(),
+			htlc_basepoint: keys.htlc_basepoint.to_public_key(),
+			first_per_commitment_point,
+			second_per_commitment_point,
+			channel_flags: if self.context.config.announced_channel {1} else {0},
+			shutdown_scriptpubkey: Some(match &self.context.shutdown_scriptpubkey {
+				Some(script) => script.clone().into_inner(),
+				None => Builder::new().into_script(),
+			}),
+			channel_type: Some(self.context.channel_type.clone()),
+			require_confirmed_inputs: None,
+		}
+	}
+
+	/// Handles an `accept_channel2` message from our counterparty, returning the first message of
+	/// the interactive construction of the funding transaction.
+	///
+	/// Note that the channel's ID changes during this call!
+	pub fn accept_channel_v2<ES: Deref>(
+		&mut self, msg: &msgs::AcceptChannelV2, default_limits: &ChannelHandshakeLimits,
+		their_features: &InitFeatures, entropy_source: &ES, holder_node_id: PublicKey,
+	) -> Result<InteractiveTxMessageSend, ChannelError> where ES::Target: EntropySource {
+		if msg.funding_satoshis >= TOTAL_BITCOIN_SUPPLY_SATOSHIS {
+			return Err(ChannelError::Close(format!("Funding must be smaller than the total bitcoin supply. It was {}", msg.funding_satoshis)));
+		}
+		let channel_value_satoshis = self.dual_funding_context.our_funding_satoshis + msg.funding_satoshis;
+		if !their_features.supports_wumbo() && channel_value_satoshis > MAX_FUNDING_SATOSHIS_NO_WUMBO {
+			return Err(ChannelError::Close(format!("Channel value must not exceed {}, it was {}", MAX_FUNDING_SATOSHIS_NO_WUMBO, channel_value_satoshis)));
+		}
+
+		// The reserves of dual-funded channels depend on the total channel value, which we only
+		// learn now.
+		self.context.channel_value_satoshis = channel_value_satoshis;
+		self.context.holder_selected_channel_reserve_satoshis = get_v2_channel_reserve_satoshis(
+			channel_value_satoshis, MIN_CHAN_DUST_LIMIT_SATOSHIS);
+		#[cfg(debug_assertions)]
+		{
+			let balances = (self.context.value_to_self_msat, msg.funding_satoshis * 1000);
+			*self.context.holder_max_commitment_tx_output.lock().unwrap() = balances;
+			*self.context.counterparty_max_commitment_tx_output.lock().unwrap() = balances;
+		}
+
+		self.context.do_accept_channel_checks(default_limits, their_features, &msgs::AcceptChannel {
+			temporary_channel_id: msg.temporary_channel_id,
+			dust_limit_satoshis: msg.dust_limit_satoshis,
+			max_htlc_value_in_flight_msat: msg.max_htlc_value_in_flight_msat,
+			channel_reserve_satoshis: get_v2_channel_reserve_satoshis(channel_value_satoshis, msg.dust_limit_satoshis),
+			htlc_minimum_msat: msg.htlc_minimum_msat,
+			minimum_depth: msg.minimum_depth,
+			to_self_delay: msg.to_self_delay,
+			max_accepted_htlcs: msg.max_accepted_htlcs,
+			funding_pubkey: msg.funding_pubkey,
+			revocation_basepoint: msg.revocation_basepoint,
+			payment_point: msg.payment_basepoint,
+			delayed_payment_basepoint: msg.delayed_payment_basepoint,
+			htlc_basepoint: msg.htlc_basepoint,
+			first_per_commitment_point: msg.first_per_commitment_point,
+			shutdown_scriptpubkey: msg.shutdown_scriptpubkey.clone(),
+			channel_type: msg.channel_type.clone(),
+			#[cfg(taproot)]
+			next_local_nonce: None,
+		})?;
+
+		self.dual_funding_context.their_funding_satoshis = msg.funding_satoshis;
+		self.context.channel_id = ChannelId::v2_from_revocation_basepoints(
+			&self.context.get_holder_pubkeys().revocation_basepoint, &self.context.get_counterparty_pubkeys().revocation_basepoint);
+
+		let mut pending_splice = self.context.pending_splice.take().expect("Outbound dual-funded channels start with a pending negotiation");
+		pending_splice.their_funding_contribution_satoshis = msg.funding_satoshis as i64;
+		let params = self.context.get_interactive_tx_parameters(holder_node_id, &pending_splice, channel_value_satoshis, false);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params,
+			mem::take(&mut pending_splice.our_funding_inputs), mem::take(&mut pending_splice.our_funding_outputs));
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		self.context.pending_splice = Some(pending_splice);
+		Ok(first_msg.expect("The initiator always sends the first message"))
+	}
+
+	/// Handles a `tx_complete` while negotiating the funding transaction, returning our response, if
+	/// any, and once the negotiation completes, our `commitment_signed` for the initial commitment
+	/// transaction.
+	pub fn tx_complete<L: Deref>(
+		&mut self, msg: &msgs::TxComplete, logger: &L,
+	) -> Result<(Option<InteractiveTxMessageSend>, Option<FundingTxNegotiated>), msgs::TxAbort> where L::Target: Logger {
+		self.context.dual_funding_tx_complete(msg, logger)
+	}
+
+	/// Handles our counterparty's `commitment_signed` for the initial commitment transaction,
+	/// promoting the channel to a [`Channel`] which awaits the exchange of `tx_signatures`.
+	pub fn commitment_signed<L: Deref>(
+		mut self, msg: &msgs::CommitmentSigned, best_block: BestBlock, signer_provider: &SP, logger: &L
+	) -> Result<(Channel<SP>, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>), (Self, ChannelError)>
+	where L::Target: Logger {
+		match self.context.dual_funding_commitment_signed(msg, best_block, signer_provider, logger) {
+			Ok(channel_monitor) => {
+				let mut channel = Channel { context: self.context };
+				// Our `tx_signatures` must wait until the monitor has been persisted.
+				channel.monitor_updating_paused(false, false, false, Vec::new(), Vec::new(), Vec::new());
+				channel.context.monitor_pending_tx_signatures = true;
+				Ok((channel, channel_monitor))
+			},
+			Err(err) => Err((self, err)),
+		}
+	}
+}
+
+/// A not-yet-funded inbound (from counterparty) channel using V2 channel establishment.
+pub(super) struct InboundV2Channel<SP: Deref> where SP::Target: SignerProvider {
+	pub context: ChannelContext<SP>,
+	pub unfunded_context: UnfundedChannelContext,
+	pub dual_funding_context: DualFundingChannelContext,
+}
+
+impl<SP: Deref> InboundV2Channel<SP> where SP::Target: SignerProvider {
+	/// Creates a new dual-funded channel from a remote side's request for one, contributing the
+	/// given funds to it.
+	/// Assumes chain_hash has already been checked and corresponds with what we expect!
+	pub fn new<ES: Deref, F: Deref, L: Deref>(
+		fee_estimator: &LowerBoundedFeeEstimator<F>, entropy_source: &ES, signer_provider: &SP,
+		holder_node_id: PublicKey, counterparty_node_id: PublicKey, our_supported_features: &ChannelTypeFeatures,
+		their_features: &InitFeatures, msg: &msgs::OpenChannelV2, contribution: FundingContribution,
+		user_id: u128, config: &UserConfig, current_chain_height: u32, logger: &L, is_0conf: bool,
+	) -> Result<InboundV2Channel<SP>, ChannelError>
+		where ES::Target: EntropySource,
+			  F::Target: FeeEstimator,
+			  L::Target: Logger,
+	{
+		if msg.funding_satoshis >= TOTAL_BITCOIN_SUPPLY_SATOSHIS || contribution.value_satoshis >= TOTAL_BITCOIN_SUPPLY_SATOSHIS {
+			return Err(ChannelError::Close(format!("Funding must be smaller than the total bitcoin supply. It was {}", msg.funding_satoshis)));
+		}
+		if msg.funding_feerate_sat_per_1000_weight < FEERATE_FLOOR_SATS_PER_KW {
+			return Err(ChannelError::Close(format!("Funding feerate of {} sat/kW is too low", msg.funding_feerate_sat_per_1000_weight)));
+		}
+		let channel_value_satoshis = msg.funding_satoshis + contribution.value_satoshis;
+
+		let mut chan = InboundV1Channel::new(fee_estimator, entropy_source, signer_provider, counterparty_node_id,
+			our_supported_features, their_features, &open_channel_v1_from_v2(msg, contribution.value_satoshis),
+			user_id, config, current_chain_height, logger, is_0conf)?;
+		chan.context.holder_selected_channel_reserve_satoshis = get_v2_channel_reserve_satoshis(
+			channel_value_satoshis, msg.dust_limit_satoshis);
+		chan.context.channel_id = ChannelId::v2_from_revocation_basepoints(
+			&chan.context.get_holder_pubkeys().revocation_basepoint, &chan.context.get_counterparty_pubkeys().revocation_basepoint);
+		chan.context.dual_funding_feerate_sat_per_1000_weight = Some(msg.funding_feerate_sat_per_1000_weight);
+
+		let funding_script = chan.context.get_funding_redeemscript().to_v0_p2wsh();
+		let (our_funding_inputs, our_funding_outputs) = get_funding_inputs_and_change(contribution.value_satoshis,
+			contribution.inputs, contribution.change_script, false, false, &funding_script, msg.funding_feerate_sat_per_1000_weight)
+			.map_err(|err| ChannelError::Close(format!("Invalid funding contribution: {:?}", err)))?;
+		let mut pending_splice = PendingSplice {
+			is_initiator: false,
+			our_funding_contribution_satoshis: contribution.value_satoshis as i64,
+			their_funding_contribution_satoshis: msg.funding_satoshis as i64,
+			funding_feerate_per_kw: msg.funding_feerate_sat_per_1000_weight,
+			locktime: msg.locktime,
+			our_funding_inputs: Vec::new(),
+			our_funding_outputs: Vec::new(),
+			interactive_tx_constructor: None,
+		};
+		let params = chan.context.get_interactive_tx_parameters(holder_node_id, &pending_splice, channel_value_satoshis, false);
+		let (constructor, first_msg) = InteractiveTxConstructor::new(entropy_source, params, our_funding_inputs, our_funding_outputs);
+		debug_assert!(first_msg.is_none());
+		pending_splice.interactive_tx_constructor = Some(constructor);
+		chan.context.pending_splice = Some(pending_splice);
+
+		Ok(Self {
+			context: chan.context,
+			unfunded_context: chan.unfunded_context,
+			dual_funding_context: DualFundingChannelContext {
+				our_funding_satoshis: contribution.value_satoshis,
+				their_funding_satoshis: msg.funding_satoshis,
+				funding_tx_locktime: msg.locktime,
+				funding_feerate_sat_per_1000_weight: msg.funding_feerate_sat_per_1000_weight,
+			},
+		})
+	}
+
+	/// Generates the [`msgs::AcceptChannelV2`] message to send back to the counterparty node.
+	///
+	/// [`msgs::AcceptChannelV2`]: crate::ln::msgs::AcceptChannelV2
+	pub fn accept_inbound_dual_funded_channel(&self) -> msgs::AcceptChannelV2 {
+		if self.context.is_outbound() {
+			panic!("Tried to send accept_channel2 for an outbound channel?");
+		}
+		if !matches!(
+			self.context.channel_state, ChannelState::NegotiatingFunding(flags)
+			if flags == (NegotiatingFundingFlags::OUR_INIT_SENT | NegotiatingFundingFlags::THEIR_INIT_SENT)
+		) {
+			panic!("Tried to send accept_channel2 after channel had moved forward");
+		}
+		if self.context.cur_holder_commitment_transaction_number != INITIAL_COMMITMENT_NUMBER {
+			panic!("Tried to send an accept_channel2 for a channel that has already advanced");
+		}
+
+		let first_per_commitment_point = self.context.holder_signer.as_ref()
+			.get_per_commitment_point(self.context.cur_holder_commitment_transaction_number, &self.context.secp_ctx);
+		let second_per_commitment_point = self.context.holder_signer.as_ref()
+			.get_per_commitment_point(self.context.cur_holder_commitment_transaction_number - 1, &self.context.secp_ctx);
+		let keys = self.context.get_holder_pubkeys();
+
+		msgs::AcceptChannelV2 {
+			temporary_channel_id: self.context.temporary_channel_id.unwrap(),
+			funding_satoshis: self.dual_funding_context.our_funding_satoshis,
+			dust_limit_satoshis: self.context.holder_dust_limit_satoshis,
+			max_htlc_value_in_flight_msat: self.context.holder_max_htlc_value_in_flight_msat,
+			htlc_minimum_msat: self.context.holder_htlc_minimum_msat,
+			minimum_depth: self.context.minimum_depth.unwrap(),
+			to_self_delay: self.context.get_holder_selected_contest_delay(),
+			max_accepted_htlcs: self.context.holder_max_accepted_htlcs,
+			funding_pubkey: keys.funding_pubkey,
+			revocation_basepoint: keys.revocation_basepoint.to_public_key(),
+			payment_basepoint: keys.payment_point,
+			delayed_payment_basepoint: keys.delayed_payment_basepoint.to_public_key(),
+			htlc_basepoint: keys.htlc_basepoint.to_public_key(),
+			first_per_commitment_point,
+			second_per_commitment_point,
+			shutdown_scriptpubkey: Some(match &self.context.shutdown_scriptpubkey {
+				Some(script) => script.clone().into_inner(),
+				None => Builder::new().into_script(),
+			}),
+			channel_type: Some(self.context.channel_type.clone()),
+			require_confirmed_inputs: None,
+		}
+	}
+
+	/// Handles a `tx_complete` while negotiating the funding transaction, returning our response, if
+	/// any, and once the negotiation completes, our `commitment_signed` for the initial commitment
+	/// transaction.
+	pub fn tx_complete<L: Deref>(
+		&mut self, msg: &msgs::TxComplete, logger: &L,
+	) -> Result<(Option<InteractiveTxMessageSend>, Option<FundingTxNegotiated>), msgs::TxAbort> where L::Target: Logger {
+		self.context.dual_funding_tx_complete(msg, logger)
+	}
+
+	/// Handles our counterparty's `commitment_signed` for the initial commitment transaction,
+	/// promoting the channel to a [`Channel`] which awaits the exchange of `tx_signatures`.
+	pub fn commitment_signed<L: Deref>(
+		mut self, msg: &msgs::CommitmentSigned, best_block: BestBlock, signer_provider: &SP, logger: &L
+	) -> Result<(Channel<SP>, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>), (Self, ChannelError)>
+	where L::Target: Logger {
+		match self.context.dual_funding_commitment_signed(msg, best_block, signer_provider, logger) {
+			Ok(channel_monitor) => {
+				let mut channel = Channel { context: self.context };
+				// Our `tx_signatures` must wait until the monitor has been persisted.
+				channel.monitor_updating_paused(false, false, false, Vec::new(), Vec::new(), Vec::new());
+				channel.context.monitor_pending_tx_signatures = true;
+				Ok((channel, channel_monitor))
+			},
+			Err(err) => Err((self, err)),
+		}
+	}
+}
+
 const SERIALIZATION_VERSION: u8 = 3;
 const MIN_SERIALIZATION_VERSION: u8 = 3;
 
@@ -7478,6 +9707,13 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 			if matches!(channel_state, ChannelState::AwaitingChannelReady(_)|ChannelState::ChannelReady(_)) {
 				channel_state.set_peer_disconnected();
 			}
+			// Quiescence is terminated upon disconnection and thus never needs to be persisted.
+			if matches!(channel_state, ChannelState::ChannelReady(_)) {
+				channel_state.clear_awaiting_quiescence();
+				channel_state.clear_local_stfu_sent();
+				channel_state.clear_remote_stfu_sent();
+				channel_state.clear_quiescent();
+			}
 			channel_state.to_u32().write(writer)?;
 		}
 		self.context.channel_value_satoshis.write(writer)?;
@@ -7534,6 +9770,7 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 		let mut preimages: Vec<&Option<PaymentPreimage>> = vec![];
 		let mut pending_outbound_skimmed_fees: Vec<Option<u64>> = Vec::new();
 		let mut pending_outbound_blinding_points: Vec<Option<PublicKey>> = Vec::new();
+		let mut pending_outbound_endorsements: Vec<Option<bool>> = Vec::new();
 
 		(self.context.pending_outbound_htlcs.len() as u64).write(writer)?;
 		for htlc in self.context.pending_outbound_htlcs.iter() {
@@ -7574,10 +9811,12 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 			}
 			pending_outbound_skimmed_fees.push(htlc.skimmed_fee_msat);
 			pending_outbound_blinding_points.push(htlc.blinding_point);
+			pending_outbound_endorsements.push(htlc.endorsed);
 		}
 
 		let mut holding_cell_skimmed_fees: Vec<Option<u64>> = Vec::new();
 		let mut holding_cell_blinding_points: Vec<Option<PublicKey>> = Vec::new();
+		let mut holding_cell_endorsements: Vec<Option<bool>> = Vec::new();
 		// Vec of (htlc_id, failure_code, sha256_of_onion)
 		let mut malformed_htlcs: Vec<(u64, u16, [u8; 32])> = Vec::new();
 		(self.context.holding_cell_htlc_updates.len() as u64).write(writer)?;
@@ -7585,7 +9824,7 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 			match update {
 				&HTLCUpdateAwaitingACK::AddHTLC {
 					ref amount_msat, ref cltv_expiry, ref payment_hash, ref source, ref onion_routing_packet,
-					blinding_point, skimmed_fee_msat,
+					blinding_point, skimmed_fee_msat, endorsed,
 				} => {
 					0u8.write(writer)?;
 					amount_msat.write(writer)?;
@@ -7596,6 +9835,7 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 
 					holding_cell_skimmed_fees.push(skimmed_fee_msat);
 					holding_cell_blinding_points.push(blinding_point);
+					holding_cell_endorsements.push(endorsed);
 				},
 				&HTLCUpdateAwaitingACK::ClaimHTLC { ref payment_preimage, ref htlc_id } => {
 					1u8.write(writer)?;
@@ -7780,6 +10020,13 @@ impl<SP: Deref> Writeable for Channel<SP> where SP::Target: SignerProvider {
 			(39, pending_outbound_blinding_points, optional_vec),
 			(41, holding_cell_blinding_points, optional_vec),
 			(43, malformed_htlcs, optional_vec), // Added in 0.0.119
+			(44, self.context.original_funding_outpoint, option),
+			(45, pending_outbound_endorsements, optional_vec),
+			(46, self.context.pending_splice_funding, option),
+			(47, holding_cell_endorsements, optional_vec),
+			(48, self.context.dual_funding_feerate_sat_per_1000_weight, option),
+			(50, self.context.dual_funding_transaction, option),
+			(52, self.context.monitor_pending_splice_locked, option),
 		});
 
 		Ok(())
@@ -7892,6 +10139,7 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 				},
 				skimmed_fee_msat: None,
 				blinding_point: None,
+				endorsed: None,
 			});
 		}
 
@@ -7907,6 +10155,7 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 					onion_routing_packet: Readable::read(reader)?,
 					skimmed_fee_msat: None,
 					blinding_point: None,
+					endorsed: None,
 				},
 				1 => HTLCUpdateAwaitingACK::ClaimHTLC {
 					payment_preimage: Readable::read(reader)?,
@@ -8070,7 +10319,15 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 		let mut pending_outbound_blinding_points_opt: Option<Vec<Option<PublicKey>>> = None;
 		let mut holding_cell_blinding_points_opt: Option<Vec<Option<PublicKey>>> = None;
 
+		let mut pending_outbound_endorsements_opt: Option<Vec<Option<bool>>> = None;
+		let mut holding_cell_endorsements_opt: Option<Vec<Option<bool>>> = None;
+
 		let mut malformed_htlcs: Option<Vec<(u64, u16, [u8; 32])>> = None;
+		let mut original_funding_outpoint: Option<OutPoint> = None;
+		let mut pending_splice_funding: Option<SpliceFunding> = None;
+		let mut dual_funding_feerate_sat_per_1000_weight: Option<u32> = None;
+		let mut dual_funding_transaction: Option<Transaction> = None;
+		let mut monitor_pending_splice_locked: Option<Txid> = None;
 
 		read_tlv_fields!(reader, {
 			(0, announcement_sigs, option),
@@ -8101,6 +10358,13 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 			(39, pending_outbound_blinding_points_opt, optional_vec),
 			(41, holding_cell_blinding_points_opt, optional_vec),
 			(43, malformed_htlcs, optional_vec), // Added in 0.0.119
+			(44, original_funding_outpoint, option),
+			(45, pending_outbound_endorsements_opt, optional_vec),
+			(46, pending_splice_funding, option),
+			(47, holding_cell_endorsements_opt, optional_vec),
+			(48, dual_funding_feerate_sat_per_1000_weight, option),
+			(50, dual_funding_transaction, option),
+			(52, monitor_pending_splice_locked, option),
 		});
 
 		let (channel_keys_id, holder_signer) = if let Some(channel_keys_id) = channel_keys_id {
@@ -8194,6 +10458,24 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 			// We expect all blinding points to be consumed above
 			if iter.next().is_some() { return Err(DecodeError::InvalidValue) }
 		}
+		if let Some(endorsements) = pending_outbound_endorsements_opt {
+			let mut iter = endorsements.into_iter();
+			for htlc in pending_outbound_htlcs.iter_mut() {
+				htlc.endorsed = iter.next().ok_or(DecodeError::InvalidValue)?;
+			}
+			// We expect all endorsements to be consumed above
+			if iter.next().is_some() { return Err(DecodeError::InvalidValue) }
+		}
+		if let Some(endorsements) = holding_cell_endorsements_opt {
+			let mut iter = endorsements.into_iter();
+			for htlc in holding_cell_htlc_updates.iter_mut() {
+				if let HTLCUpdateAwaitingACK::AddHTLC { ref mut endorsed, .. } = htlc {
+					*endorsed = iter.next().ok_or(DecodeError::InvalidValue)?;
+				}
+			}
+			// We expect all endorsements to be consumed above
+			if iter.next().is_some() { return Err(DecodeError::InvalidValue) }
+		}
 
 		if let Some(malformed_htlcs) = malformed_htlcs {
 			for (malformed_htlc_id, failure_code, sha256_of_onion) in malformed_htlcs {
@@ -8250,6 +10532,8 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 				monitor_pending_channel_ready,
 				monitor_pending_revoke_and_ack,
 				monitor_pending_commitment_signed,
+				monitor_pending_tx_signatures: false,
+				monitor_pending_splice_locked,
 				monitor_pending_forwards,
 				monitor_pending_failures,
 				monitor_pending_finalized_fulfills: monitor_pending_finalized_fulfills.unwrap(),
@@ -8317,6 +10601,7 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 
 				workaround_lnd_bug_4006: None,
 				sent_message_awaiting_response: None,
+				is_holder_quiescence_initiator: None,
 
 				latest_inbound_scid_alias,
 				// Later in the ChannelManager deserialization phase we scan for channels and assign scid aliases if its missing
@@ -8332,6 +10617,12 @@ impl<'a, 'b, 'c, ES: Deref, SP: Deref> ReadableArgs<(&'a ES, &'b SP, u32, &'c Ch
 				channel_keys_id,
 
 				blocked_monitor_updates: blocked_monitor_updates.unwrap(),
+
+				original_funding_outpoint,
+				pending_splice: None,
+				pending_splice_funding,
+				dual_funding_feerate_sat_per_1000_weight,
+				dual_funding_transaction,
 			}
 		})
 	}
@@ -8547,13 +10838,14 @@ mod tests {
 			cltv_expiry: 200000000,
 			state: OutboundHTLCState::Committed,
 			source: HTLCSource::OutboundRoute {
-				path: Path { hops: Vec::new(), blinded_tail: None },
+				path: Path { hops: Vec::new(), blinded_tail: None, trampoline_hops: vec![] },
 				session_priv: SecretKey::from_slice(&<Vec<u8>>::from_hex("0fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap()[..]).unwrap(),
 				first_hop_htlc_msat: 548,
 				payment_id: PaymentId([42; 32]),
 			},
 			skimmed_fee_msat: None,
 			blinding_point: None,
+			endorsed: None,
 		});
 
 		// Make sure when Node A calculates their local commitment transaction, none of the HTLCs pass
@@ -8883,8 +11175,8 @@ mod tests {
 
 	#[test]
 	fn blinding_point_skimmed_fee_malformed_ser() {
-		// Ensure that channel blinding points, skimmed fees, and malformed HTLCs are (de)serialized
-		// properly.
+		// Ensure that channel blinding points, skimmed fees, endorsements, and malformed HTLCs are
+		// (de)serialized properly.
 		let feeest = LowerBoundedFeeEstimator::new(&TestFeeEstimator{fee_est: 15000});
 		let secp_ctx = Secp256k1::new();
 		let seed = [42; 32];
@@ -8904,7 +11196,8 @@ mod tests {
 					node_features: NodeFeatures::empty(), short_channel_id: 0, fee_msat: 0,
 					cltv_expiry_delta: 0, maybe_announced_channel: false,
 				}],
-				blinded_tail: None
+				blinded_tail: None,
+				trampoline_hops: vec![]
 			},
 			session_priv: test_utils::privkey(42),
 			first_hop_htlc_msat: 0,
@@ -8919,6 +11212,7 @@ mod tests {
 			source: dummy_htlc_source.clone(),
 			skimmed_fee_msat: None,
 			blinding_point: None,
+			endorsed: None,
 		};
 		let mut pending_outbound_htlcs = vec![dummy_outbound_output.clone(); 10];
 		for (idx, htlc) in pending_outbound_htlcs.iter_mut().enumerate() {
@@ -8928,6 +11222,9 @@ mod tests {
 			if idx % 3 == 0 {
 				htlc.skimmed_fee_msat = Some(1);
 			}
+			if idx % 4 != 3 {
+				htlc.endorsed = Some(idx % 4 == 0);
+			}
 		}
 		chan.context.pending_outbound_htlcs = pending_outbound_htlcs.clone();
 
@@ -8944,6 +11241,7 @@ mod tests {
 			},
 			skimmed_fee_msat: None,
 			blinding_point: None,
+			endorsed: None,
 		};
 		let dummy_holding_cell_claim_htlc = HTLCUpdateAwaitingACK::ClaimHTLC {
 			payment_preimage: PaymentPreimage([42; 32]),
@@ -8964,10 +11262,11 @@ mod tests {
 			} else if i % 5 == 2 {
 				let mut dummy_add = dummy_holding_cell_add_htlc.clone();
 				if let HTLCUpdateAwaitingACK::AddHTLC {
-					ref mut blinding_point, ref mut skimmed_fee_msat, ..
+					ref mut blinding_point, ref mut skimmed_fee_msat, ref mut endorsed, ..
 				} = &mut dummy_add {
 					*blinding_point = Some(test_utils::pubkey(42 + i));
 					*skimmed_fee_msat = Some(42);
+					*endorsed = Some(true);
 				} else { panic!() }
 				holding_cell_htlc_updates.push(dummy_add);
 			} else if i % 5 == 3 {
@@ -9238,6 +11537,7 @@ mod tests {
 				source: HTLCSource::dummy(),
 				skimmed_fee_msat: None,
 				blinding_point: None,
+				endorsed: None,
 			};
 			out.payment_hash.0 = Sha256::hash(&<Vec<u8>>::from_hex("0202020202020202020202020202020202020202020202020202020202020202").unwrap()).to_byte_array();
 			out
@@ -9252,6 +11552,7 @@ mod tests {
 				source: HTLCSource::dummy(),
 				skimmed_fee_msat: None,
 				blinding_point: None,
+				endorsed: None,
 			};
 			out.payment_hash.0 = Sha256::hash(&<Vec<u8>>::from_hex("0303030303030303030303030303030303030303030303030303030303030303").unwrap()).to_byte_array();
 			out
@@ -9664,6 +11965,7 @@ mod tests {
 				source: HTLCSource::dummy(),
 				skimmed_fee_msat: None,
 				blinding_point: None,
+				endorsed: None,
 			};
 			out.payment_hash.0 = Sha256::hash(&<Vec<u8>>::from_hex("0505050505050505050505050505050505050505050505050505050505050505").unwrap()).to_byte_array();
 			out
@@ -9678,6 +11980,7 @@ mod tests {
 				source: HTLCSource::dummy(),
 				skimmed_fee_msat: None,
 				blinding_point: None,
+				endorsed: None,
 			};
 			out.payment_hash.0 = Sha256::hash(&<Vec<u8>>::from_hex("0505050505050505050505050505050505050505050505050505050505050505").unwrap()).to_byte_array();
 			out
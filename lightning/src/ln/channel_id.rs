@@ -9,10 +9,14 @@
 
 //! ChannelId definition.
 
+use crate::ln::channel_keys::RevocationBasepoint;
 use crate::ln::msgs::DecodeError;
 use crate::sign::EntropySource;
 use crate::util::ser::{Readable, Writeable, Writer};
 
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::hashes::sha256::Hash as Sha256;
+
 use crate::io;
 use core::fmt;
 use core::ops::Deref;
@@ -20,10 +24,12 @@ use core::ops::Deref;
 /// A unique 32-byte identifier for a channel.
 /// Depending on how the ID is generated, several varieties are distinguished
 /// (but all are stored as 32 bytes):
-///   _v1_ and _temporary_.
+///   _v1_, _v2_ and _temporary_.
 /// A _v1_ channel ID is generated based on funding tx outpoint (txid & index).
-/// A _temporary_ ID is generated randomly.
-/// (Later revocation-point-based _v2_ is a possibility.)
+/// A _v2_ channel ID is generated based on the revocation basepoints of both parties, as used
+/// by dual-funded channels.
+/// A _temporary_ ID is generated randomly, or for dual-funded channels, based on our
+/// revocation basepoint only.
 /// The variety (context) is not stored, it is relevant only at creation.
 ///
 /// This is not exported to bindings users as we just use [u8; 32] directly.
@@ -46,6 +52,34 @@ impl ChannelId {
 		Self(entropy_source.get_secure_random_bytes())
 	}
 
+	/// Create _v2_ channel ID by concatenating the holder revocation basepoint with the counterparty
+	/// revocation basepoint in ascending order and hashing the result.
+	pub fn v2_from_revocation_basepoints(
+		ours: &RevocationBasepoint, theirs: &RevocationBasepoint,
+	) -> Self {
+		let our_revocation_point_bytes = ours.to_public_key().serialize();
+		let their_revocation_point_bytes = theirs.to_public_key().serialize();
+		let (lesser_revocation_basepoint, greater_revocation_basepoint) =
+			if our_revocation_point_bytes < their_revocation_point_bytes {
+				(our_revocation_point_bytes, their_revocation_point_bytes)
+			} else {
+				(their_revocation_point_bytes, our_revocation_point_bytes)
+			};
+		let mut engine = Sha256::engine();
+		engine.input(&lesser_revocation_basepoint);
+		engine.input(&greater_revocation_basepoint);
+		Self(Sha256::from_engine(engine).to_byte_array())
+	}
+
+	/// Create temporary _v2_ channel ID by concatenating a zeroed out basepoint with the holder
+	/// revocation basepoint and hashing the result.
+	pub fn temporary_v2_from_revocation_basepoint(our_revocation_basepoint: &RevocationBasepoint) -> Self {
+		let mut engine = Sha256::engine();
+		engine.input(&[0u8; 33]);
+		engine.input(&our_revocation_basepoint.to_public_key().serialize());
+		Self(Sha256::from_engine(engine).to_byte_array())
+	}
+
 	/// Generic constructor; create a new channel ID from the provided data.
 	/// Use a more specific `*_from_*` constructor when possible.
 	pub fn from_bytes(data: [u8; 32]) -> Self {
@@ -84,9 +118,14 @@ impl fmt::Display for ChannelId {
 
 #[cfg(test)]
 mod tests {
+	use bitcoin::hashes::{Hash, HashEngine};
+	use bitcoin::hashes::sha256::Hash as Sha256;
+	use bitcoin::secp256k1::PublicKey;
+	use bitcoin::hashes::hex::FromHex;
 	use hex::DisplayHex;
 
 	use crate::ln::ChannelId;
+	use crate::ln::channel_keys::RevocationBasepoint;
 	use crate::util::ser::{Readable, Writeable};
 	use crate::util::test_utils;
 	use crate::prelude::*;
@@ -132,4 +171,31 @@ mod tests {
 		let channel_id = ChannelId::v1_from_funding_txid(&[2; 32], 1);
 		assert_eq!(format!("{}", &channel_id), "0202020202020202020202020202020202020202020202020202020202020203");
 	}
+
+	#[test]
+	fn test_channel_id_v2_from_basepoints() {
+		let ours = RevocationBasepoint(PublicKey::from_slice(&<Vec<u8>>::from_hex("0324653eac434488002cc06bbfb7f10fe18991e35f9fe4302dbea6d2353dc0ab1c").unwrap()[..]).unwrap());
+		let theirs = RevocationBasepoint(PublicKey::from_slice(&<Vec<u8>>::from_hex("02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619").unwrap()[..]).unwrap());
+
+		// The channel ID must not depend on which party computes it.
+		let channel_id = ChannelId::v2_from_revocation_basepoints(&ours, &theirs);
+		assert_eq!(channel_id, ChannelId::v2_from_revocation_basepoints(&theirs, &ours));
+
+		let mut engine = Sha256::engine();
+		engine.input(&theirs.to_public_key().serialize());
+		engine.input(&ours.to_public_key().serialize());
+		assert_eq!(channel_id.0, Sha256::from_engine(engine).to_byte_array());
+	}
+
+	#[test]
+	fn test_temporary_channel_id_v2_from_basepoint() {
+		let ours = RevocationBasepoint(PublicKey::from_slice(&<Vec<u8>>::from_hex("02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619").unwrap()[..]).unwrap());
+		let temporary_channel_id = ChannelId::temporary_v2_from_revocation_basepoint(&ours);
+
+		let mut engine = Sha256::engine();
+		engine.input(&[0; 33]);
+		engine.input(&ours.to_public_key().serialize());
+		assert_eq!(temporary_channel_id.0, Sha256::from_engine(engine).to_byte_array());
+		assert_ne!(temporary_channel_id, ChannelId::v2_from_revocation_basepoints(&ours, &ours));
+	}
 }
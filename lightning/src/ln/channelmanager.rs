@@ -18,7 +18,8 @@
 //! imply it needs to fail HTLCs/payments/channels it manages).
 
 use bitcoin::blockdata::block::Header;
-use bitcoin::blockdata::transaction::Transaction;
+use bitcoin::blockdata::script::ScriptBuf;
+use bitcoin::blockdata::transaction::{Transaction, TxIn, TxOut};
 use bitcoin::blockdata::constants::ChainHash;
 use bitcoin::key::constants::SECRET_KEY_SIZE;
 use bitcoin::network::constants::Network;
@@ -39,17 +40,20 @@ use crate::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, Fee
 use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, WithChannelMonitor, ChannelMonitorUpdateStep, HTLC_FAIL_BACK_BUFFER, CLTV_CLAIM_BUFFER, LATENCY_GRACE_PERIOD_BLOCKS, ANTI_REORG_DELAY, MonitorEvent, CLOSED_CHANNEL_UPDATE_ID};
 use crate::chain::transaction::{OutPoint, TransactionData};
 use crate::events;
-use crate::events::{Event, EventHandler, EventsProvider, MessageSendEvent, MessageSendEventsProvider, ClosureReason, HTLCDestination, PaymentFailureReason};
+use crate::events::{Event, EventHandler, EventsProvider, MessageSendEvent, MessageSendEventsProvider, ClosureReason, HTLCDestination, PaymentFailureReason, InboundChannelFunds};
 // Since this struct is returned in `list_channels` methods, expose it here in case users want to
 // construct one themselves.
 use crate::ln::{inbound_payment, ChannelId, PaymentHash, PaymentPreimage, PaymentSecret};
-use crate::ln::channel::{self, Channel, ChannelPhase, ChannelContext, ChannelError, ChannelUpdateStatus, ShutdownResult, UnfundedChannelContext, UpdateFulfillCommitFetch, OutboundV1Channel, InboundV1Channel, WithChannelContext};
+use crate::ln::channel::{self, AbortedSplice, Channel, ChannelPhase, ChannelContext, ChannelError, ChannelUpdateStatus, FundingTxNegotiated, ShutdownResult, UnfundedChannelContext, UpdateFulfillCommitFetch, OutboundV1Channel, InboundV1Channel, OutboundV2Channel, InboundV2Channel, WithChannelContext};
 use crate::ln::features::{Bolt12InvoiceFeatures, ChannelFeatures, ChannelTypeFeatures, InitFeatures, NodeFeatures};
 #[cfg(any(feature = "_test_utils", test))]
 use crate::ln::features::Bolt11InvoiceFeatures;
-use crate::routing::router::{BlindedTail, InFlightHtlcs, Path, Payee, PaymentParameters, Route, RouteParameters, Router};
-use crate::ln::onion_payment::{check_incoming_htlc_cltv, create_recv_pending_htlc_info, create_fwd_pending_htlc_info, decode_incoming_update_add_htlc_onion, InboundHTLCErr, NextPacketDetails};
+use crate::routing::router::{BlindedTail, InFlightHtlcs, Path, Payee, PaymentParameters, Route, RouteParameters, Router, TrampolineHop};
+use crate::ln::onion_payment::{check_incoming_htlc_cltv, create_recv_pending_htlc_info, create_fwd_pending_htlc_info, create_trampoline_fwd_pending_htlc_info, decode_incoming_update_add_htlc_onion, trampoline_fee_msat, trampoline_fee_or_expiry_insufficient_data, InboundHTLCErr, NextPacketDetails};
+use crate::ln::interactivetxs::InteractiveTxMessageSend;
 use crate::ln::msgs;
+use crate::ln::our_peer_storage::{OurPeerStorage, PeerStorageMonitorHolder};
+use crate::ln::reputation::HTLCReputationTracker;
 use crate::ln::onion_utils;
 use crate::ln::onion_utils::{HTLCFailReason, INVALID_ONION_BLINDING};
 use crate::ln::msgs::{ChannelMessageHandler, DecodeError, LightningError};
@@ -60,9 +64,11 @@ use crate::ln::wire::Encode;
 use crate::offers::invoice::{BlindedPayInfo, Bolt12Invoice, DEFAULT_RELATIVE_EXPIRY, DerivedSigningPubkey, InvoiceBuilder};
 use crate::offers::invoice_error::InvoiceError;
 use crate::offers::merkle::SignError;
-use crate::offers::offer::{DerivedMetadata, Offer, OfferBuilder};
+use crate::offers::offer::{Amount, CurrencyCode, CurrencyConversion, DerivedMetadata, Offer, OfferBuilder};
 use crate::offers::parse::Bolt12SemanticError;
 use crate::offers::refund::{Refund, RefundBuilder};
+use crate::offers::static_invoice::StaticInvoice;
+use crate::onion_message::async_payments::{AsyncPaymentsMessage, AsyncPaymentsMessageHandler, HeldHtlcAvailable, ReleaseHeldHtlc};
 use crate::onion_message::messenger::{Destination, MessageRouter, PendingOnionMessage, new_pending_onion_message};
 use crate::onion_message::offers::{OffersMessage, OffersMessageHandler};
 use crate::sign::{EntropySource, NodeSigner, Recipient, SignerProvider};
@@ -71,7 +77,7 @@ use crate::util::config::{UserConfig, ChannelConfig, ChannelConfigUpdate};
 use crate::util::wakers::{Future, Notifier};
 use crate::util::scid_utils::fake_scid;
 use crate::util::string::UntrustedString;
-use crate::util::ser::{BigSize, FixedLengthReader, Readable, ReadableArgs, MaybeReadable, Writeable, Writer, VecWriter};
+use crate::util::ser::{BigSize, FixedLengthReader, LengthReadable, Readable, ReadableArgs, MaybeReadable, Writeable, Writer, VecWriter};
 use crate::util::logger::{Level, Logger, WithContext};
 use crate::util::errors::APIError;
 #[cfg(not(c_bindings))]
@@ -80,6 +86,8 @@ use {
 	crate::routing::gossip::NetworkGraph,
 	crate::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringFeeParameters},
 	crate::sign::KeysManager,
+	crate::util::metrics::IgnoringMetricsRecorder,
+	crate::offers::offer::NoCurrencyConversion,
 };
 
 use alloc::collections::{btree_map, BTreeMap};
@@ -132,6 +140,29 @@ pub enum PendingHTLCRouting {
 		short_channel_id: u64, // This should be NonZero<u64> eventually when we bump MSRV
 		/// Set if this HTLC is being forwarded within a blinded path.
 		blinded: Option<BlindedForward>,
+		/// Set to the shared secret of the trampoline onion we received if this HTLC is the first
+		/// leg of a trampoline forward we routed ourselves. Failures from downstream are encrypted to
+		/// us, so we must fail back with a trampoline error of our own, encrypted with this secret.
+		incoming_trampoline_shared_secret: Option<[u8; 32]>,
+		/// Set if the sender of an async payment asked us to hold this HTLC until the often-offline
+		/// recipient releases it with a [`ReleaseHeldHtlc`] message carrying this secret.
+		hold_htlc: Option<[u8; 32]>,
+	},
+	/// The onion indicates that we should act as a trampoline node and find a route on to the
+	/// next trampoline node (or the recipient), which will receive the wrapped trampoline onion.
+	///
+	/// These are turned into [`PendingHTLCRouting::Forward`]s once a route has been found in
+	/// [`ChannelManager::process_pending_htlc_forwards`].
+	TrampolineForward {
+		/// The trampoline onion which should be included in the outer onion for the next
+		/// trampoline node.
+		onion_packet: msgs::TrampolineOnionPacket,
+		/// The node id of the next trampoline node (or the recipient) we were instructed to reach.
+		node_id: PublicKey,
+		/// CLTV expiry of the received HTLC, used to bound the CLTV of the route we find.
+		incoming_cltv_expiry: u32,
+		/// Shared secret of the trampoline onion we received, used to encrypt any failures.
+		incoming_trampoline_shared_secret: [u8; 32],
 	},
 	/// The onion indicates that this is a payment for an invoice (supposedly) generated by us.
 	///
@@ -216,6 +247,14 @@ impl PendingHTLCRouting {
 			_ => None,
 		}
 	}
+
+	// Used to encrypt failures with the trampoline onion if we routed this HTLC as a trampoline node.
+	fn trampoline_shared_secret(&self) -> Option<[u8; 32]> {
+		match self {
+			Self::Forward { incoming_trampoline_shared_secret, .. } => *incoming_trampoline_shared_secret,
+			_ => None,
+		}
+	}
 }
 
 /// Information about an incoming HTLC, including the [`PendingHTLCRouting`] describing where it
@@ -261,6 +300,10 @@ pub struct PendingHTLCInfo {
 	/// This is used to allow LSPs to take fees as a part of payments, without the sender having to
 	/// shoulder them.
 	pub skimmed_fee_msat: Option<u64>,
+	/// Whether our counterparty endorsed the HTLC we received, see [`msgs::UpdateAddHTLC::endorsed`].
+	///
+	/// Only tracked for HTLCs we forward, this is always `false` for received payments.
+	pub incoming_endorsed: bool,
 }
 
 #[derive(Clone)] // See Channel::revoke_and_ack for why, tl;dr: Rust bug
@@ -328,6 +371,7 @@ pub(crate) struct HTLCPreviousHopData {
 	incoming_packet_shared_secret: [u8; 32],
 	phantom_shared_secret: Option<[u8; 32]>,
 	blinded_failure: Option<BlindedFailure>,
+	trampoline_shared_secret: Option<[u8; 32]>,
 
 	// This field is consumed by `claim_funds_from_hop()` when updating a force-closed backwards
 	// channel with a preimage provided by the forward channel.
@@ -365,14 +409,16 @@ struct ClaimableHTLC {
 	counterparty_skimmed_fee_msat: Option<u64>,
 }
 
-impl From<&ClaimableHTLC> for events::ClaimedHTLC {
-	fn from(val: &ClaimableHTLC) -> Self {
+impl ClaimableHTLC {
+	/// Gets the [`events::ClaimedHTLC`] for this HTLC, which was received over the channel with the
+	/// given `channel_id`.
+	fn to_claimed_htlc(&self, channel_id: ChannelId) -> events::ClaimedHTLC {
 		events::ClaimedHTLC {
-			channel_id: val.prev_hop.outpoint.to_channel_id(),
-			user_channel_id: val.prev_hop.user_channel_id.unwrap_or(0),
-			cltv_expiry: val.cltv_expiry,
-			value_msat: val.value,
-			counterparty_skimmed_fee_msat: val.counterparty_skimmed_fee_msat.unwrap_or(0),
+			channel_id,
+			user_channel_id: self.prev_hop.user_channel_id.unwrap_or(0),
+			cltv_expiry: self.cltv_expiry,
+			value_msat: self.value,
+			counterparty_skimmed_fee_msat: self.counterparty_skimmed_fee_msat.unwrap_or(0),
 		}
 	}
 }
@@ -493,7 +539,7 @@ impl HTLCSource {
 	#[cfg(test)]
 	pub fn dummy() -> Self {
 		HTLCSource::OutboundRoute {
-			path: Path { hops: Vec::new(), blinded_tail: None },
+			path: Path { hops: Vec::new(), blinded_tail: None, trampoline_hops: vec![] },
 			session_priv: SecretKey::from_slice(&[1; 32]).unwrap(),
 			first_hop_htlc_msat: 0,
 			payment_id: PaymentId([2; 32]),
@@ -612,6 +658,15 @@ impl MsgHandleErrInternal {
 						log_level: Level::Warn,
 					},
 				},
+				ChannelError::WarnAndDisconnect(msg) => LightningError {
+					err: msg.clone(),
+					action: msgs::ErrorAction::DisconnectPeerWithWarning {
+						msg: msgs::WarningMessage {
+							channel_id,
+							data: msg
+						},
+					},
+				},
 				ChannelError::Ignore(msg) => LightningError {
 					err: msg,
 					action: msgs::ErrorAction::IgnoreError,
@@ -730,6 +785,14 @@ enum BackgroundEvent {
 		counterparty_node_id: PublicKey,
 		channel_id: ChannelId,
 	},
+	/// A splice has been locked by both parties while we couldn't apply a [`ChannelMonitorUpdate`],
+	/// so we still have to let the channel's [`ChannelMonitor`] know.
+	///
+	/// This is regenerated on startup for any channels which have yet to do so.
+	SpliceLocked {
+		counterparty_node_id: PublicKey,
+		channel_id: ChannelId,
+	},
 }
 
 #[derive(Debug)]
@@ -820,9 +883,9 @@ pub(crate) enum RAAMonitorUpdateBlockingAction {
 }
 
 impl RAAMonitorUpdateBlockingAction {
-	fn from_prev_hop_data(prev_hop: &HTLCPreviousHopData) -> Self {
+	fn from_prev_hop_data(prev_hop: &HTLCPreviousHopData, prev_channel_id: ChannelId) -> Self {
 		Self::ForwardedPaymentInboundClaim {
-			channel_id: prev_hop.outpoint.to_channel_id(),
+			channel_id: prev_channel_id,
 			htlc_id: prev_hop.htlc_id,
 		}
 	}
@@ -882,6 +945,9 @@ pub(super) struct PeerState<SP: Deref> where SP::Target: SignerProvider {
 	/// [`ChannelMessageHandler::peer_connected`] and no corresponding
 	/// [`ChannelMessageHandler::peer_disconnected`].
 	is_connected: bool,
+	/// The latest backup blob the peer asked us to store via [`msgs::PeerStorage`], which we
+	/// return to it via [`msgs::PeerStorageRetrieval`] whenever it reconnects.
+	peer_storage: Vec<u8>,
 }
 
 impl <SP: Deref> PeerState<SP> where SP::Target: SignerProvider {
@@ -892,7 +958,7 @@ impl <SP: Deref> PeerState<SP> where SP::Target: SignerProvider {
 		if require_disconnected && self.is_connected {
 			return false
 		}
-		self.channel_by_id.iter().filter(|(_, phase)| matches!(phase, ChannelPhase::Funded(_))).count() == 0
+		!self.has_funded_channel()
 			&& self.monitor_update_blocked_actions.is_empty()
 			&& self.in_flight_monitor_updates.is_empty()
 	}
@@ -907,13 +973,48 @@ impl <SP: Deref> PeerState<SP> where SP::Target: SignerProvider {
 		self.channel_by_id.contains_key(channel_id) ||
 			self.inbound_channel_request_by_id.contains_key(channel_id)
 	}
+
+	// Returns a bool indicating if we have at least one funded channel with this peer.
+	fn has_funded_channel(&self) -> bool {
+		self.channel_by_id.iter().any(|(_, phase)| matches!(phase, ChannelPhase::Funded(_)))
+	}
+
+	// Returns a bool indicating if we should hand our backup to this peer, i.e. it is connected,
+	// supports `option_provide_storage` and we have a funded channel with it.
+	fn wants_our_peer_storage(&self) -> bool {
+		self.is_connected && self.latest_features.supports_provide_storage() && self.has_funded_channel()
+	}
+}
+
+/// The message a peer requested a new channel with.
+pub(super) enum OpenChannelMessage {
+	/// An `open_channel` message, using V1 channel establishment.
+	V1(msgs::OpenChannel),
+	/// An `open_channel2` message, using V2 channel establishment.
+	V2(msgs::OpenChannelV2),
+}
+
+impl OpenChannelMessage {
+	fn chain_hash(&self) -> ChainHash {
+		match self {
+			OpenChannelMessage::V1(msg) => msg.chain_hash,
+			OpenChannelMessage::V2(msg) => msg.chain_hash,
+		}
+	}
+
+	fn temporary_channel_id(&self) -> ChannelId {
+		match self {
+			OpenChannelMessage::V1(msg) => msg.temporary_channel_id,
+			OpenChannelMessage::V2(msg) => msg.temporary_channel_id,
+		}
+	}
 }
 
 /// A not-yet-accepted inbound (from counterparty) channel. Once
 /// accepted, the parameters will be used to construct a channel.
 pub(super) struct InboundChannelRequest {
 	/// The original OpenChannel message.
-	pub open_channel_msg: msgs::OpenChannel,
+	pub open_channel_msg: OpenChannelMessage,
 	/// The number of ticks remaining before the request expires.
 	pub ticks_remaining: i32,
 }
@@ -967,8 +1068,10 @@ pub type SimpleArcChannelManager<M, T, F, L> = ChannelManager<
 		Arc<RwLock<ProbabilisticScorer<Arc<NetworkGraph<Arc<L>>>, Arc<L>>>>,
 		ProbabilisticScoringFeeParameters,
 		ProbabilisticScorer<Arc<NetworkGraph<Arc<L>>>, Arc<L>>,
+		IgnoringMetricsRecorder,
 	>>,
-	Arc<L>
+	Arc<L>,
+	NoCurrencyConversion,
 >;
 
 /// [`SimpleRefChannelManager`] is a type alias for a ChannelManager reference, and is the reference
@@ -996,9 +1099,11 @@ pub type SimpleRefChannelManager<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, M, T, F, L> =
 			&'g L,
 			&'h RwLock<ProbabilisticScorer<&'f NetworkGraph<&'g L>, &'g L>>,
 			ProbabilisticScoringFeeParameters,
-			ProbabilisticScorer<&'f NetworkGraph<&'g L>, &'g L>
+			ProbabilisticScorer<&'f NetworkGraph<&'g L>, &'g L>,
+			IgnoringMetricsRecorder
 		>,
-		&'g L
+		&'g L,
+		NoCurrencyConversion
 	>;
 
 /// A trivial trait which describes any [`ChannelManager`].
@@ -1040,12 +1145,16 @@ pub trait AChannelManager {
 	type Logger: Logger + ?Sized;
 	/// A type that may be dereferenced to [`Self::Logger`].
 	type L: Deref<Target = Self::Logger>;
+	/// A type implementing [`CurrencyConversion`].
+	type CurrencyConversion: CurrencyConversion + ?Sized;
+	/// A type that may be dereferenced to [`Self::CurrencyConversion`].
+	type CC: Deref<Target = Self::CurrencyConversion>;
 	/// Returns a reference to the actual [`ChannelManager`] object.
-	fn get_cm(&self) -> &ChannelManager<Self::M, Self::T, Self::ES, Self::NS, Self::SP, Self::F, Self::R, Self::L>;
+	fn get_cm(&self) -> &ChannelManager<Self::M, Self::T, Self::ES, Self::NS, Self::SP, Self::F, Self::R, Self::L, Self::CC>;
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> AChannelManager
-for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> AChannelManager
+for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -1055,6 +1164,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	type Watch = M::Target;
 	type M = M;
@@ -1073,7 +1183,9 @@ where
 	type R = R;
 	type Logger = L::Target;
 	type L = L;
-	fn get_cm(&self) -> &ChannelManager<M, T, ES, NS, SP, F, R, L> { self }
+	type CurrencyConversion = CC::Target;
+	type CC = CC;
+	fn get_cm(&self) -> &ChannelManager<M, T, ES, NS, SP, F, R, L, CC> { self }
 }
 
 /// Manager which keeps track of a number of channels and sends messages to the appropriate
@@ -1142,6 +1254,14 @@ where
 //
 // `pending_offers_messages`
 //
+// `pending_async_payments_messages`
+//
+// `pending_held_htlcs`
+//
+// `static_invoices`
+//
+// `last_peer_storage_snapshot`
+//
 // `total_consistency_lock`
 //  |
 //  |__`forward_htlcs`
@@ -1150,6 +1270,8 @@ where
 //  |
 //  |__`per_peer_state`
 //      |
+//      |__`pending_peer_storage_persists`
+//      |
 //      |__`pending_inbound_payments`
 //          |
 //          |__`claimable_payments`
@@ -1160,17 +1282,21 @@ where
 //                  |
 //                  |__`outpoint_to_peer`
 //                  |
+//                  |__`outpoint_to_channel_id`
+//                  |
 //                  |__`short_to_chan_info`
 //                  |
 //                  |__`outbound_scid_aliases`
 //                  |
 //                  |__`best_block`
 //                  |
+//                  |__`htlc_reputation`
+//                  |
 //                  |__`pending_events`
 //                      |
 //                      |__`pending_background_events`
 //
-pub struct ChannelManager<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
+pub struct ChannelManager<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -1180,6 +1306,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	default_configuration: UserConfig,
 	chain_hash: ChainHash,
@@ -1237,6 +1364,11 @@ where
 	///
 	/// See `ChannelManager` struct-level documentation for lock order requirements.
 	pending_intercepted_htlcs: Mutex<HashMap<InterceptId, PendingAddHTLCInfo>>,
+	/// Storage for HTLCs the sender of an async payment asked us to hold until the often-offline
+	/// recipient releases them with a [`ReleaseHeldHtlc`] message, keyed by the secret it carries.
+	///
+	/// See `ChannelManager` struct-level documentation for lock order requirements.
+	pending_held_htlcs: Mutex<HashMap<[u8; 32], Vec<PendingAddHTLCInfo>>>,
 
 	/// The sets of payments which are claimable or currently being claimed. See
 	/// [`ClaimablePayments`]' individual field docs for more info.
@@ -1275,6 +1407,17 @@ where
 	#[cfg(test)]
 	pub(crate) outpoint_to_peer: Mutex<HashMap<OutPoint, PublicKey>>,
 
+	/// Channel funding outpoint -> `channel_id`, for every channel we have a [`ChannelMonitor`]
+	/// for.
+	///
+	/// Like `outpoint_to_peer`, this is used to find the channel a `MonitorEvent` or an HTLC's
+	/// previous hop refers to, as we only have access to the channel's funding outpoint there. See
+	/// `channel_id_from_funding_txo`. Unlike `outpoint_to_peer`, entries are not removed when a
+	/// channel is closed as its [`ChannelMonitor`] may still generate events for it.
+	///
+	/// See `ChannelManager` struct-level documentation for lock order requirements.
+	outpoint_to_channel_id: Mutex<HashMap<OutPoint, ChannelId>>,
+
 	/// SCIDs (and outbound SCID aliases) -> `counterparty_node_id`s and `channel_id`s.
 	///
 	/// Outbound SCID aliases are added here once the channel is available for normal use, with
@@ -1380,6 +1523,31 @@ where
 	needs_persist_flag: AtomicBool,
 
 	pending_offers_messages: Mutex<Vec<PendingOnionMessage<OffersMessage>>>,
+	pending_async_payments_messages: Mutex<Vec<PendingOnionMessage<AsyncPaymentsMessage>>>,
+
+	/// [`StaticInvoice`]s we serve on behalf of often-offline recipients, keyed by the signing
+	/// pubkey of the [`Offer`] they were built for. See [`ChannelManager::add_static_invoice`].
+	///
+	/// See `ChannelManager` struct-level documentation for lock order requirements.
+	static_invoices: Mutex<HashMap<PublicKey, StaticInvoice>>,
+
+	/// The [`ChannelMonitor`] summaries last handed to our peers via [`msgs::PeerStorage`], used to
+	/// only send a new backup when our channel state changed. Only used if
+	/// [`UserConfig::provide_peer_storage`] is set.
+	last_peer_storage_snapshot: Mutex<Vec<PeerStorageMonitorHolder>>,
+
+	/// The peers whose backup blob changed since it was last taken via
+	/// [`ChannelManager::take_pending_peer_storage_updates`] to be persisted.
+	///
+	/// See `ChannelManager` struct-level documentation for lock order requirements.
+	pending_peer_storage_persists: Mutex<HashSet<PublicKey>>,
+
+	/// The local reputation of our incoming channels, used to decide whether HTLCs we forward
+	/// should be endorsed to the next hop.
+	htlc_reputation: Mutex<HTLCReputationTracker>,
+
+	/// Used to price offers denominated in a currency other than bitcoin.
+	currency_conversion: CC,
 
 	entropy_source: ES,
 	node_signer: NS,
@@ -1626,8 +1794,8 @@ pub struct ChannelDetails {
 	/// The Channel's funding transaction output, if we've negotiated the funding transaction with
 	/// our counterparty already.
 	///
-	/// Note that, if this has been set, `channel_id` will be equivalent to
-	/// `funding_txo.unwrap().to_channel_id()`.
+	/// Note that, if this has been set and the channel was not opened using V2 channel
+	/// establishment, `channel_id` will be equivalent to `funding_txo.unwrap().to_channel_id()`.
 	pub funding_txo: Option<OutPoint>,
 	/// The features which this channel operates with. See individual features for more info.
 	///
@@ -1843,7 +2011,7 @@ impl ChannelDetails {
 					Some(context.get_counterparty_htlc_minimum_msat()) } else { None },
 				outbound_htlc_maximum_msat: context.get_counterparty_htlc_maximum_msat(),
 			},
-			funding_txo: context.get_funding_txo(),
+			funding_txo: context.get_current_funding_txo(),
 			// Note that accept_channel (or open_channel) is always the first message, so
 			// `have_received_message` indicates that type negotiation has completed.
 			channel_type: if context.have_received_message() { Some(context.get_channel_type().clone()) } else { None },
@@ -1896,6 +2064,58 @@ pub enum ChannelShutdownState {
 	ShutdownComplete,
 }
 
+/// The funds to add to or remove from a channel in a splice, passed to
+/// [`ChannelManager::splice_channel`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpliceContribution {
+	/// Adds funds to the channel by spending the given inputs in the splice transaction.
+	SpliceIn {
+		/// The amount, in satoshis, by which to increase our balance in the channel.
+		value_satoshis: u64,
+		/// The inputs to spend, along with the transactions they spend. Each must spend a segwit
+		/// output, and together they must cover `value_satoshis` and the fee for our inputs and
+		/// outputs in the splice transaction.
+		///
+		/// The inputs will need to be signed once the splice transaction has been negotiated, see
+		/// [`Event::FundingTransactionReadyForSigning`].
+		///
+		/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+		inputs: Vec<(TxIn, Transaction)>,
+		/// The script to send any value left over after fees to. If `None`, or if the remainder
+		/// would be dust, it is added to the transaction fee.
+		change_script: Option<ScriptBuf>,
+	},
+	/// Removes funds from the channel by paying them to the given outputs in the splice
+	/// transaction. The fee for the outputs is paid from our channel balance as well.
+	SpliceOut {
+		/// The outputs to pay, none of which may be dust.
+		outputs: Vec<TxOut>,
+	},
+}
+
+/// The funds we contribute to the funding transaction of a dual-funded channel, passed to
+/// [`ChannelManager::create_dual_funded_channel`],
+/// [`ChannelManager::accept_inbound_channel_with_contribution`] and
+/// [`ChannelManager::bump_channel_funding_fee`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FundingContribution {
+	/// The amount, in satoshis, we contribute to the channel, which will make up our initial
+	/// balance. May be zero, in which case no inputs are required.
+	pub value_satoshis: u64,
+	/// The inputs to spend, along with the transactions they spend. Each must spend a segwit
+	/// output, and together they must cover `value_satoshis` and the fee for our inputs and
+	/// outputs in the funding transaction.
+	///
+	/// The inputs will need to be signed once the funding transaction has been negotiated, see
+	/// [`Event::FundingTransactionReadyForSigning`].
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	pub inputs: Vec<(TxIn, Transaction)>,
+	/// The script to send any value left over after fees to. If `None`, or if the remainder
+	/// would be dust, it is added to the transaction fee.
+	pub change_script: Option<ScriptBuf>,
+}
+
 /// Used by [`ChannelManager::list_recent_payments`] to express the status of recent payments.
 /// These include payments that have yet to find a successful path, or have unresolved HTLCs.
 #[derive(Debug, PartialEq)]
@@ -2037,6 +2257,9 @@ macro_rules! convert_chan_phase_err {
 			ChannelError::Warn(msg) => {
 				(false, MsgHandleErrInternal::from_chan_no_close(ChannelError::Warn(msg), *$channel_id))
 			},
+			ChannelError::WarnAndDisconnect(msg) => {
+				(false, MsgHandleErrInternal::from_chan_no_close(ChannelError::WarnAndDisconnect(msg), *$channel_id))
+			},
 			ChannelError::Ignore(msg) => {
 				(false, MsgHandleErrInternal::from_chan_no_close(ChannelError::Ignore(msg), *$channel_id))
 			},
@@ -2069,6 +2292,12 @@ macro_rules! convert_chan_phase_err {
 			ChannelPhase::UnfundedInboundV1(channel) => {
 				convert_chan_phase_err!($self, $err, channel, $channel_id, UNFUNDED_CHANNEL)
 			},
+			ChannelPhase::UnfundedOutboundV2(channel) => {
+				convert_chan_phase_err!($self, $err, channel, $channel_id, UNFUNDED_CHANNEL)
+			},
+			ChannelPhase::UnfundedInboundV2(channel) => {
+				convert_chan_phase_err!($self, $err, channel, $channel_id, UNFUNDED_CHANNEL)
+			},
 		}
 	};
 }
@@ -2194,6 +2423,8 @@ macro_rules! handle_monitor_update_completion {
 			updates.commitment_update, updates.order, updates.accepted_htlcs,
 			updates.funding_broadcastable, updates.channel_ready,
 			updates.announcement_sigs);
+		$self.handle_splice_tx_signatures(&mut $peer_state.pending_msg_events, $chan,
+			updates.tx_signatures, updates.splice_funding_broadcastable);
 		if let Some(upd) = channel_update {
 			$peer_state.pending_msg_events.push(upd);
 		}
@@ -2377,7 +2608,7 @@ macro_rules! process_events_body {
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -2387,6 +2618,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// Constructs a new `ChannelManager` to hold several channels and route between them.
 	///
@@ -2407,8 +2639,8 @@ where
 	/// [`params.best_block.block_hash`]: chain::BestBlock::block_hash
 	pub fn new(
 		fee_est: F, chain_monitor: M, tx_broadcaster: T, router: R, logger: L, entropy_source: ES,
-		node_signer: NS, signer_provider: SP, config: UserConfig, params: ChainParameters,
-		current_timestamp: u32,
+		node_signer: NS, signer_provider: SP, currency_conversion: CC, config: UserConfig,
+		params: ChainParameters, current_timestamp: u32,
 	) -> Self {
 		let mut secp_ctx = Secp256k1::new();
 		secp_ctx.seeded_randomize(&entropy_source.get_secure_random_bytes());
@@ -2430,7 +2662,9 @@ where
 			forward_htlcs: Mutex::new(HashMap::new()),
 			claimable_payments: Mutex::new(ClaimablePayments { claimable_payments: HashMap::new(), pending_claiming_payments: HashMap::new() }),
 			pending_intercepted_htlcs: Mutex::new(HashMap::new()),
+			pending_held_htlcs: Mutex::new(HashMap::new()),
 			outpoint_to_peer: Mutex::new(HashMap::new()),
+			outpoint_to_channel_id: Mutex::new(HashMap::new()),
 			short_to_chan_info: FairRwLock::new(HashMap::new()),
 
 			our_network_pubkey: node_signer.get_node_id(Recipient::Node).unwrap(),
@@ -2455,6 +2689,12 @@ where
 			funding_batch_states: Mutex::new(BTreeMap::new()),
 
 			pending_offers_messages: Mutex::new(Vec::new()),
+			pending_async_payments_messages: Mutex::new(Vec::new()),
+			static_invoices: Mutex::new(HashMap::new()),
+			last_peer_storage_snapshot: Mutex::new(Vec::new()),
+			pending_peer_storage_persists: Mutex::new(HashSet::new()),
+			htlc_reputation: Mutex::new(HTLCReputationTracker::new()),
+			currency_conversion,
 
 			entropy_source,
 			node_signer,
@@ -2469,6 +2709,68 @@ where
 		&self.default_configuration
 	}
 
+	/// Converts an offer amount denominated in a currency other than bitcoin into millisatoshis
+	/// for the given `quantity` of items.
+	fn convert_currency_amount(
+		&self, iso4217_code: CurrencyCode, amount: u64, quantity: Option<u64>
+	) -> Result<u64, Bolt12SemanticError> {
+		let amount_msats = self.currency_conversion.convert_to_msats(iso4217_code, amount)
+			.map_err(|()| Bolt12SemanticError::UnsupportedCurrency)?;
+		amount_msats.checked_mul(quantity.unwrap_or(1)).ok_or(Bolt12SemanticError::InvalidAmount)
+	}
+
+	/// Checks that an [`InvoiceRequest`] for one of our offers denominated in a currency other
+	/// than bitcoin pays at least our own conversion of the offer amount, less the
+	/// [`CurrencyConversionTolerance::max_underpayment_ppm`].
+	///
+	/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+	/// [`CurrencyConversionTolerance::max_underpayment_ppm`]: crate::util::config::CurrencyConversionTolerance::max_underpayment_ppm
+	fn check_invoice_request_currency_amount(
+		&self, invoice_request: &crate::offers::invoice_request::InvoiceRequest
+	) -> Result<(), Bolt12SemanticError> {
+		let (iso4217_code, amount) = match invoice_request.amount() {
+			Some(Amount::Currency { iso4217_code, amount }) => (*iso4217_code, *amount),
+			_ => return Ok(()),
+		};
+		let expected_amount_msats =
+			self.convert_currency_amount(iso4217_code, amount, invoice_request.quantity())?;
+		let amount_msats = invoice_request.amount_msats()
+			.ok_or(Bolt12SemanticError::MissingAmount)?;
+		let max_underpayment_ppm =
+			self.default_configuration.currency_conversion_tolerance.max_underpayment_ppm;
+		let min_amount_msats = expected_amount_msats
+			.saturating_sub(proportion_of(expected_amount_msats, max_underpayment_ppm));
+		if amount_msats < min_amount_msats {
+			return Err(Bolt12SemanticError::InsufficientAmount);
+		}
+		Ok(())
+	}
+
+	/// Returns the unexpired [`StaticInvoice`] we serve for the offer `invoice_request` is for, if any.
+	/// See [`ChannelManager::add_static_invoice`].
+	fn static_invoice_for(
+		&self, invoice_request: &crate::offers::invoice_request::InvoiceRequest
+	) -> Option<StaticInvoice> {
+		let duration_since_epoch = self.duration_since_epoch();
+		self.static_invoices.lock().unwrap()
+			.get(&invoice_request.signing_pubkey())
+			.filter(|invoice| invoice.is_from_same_offer(invoice_request))
+			.filter(|invoice| !invoice.is_expired_no_std(duration_since_epoch))
+			.cloned()
+	}
+
+	/// Returns the current time as a duration since the Unix epoch, falling back to the highest
+	/// block timestamp we've seen without `std`.
+	fn duration_since_epoch(&self) -> Duration {
+		#[cfg(not(feature = "std"))]
+		let now = Duration::from_secs(self.highest_seen_timestamp.load(Ordering::Acquire) as u64);
+		#[cfg(feature = "std")]
+		let now = std::time::SystemTime::now()
+			.duration_since(std::time::SystemTime::UNIX_EPOCH)
+			.expect("SystemTime::now() should come after SystemTime::UNIX_EPOCH");
+		now
+	}
+
 	fn create_and_insert_outbound_scid_alias(&self) -> u64 {
 		let height = self.best_block.read().unwrap().height();
 		let mut outbound_scid_alias = 0;
@@ -2488,6 +2790,17 @@ where
 		outbound_scid_alias
 	}
 
+	/// Gets the `channel_id` of the channel with the given funding outpoint, as returned by
+	/// `ChannelContext::get_funding_txo`.
+	///
+	/// The `channel_id` of a channel opened using V2 channel establishment cannot be derived from
+	/// its funding outpoint, so we instead rely on `outpoint_to_channel_id` tracking every channel
+	/// we have handed a [`ChannelMonitor`] to the [`chain::Watch`] for (or were deserialized with).
+	fn channel_id_from_funding_txo(&self, funding_txo: &OutPoint) -> ChannelId {
+		*self.outpoint_to_channel_id.lock().unwrap().get(funding_txo)
+			.expect("We should have the channel_id of every channel we have a ChannelMonitor for")
+	}
+
 	/// Creates a new outbound channel to the given remote node and with the given value.
 	///
 	/// `user_channel_id` will be provided back as in
@@ -2578,6 +2891,91 @@ where
 		Ok(temporary_channel_id)
 	}
 
+	/// Creates a new outbound dual-funded channel to the given remote node, to which both we and
+	/// the counterparty may contribute funds.
+	///
+	/// Unlike [`ChannelManager::create_channel`], the funding transaction is constructed
+	/// interactively with the counterparty from the inputs each side contributes, with our
+	/// inputs and change given by `contribution` and each side paying for its own inputs and
+	/// outputs at `funding_feerate_sat_per_1000_weight`. `funding_tx_locktime` defaults to the
+	/// current best block height.
+	///
+	/// Once the funding transaction has been negotiated, an
+	/// [`Event::FundingTransactionReadyForSigning`] is generated, which must be signed and passed
+	/// back via [`ChannelManager::funding_transaction_signed`]. Once both parties have provided
+	/// their signatures, the funding transaction is broadcast and an [`Event::ChannelPending`] is
+	/// generated. Until the funding transaction confirms, it may be fee-bumped via
+	/// [`ChannelManager::bump_channel_funding_fee`].
+	///
+	/// Raises [`APIError::APIMisuseError`] if dual-funded channels are not enabled in our config,
+	/// the peer does not support them, or `contribution` is zero or has no inputs. See
+	/// [`ChannelManager::create_channel`] for the remaining requirements and return value, noting
+	/// that the channel's ID changes once the counterparty accepts it rather than once it's
+	/// funded.
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	/// [`Event::ChannelPending`]: events::Event::ChannelPending
+	pub fn create_dual_funded_channel(
+		&self, their_network_key: PublicKey, contribution: FundingContribution,
+		funding_feerate_sat_per_1000_weight: u32, funding_tx_locktime: Option<u32>, user_channel_id: u128,
+		override_config: Option<UserConfig>,
+	) -> Result<ChannelId, APIError> {
+		let config = override_config.as_ref().unwrap_or(&self.default_configuration);
+		if !config.enable_dual_funded_channels {
+			return Err(APIError::APIMisuseError { err: "Dual-funded channels are not enabled in our configuration".to_owned() });
+		}
+		if contribution.value_satoshis < 1000 {
+			return Err(APIError::APIMisuseError { err: format!("Channel value must be at least 1000 satoshis. It was {}", contribution.value_satoshis) });
+		}
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		// We want to make sure the lock is actually acquired by PersistenceNotifierGuard.
+		debug_assert!(&self.total_consistency_lock.try_write().is_err());
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+
+		let peer_state_mutex = per_peer_state.get(&their_network_key)
+			.ok_or_else(|| APIError::APIMisuseError{ err: format!("Not connected to node: {}", their_network_key) })?;
+
+		let mut peer_state = peer_state_mutex.lock().unwrap();
+
+		let channel = {
+			let outbound_scid_alias = self.create_and_insert_outbound_scid_alias();
+			let their_features = &peer_state.latest_features;
+			let best_block_height = self.best_block.read().unwrap().height();
+			match OutboundV2Channel::new(&self.fee_estimator, &self.entropy_source, &self.signer_provider,
+				their_network_key, their_features, contribution, funding_feerate_sat_per_1000_weight,
+				funding_tx_locktime.unwrap_or(best_block_height), user_channel_id, config, best_block_height,
+				outbound_scid_alias)
+			{
+				Ok(res) => res,
+				Err(e) => {
+					self.outbound_scid_aliases.lock().unwrap().remove(&outbound_scid_alias);
+					return Err(e);
+				},
+			}
+		};
+		let res = channel.get_open_channel_v2(self.chain_hash);
+
+		let temporary_channel_id = channel.context.channel_id();
+		match peer_state.channel_by_id.entry(temporary_channel_id) {
+			hash_map::Entry::Occupied(_) => {
+				if cfg!(fuzzing) {
+					return Err(APIError::APIMisuseError { err: "Fuzzy bad RNG".to_owned() });
+				} else {
+					panic!("RNG is bad???");
+				}
+			},
+			hash_map::Entry::Vacant(entry) => { entry.insert(ChannelPhase::UnfundedOutboundV2(channel)); }
+		}
+
+		peer_state.pending_msg_events.push(events::MessageSendEvent::SendOpenChannelV2 {
+			node_id: their_network_key,
+			msg: res,
+		});
+		Ok(temporary_channel_id)
+	}
+
 	fn list_funded_channels_with_filter<Fn: FnMut(&(&ChannelId, &Channel<SP>)) -> bool + Copy>(&self, f: Fn) -> Vec<ChannelDetails> {
 		// Allocate our best estimate of the number of channels we have in the `res`
 		// Vec. Sadly the `short_to_chan_info` map doesn't cover channels without
@@ -2687,6 +3085,13 @@ where
 				PendingOutboundPayment::InvoiceReceived { .. } => {
 					Some(RecentPaymentDetails::AwaitingInvoice { payment_id: *payment_id })
 				},
+				PendingOutboundPayment::StaticInvoiceReceived { payment_hash, route_params, .. } => {
+					Some(RecentPaymentDetails::Pending {
+						payment_id: *payment_id,
+						payment_hash: *payment_hash,
+						total_msat: route_params.final_value_msat,
+					})
+				},
 				PendingOutboundPayment::Retryable { payment_hash, total_msat, .. } => {
 					Some(RecentPaymentDetails::Pending {
 						payment_id: *payment_id,
@@ -2929,7 +3334,8 @@ where
 						self.finish_close_channel(chan.context.force_shutdown(broadcast, closure_reason));
 						(self.get_channel_update_for_broadcast(&chan).ok(), chan.context.get_counterparty_node_id())
 					},
-					ChannelPhase::UnfundedOutboundV1(_) | ChannelPhase::UnfundedInboundV1(_) => {
+					ChannelPhase::UnfundedOutboundV1(_) | ChannelPhase::UnfundedInboundV1(_) |
+					ChannelPhase::UnfundedOutboundV2(_) | ChannelPhase::UnfundedInboundV2(_) => {
 						self.finish_close_channel(chan_phase.context_mut().force_shutdown(false, closure_reason));
 						// Unfunded channel has no update
 						(None, chan_phase.context().get_counterparty_node_id())
@@ -3205,6 +3611,9 @@ where
 	) -> PendingHTLCStatus {
 		macro_rules! return_err {
 			($msg: expr, $err_code: expr, $data: expr) => {
+				return_err!($msg, $err_code, $data, None)
+			};
+			($msg: expr, $err_code: expr, $data: expr, $trampoline_shared_secret: expr) => {
 				{
 					let logger = WithContext::from(&self.logger, Some(*counterparty_node_id), Some(msg.channel_id));
 					log_info!(logger, "Failed to accept/forward incoming HTLC: {}", $msg);
@@ -3222,7 +3631,7 @@ where
 						channel_id: msg.channel_id,
 						htlc_id: msg.htlc_id,
 						reason: HTLCFailReason::reason($err_code, $data.to_vec())
-							.get_encrypted_failure_packet(&shared_secret, &None),
+							.get_encrypted_failure_packet(&shared_secret, &$trampoline_shared_secret),
 					}));
 				}
 			}
@@ -3251,7 +3660,23 @@ where
 					Ok(info) => PendingHTLCStatus::Forward(info),
 					Err(InboundHTLCErr { err_code, err_data, msg }) => return_err!(msg, err_code, &err_data)
 				}
-			}
+			},
+			onion_utils::Hop::TrampolineForward {
+				next_trampoline_hop_data, next_trampoline_hop_hmac, new_trampoline_packet_bytes,
+				next_trampoline_packet_pubkey, trampoline_shared_secret,
+			} => {
+				if !self.default_configuration.accept_trampoline_forwards {
+					return_err!("We do not accept trampoline forwards", 0x4000 | 22, &[0; 0]);
+				}
+				match create_trampoline_fwd_pending_htlc_info(msg, next_trampoline_hop_data,
+					next_trampoline_hop_hmac, new_trampoline_packet_bytes, shared_secret,
+					trampoline_shared_secret, next_trampoline_packet_pubkey)
+				{
+					Ok(info) => PendingHTLCStatus::Forward(info),
+					Err(InboundHTLCErr { err_code, err_data, msg }) =>
+						return_err!(msg, err_code, &err_data, Some(trampoline_shared_secret))
+				}
+			},
 		}
 	}
 
@@ -3343,14 +3768,14 @@ where
 		let _lck = self.total_consistency_lock.read().unwrap();
 		self.send_payment_along_path(SendAlongPathArgs {
 			path, payment_hash, recipient_onion, total_value, cur_height, payment_id, keysend_preimage,
-			session_priv_bytes
+			hold_htlc_at_next_hop: None, session_priv_bytes
 		})
 	}
 
 	fn send_payment_along_path(&self, args: SendAlongPathArgs) -> Result<(), APIError> {
 		let SendAlongPathArgs {
 			path, payment_hash, recipient_onion, total_value, cur_height, payment_id, keysend_preimage,
-			session_priv_bytes
+			hold_htlc_at_next_hop, session_priv_bytes
 		} = args;
 		// The top-level caller should hold the total_consistency_lock read lock.
 		debug_assert!(self.total_consistency_lock.try_write().is_err());
@@ -3359,7 +3784,7 @@ where
 
 		let (onion_packet, htlc_msat, htlc_cltv) = onion_utils::create_payment_onion(
 			&self.secp_ctx, &path, &session_priv, total_value, recipient_onion, cur_height,
-			payment_hash, keysend_preimage, prng_seed
+			payment_hash, keysend_preimage, hold_htlc_at_next_hop, prng_seed
 		).map_err(|e| {
 			let logger = WithContext::from(&self.logger, Some(path.hops.first().unwrap().pubkey), None);
 			log_error!(logger, "Failed to build an onion for path for payment hash {}", payment_hash);
@@ -3542,6 +3967,20 @@ where
 			)
 	}
 
+	fn send_payment_for_static_invoice(
+		&self, payment_id: PaymentId, first_hops: Vec<ChannelDetails>
+	) -> Result<(), Bolt12PaymentError> {
+		let best_block_height = self.best_block.read().unwrap().height();
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		self.pending_outbound_payments
+			.send_payment_for_static_invoice(
+				payment_id, &self.router, first_hops,
+				|| self.compute_inflight_htlcs(), &self.entropy_source, &self.node_signer,
+				best_block_height, &self.logger, &self.pending_events,
+				|args| self.send_payment_along_path(args)
+			)
+	}
+
 	/// Signals that no further attempts for the given payment should occur. Useful if you have a
 	/// pending outbound payment with retries remaining, but wish to stop retrying the payment before
 	/// retries are exhausted.
@@ -4067,6 +4506,231 @@ where
 		Ok(())
 	}
 
+	/// Initiates a splice of the given channel, adding funds to or removing funds from it while it
+	/// remains open, as described by the given [`SpliceContribution`].
+	///
+	/// Splicing requires [`UserConfig::enable_splicing`] to be set and our counterparty to signal
+	/// support for it. The channel must be live and have no HTLCs or fee updates pending. No new
+	/// HTLCs may be added until the splice transaction has confirmed and been locked by both
+	/// parties.
+	///
+	/// The splice transaction will pay `funding_feerate_per_kw` for our inputs and outputs and
+	/// will have the given `locktime`, defaulting to the current best block height.
+	///
+	/// Once the splice transaction has been negotiated with our counterparty, an
+	/// [`Event::FundingTransactionReadyForSigning`] will be generated if we contributed inputs,
+	/// which must be signed and passed back via [`ChannelManager::funding_transaction_signed`].
+	/// Once the transaction is fully signed and broadcast an [`Event::SplicePending`] is
+	/// generated, followed by an [`Event::SpliceLocked`] once it has confirmed and been locked by
+	/// both parties. If the splice is aborted, an [`Event::SpliceFailed`] is generated instead.
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	/// [`Event::SplicePending`]: events::Event::SplicePending
+	/// [`Event::SpliceLocked`]: events::Event::SpliceLocked
+	/// [`Event::SpliceFailed`]: events::Event::SpliceFailed
+	pub fn splice_channel(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, contribution: SpliceContribution,
+		funding_feerate_per_kw: u32, locktime: Option<u32>,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		if !self.default_configuration.enable_splicing {
+			return Err(APIError::APIMisuseError { err: "Splicing is not enabled in our configuration".to_owned() });
+		}
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| APIError::ChannelUnavailable { err: format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id) })?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		if !peer_state.latest_features.supports_splicing() {
+			return Err(APIError::ChannelUnavailable { err: format!("Peer {} does not support splicing", counterparty_node_id) });
+		}
+		match peer_state.channel_by_id.get_mut(channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let logger = WithChannelContext::from(&self.logger, &chan.context);
+				let locktime = locktime.unwrap_or(self.best_block.read().unwrap().height());
+				let msg = chan.splice_channel(contribution, funding_feerate_per_kw, locktime, self.chain_hash, &&logger)?;
+				peer_state.pending_msg_events.push(events::MessageSendEvent::SendSplice {
+					node_id: *counterparty_node_id,
+					msg,
+				});
+				Ok(())
+			},
+			Some(_) => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} is not funded, cannot splice it", channel_id),
+			}),
+			None => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} not found for the passed counterparty node_id {}", channel_id, counterparty_node_id),
+			}),
+		}
+	}
+
+	/// Requests that the given channel be made quiescent, i.e. that neither side may propose any
+	/// further updates to it, as is required by protocols such as splicing which change the
+	/// channel's commitment structure.
+	///
+	/// Any updates which are still pending will first be irrevocably committed, after which an
+	/// `stfu` message is sent to our counterparty, which must in turn respond with its own once its
+	/// pending updates have been committed. Any new HTLCs or fee updates sent or received in the
+	/// meantime are held until quiescence terminates. If the counterparty does not respond in a
+	/// timely manner, it will be disconnected on a later [`ChannelManager::timer_tick_occurred`].
+	///
+	/// Quiescence always terminates upon disconnection. If the channel had not yet become
+	/// quiescent by then, the request will be retried once the channel is reestablished.
+	///
+	/// Requires our counterparty to signal support for quiescence.
+	pub fn propose_quiescence(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| APIError::ChannelUnavailable { err: format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id) })?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		if !peer_state.latest_features.supports_quiescence() {
+			return Err(APIError::ChannelUnavailable { err: format!("Peer {} does not support quiescence", counterparty_node_id) });
+		}
+		match peer_state.channel_by_id.get_mut(channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let logger = WithChannelContext::from(&self.logger, &chan.context);
+				let stfu_opt = chan.propose_quiescence(&&logger)
+					.map_err(|e| APIError::APIMisuseError { err: e.to_string() })?;
+				if let Some(msg) = stfu_opt {
+					peer_state.pending_msg_events.push(events::MessageSendEvent::SendStfu {
+						node_id: *counterparty_node_id,
+						msg,
+					});
+				}
+				self.push_channel_quiescent_event(chan);
+				Ok(())
+			},
+			Some(_) => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} is not funded, cannot make it quiescent", channel_id),
+			}),
+			None => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} not found for the passed counterparty node_id {}", channel_id, counterparty_node_id),
+			}),
+		}
+	}
+
+	/// Terminates quiescence on the given channel, freeing any updates which were held while it was
+	/// quiescent. Returns whether we were the quiescence initiator.
+	///
+	/// Should be called once the protocol which required quiescence completes, after an
+	/// [`Event::ChannelQuiescent`] was generated for the channel. Our counterparty is expected to do
+	/// the same, otherwise we'll disconnect it if it keeps the channel quiescent for too long.
+	pub fn exit_quiescence(&self, counterparty_node_id: &PublicKey, channel_id: &ChannelId) -> Result<bool, APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let initiator = {
+			let per_peer_state = self.per_peer_state.read().unwrap();
+			let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+				.ok_or_else(|| APIError::ChannelUnavailable { err: format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id) })?;
+			let mut peer_state = peer_state_mutex.lock().unwrap();
+			match peer_state.channel_by_id.get_mut(channel_id) {
+				Some(ChannelPhase::Funded(chan)) => chan.exit_quiescence()
+					.map_err(|e| APIError::APIMisuseError { err: e.to_string() })?,
+				_ => return Err(APIError::ChannelUnavailable {
+					err: format!("Channel with id {} not found for the passed counterparty node_id {}", channel_id, counterparty_node_id),
+				}),
+			}
+		};
+		self.check_free_holding_cells();
+		Ok(initiator)
+	}
+
+	/// Replaces the unconfirmed funding transaction of a dual-funded channel we opened via
+	/// [`ChannelManager::create_dual_funded_channel`] with one paying
+	/// `funding_feerate_sat_per_1000_weight`, which must be at least 25/24 of the feerate of the
+	/// funding transaction it replaces.
+	///
+	/// `contribution` replaces our previous contribution and must spend at least one of the
+	/// inputs of the funding transaction being replaced, ensuring only one of them can confirm.
+	/// `locktime` defaults to the current best block height.
+	///
+	/// Only the opener of the channel may initiate a fee-bump, calling this for a channel our
+	/// counterparty opened returns an [`APIError::APIMisuseError`]. As the acceptor, we take part
+	/// in fee-bumps initiated by our counterparty automatically, though only if we didn't
+	/// contribute to the funding transaction. Otherwise, the fee-bump is rejected with a
+	/// `tx_abort`.
+	///
+	/// As with the initial funding transaction, an [`Event::FundingTransactionReadyForSigning`] is
+	/// generated once the replacement has been negotiated, followed by an
+	/// [`Event::ChannelPending`] once it has been broadcast. If the counterparty rejects the
+	/// fee-bump, the previous funding transaction remains in place.
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	/// [`Event::ChannelPending`]: events::Event::ChannelPending
+	pub fn bump_channel_funding_fee(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, contribution: FundingContribution,
+		funding_feerate_sat_per_1000_weight: u32, locktime: Option<u32>,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| APIError::ChannelUnavailable { err: format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id) })?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.get_mut(channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let logger = WithChannelContext::from(&self.logger, &chan.context);
+				let locktime = locktime.unwrap_or(self.best_block.read().unwrap().height());
+				let msg = chan.bump_funding_fee(contribution, funding_feerate_sat_per_1000_weight, locktime, &&logger)?;
+				peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxInitRbf {
+					node_id: *counterparty_node_id,
+					msg,
+				});
+				Ok(())
+			},
+			Some(_) => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} has not been funded yet, cannot fee-bump its funding transaction", channel_id),
+			}),
+			None => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} not found for the passed counterparty node_id {}", channel_id, counterparty_node_id),
+			}),
+		}
+	}
+
+	/// Provides the funding transaction of a dual-funded channel or a splice from an
+	/// [`Event::FundingTransactionReadyForSigning`] with witnesses for all of the inputs we
+	/// contributed to it.
+	///
+	/// Once our counterparty has provided their signatures as well, the transaction is broadcast
+	/// and an [`Event::ChannelPending`] or [`Event::SplicePending`] is generated, respectively.
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	/// [`Event::ChannelPending`]: events::Event::ChannelPending
+	/// [`Event::SplicePending`]: events::Event::SplicePending
+	pub fn funding_transaction_signed(
+		&self, channel_id: &ChannelId, counterparty_node_id: &PublicKey, transaction: Transaction,
+	) -> Result<(), APIError> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| APIError::ChannelUnavailable { err: format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id) })?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.get_mut(channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let logger = WithChannelContext::from(&self.logger, &chan.context);
+				let (tx_signatures, funding_tx) = chan.funding_transaction_signed(transaction, &&logger)?;
+				self.handle_splice_tx_signatures(&mut peer_state.pending_msg_events, chan, tx_signatures, funding_tx);
+				Ok(())
+			},
+			// We may be done signing before our counterparty's `commitment_signed` has arrived, in
+			// which case our signatures are sent once the channel has been funded.
+			Some(phase @ ChannelPhase::UnfundedOutboundV2(_)) | Some(phase @ ChannelPhase::UnfundedInboundV2(_)) => {
+				phase.context_mut().funding_transaction_signed(transaction)
+			},
+			_ => Err(APIError::ChannelUnavailable {
+				err: format!("Channel with id {} not found for the passed counterparty node_id {}", channel_id, counterparty_node_id),
+			}),
+		}
+	}
+
 	/// Atomically updates the [`ChannelConfig`] for the given channels.
 	///
 	/// Once the updates are applied, each eligible channel (advertised with a known short channel
@@ -4160,9 +4824,10 @@ where
 			})?;
 
 		let routing = match payment.forward_info.routing {
-			PendingHTLCRouting::Forward { onion_packet, blinded, .. } => {
+			PendingHTLCRouting::Forward { onion_packet, blinded, incoming_trampoline_shared_secret, .. } => {
 				PendingHTLCRouting::Forward {
-					onion_packet, blinded, short_channel_id: next_hop_scid
+					onion_packet, blinded, short_channel_id: next_hop_scid, incoming_trampoline_shared_secret,
+					hold_htlc: None,
 				}
 			},
 			_ => unreachable!() // Only `PendingHTLCRouting::Forward`s are intercepted
@@ -4208,6 +4873,7 @@ where
 				incoming_packet_shared_secret: payment.forward_info.incoming_shared_secret,
 				phantom_shared_secret: None,
 				blinded_failure: payment.forward_info.routing.blinded_failure(),
+				trampoline_shared_secret: payment.forward_info.routing.trampoline_shared_secret(),
 			});
 
 			let failure_reason = HTLCFailReason::from_failure_code(0x4000 | 10);
@@ -4218,10 +4884,168 @@ where
 		Ok(())
 	}
 
-	/// Processes HTLCs which are pending waiting on random forward delay.
-	///
-	/// Should only really ever be called in response to a PendingHTLCsForwardable event.
-	/// Will likely generate further events.
+	/// Finds routes for the HTLCs we were asked to forward as a trampoline node, replacing them in
+	/// `forward_htlcs` with regular forwards over the first hop of the route we found.
+	fn route_trampoline_forwards(
+		&self, forward_htlcs: &mut HashMap<u64, Vec<HTLCForwardInfo>>,
+		failed_forwards: &mut Vec<(HTLCSource, PaymentHash, HTLCFailReason, HTLCDestination)>
+	) {
+		let pending_forwards = match forward_htlcs.remove(&0) {
+			Some(pending_forwards) => pending_forwards,
+			None => return,
+		};
+		let (trampoline_forwards, other_forwards): (Vec<_>, Vec<_>) = pending_forwards.into_iter()
+			.partition(|forward| matches!(forward, HTLCForwardInfo::AddHTLC(PendingAddHTLCInfo {
+				forward_info: PendingHTLCInfo { routing: PendingHTLCRouting::TrampolineForward { .. }, .. }, ..
+			})));
+		if !other_forwards.is_empty() { forward_htlcs.insert(0, other_forwards); }
+		if trampoline_forwards.is_empty() { return; }
+
+		let our_node_id = self.get_our_node_id();
+		let first_hops = self.list_usable_channels();
+		let cur_height = self.best_block.read().unwrap().height() + 1;
+		for forward in trampoline_forwards {
+			let (prev_short_channel_id, prev_htlc_id, prev_funding_outpoint, prev_user_channel_id, forward_info) = match forward {
+				HTLCForwardInfo::AddHTLC(PendingAddHTLCInfo {
+					prev_short_channel_id, prev_htlc_id, prev_funding_outpoint, prev_user_channel_id,
+					forward_info,
+				}) => (prev_short_channel_id, prev_htlc_id, prev_funding_outpoint, prev_user_channel_id, forward_info),
+				_ => unreachable!(),
+			};
+			let (onion_packet, next_trampoline, incoming_cltv_expiry, incoming_trampoline_shared_secret) = match forward_info.routing {
+				PendingHTLCRouting::TrampolineForward {
+					ref onion_packet, node_id, incoming_cltv_expiry, incoming_trampoline_shared_secret
+				} => (onion_packet.clone(), node_id, incoming_cltv_expiry, incoming_trampoline_shared_secret),
+				_ => unreachable!(),
+			};
+			let logger = WithContext::from(&self.logger, Some(next_trampoline), Some(self.channel_id_from_funding_txo(&prev_funding_outpoint)));
+			macro_rules! fail_trampoline_forward {
+				($msg: expr, $err_code: expr, $err_data: expr) => { {
+					log_info!(logger, "Failed to forward incoming trampoline HTLC: {}", $msg);
+					let htlc_source = HTLCSource::PreviousHopData(HTLCPreviousHopData {
+						short_channel_id: prev_short_channel_id,
+						user_channel_id: Some(prev_user_channel_id),
+						outpoint: prev_funding_outpoint,
+						htlc_id: prev_htlc_id,
+						incoming_packet_shared_secret: forward_info.incoming_shared_secret,
+						phantom_shared_secret: None,
+						blinded_failure: None,
+						trampoline_shared_secret: Some(incoming_trampoline_shared_secret),
+					});
+					failed_forwards.push((htlc_source, forward_info.payment_hash,
+						HTLCFailReason::reason($err_code, $err_data),
+						HTLCDestination::NextTrampoline { node_id: next_trampoline },
+					));
+					continue;
+				} }
+			}
+
+			if !self.default_configuration.accept_trampoline_forwards {
+				fail_trampoline_forward!("We do not accept trampoline forwards", 0x4000 | 22, Vec::new());
+			}
+
+			// Whatever the sender left us beyond our own fee may be spent on routing fees, and whatever
+			// CLTV delta they left us beyond our own may be spent on the route's CLTV deltas. As we only
+			// know the outgoing channel, and thus our fee and CLTV delta, once we found the route, we
+			// reserve the most any of our channels would require.
+			let incoming_amt_msat = forward_info.incoming_amt_msat.unwrap_or(forward_info.outgoing_amt_msat);
+			let max_trampoline_fee_msat = first_hops.iter().filter_map(|chan| chan.config)
+				.map(|config| trampoline_fee_msat(&config, incoming_amt_msat))
+				.max().unwrap_or(0);
+			let max_cltv_expiry_delta = first_hops.iter().filter_map(|chan| chan.config)
+				.map(|config| config.cltv_expiry_delta as u32)
+				.max().unwrap_or(0);
+			let max_total_routing_fee_msat = incoming_amt_msat
+				.saturating_sub(forward_info.outgoing_amt_msat)
+				.saturating_sub(max_trampoline_fee_msat);
+			let max_total_cltv_expiry_delta = incoming_cltv_expiry
+				.saturating_sub(max_cltv_expiry_delta)
+				.saturating_sub(cur_height);
+			if forward_info.outgoing_cltv_value <= cur_height {
+				fail_trampoline_forward!("Outgoing CLTV value has already expired", 0x4000 | 22, Vec::new());
+			}
+			let mut payment_params = PaymentParameters::from_node_id(
+				next_trampoline, forward_info.outgoing_cltv_value - cur_height
+			);
+			payment_params.max_total_cltv_expiry_delta = max_total_cltv_expiry_delta;
+			// We forward the incoming HTLC as a single outgoing HTLC, so we need a single-path route.
+			payment_params.max_path_count = 1;
+			let route_params = RouteParameters {
+				payment_params,
+				final_value_msat: forward_info.outgoing_amt_msat,
+				max_total_routing_fee_msat: Some(max_total_routing_fee_msat),
+			};
+			let route = match self.router.find_route(
+				&our_node_id, &route_params, Some(&first_hops.iter().collect::<Vec<_>>()),
+				self.compute_inflight_htlcs()
+			) {
+				Ok(route) => route,
+				Err(e) => fail_trampoline_forward!(
+					format!("Unable to find a route to the next trampoline {}: {}", next_trampoline, e.err),
+					0x2000 | 25, Vec::new()
+				),
+			};
+			if route.paths.len() != 1 {
+				fail_trampoline_forward!("Router returned a route that isn't single-path", 0x2000 | 25, Vec::new());
+			}
+			let path = route.paths.into_iter().next().expect("Checked the route has a single path");
+
+			let session_priv = SecretKey::from_slice(&self.entropy_source.get_secure_random_bytes())
+				.expect("RNG is busted");
+			let prng_seed = self.entropy_source.get_secure_random_bytes();
+			let (outer_onion, htlc_msat, htlc_cltv) = match onion_utils::create_trampoline_forward_onion(
+				&self.secp_ctx, &path, &session_priv, onion_packet, cur_height,
+				&forward_info.payment_hash, prng_seed
+			) {
+				Ok(res) => res,
+				Err(_) => fail_trampoline_forward!("Failed to build an onion for the route to the next trampoline", 0x2000 | 25, Vec::new()),
+			};
+
+			// As for regular forwards, our fee and CLTV delta are those of the outgoing channel.
+			let short_channel_id = path.hops[0].short_channel_id;
+			let fee_policy = match first_hops.iter()
+				.find(|chan| chan.short_channel_id == Some(short_channel_id) ||
+					chan.outbound_scid_alias == Some(short_channel_id))
+				.and_then(|chan| chan.config)
+			{
+				Some(config) => config,
+				None => fail_trampoline_forward!("Router returned a route via an unknown first hop", 0x2000 | 25, Vec::new()),
+			};
+			let insufficient_fee = trampoline_fee_msat(&fee_policy, htlc_msat).checked_add(htlc_msat)
+				.map_or(true, |required_amt_msat| incoming_amt_msat < required_amt_msat);
+			let insufficient_cltv =
+				(incoming_cltv_expiry as u64) < htlc_cltv as u64 + fee_policy.cltv_expiry_delta as u64;
+			if insufficient_fee || insufficient_cltv {
+				fail_trampoline_forward!("Route to the next trampoline exceeds the fee or CLTV budget", 0x2000 | 26,
+					trampoline_fee_or_expiry_insufficient_data(&fee_policy));
+			}
+
+			log_trace!(logger, "Routing trampoline HTLC with payment_hash {} to {} via short id {}",
+				&forward_info.payment_hash, next_trampoline, short_channel_id);
+			let forward_info = PendingHTLCInfo {
+				routing: PendingHTLCRouting::Forward {
+					onion_packet: outer_onion,
+					short_channel_id,
+					blinded: None,
+					incoming_trampoline_shared_secret: Some(incoming_trampoline_shared_secret),
+					hold_htlc: None,
+				},
+				outgoing_amt_msat: htlc_msat,
+				outgoing_cltv_value: htlc_cltv,
+				..forward_info
+			};
+			forward_htlcs.entry(short_channel_id).or_insert_with(Vec::new)
+				.push(HTLCForwardInfo::AddHTLC(PendingAddHTLCInfo {
+					prev_short_channel_id, prev_htlc_id, prev_funding_outpoint, prev_user_channel_id,
+					forward_info,
+				}));
+		}
+	}
+
+	/// Processes HTLCs which are pending waiting on random forward delay.
+	///
+	/// Should only really ever be called in response to a PendingHTLCsForwardable event.
+	/// Will likely generate further events.
 	pub fn process_pending_htlc_forwards(&self) {
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
 
@@ -4231,6 +5055,7 @@ where
 		{
 			let mut forward_htlcs = HashMap::new();
 			mem::swap(&mut forward_htlcs, &mut self.forward_htlcs.lock().unwrap());
+			self.route_trampoline_forwards(&mut forward_htlcs, &mut failed_forwards);
 
 			for (short_chan_id, mut pending_forwards) in forward_htlcs {
 				if short_chan_id != 0 {
@@ -4248,7 +5073,7 @@ where
 									}) => {
 										macro_rules! failure_handler {
 											($msg: expr, $err_code: expr, $err_data: expr, $phantom_ss: expr, $next_hop_unknown: expr) => {
-												let logger = WithContext::from(&self.logger, forwarding_counterparty, Some(prev_funding_outpoint.to_channel_id()));
+												let logger = WithContext::from(&self.logger, forwarding_counterparty, Some(self.channel_id_from_funding_txo(&prev_funding_outpoint)));
 												log_info!(logger, "Failed to accept/forward incoming HTLC: {}", $msg);
 
 												let htlc_source = HTLCSource::PreviousHopData(HTLCPreviousHopData {
@@ -4259,6 +5084,7 @@ where
 													incoming_packet_shared_secret: incoming_shared_secret,
 													phantom_shared_secret: $phantom_ss,
 													blinded_failure: routing.blinded_failure(),
+													trampoline_shared_secret: routing.trampoline_shared_secret(),
 												});
 
 												let reason = if $next_hop_unknown {
@@ -4364,10 +5190,10 @@ where
 								HTLCForwardInfo::AddHTLC(PendingAddHTLCInfo {
 									prev_short_channel_id, prev_htlc_id, prev_funding_outpoint, prev_user_channel_id,
 									forward_info: PendingHTLCInfo {
-										incoming_shared_secret, payment_hash, outgoing_amt_msat, outgoing_cltv_value,
-										routing: PendingHTLCRouting::Forward {
-											onion_packet, blinded, ..
-										}, skimmed_fee_msat, ..
+										incoming_shared_secret, payment_hash, incoming_amt_msat, outgoing_amt_msat,
+										outgoing_cltv_value, routing: PendingHTLCRouting::Forward {
+											onion_packet, blinded, incoming_trampoline_shared_secret, ..
+										}, skimmed_fee_msat, incoming_endorsed,
 									},
 								}) => {
 									log_trace!(logger, "Adding HTLC from short id {} with payment_hash {} to channel with short id {} after delay", prev_short_channel_id, &payment_hash, short_chan_id);
@@ -4380,6 +5206,7 @@ where
 										// Phantom payments are only PendingHTLCRouting::Receive.
 										phantom_shared_secret: None,
 										blinded_failure: blinded.map(|b| b.failure),
+										trampoline_shared_secret: incoming_trampoline_shared_secret,
 									});
 									let next_blinding_point = blinded.and_then(|b| {
 										let encrypted_tlvs_ss = self.node_signer.ecdh(
@@ -4389,11 +5216,24 @@ where
 											&self.secp_ctx, b.inbound_blinding_point, &encrypted_tlvs_ss
 										).ok()
 									});
-									if let Err(e) = chan.queue_add_htlc(outgoing_amt_msat,
-										payment_hash, outgoing_cltv_value, htlc_source.clone(),
-										onion_packet, skimmed_fee_msat, next_blinding_point, &self.fee_estimator,
-										&&logger)
-									{
+									// We only endorse the HTLC to the next hop if it was endorsed to us and
+									// the incoming channel has sufficient reputation with us. Otherwise, it
+									// may only use the share of the outgoing channel's resources we allow
+									// unendorsed HTLCs to occupy.
+									let fee_msat = incoming_amt_msat.unwrap_or(outgoing_amt_msat)
+										.saturating_sub(outgoing_amt_msat);
+									let blocks_to_expiry = outgoing_cltv_value
+										.saturating_sub(self.best_block.read().unwrap().height());
+									let endorsed = incoming_endorsed && self.htlc_reputation.lock().unwrap()
+										.should_endorse(prev_short_channel_id, fee_msat, blocks_to_expiry);
+									let add_res = if !endorsed && !chan.can_add_unendorsed_htlc(outgoing_amt_msat) {
+										Err(ChannelError::Ignore("Unendorsed HTLC would exceed the resources reserved for unendorsed HTLCs".to_owned()))
+									} else {
+										chan.queue_add_htlc(outgoing_amt_msat, payment_hash, outgoing_cltv_value,
+											htlc_source.clone(), onion_packet, skimmed_fee_msat, next_blinding_point,
+											Some(endorsed), &self.fee_estimator, &&logger)
+									};
+									if let Err(e) = add_res {
 										if let ChannelError::Ignore(msg) = e {
 											log_trace!(logger, "Failed to forward HTLC with payment_hash {}: {}", &payment_hash, msg);
 										} else {
@@ -4406,6 +5246,9 @@ where
 										));
 										continue;
 									}
+									self.htlc_reputation.lock().unwrap().htlc_forwarded(
+										prev_short_channel_id, prev_htlc_id, fee_msat, blocks_to_expiry, endorsed
+									);
 									None
 								},
 								HTLCForwardInfo::AddHTLC { .. } => {
@@ -4485,6 +5328,7 @@ where
 										incoming_packet_shared_secret: incoming_shared_secret,
 										phantom_shared_secret,
 										blinded_failure,
+										trampoline_shared_secret: None,
 									},
 									// We differentiate the received value from the sender intended value
 									// if possible so that we don't prematurely mark MPP payments complete
@@ -4516,6 +5360,7 @@ where
 												incoming_packet_shared_secret: $htlc.prev_hop.incoming_packet_shared_secret,
 												phantom_shared_secret,
 												blinded_failure,
+												trampoline_shared_secret: None,
 											}), payment_hash,
 											HTLCFailReason::reason(0x4000 | 15, htlc_msat_height_data),
 											HTLCDestination::FailedPayment { payment_hash: $payment_hash },
@@ -4591,7 +5436,7 @@ where
 											#[allow(unused_assignments)] {
 												committed_to_claimable = true;
 											}
-											let prev_channel_id = prev_funding_outpoint.to_channel_id();
+											let prev_channel_id = self.channel_id_from_funding_txo(&prev_funding_outpoint);
 											htlcs.push(claimable_htlc);
 											let amount_msat = htlcs.iter().map(|htlc| htlc.value).sum();
 											htlcs.iter_mut().for_each(|htlc| htlc.total_value_received = Some(amount_msat));
@@ -4747,7 +5592,7 @@ where
 						if let Some(peer_state_mutex) = per_peer_state.get(&counterparty_node_id) {
 							let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 							let peer_state = &mut *peer_state_lock;
-							match peer_state.channel_by_id.entry(funding_txo.to_channel_id()) {
+							match peer_state.channel_by_id.entry(self.channel_id_from_funding_txo(&funding_txo)) {
 								hash_map::Entry::Occupied(mut chan_phase) => {
 									if let ChannelPhase::Funded(chan) = chan_phase.get_mut() {
 										updated_chan = true;
@@ -4782,6 +5627,20 @@ where
 						}
 					}
 				},
+				BackgroundEvent::SpliceLocked { counterparty_node_id, channel_id } => {
+					let per_peer_state = self.per_peer_state.read().unwrap();
+					if let Some(peer_state_mutex) = per_peer_state.get(&counterparty_node_id) {
+						let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+						let peer_state = &mut *peer_state_lock;
+						if let Some(ChannelPhase::Funded(chan)) = peer_state.channel_by_id.get_mut(&channel_id) {
+							if let Some(monitor_update) = chan.get_splice_locked_monitor_update() {
+								let funding_txo = chan.context.get_funding_txo().unwrap();
+								handle_new_monitor_update!(self, funding_txo, monitor_update,
+									peer_state_lock, peer_state, per_peer_state, chan);
+							}
+						}
+					}
+				},
 			}
 		}
 		NotifyOption::DoPersist
@@ -4796,6 +5655,8 @@ where
 
 	fn update_channel_fee(&self, chan_id: &ChannelId, chan: &mut Channel<SP>, new_feerate: u32) -> NotifyOption {
 		if !chan.context.is_outbound() { return NotifyOption::SkipPersistNoEvents; }
+		// Zero-fee commitment transactions never pay a fee themselves.
+		if chan.context.get_channel_type().supports_anchor_zero_fee_commitments() { return NotifyOption::SkipPersistNoEvents; }
 
 		let logger = WithChannelContext::from(&self.logger, &chan.context);
 
@@ -4867,6 +5728,8 @@ where
 	///    or those awaiting an invoice that hasn't been delivered in the necessary amount of time.
 	///    The latter is determined using the system clock in `std` and the highest seen block time
 	///    minus two hours in `no-std`.
+	///  * Decaying the local reputation of our incoming channels, which determines whether HTLCs we
+	///    forward are endorsed to the next hop.
 	///
 	/// Note that this may cause reentrancy through [`chain::Watch::update_channel`] calls or feerate
 	/// estimate fetches.
@@ -4877,6 +5740,8 @@ where
 		PersistenceNotifierGuard::optionally_notify(self, || {
 			let mut should_persist = NotifyOption::SkipPersistNoEvents;
 
+			self.htlc_reputation.lock().unwrap().timer_tick_occurred();
+
 			let non_anchor_feerate = self.fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::NonAnchorChannelFee);
 			let anchor_feerate = self.fee_estimator.bounded_sat_per_1000_weight(ConfirmationTarget::AnchorChannelFee);
 
@@ -5003,6 +5868,14 @@ where
 								process_unfunded_channel_tick(chan_id, &mut chan.context, &mut chan.unfunded_context,
 									pending_msg_events, counterparty_node_id)
 							},
+							ChannelPhase::UnfundedInboundV2(chan) => {
+								process_unfunded_channel_tick(chan_id, &mut chan.context, &mut chan.unfunded_context,
+									pending_msg_events, counterparty_node_id)
+							},
+							ChannelPhase::UnfundedOutboundV2(chan) => {
+								process_unfunded_channel_tick(chan_id, &mut chan.context, &mut chan.unfunded_context,
+									pending_msg_events, counterparty_node_id)
+							},
 						}
 					});
 
@@ -5028,6 +5901,33 @@ where
 				}
 			}
 
+			// Hand our peers an updated backup if our channel state changed since the last one.
+			if self.default_configuration.provide_peer_storage {
+				let snapshot = self.chain_monitor.get_peer_storage_snapshot();
+				let snapshot_changed = {
+					let mut last_snapshot = self.last_peer_storage_snapshot.lock().unwrap();
+					let changed = *last_snapshot != snapshot;
+					if changed { *last_snapshot = snapshot.clone(); }
+					changed
+				};
+				if snapshot_changed {
+					let per_peer_state = self.per_peer_state.read().unwrap();
+					for (counterparty_node_id, peer_state_mutex) in per_peer_state.iter() {
+						let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+						let peer_state = &mut *peer_state_lock;
+						if peer_state.wants_our_peer_storage() {
+							peer_state.pending_msg_events.push(events::MessageSendEvent::SendPeerStorage {
+								node_id: *counterparty_node_id,
+								msg: self.our_peer_storage_msg(snapshot.clone()),
+							});
+							if should_persist == NotifyOption::SkipPersistNoEvents {
+								should_persist = NotifyOption::SkipPersistHandleEvents;
+							}
+						}
+					}
+				}
+			}
+
 			// When a peer disconnects but still has channels, the peer's `peer_state` entry in the
 			// `per_peer_state` is not removed by the `peer_disconnected` function. If the channels
 			// of to that peer is later closed while still being disconnected (i.e. force closed),
@@ -5042,12 +5942,16 @@ where
 						hash_map::Entry::Occupied(entry) => {
 							// Remove the entry if the peer is still disconnected and we still
 							// have no channels to the peer.
-							let remove_entry = {
+							let (remove_entry, has_peer_storage) = {
 								let peer_state = entry.get().lock().unwrap();
-								peer_state.ok_to_remove(true)
+								(peer_state.ok_to_remove(true), !peer_state.peer_storage.is_empty())
 							};
 							if remove_entry {
 								entry.remove_entry();
+								// The peer's backup has to be removed from our persisted state, too.
+								if has_peer_storage {
+									self.pending_peer_storage_persists.lock().unwrap().insert(counterparty_node_id);
+								}
 							}
 						},
 						hash_map::Entry::Vacant(_) => { /* The PeerState has already been removed */ }
@@ -5286,10 +6190,11 @@ where
 			},
 			HTLCSource::PreviousHopData(HTLCPreviousHopData {
 				ref short_channel_id, ref htlc_id, ref incoming_packet_shared_secret,
-				ref phantom_shared_secret, ref outpoint, ref blinded_failure, ..
+				ref phantom_shared_secret, ref outpoint, ref blinded_failure, ref trampoline_shared_secret, ..
 			}) => {
+				self.htlc_reputation.lock().unwrap().htlc_resolved(*short_channel_id, *htlc_id, false);
 				log_trace!(
-					WithContext::from(&self.logger, None, Some(outpoint.to_channel_id())),
+					WithContext::from(&self.logger, None, Some(self.channel_id_from_funding_txo(&outpoint))),
 					"Failing {}HTLC with payment_hash {} backwards from us: {:?}",
 					if blinded_failure.is_some() { "blinded " } else { "" }, &payment_hash, onion_error
 				);
@@ -5308,6 +6213,12 @@ where
 							sha256_of_onion: [0; 32]
 						}
 					},
+					None if trampoline_shared_secret.is_some() => {
+						let err_packet = onion_error.get_encrypted_trampoline_failure_packet(
+							incoming_packet_shared_secret, trampoline_shared_secret.as_ref().unwrap()
+						);
+						HTLCForwardInfo::FailHTLC { htlc_id: *htlc_id, err_packet }
+					},
 					None => {
 						let err_packet = onion_error.get_encrypted_failure_packet(
 							incoming_packet_shared_secret, phantom_shared_secret
@@ -5333,7 +6244,7 @@ where
 				if push_forward_ev { self.push_pending_forwards_ev(); }
 				let mut pending_events = self.pending_events.lock().unwrap();
 				pending_events.push_back((events::Event::HTLCHandlingFailed {
-					prev_channel_id: outpoint.to_channel_id(),
+					prev_channel_id: self.channel_id_from_funding_txo(&outpoint),
 					failed_next_destination: destination,
 				}, None));
 			},
@@ -5399,7 +6310,9 @@ where
 					}
 				}
 
-				let htlcs = payment.htlcs.iter().map(events::ClaimedHTLC::from).collect();
+				let htlcs = payment.htlcs.iter()
+					.map(|htlc| htlc.to_claimed_htlc(self.channel_id_from_funding_txo(&htlc.prev_hop.outpoint)))
+					.collect();
 				let sender_intended_value = payment.htlcs.first().map(|htlc| htlc.total_msat);
 				let dup_purpose = claimable_payments.pending_claiming_payments.insert(payment_hash,
 					ClaimingPayment { amount_msat: payment.htlcs.iter().map(|source| source.value).sum(),
@@ -5474,7 +6387,7 @@ where
 		}
 		if valid_mpp {
 			for htlc in sources.drain(..) {
-				let prev_hop_chan_id = htlc.prev_hop.outpoint.to_channel_id();
+				let prev_hop_chan_id = self.channel_id_from_funding_txo(&htlc.prev_hop.outpoint);
 				if let Err((pk, err)) = self.claim_funds_from_hop(
 					htlc.prev_hop, payment_preimage,
 					|_, definitely_duplicate| {
@@ -5527,7 +6440,7 @@ where
 
 		{
 			let per_peer_state = self.per_peer_state.read().unwrap();
-			let chan_id = prev_hop.outpoint.to_channel_id();
+			let chan_id = self.channel_id_from_funding_txo(&prev_hop.outpoint);
 			let counterparty_node_id_opt = match self.short_to_chan_info.read().unwrap().get(&prev_hop.short_channel_id) {
 				Some((cp_id, _dup_chan_id)) => Some(cp_id.clone()),
 				None => None
@@ -5595,7 +6508,7 @@ where
 									let mut peer_state = peer_state_mtx.lock().unwrap();
 									if let Some(blockers) = peer_state
 										.actions_blocking_raa_monitor_updates
-										.get_mut(&funding_outpoint.to_channel_id())
+										.get_mut(&self.channel_id_from_funding_txo(&funding_outpoint))
 									{
 										let mut found_blocker = false;
 										blockers.retain(|iter| {
@@ -5635,7 +6548,7 @@ where
 				// with a preimage we *must* somehow manage to propagate it to the upstream
 				// channel, or we must have an ability to receive the same event and try
 				// again on restart.
-				log_error!(WithContext::from(&self.logger, None, Some(prev_hop.outpoint.to_channel_id())), "Critical error: failed to update channel monitor with preimage {:?}: {:?}",
+				log_error!(WithContext::from(&self.logger, None, Some(self.channel_id_from_funding_txo(&prev_hop.outpoint))), "Critical error: failed to update channel monitor with preimage {:?}: {:?}",
 					payment_preimage, update_res);
 			}
 		} else {
@@ -5687,8 +6600,11 @@ where
 					&self.logger);
 			},
 			HTLCSource::PreviousHopData(hop_data) => {
+				self.htlc_reputation.lock().unwrap()
+					.htlc_resolved(hop_data.short_channel_id, hop_data.htlc_id, true);
 				let prev_outpoint = hop_data.outpoint;
-				let completed_blocker = RAAMonitorUpdateBlockingAction::from_prev_hop_data(&hop_data);
+				let completed_blocker = RAAMonitorUpdateBlockingAction::from_prev_hop_data(
+					&hop_data, self.channel_id_from_funding_txo(&prev_outpoint));
 				#[cfg(debug_assertions)]
 				let claiming_chan_funding_outpoint = hop_data.outpoint;
 				let res = self.claim_funds_from_hop(hop_data, payment_preimage,
@@ -5748,7 +6664,7 @@ where
 										BackgroundEvent::MonitorUpdatesComplete {
 											channel_id, ..
 										} =>
-											*channel_id == claiming_chan_funding_outpoint.to_channel_id(),
+											*channel_id == self.channel_id_from_funding_txo(&claiming_chan_funding_outpoint),
 									}
 								}), "{:?}", *background_events);
 							}
@@ -5771,8 +6687,8 @@ where
 								event: events::Event::PaymentForwarded {
 									fee_earned_msat,
 									claim_from_onchain_tx: from_onchain,
-									prev_channel_id: Some(prev_outpoint.to_channel_id()),
-									next_channel_id: Some(next_channel_outpoint.to_channel_id()),
+									prev_channel_id: Some(self.channel_id_from_funding_txo(&prev_outpoint)),
+									next_channel_id: Some(self.channel_id_from_funding_txo(&next_channel_outpoint)),
 									outbound_amount_forwarded_msat: forwarded_htlc_value_msat,
 								},
 								downstream_counterparty_and_funding_outpoint: chan_to_release,
@@ -5915,6 +6831,91 @@ where
 		htlc_forwards
 	}
 
+	/// Sends our `tx_signatures` for an interactively constructed funding transaction, i.e. a
+	/// splice or the funding transaction of a dual-funded channel, and broadcasts it once it's fully
+	/// signed.
+	fn handle_splice_tx_signatures(&self, pending_msg_events: &mut Vec<MessageSendEvent>,
+		channel: &mut Channel<SP>, tx_signatures: Option<msgs::TxSignatures>, funding_broadcastable: Option<Transaction>)
+	{
+		let counterparty_node_id = channel.context.get_counterparty_node_id();
+		if let Some(msg) = tx_signatures {
+			pending_msg_events.push(events::MessageSendEvent::SendTxSignatures {
+				node_id: counterparty_node_id,
+				msg,
+			});
+		}
+		if let Some(tx) = funding_broadcastable {
+			let logger = WithChannelContext::from(&self.logger, &channel.context);
+			log_info!(logger, "Broadcasting funding transaction with txid {}", tx.txid());
+			self.tx_broadcaster.broadcast_transactions(&[&tx]);
+			if channel.context.get_funding_txo().map(|txo| txo.txid) == Some(tx.txid()) {
+				let mut pending_events = self.pending_events.lock().unwrap();
+				emit_channel_pending_event!(pending_events, channel);
+			} else if !channel.context.is_funding_rbf_pending() {
+				// Fee-bumps of a dual-funded channel's funding transaction aren't splices, so we
+				// don't generate a `SplicePending` for them.
+				if let (Some(new_funding_txo), Some(channel_value_satoshis)) = (
+					channel.context.get_pending_splice_funding_txo(), channel.context.get_pending_splice_channel_value_satoshis()
+				) {
+					self.pending_events.lock().unwrap().push_back((events::Event::SplicePending {
+						channel_id: channel.context.channel_id(),
+						user_channel_id: channel.context.get_user_id(),
+						counterparty_node_id,
+						new_funding_txo: new_funding_txo.into_bitcoin_outpoint(),
+						channel_value_satoshis,
+					}, None));
+				}
+			}
+		}
+	}
+
+	/// Handles a splice having been locked by both parties, moving the channel to its new short
+	/// channel id and announcing it under it.
+	fn complete_splice(&self, pending_msg_events: &mut Vec<MessageSendEvent>,
+		channel: &mut Channel<SP>, previous_short_channel_id: Option<u64>)
+	{
+		{
+			let mut short_to_chan_info = self.short_to_chan_info.write().unwrap();
+			if let Some(previous_scid) = previous_short_channel_id {
+				short_to_chan_info.remove(&previous_scid);
+			}
+			if let Some(real_scid) = channel.context.get_short_channel_id() {
+				short_to_chan_info.insert(real_scid, (channel.context.get_counterparty_node_id(), channel.context.channel_id()));
+			}
+		}
+		self.pending_events.lock().unwrap().push_back((events::Event::SpliceLocked {
+			channel_id: channel.context.channel_id(),
+			user_channel_id: channel.context.get_user_id(),
+			counterparty_node_id: channel.context.get_counterparty_node_id(),
+			funding_txo: channel.context.get_current_funding_txo().unwrap().into_bitcoin_outpoint(),
+			channel_value_satoshis: channel.context.get_value_satoshis(),
+		}, None));
+
+		let logger = WithChannelContext::from(&self.logger, &channel.context);
+		if let Some(msg) = channel.get_announcement_sigs(&self.node_signer, self.chain_hash,
+			&self.default_configuration, self.best_block.read().unwrap().height(), &&logger)
+		{
+			pending_msg_events.push(events::MessageSendEvent::SendAnnouncementSignatures {
+				node_id: channel.context.get_counterparty_node_id(),
+				msg,
+			});
+		}
+	}
+
+	fn handle_splice_aborted(&self, channel: &Channel<SP>, aborted_splice: AbortedSplice) {
+		if aborted_splice.is_funding_rbf {
+			// The channel remains funded by its previous funding transaction, which is still
+			// pending confirmation.
+			return;
+		}
+		self.pending_events.lock().unwrap().push_back((events::Event::SpliceFailed {
+			channel_id: channel.context.channel_id(),
+			user_channel_id: channel.context.get_user_id(),
+			counterparty_node_id: channel.context.get_counterparty_node_id(),
+			abandoned_funding_txo: aborted_splice.funding_txo.map(|txo| txo.into_bitcoin_outpoint()),
+		}, None));
+	}
+
 	fn channel_monitor_updated(&self, funding_txo: &OutPoint, highest_applied_update_id: u64, counterparty_node_id: Option<&PublicKey>) {
 		debug_assert!(self.total_consistency_lock.try_write().is_err()); // Caller holds read lock
 
@@ -5937,11 +6938,11 @@ where
 		peer_state_lock = peer_state_mutex_opt.unwrap().lock().unwrap();
 		let peer_state = &mut *peer_state_lock;
 		let channel =
-			if let Some(ChannelPhase::Funded(chan)) = peer_state.channel_by_id.get_mut(&funding_txo.to_channel_id()) {
+			if let Some(ChannelPhase::Funded(chan)) = peer_state.channel_by_id.get_mut(&self.channel_id_from_funding_txo(&funding_txo)) {
 				chan
 			} else {
 				let update_actions = peer_state.monitor_update_blocked_actions
-					.remove(&funding_txo.to_channel_id()).unwrap_or(Vec::new());
+					.remove(&self.channel_id_from_funding_txo(&funding_txo)).unwrap_or(Vec::new());
 				mem::drop(peer_state_lock);
 				mem::drop(per_peer_state);
 				self.handle_monitor_update_completion_actions(update_actions);
@@ -5979,7 +6980,7 @@ where
 	/// [`Event::OpenChannelRequest`]: events::Event::OpenChannelRequest
 	/// [`Event::ChannelClosed::user_channel_id`]: events::Event::ChannelClosed::user_channel_id
 	pub fn accept_inbound_channel(&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey, user_channel_id: u128) -> Result<(), APIError> {
-		self.do_accept_inbound_channel(temporary_channel_id, counterparty_node_id, false, user_channel_id)
+		self.do_accept_inbound_channel(temporary_channel_id, counterparty_node_id, false, user_channel_id, None)
 	}
 
 	/// Accepts a request to open a channel after a [`events::Event::OpenChannelRequest`], treating
@@ -6001,10 +7002,33 @@ where
 	/// [`Event::OpenChannelRequest`]: events::Event::OpenChannelRequest
 	/// [`Event::ChannelClosed::user_channel_id`]: events::Event::ChannelClosed::user_channel_id
 	pub fn accept_inbound_channel_from_trusted_peer_0conf(&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey, user_channel_id: u128) -> Result<(), APIError> {
-		self.do_accept_inbound_channel(temporary_channel_id, counterparty_node_id, true, user_channel_id)
+		self.do_accept_inbound_channel(temporary_channel_id, counterparty_node_id, true, user_channel_id, None)
 	}
 
-	fn do_accept_inbound_channel(&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey, accept_0conf: bool, user_channel_id: u128) -> Result<(), APIError> {
+	/// Accepts a request to open a dual-funded channel after an [`Event::OpenChannelRequest`],
+	/// contributing the given funds to the channel's funding transaction.
+	///
+	/// Once the funding transaction has been negotiated, an
+	/// [`Event::FundingTransactionReadyForSigning`] will be generated if we contributed inputs,
+	/// which must be signed and passed back via [`ChannelManager::funding_transaction_signed`].
+	///
+	/// Otherwise behaves like [`ChannelManager::accept_inbound_channel`], which may also be used
+	/// to accept a dual-funded channel without contributing to it. Returns an
+	/// [`APIError::APIMisuseError`] if the request was not for a dual-funded channel.
+	///
+	/// [`Event::OpenChannelRequest`]: events::Event::OpenChannelRequest
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	pub fn accept_inbound_channel_with_contribution(
+		&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey, user_channel_id: u128,
+		contribution: FundingContribution,
+	) -> Result<(), APIError> {
+		self.do_accept_inbound_channel(temporary_channel_id, counterparty_node_id, false, user_channel_id, Some(contribution))
+	}
+
+	fn do_accept_inbound_channel(
+		&self, temporary_channel_id: &ChannelId, counterparty_node_id: &PublicKey, accept_0conf: bool,
+		user_channel_id: u128, contribution: Option<FundingContribution>,
+	) -> Result<(), APIError> {
 
 		let logger = WithContext::from(&self.logger, Some(*counterparty_node_id), Some(*temporary_channel_id));
 		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
@@ -6027,19 +7051,36 @@ where
 		// happening and return an error. N.B. that we create channel with an outbound SCID of zero so
 		// that we can delay allocating the SCID until after we're sure that the checks below will
 		// succeed.
-		let mut channel = match peer_state.inbound_channel_request_by_id.remove(temporary_channel_id) {
+		if contribution.is_some() && !matches!(
+			peer_state.inbound_channel_request_by_id.get(temporary_channel_id),
+			Some(InboundChannelRequest { open_channel_msg: OpenChannelMessage::V2(_), .. })
+		) {
+			let err_str = "No such dual-funded channel awaiting to be accepted.".to_owned();
+			log_error!(logger, "{}", err_str);
+
+			return Err(APIError::APIMisuseError { err: err_str });
+		}
+		let mut channel_phase = match peer_state.inbound_channel_request_by_id.remove(temporary_channel_id) {
 			Some(unaccepted_channel) => {
 				let best_block_height = self.best_block.read().unwrap().height();
-				InboundV1Channel::new(&self.fee_estimator, &self.entropy_source, &self.signer_provider,
-					counterparty_node_id.clone(), &self.channel_type_features(), &peer_state.latest_features,
-					&unaccepted_channel.open_channel_msg, user_channel_id, &self.default_configuration, best_block_height,
-					&self.logger, accept_0conf).map_err(|e| {
-						let err_str = e.to_string();
-						log_error!(logger, "{}", err_str);
-
-						APIError::ChannelUnavailable { err: err_str }
-					})
-				}
+				match unaccepted_channel.open_channel_msg {
+					OpenChannelMessage::V1(msg) => InboundV1Channel::new(&self.fee_estimator, &self.entropy_source,
+						&self.signer_provider, counterparty_node_id.clone(), &self.channel_type_features(),
+						&peer_state.latest_features, &msg, user_channel_id, &self.default_configuration,
+						best_block_height, &self.logger, accept_0conf).map(ChannelPhase::UnfundedInboundV1),
+					OpenChannelMessage::V2(msg) => InboundV2Channel::new(&self.fee_estimator, &self.entropy_source,
+						&self.signer_provider, self.get_our_node_id(), counterparty_node_id.clone(),
+						&self.channel_type_features(), &peer_state.latest_features, &msg,
+						contribution.unwrap_or(FundingContribution { value_satoshis: 0, inputs: Vec::new(), change_script: None }),
+						user_channel_id, &self.default_configuration, best_block_height, &self.logger, accept_0conf,
+					).map(ChannelPhase::UnfundedInboundV2),
+				}.map_err(|e| {
+					let err_str = e.to_string();
+					log_error!(logger, "{}", err_str);
+
+					APIError::ChannelUnavailable { err: err_str }
+				})
+			}
 			_ => {
 				let err_str = "No such channel awaiting to be accepted.".to_owned();
 				log_error!(logger, "{}", err_str);
@@ -6047,13 +7088,14 @@ where
 				Err(APIError::APIMisuseError { err: err_str })
 			}
 		}?;
+		let channel = channel_phase.context_mut();
 
 		if accept_0conf {
 			// This should have been correctly configured by the call to InboundV1Channel::new.
-			debug_assert!(channel.context.minimum_depth().unwrap() == 0);
-		} else if channel.context.get_channel_type().requires_zero_conf() {
+			debug_assert!(channel.minimum_depth().unwrap() == 0);
+		} else if channel.get_channel_type().requires_zero_conf() {
 			let send_msg_err_event = events::MessageSendEvent::HandleError {
-				node_id: channel.context.get_counterparty_node_id(),
+				node_id: channel.get_counterparty_node_id(),
 				action: msgs::ErrorAction::SendErrorMessage{
 					msg: msgs::ErrorMessage { channel_id: temporary_channel_id.clone(), data: "No zero confirmation channels accepted".to_owned(), }
 				}
@@ -6069,7 +7111,7 @@ where
 			// channels per-peer we can accept channels from a peer with existing ones.
 			if is_only_peer_channel && peers_without_funded_channels >= MAX_UNFUNDED_CHANNEL_PEERS {
 				let send_msg_err_event = events::MessageSendEvent::HandleError {
-					node_id: channel.context.get_counterparty_node_id(),
+					node_id: channel.get_counterparty_node_id(),
 					action: msgs::ErrorAction::SendErrorMessage{
 						msg: msgs::ErrorMessage { channel_id: temporary_channel_id.clone(), data: "Have too many peers with unfunded channels, not accepting new ones".to_owned(), }
 					}
@@ -6084,14 +7126,25 @@ where
 
 		// Now that we know we have a channel, assign an outbound SCID alias.
 		let outbound_scid_alias = self.create_and_insert_outbound_scid_alias();
-		channel.context.set_outbound_scid_alias(outbound_scid_alias);
+		channel.set_outbound_scid_alias(outbound_scid_alias);
 
-		peer_state.pending_msg_events.push(events::MessageSendEvent::SendAcceptChannel {
-			node_id: channel.context.get_counterparty_node_id(),
-			msg: channel.accept_inbound_channel(),
-		});
+		// Dual-funded channels already have their final channel ID, which our counterparty will
+		// use from here on.
+		let channel_id = channel.channel_id();
+		let msg_send_event = match &mut channel_phase {
+			ChannelPhase::UnfundedInboundV1(channel) => events::MessageSendEvent::SendAcceptChannel {
+				node_id: channel.context.get_counterparty_node_id(),
+				msg: channel.accept_inbound_channel(),
+			},
+			ChannelPhase::UnfundedInboundV2(channel) => events::MessageSendEvent::SendAcceptChannelV2 {
+				node_id: channel.context.get_counterparty_node_id(),
+				msg: channel.accept_inbound_dual_funded_channel(),
+			},
+			_ => unreachable!(),
+		};
+		peer_state.pending_msg_events.push(msg_send_event);
 
-		peer_state.channel_by_id.insert(temporary_channel_id.clone(), ChannelPhase::UnfundedInboundV1(channel));
+		peer_state.channel_by_id.insert(channel_id, channel_phase);
 
 		Ok(())
 	}
@@ -6139,7 +7192,12 @@ where
 						num_unfunded_channels += 1;
 					}
 				},
-				ChannelPhase::UnfundedOutboundV1(_) => {
+				ChannelPhase::UnfundedInboundV2(chan) => {
+					if chan.context.minimum_depth().unwrap_or(1) != 0 {
+						num_unfunded_channels += 1;
+					}
+				},
+				ChannelPhase::UnfundedOutboundV1(_) | ChannelPhase::UnfundedOutboundV2(_) => {
 					// Outbound channels don't contribute to the unfunded count in the DoS context.
 					continue;
 				}
@@ -6148,15 +7206,22 @@ where
 		num_unfunded_channels + peer.inbound_channel_request_by_id.len()
 	}
 
-	fn internal_open_channel(&self, counterparty_node_id: &PublicKey, msg: &msgs::OpenChannel) -> Result<(), MsgHandleErrInternal> {
+	fn internal_open_channel(&self, counterparty_node_id: &PublicKey, msg: OpenChannelMessage) -> Result<(), MsgHandleErrInternal> {
 		// Note that the ChannelManager is NOT re-persisted on disk after this, so any changes are
 		// likely to be lost on restart!
-		if msg.chain_hash != self.chain_hash {
-			return Err(MsgHandleErrInternal::send_err_msg_no_close("Unknown genesis block hash".to_owned(), msg.temporary_channel_id.clone()));
+		let temporary_channel_id = msg.temporary_channel_id();
+		if msg.chain_hash() != self.chain_hash {
+			return Err(MsgHandleErrInternal::send_err_msg_no_close("Unknown genesis block hash".to_owned(), temporary_channel_id));
 		}
 
 		if !self.default_configuration.accept_inbound_channels {
-			return Err(MsgHandleErrInternal::send_err_msg_no_close("No inbound channels accepted".to_owned(), msg.temporary_channel_id.clone()));
+			return Err(MsgHandleErrInternal::send_err_msg_no_close("No inbound channels accepted".to_owned(), temporary_channel_id));
+		}
+
+		if let OpenChannelMessage::V2(_) = msg {
+			if !self.default_configuration.enable_dual_funded_channels {
+				return Err(MsgHandleErrInternal::send_err_msg_no_close("Dual-funded channels not supported".to_owned(), temporary_channel_id));
+			}
 		}
 
 		// Get the number of peers with channels, but without funded ones. We don't care too much
@@ -6169,7 +7234,7 @@ where
 		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
 		    .ok_or_else(|| {
 				debug_assert!(false);
-				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.temporary_channel_id.clone())
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), temporary_channel_id)
 			})?;
 		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 		let peer_state = &mut *peer_state_lock;
@@ -6183,39 +7248,50 @@ where
 		{
 			return Err(MsgHandleErrInternal::send_err_msg_no_close(
 				"Have too many peers with unfunded channels, not accepting new ones".to_owned(),
-				msg.temporary_channel_id.clone()));
+				temporary_channel_id));
 		}
 
 		let best_block_height = self.best_block.read().unwrap().height();
 		if Self::unfunded_channel_count(peer_state, best_block_height) >= MAX_UNFUNDED_CHANS_PER_PEER {
 			return Err(MsgHandleErrInternal::send_err_msg_no_close(
 				format!("Refusing more than {} unfunded channels.", MAX_UNFUNDED_CHANS_PER_PEER),
-				msg.temporary_channel_id.clone()));
+				temporary_channel_id));
 		}
 
-		let channel_id = msg.temporary_channel_id;
+		let channel_id = temporary_channel_id;
 		let channel_exists = peer_state.has_channel(&channel_id);
 		if channel_exists {
-			return Err(MsgHandleErrInternal::send_err_msg_no_close("temporary_channel_id collision for the same peer!".to_owned(), msg.temporary_channel_id.clone()));
+			return Err(MsgHandleErrInternal::send_err_msg_no_close("temporary_channel_id collision for the same peer!".to_owned(), temporary_channel_id));
 		}
 
 		// If we're doing manual acceptance checks on the channel, then defer creation until we're sure we want to accept.
 		if self.default_configuration.manually_accept_inbound_channels {
-			let channel_type = channel::channel_type_from_open_channel(
-					&msg, &peer_state.latest_features, &self.channel_type_features()
-				).map_err(|e|
-					MsgHandleErrInternal::from_chan_no_close(e, msg.temporary_channel_id)
-				)?;
+			let (channel_type_res, funding_satoshis, channel_negotiation_type) = match &msg {
+				OpenChannelMessage::V1(msg) => (
+					channel::channel_type_from_open_channel(msg, &peer_state.latest_features, &self.channel_type_features()),
+					msg.funding_satoshis, InboundChannelFunds::PushMsat(msg.push_msat),
+				),
+				OpenChannelMessage::V2(msg) => (
+					channel::channel_type_from_open_channel(&channel::open_channel_v1_from_v2(msg, 0),
+						&peer_state.latest_features, &self.channel_type_features()),
+					msg.funding_satoshis, InboundChannelFunds::DualFunded {
+						funding_feerate_sat_per_1000_weight: msg.funding_feerate_sat_per_1000_weight,
+					},
+				),
+			};
+			let channel_type = channel_type_res.map_err(|e|
+				MsgHandleErrInternal::from_chan_no_close(e, temporary_channel_id)
+			)?;
 			let mut pending_events = self.pending_events.lock().unwrap();
 			pending_events.push_back((events::Event::OpenChannelRequest {
-				temporary_channel_id: msg.temporary_channel_id.clone(),
+				temporary_channel_id,
 				counterparty_node_id: counterparty_node_id.clone(),
-				funding_satoshis: msg.funding_satoshis,
-				push_msat: msg.push_msat,
+				funding_satoshis,
+				channel_negotiation_type,
 				channel_type,
 			}, None));
 			peer_state.inbound_channel_request_by_id.insert(channel_id, InboundChannelRequest {
-				open_channel_msg: msg.clone(),
+				open_channel_msg: msg,
 				ticks_remaining: UNACCEPTED_INBOUND_CHANNEL_AGE_LIMIT_TICKS,
 			});
 			return Ok(());
@@ -6225,32 +7301,51 @@ where
 		let mut random_bytes = [0u8; 16];
 		random_bytes.copy_from_slice(&self.entropy_source.get_secure_random_bytes()[..16]);
 		let user_channel_id = u128::from_be_bytes(random_bytes);
-		let mut channel = match InboundV1Channel::new(&self.fee_estimator, &self.entropy_source, &self.signer_provider,
-			counterparty_node_id.clone(), &self.channel_type_features(), &peer_state.latest_features, msg, user_channel_id,
-			&self.default_configuration, best_block_height, &self.logger, /*is_0conf=*/false)
-		{
+		let channel_phase_res = match &msg {
+			OpenChannelMessage::V1(msg) => InboundV1Channel::new(&self.fee_estimator, &self.entropy_source,
+				&self.signer_provider, counterparty_node_id.clone(), &self.channel_type_features(),
+				&peer_state.latest_features, msg, user_channel_id, &self.default_configuration, best_block_height,
+				&self.logger, /*is_0conf=*/false).map(ChannelPhase::UnfundedInboundV1),
+			// Without manual acceptance we have no funds to contribute to dual-funded channels.
+			OpenChannelMessage::V2(msg) => InboundV2Channel::new(&self.fee_estimator, &self.entropy_source,
+				&self.signer_provider, self.get_our_node_id(), counterparty_node_id.clone(),
+				&self.channel_type_features(), &peer_state.latest_features, msg,
+				FundingContribution { value_satoshis: 0, inputs: Vec::new(), change_script: None }, user_channel_id,
+				&self.default_configuration, best_block_height, &self.logger, /*is_0conf=*/false,
+			).map(ChannelPhase::UnfundedInboundV2),
+		};
+		let mut channel_phase = match channel_phase_res {
 			Err(e) => {
-				return Err(MsgHandleErrInternal::from_chan_no_close(e, msg.temporary_channel_id));
+				return Err(MsgHandleErrInternal::from_chan_no_close(e, temporary_channel_id));
 			},
 			Ok(res) => res
 		};
 
-		let channel_type = channel.context.get_channel_type();
+		let channel_type = channel_phase.context().get_channel_type();
 		if channel_type.requires_zero_conf() {
-			return Err(MsgHandleErrInternal::send_err_msg_no_close("No zero confirmation channels accepted".to_owned(), msg.temporary_channel_id.clone()));
+			return Err(MsgHandleErrInternal::send_err_msg_no_close("No zero confirmation channels accepted".to_owned(), temporary_channel_id));
 		}
 		if channel_type.requires_anchors_zero_fee_htlc_tx() {
-			return Err(MsgHandleErrInternal::send_err_msg_no_close("No channels with anchor outputs accepted".to_owned(), msg.temporary_channel_id.clone()));
+			return Err(MsgHandleErrInternal::send_err_msg_no_close("No channels with anchor outputs accepted".to_owned(), temporary_channel_id));
 		}
 
 		let outbound_scid_alias = self.create_and_insert_outbound_scid_alias();
-		channel.context.set_outbound_scid_alias(outbound_scid_alias);
+		channel_phase.context_mut().set_outbound_scid_alias(outbound_scid_alias);
 
-		peer_state.pending_msg_events.push(events::MessageSendEvent::SendAcceptChannel {
-			node_id: counterparty_node_id.clone(),
-			msg: channel.accept_inbound_channel(),
-		});
-		peer_state.channel_by_id.insert(channel_id, ChannelPhase::UnfundedInboundV1(channel));
+		let channel_id = channel_phase.context().channel_id();
+		let msg_send_event = match &mut channel_phase {
+			ChannelPhase::UnfundedInboundV1(channel) => events::MessageSendEvent::SendAcceptChannel {
+				node_id: counterparty_node_id.clone(),
+				msg: channel.accept_inbound_channel(),
+			},
+			ChannelPhase::UnfundedInboundV2(channel) => events::MessageSendEvent::SendAcceptChannelV2 {
+				node_id: counterparty_node_id.clone(),
+				msg: channel.accept_inbound_dual_funded_channel(),
+			},
+			_ => unreachable!(),
+		};
+		peer_state.pending_msg_events.push(msg_send_event);
+		peer_state.channel_by_id.insert(channel_id, channel_phase);
 		Ok(())
 	}
 
@@ -6292,6 +7387,44 @@ where
 		Ok(())
 	}
 
+	fn internal_accept_channel_v2(&self, counterparty_node_id: &PublicKey, msg: &msgs::AcceptChannelV2) -> Result<(), MsgHandleErrInternal> {
+		// Note that the ChannelManager is NOT re-persisted on disk after this, so any changes are
+		// likely to be lost on restart!
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.temporary_channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		let first_msg = match peer_state.channel_by_id.entry(msg.temporary_channel_id) {
+			hash_map::Entry::Occupied(mut phase) => {
+				match phase.get_mut() {
+					ChannelPhase::UnfundedOutboundV2(chan) => {
+						try_chan_phase_entry!(self, chan.accept_channel_v2(&msg, &self.default_configuration.channel_handshake_limits,
+							&peer_state.latest_features, &self.entropy_source, self.get_our_node_id()), phase)
+					},
+					_ => {
+						return Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got an unexpected accept_channel2 message from peer with counterparty_node_id {}", counterparty_node_id), msg.temporary_channel_id));
+					}
+				}
+			},
+			hash_map::Entry::Vacant(_) => return Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), msg.temporary_channel_id))
+		};
+
+		// The channel is now known by its final ID, derived from both parties' revocation basepoints.
+		let mut channel_phase = peer_state.channel_by_id.remove(&msg.temporary_channel_id).unwrap();
+		let channel_id = channel_phase.context().channel_id();
+		if peer_state.channel_by_id.contains_key(&channel_id) {
+			let err = ChannelError::Close("Already had channel with the new channel_id".to_owned());
+			return Err(convert_chan_phase_err!(self, err, &mut channel_phase, &channel_id).1);
+		}
+		peer_state.channel_by_id.insert(channel_id, channel_phase);
+		peer_state.pending_msg_events.push(first_msg.into_msg_send_event(*counterparty_node_id));
+		Ok(())
+	}
+
 	fn internal_funding_created(&self, counterparty_node_id: &PublicKey, msg: &msgs::FundingCreated) -> Result<(), MsgHandleErrInternal> {
 		let best_block = *self.best_block.read().unwrap();
 
@@ -6355,9 +7488,11 @@ where
 						fail_chan!("The funding_created message had the same funding_txid as an existing channel - funding is not possible");
 					},
 					hash_map::Entry::Vacant(i_e) => {
-						let monitor_res = self.chain_monitor.watch_channel(monitor.get_funding_txo().0, monitor);
+						let funding_txo = monitor.get_funding_txo().0;
+						let monitor_res = self.chain_monitor.watch_channel(funding_txo, monitor);
 						if let Ok(persist_state) = monitor_res {
 							i_e.insert(chan.context.get_counterparty_node_id());
+							self.outpoint_to_channel_id.lock().unwrap().insert(funding_txo, funded_channel_id);
 							mem::drop(outpoint_to_peer_lock);
 
 							// There's no problem signing a counterparty's funding transaction if our monitor
@@ -6413,7 +7548,9 @@ where
 						chan.funding_signed(&msg, best_block, &self.signer_provider, &&logger);
 					match res {
 						Ok((mut chan, monitor)) => {
-							if let Ok(persist_status) = self.chain_monitor.watch_channel(chan.context.get_funding_txo().unwrap(), monitor) {
+							let funding_txo = chan.context.get_funding_txo().unwrap();
+							if let Ok(persist_status) = self.chain_monitor.watch_channel(funding_txo, monitor) {
+								self.outpoint_to_channel_id.lock().unwrap().insert(funding_txo, msg.channel_id);
 								// We really should be able to insert here without doing a second
 								// lookup, but sadly rust stdlib doesn't currently allow keeping
 								// the original Entry around with the value removed.
@@ -6547,7 +7684,8 @@ where
 								peer_state_lock, peer_state, per_peer_state, chan);
 						}
 					},
-					ChannelPhase::UnfundedInboundV1(_) | ChannelPhase::UnfundedOutboundV1(_) => {
+					ChannelPhase::UnfundedInboundV1(_) | ChannelPhase::UnfundedOutboundV1(_) |
+					ChannelPhase::UnfundedInboundV2(_) | ChannelPhase::UnfundedOutboundV2(_) => {
 						let context = phase.context_mut();
 						let logger = WithChannelContext::from(&self.logger, context);
 						log_error!(logger, "Immediately closing unfunded channel {} as peer asked to cooperatively shut it down (which is unnecessary)", &msg.channel_id);
@@ -6732,7 +7870,8 @@ where
 								msg.channel_id);
 							peer_state.actions_blocking_raa_monitor_updates.entry(msg.channel_id)
 								.or_insert_with(Vec::new)
-								.push(RAAMonitorUpdateBlockingAction::from_prev_hop_data(&prev_hop));
+								.push(RAAMonitorUpdateBlockingAction::from_prev_hop_data(
+									&prev_hop, self.channel_id_from_funding_txo(&prev_hop.outpoint)));
 						}
 						// Note that we do not need to push an `actions_blocking_raa_monitor_updates`
 						// entry here, even though we *do* need to block the next RAA monitor update.
@@ -6809,6 +7948,7 @@ where
 	}
 
 	fn internal_commitment_signed(&self, counterparty_node_id: &PublicKey, msg: &msgs::CommitmentSigned) -> Result<(), MsgHandleErrInternal> {
+		let best_block = *self.best_block.read().unwrap();
 		let per_peer_state = self.per_peer_state.read().unwrap();
 		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
 			.ok_or_else(|| {
@@ -6828,6 +7968,52 @@ where
 							peer_state, per_peer_state, chan);
 					}
 					Ok(())
+				} else if matches!(chan_phase_entry.get(), ChannelPhase::UnfundedOutboundV2(_) | ChannelPhase::UnfundedInboundV2(_)) {
+					// The initial commitment of a dual-funded channel, which funds the channel.
+					let logger = WithChannelContext::from(&self.logger, chan_phase_entry.get().context());
+					let res = match chan_phase_entry.remove() {
+						ChannelPhase::UnfundedOutboundV2(chan) => chan.commitment_signed(&msg, best_block, &self.signer_provider, &&logger)
+							.map_err(|(chan, e)| (ChannelPhase::UnfundedOutboundV2(chan), e)),
+						ChannelPhase::UnfundedInboundV2(chan) => chan.commitment_signed(&msg, best_block, &self.signer_provider, &&logger)
+							.map_err(|(chan, e)| (ChannelPhase::UnfundedInboundV2(chan), e)),
+						_ => unreachable!(),
+					};
+					let (mut chan, monitor) = match res {
+						Ok(res) => res,
+						Err((mut chan_phase, e)) => {
+							debug_assert!(matches!(e, ChannelError::Close(_)),
+								"We don't have a channel anymore, so the error better have expected close");
+							return Err(convert_chan_phase_err!(self, e, &mut chan_phase, &msg.channel_id).1);
+						},
+					};
+					let funding_txo = chan.context.get_funding_txo().unwrap();
+					let mut outpoint_to_peer_lock = self.outpoint_to_peer.lock().unwrap();
+					let monitor_res = match outpoint_to_peer_lock.entry(funding_txo) {
+						hash_map::Entry::Occupied(_) => Err(()),
+						hash_map::Entry::Vacant(i_e) => {
+							let monitor_res = self.chain_monitor.watch_channel(funding_txo, monitor);
+							if monitor_res.is_ok() {
+								i_e.insert(*counterparty_node_id);
+								self.outpoint_to_channel_id.lock().unwrap().insert(funding_txo, msg.channel_id);
+							}
+							monitor_res
+						},
+					};
+					mem::drop(outpoint_to_peer_lock);
+					if let Ok(persist_status) = monitor_res {
+						if let ChannelPhase::Funded(chan) = peer_state.channel_by_id.entry(msg.channel_id).or_insert(ChannelPhase::Funded(chan)) {
+							handle_new_monitor_update!(self, persist_status, peer_state_lock, peer_state, per_peer_state, chan, INITIAL_MONITOR);
+						} else {
+							unreachable!("This must be a funded channel as we just inserted it.");
+						}
+						Ok(())
+					} else {
+						// As with V1 channels, we must not remove the existing channel with the
+						// same funding outpoint from our maps.
+						let e = ChannelError::Close("Channel funding outpoint was a duplicate".to_owned());
+						chan.unset_funding_info(msg.channel_id);
+						Err(convert_chan_phase_err!(self, e, &mut ChannelPhase::Funded(chan), &msg.channel_id).1)
+					}
 				} else {
 					return try_chan_phase_entry!(self, Err(ChannelError::Close(
 						"Got a commitment_signed message for an unfunded channel!".into())), chan_phase_entry);
@@ -6837,6 +8023,296 @@ where
 		}
 	}
 
+	/// Handles a message which is part of negotiating a splice, sending a `tx_abort` if `f` rejects
+	/// it.
+	fn internal_splice_negotiation<FN>(
+		&self, counterparty_node_id: &PublicKey, channel_id: ChannelId, f: FN,
+	) -> Result<(), MsgHandleErrInternal>
+	where FN: FnOnce(&mut Channel<SP>, &mut Vec<MessageSendEvent>) -> Result<(), msgs::TxAbort> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.get_mut(&channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let splice_was_pending = chan.context.is_splice_pending();
+				let is_funding_rbf = chan.context.is_funding_rbf_pending();
+				if let Err(msg) = f(chan, &mut peer_state.pending_msg_events) {
+					peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxAbort {
+						node_id: *counterparty_node_id,
+						msg,
+					});
+					if splice_was_pending && !chan.context.is_splice_pending() {
+						self.handle_splice_aborted(chan, AbortedSplice { funding_txo: None, is_funding_rbf });
+					}
+				}
+				Ok(())
+			},
+			Some(_) => Err(MsgHandleErrInternal::send_err_msg_no_close(
+				"Got a splice message for an unfunded channel!".to_owned(), channel_id)),
+			None => Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), channel_id))
+		}
+	}
+
+	/// Handles a message negotiating the funding transaction of a dual-funded channel which has yet
+	/// to be funded, closing the channel if `f` rejects it, or otherwise calls
+	/// [`Self::internal_splice_negotiation`].
+	///
+	/// Returns whether the channel was yet to be funded.
+	fn internal_unfunded_v2_negotiation<FN>(
+		&self, counterparty_node_id: &PublicKey, channel_id: ChannelId, f: FN,
+	) -> Result<bool, MsgHandleErrInternal>
+	where FN: FnOnce(&mut ChannelPhase<SP>, &mut Vec<MessageSendEvent>) -> Result<(), msgs::TxAbort> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.entry(channel_id) {
+			hash_map::Entry::Occupied(mut chan_phase_entry) => match chan_phase_entry.get() {
+				ChannelPhase::UnfundedOutboundV2(_) | ChannelPhase::UnfundedInboundV2(_) => {
+					if let Err(tx_abort) = f(chan_phase_entry.get_mut(), &mut peer_state.pending_msg_events) {
+						// A failed negotiation of the funding transaction can't be retried, so we
+						// forget the channel.
+						let err = String::from_utf8_lossy(&tx_abort.data).into_owned();
+						peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxAbort {
+							node_id: *counterparty_node_id,
+							msg: tx_abort,
+						});
+						return try_chan_phase_entry!(self, Err(ChannelError::Close(err)), chan_phase_entry);
+					}
+					Ok(true)
+				},
+				_ => Ok(false),
+			},
+			hash_map::Entry::Vacant(_) => Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), channel_id))
+		}
+	}
+
+	/// Handles an interactive transaction construction message which we respond to with another,
+	/// whether negotiating the funding transaction of a dual-funded channel or a splice.
+	fn internal_interactive_tx_msg<FN>(
+		&self, counterparty_node_id: &PublicKey, channel_id: ChannelId, f: FN,
+	) -> Result<(), MsgHandleErrInternal>
+	where FN: Fn(&mut ChannelContext<SP>) -> Result<InteractiveTxMessageSend, msgs::TxAbort> {
+		let handle_msg = |context: &mut ChannelContext<SP>, pending_msg_events: &mut Vec<MessageSendEvent>| -> Result<(), msgs::TxAbort> {
+			let msg_send = f(context)?;
+			pending_msg_events.push(msg_send.into_msg_send_event(*counterparty_node_id));
+			Ok(())
+		};
+		if self.internal_unfunded_v2_negotiation(counterparty_node_id, channel_id,
+			|phase, pending_msg_events| handle_msg(phase.context_mut(), pending_msg_events))?
+		{
+			return Ok(());
+		}
+		self.internal_splice_negotiation(counterparty_node_id, channel_id,
+			|chan, pending_msg_events| handle_msg(&mut chan.context, pending_msg_events))
+	}
+
+	fn internal_splice(&self, counterparty_node_id: &PublicKey, msg: &msgs::Splice) -> Result<(), MsgHandleErrInternal> {
+		self.internal_splice_negotiation(counterparty_node_id, msg.channel_id, |chan, pending_msg_events| {
+			if !self.default_configuration.enable_splicing {
+				return Err(msgs::TxAbort { channel_id: msg.channel_id, data: b"Splicing is not enabled".to_vec() });
+			}
+			let logger = WithChannelContext::from(&self.logger, &chan.context);
+			let splice_ack = chan.splice(msg, self.chain_hash, &self.entropy_source, self.get_our_node_id(), &&logger)?;
+			pending_msg_events.push(events::MessageSendEvent::SendSpliceAck {
+				node_id: *counterparty_node_id,
+				msg: splice_ack,
+			});
+			Ok(())
+		})
+	}
+
+	fn internal_splice_ack(&self, counterparty_node_id: &PublicKey, msg: &msgs::SpliceAck) -> Result<(), MsgHandleErrInternal> {
+		self.internal_splice_negotiation(counterparty_node_id, msg.channel_id, |chan, pending_msg_events| {
+			let logger = WithChannelContext::from(&self.logger, &chan.context);
+			let msg_send = chan.splice_ack(msg, &self.entropy_source, self.get_our_node_id(), &&logger)?;
+			pending_msg_events.push(msg_send.into_msg_send_event(*counterparty_node_id));
+			Ok(())
+		})
+	}
+
+	/// Sends our response to a `tx_complete` and, if the funding transaction has been negotiated,
+	/// our `commitment_signed` for it and an [`Event::FundingTransactionReadyForSigning`] if we
+	/// need to sign it.
+	///
+	/// [`Event::FundingTransactionReadyForSigning`]: events::Event::FundingTransactionReadyForSigning
+	fn handle_tx_complete_responses(&self, pending_msg_events: &mut Vec<MessageSendEvent>,
+		context: &ChannelContext<SP>, msg_send: Option<InteractiveTxMessageSend>, funding_tx_negotiated: Option<FundingTxNegotiated>)
+	{
+		let counterparty_node_id = context.get_counterparty_node_id();
+		if let Some(msg_send) = msg_send {
+			pending_msg_events.push(msg_send.into_msg_send_event(counterparty_node_id));
+		}
+		if let Some(funding_tx_negotiated) = funding_tx_negotiated {
+			pending_msg_events.push(events::MessageSendEvent::UpdateHTLCs {
+				node_id: counterparty_node_id,
+				updates: msgs::CommitmentUpdate {
+					update_add_htlcs: Vec::new(),
+					update_fulfill_htlcs: Vec::new(),
+					update_fail_htlcs: Vec::new(),
+					update_fail_malformed_htlcs: Vec::new(),
+					update_fee: None,
+					commitment_signed: funding_tx_negotiated.commitment_signed,
+				},
+			});
+			if let Some(unsigned_transaction) = funding_tx_negotiated.unsigned_transaction {
+				self.pending_events.lock().unwrap().push_back((events::Event::FundingTransactionReadyForSigning {
+					channel_id: context.channel_id(),
+					counterparty_node_id,
+					user_channel_id: context.get_user_id(),
+					unsigned_transaction,
+				}, None));
+			}
+		}
+	}
+
+	fn internal_tx_complete(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxComplete) -> Result<(), MsgHandleErrInternal> {
+		if self.internal_unfunded_v2_negotiation(counterparty_node_id, msg.channel_id, |phase, pending_msg_events| {
+			let logger = WithChannelContext::from(&self.logger, phase.context());
+			let (msg_send, funding_tx_negotiated) = match phase {
+				ChannelPhase::UnfundedOutboundV2(chan) => chan.tx_complete(msg, &&logger)?,
+				ChannelPhase::UnfundedInboundV2(chan) => chan.tx_complete(msg, &&logger)?,
+				_ => unreachable!(),
+			};
+			self.handle_tx_complete_responses(pending_msg_events, phase.context(), msg_send, funding_tx_negotiated);
+			Ok(())
+		})? {
+			return Ok(());
+		}
+		self.internal_splice_negotiation(counterparty_node_id, msg.channel_id, |chan, pending_msg_events| {
+			let logger = WithChannelContext::from(&self.logger, &chan.context);
+			let (msg_send, funding_tx_negotiated) = chan.tx_complete(msg, &&logger)?;
+			self.handle_tx_complete_responses(pending_msg_events, &chan.context, msg_send, funding_tx_negotiated);
+			Ok(())
+		})
+	}
+
+	fn internal_tx_init_rbf(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxInitRbf) -> Result<(), MsgHandleErrInternal> {
+		self.internal_splice_negotiation(counterparty_node_id, msg.channel_id, |chan, pending_msg_events| {
+			let logger = WithChannelContext::from(&self.logger, &chan.context);
+			let tx_ack_rbf = chan.tx_init_rbf(msg, &self.entropy_source, self.get_our_node_id(), &&logger)?;
+			pending_msg_events.push(events::MessageSendEvent::SendTxAckRbf {
+				node_id: *counterparty_node_id,
+				msg: tx_ack_rbf,
+			});
+			Ok(())
+		})
+	}
+
+	fn internal_tx_ack_rbf(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAckRbf) -> Result<(), MsgHandleErrInternal> {
+		self.internal_splice_negotiation(counterparty_node_id, msg.channel_id, |chan, pending_msg_events| {
+			let logger = WithChannelContext::from(&self.logger, &chan.context);
+			let msg_send = chan.tx_ack_rbf(msg, &self.entropy_source, self.get_our_node_id(), &&logger)?;
+			pending_msg_events.push(msg_send.into_msg_send_event(*counterparty_node_id));
+			Ok(())
+		})
+	}
+
+	fn internal_tx_signatures(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxSignatures) -> Result<(), MsgHandleErrInternal> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.entry(msg.channel_id) {
+			hash_map::Entry::Occupied(mut chan_phase_entry) => {
+				if let ChannelPhase::Funded(chan) = chan_phase_entry.get_mut() {
+					let logger = WithChannelContext::from(&self.logger, &chan.context);
+					let (tx_signatures, funding_tx) = try_chan_phase_entry!(self, chan.tx_signatures(&msg, &&logger), chan_phase_entry);
+					self.handle_splice_tx_signatures(&mut peer_state.pending_msg_events, chan, tx_signatures, funding_tx);
+				} else {
+					return try_chan_phase_entry!(self, Err(ChannelError::Close(
+						"Got a tx_signatures message for an unfunded channel!".into())), chan_phase_entry);
+				}
+			},
+			hash_map::Entry::Vacant(_) => return Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), msg.channel_id))
+		}
+		Ok(())
+	}
+
+	fn internal_tx_abort(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAbort) -> Result<(), MsgHandleErrInternal> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.get_mut(&msg.channel_id) {
+			Some(ChannelPhase::Funded(chan)) => {
+				let logger = WithChannelContext::from(&self.logger, &chan.context);
+				if let Some((tx_abort, aborted_splice)) = chan.tx_abort(msg, &&logger) {
+					peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxAbort {
+						node_id: *counterparty_node_id,
+						msg: tx_abort,
+					});
+					self.handle_splice_aborted(chan, aborted_splice);
+				}
+			},
+			Some(ChannelPhase::UnfundedOutboundV2(_)) | Some(ChannelPhase::UnfundedInboundV2(_)) => {
+				// Our counterparty gave up on negotiating the funding transaction, so we
+				// acknowledge their `tx_abort` and forget the channel.
+				peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxAbort {
+					node_id: *counterparty_node_id,
+					msg: msgs::TxAbort { channel_id: msg.channel_id, data: Vec::new() },
+				});
+				let mut chan_phase = peer_state.channel_by_id.remove(&msg.channel_id).unwrap();
+				let err = ChannelError::Close(format!("Counterparty aborted the funding negotiation: {}",
+					String::from_utf8_lossy(&msg.data)));
+				return Err(convert_chan_phase_err!(self, err, &mut chan_phase, &msg.channel_id).1);
+			},
+			Some(_) => return Err(MsgHandleErrInternal::send_err_msg_no_close(
+				"Got a tx_abort message for an unfunded channel!".to_owned(), msg.channel_id)),
+			None => return Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), msg.channel_id))
+		}
+		Ok(())
+	}
+
+	fn internal_splice_locked(&self, counterparty_node_id: &PublicKey, msg: &msgs::SpliceLocked) -> Result<(), MsgHandleErrInternal> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		match peer_state.channel_by_id.entry(msg.channel_id) {
+			hash_map::Entry::Occupied(mut chan_phase_entry) => {
+				if let ChannelPhase::Funded(chan) = chan_phase_entry.get_mut() {
+					let logger = WithChannelContext::from(&self.logger, &chan.context);
+					let previous_short_channel_id = chan.context.get_short_channel_id();
+					if try_chan_phase_entry!(self, chan.splice_locked(&msg, &&logger), chan_phase_entry) {
+						self.complete_splice(&mut peer_state.pending_msg_events, chan, previous_short_channel_id);
+					}
+					if let Some(monitor_update) = chan.get_splice_locked_monitor_update() {
+						let funding_txo = chan.context.get_funding_txo().unwrap();
+						handle_new_monitor_update!(self, funding_txo, monitor_update, peer_state_lock,
+							peer_state, per_peer_state, chan);
+					}
+				} else {
+					return try_chan_phase_entry!(self, Err(ChannelError::Close(
+						"Got a splice_locked message for an unfunded channel!".into())), chan_phase_entry);
+				}
+			},
+			hash_map::Entry::Vacant(_) => return Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), msg.channel_id))
+		}
+		Ok(())
+	}
+
 	#[inline]
 	fn forward_htlcs(&self, per_source_pending_forwards: &mut [(u64, OutPoint, u128, Vec<(PendingHTLCInfo, u64)>)]) {
 		for &mut (prev_short_channel_id, prev_funding_outpoint, prev_user_channel_id, ref mut pending_forwards) in per_source_pending_forwards {
@@ -6845,10 +8321,20 @@ where
 			let mut failed_intercept_forwards = Vec::new();
 			if !pending_forwards.is_empty() {
 				for (forward_info, prev_htlc_id) in pending_forwards.drain(..) {
+					if let PendingHTLCRouting::Forward { hold_htlc: Some(release_secret), .. } = forward_info.routing {
+						// The sender of an async payment asked us to hold the HTLC until the often-offline
+						// recipient comes online and releases it. See `release_held_htlc`.
+						self.pending_held_htlcs.lock().unwrap().entry(release_secret).or_insert_with(Vec::new)
+							.push(PendingAddHTLCInfo {
+								prev_short_channel_id, prev_funding_outpoint, prev_htlc_id, prev_user_channel_id, forward_info
+							});
+						continue;
+					}
 					let scid = match forward_info.routing {
 						PendingHTLCRouting::Forward { short_channel_id, .. } => short_channel_id,
 						PendingHTLCRouting::Receive { .. } => 0,
 						PendingHTLCRouting::ReceiveKeysend { .. } => 0,
+						PendingHTLCRouting::TrampolineForward { .. } => 0,
 					};
 					// Pull this now to avoid introducing a lock order with `forward_htlcs`.
 					let is_our_scid = self.short_to_chan_info.read().unwrap().contains_key(&scid);
@@ -6879,7 +8365,7 @@ where
 											prev_short_channel_id, prev_funding_outpoint, prev_htlc_id, prev_user_channel_id, forward_info });
 									},
 									hash_map::Entry::Occupied(_) => {
-										let logger = WithContext::from(&self.logger, None, Some(prev_funding_outpoint.to_channel_id()));
+										let logger = WithContext::from(&self.logger, None, Some(self.channel_id_from_funding_txo(&prev_funding_outpoint)));
 										log_info!(logger, "Failed to forward incoming HTLC: detected duplicate intercepted payment over short channel id {}", scid);
 										let htlc_source = HTLCSource::PreviousHopData(HTLCPreviousHopData {
 											short_channel_id: prev_short_channel_id,
@@ -6889,6 +8375,7 @@ where
 											incoming_packet_shared_secret: forward_info.incoming_shared_secret,
 											phantom_shared_secret: None,
 											blinded_failure: forward_info.routing.blinded_failure(),
+											trampoline_shared_secret: forward_info.routing.trampoline_shared_secret(),
 										});
 
 										failed_intercept_forwards.push((htlc_source, forward_info.payment_hash,
@@ -6951,7 +8438,7 @@ where
 		channel_funding_outpoint: OutPoint, counterparty_node_id: PublicKey
 	) -> bool {
 		actions_blocking_raa_monitor_updates
-			.get(&channel_funding_outpoint.to_channel_id()).map(|v| !v.is_empty()).unwrap_or(false)
+			.get(&self.channel_id_from_funding_txo(&channel_funding_outpoint)).map(|v| !v.is_empty()).unwrap_or(false)
 		|| self.pending_events.lock().unwrap().iter().any(|(_, action)| {
 			action == &Some(EventCompletionAction::ReleaseRAAChannelMonitorUpdate {
 				channel_funding_outpoint,
@@ -7041,6 +8528,139 @@ where
 		Ok(())
 	}
 
+	fn internal_stfu(&self, counterparty_node_id: &PublicKey, msg: &msgs::Stfu) -> Result<bool, MsgHandleErrInternal> {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), msg.channel_id)
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		if !self.init_features().supports_quiescence() {
+			return Err(MsgHandleErrInternal::from_chan_no_close(
+				ChannelError::Warn("Quiescence not supported".to_owned()), msg.channel_id));
+		}
+		match peer_state.channel_by_id.entry(msg.channel_id) {
+			hash_map::Entry::Occupied(mut chan_phase_entry) => {
+				if let ChannelPhase::Funded(chan) = chan_phase_entry.get_mut() {
+					let logger = WithChannelContext::from(&self.logger, &chan.context);
+					let stfu_opt = try_chan_phase_entry!(self, chan.stfu(&msg, &&logger), chan_phase_entry);
+					let sent_stfu = stfu_opt.is_some();
+					if let Some(stfu) = stfu_opt {
+						peer_state.pending_msg_events.push(events::MessageSendEvent::SendStfu {
+							node_id: *counterparty_node_id,
+							msg: stfu,
+						});
+					}
+					self.push_channel_quiescent_event(chan);
+					Ok(sent_stfu)
+				} else {
+					try_chan_phase_entry!(self, Err(ChannelError::Warn(
+						"Got an stfu message for an unfunded channel!".into())), chan_phase_entry)
+				}
+			},
+			hash_map::Entry::Vacant(_) => Err(MsgHandleErrInternal::send_err_msg_no_close(format!("Got a message for a channel from the wrong node! No such channel for the passed counterparty_node_id {}", counterparty_node_id), msg.channel_id))
+		}
+	}
+
+	/// Stores the backup blob handed to us by the peer, returning whether it changed.
+	fn internal_peer_storage(&self, counterparty_node_id: &PublicKey, msg: &msgs::PeerStorage) -> Result<bool, MsgHandleErrInternal> {
+		if !self.default_configuration.provide_peer_storage {
+			return Err(MsgHandleErrInternal::from_chan_no_close(
+				ChannelError::Warn("Peer storage not supported".to_owned()), ChannelId::new_zero()));
+		}
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
+			.ok_or_else(|| {
+				debug_assert!(false);
+				MsgHandleErrInternal::send_err_msg_no_close(format!("Can't find a peer matching the passed counterparty node_id {}", counterparty_node_id), ChannelId::new_zero())
+			})?;
+		let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+		let peer_state = &mut *peer_state_lock;
+		// We only store backups for peers we have funded channels with, as otherwise anyone could
+		// make us store (and persist) data by simply connecting to us.
+		if !peer_state.has_funded_channel() {
+			return Err(MsgHandleErrInternal::from_chan_no_close(
+				ChannelError::Warn("Ignoring peer_storage as we have no funded channels with you".to_owned()),
+				ChannelId::new_zero()));
+		}
+		if msg.data.len() > msgs::MAX_PEER_STORAGE_SIZE {
+			return Err(MsgHandleErrInternal::from_chan_no_close(
+				ChannelError::Warn(format!("Ignoring peer_storage of {} bytes, exceeding the maximum of {} bytes",
+					msg.data.len(), msgs::MAX_PEER_STORAGE_SIZE)),
+				ChannelId::new_zero()));
+		}
+		if peer_state.peer_storage == msg.data {
+			return Ok(false);
+		}
+		let logger = WithContext::from(&self.logger, Some(*counterparty_node_id), None);
+		log_trace!(logger, "Storing {} byte peer_storage from peer {}", msg.data.len(), log_pubkey!(counterparty_node_id));
+		peer_state.peer_storage = msg.data.clone();
+		self.pending_peer_storage_persists.lock().unwrap().insert(*counterparty_node_id);
+		Ok(true)
+	}
+
+	/// Checks the backup of our channel state returned by a peer against our current
+	/// [`ChannelMonitor`]s.
+	///
+	/// # Panics
+	///
+	/// Panics if the backup shows that one of our [`ChannelMonitor`]s is stale, as continuing to
+	/// operate the channel (or broadcasting our latest commitment transaction) would allow our
+	/// counterparty to claim all of its funds.
+	fn internal_peer_storage_retrieval(&self, counterparty_node_id: &PublicKey, msg: &msgs::PeerStorageRetrieval) {
+		let logger = WithContext::from(&self.logger, Some(*counterparty_node_id), None);
+		let our_peer_storage = match OurPeerStorage::decrypt(&self.node_signer.get_peer_storage_key(), &msg.data) {
+			Ok(our_peer_storage) => our_peer_storage,
+			Err(_) => {
+				log_debug!(logger, "Ignoring peer_storage_retrieval from peer {} which we failed to decrypt", log_pubkey!(counterparty_node_id));
+				return;
+			},
+		};
+		let current_snapshot = self.chain_monitor.get_peer_storage_snapshot();
+		for backup in our_peer_storage.monitors.iter() {
+			let current = match current_snapshot.iter().find(|current| current.funding_outpoint == backup.funding_outpoint) {
+				Some(current) => current,
+				None => {
+					// The monitor may have legitimately been archived once the channel was closed and
+					// fully resolved on chain, so all we can do is let the user know.
+					log_error!(logger, "Our backup returned by peer {} contains a channel with funding outpoint {} and counterparty {:?} for which we have no ChannelMonitor. We may have lost channel state!",
+						log_pubkey!(counterparty_node_id), backup.funding_outpoint, backup.counterparty_node_id);
+					continue;
+				},
+			};
+			if current.channel_keys_id != backup.channel_keys_id || current.counterparty_node_id != backup.counterparty_node_id {
+				log_error!(logger, "Our backup returned by peer {} contains a channel with funding outpoint {} which doesn't match our ChannelMonitor, ignoring it",
+					log_pubkey!(counterparty_node_id), backup.funding_outpoint);
+				continue;
+			}
+			if current.latest_commitment_number > backup.latest_commitment_number {
+				macro_rules! log_and_panic {
+					($err_msg: expr) => {
+						log_error!(logger, $err_msg, backup.funding_outpoint, log_pubkey!(counterparty_node_id));
+						panic!($err_msg, backup.funding_outpoint, log_pubkey!(counterparty_node_id));
+					}
+				}
+				log_and_panic!("We have fallen behind - the backup of our channel state returned by our peer is more recent than our ChannelMonitor.\n\
+					This implies you have restarted with lost ChannelMonitor state, which is a violation of the LDK chain::Watch requirements.\n\
+					More specifically, this means you have a bug in your implementation that can cause loss of funds, or you are running with an old backup, which is unsafe.\n\
+					Broadcasting our latest commitment transaction would allow our counterparty to claim all funds in the channel. If you have restored from an old\n\
+					backup and wish to force-close channels and return to operation, you should start up, call ChannelManager::force_close_without_broadcasting_txn\n\
+					on the channel with funding outpoint {} (as reported by peer {}) or ChannelManager::force_close_all_channels_without_broadcasting_txn,\n\
+					then reconnect to peer(s).");
+			}
+		}
+	}
+
+	/// Builds a [`msgs::PeerStorage`] containing our encrypted backup of the given
+	/// [`ChannelMonitor`] summaries.
+	fn our_peer_storage_msg(&self, monitors: Vec<PeerStorageMonitorHolder>) -> msgs::PeerStorage {
+		let data = OurPeerStorage { monitors }
+			.encrypt(&self.node_signer.get_peer_storage_key(), &self.entropy_source.get_secure_random_bytes());
+		msgs::PeerStorage { data }
+	}
+
 	fn internal_announcement_signatures(&self, counterparty_node_id: &PublicKey, msg: &msgs::AnnouncementSignatures) -> Result<(), MsgHandleErrInternal> {
 		let per_peer_state = self.per_peer_state.read().unwrap();
 		let peer_state_mutex = per_peer_state.get(counterparty_node_id)
@@ -7175,6 +8795,33 @@ where
 						htlc_forwards = self.handle_channel_resumption(
 							&mut peer_state.pending_msg_events, chan, responses.raa, responses.commitment_update, responses.order,
 							Vec::new(), None, responses.channel_ready, responses.announcement_sigs);
+						if let Some(commitment_signed) = responses.splice_commitment_signed {
+							peer_state.pending_msg_events.push(events::MessageSendEvent::UpdateHTLCs {
+								node_id: *counterparty_node_id,
+								updates: msgs::CommitmentUpdate {
+									update_add_htlcs: Vec::new(),
+									update_fulfill_htlcs: Vec::new(),
+									update_fail_htlcs: Vec::new(),
+									update_fail_malformed_htlcs: Vec::new(),
+									update_fee: None,
+									commitment_signed,
+								},
+							});
+						}
+						self.handle_splice_tx_signatures(&mut peer_state.pending_msg_events, chan,
+							responses.tx_signatures, responses.splice_funding_broadcastable);
+						if let Some(msg) = responses.tx_abort {
+							peer_state.pending_msg_events.push(events::MessageSendEvent::SendTxAbort {
+								node_id: *counterparty_node_id,
+								msg,
+							});
+						}
+						if let Some(msg) = responses.splice_locked {
+							peer_state.pending_msg_events.push(events::MessageSendEvent::SendSpliceLocked {
+								node_id: *counterparty_node_id,
+								msg,
+							});
+						}
 						if let Some(upd) = channel_update {
 							peer_state.pending_msg_events.push(upd);
 						}
@@ -7243,13 +8890,13 @@ where
 			for monitor_event in monitor_events.drain(..) {
 				match monitor_event {
 					MonitorEvent::HTLCEvent(htlc_update) => {
-						let logger = WithContext::from(&self.logger, counterparty_node_id, Some(funding_outpoint.to_channel_id()));
+						let logger = WithContext::from(&self.logger, counterparty_node_id, Some(self.channel_id_from_funding_txo(&funding_outpoint)));
 						if let Some(preimage) = htlc_update.payment_preimage {
 							log_trace!(logger, "Claiming HTLC with preimage {} from our monitor", preimage);
 							self.claim_funds_internal(htlc_update.source, preimage, htlc_update.htlc_value_satoshis.map(|v| v * 1000), true, false, counterparty_node_id, funding_outpoint);
 						} else {
 							log_trace!(logger, "Failing HTLC with hash {} from our monitor", &htlc_update.payment_hash);
-							let receiver = HTLCDestination::NextHopChannel { node_id: counterparty_node_id, channel_id: funding_outpoint.to_channel_id() };
+							let receiver = HTLCDestination::NextHopChannel { node_id: counterparty_node_id, channel_id: self.channel_id_from_funding_txo(&funding_outpoint) };
 							let reason = HTLCFailReason::from_failure_code(0x4000 | 8);
 							self.fail_htlc_backwards_internal(&htlc_update.source, &htlc_update.payment_hash, &reason, receiver);
 						}
@@ -7270,7 +8917,7 @@ where
 								let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 								let peer_state = &mut *peer_state_lock;
 								let pending_msg_events = &mut peer_state.pending_msg_events;
-								if let hash_map::Entry::Occupied(chan_phase_entry) = peer_state.channel_by_id.entry(funding_outpoint.to_channel_id()) {
+								if let hash_map::Entry::Occupied(chan_phase_entry) = peer_state.channel_by_id.entry(self.channel_id_from_funding_txo(&funding_outpoint)) {
 									if let ChannelPhase::Funded(mut chan) = remove_channel_phase!(self, chan_phase_entry) {
 										failed_channels.push(chan.context.force_shutdown(false, ClosureReason::HolderForceClosed));
 										if let Ok(update) = self.get_channel_update_for_broadcast(&chan) {
@@ -7295,21 +8942,57 @@ where
 				}
 			}
 		}
-
-		for failure in failed_channels.drain(..) {
-			self.finish_close_channel(failure);
-		}
-
-		has_pending_monitor_events
+
+		for failure in failed_channels.drain(..) {
+			self.finish_close_channel(failure);
+		}
+
+		has_pending_monitor_events
+	}
+
+	/// In chanmon_consistency_target, we'd like to be able to restore monitor updating without
+	/// handling all pending events (i.e. not PendingHTLCsForwardable). Thus, we expose monitor
+	/// update events as a separate process method here.
+	#[cfg(fuzzing)]
+	pub fn process_monitor_events(&self) {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		self.process_pending_monitor_events();
+	}
+
+	/// Sends `stfu` on any channels which are awaiting quiescence and have since had all of their
+	/// pending updates irrevocably committed.
+	fn maybe_send_stfu(&self) {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		for (counterparty_node_id, peer_state_mutex) in per_peer_state.iter() {
+			let mut peer_state_lock = peer_state_mutex.lock().unwrap();
+			let peer_state = &mut *peer_state_lock;
+			let pending_msg_events = &mut peer_state.pending_msg_events;
+			for (_, phase) in peer_state.channel_by_id.iter_mut() {
+				if let ChannelPhase::Funded(chan) = phase {
+					let logger = WithChannelContext::from(&self.logger, &chan.context);
+					if let Some(msg) = chan.try_send_stfu(&&logger) {
+						pending_msg_events.push(events::MessageSendEvent::SendStfu {
+							node_id: *counterparty_node_id,
+							msg,
+						});
+						self.push_channel_quiescent_event(chan);
+					}
+				}
+			}
+		}
 	}
 
-	/// In chanmon_consistency_target, we'd like to be able to restore monitor updating without
-	/// handling all pending events (i.e. not PendingHTLCsForwardable). Thus, we expose monitor
-	/// update events as a separate process method here.
-	#[cfg(fuzzing)]
-	pub fn process_monitor_events(&self) {
-		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
-		self.process_pending_monitor_events();
+	/// Generates an [`Event::ChannelQuiescent`] if the given channel is now quiescent. Must only be
+	/// called after handling an `stfu` exchange step on a channel which was not yet quiescent.
+	fn push_channel_quiescent_event(&self, chan: &Channel<SP>) {
+		if let Some(is_initiator) = chan.quiescence_initiator() {
+			self.pending_events.lock().unwrap().push_back((events::Event::ChannelQuiescent {
+				channel_id: chan.context.channel_id(),
+				user_channel_id: chan.context.get_user_id(),
+				counterparty_node_id: chan.context.get_counterparty_node_id(),
+				is_initiator,
+			}, None));
+		}
 	}
 
 	/// Check the holding cell in each channel and free any pending HTLCs in them if possible.
@@ -7401,7 +9084,8 @@ where
 						});
 					}
 				}
-				ChannelPhase::UnfundedInboundV1(_) => {},
+				ChannelPhase::UnfundedInboundV1(_) | ChannelPhase::UnfundedOutboundV2(_) |
+				ChannelPhase::UnfundedInboundV2(_) => {},
 			}
 		};
 
@@ -7634,7 +9318,7 @@ where
 		let expiration = StaleExpiration::AbsoluteTimeout(absolute_expiry);
 		self.pending_outbound_payments
 			.add_new_awaiting_invoice(
-				payment_id, expiration, retry_strategy, max_total_routing_fee_msat,
+				payment_id, expiration, retry_strategy, max_total_routing_fee_msat, None,
 			)
 			.map_err(|_| Bolt12SemanticError::DuplicatePaymentId)?;
 
@@ -7653,6 +9337,11 @@ where
 	/// - `amount_msats` if overpaying what is required for the given `quantity` is desired, and
 	/// - `payer_note` for [`InvoiceRequest::payer_note`].
 	///
+	/// If the [`Offer::amount`] is denominated in a currency other than bitcoin and no
+	/// `amount_msats` is given, the amount is converted using the [`CurrencyConversion`] the
+	/// `ChannelManager` was constructed with, increased by
+	/// [`CurrencyConversionTolerance::overpayment_ppm`].
+	///
 	/// If `max_total_routing_fee_msat` is not specified, The default from
 	/// [`RouteParameters::from_payment_params_and_value`] is applied.
 	///
@@ -7684,10 +9373,13 @@ where
 	/// Errors if:
 	/// - a duplicate `payment_id` is provided given the caveats in the aforementioned link,
 	/// - the provided parameters are invalid for the offer,
+	/// - the offer is denominated in a currency other than bitcoin, no `amount_msats` is given and
+	///   the currency can't be converted,
 	/// - the parameterized [`Router`] is unable to create a blinded reply path for the invoice
 	///   request.
 	///
 	/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+	/// [`CurrencyConversionTolerance::overpayment_ppm`]: crate::util::config::CurrencyConversionTolerance::overpayment_ppm
 	/// [`InvoiceRequest::quantity`]: crate::offers::invoice_request::InvoiceRequest::quantity
 	/// [`InvoiceRequest::payer_note`]: crate::offers::invoice_request::InvoiceRequest::payer_note
 	/// [`InvoiceRequestBuilder`]: crate::offers::invoice_request::InvoiceRequestBuilder
@@ -7710,6 +9402,15 @@ where
 			None => builder,
 			Some(quantity) => builder.quantity(quantity)?,
 		};
+		let amount_msats = match (amount_msats, offer.amount()) {
+			(None, Some(Amount::Currency { iso4217_code, amount })) => {
+				let amount_msats = self.convert_currency_amount(*iso4217_code, *amount, quantity)?;
+				let overpayment_ppm =
+					self.default_configuration.currency_conversion_tolerance.overpayment_ppm;
+				Some(amount_msats.saturating_add(proportion_of(amount_msats, overpayment_ppm)))
+			},
+			(amount_msats, _) => amount_msats,
+		};
 		let builder = match amount_msats {
 			None => builder,
 			Some(amount_msats) => builder.amount_msats(amount_msats)?,
@@ -7724,7 +9425,8 @@ where
 		let expiration = StaleExpiration::TimerTicks(1);
 		self.pending_outbound_payments
 			.add_new_awaiting_invoice(
-				payment_id, expiration, retry_strategy, max_total_routing_fee_msat
+				payment_id, expiration, retry_strategy, max_total_routing_fee_msat,
+				Some(invoice_request.clone())
 			)
 			.map_err(|_| Bolt12SemanticError::DuplicatePaymentId)?;
 
@@ -7828,6 +9530,89 @@ where
 		}
 	}
 
+	/// Serves `invoice` in response to [`InvoiceRequest`]s for the [`Offer`] it was built for, on
+	/// behalf of an often-offline recipient, typically one of our LSP clients which handed us the
+	/// invoice out of band.
+	///
+	/// Replaces any invoice previously added for the same offer. Expired invoices are no longer
+	/// served but are kept until removed via [`ChannelManager::remove_static_invoice`].
+	///
+	/// # Errors
+	///
+	/// Errors if `invoice` has already expired.
+	///
+	/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+	pub fn add_static_invoice(&self, invoice: StaticInvoice) -> Result<(), Bolt12SemanticError> {
+		if invoice.is_expired_no_std(self.duration_since_epoch()) {
+			return Err(Bolt12SemanticError::AlreadyExpired);
+		}
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		self.static_invoices.lock().unwrap().insert(invoice.signing_pubkey(), invoice);
+		Ok(())
+	}
+
+	/// Stops serving the [`StaticInvoice`] added via [`ChannelManager::add_static_invoice`] for the
+	/// [`Offer`] with the given [`Offer::signing_pubkey`], returning it if there was one.
+	pub fn remove_static_invoice(&self, offer_signing_pubkey: &PublicKey) -> Option<StaticInvoice> {
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		self.static_invoices.lock().unwrap().remove(offer_signing_pubkey)
+	}
+
+	/// Takes the backup blobs our peers handed us via [`msgs::PeerStorage`] which changed since they
+	/// were last taken, allowing them to be persisted separately from the `ChannelManager`.
+	///
+	/// A blob of `None` indicates that we no longer store a backup for the given peer and any
+	/// previously persisted one should be removed. If persisting the updates fails, they must be
+	/// handed back via [`Self::restore_pending_peer_storage_updates`].
+	///
+	/// Note that users of the [`KVStore`] implementation of [`Persister`] do not need to call this.
+	///
+	/// [`KVStore`]: crate::util::persist::KVStore
+	/// [`Persister`]: crate::util::persist::Persister
+	pub fn take_pending_peer_storage_updates(&self) -> Vec<(PublicKey, Option<Vec<u8>>)> {
+		let node_ids = mem::take(&mut *self.pending_peer_storage_persists.lock().unwrap());
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		let mut updates: Vec<_> = node_ids.into_iter().map(|node_id| {
+			let peer_storage = per_peer_state.get(&node_id)
+				.map(|peer_state_mutex| peer_state_mutex.lock().unwrap().peer_storage.clone())
+				.filter(|peer_storage| !peer_storage.is_empty());
+			(node_id, peer_storage)
+		}).collect();
+		updates.sort_unstable_by_key(|(node_id, _)| *node_id);
+		updates
+	}
+
+	/// Hands back updates previously returned by [`Self::take_pending_peer_storage_updates`] which
+	/// could not be persisted, marking them as pending again.
+	pub fn restore_pending_peer_storage_updates(&self, updates: Vec<(PublicKey, Option<Vec<u8>>)>) {
+		self.pending_peer_storage_persists.lock().unwrap()
+			.extend(updates.into_iter().map(|(node_id, _)| node_id));
+	}
+
+	/// Loads the backup blobs our peers handed us via [`msgs::PeerStorage`], as previously persisted
+	/// after being taken via [`Self::take_pending_peer_storage_updates`]. This should be called
+	/// once after deserializing the `ChannelManager`, before connecting to any peers.
+	///
+	/// As we only store backups for peers we have channels with, backups of any other peers are
+	/// dropped and marked for removal from the persisted state.
+	///
+	/// Users of the [`KVStore`] implementation of [`Persister`] can read the blobs via
+	/// [`read_peer_storage`].
+	///
+	/// [`KVStore`]: crate::util::persist::KVStore
+	/// [`Persister`]: crate::util::persist::Persister
+	/// [`read_peer_storage`]: crate::util::persist::read_peer_storage
+	pub fn load_peer_storage(&self, peer_storage: Vec<(PublicKey, Vec<u8>)>) {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		for (node_id, data) in peer_storage {
+			match per_peer_state.get(&node_id) {
+				Some(peer_state_mutex) => peer_state_mutex.lock().unwrap().peer_storage = data,
+				None => { self.pending_peer_storage_persists.lock().unwrap().insert(node_id); },
+			}
+		}
+	}
+
 	/// Gets a payment secret and payment hash for use in an invoice given to a third party wishing
 	/// to pay us.
 	///
@@ -8074,9 +9859,8 @@ where
 	/// operation. It will double-check that nothing *else* is also blocking the same channel from
 	/// making progress and then let any blocked [`ChannelMonitorUpdate`]s fly.
 	fn handle_monitor_update_release(&self, counterparty_node_id: PublicKey, channel_funding_outpoint: OutPoint, mut completed_blocker: Option<RAAMonitorUpdateBlockingAction>) {
-		let logger = WithContext::from(
-			&self.logger, Some(counterparty_node_id), Some(channel_funding_outpoint.to_channel_id())
-		);
+		let channel_id = self.channel_id_from_funding_txo(&channel_funding_outpoint);
+		let logger = WithContext::from(&self.logger, Some(counterparty_node_id), Some(channel_id));
 		loop {
 			let per_peer_state = self.per_peer_state.read().unwrap();
 			if let Some(peer_state_mtx) = per_peer_state.get(&counterparty_node_id) {
@@ -8085,7 +9869,7 @@ where
 				if let Some(blocker) = completed_blocker.take() {
 					// Only do this on the first iteration of the loop.
 					if let Some(blockers) = peer_state.actions_blocking_raa_monitor_updates
-						.get_mut(&channel_funding_outpoint.to_channel_id())
+						.get_mut(&channel_id)
 					{
 						blockers.retain(|iter| iter != &blocker);
 					}
@@ -8097,16 +9881,16 @@ where
 					// blocking monitor updates for this channel. If we do, release the monitor
 					// update(s) when those blockers complete.
 					log_trace!(logger, "Delaying monitor unlock for channel {} as another channel's mon update needs to complete first",
-						&channel_funding_outpoint.to_channel_id());
+						&channel_id);
 					break;
 				}
 
-				if let hash_map::Entry::Occupied(mut chan_phase_entry) = peer_state.channel_by_id.entry(channel_funding_outpoint.to_channel_id()) {
+				if let hash_map::Entry::Occupied(mut chan_phase_entry) = peer_state.channel_by_id.entry(channel_id) {
 					if let ChannelPhase::Funded(chan) = chan_phase_entry.get_mut() {
 						debug_assert_eq!(chan.context.get_funding_txo().unwrap(), channel_funding_outpoint);
 						if let Some((monitor_update, further_update_exists)) = chan.unblock_next_blocked_monitor_update() {
 							log_debug!(logger, "Unlocking monitor updating for channel {} and updating monitor",
-								channel_funding_outpoint.to_channel_id());
+								channel_id);
 							handle_new_monitor_update!(self, channel_funding_outpoint, monitor_update,
 								peer_state_lck, peer_state, per_peer_state, chan);
 							if further_update_exists {
@@ -8116,7 +9900,7 @@ where
 							}
 						} else {
 							log_trace!(logger, "Unlocked monitor updating for channel {} without monitors to update",
-								channel_funding_outpoint.to_channel_id());
+								channel_id);
 						}
 					}
 				}
@@ -8153,7 +9937,7 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> MessageSendEventsProvider for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> MessageSendEventsProvider for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8163,6 +9947,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// Returns `MessageSendEvent`s strictly ordered per-peer, in the order they were generated.
 	/// The returned array will contain `MessageSendEvent`s for different peers if
@@ -8194,6 +9979,7 @@ where
 			if self.maybe_generate_initial_closing_signed() {
 				result = NotifyOption::DoPersist;
 			}
+			self.maybe_send_stfu();
 
 			let mut pending_events = Vec::new();
 			let per_peer_state = self.per_peer_state.read().unwrap();
@@ -8215,7 +10001,7 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> EventsProvider for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> EventsProvider for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8225,6 +10011,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// Processes events that must be periodically handled.
 	///
@@ -8236,7 +10023,7 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> chain::Listen for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> chain::Listen for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8246,6 +10033,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		{
@@ -8278,7 +10066,7 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> chain::Confirm for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> chain::Confirm for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8288,6 +10076,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	fn transactions_confirmed(&self, header: &Header, txdata: &TransactionData, height: u32) {
 		// Note that we MUST NOT end up calling methods on self.chain_monitor here - we're called
@@ -8354,12 +10143,15 @@ where
 			let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 			let peer_state = &mut *peer_state_lock;
 			for chan in peer_state.channel_by_id.values().filter_map(|phase| if let ChannelPhase::Funded(chan) = phase { Some(chan) } else { None }) {
-				let txid_opt = chan.context.get_funding_txo();
+				let txid_opt = chan.context.get_current_funding_txo();
 				let height_opt = chan.context.get_funding_tx_confirmation_height();
 				let hash_opt = chan.context.get_funding_tx_confirmed_in();
 				if let (Some(funding_txo), Some(conf_height), Some(block_hash)) = (txid_opt, height_opt, hash_opt) {
 					res.push((funding_txo.txid, conf_height, Some(block_hash)));
 				}
+				if let Some((txid, conf_height, block_hash)) = chan.get_splice_funding_confirmation() {
+					res.push((txid, conf_height, Some(block_hash)));
+				}
 			}
 		}
 		res
@@ -8370,7 +10162,8 @@ where
 			PersistenceNotifierGuard::optionally_notify_skipping_background_events(
 				self, || -> NotifyOption { NotifyOption::DoPersist });
 		self.do_chain_event(None, |channel| {
-			if let Some(funding_txo) = channel.context.get_funding_txo() {
+			channel.splice_funding_unconfirmed(txid);
+			if let Some(funding_txo) = channel.context.get_current_funding_txo() {
 				if funding_txo.txid == *txid {
 					channel.funding_transaction_unconfirmed(&&WithChannelContext::from(&self.logger, &channel.context)).map(|()| (None, Vec::new(), None))
 				} else { Ok((None, Vec::new(), None)) }
@@ -8379,7 +10172,7 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8389,6 +10182,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// Calls a function which handles an on-chain event (blocks dis/connected, transactions
 	/// un/confirmed, etc) on each channel, handling any resulting errors or messages generated by
@@ -8410,7 +10204,8 @@ where
 				peer_state.channel_by_id.retain(|_, phase| {
 					match phase {
 						// Retain unfunded channels.
-						ChannelPhase::UnfundedOutboundV1(_) | ChannelPhase::UnfundedInboundV1(_) => true,
+						ChannelPhase::UnfundedOutboundV1(_) | ChannelPhase::UnfundedInboundV1(_) |
+						ChannelPhase::UnfundedOutboundV2(_) | ChannelPhase::UnfundedInboundV2(_) => true,
 						ChannelPhase::Funded(channel) => {
 							let res = f(channel);
 							if let Ok((channel_ready_opt, mut timed_out_pending_htlcs, announcement_sigs)) = res {
@@ -8440,6 +10235,26 @@ where
 									emit_channel_ready_event!(pending_events, channel);
 								}
 
+								if let Some(height) = height_opt {
+									let previous_short_channel_id = channel.context.get_short_channel_id();
+									if let Some((splice_locked, promoted)) = channel.check_get_splice_locked(height, &&logger) {
+										pending_msg_events.push(events::MessageSendEvent::SendSpliceLocked {
+											node_id: channel.context.get_counterparty_node_id(),
+											msg: splice_locked,
+										});
+										if promoted {
+											self.complete_splice(pending_msg_events, channel, previous_short_channel_id);
+											// We can't call into the `chain::Watch` here, so let the
+											// `ChannelMonitor` know about the splice having been locked later.
+											self.pending_background_events.lock().unwrap().push(
+												BackgroundEvent::SpliceLocked {
+													counterparty_node_id: channel.context.get_counterparty_node_id(),
+													channel_id: channel.context.channel_id(),
+												});
+										}
+									}
+								}
+
 								if let Some(announcement_sigs) = announcement_sigs {
 									log_trace!(logger, "Sending announcement_signatures for channel {}", channel.context.channel_id());
 									pending_msg_events.push(events::MessageSendEvent::SendAnnouncementSignatures {
@@ -8532,6 +10347,7 @@ where
 						phantom_shared_secret: None,
 						outpoint: htlc.prev_funding_outpoint,
 						blinded_failure: htlc.forward_info.routing.blinded_failure(),
+						trampoline_shared_secret: htlc.forward_info.routing.trampoline_shared_secret(),
 					});
 
 					let requested_forward_scid /* intercept scid */ = match htlc.forward_info.routing {
@@ -8542,7 +10358,7 @@ where
 							HTLCFailReason::from_failure_code(0x2000 | 2),
 							HTLCDestination::InvalidForward { requested_forward_scid }));
 					let logger = WithContext::from(
-						&self.logger, None, Some(htlc.prev_funding_outpoint.to_channel_id())
+						&self.logger, None, Some(self.channel_id_from_funding_txo(&htlc.prev_funding_outpoint))
 					);
 					log_trace!(logger, "Timing out intercepted HTLC with requested forward scid {}", requested_forward_scid);
 					false
@@ -8550,6 +10366,38 @@ where
 			});
 		}
 
+		self.pending_held_htlcs.lock().unwrap().retain(|_, htlcs| {
+			htlcs.retain(|htlc| {
+				if height >= htlc.forward_info.outgoing_cltv_value - HTLC_FAIL_BACK_BUFFER {
+					let prev_hop_data = HTLCSource::PreviousHopData(HTLCPreviousHopData {
+						short_channel_id: htlc.prev_short_channel_id,
+						user_channel_id: Some(htlc.prev_user_channel_id),
+						htlc_id: htlc.prev_htlc_id,
+						incoming_packet_shared_secret: htlc.forward_info.incoming_shared_secret,
+						phantom_shared_secret: None,
+						outpoint: htlc.prev_funding_outpoint,
+						blinded_failure: htlc.forward_info.routing.blinded_failure(),
+						trampoline_shared_secret: htlc.forward_info.routing.trampoline_shared_secret(),
+					});
+
+					let requested_forward_scid = match htlc.forward_info.routing {
+						PendingHTLCRouting::Forward { short_channel_id, .. } => short_channel_id,
+						_ => unreachable!(), // Only `PendingHTLCRouting::Forward`s are held
+					};
+					timed_out_htlcs.push((prev_hop_data, htlc.forward_info.payment_hash,
+							HTLCFailReason::from_failure_code(0x2000 | 2),
+							HTLCDestination::InvalidForward { requested_forward_scid }));
+					let logger = WithContext::from(
+						&self.logger, None, Some(self.channel_id_from_funding_txo(&htlc.prev_funding_outpoint))
+					);
+					log_trace!(logger, "Timing out held HTLC with payment hash {} which was never released",
+						htlc.forward_info.payment_hash);
+					false
+				} else { true }
+			});
+			!htlcs.is_empty()
+		});
+
 		self.handle_init_event_channel_failures(failed_channels);
 
 		for (source, payment_hash, reason, destination) in timed_out_htlcs.drain(..) {
@@ -8626,8 +10474,8 @@ where
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
-	ChannelMessageHandler for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+	ChannelMessageHandler for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -8637,13 +10485,14 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	fn handle_open_channel(&self, counterparty_node_id: &PublicKey, msg: &msgs::OpenChannel) {
 		// Note that we never need to persist the updated ChannelManager for an inbound
 		// open_channel message - pre-funded channels are never written so there should be no
 		// change to the contents.
 		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
-			let res = self.internal_open_channel(counterparty_node_id, msg);
+			let res = self.internal_open_channel(counterparty_node_id, OpenChannelMessage::V1(msg.clone()));
 			let persist = match &res {
 				Err(e) if e.closes_channel() => {
 					debug_assert!(false, "We shouldn't close a new channel");
@@ -8657,9 +10506,21 @@ where
 	}
 
 	fn handle_open_channel_v2(&self, counterparty_node_id: &PublicKey, msg: &msgs::OpenChannelV2) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.temporary_channel_id.clone())), *counterparty_node_id);
+		// Note that we never need to persist the updated ChannelManager for an inbound
+		// open_channel2 message - pre-funded channels are never written so there should be no
+		// change to the contents.
+		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
+			let res = self.internal_open_channel(counterparty_node_id, OpenChannelMessage::V2(msg.clone()));
+			let persist = match &res {
+				Err(e) if e.closes_channel() => {
+					debug_assert!(false, "We shouldn't close a new channel");
+					NotifyOption::DoPersist
+				},
+				_ => NotifyOption::SkipPersistHandleEvents,
+			};
+			let _ = handle_error!(self, res, *counterparty_node_id);
+			persist
+		});
 	}
 
 	fn handle_accept_channel(&self, counterparty_node_id: &PublicKey, msg: &msgs::AcceptChannel) {
@@ -8673,9 +10534,13 @@ where
 	}
 
 	fn handle_accept_channel_v2(&self, counterparty_node_id: &PublicKey, msg: &msgs::AcceptChannelV2) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.temporary_channel_id.clone())), *counterparty_node_id);
+		// Note that we never need to persist the updated ChannelManager for an inbound
+		// accept_channel2 message - pre-funded channels are never written so there should be no
+		// change to the contents.
+		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
+			let _ = handle_error!(self, self.internal_accept_channel_v2(counterparty_node_id, msg), *counterparty_node_id);
+			NotifyOption::SkipPersistHandleEvents
+		});
 	}
 
 	fn handle_funding_created(&self, counterparty_node_id: &PublicKey, msg: &msgs::FundingCreated) {
@@ -8705,27 +10570,49 @@ where
 	}
 
 	fn handle_stfu(&self, counterparty_node_id: &PublicKey, msg: &msgs::Stfu) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Quiescence not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		// Quiescence is never persisted, so we only need to persist if the channel was closed.
+		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
+			let res = self.internal_stfu(counterparty_node_id, msg);
+			let persist = match &res {
+				Err(e) if e.closes_channel() => NotifyOption::DoPersist,
+				Ok(false) => NotifyOption::SkipPersistNoEvents,
+				_ => NotifyOption::SkipPersistHandleEvents,
+			};
+			let _ = handle_error!(self, res, *counterparty_node_id);
+			persist
+		});
+	}
+
+	fn handle_peer_storage(&self, counterparty_node_id: &PublicKey, msg: &msgs::PeerStorage) {
+		let _persistence_guard = PersistenceNotifierGuard::optionally_notify(self, || {
+			let res = self.internal_peer_storage(counterparty_node_id, msg);
+			let persist = match &res {
+				Ok(true) => NotifyOption::DoPersist,
+				Ok(false) => NotifyOption::SkipPersistNoEvents,
+				Err(_) => NotifyOption::SkipPersistHandleEvents,
+			};
+			let _ = handle_error!(self, res, *counterparty_node_id);
+			persist
+		});
+	}
+
+	fn handle_peer_storage_retrieval(&self, counterparty_node_id: &PublicKey, msg: &msgs::PeerStorageRetrieval) {
+		self.internal_peer_storage_retrieval(counterparty_node_id, msg);
 	}
 
 	fn handle_splice(&self, counterparty_node_id: &PublicKey, msg: &msgs::Splice) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Splicing not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_splice(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_splice_ack(&self, counterparty_node_id: &PublicKey, msg: &msgs::SpliceAck) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Splicing not supported (splice_ack)".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_splice_ack(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_splice_locked(&self, counterparty_node_id: &PublicKey, msg: &msgs::SpliceLocked) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Splicing not supported (splice_locked)".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_splice_locked(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_shutdown(&self, counterparty_node_id: &PublicKey, msg: &msgs::Shutdown) {
@@ -8864,6 +10751,9 @@ where
 					let context = match phase {
 						ChannelPhase::Funded(chan) => {
 							let logger = WithChannelContext::from(&self.logger, &chan.context);
+							if let Some(aborted_splice) = chan.abort_splice_on_disconnect(&&logger) {
+								self.handle_splice_aborted(chan, aborted_splice);
+							}
 							if chan.remove_uncommitted_htlcs_and_mark_paused(&&logger).is_ok() {
 								// We only retain funded channels that are not shutdown.
 								return true;
@@ -8877,6 +10767,12 @@ where
 						ChannelPhase::UnfundedInboundV1(chan) => {
 							&mut chan.context
 						},
+						ChannelPhase::UnfundedOutboundV2(chan) => {
+							&mut chan.context
+						},
+						ChannelPhase::UnfundedInboundV2(chan) => {
+							&mut chan.context
+						},
 					};
 					// Clean up for removal.
 					update_maps_on_chan_removal!(self, &context);
@@ -8901,6 +10797,9 @@ where
 						&events::MessageSendEvent::SendAnnouncementSignatures { .. } => false,
 						// Quiescence
 						&events::MessageSendEvent::SendStfu { .. } => false,
+						// Peer storage
+						&events::MessageSendEvent::SendPeerStorage { .. } => false,
+						&events::MessageSendEvent::SendPeerStorageRetrieval { .. } => false,
 						// Splicing
 						&events::MessageSendEvent::SendSplice { .. } => false,
 						&events::MessageSendEvent::SendSpliceAck { .. } => false,
@@ -8940,7 +10839,11 @@ where
 			} else { debug_assert!(false, "Unconnected peer disconnected"); true }
 		};
 		if remove_peer {
-			per_peer_state.remove(counterparty_node_id);
+			let peer_state_mutex = per_peer_state.remove(counterparty_node_id);
+			// The peer's backup has to be removed from our persisted state, too.
+			if peer_state_mutex.map_or(false, |peer_state| !peer_state.lock().unwrap().peer_storage.is_empty()) {
+				self.pending_peer_storage_persists.lock().unwrap().insert(*counterparty_node_id);
+			}
 		}
 		mem::drop(per_peer_state);
 
@@ -8983,6 +10886,7 @@ where
 							monitor_update_blocked_actions: BTreeMap::new(),
 							actions_blocking_raa_monitor_updates: BTreeMap::new(),
 							is_connected: true,
+							peer_storage: Vec::new(),
 						}));
 					},
 					hash_map::Entry::Occupied(e) => {
@@ -9010,6 +10914,8 @@ where
 			if let Some(peer_state_mutex) = per_peer_state.get(counterparty_node_id) {
 				let mut peer_state_lock = peer_state_mutex.lock().unwrap();
 				let peer_state = &mut *peer_state_lock;
+				let send_our_peer_storage = self.default_configuration.provide_peer_storage &&
+					peer_state.wants_our_peer_storage();
 				let pending_msg_events = &mut peer_state.pending_msg_events;
 
 				peer_state.channel_by_id.iter_mut().filter_map(|(_, phase)|
@@ -9021,6 +10927,19 @@ where
 						msg: chan.get_channel_reestablish(&&logger),
 					});
 				});
+
+				if !peer_state.peer_storage.is_empty() {
+					pending_msg_events.push(events::MessageSendEvent::SendPeerStorageRetrieval {
+						node_id: *counterparty_node_id,
+						msg: msgs::PeerStorageRetrieval { data: peer_state.peer_storage.clone() },
+					});
+				}
+				if send_our_peer_storage {
+					pending_msg_events.push(events::MessageSendEvent::SendPeerStorage {
+						node_id: *counterparty_node_id,
+						msg: self.our_peer_storage_msg(self.chain_monitor.get_peer_storage_snapshot()),
+					});
+				}
 			}
 
 			return NotifyOption::SkipPersistHandleEvents;
@@ -9133,63 +11052,64 @@ where
 		Some(vec![self.chain_hash])
 	}
 
+	fn peer_has_channels(&self, counterparty_node_id: &PublicKey) -> bool {
+		let per_peer_state = self.per_peer_state.read().unwrap();
+		per_peer_state.get(counterparty_node_id)
+			.map_or(false, |peer_state_mutex| peer_state_mutex.lock().unwrap().has_funded_channel())
+	}
+
 	fn handle_tx_add_input(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAddInput) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_interactive_tx_msg(counterparty_node_id, msg.channel_id,
+			|context| context.tx_add_input(msg)), *counterparty_node_id);
 	}
 
 	fn handle_tx_add_output(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAddOutput) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_interactive_tx_msg(counterparty_node_id, msg.channel_id,
+			|context| context.tx_add_output(msg)), *counterparty_node_id);
 	}
 
 	fn handle_tx_remove_input(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxRemoveInput) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_interactive_tx_msg(counterparty_node_id, msg.channel_id,
+			|context| context.tx_remove_input(msg)), *counterparty_node_id);
 	}
 
 	fn handle_tx_remove_output(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxRemoveOutput) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_interactive_tx_msg(counterparty_node_id, msg.channel_id,
+			|context| context.tx_remove_output(msg)), *counterparty_node_id);
 	}
 
 	fn handle_tx_complete(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxComplete) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_tx_complete(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_tx_signatures(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxSignatures) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_tx_signatures(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_tx_init_rbf(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxInitRbf) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_tx_init_rbf(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_tx_ack_rbf(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAckRbf) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_tx_ack_rbf(counterparty_node_id, msg), *counterparty_node_id);
 	}
 
 	fn handle_tx_abort(&self, counterparty_node_id: &PublicKey, msg: &msgs::TxAbort) {
-		let _: Result<(), _> = handle_error!(self, Err(MsgHandleErrInternal::send_err_msg_no_close(
-			"Dual-funded channels not supported".to_owned(),
-			 msg.channel_id.clone())), *counterparty_node_id);
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		let _ = handle_error!(self, self.internal_tx_abort(counterparty_node_id, msg), *counterparty_node_id);
 	}
 }
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
-OffersMessageHandler for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+OffersMessageHandler for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -9199,6 +11119,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	fn handle_message(&self, message: OffersMessage) -> Option<OffersMessage> {
 		let secp_ctx = &self.secp_ctx;
@@ -9206,6 +11127,14 @@ where
 
 		match message {
 			OffersMessage::InvoiceRequest(invoice_request) => {
+				// Requests for an offer of an often-offline recipient we serve a static invoice for are
+				// answered on its behalf, as they can't be for one of our own offers.
+				if let Some(invoice) = self.static_invoice_for(&invoice_request) {
+					return Some(OffersMessage::StaticInvoice(invoice));
+				}
+				if let Err(error) = self.check_invoice_request_currency_amount(&invoice_request) {
+					return Some(OffersMessage::InvoiceError(error.into()));
+				}
 				let amount_msats = match InvoiceBuilder::<DerivedSigningPubkey>::amount_msats(
 					&invoice_request
 				) {
@@ -9302,6 +11231,70 @@ where
 					},
 				}
 			},
+			OffersMessage::StaticInvoice(invoice) => {
+				if invoice.invoice_features().requires_unknown_bits_from(&self.bolt12_invoice_features()) {
+					return Some(OffersMessage::InvoiceError(Bolt12SemanticError::UnknownRequiredFeatures.into()));
+				}
+				if invoice.is_expired_no_std(self.duration_since_epoch()) {
+					return Some(OffersMessage::InvoiceError(Bolt12SemanticError::AlreadyExpired.into()));
+				}
+
+				// The HTLC is held by our LSP until the recipient comes online, so it must be sent over
+				// the LSP's channels. Use the onion message peer we can currently send the most to.
+				let usable_channels = self.list_usable_channels();
+				let lsp_node_id = match usable_channels.iter()
+					.filter(|chan| chan.counterparty.features.supports_onion_messages())
+					.max_by_key(|chan| chan.next_outbound_htlc_limit_msat)
+				{
+					Some(chan) => chan.counterparty.node_id,
+					None => {
+						log_trace!(self.logger, "No LSP channel to hold a payment for a static invoice");
+						return None;
+					},
+				};
+				let first_hops = usable_channels.into_iter()
+					.filter(|chan| chan.counterparty.node_id == lsp_node_id)
+					.collect();
+
+				// The recipient's release message must reach the LSP holding the HTLC.
+				let reply_path = match BlindedPath::one_hop_for_message(
+					lsp_node_id, &*self.entropy_source, &self.secp_ctx
+				) {
+					Ok(reply_path) => reply_path,
+					Err(()) => {
+						log_trace!(self.logger, "Failed to create a reply path for held_htlc_available");
+						return None;
+					},
+				};
+
+				let (payment_id, payment_release_secret) = match self.pending_outbound_payments
+					.static_invoice_received(&invoice, &self.entropy_source)
+				{
+					Ok(held_payment) => held_payment,
+					Err(e) => {
+						log_trace!(self.logger, "Failed holding payment for static invoice: {:?}", e);
+						return Some(OffersMessage::InvoiceError(InvoiceError::from_string(format!("{:?}", e))));
+					},
+				};
+				if let Err(e) = self.send_payment_for_static_invoice(payment_id, first_hops) {
+					log_trace!(self.logger, "Failed paying static invoice: {:?}", e);
+					return Some(OffersMessage::InvoiceError(InvoiceError::from_string(format!("{:?}", e))));
+				}
+
+				// Notify the recipient over each of its message paths (with an upper bound), any of
+				// which may be used to have our LSP release the HTLC once it comes online.
+				const MESSAGE_LIMIT: usize = 10;
+				let mut pending_async_payments_messages = self.pending_async_payments_messages.lock().unwrap();
+				for path in invoice.message_paths().iter().take(MESSAGE_LIMIT) {
+					let message = new_pending_onion_message(
+						AsyncPaymentsMessage::HeldHtlcAvailable(HeldHtlcAvailable { payment_release_secret }),
+						Destination::BlindedPath(path.clone()),
+						Some(reply_path.clone()),
+					);
+					pending_async_payments_messages.push(message);
+				}
+				None
+			},
 			OffersMessage::InvoiceError(invoice_error) => {
 				log_trace!(self.logger, "Received invoice_error: {}", invoice_error);
 				None
@@ -9314,6 +11307,62 @@ where
 	}
 }
 
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+AsyncPaymentsMessageHandler for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
+where
+	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
+	T::Target: BroadcasterInterface,
+	ES::Target: EntropySource,
+	NS::Target: NodeSigner,
+	SP::Target: SignerProvider,
+	F::Target: FeeEstimator,
+	R::Target: Router,
+	L::Target: Logger,
+	CC::Target: CurrencyConversion,
+{
+	fn held_htlc_available(&self, message: HeldHtlcAvailable) -> Option<ReleaseHeldHtlc> {
+		// Receiving the message means we're online, so the held HTLC can be released right away.
+		Some(ReleaseHeldHtlc { payment_release_secret: message.payment_release_secret })
+	}
+
+	fn release_held_htlc(&self, message: ReleaseHeldHtlc) {
+		let held_htlcs = match self.pending_held_htlcs.lock().unwrap()
+			.remove(&message.payment_release_secret)
+		{
+			Some(held_htlcs) => held_htlcs,
+			None => {
+				log_trace!(self.logger, "Received release_held_htlc for an unknown or already released HTLC");
+				return;
+			},
+		};
+
+		let _persistence_guard = PersistenceNotifierGuard::notify_on_drop(self);
+		for held_htlc in held_htlcs {
+			let PendingAddHTLCInfo {
+				prev_short_channel_id, prev_funding_outpoint, prev_htlc_id, prev_user_channel_id,
+				mut forward_info,
+			} = held_htlc;
+			if let PendingHTLCRouting::Forward { ref mut hold_htlc, .. } = forward_info.routing {
+				*hold_htlc = None;
+			}
+			log_trace!(self.logger, "Releasing held HTLC with payment hash {}", forward_info.payment_hash);
+			self.forward_htlcs(&mut [(
+				prev_short_channel_id, prev_funding_outpoint, prev_user_channel_id,
+				vec![(forward_info, prev_htlc_id)]
+			)]);
+		}
+	}
+
+	fn release_pending_messages(&self) -> Vec<PendingOnionMessage<AsyncPaymentsMessage>> {
+		core::mem::take(&mut self.pending_async_payments_messages.lock().unwrap())
+	}
+}
+
+/// Returns the given proportion, in parts per million, of `amount_msats`, rounded down.
+fn proportion_of(amount_msats: u64, ppm: u32) -> u64 {
+	(amount_msats as u128 * ppm as u128 / 1_000_000) as u64
+}
+
 /// Fetches the set of [`NodeFeatures`] flags that are provided by or required by
 /// [`ChannelManager`].
 pub(crate) fn provided_node_features(config: &UserConfig) -> NodeFeatures {
@@ -9369,9 +11418,26 @@ pub fn provided_init_features(config: &UserConfig) -> InitFeatures {
 	features.set_scid_privacy_optional();
 	features.set_zero_conf_optional();
 	features.set_route_blinding_optional();
-	if config.channel_handshake_config.negotiate_anchors_zero_fee_htlc_tx {
+	features.set_quiescence_optional();
+	if config.channel_handshake_config.negotiate_anchors_zero_fee_htlc_tx ||
+		config.channel_handshake_config.negotiate_anchor_zero_fee_commitments {
 		features.set_anchors_zero_fee_htlc_tx_optional();
 	}
+	if config.channel_handshake_config.negotiate_anchor_zero_fee_commitments {
+		features.set_anchor_zero_fee_commitments_optional();
+	}
+	if config.enable_splicing {
+		features.set_splicing_optional();
+	}
+	if config.enable_dual_funded_channels {
+		features.set_dual_fund_optional();
+	}
+	if config.accept_trampoline_forwards {
+		features.set_trampoline_routing_optional();
+	}
+	if config.provide_peer_storage {
+		features.set_provide_storage_optional();
+	}
 	features
 }
 
@@ -9522,6 +11588,8 @@ impl_writeable_tlv_based_enum!(PendingHTLCRouting,
 		(0, onion_packet, required),
 		(1, blinded, option),
 		(2, short_channel_id, required),
+		(3, incoming_trampoline_shared_secret, option),
+		(5, hold_htlc, option),
 	},
 	(1, Receive) => {
 		(0, payment_data, required),
@@ -9538,6 +11606,12 @@ impl_writeable_tlv_based_enum!(PendingHTLCRouting,
 		(4, payment_data, option), // Added in 0.0.116
 		(5, custom_tlvs, optional_vec),
 	},
+	(3, TrampolineForward) => {
+		(0, onion_packet, (required: LengthReadable)),
+		(2, node_id, required),
+		(4, incoming_cltv_expiry, required),
+		(6, incoming_trampoline_shared_secret, required),
+	},
 ;);
 
 impl_writeable_tlv_based!(PendingHTLCInfo, {
@@ -9548,6 +11622,7 @@ impl_writeable_tlv_based!(PendingHTLCInfo, {
 	(8, outgoing_cltv_value, required),
 	(9, incoming_amt_msat, option),
 	(10, skimmed_fee_msat, option),
+	(11, incoming_endorsed, (default_value, false)),
 });
 
 
@@ -9636,6 +11711,7 @@ impl_writeable_tlv_based!(HTLCPreviousHopData, {
 	(4, htlc_id, required),
 	(6, incoming_packet_shared_secret, required),
 	(7, user_channel_id, option),
+	(9, trampoline_shared_secret, option),
 });
 
 impl Writeable for ClaimableHTLC {
@@ -9719,6 +11795,7 @@ impl Readable for HTLCSource {
 				let mut payment_id = None;
 				let mut payment_params: Option<PaymentParameters> = None;
 				let mut blinded_tail: Option<BlindedTail> = None;
+				let mut trampoline_hops: Option<Vec<TrampolineHop>> = Some(Vec::new());
 				read_tlv_fields!(reader, {
 					(0, session_priv, required),
 					(1, payment_id, option),
@@ -9726,13 +11803,14 @@ impl Readable for HTLCSource {
 					(4, path_hops, required_vec),
 					(5, payment_params, (option: ReadableArgs, 0)),
 					(6, blinded_tail, option),
+					(8, trampoline_hops, optional_vec),
 				});
 				if payment_id.is_none() {
 					// For backwards compat, if there was no payment_id written, use the session_priv bytes
 					// instead.
 					payment_id = Some(PaymentId(*session_priv.0.unwrap().as_ref()));
 				}
-				let path = Path { hops: path_hops, blinded_tail };
+				let path = Path { hops: path_hops, blinded_tail, trampoline_hops: trampoline_hops.unwrap() };
 				if path.hops.len() == 0 {
 					return Err(DecodeError::InvalidValue);
 				}
@@ -9770,6 +11848,7 @@ impl Writeable for HTLCSource {
 					(4, path.hops, required_vec),
 					(5, None::<PaymentParameters>, option), // payment_params in LDK versions prior to 0.0.115
 					(6, path.blinded_tail, option),
+					(8, path.trampoline_hops, optional_vec),
 				 });
 			}
 			HTLCSource::PreviousHopData(ref field) => {
@@ -9860,7 +11939,7 @@ impl_writeable_tlv_based!(PendingInboundPayment, {
 	(8, min_value_msat, required),
 });
 
-impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref> Writeable for ChannelManager<M, T, ES, NS, SP, F, R, L>
+impl<M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref> Writeable for ChannelManager<M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -9870,6 +11949,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), io::Error> {
 		let _consistency_lock = self.total_consistency_lock.write().unwrap();
@@ -10024,6 +12104,7 @@ where
 				}
 				PendingOutboundPayment::AwaitingInvoice { .. } => {},
 				PendingOutboundPayment::InvoiceReceived { .. } => {},
+				PendingOutboundPayment::StaticInvoiceReceived { .. } => {},
 				PendingOutboundPayment::Fulfilled { .. } => {},
 				PendingOutboundPayment::Abandoned { .. } => {},
 			}
@@ -10047,6 +12128,19 @@ where
 			pending_intercepted_htlcs = Some(our_pending_intercepts);
 		}
 
+		let our_held_htlcs = self.pending_held_htlcs.lock().unwrap();
+		let pending_held_htlcs: Vec<([u8; 32], &PendingAddHTLCInfo)> = our_held_htlcs.iter()
+			.flat_map(|(release_secret, htlcs)| htlcs.iter().map(move |htlc| (*release_secret, htlc)))
+			.collect();
+
+		let mut static_invoices = None;
+		let our_static_invoices = self.static_invoices.lock().unwrap();
+		if !our_static_invoices.is_empty() {
+			static_invoices = Some(our_static_invoices.iter()
+				.map(|(signing_pubkey, invoice)| (*signing_pubkey, invoice.encode()))
+				.collect::<HashMap<PublicKey, Vec<u8>>>());
+		}
+
 		let mut pending_claiming_payments = Some(&claimable_payments.pending_claiming_payments);
 		if pending_claiming_payments.as_ref().unwrap().is_empty() {
 			// LDK versions prior to 0.0.113 do not know how to read the pending claimed payments
@@ -10077,6 +12171,8 @@ where
 			(10, in_flight_monitor_updates, option),
 			(11, self.probing_cookie_secret, required),
 			(13, htlc_onion_fields, optional_vec),
+			(15, pending_held_htlcs, optional_vec),
+			(17, static_invoices, option),
 		});
 
 		Ok(())
@@ -10164,7 +12260,7 @@ impl_writeable_tlv_based_enum!(ChannelShutdownState,
 /// which you've already broadcasted the transaction.
 ///
 /// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
-pub struct ChannelManagerReadArgs<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
+pub struct ChannelManagerReadArgs<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -10174,6 +12270,7 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// A cryptographically secure source of entropy.
 	pub entropy_source: ES,
@@ -10209,6 +12306,11 @@ where
 	/// The Logger for use in the ChannelManager and which may be used to log information during
 	/// deserialization.
 	pub logger: L,
+	/// The source of exchange rates used in the ChannelManager in the future to price offers
+	/// denominated in a currency other than bitcoin.
+	///
+	/// No calls to the currency conversion will be made during deserialization.
+	pub currency_conversion: CC,
 	/// Default settings used for new channels. Any existing channels will continue to use the
 	/// runtime settings which were stored when the ChannelManager was serialized.
 	pub default_config: UserConfig,
@@ -10228,8 +12330,8 @@ where
 	pub channel_monitors: HashMap<OutPoint, &'a mut ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>>,
 }
 
-impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
-		ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L>
+impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+		ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L, CC>
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -10239,14 +12341,15 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
 	/// Simple utility function to create a ChannelManagerReadArgs which creates the monitor
 	/// HashMap for you. This is primarily useful for C bindings where it is not practical to
 	/// populate a HashMap directly from C.
-	pub fn new(entropy_source: ES, node_signer: NS, signer_provider: SP, fee_estimator: F, chain_monitor: M, tx_broadcaster: T, router: R, logger: L, default_config: UserConfig,
+	pub fn new(entropy_source: ES, node_signer: NS, signer_provider: SP, fee_estimator: F, chain_monitor: M, tx_broadcaster: T, router: R, logger: L, currency_conversion: CC, default_config: UserConfig,
 			mut channel_monitors: Vec<&'a mut ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>>) -> Self {
 		Self {
-			entropy_source, node_signer, signer_provider, fee_estimator, chain_monitor, tx_broadcaster, router, logger, default_config,
+			entropy_source, node_signer, signer_provider, fee_estimator, chain_monitor, tx_broadcaster, router, logger, currency_conversion, default_config,
 			channel_monitors: channel_monitors.drain(..).map(|monitor| { (monitor.get_funding_txo().0, monitor) }).collect()
 		}
 	}
@@ -10254,8 +12357,8 @@ where
 
 // Implement ReadableArgs for an Arc'd ChannelManager to make it a bit easier to work with the
 // SipmleArcChannelManager type:
-impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
-	ReadableArgs<ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L>> for (BlockHash, Arc<ChannelManager<M, T, ES, NS, SP, F, R, L>>)
+impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+	ReadableArgs<ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L, CC>> for (BlockHash, Arc<ChannelManager<M, T, ES, NS, SP, F, R, L, CC>>)
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -10265,15 +12368,16 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
-	fn read<Reader: io::Read>(reader: &mut Reader, args: ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L>) -> Result<Self, DecodeError> {
-		let (blockhash, chan_manager) = <(BlockHash, ChannelManager<M, T, ES, NS, SP, F, R, L>)>::read(reader, args)?;
+	fn read<Reader: io::Read>(reader: &mut Reader, args: ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L, CC>) -> Result<Self, DecodeError> {
+		let (blockhash, chan_manager) = <(BlockHash, ChannelManager<M, T, ES, NS, SP, F, R, L, CC>)>::read(reader, args)?;
 		Ok((blockhash, Arc::new(chan_manager)))
 	}
 }
 
-impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref>
-	ReadableArgs<ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L>> for (BlockHash, ChannelManager<M, T, ES, NS, SP, F, R, L>)
+impl<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref>
+	ReadableArgs<ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L, CC>> for (BlockHash, ChannelManager<M, T, ES, NS, SP, F, R, L, CC>)
 where
 	M::Target: chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 	T::Target: BroadcasterInterface,
@@ -10283,8 +12387,9 @@ where
 	F::Target: FeeEstimator,
 	R::Target: Router,
 	L::Target: Logger,
+	CC::Target: CurrencyConversion,
 {
-	fn read<Reader: io::Read>(reader: &mut Reader, mut args: ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L>) -> Result<Self, DecodeError> {
+	fn read<Reader: io::Read>(reader: &mut Reader, mut args: ChannelManagerReadArgs<'a, M, T, ES, NS, SP, F, R, L, CC>) -> Result<Self, DecodeError> {
 		let _ver = read_ver_prefix!(reader, SERIALIZATION_VERSION);
 
 		let chain_hash: ChainHash = Readable::read(reader)?;
@@ -10297,6 +12402,7 @@ where
 		let mut funding_txo_set = HashSet::with_capacity(cmp::min(channel_count as usize, 128));
 		let mut funded_peer_channels: HashMap<PublicKey, HashMap<ChannelId, ChannelPhase<SP>>> = HashMap::with_capacity(cmp::min(channel_count as usize, 128));
 		let mut outpoint_to_peer = HashMap::with_capacity(cmp::min(channel_count as usize, 128));
+		let mut outpoint_to_channel_id = HashMap::new();
 		let mut short_to_chan_info = HashMap::with_capacity(cmp::min(channel_count as usize, 128));
 		let mut channel_closures = VecDeque::new();
 		let mut close_background_events = Vec::new();
@@ -10378,6 +12484,13 @@ where
 					if let Some(funding_txo) = channel.context.get_funding_txo() {
 						outpoint_to_peer.insert(funding_txo, channel.context.get_counterparty_node_id());
 					}
+					if channel.is_monitor_pending_splice_locked() {
+						// Applied after any in-flight updates we replay below.
+						close_background_events.push(BackgroundEvent::SpliceLocked {
+							counterparty_node_id: channel.context.get_counterparty_node_id(),
+							channel_id: channel.context.channel_id(),
+						});
+					}
 					match funded_peer_channels.entry(channel.context.get_counterparty_node_id()) {
 						hash_map::Entry::Occupied(mut entry) => {
 							let by_id_map = entry.get_mut();
@@ -10414,10 +12527,11 @@ where
 		}
 
 		for (funding_txo, monitor) in args.channel_monitors.iter() {
+			outpoint_to_channel_id.insert(*funding_txo, monitor.channel_id());
 			if !funding_txo_set.contains(funding_txo) {
 				let logger = WithChannelMonitor::from(&args.logger, monitor);
 				log_info!(logger, "Queueing monitor update to ensure missing channel {} is force closed",
-					&funding_txo.to_channel_id());
+					&monitor.channel_id());
 				let monitor_update = ChannelMonitorUpdate {
 					update_id: CLOSED_CHANNEL_UPDATE_ID,
 					counterparty_node_id: None,
@@ -10462,6 +12576,7 @@ where
 				monitor_update_blocked_actions: BTreeMap::new(),
 				actions_blocking_raa_monitor_updates: BTreeMap::new(),
 				is_connected: false,
+				peer_storage: Vec::new(),
 			}
 		};
 
@@ -10536,6 +12651,8 @@ where
 		let mut monitor_update_blocked_actions_per_peer: Option<Vec<(_, BTreeMap<_, Vec<_>>)>> = Some(Vec::new());
 		let mut events_override = None;
 		let mut in_flight_monitor_updates: Option<HashMap<(PublicKey, OutPoint), Vec<ChannelMonitorUpdate>>> = None;
+		let mut pending_held_htlcs: Option<Vec<([u8; 32], PendingAddHTLCInfo)>> = None;
+		let mut encoded_static_invoices: Option<HashMap<PublicKey, Vec<u8>>> = None;
 		read_tlv_fields!(reader, {
 			(1, pending_outbound_payments_no_retry, option),
 			(2, pending_intercepted_htlcs, option),
@@ -10549,6 +12666,8 @@ where
 			(10, in_flight_monitor_updates, option),
 			(11, probing_cookie_secret, option),
 			(13, claimable_htlc_onion_fields, optional_vec),
+			(15, pending_held_htlcs, optional_vec),
+			(17, encoded_static_invoices, option),
 		});
 		if fake_scid_rand_bytes.is_none() {
 			fake_scid_rand_bytes = Some(args.entropy_source.get_secure_random_bytes());
@@ -10558,6 +12677,17 @@ where
 			probing_cookie_secret = Some(args.entropy_source.get_secure_random_bytes());
 		}
 
+		let mut held_htlcs: HashMap<[u8; 32], Vec<PendingAddHTLCInfo>> = HashMap::new();
+		for (release_secret, htlc) in pending_held_htlcs.unwrap_or_default() {
+			held_htlcs.entry(release_secret).or_insert_with(Vec::new).push(htlc);
+		}
+
+		let mut static_invoices = HashMap::new();
+		for (signing_pubkey, bytes) in encoded_static_invoices.unwrap_or_default() {
+			let invoice = StaticInvoice::try_from(bytes).map_err(|_| DecodeError::InvalidValue)?;
+			static_invoices.insert(signing_pubkey, invoice);
+		}
+
 		if let Some(events) = events_override {
 			pending_events_read = events;
 		}
@@ -10591,6 +12721,10 @@ where
 		// available) and then walk any remaining in-flight updates.
 		//
 		// Because the actual handling of the in-flight updates is the same, it's macro'ized here:
+		let channel_id_from_funding_txo = |funding_txo: &OutPoint| {
+			*outpoint_to_channel_id.get(funding_txo)
+				.expect("We should have the channel_id of every channel we have a ChannelMonitor for")
+		};
 		let mut pending_background_events = Vec::new();
 		macro_rules! handle_in_flight_updates {
 			($counterparty_node_id: expr, $chan_in_flight_upds: expr, $funding_txo: expr,
@@ -10600,7 +12734,7 @@ where
 				$chan_in_flight_upds.retain(|upd| upd.update_id > $monitor.get_latest_update_id());
 				for update in $chan_in_flight_upds.iter() {
 					log_trace!($logger, "Replaying ChannelMonitorUpdate {} for {}channel {}",
-						update.update_id, $channel_info_log, &$funding_txo.to_channel_id());
+						update.update_id, $channel_info_log, &channel_id_from_funding_txo(&$funding_txo));
 					max_in_flight_update_id = cmp::max(max_in_flight_update_id, update.update_id);
 					pending_background_events.push(
 						BackgroundEvent::MonitorUpdateRegeneratedOnStartup {
@@ -10616,7 +12750,7 @@ where
 					pending_background_events.push(
 						BackgroundEvent::MonitorUpdatesComplete {
 							counterparty_node_id: $counterparty_node_id,
-							channel_id: $funding_txo.to_channel_id(),
+							channel_id: channel_id_from_funding_txo(&$funding_txo),
 						});
 				}
 				if $peer_state.in_flight_monitor_updates.insert($funding_txo, $chan_in_flight_upds).is_some() {
@@ -10670,7 +12804,7 @@ where
 
 		if let Some(in_flight_upds) = in_flight_monitor_updates {
 			for ((counterparty_id, funding_txo), mut chan_in_flight_updates) in in_flight_upds {
-				let logger = WithContext::from(&args.logger, Some(counterparty_id), Some(funding_txo.to_channel_id()));
+				let logger = WithContext::from(&args.logger, Some(counterparty_id), Some(channel_id_from_funding_txo(&funding_txo)));
 				if let Some(monitor) = args.channel_monitors.get(&funding_txo) {
 					// Now that we've removed all the in-flight monitor updates for channels that are
 					// still open, we need to replay any monitor updates that are for closed channels,
@@ -10684,7 +12818,7 @@ where
 				} else {
 					log_error!(logger, "A ChannelMonitor is missing even though we have in-flight updates for it! This indicates a potentially-critical violation of the chain::Watch API!");
 					log_error!(logger, " The ChannelMonitor for channel {} is missing.",
-						&funding_txo.to_channel_id());
+						&channel_id_from_funding_txo(&funding_txo));
 					log_error!(logger, " The chain::Watch API *requires* that monitors are persisted durably before returning,");
 					log_error!(logger, " client applications must ensure that ChannelMonitor data is always available and the latest to avoid funds loss!");
 					log_error!(logger, " Without the latest ChannelMonitor we cannot continue without risking funds.");
@@ -10772,7 +12906,7 @@ where
 										if let HTLCForwardInfo::AddHTLC(htlc_info) = forward {
 											if pending_forward_matches_htlc(&htlc_info) {
 												log_info!(logger, "Removing pending to-forward HTLC with hash {} as it was forwarded to the closed channel {}",
-													&htlc.payment_hash, &monitor.get_funding_txo().0.to_channel_id());
+													&htlc.payment_hash, &monitor.channel_id());
 												false
 											} else { true }
 										} else { true }
@@ -10782,7 +12916,7 @@ where
 								pending_intercepted_htlcs.as_mut().unwrap().retain(|intercepted_id, htlc_info| {
 									if pending_forward_matches_htlc(&htlc_info) {
 										log_info!(logger, "Removing pending intercepted HTLC with hash {} as it was forwarded to the closed channel {}",
-											&htlc.payment_hash, &monitor.get_funding_txo().0.to_channel_id());
+											&htlc.payment_hash, &monitor.channel_id());
 										pending_events_read.retain(|(event, _)| {
 											if let Event::HTLCIntercepted { intercept_id: ev_id, .. } = event {
 												intercepted_id != ev_id
@@ -11004,7 +13138,7 @@ where
 						// this channel as well. On the flip side, there's no harm in restarting
 						// without the new monitor persisted - we'll end up right back here on
 						// restart.
-						let previous_channel_id = claimable_htlc.prev_hop.outpoint.to_channel_id();
+						let previous_channel_id = channel_id_from_funding_txo(&claimable_htlc.prev_hop.outpoint);
 						if let Some(peer_node_id) = outpoint_to_peer.get(&claimable_htlc.prev_hop.outpoint) {
 							let peer_state_mutex = per_peer_state.get(peer_node_id).unwrap();
 							let mut peer_state_lock = peer_state_mutex.lock().unwrap();
@@ -11023,7 +13157,9 @@ where
 						payment_hash,
 						purpose: payment.purpose,
 						amount_msat: claimable_amt_msat,
-						htlcs: payment.htlcs.iter().map(events::ClaimedHTLC::from).collect(),
+						htlcs: payment.htlcs.iter()
+							.map(|htlc| htlc.to_claimed_htlc(channel_id_from_funding_txo(&htlc.prev_hop.outpoint)))
+							.collect(),
 						sender_intended_total_msat: payment.htlcs.first().map(|htlc| htlc.total_msat),
 					}, None));
 				}
@@ -11040,11 +13176,12 @@ where
 								Some((blocked_node_id, blocked_channel_outpoint, blocking_action)), ..
 						} = action {
 							if let Some(blocked_peer_state) = per_peer_state.get(&blocked_node_id) {
+								let blocked_channel_id = channel_id_from_funding_txo(&blocked_channel_outpoint);
 								log_trace!(logger,
 									"Holding the next revoke_and_ack from {} until the preimage is durably persisted in the inbound edge's ChannelMonitor",
-									blocked_channel_outpoint.to_channel_id());
+									blocked_channel_id);
 								blocked_peer_state.lock().unwrap().actions_blocking_raa_monitor_updates
-									.entry(blocked_channel_outpoint.to_channel_id())
+									.entry(blocked_channel_id)
 									.or_insert_with(Vec::new).push(blocking_action.clone());
 							} else {
 								// If the channel we were blocking has closed, we don't need to
@@ -11079,11 +13216,13 @@ where
 			pending_inbound_payments: Mutex::new(pending_inbound_payments),
 			pending_outbound_payments: pending_outbounds,
 			pending_intercepted_htlcs: Mutex::new(pending_intercepted_htlcs.unwrap()),
+			pending_held_htlcs: Mutex::new(held_htlcs),
 
 			forward_htlcs: Mutex::new(forward_htlcs),
 			claimable_payments: Mutex::new(ClaimablePayments { claimable_payments, pending_claiming_payments: pending_claiming_payments.unwrap() }),
 			outbound_scid_aliases: Mutex::new(outbound_scid_aliases),
 			outpoint_to_peer: Mutex::new(outpoint_to_peer),
+			outpoint_to_channel_id: Mutex::new(outpoint_to_channel_id),
 			short_to_chan_info: FairRwLock::new(short_to_chan_info),
 			fake_scid_rand_bytes: fake_scid_rand_bytes.unwrap(),
 
@@ -11108,6 +13247,12 @@ where
 			funding_batch_states: Mutex::new(BTreeMap::new()),
 
 			pending_offers_messages: Mutex::new(Vec::new()),
+			pending_async_payments_messages: Mutex::new(Vec::new()),
+			static_invoices: Mutex::new(static_invoices),
+			last_peer_storage_snapshot: Mutex::new(Vec::new()),
+			pending_peer_storage_persists: Mutex::new(HashSet::new()),
+			htlc_reputation: Mutex::new(HTLCReputationTracker::new()),
+			currency_conversion: args.currency_conversion,
 
 			entropy_source: args.entropy_source,
 			node_signer: args.node_signer,
@@ -12285,6 +14430,46 @@ mod tests {
 		assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
 	}
 
+	#[test]
+	fn test_anchor_zero_fee_commitments_fallback() {
+		// Tests that if the remote node rejects a zero-fee commitment channel, we first retry with
+		// regular anchors, and only then without anchors at all.
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let mut zero_fee_config = test_default_channel_config();
+		zero_fee_config.channel_handshake_config.negotiate_anchor_zero_fee_commitments = true;
+		zero_fee_config.manually_accept_inbound_channels = true;
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(zero_fee_config.clone()), Some(zero_fee_config.clone())]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+		nodes[0].node.create_channel(nodes[1].node.get_our_node_id(), 100_000, 0, 0, None, None).unwrap();
+		let mut open_channel_msg = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, nodes[1].node.get_our_node_id());
+		let channel_type = open_channel_msg.channel_type.as_ref().unwrap();
+		assert!(channel_type.supports_anchor_zero_fee_commitments());
+		assert!(channel_type.supports_anchors_zero_fee_htlc_tx());
+		assert_eq!(open_channel_msg.feerate_per_kw, 0);
+
+		for expect_anchors in [true, false] {
+			nodes[1].node.handle_open_channel(&nodes[0].node.get_our_node_id(), &open_channel_msg);
+			let events = nodes[1].node.get_and_clear_pending_events();
+			match events[0] {
+				Event::OpenChannelRequest { temporary_channel_id, .. } => {
+					nodes[1].node.force_close_broadcasting_latest_txn(&temporary_channel_id, &nodes[0].node.get_our_node_id()).unwrap();
+				}
+				_ => panic!("Unexpected event"),
+			}
+
+			let error_msg = get_err_msg(&nodes[1], &nodes[0].node.get_our_node_id());
+			nodes[0].node.handle_error(&nodes[1].node.get_our_node_id(), &error_msg);
+
+			open_channel_msg = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, nodes[1].node.get_our_node_id());
+			let channel_type = open_channel_msg.channel_type.as_ref().unwrap();
+			assert!(!channel_type.supports_anchor_zero_fee_commitments());
+			assert_eq!(channel_type.supports_anchors_zero_fee_htlc_tx(), expect_anchors);
+			assert_ne!(open_channel_msg.feerate_per_kw, 0);
+		}
+	}
+
 	#[test]
 	fn test_update_channel_config() {
 		let chanmon_cfg = create_chanmon_cfgs(2);
@@ -12487,6 +14672,59 @@ mod tests {
 
 		expect_pending_htlcs_forwardable!(nodes[0]);
 	}
+
+	#[test]
+	fn pays_for_offer_denominated_in_currency() {
+		// Checks that offers priced in a currency other than bitcoin are converted using each
+		// node's exchange rate, with some slack allowed for the rates to differ.
+		use crate::offers::offer::Amount;
+		use crate::offers::parse::Bolt12SemanticError;
+		use crate::onion_message::offers::{OffersMessage, OffersMessageHandler};
+		use super::Retry;
+
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		create_announced_chan_between_nodes(&nodes, 0, 1);
+
+		let offer = nodes[1].node.create_offer_builder("coffee".to_string()).unwrap()
+			.amount(Amount::Currency { iso4217_code: *b"USD", amount: 1_000 })
+			.build().unwrap();
+
+		// Without an exchange rate, the payer has to pick the amount itself.
+		let payment_id = PaymentId([1; 32]);
+		assert_eq!(
+			nodes[0].node.pay_for_offer(&offer, None, None, None, payment_id, Retry::Attempts(0), None),
+			Err(Bolt12SemanticError::UnsupportedCurrency)
+		);
+
+		// The payer's rate is slightly more favorable than the recipient's, but within tolerance.
+		nodes[0].currency_conversion.set_rate(*b"USD", 995);
+		nodes[1].currency_conversion.set_rate(*b"USD", 1_000);
+		nodes[0].node.pay_for_offer(&offer, None, None, None, payment_id, Retry::Attempts(0), None).unwrap();
+		let invoice_request = match nodes[0].node.release_pending_messages().pop().unwrap().contents {
+			OffersMessage::InvoiceRequest(invoice_request) => invoice_request,
+			_ => panic!("Unexpected message"),
+		};
+		assert_eq!(invoice_request.amount_msats(), Some(999_975));
+		match nodes[1].node.handle_message(OffersMessage::InvoiceRequest(invoice_request)) {
+			Some(OffersMessage::Invoice(invoice)) => assert_eq!(invoice.amount_msats(), 999_975),
+			_ => panic!("Unexpected message"),
+		}
+
+		// Requests paying too little at the recipient's rate are answered with an error.
+		let payment_id = PaymentId([2; 32]);
+		nodes[0].node.pay_for_offer(&offer, None, Some(980_000), None, payment_id, Retry::Attempts(0), None).unwrap();
+		let invoice_request = match nodes[0].node.release_pending_messages().pop().unwrap().contents {
+			OffersMessage::InvoiceRequest(invoice_request) => invoice_request,
+			_ => panic!("Unexpected message"),
+		};
+		match nodes[1].node.handle_message(OffersMessage::InvoiceRequest(invoice_request)) {
+			Some(OffersMessage::InvoiceError(_)) => {},
+			_ => panic!("Unexpected message"),
+		}
+	}
 }
 
 #[cfg(ldk_bench)]
@@ -12499,6 +14737,7 @@ pub mod bench {
 	use crate::ln::functional_test_utils::*;
 	use crate::ln::msgs::{ChannelMessageHandler, Init};
 	use crate::routing::gossip::NetworkGraph;
+	use crate::offers::offer::NoCurrencyConversion;
 	use crate::routing::router::{PaymentParameters, RouteParameters};
 	use crate::util::test_utils;
 	use crate::util::config::{UserConfig, MaxDustHTLCExposure};
@@ -12518,7 +14757,7 @@ pub mod bench {
 			&'a test_utils::TestLogger, &'a P>,
 		&'a test_utils::TestBroadcaster, &'a KeysManager, &'a KeysManager, &'a KeysManager,
 		&'a test_utils::TestFeeEstimator, &'a test_utils::TestRouter<'a>,
-		&'a test_utils::TestLogger>;
+		&'a test_utils::TestLogger, NoCurrencyConversion>;
 
 	struct ANodeHolder<'node_cfg, 'chan_mon_cfg: 'node_cfg, P: Persist<InMemorySigner>> {
 		node: &'node_cfg Manager<'chan_mon_cfg, P>,
@@ -12555,7 +14794,7 @@ pub mod bench {
 		let chain_monitor_a = ChainMonitor::new(None, &tx_broadcaster, &logger_a, &fee_estimator, &persister_a);
 		let seed_a = [1u8; 32];
 		let keys_manager_a = KeysManager::new(&seed_a, 42, 42);
-		let node_a = ChannelManager::new(&fee_estimator, &chain_monitor_a, &tx_broadcaster, &router, &logger_a, &keys_manager_a, &keys_manager_a, &keys_manager_a, config.clone(), ChainParameters {
+		let node_a = ChannelManager::new(&fee_estimator, &chain_monitor_a, &tx_broadcaster, &router, &logger_a, &keys_manager_a, &keys_manager_a, &keys_manager_a, NoCurrencyConversion {}, config.clone(), ChainParameters {
 			network,
 			best_block: BestBlock::from_network(network),
 		}, genesis_block.header.time);
@@ -12565,7 +14804,7 @@ pub mod bench {
 		let chain_monitor_b = ChainMonitor::new(None, &tx_broadcaster, &logger_a, &fee_estimator, &persister_b);
 		let seed_b = [2u8; 32];
 		let keys_manager_b = KeysManager::new(&seed_b, 42, 42);
-		let node_b = ChannelManager::new(&fee_estimator, &chain_monitor_b, &tx_broadcaster, &router, &logger_b, &keys_manager_b, &keys_manager_b, &keys_manager_b, config.clone(), ChainParameters {
+		let node_b = ChannelManager::new(&fee_estimator, &chain_monitor_b, &tx_broadcaster, &router, &logger_b, &keys_manager_b, &keys_manager_b, &keys_manager_b, NoCurrencyConversion {}, config.clone(), ChainParameters {
 			network,
 			best_block: BestBlock::from_network(network),
 		}, genesis_block.header.time);
@@ -0,0 +1,361 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of opening dual-funded channels, covering the V2 channel establishment handshake, the
+//! interactive construction of the funding transaction and fee-bumping it with `tx_init_rbf`.
+
+use crate::events::{ClosureReason, Event, InboundChannelFunds, MessageSendEvent, MessageSendEventsProvider};
+use crate::ln::ChannelId;
+use crate::ln::channelmanager::FundingContribution;
+use crate::ln::msgs::{self, ChannelMessageHandler, ErrorAction};
+use crate::util::config::UserConfig;
+use crate::util::errors::APIError;
+
+use bitcoin::{Sequence, Transaction, TxIn, TxOut, Witness};
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::blockdata::script::ScriptBuf;
+use bitcoin::blockdata::transaction::OutPoint as BitcoinOutPoint;
+use bitcoin::hash_types::WPubkeyHash;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+
+use crate::ln::functional_test_utils::*;
+
+fn dual_funding_config() -> UserConfig {
+	let mut config = test_default_channel_config();
+	config.enable_dual_funded_channels = true;
+	config
+}
+
+/// A wallet UTXO controlled by `key` which can be contributed to a funding transaction.
+struct FundingInput {
+	key: SecretKey,
+	prevtx: Transaction,
+}
+
+impl FundingInput {
+	fn new(key_byte: u8, value: u64) -> Self {
+		let key = SecretKey::from_slice(&[key_byte; 32]).unwrap();
+		let pubkey = bitcoin::PublicKey::new(key.public_key(&Secp256k1::new()));
+		let prevtx = Transaction {
+			version: 2,
+			lock_time: LockTime::ZERO,
+			input: vec![],
+			output: vec![TxOut { value, script_pubkey: ScriptBuf::new_v0_p2wpkh(&pubkey.wpubkey_hash().unwrap()) }],
+		};
+		FundingInput { key, prevtx }
+	}
+
+	fn contribution(&self, value_satoshis: u64) -> FundingContribution {
+		let input = TxIn {
+			previous_output: BitcoinOutPoint { txid: self.prevtx.txid(), vout: 0 },
+			script_sig: ScriptBuf::new(),
+			sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+			witness: Witness::new(),
+		};
+		FundingContribution {
+			value_satoshis,
+			inputs: vec![(input, self.prevtx.clone())],
+			change_script: Some(ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([self.key[0]; 20]))),
+		}
+	}
+
+	fn sign(&self, tx: &mut Transaction) {
+		let secp_ctx = Secp256k1::new();
+		let pubkey = bitcoin::PublicKey::new(self.key.public_key(&secp_ctx));
+		let input_index = tx.input.iter().position(|input| input.previous_output.txid == self.prevtx.txid()).unwrap();
+		let sighash = SighashCache::new(&*tx).segwit_signature_hash(input_index,
+			&ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()), self.prevtx.output[0].value, EcdsaSighashType::All).unwrap();
+		let signature = secp_ctx.sign_ecdsa(&Message::from_slice(&sighash[..]).unwrap(), &self.key);
+		let mut signature = signature.serialize_der().to_vec();
+		signature.push(EcdsaSighashType::All as u8);
+		tx.input[input_index].witness = Witness::from_slice(&[signature, pubkey.to_bytes()]);
+	}
+}
+
+/// Runs the interactive construction of the funding transaction through to both nodes having
+/// queued their `commitment_signed`, returning them along with the unsigned funding transaction
+/// each node needs to sign, if any.
+fn negotiate_funding_tx<'a, 'b, 'c>(
+	initiator: &Node<'a, 'b, 'c>, acceptor: &Node<'a, 'b, 'c>,
+) -> ((msgs::CommitmentSigned, Option<Transaction>), (msgs::CommitmentSigned, Option<Transaction>)) {
+	let (mut initiator_commitment_signed, mut acceptor_commitment_signed) = (None, None);
+	while initiator_commitment_signed.is_none() || acceptor_commitment_signed.is_none() {
+		if let Some(msg) = handle_interactive_tx_msg_events(initiator, acceptor) {
+			initiator_commitment_signed = Some(msg);
+		}
+		if let Some(msg) = handle_interactive_tx_msg_events(acceptor, initiator) {
+			acceptor_commitment_signed = Some(msg);
+		}
+	}
+	let get_unsigned_transaction = |node: &Node, counterparty: &Node| {
+		node.node.get_and_clear_pending_events().pop().map(|event| match event {
+			Event::FundingTransactionReadyForSigning { unsigned_transaction, counterparty_node_id, .. } => {
+				assert_eq!(counterparty_node_id, counterparty.node.get_our_node_id());
+				unsigned_transaction
+			},
+			_ => panic!("Unexpected event {:?}", event),
+		})
+	};
+	let initiator_unsigned_tx = get_unsigned_transaction(initiator, acceptor);
+	let acceptor_unsigned_tx = get_unsigned_transaction(acceptor, initiator);
+	((initiator_commitment_signed.unwrap(), initiator_unsigned_tx), (acceptor_commitment_signed.unwrap(), acceptor_unsigned_tx))
+}
+
+/// Opens a dual-funded channel from `nodes[0]` contributing 100k sats to `nodes[1]`, which
+/// contributes `acceptor_funding` if set and otherwise accepts the channel without contributing,
+/// returning the channel's ID and its broadcast funding transaction.
+fn open_dual_funded_channel(
+	nodes: &Vec<Node>, initiator_funding: &FundingInput, acceptor_funding: Option<&FundingInput>,
+) -> (ChannelId, Transaction) {
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+	let temporary_channel_id = nodes[0].node.create_dual_funded_channel(node_b_id,
+		initiator_funding.contribution(100_000), 253, None, 42, None).unwrap();
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_b_id);
+	assert_eq!(open_channel.temporary_channel_id, temporary_channel_id);
+	assert_eq!(open_channel.funding_satoshis, 100_000);
+	nodes[1].node.handle_open_channel_v2(&node_a_id, &open_channel);
+
+	if let Some(acceptor_funding) = acceptor_funding {
+		let events = nodes[1].node.get_and_clear_pending_events();
+		assert_eq!(events.len(), 1);
+		match &events[0] {
+			Event::OpenChannelRequest { temporary_channel_id: request_channel_id, funding_satoshis, channel_negotiation_type, .. } => {
+				assert_eq!(*request_channel_id, temporary_channel_id);
+				assert_eq!(*funding_satoshis, 100_000);
+				assert_eq!(*channel_negotiation_type, InboundChannelFunds::DualFunded { funding_feerate_sat_per_1000_weight: 253 });
+			},
+			_ => panic!("Unexpected event {:?}", events[0]),
+		}
+		nodes[1].node.accept_inbound_channel_with_contribution(&temporary_channel_id, &node_a_id, 43,
+			acceptor_funding.contribution(50_000)).unwrap();
+	}
+	let accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_a_id);
+	assert_eq!(accept_channel.funding_satoshis, if acceptor_funding.is_some() { 50_000 } else { 0 });
+	nodes[0].node.handle_accept_channel_v2(&node_b_id, &accept_channel);
+
+	let ((initiator_commitment_signed, initiator_unsigned_tx), (acceptor_commitment_signed, acceptor_unsigned_tx)) =
+		negotiate_funding_tx(&nodes[0], &nodes[1]);
+	let channel_id = initiator_commitment_signed.channel_id;
+	assert_ne!(channel_id, temporary_channel_id);
+	assert_eq!(channel_id, acceptor_commitment_signed.channel_id);
+
+	// The initiator signs its inputs before its counterparty's commitment_signed arrives, while the
+	// acceptor signs them afterwards.
+	let mut initiator_tx = initiator_unsigned_tx.unwrap();
+	initiator_funding.sign(&mut initiator_tx);
+	nodes[0].node.funding_transaction_signed(&channel_id, &node_b_id, initiator_tx).unwrap();
+
+	nodes[1].node.handle_commitment_signed(&node_a_id, &initiator_commitment_signed);
+	check_added_monitors(&nodes[1], 1);
+	nodes[0].node.handle_commitment_signed(&node_b_id, &acceptor_commitment_signed);
+	check_added_monitors(&nodes[0], 1);
+
+	match acceptor_funding {
+		Some(acceptor_funding) => {
+			let mut acceptor_tx = acceptor_unsigned_tx.unwrap();
+			acceptor_funding.sign(&mut acceptor_tx);
+			nodes[1].node.funding_transaction_signed(&channel_id, &node_a_id, acceptor_tx).unwrap();
+		},
+		None => assert!(acceptor_unsigned_tx.is_none()),
+	}
+
+	let funding_tx = exchange_tx_signatures(&nodes[0], &nodes[1]);
+	expect_channel_pending_event(&nodes[0], &node_b_id);
+	expect_channel_pending_event(&nodes[1], &node_a_id);
+	(channel_id, funding_tx)
+}
+
+#[test]
+fn test_dual_funded_channel_open() {
+	// Open a channel to which both parties contribute funds, checking that it can be used once
+	// the funding transaction confirms.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut acceptor_config = dual_funding_config();
+	acceptor_config.manually_accept_inbound_channels = true;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(dual_funding_config()), Some(acceptor_config)]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let initiator_funding = FundingInput::new(42, 130_000);
+	let acceptor_funding = FundingInput::new(43, 60_000);
+	let (channel_id, funding_tx) = open_dual_funded_channel(&nodes, &initiator_funding, Some(&acceptor_funding));
+	assert_eq!(funding_tx.input.len(), 2);
+	let funding_output_index = funding_tx.output.iter().position(|output| output.value == 150_000).unwrap();
+	for node in nodes.iter() {
+		let channel = &node.node.list_channels()[0];
+		assert_eq!(channel.channel_id, channel_id);
+		assert_eq!(channel.channel_value_satoshis, 150_000);
+		assert_eq!(channel.funding_txo.unwrap().txid, funding_tx.txid());
+		assert_eq!(channel.funding_txo.unwrap().index as usize, funding_output_index);
+		assert!(!channel.is_channel_ready);
+	}
+	assert_eq!(nodes[0].node.list_channels()[0].user_channel_id, 42);
+	assert_eq!(nodes[1].node.list_channels()[0].user_channel_id, 43);
+
+	let (channel_ready, _) = create_chan_between_nodes_with_value_confirm(&nodes[0], &nodes[1], &funding_tx);
+	let (announcement, as_update, bs_update) = create_chan_between_nodes_with_value_b(&nodes[0], &nodes[1], &channel_ready);
+	update_nodes_with_chan_announce(&nodes, 0, 1, &announcement, &as_update, &bs_update);
+
+	// Both sides start with the funds they contributed.
+	assert_eq!(nodes[1].node.list_channels()[0].balance_msat, 50_000_000);
+	send_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+	send_payment(&nodes[1], &[&nodes[0]], 5_000_000);
+
+	// Commitment transactions spend the negotiated funding output.
+	nodes[0].node.force_close_broadcasting_latest_txn(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast!(nodes[0], true);
+	check_closed_event!(nodes[0], 1, ClosureReason::HolderForceClosed, [nodes[1].node.get_our_node_id()], 150_000);
+	let commitment_txn = nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert_eq!(commitment_txn.len(), 1);
+	check_spends!(commitment_txn[0], funding_tx);
+}
+
+#[test]
+fn test_dual_funded_channel_open_without_acceptor_contribution() {
+	// Without manual acceptance, dual-funded channels are accepted without contributing to them.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(dual_funding_config()), Some(dual_funding_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let initiator_funding = FundingInput::new(42, 130_000);
+	let (_, funding_tx) = open_dual_funded_channel(&nodes, &initiator_funding, None);
+	assert_eq!(funding_tx.input.len(), 1);
+	assert_eq!(nodes[1].node.list_channels()[0].channel_value_satoshis, 100_000);
+
+	let (channel_ready, _) = create_chan_between_nodes_with_value_confirm(&nodes[0], &nodes[1], &funding_tx);
+	let (announcement, as_update, bs_update) = create_chan_between_nodes_with_value_b(&nodes[0], &nodes[1], &channel_ready);
+	update_nodes_with_chan_announce(&nodes, 0, 1, &announcement, &as_update, &bs_update);
+	assert_eq!(nodes[1].node.list_channels()[0].balance_msat, 0);
+	send_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+}
+
+#[test]
+fn test_dual_funded_channel_requires_peer_support() {
+	// We can't open a dual-funded channel with a peer which hasn't signaled support for them.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(dual_funding_config()), None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+
+	let initiator_funding = FundingInput::new(42, 130_000);
+	match nodes[0].node.create_dual_funded_channel(nodes[1].node.get_our_node_id(),
+		initiator_funding.contribution(100_000), 253, None, 42, None)
+	{
+		Err(APIError::APIMisuseError { err }) => assert!(err.contains("does not support dual-funded channels")),
+		res => panic!("Unexpected result {:?}", res),
+	}
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+}
+
+#[test]
+fn test_dual_funded_channel_negotiation_aborted() {
+	// A tx_abort while negotiating the funding transaction closes the channel.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(dual_funding_config()), Some(dual_funding_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let initiator_funding = FundingInput::new(42, 130_000);
+	nodes[0].node.create_dual_funded_channel(node_b_id, initiator_funding.contribution(100_000), 253, None, 42, None).unwrap();
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannelV2, node_b_id);
+	nodes[1].node.handle_open_channel_v2(&node_a_id, &open_channel);
+	let accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannelV2, node_a_id);
+	nodes[0].node.handle_accept_channel_v2(&node_b_id, &accept_channel);
+	let tx_add_input = get_event_msg!(nodes[0], MessageSendEvent::SendTxAddInput, node_b_id);
+	let channel_id = tx_add_input.channel_id;
+
+	nodes[0].node.handle_tx_abort(&node_b_id, &msgs::TxAbort { channel_id, data: b"Changed our mind".to_vec() });
+	let events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 2);
+	match &events[0] {
+		MessageSendEvent::SendTxAbort { msg, .. } => assert_eq!(msg.channel_id, channel_id),
+		_ => panic!("Unexpected event {:?}", events[0]),
+	}
+	match &events[1] {
+		MessageSendEvent::HandleError { action: ErrorAction::SendErrorMessage { msg }, .. } =>
+			assert_eq!(msg.channel_id, channel_id),
+		_ => panic!("Unexpected event {:?}", events[1]),
+	}
+	check_closed_event!(nodes[0], 1, ClosureReason::ProcessingError {
+		err: "Counterparty aborted the funding negotiation: Changed our mind".to_owned()
+	}, [node_b_id], 100_000);
+	assert!(nodes[0].node.list_channels().is_empty());
+}
+
+#[test]
+fn test_dual_funded_channel_rbf() {
+	// Fee-bump the funding transaction of a dual-funded channel before it confirms, checking that
+	// the channel is usable once the replacement confirms.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(dual_funding_config()), Some(dual_funding_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	let initiator_funding = FundingInput::new(42, 130_000);
+	let (channel_id, funding_tx) = open_dual_funded_channel(&nodes, &initiator_funding, None);
+
+	// The feerate has to increase by at least 25/24.
+	match nodes[0].node.bump_channel_funding_fee(&channel_id, &node_b_id, initiator_funding.contribution(100_000), 260, None) {
+		Err(APIError::ChannelUnavailable { err }) => assert!(err.contains("must be at least 263 sat/kW")),
+		res => panic!("Unexpected result {:?}", res),
+	}
+	// Only the opener may fee-bump the funding transaction.
+	match nodes[1].node.bump_channel_funding_fee(&channel_id, &node_a_id, initiator_funding.contribution(0), 1000, None) {
+		Err(APIError::APIMisuseError { err }) => assert!(err.contains("Only the opener")),
+		res => panic!("Unexpected result {:?}", res),
+	}
+
+	nodes[0].node.bump_channel_funding_fee(&channel_id, &node_b_id, initiator_funding.contribution(100_000), 1000, None).unwrap();
+	let tx_init_rbf = get_event_msg!(nodes[0], MessageSendEvent::SendTxInitRbf, node_b_id);
+	assert_eq!(tx_init_rbf.feerate_sat_per_1000_weight, 1000);
+	nodes[1].node.handle_tx_init_rbf(&node_a_id, &tx_init_rbf);
+	let tx_ack_rbf = get_event_msg!(nodes[1], MessageSendEvent::SendTxAckRbf, node_a_id);
+	nodes[0].node.handle_tx_ack_rbf(&node_b_id, &tx_ack_rbf);
+
+	let ((initiator_commitment_signed, initiator_unsigned_tx), (acceptor_commitment_signed, acceptor_unsigned_tx)) =
+		negotiate_funding_tx(&nodes[0], &nodes[1]);
+	assert!(acceptor_unsigned_tx.is_none());
+	nodes[1].node.handle_commitment_signed(&node_a_id, &initiator_commitment_signed);
+	check_added_monitors(&nodes[1], 1);
+	nodes[0].node.handle_commitment_signed(&node_b_id, &acceptor_commitment_signed);
+	check_added_monitors(&nodes[0], 1);
+	let mut rbf_tx = initiator_unsigned_tx.unwrap();
+	initiator_funding.sign(&mut rbf_tx);
+	nodes[0].node.funding_transaction_signed(&channel_id, &node_b_id, rbf_tx).unwrap();
+
+	let rbf_tx = exchange_tx_signatures(&nodes[0], &nodes[1]);
+	assert_ne!(rbf_tx.txid(), funding_tx.txid());
+	assert_eq!(rbf_tx.input[0].previous_output, funding_tx.input[0].previous_output);
+	let fee = |tx: &Transaction| 130_000 - tx.output.iter().map(|output| output.value).sum::<u64>();
+	assert!(fee(&rbf_tx) > fee(&funding_tx));
+	// The fee-bump doesn't generate another `ChannelPending` event.
+	assert!(nodes[0].node.get_and_clear_pending_events().is_empty());
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+
+	let (channel_ready, _) = create_chan_between_nodes_with_value_confirm(&nodes[0], &nodes[1], &rbf_tx);
+	let (announcement, as_update, bs_update) = create_chan_between_nodes_with_value_b(&nodes[0], &nodes[1], &channel_ready);
+	update_nodes_with_chan_announce(&nodes, 0, 1, &announcement, &as_update, &bs_update);
+	let rbf_funding_output_index = rbf_tx.output.iter().position(|output| output.value == 100_000).unwrap();
+	for node in nodes.iter() {
+		let channel = &node.node.list_channels()[0];
+		assert_eq!(channel.channel_id, channel_id);
+		assert_eq!(channel.funding_txo.unwrap().txid, rbf_tx.txid());
+		assert_eq!(channel.funding_txo.unwrap().index as usize, rbf_funding_output_index);
+	}
+	send_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+}
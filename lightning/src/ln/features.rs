@@ -49,9 +49,20 @@
 //!     (see [BOLT-4](https://github.com/lightning/bolts/blob/master/04-onion-routing.md#route-blinding) for more information).
 //! - `ShutdownAnySegwit` - requires/supports that future segwit versions are allowed in `shutdown`
 //!     (see [BOLT-2](https://github.com/lightning/bolts/blob/master/02-peer-protocol.md) for more information).
+//! - `DualFund` - requires/supports V2 channel establishment, in which both parties may contribute
+//!     funds to the channel's funding transaction
+//!     (see [BOLT-2](https://github.com/lightning/bolts/blob/master/02-peer-protocol.md#channel-establishment-v2) for more information).
+//! - `Quiescence` - requires/supports the `stfu` message, used to pause updates to a channel
+//!     (see [BOLT-2](https://github.com/lightning/bolts/pull/869) for more information).
 //! - `OnionMessages` - requires/supports forwarding onion messages
 //!     (see [BOLT-7](https://github.com/lightning/bolts/pull/759/files) for more information).
 //     TODO: update link
+//! - `AnchorZeroFeeCommitments` - requires/supports that commitment transactions are version 3
+//!     (TRUC) transactions paying zero fee with a single shared ephemeral anchor output
+//!     (see [BOLT-3](https://github.com/lightning/bolts/pull/1228) for more information).
+//! - `ProvideStorage` - requires/supports storing encrypted backup blobs on behalf of peers we
+//!     have channels with (see [BOLT-1](https://github.com/lightning/bolts/pull/1110) for more
+//!     information).
 //! - `ChannelType` - node supports the channel_type field in open/accept
 //!     (see [BOLT-2](https://github.com/lightning/bolts/blob/master/02-peer-protocol.md) for more information).
 //! - `SCIDPrivacy` - supply channel aliases for routing
@@ -66,6 +77,11 @@
 //!      for more info).
 //! - `Keysend` - send funds to a node without an invoice
 //!     (see the [`Keysend` feature assignment proposal](https://github.com/lightning/bolts/issues/605#issuecomment-606679798) for more information).
+//! - `TrampolineRouting` - supports forwarding payments as a trampoline node, i.e. computing the
+//!     route to the next trampoline node or the recipient on behalf of the sender
+//!     (see [BOLT-4](https://github.com/lightning/bolts/pull/836) for more information).
+//! - `Splicing` - supports replacing the funding transaction of a live channel to add or remove
+//!     funds (see [BOLT-2](https://github.com/lightning/bolts/pull/863) for more information).
 //!
 //! LDK knows about the following features, but does not support them:
 //! - `AnchorsNonzeroFeeHtlcTx` - the initial version of anchor outputs, which was later found to be
@@ -145,13 +161,15 @@ mod sealed {
 		// Byte 2
 		BasicMPP | Wumbo | AnchorsNonzeroFeeHtlcTx | AnchorsZeroFeeHtlcTx,
 		// Byte 3
-		RouteBlinding | ShutdownAnySegwit | Taproot,
+		RouteBlinding | ShutdownAnySegwit | DualFund | Taproot,
 		// Byte 4
-		OnionMessages,
+		Quiescence | OnionMessages,
 		// Byte 5
-		ChannelType | SCIDPrivacy,
+		AnchorZeroFeeCommitments | ProvideStorage | ChannelType | SCIDPrivacy,
 		// Byte 6
 		ZeroConf,
+		// Byte 7
+		TrampolineRouting | Splicing,
 	]);
 	define_context!(NodeContext, [
 		// Byte 0
@@ -161,13 +179,15 @@ mod sealed {
 		// Byte 2
 		BasicMPP | Wumbo | AnchorsNonzeroFeeHtlcTx | AnchorsZeroFeeHtlcTx,
 		// Byte 3
-		RouteBlinding | ShutdownAnySegwit | Taproot,
+		RouteBlinding | ShutdownAnySegwit | DualFund | Taproot,
 		// Byte 4
-		OnionMessages,
+		Quiescence | OnionMessages,
 		// Byte 5
-		ChannelType | SCIDPrivacy,
+		AnchorZeroFeeCommitments | ProvideStorage | ChannelType | SCIDPrivacy,
 		// Byte 6
 		ZeroConf | Keysend,
+		// Byte 7
+		TrampolineRouting | Splicing,
 	]);
 	define_context!(ChannelContext, []);
 	define_context!(Bolt11InvoiceContext, [
@@ -211,7 +231,7 @@ mod sealed {
 		// Byte 4
 		,
 		// Byte 5
-		SCIDPrivacy,
+		AnchorZeroFeeCommitments | SCIDPrivacy,
 		// Byte 6
 		ZeroConf,
 	]);
@@ -399,12 +419,25 @@ mod sealed {
 	define_feature!(27, ShutdownAnySegwit, [InitContext, NodeContext],
 		"Feature flags for `opt_shutdown_anysegwit`.", set_shutdown_any_segwit_optional,
 		set_shutdown_any_segwit_required, supports_shutdown_anysegwit, requires_shutdown_anysegwit);
+	define_feature!(29, DualFund, [InitContext, NodeContext],
+		"Feature flags for `option_dual_fund`.", set_dual_fund_optional, set_dual_fund_required,
+		supports_dual_fund, requires_dual_fund);
 	define_feature!(31, Taproot, [InitContext, NodeContext, ChannelTypeContext],
 		"Feature flags for `option_taproot`.", set_taproot_optional,
 		set_taproot_required, supports_taproot, requires_taproot);
+	define_feature!(35, Quiescence, [InitContext, NodeContext],
+		"Feature flags for `option_quiesce`.", set_quiescence_optional, set_quiescence_required,
+		supports_quiescence, requires_quiescence);
 	define_feature!(39, OnionMessages, [InitContext, NodeContext],
 		"Feature flags for `option_onion_messages`.", set_onion_messages_optional,
 		set_onion_messages_required, supports_onion_messages, requires_onion_messages);
+	define_feature!(41, AnchorZeroFeeCommitments, [InitContext, NodeContext, ChannelTypeContext],
+		"Feature flags for `option_zero_fee_commitments`.", set_anchor_zero_fee_commitments_optional,
+		set_anchor_zero_fee_commitments_required, supports_anchor_zero_fee_commitments,
+		requires_anchor_zero_fee_commitments);
+	define_feature!(43, ProvideStorage, [InitContext, NodeContext],
+		"Feature flags for `option_provide_storage`.", set_provide_storage_optional,
+		set_provide_storage_required, supports_provide_storage, requires_provide_storage);
 	define_feature!(45, ChannelType, [InitContext, NodeContext],
 		"Feature flags for `option_channel_type`.", set_channel_type_optional,
 		set_channel_type_required, supports_channel_type, requires_channel_type);
@@ -420,6 +453,12 @@ mod sealed {
 	define_feature!(55, Keysend, [NodeContext],
 		"Feature flags for keysend payments.", set_keysend_optional, set_keysend_required,
 		supports_keysend, requires_keysend);
+	define_feature!(57, TrampolineRouting, [InitContext, NodeContext],
+		"Feature flags for `option_trampoline_routing`.", set_trampoline_routing_optional,
+		set_trampoline_routing_required, supports_trampoline_routing, requires_trampoline_routing);
+	define_feature!(63, Splicing, [InitContext, NodeContext],
+		"Feature flags for `option_splice`.", set_splicing_optional, set_splicing_required,
+		supports_splicing, requires_splicing);
 	// Note: update the module-level docs when a new feature bit is added!
 
 	#[cfg(test)]
@@ -622,6 +661,14 @@ impl ChannelTypeFeatures {
 		<sealed::ChannelTypeContext as sealed::AnchorsZeroFeeHtlcTx>::set_required_bit(&mut ret.flags);
 		ret
 	}
+
+	/// Constructs a ChannelTypeFeatures with zero-fee commitments support. These build on top of
+	/// anchors, using the same output scripts and zero-fee HTLC transactions.
+	pub(crate) fn anchors_zero_fee_commitments_and_dependencies() -> Self {
+		let mut ret = Self::anchors_zero_htlc_fee_and_dependencies();
+		<sealed::ChannelTypeContext as sealed::AnchorZeroFeeCommitments>::set_required_bit(&mut ret.flags);
+		ret
+	}
 }
 
 impl ToBase32 for Bolt11InvoiceFeatures {
@@ -932,6 +979,12 @@ impl<T: sealed::AnchorsZeroFeeHtlcTx> Features<T> {
 	}
 }
 
+impl<T: sealed::AnchorZeroFeeCommitments> Features<T> {
+	pub(crate) fn clear_anchor_zero_fee_commitments(&mut self) {
+		<T as sealed::AnchorZeroFeeCommitments>::clear_bits(&mut self.flags);
+	}
+}
+
 #[cfg(test)]
 impl<T: sealed::UnknownFeature> Features<T> {
 	pub(crate) fn unknown() -> Self {
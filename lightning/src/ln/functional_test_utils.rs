@@ -381,6 +381,7 @@ pub struct TestChanMonCfg {
 	pub logger: test_utils::TestLogger,
 	pub keys_manager: test_utils::TestKeysInterface,
 	pub scorer: RwLock<test_utils::TestScorer>,
+	pub currency_conversion: test_utils::TestCurrencyConversion,
 }
 
 pub struct NodeCfg<'a> {
@@ -391,6 +392,7 @@ pub struct NodeCfg<'a> {
 	pub chain_monitor: test_utils::TestChainMonitor<'a>,
 	pub keys_manager: &'a test_utils::TestKeysInterface,
 	pub logger: &'a test_utils::TestLogger,
+	pub currency_conversion: &'a test_utils::TestCurrencyConversion,
 	pub network_graph: Arc<NetworkGraph<&'a test_utils::TestLogger>>,
 	pub node_seed: [u8; 32],
 	pub override_init_features: Rc<RefCell<Option<InitFeatures>>>,
@@ -405,6 +407,7 @@ type TestChannelManager<'node_cfg, 'chan_mon_cfg> = ChannelManager<
 	&'chan_mon_cfg test_utils::TestFeeEstimator,
 	&'node_cfg test_utils::TestRouter<'chan_mon_cfg>,
 	&'chan_mon_cfg test_utils::TestLogger,
+	&'chan_mon_cfg test_utils::TestCurrencyConversion,
 >;
 
 pub struct Node<'chan_man, 'node_cfg: 'chan_man, 'chan_mon_cfg: 'node_cfg> {
@@ -421,6 +424,7 @@ pub struct Node<'chan_man, 'node_cfg: 'chan_man, 'chan_mon_cfg: 'node_cfg> {
 	pub network_payment_count: Rc<RefCell<u8>>,
 	pub network_chan_count: Rc<RefCell<u32>>,
 	pub logger: &'chan_mon_cfg test_utils::TestLogger,
+	pub currency_conversion: &'chan_mon_cfg test_utils::TestCurrencyConversion,
 	pub blocks: Arc<Mutex<Vec<(Block, u32)>>>,
 	pub connect_style: Rc<RefCell<ConnectStyle>>,
 	pub override_init_features: Rc<RefCell<Option<InitFeatures>>>,
@@ -491,7 +495,8 @@ pub trait NodeHolder {
 		<Self::CM as AChannelManager>::SP,
 		<Self::CM as AChannelManager>::F,
 		<Self::CM as AChannelManager>::R,
-		<Self::CM as AChannelManager>::L>;
+		<Self::CM as AChannelManager>::L,
+		<Self::CM as AChannelManager>::CC>;
 	fn chain_monitor(&self) -> Option<&test_utils::TestChainMonitor>;
 }
 impl<H: NodeHolder> NodeHolder for &H {
@@ -504,7 +509,8 @@ impl<H: NodeHolder> NodeHolder for &H {
 		<Self::CM as AChannelManager>::SP,
 		<Self::CM as AChannelManager>::F,
 		<Self::CM as AChannelManager>::R,
-		<Self::CM as AChannelManager>::L> { (*self).node() }
+		<Self::CM as AChannelManager>::L,
+		<Self::CM as AChannelManager>::CC> { (*self).node() }
 	fn chain_monitor(&self) -> Option<&test_utils::TestChainMonitor> { (*self).chain_monitor() }
 }
 impl<'a, 'b: 'a, 'c: 'b> NodeHolder for Node<'a, 'b, 'c> {
@@ -593,7 +599,7 @@ impl<'a, 'b, 'c> Drop for Node<'a, 'b, 'c> {
 				let scorer = RwLock::new(test_utils::TestScorer::new());
 				let mut w = test_utils::TestVecWriter(Vec::new());
 				self.node.write(&mut w).unwrap();
-				<(BlockHash, ChannelManager<&test_utils::TestChainMonitor, &test_utils::TestBroadcaster, &test_utils::TestKeysInterface, &test_utils::TestKeysInterface, &test_utils::TestKeysInterface, &test_utils::TestFeeEstimator, &test_utils::TestRouter, &test_utils::TestLogger>)>::read(&mut io::Cursor::new(w.0), ChannelManagerReadArgs {
+				<(BlockHash, ChannelManager<&test_utils::TestChainMonitor, &test_utils::TestBroadcaster, &test_utils::TestKeysInterface, &test_utils::TestKeysInterface, &test_utils::TestKeysInterface, &test_utils::TestFeeEstimator, &test_utils::TestRouter, &test_utils::TestLogger, &test_utils::TestCurrencyConversion>)>::read(&mut io::Cursor::new(w.0), ChannelManagerReadArgs {
 					default_config: *self.node.get_current_default_configuration(),
 					entropy_source: self.keys_manager,
 					node_signer: self.keys_manager,
@@ -603,6 +609,7 @@ impl<'a, 'b, 'c> Drop for Node<'a, 'b, 'c> {
 					chain_monitor: self.chain_monitor,
 					tx_broadcaster: &broadcaster,
 					logger: &self.logger,
+					currency_conversion: self.currency_conversion,
 					channel_monitors,
 				}).unwrap();
 			}
@@ -822,6 +829,12 @@ pub fn remove_first_msg_event_to_node(msg_node_id: &PublicKey, msg_events: &mut
 		MessageSendEvent::SendOpenChannelV2 { node_id, .. } => {
 			node_id == msg_node_id
 		},
+		MessageSendEvent::SendPeerStorage { node_id, .. } => {
+			node_id == msg_node_id
+		},
+		MessageSendEvent::SendPeerStorageRetrieval { node_id, .. } => {
+			node_id == msg_node_id
+		},
 		MessageSendEvent::SendStfu { node_id, .. } => {
 			node_id == msg_node_id
 		},
@@ -1029,6 +1042,7 @@ pub fn _reload_node<'a, 'b, 'c>(node: &'a Node<'a, 'b, 'c>, default_config: User
 			chain_monitor: node.chain_monitor,
 			tx_broadcaster: node.tx_broadcaster,
 			logger: node.logger,
+			currency_conversion: node.currency_conversion,
 			channel_monitors,
 		}).unwrap()
 	};
@@ -1148,6 +1162,64 @@ pub fn sign_funding_transaction<'a, 'b, 'c>(node_a: &Node<'a, 'b, 'c>, node_b: &
 	tx
 }
 
+/// Delivers the interactive transaction construction messages `node` has queued for
+/// `counterparty`, returning its `commitment_signed` once the transaction is negotiated.
+pub fn handle_interactive_tx_msg_events<'a, 'b, 'c>(
+	node: &Node<'a, 'b, 'c>, counterparty: &Node<'a, 'b, 'c>,
+) -> Option<msgs::CommitmentSigned> {
+	let node_id = node.node.get_our_node_id();
+	let mut commitment_signed = None;
+	for event in node.node.get_and_clear_pending_msg_events() {
+		match event {
+			MessageSendEvent::SendTxAddInput { msg, .. } => counterparty.node.handle_tx_add_input(&node_id, &msg),
+			MessageSendEvent::SendTxAddOutput { msg, .. } => counterparty.node.handle_tx_add_output(&node_id, &msg),
+			MessageSendEvent::SendTxComplete { msg, .. } => counterparty.node.handle_tx_complete(&node_id, &msg),
+			MessageSendEvent::UpdateHTLCs { updates, .. } => {
+				assert!(updates.update_add_htlcs.is_empty());
+				assert!(updates.update_fee.is_none());
+				commitment_signed = Some(updates.commitment_signed);
+			},
+			_ => panic!("Unexpected event {:?}", event),
+		}
+	}
+	commitment_signed
+}
+
+/// Exchanges `tx_signatures` for an interactively constructed transaction between the two nodes,
+/// returning the transaction they've both broadcast.
+pub fn exchange_tx_signatures<'a, 'b, 'c>(node_a: &Node<'a, 'b, 'c>, node_b: &Node<'a, 'b, 'c>) -> Transaction {
+	let node_a_id = node_a.node.get_our_node_id();
+	let node_b_id = node_b.node.get_our_node_id();
+	loop {
+		let mut delivered = false;
+		for event in node_a.node.get_and_clear_pending_msg_events() {
+			match event {
+				MessageSendEvent::SendTxSignatures { msg, .. } => {
+					node_b.node.handle_tx_signatures(&node_a_id, &msg);
+					delivered = true;
+				},
+				_ => panic!("Unexpected event {:?}", event),
+			}
+		}
+		for event in node_b.node.get_and_clear_pending_msg_events() {
+			match event {
+				MessageSendEvent::SendTxSignatures { msg, .. } => {
+					node_a.node.handle_tx_signatures(&node_b_id, &msg);
+					delivered = true;
+				},
+				_ => panic!("Unexpected event {:?}", event),
+			}
+		}
+		if !delivered { break; }
+	}
+
+	let node_a_txn = node_a.tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	let node_b_txn = node_b.tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert_eq!(node_a_txn.len(), 1);
+	assert_eq!(node_a_txn, node_b_txn);
+	node_a_txn[0].clone()
+}
+
 // Receiver must have been initialized with manually_accept_inbound_channels set to true.
 pub fn open_zero_conf_channel<'a, 'b, 'c, 'd>(initiator: &'a Node<'b, 'c, 'd>, receiver: &'a Node<'b, 'c, 'd>, initiator_config: Option<UserConfig>) -> (bitcoin::Transaction, ChannelId) {
 	let initiator_channels = initiator.node.list_usable_channels().len();
@@ -2874,8 +2946,9 @@ pub fn create_chanmon_cfgs(node_count: usize) -> Vec<TestChanMonCfg> {
 		let seed = [i as u8; 32];
 		let keys_manager = test_utils::TestKeysInterface::new(&seed, Network::Testnet);
 		let scorer = RwLock::new(test_utils::TestScorer::new());
+		let currency_conversion = test_utils::TestCurrencyConversion::new();
 
-		chan_mon_cfgs.push(TestChanMonCfg { tx_broadcaster, fee_estimator, chain_source, logger, persister, keys_manager, scorer });
+		chan_mon_cfgs.push(TestChanMonCfg { tx_broadcaster, fee_estimator, chain_source, logger, persister, keys_manager, scorer, currency_conversion });
 	}
 
 	chan_mon_cfgs
@@ -2895,6 +2968,7 @@ pub fn create_node_cfgs_with_persisters<'a>(node_count: usize, chanmon_cfgs: &'a
 		nodes.push(NodeCfg {
 			chain_source: &chanmon_cfgs[i].chain_source,
 			logger: &chanmon_cfgs[i].logger,
+			currency_conversion: &chanmon_cfgs[i].currency_conversion,
 			tx_broadcaster: &chanmon_cfgs[i].tx_broadcaster,
 			fee_estimator: &chanmon_cfgs[i].fee_estimator,
 			router: test_utils::TestRouter::new(network_graph.clone(), &chanmon_cfgs[i].scorer),
@@ -2927,7 +3001,7 @@ pub fn test_default_channel_config() -> UserConfig {
 	default_config
 }
 
-pub fn create_node_chanmgrs<'a, 'b>(node_count: usize, cfgs: &'a Vec<NodeCfg<'b>>, node_config: &[Option<UserConfig>]) -> Vec<ChannelManager<&'a TestChainMonitor<'b>, &'b test_utils::TestBroadcaster, &'a test_utils::TestKeysInterface, &'a test_utils::TestKeysInterface, &'a test_utils::TestKeysInterface, &'b test_utils::TestFeeEstimator, &'a test_utils::TestRouter<'b>, &'b test_utils::TestLogger>> {
+pub fn create_node_chanmgrs<'a, 'b>(node_count: usize, cfgs: &'a Vec<NodeCfg<'b>>, node_config: &[Option<UserConfig>]) -> Vec<ChannelManager<&'a TestChainMonitor<'b>, &'b test_utils::TestBroadcaster, &'a test_utils::TestKeysInterface, &'a test_utils::TestKeysInterface, &'a test_utils::TestKeysInterface, &'b test_utils::TestFeeEstimator, &'a test_utils::TestRouter<'b>, &'b test_utils::TestLogger, &'b test_utils::TestCurrencyConversion>> {
 	let mut chanmgrs = Vec::new();
 	for i in 0..node_count {
 		let network = Network::Testnet;
@@ -2937,14 +3011,14 @@ pub fn create_node_chanmgrs<'a, 'b>(node_count: usize, cfgs: &'a Vec<NodeCfg<'b>
 			best_block: BestBlock::from_network(network),
 		};
 		let node = ChannelManager::new(cfgs[i].fee_estimator, &cfgs[i].chain_monitor, cfgs[i].tx_broadcaster, &cfgs[i].router, cfgs[i].logger, cfgs[i].keys_manager,
-			cfgs[i].keys_manager, cfgs[i].keys_manager, if node_config[i].is_some() { node_config[i].clone().unwrap() } else { test_default_channel_config() }, params, genesis_block.header.time);
+			cfgs[i].keys_manager, cfgs[i].keys_manager, cfgs[i].currency_conversion, if node_config[i].is_some() { node_config[i].clone().unwrap() } else { test_default_channel_config() }, params, genesis_block.header.time);
 		chanmgrs.push(node);
 	}
 
 	chanmgrs
 }
 
-pub fn create_network<'a, 'b: 'a, 'c: 'b>(node_count: usize, cfgs: &'b Vec<NodeCfg<'c>>, chan_mgrs: &'a Vec<ChannelManager<&'b TestChainMonitor<'c>, &'c test_utils::TestBroadcaster, &'b test_utils::TestKeysInterface, &'b test_utils::TestKeysInterface, &'b test_utils::TestKeysInterface, &'c test_utils::TestFeeEstimator, &'c test_utils::TestRouter, &'c test_utils::TestLogger>>) -> Vec<Node<'a, 'b, 'c>> {
+pub fn create_network<'a, 'b: 'a, 'c: 'b>(node_count: usize, cfgs: &'b Vec<NodeCfg<'c>>, chan_mgrs: &'a Vec<ChannelManager<&'b TestChainMonitor<'c>, &'c test_utils::TestBroadcaster, &'b test_utils::TestKeysInterface, &'b test_utils::TestKeysInterface, &'b test_utils::TestKeysInterface, &'c test_utils::TestFeeEstimator, &'c test_utils::TestRouter, &'c test_utils::TestLogger, &'c test_utils::TestCurrencyConversion>>) -> Vec<Node<'a, 'b, 'c>> {
 	let mut nodes = Vec::new();
 	let chan_count = Rc::new(RefCell::new(0));
 	let payment_count = Rc::new(RefCell::new(0));
@@ -2960,6 +3034,7 @@ pub fn create_network<'a, 'b: 'a, 'c: 'b>(node_count: usize, cfgs: &'b Vec<NodeC
 			node: &chan_mgrs[i], network_graph: cfgs[i].network_graph.as_ref(), gossip_sync,
 			node_seed: cfgs[i].node_seed, network_chan_count: chan_count.clone(),
 			network_payment_count: payment_count.clone(), logger: cfgs[i].logger,
+			currency_conversion: cfgs[i].currency_conversion,
 			blocks: Arc::clone(&cfgs[i].tx_broadcaster.blocks),
 			connect_style: Rc::clone(&connect_style),
 			override_init_features: Rc::clone(&cfgs[i].override_init_features),
@@ -0,0 +1,106 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of HTLC endorsement signalling and the resource bucketing we apply to unendorsed HTLCs
+//! we forward.
+
+use crate::events::{HTLCDestination, MessageSendEventsProvider};
+use crate::ln::channelmanager::{PaymentId, RecipientOnionFields};
+use crate::ln::msgs::ChannelMessageHandler;
+use crate::prelude::*;
+
+use crate::ln::functional_test_utils::*;
+
+#[test]
+fn test_htlc_endorsement_signalling() {
+	// HTLCs we originate are always endorsed, while HTLCs we forward are only endorsed if the
+	// incoming channel has built up reputation with us.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+	create_announced_chan_between_nodes(&nodes, 1, 2);
+
+	let (route, payment_hash, payment_preimage, payment_secret) =
+		get_route_and_payment_hash!(nodes[0], nodes[2], 100_000);
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors!(nodes[0], 1);
+	let updates = get_htlc_update_msgs!(nodes[0], nodes[1].node.get_our_node_id());
+	assert_eq!(updates.update_add_htlcs[0].endorsed, Some(true));
+
+	nodes[1].node.handle_update_add_htlc(&nodes[0].node.get_our_node_id(), &updates.update_add_htlcs[0]);
+	commitment_signed_dance!(nodes[1], nodes[0], &updates.commitment_signed, false);
+	expect_pending_htlcs_forwardable!(nodes[1]);
+	check_added_monitors!(nodes[1], 1);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[2].node.get_our_node_id());
+	assert_eq!(updates.update_add_htlcs[0].endorsed, Some(false));
+
+	nodes[2].node.handle_update_add_htlc(&nodes[1].node.get_our_node_id(), &updates.update_add_htlcs[0]);
+	commitment_signed_dance!(nodes[2], nodes[1], &updates.commitment_signed, false);
+	expect_pending_htlcs_forwardable!(nodes[2]);
+	expect_payment_claimable!(nodes[2], payment_hash, payment_secret, 100_000);
+	claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], payment_preimage);
+}
+
+fn do_test_unendorsed_htlc_bucketing(limit_slots: bool) {
+	// Unendorsed HTLCs may only use a share of the outgoing channel's HTLC slots and liquidity, any
+	// forwards beyond that are failed back with a `temporary_channel_failure`.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let mut config = test_default_channel_config();
+	if limit_slots {
+		// nodes[2] accepts up to 50 HTLCs, of which unendorsed HTLCs may only use one.
+		config.channel_config.unendorsed_htlc_slot_share_percentage = 2;
+	} else {
+		config.channel_config.unendorsed_htlc_liquidity_share_percentage = 50;
+	}
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, Some(config), None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+	let chan_1_2 = create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 100_000, 0);
+
+	// nodes[2] allows up to 10% of the channel value in flight, of which unendorsed HTLCs may use
+	// half.
+	let amount_msat = if limit_slots { 100_000 } else { 100_000 * 1000 / 10 * 3 / 10 };
+	let (first_payment_preimage, ..) = route_payment(&nodes[0], &[&nodes[1], &nodes[2]], amount_msat);
+
+	let (route, payment_hash, _, payment_secret) =
+		get_route_and_payment_hash!(nodes[0], nodes[2], amount_msat);
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors!(nodes[0], 1);
+	let updates = get_htlc_update_msgs!(nodes[0], nodes[1].node.get_our_node_id());
+	nodes[1].node.handle_update_add_htlc(&nodes[0].node.get_our_node_id(), &updates.update_add_htlcs[0]);
+	commitment_signed_dance!(nodes[1], nodes[0], &updates.commitment_signed, false);
+	expect_pending_htlcs_forwardable!(nodes[1]);
+	expect_pending_htlcs_forwardable_and_htlc_handling_failed!(nodes[1],
+		vec![HTLCDestination::NextHopChannel { node_id: Some(nodes[2].node.get_our_node_id()), channel_id: chan_1_2.2 }]);
+	check_added_monitors!(nodes[1], 1);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[0].node.get_our_node_id());
+	assert!(nodes[2].node.get_and_clear_pending_msg_events().is_empty());
+	nodes[0].node.handle_update_fail_htlc(&nodes[1].node.get_our_node_id(), &updates.update_fail_htlcs[0]);
+	commitment_signed_dance!(nodes[0], nodes[1], &updates.commitment_signed, false);
+	expect_payment_failed_conditions(&nodes[0], payment_hash, false,
+		PaymentFailedConditions::new().blamed_scid(chan_1_2.0.contents.short_channel_id));
+	nodes[1].logger.assert_log_contains("lightning::ln::channelmanager",
+		"Unendorsed HTLC would exceed the resources reserved for unendorsed HTLCs", 1);
+
+	// Once the first HTLC resolves, the resources it used are available to new HTLCs again.
+	claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], first_payment_preimage);
+	let (second_payment_preimage, ..) = route_payment(&nodes[0], &[&nodes[1], &nodes[2]], amount_msat);
+	claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], second_payment_preimage);
+}
+
+#[test]
+fn test_unendorsed_htlc_bucketing() {
+	do_test_unendorsed_htlc_bucketing(true);
+	do_test_unendorsed_htlc_bucketing(false);
+}
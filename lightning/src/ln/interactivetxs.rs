@@ -0,0 +1,946 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Interactive transaction construction, the protocol used by two peers to collaboratively build a
+//! transaction spending to (and possibly from) a shared funding output, as used by splicing and
+//! dual-funded channel establishment.
+//!
+//! See the [interactive-tx] section of BOLT 2 for the details of the protocol.
+//!
+//! [interactive-tx]: https://github.com/lightning/bolts/blob/master/02-peer-protocol.md#interactive-transaction-construction
+
+use bitcoin::blockdata::constants::WITNESS_SCALE_FACTOR;
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{OutPoint, Sequence, Transaction, TxIn, TxOut};
+use bitcoin::consensus::encode::VarInt;
+use bitcoin::secp256k1::PublicKey;
+
+use crate::chain::chaininterface::fee_for_weight;
+use crate::events::MessageSendEvent;
+use crate::ln::ChannelId;
+use crate::ln::channel::TOTAL_BITCOIN_SUPPLY_SATOSHIS;
+use crate::ln::msgs;
+use crate::sign::EntropySource;
+use crate::util::ser::TransactionU16LenLimited;
+
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::ops::Deref;
+
+/// The number of received `tx_add_input` messages during a negotiation at which point the
+/// negotiation MUST be failed.
+const MAX_RECEIVED_TX_ADD_INPUT_COUNT: u16 = 4096;
+
+/// The number of received `tx_add_output` messages during a negotiation at which point the
+/// negotiation MUST be failed.
+const MAX_RECEIVED_TX_ADD_OUTPUT_COUNT: u16 = 4096;
+
+/// The number of inputs or outputs that the constructed transaction may have before the
+/// negotiation MUST be failed.
+const MAX_INPUTS_OUTPUTS_COUNT: usize = 252;
+
+/// The maximum weight of a transaction which will be relayed by default by Bitcoin Core.
+pub(crate) const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// The weight of the transaction fields which are paid for by the negotiation initiator: the
+/// version, locktime, input and output counts as well as the segwit marker and flag.
+pub(crate) const TX_COMMON_FIELDS_WEIGHT: u64 =
+	(4 /* version */ + 4 /* locktime */ + 1 /* input count */ + 1 /* output count */) *
+		WITNESS_SCALE_FACTOR as u64 + 2 /* segwit marker + flag */;
+
+/// The non-witness weight of a transaction input with an empty `script_sig`.
+const BASE_INPUT_WEIGHT: u64 =
+	(32 /* txid */ + 4 /* vout */ + 4 /* sequence */ + 1 /* script_sig length */) * WITNESS_SCALE_FACTOR as u64;
+
+/// The witness weight of a P2WPKH input, assuming a maximally-sized DER signature.
+const P2WPKH_INPUT_WITNESS_WEIGHT: u64 = 1 /* num stack items */ + 1 /* sig length */ +
+	73 /* sig */ + 1 /* pubkey length */ + 33 /* pubkey */;
+
+/// The witness weight of a P2TR key-path input using the default sighash type.
+const P2TR_KEY_PATH_INPUT_WITNESS_WEIGHT: u64 = 1 /* num stack items */ + 1 /* sig length */ +
+	64 /* sig */;
+
+/// The witness weight of an input spending a channel's 2-of-2 multisig funding output.
+pub(crate) const FUNDING_INPUT_WITNESS_WEIGHT: u64 = 1 /* num stack items */ +
+	1 /* empty vector required by OP_CHECKMULTISIG */ + 1 + 73 /* sig 1 */ + 1 + 73 /* sig 2 */ +
+	1 + 71 /* redeemscript */;
+
+/// A serial ID identifying an input or output during a negotiation. IDs contributed by the
+/// negotiation initiator are even, those contributed by the non-initiator are odd.
+pub(crate) type SerialId = u64;
+
+fn serial_id_is_for_initiator(serial_id: SerialId) -> bool {
+	serial_id % 2 == 0
+}
+
+/// Estimates the weight of an input spending `prev_output`, including its witness.
+///
+/// Only P2WPKH and P2TR key-path spends can be estimated precisely. For any other witness program
+/// the witness size is unknown, so only the non-witness weight is counted.
+pub(crate) fn estimate_input_weight(prev_output: &TxOut) -> u64 {
+	BASE_INPUT_WEIGHT + 1 /* witness stack item count if the witness is otherwise unknown */ +
+		if prev_output.script_pubkey.is_v0_p2wpkh() {
+			P2WPKH_INPUT_WITNESS_WEIGHT - 1
+		} else if prev_output.script_pubkey.is_v1_p2tr() {
+			P2TR_KEY_PATH_INPUT_WITNESS_WEIGHT - 1
+		} else {
+			0
+		}
+}
+
+/// The weight of an output paying to `script_pubkey`.
+pub(crate) fn get_output_weight(script_pubkey: &Script) -> u64 {
+	(8 /* value */ + VarInt(script_pubkey.len() as u64).len() as u64 + script_pubkey.len() as u64) *
+		WITNESS_SCALE_FACTOR as u64
+}
+
+/// Estimates the weight of the contributions a party pays fees for: the inputs spending
+/// `input_prev_outputs` and the given `outputs`, plus the common transaction fields and the shared
+/// input and output if they are the negotiation initiator.
+pub(crate) fn estimate_contribution_weight<'a, I: Iterator<Item = &'a TxOut>>(
+	is_initiator: bool, has_shared_input: bool, shared_output_script: &Script, input_prev_outputs: I,
+	outputs: &[TxOut],
+) -> u64 {
+	let mut weight = 0;
+	if is_initiator {
+		weight += TX_COMMON_FIELDS_WEIGHT + get_output_weight(shared_output_script);
+		if has_shared_input {
+			weight += BASE_INPUT_WEIGHT + FUNDING_INPUT_WITNESS_WEIGHT;
+		}
+	}
+	weight += input_prev_outputs.map(estimate_input_weight).sum::<u64>();
+	weight + outputs.iter().map(|output| get_output_weight(&output.script_pubkey)).sum::<u64>()
+}
+
+/// The reason an interactive transaction negotiation was aborted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AbortReason {
+	/// The counterparty sent a message when it was not their turn to do so.
+	UnexpectedCounterpartyMessage,
+	/// The counterparty sent more `tx_add_input` messages than allowed.
+	ReceivedTooManyTxAddInputs,
+	/// The counterparty sent more `tx_add_output` messages than allowed.
+	ReceivedTooManyTxAddOutputs,
+	/// An input was added with a sequence number which does not signal RBF.
+	IncorrectInputSequenceValue,
+	/// An input or output was added with a serial ID of the wrong parity.
+	IncorrectSerialIdParity,
+	/// An input or output removal referenced a serial ID which was never added.
+	SerialIdUnknown,
+	/// An input or output was added with a serial ID which is already in use.
+	DuplicateSerialId,
+	/// An input referenced a non-existent or non-segwit output, or an output already being spent.
+	PrevTxOutInvalid,
+	/// An output was added with a value larger than the total bitcoin supply.
+	ExceededMaximumSatsAllowed,
+	/// The negotiated transaction has too many inputs or outputs.
+	ExceededNumberOfInputsOrOutputs,
+	/// The negotiated transaction exceeds the standard transaction weight.
+	TransactionTooLarge,
+	/// An output was added with a value below the dust limit for its script.
+	BelowDustLimit,
+	/// An output was added with a non-standard script.
+	InvalidOutputScript,
+	/// The counterparty did not contribute enough to pay for the inputs and outputs they added.
+	InsufficientFees,
+	/// The negotiated transaction does not contain the shared funding output, or it has an
+	/// unexpected value.
+	MissingFundingOutput,
+	/// The shared funding output was added more than once.
+	DuplicateFundingOutput,
+	/// The negotiated transaction does not spend the channel's current funding output.
+	MissingSharedInput,
+	/// The shared input was added by the wrong party, more than once or does not match the
+	/// channel's current funding output.
+	InvalidSharedInput,
+}
+
+impl AbortReason {
+	/// Builds the `tx_abort` message to send to our counterparty for this reason.
+	pub(crate) fn into_tx_abort_msg(self, channel_id: ChannelId) -> msgs::TxAbort {
+		msgs::TxAbort { channel_id, data: self.to_string().into_bytes() }
+	}
+}
+
+impl fmt::Display for AbortReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			AbortReason::UnexpectedCounterpartyMessage => "Unexpected message",
+			AbortReason::ReceivedTooManyTxAddInputs => "Too many `tx_add_input`s received",
+			AbortReason::ReceivedTooManyTxAddOutputs => "Too many `tx_add_output`s received",
+			AbortReason::IncorrectInputSequenceValue => "Input has a sequence value greater than 0xFFFFFFFD",
+			AbortReason::IncorrectSerialIdParity => "Parity for `serial_id` was incorrect",
+			AbortReason::SerialIdUnknown => "The `serial_id` is unknown",
+			AbortReason::DuplicateSerialId => "The `serial_id` already exists",
+			AbortReason::PrevTxOutInvalid => "Invalid previous transaction output",
+			AbortReason::ExceededMaximumSatsAllowed => "Output amount exceeded total bitcoin supply",
+			AbortReason::ExceededNumberOfInputsOrOutputs => "Too many inputs or outputs",
+			AbortReason::TransactionTooLarge => "Transaction weight is too large",
+			AbortReason::BelowDustLimit => "Output amount is below the dust limit",
+			AbortReason::InvalidOutputScript => "The output script is non-standard",
+			AbortReason::InsufficientFees => "Insufficient fees paid",
+			AbortReason::MissingFundingOutput => "No shared funding output found",
+			AbortReason::DuplicateFundingOutput => "More than one shared funding output found",
+			AbortReason::MissingSharedInput => "The shared input was not added",
+			AbortReason::InvalidSharedInput => "The shared input is invalid",
+		})
+	}
+}
+
+/// The channel's current funding output, spent by the new funding transaction of a splice.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SharedFundingInput {
+	/// The outpoint of the current funding output.
+	pub outpoint: OutPoint,
+	/// The current funding output itself.
+	pub prev_output: TxOut,
+}
+
+/// A message to send to our counterparty as the next step of a negotiation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum InteractiveTxMessageSend {
+	TxAddInput(msgs::TxAddInput),
+	TxAddOutput(msgs::TxAddOutput),
+	TxComplete(msgs::TxComplete),
+}
+
+impl InteractiveTxMessageSend {
+	pub(crate) fn into_msg_send_event(self, counterparty_node_id: PublicKey) -> MessageSendEvent {
+		match self {
+			InteractiveTxMessageSend::TxAddInput(msg) =>
+				MessageSendEvent::SendTxAddInput { node_id: counterparty_node_id, msg },
+			InteractiveTxMessageSend::TxAddOutput(msg) =>
+				MessageSendEvent::SendTxAddOutput { node_id: counterparty_node_id, msg },
+			InteractiveTxMessageSend::TxComplete(msg) =>
+				MessageSendEvent::SendTxComplete { node_id: counterparty_node_id, msg },
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NegotiatedTxInput {
+	serial_id: SerialId,
+	txin: TxIn,
+	prev_output: TxOut,
+	added_by_holder: bool,
+	is_shared: bool,
+}
+
+impl NegotiatedTxInput {
+	fn weight(&self) -> u64 {
+		if self.is_shared {
+			BASE_INPUT_WEIGHT + FUNDING_INPUT_WITNESS_WEIGHT
+		} else {
+			estimate_input_weight(&self.prev_output)
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NegotiatedTxOutput {
+	serial_id: SerialId,
+	txout: TxOut,
+	added_by_holder: bool,
+	is_shared: bool,
+}
+
+enum InputToContribute {
+	Shared(SharedFundingInput),
+	Owned { txin: TxIn, prevtx: TransactionU16LenLimited },
+}
+
+struct OutputToContribute {
+	txout: TxOut,
+	is_shared: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NegotiationState {
+	/// We are waiting on the counterparty's next message.
+	AwaitingCounterparty { holder_sent_tx_complete: bool },
+	/// Both parties have sent `tx_complete` consecutively.
+	Complete,
+}
+
+/// The parameters of a negotiation, fixed by the messages which initiated it (e.g. `splice` and
+/// `splice_ack`).
+pub(crate) struct InteractiveTxParameters {
+	pub channel_id: ChannelId,
+	pub holder_is_initiator: bool,
+	pub holder_node_id: PublicKey,
+	pub counterparty_node_id: PublicKey,
+	pub feerate_sat_per_kw: u32,
+	pub tx_locktime: LockTime,
+	/// The channel's current funding output, which the initiator must add, if any.
+	pub shared_input: Option<SharedFundingInput>,
+	/// The new funding output, which the initiator must add.
+	pub shared_output: TxOut,
+	/// The change in channel value contributed by the counterparty, which their inputs and
+	/// outputs (plus fees) must account for.
+	pub counterparty_funding_contribution_satoshis: i64,
+}
+
+/// Drives one side of an interactive transaction negotiation.
+///
+/// The initiator's first message is returned on construction. Afterwards, every message received
+/// from the counterparty is answered with either one of our own contributions or a `tx_complete`,
+/// until both sides have sent `tx_complete` consecutively and a [`ConstructedTransaction`] is
+/// returned.
+pub(crate) struct InteractiveTxConstructor {
+	params: InteractiveTxParameters,
+	state: NegotiationState,
+	inputs_to_contribute: Vec<(SerialId, InputToContribute)>,
+	outputs_to_contribute: Vec<(SerialId, OutputToContribute)>,
+	inputs: BTreeMap<SerialId, NegotiatedTxInput>,
+	outputs: BTreeMap<SerialId, NegotiatedTxOutput>,
+	received_tx_add_input_count: u16,
+	received_tx_add_output_count: u16,
+}
+
+impl InteractiveTxConstructor {
+	/// Starts a new negotiation, contributing the given inputs and outputs in addition to the shared
+	/// input and output if we are the initiator.
+	///
+	/// Returns the first message to send if we are the initiator.
+	pub(crate) fn new<ES: Deref>(
+		entropy_source: &ES, params: InteractiveTxParameters,
+		inputs_to_contribute: Vec<(TxIn, TransactionU16LenLimited)>, outputs_to_contribute: Vec<TxOut>,
+	) -> (Self, Option<InteractiveTxMessageSend>)
+	where ES::Target: EntropySource,
+	{
+		let holder_is_initiator = params.holder_is_initiator;
+		let generate_serial_id = || {
+			let mut bytes = [0u8; 8];
+			bytes.copy_from_slice(&entropy_source.get_secure_random_bytes()[..8]);
+			let serial_id = u64::from_be_bytes(bytes);
+			if holder_is_initiator { serial_id & !1 } else { serial_id | 1 }
+		};
+
+		let mut inputs = Vec::with_capacity(inputs_to_contribute.len() + 1);
+		let mut outputs = Vec::with_capacity(outputs_to_contribute.len() + 1);
+		if holder_is_initiator {
+			if let Some(shared_input) = &params.shared_input {
+				inputs.push((generate_serial_id(), InputToContribute::Shared(shared_input.clone())));
+			}
+			outputs.push((generate_serial_id(), OutputToContribute {
+				txout: params.shared_output.clone(), is_shared: true,
+			}));
+		}
+		for (txin, prevtx) in inputs_to_contribute {
+			inputs.push((generate_serial_id(), InputToContribute::Owned { txin, prevtx }));
+		}
+		for txout in outputs_to_contribute {
+			outputs.push((generate_serial_id(), OutputToContribute { txout, is_shared: false }));
+		}
+		// Contributions are sent in order by popping from the back.
+		inputs.reverse();
+		outputs.reverse();
+
+		let mut constructor = Self {
+			params,
+			state: NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: false },
+			inputs_to_contribute: inputs,
+			outputs_to_contribute: outputs,
+			inputs: BTreeMap::new(),
+			outputs: BTreeMap::new(),
+			received_tx_add_input_count: 0,
+			received_tx_add_output_count: 0,
+		};
+		let first_msg = if holder_is_initiator {
+			// We can't have completed the negotiation yet as the counterparty hasn't sent anything.
+			Some(constructor.next_message(false).expect("first message cannot complete negotiation").0)
+		} else {
+			None
+		};
+		(constructor, first_msg)
+	}
+
+	fn check_awaiting_counterparty(&self) -> Result<(), AbortReason> {
+		match self.state {
+			NegotiationState::AwaitingCounterparty { .. } => Ok(()),
+			NegotiationState::Complete => Err(AbortReason::UnexpectedCounterpartyMessage),
+		}
+	}
+
+	fn check_counterparty_serial_id(&self, serial_id: SerialId) -> Result<(), AbortReason> {
+		// The counterparty's serial IDs have the opposite parity of ours.
+		if serial_id_is_for_initiator(serial_id) == self.params.holder_is_initiator {
+			return Err(AbortReason::IncorrectSerialIdParity);
+		}
+		Ok(())
+	}
+
+	/// Produces our next message, returning the constructed transaction alongside a `tx_complete`
+	/// if it completes the negotiation.
+	fn next_message(&mut self, counterparty_sent_tx_complete: bool)
+	-> Result<(InteractiveTxMessageSend, Option<ConstructedTransaction>), AbortReason> {
+		let channel_id = self.params.channel_id;
+		if let Some((serial_id, input)) = self.inputs_to_contribute.pop() {
+			let msg = match input {
+				InputToContribute::Shared(shared_input) => {
+					self.inputs.insert(serial_id, NegotiatedTxInput {
+						serial_id,
+						txin: TxIn {
+							previous_output: shared_input.outpoint,
+							sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+							..Default::default()
+						},
+						prev_output: shared_input.prev_output,
+						added_by_holder: true,
+						is_shared: true,
+					});
+					msgs::TxAddInput {
+						channel_id, serial_id, prevtx: None, prevtx_out: shared_input.outpoint.vout,
+						sequence: Sequence::ENABLE_RBF_NO_LOCKTIME.0,
+						shared_input_txid: Some(shared_input.outpoint.txid),
+					}
+				},
+				InputToContribute::Owned { txin, prevtx } => {
+					let prevtx_out = txin.previous_output.vout;
+					let prev_output = prevtx.as_transaction().output[prevtx_out as usize].clone();
+					let sequence = txin.sequence.0;
+					self.inputs.insert(serial_id, NegotiatedTxInput {
+						serial_id, txin, prev_output, added_by_holder: true, is_shared: false,
+					});
+					msgs::TxAddInput {
+						channel_id, serial_id, prevtx: Some(prevtx), prevtx_out, sequence,
+						shared_input_txid: None,
+					}
+				},
+			};
+			self.state = NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: false };
+			Ok((InteractiveTxMessageSend::TxAddInput(msg), None))
+		} else if let Some((serial_id, output)) = self.outputs_to_contribute.pop() {
+			let msg = msgs::TxAddOutput {
+				channel_id, serial_id, sats: output.txout.value, script: output.txout.script_pubkey.clone(),
+			};
+			self.outputs.insert(serial_id, NegotiatedTxOutput {
+				serial_id, txout: output.txout, added_by_holder: true, is_shared: output.is_shared,
+			});
+			self.state = NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: false };
+			Ok((InteractiveTxMessageSend::TxAddOutput(msg), None))
+		} else {
+			let msg = InteractiveTxMessageSend::TxComplete(msgs::TxComplete { channel_id });
+			if counterparty_sent_tx_complete {
+				let tx = self.complete()?;
+				Ok((msg, Some(tx)))
+			} else {
+				self.state = NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: true };
+				Ok((msg, None))
+			}
+		}
+	}
+
+	/// Handles a `tx_add_input` from the counterparty, returning our response.
+	pub(crate) fn handle_tx_add_input(&mut self, msg: &msgs::TxAddInput)
+	-> Result<InteractiveTxMessageSend, AbortReason> {
+		self.check_awaiting_counterparty()?;
+		self.check_counterparty_serial_id(msg.serial_id)?;
+		self.received_tx_add_input_count += 1;
+		if self.received_tx_add_input_count > MAX_RECEIVED_TX_ADD_INPUT_COUNT {
+			return Err(AbortReason::ReceivedTooManyTxAddInputs);
+		}
+		if msg.sequence >= Sequence::ENABLE_LOCKTIME_NO_RBF.0 {
+			return Err(AbortReason::IncorrectInputSequenceValue);
+		}
+		if self.inputs.contains_key(&msg.serial_id) {
+			return Err(AbortReason::DuplicateSerialId);
+		}
+
+		let (outpoint, prev_output, is_shared) = match (&msg.prevtx, msg.shared_input_txid) {
+			(None, Some(shared_input_txid)) => {
+				let shared_input = match &self.params.shared_input {
+					// Only the initiator may add the shared input.
+					Some(shared_input) if !self.params.holder_is_initiator => shared_input,
+					_ => return Err(AbortReason::InvalidSharedInput),
+				};
+				let outpoint = OutPoint { txid: shared_input_txid, vout: msg.prevtx_out };
+				if outpoint != shared_input.outpoint || self.inputs.values().any(|input| input.is_shared) {
+					return Err(AbortReason::InvalidSharedInput);
+				}
+				(outpoint, shared_input.prev_output.clone(), true)
+			},
+			(Some(prevtx), None) => {
+				let prevtx = prevtx.as_transaction();
+				let prev_output = match prevtx.output.get(msg.prevtx_out as usize) {
+					Some(prev_output) if prev_output.script_pubkey.is_witness_program() => prev_output,
+					_ => return Err(AbortReason::PrevTxOutInvalid),
+				};
+				(OutPoint { txid: prevtx.txid(), vout: msg.prevtx_out }, prev_output.clone(), false)
+			},
+			_ => return Err(AbortReason::PrevTxOutInvalid),
+		};
+		if self.inputs.values().any(|input| input.txin.previous_output == outpoint) {
+			return Err(AbortReason::PrevTxOutInvalid);
+		}
+
+		self.inputs.insert(msg.serial_id, NegotiatedTxInput {
+			serial_id: msg.serial_id,
+			txin: TxIn {
+				previous_output: outpoint,
+				sequence: Sequence(msg.sequence),
+				..Default::default()
+			},
+			prev_output,
+			added_by_holder: false,
+			is_shared,
+		});
+		self.next_message(false).map(|(msg, _)| msg)
+	}
+
+	/// Handles a `tx_add_output` from the counterparty, returning our response.
+	pub(crate) fn handle_tx_add_output(&mut self, msg: &msgs::TxAddOutput)
+	-> Result<InteractiveTxMessageSend, AbortReason> {
+		self.check_awaiting_counterparty()?;
+		self.check_counterparty_serial_id(msg.serial_id)?;
+		self.received_tx_add_output_count += 1;
+		if self.received_tx_add_output_count > MAX_RECEIVED_TX_ADD_OUTPUT_COUNT {
+			return Err(AbortReason::ReceivedTooManyTxAddOutputs);
+		}
+		if msg.sats > TOTAL_BITCOIN_SUPPLY_SATOSHIS {
+			return Err(AbortReason::ExceededMaximumSatsAllowed);
+		}
+		if msg.sats < msg.script.dust_value().to_sat() {
+			return Err(AbortReason::BelowDustLimit);
+		}
+		if !msg.script.is_witness_program() && !msg.script.is_p2pkh() && !msg.script.is_p2sh() {
+			return Err(AbortReason::InvalidOutputScript);
+		}
+		if self.outputs.contains_key(&msg.serial_id) {
+			return Err(AbortReason::DuplicateSerialId);
+		}
+
+		let is_shared = msg.script == self.params.shared_output.script_pubkey;
+		if is_shared && (self.params.holder_is_initiator || self.outputs.values().any(|output| output.is_shared)) {
+			return Err(AbortReason::DuplicateFundingOutput);
+		}
+		self.outputs.insert(msg.serial_id, NegotiatedTxOutput {
+			serial_id: msg.serial_id,
+			txout: TxOut { value: msg.sats, script_pubkey: msg.script.clone() },
+			added_by_holder: false,
+			is_shared,
+		});
+		self.next_message(false).map(|(msg, _)| msg)
+	}
+
+	/// Handles a `tx_remove_input` from the counterparty, returning our response.
+	pub(crate) fn handle_tx_remove_input(&mut self, msg: &msgs::TxRemoveInput)
+	-> Result<InteractiveTxMessageSend, AbortReason> {
+		self.check_awaiting_counterparty()?;
+		self.check_counterparty_serial_id(msg.serial_id)?;
+		if self.inputs.remove(&msg.serial_id).is_none() {
+			return Err(AbortReason::SerialIdUnknown);
+		}
+		self.next_message(false).map(|(msg, _)| msg)
+	}
+
+	/// Handles a `tx_remove_output` from the counterparty, returning our response.
+	pub(crate) fn handle_tx_remove_output(&mut self, msg: &msgs::TxRemoveOutput)
+	-> Result<InteractiveTxMessageSend, AbortReason> {
+		self.check_awaiting_counterparty()?;
+		self.check_counterparty_serial_id(msg.serial_id)?;
+		if self.outputs.remove(&msg.serial_id).is_none() {
+			return Err(AbortReason::SerialIdUnknown);
+		}
+		self.next_message(false).map(|(msg, _)| msg)
+	}
+
+	/// Handles a `tx_complete` from the counterparty, returning our response, if any, and the
+	/// constructed transaction if the negotiation is now complete.
+	pub(crate) fn handle_tx_complete(&mut self, _msg: &msgs::TxComplete)
+	-> Result<(Option<InteractiveTxMessageSend>, Option<ConstructedTransaction>), AbortReason> {
+		match self.state {
+			NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: true } => {
+				Ok((None, Some(self.complete()?)))
+			},
+			NegotiationState::AwaitingCounterparty { holder_sent_tx_complete: false } => {
+				self.next_message(true).map(|(msg, tx)| (Some(msg), tx))
+			},
+			NegotiationState::Complete => Err(AbortReason::UnexpectedCounterpartyMessage),
+		}
+	}
+
+	/// Validates the negotiated transaction once both parties have sent `tx_complete`.
+	fn complete(&mut self) -> Result<ConstructedTransaction, AbortReason> {
+		self.state = NegotiationState::Complete;
+
+		if self.inputs.len() > MAX_INPUTS_OUTPUTS_COUNT || self.outputs.len() > MAX_INPUTS_OUTPUTS_COUNT {
+			return Err(AbortReason::ExceededNumberOfInputsOrOutputs);
+		}
+		let mut shared_outputs = self.outputs.values().filter(|output| output.is_shared);
+		match (shared_outputs.next(), shared_outputs.next()) {
+			(Some(output), None) if output.txout == self.params.shared_output => {},
+			(Some(_), Some(_)) => return Err(AbortReason::DuplicateFundingOutput),
+			_ => return Err(AbortReason::MissingFundingOutput),
+		}
+		if self.params.shared_input.is_some() && !self.inputs.values().any(|input| input.is_shared) {
+			return Err(AbortReason::MissingSharedInput);
+		}
+
+		let mut tx_weight = TX_COMMON_FIELDS_WEIGHT;
+		let mut counterparty_weight = 0;
+		let mut counterparty_inputs_value = 0;
+		let mut counterparty_outputs_value = 0;
+		let counterparty_is_initiator = !self.params.holder_is_initiator;
+		if counterparty_is_initiator {
+			counterparty_weight += TX_COMMON_FIELDS_WEIGHT;
+		}
+		for input in self.inputs.values() {
+			tx_weight += input.weight();
+			if input.is_shared {
+				if counterparty_is_initiator { counterparty_weight += input.weight(); }
+			} else if !input.added_by_holder {
+				counterparty_weight += input.weight();
+				counterparty_inputs_value += input.prev_output.value;
+			}
+		}
+		for output in self.outputs.values() {
+			let output_weight = get_output_weight(&output.txout.script_pubkey);
+			tx_weight += output_weight;
+			if output.is_shared {
+				if counterparty_is_initiator { counterparty_weight += output_weight; }
+			} else if !output.added_by_holder {
+				counterparty_weight += output_weight;
+				counterparty_outputs_value += output.txout.value;
+			}
+		}
+		if tx_weight > MAX_STANDARD_TX_WEIGHT {
+			return Err(AbortReason::TransactionTooLarge);
+		}
+
+		// The counterparty's inputs must cover their outputs, their contribution to the funding
+		// output and the fees for everything they are responsible for.
+		let counterparty_fees_paid = counterparty_inputs_value as i64 - counterparty_outputs_value as i64
+			- self.params.counterparty_funding_contribution_satoshis;
+		let required_fees = fee_for_weight(self.params.feerate_sat_per_kw, counterparty_weight);
+		if counterparty_fees_paid < required_fees as i64 {
+			return Err(AbortReason::InsufficientFees);
+		}
+
+		let holder_inputs_value = self.inputs.values()
+			.filter(|input| input.added_by_holder || (input.is_shared && self.params.holder_is_initiator))
+			.map(|input| input.prev_output.value)
+			.sum::<u64>();
+		let counterparty_inputs_value = self.inputs.values()
+			.filter(|input| !input.added_by_holder || (input.is_shared && counterparty_is_initiator))
+			.map(|input| input.prev_output.value)
+			.sum::<u64>();
+		// The party which contributed the lowest input value sends its `tx_signatures` first, with
+		// ties broken in favor of the lowest node id.
+		let holder_sends_tx_signatures_first = holder_inputs_value < counterparty_inputs_value ||
+			(holder_inputs_value == counterparty_inputs_value &&
+				self.params.holder_node_id.serialize() < self.params.counterparty_node_id.serialize());
+
+		Ok(ConstructedTransaction {
+			inputs: self.inputs.values().cloned().collect(),
+			outputs: self.outputs.values().cloned().collect(),
+			lock_time: self.params.tx_locktime,
+			holder_sends_tx_signatures_first,
+		})
+	}
+}
+
+/// A transaction which has been successfully negotiated, with inputs and outputs ordered by serial
+/// ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ConstructedTransaction {
+	inputs: Vec<NegotiatedTxInput>,
+	outputs: Vec<NegotiatedTxOutput>,
+	lock_time: LockTime,
+	holder_sends_tx_signatures_first: bool,
+}
+
+impl ConstructedTransaction {
+	/// Builds the negotiated transaction, without any witnesses.
+	pub(crate) fn build_unsigned_tx(&self) -> Transaction {
+		Transaction {
+			version: 2,
+			lock_time: self.lock_time,
+			input: self.inputs.iter().map(|input| input.txin.clone()).collect(),
+			output: self.outputs.iter().map(|output| output.txout.clone()).collect(),
+		}
+	}
+
+	/// The index of the output paying to the new funding script.
+	pub(crate) fn shared_output_index(&self) -> u32 {
+		self.outputs.iter().position(|output| output.is_shared)
+			.expect("Negotiation cannot complete without a shared output") as u32
+	}
+
+	/// The index of the input spending the channel's previous funding output, if any.
+	pub(crate) fn shared_input_index(&self) -> Option<usize> {
+		self.inputs.iter().position(|input| input.is_shared)
+	}
+
+	/// The number of inputs besides the shared input which were contributed by us and need our
+	/// signature.
+	pub(crate) fn holder_owned_input_count(&self) -> usize {
+		self.inputs.iter().filter(|input| input.added_by_holder && !input.is_shared).count()
+	}
+
+	/// Whether we must send our `tx_signatures` before receiving the counterparty's.
+	pub(crate) fn holder_sends_tx_signatures_first(&self) -> bool {
+		self.holder_sends_tx_signatures_first
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ln::chan_utils::make_funding_redeemscript;
+	use crate::util::test_utils::TestKeysInterface;
+
+	use bitcoin::blockdata::opcodes;
+	use bitcoin::blockdata::script::{Builder, ScriptBuf};
+	use bitcoin::hash_types::Txid;
+	use bitcoin::hashes::Hash;
+	use bitcoin::network::constants::Network;
+	use bitcoin::secp256k1::{Secp256k1, SecretKey};
+	use bitcoin::WPubkeyHash;
+
+	const FEERATE: u32 = 253;
+
+	fn pubkey(byte: u8) -> PublicKey {
+		PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[byte; 32]).unwrap())
+	}
+
+	fn p2wpkh_script(byte: u8) -> ScriptBuf {
+		ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([byte; 20]))
+	}
+
+	fn funding_script() -> ScriptBuf {
+		make_funding_redeemscript(&pubkey(1), &pubkey(2)).to_v0_p2wsh()
+	}
+
+	fn wallet_input(value: u64, byte: u8) -> (TxIn, TransactionU16LenLimited) {
+		let prevtx = Transaction {
+			version: 2,
+			lock_time: LockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: OutPoint { txid: Txid::from_byte_array([byte; 32]), vout: 0 },
+				..Default::default()
+			}],
+			output: vec![TxOut { value, script_pubkey: p2wpkh_script(byte) }],
+		};
+		let txin = TxIn {
+			previous_output: OutPoint { txid: prevtx.txid(), vout: 0 },
+			sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+			..Default::default()
+		};
+		(txin, TransactionU16LenLimited::new(prevtx).unwrap())
+	}
+
+	fn shared_input(value: u64) -> SharedFundingInput {
+		SharedFundingInput {
+			outpoint: OutPoint { txid: Txid::from_byte_array([42; 32]), vout: 1 },
+			prev_output: TxOut { value, script_pubkey: funding_script() },
+		}
+	}
+
+	fn params(
+		holder_is_initiator: bool, shared_input: Option<SharedFundingInput>, shared_output_value: u64,
+		counterparty_funding_contribution_satoshis: i64,
+	) -> InteractiveTxParameters {
+		let (holder_node_id, counterparty_node_id) =
+			if holder_is_initiator { (pubkey(3), pubkey(4)) } else { (pubkey(4), pubkey(3)) };
+		InteractiveTxParameters {
+			channel_id: ChannelId::from_bytes([7; 32]),
+			holder_is_initiator,
+			holder_node_id,
+			counterparty_node_id,
+			feerate_sat_per_kw: FEERATE,
+			tx_locktime: LockTime::ZERO,
+			shared_input,
+			shared_output: TxOut { value: shared_output_value, script_pubkey: funding_script() },
+			counterparty_funding_contribution_satoshis,
+		}
+	}
+
+	fn handle(constructor: &mut InteractiveTxConstructor, msg: InteractiveTxMessageSend)
+	-> Result<(Option<InteractiveTxMessageSend>, Option<ConstructedTransaction>), AbortReason> {
+		match msg {
+			InteractiveTxMessageSend::TxAddInput(msg) =>
+				constructor.handle_tx_add_input(&msg).map(|msg| (Some(msg), None)),
+			InteractiveTxMessageSend::TxAddOutput(msg) =>
+				constructor.handle_tx_add_output(&msg).map(|msg| (Some(msg), None)),
+			InteractiveTxMessageSend::TxComplete(msg) => constructor.handle_tx_complete(&msg),
+		}
+	}
+
+	/// Runs a negotiation to completion, returning the initiator's and non-initiator's view of the
+	/// constructed transaction.
+	fn do_negotiation(
+		mut initiator: InteractiveTxConstructor, first_msg: InteractiveTxMessageSend,
+		mut acceptor: InteractiveTxConstructor,
+	) -> Result<(ConstructedTransaction, ConstructedTransaction), AbortReason> {
+		let mut msg = first_msg;
+		let mut initiator_tx = None;
+		let mut acceptor_tx = None;
+		let mut acceptors_turn = true;
+		loop {
+			let (next_msg, tx) = if acceptors_turn {
+				handle(&mut acceptor, msg)?
+			} else {
+				handle(&mut initiator, msg)?
+			};
+			if let Some(tx) = tx {
+				if acceptors_turn { acceptor_tx = Some(tx); } else { initiator_tx = Some(tx); }
+			}
+			match next_msg {
+				Some(next_msg) => msg = next_msg,
+				None => break,
+			}
+			acceptors_turn = !acceptors_turn;
+		}
+		Ok((initiator_tx.unwrap(), acceptor_tx.unwrap()))
+	}
+
+	#[test]
+	fn test_splice_in_negotiation() {
+		let keys = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		let (wallet_txin, wallet_prevtx) = wallet_input(200_000, 5);
+		let change = TxOut { value: 49_000, script_pubkey: p2wpkh_script(6) };
+		let (initiator, first_msg) = InteractiveTxConstructor::new(&&keys,
+			params(true, Some(shared_input(1_000_000)), 1_150_000, 0),
+			vec![(wallet_txin.clone(), wallet_prevtx)], vec![change.clone()]);
+		let (acceptor, no_msg) = InteractiveTxConstructor::new(&&keys,
+			params(false, Some(shared_input(1_000_000)), 1_150_000, 150_000), Vec::new(), Vec::new());
+		assert!(no_msg.is_none());
+
+		let (initiator_tx, acceptor_tx) = do_negotiation(initiator, first_msg.unwrap(), acceptor).unwrap();
+		let tx = initiator_tx.build_unsigned_tx();
+		assert_eq!(tx, acceptor_tx.build_unsigned_tx());
+		assert_eq!(tx.input.len(), 2);
+		assert_eq!(tx.output.len(), 2);
+		assert!(tx.input.iter().any(|input| input.previous_output == wallet_txin.previous_output));
+		assert_eq!(tx.input[initiator_tx.shared_input_index().unwrap()].previous_output, shared_input(0).outpoint);
+		assert_eq!(tx.output[initiator_tx.shared_output_index() as usize].value, 1_150_000);
+		assert_eq!(initiator_tx.holder_owned_input_count(), 1);
+		assert_eq!(acceptor_tx.holder_owned_input_count(), 0);
+		// The acceptor didn't contribute any inputs, so it goes first.
+		assert!(acceptor_tx.holder_sends_tx_signatures_first());
+		assert!(!initiator_tx.holder_sends_tx_signatures_first());
+	}
+
+	#[test]
+	fn test_splice_out_negotiation() {
+		let keys = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		let splice_out = TxOut { value: 99_000, script_pubkey: p2wpkh_script(6) };
+		let (initiator, first_msg) = InteractiveTxConstructor::new(&&keys,
+			params(true, Some(shared_input(1_000_000)), 900_000, 0), Vec::new(), vec![splice_out]);
+		let (acceptor, _) = InteractiveTxConstructor::new(&&keys,
+			params(false, Some(shared_input(1_000_000)), 900_000, -100_000), Vec::new(), Vec::new());
+		let (initiator_tx, acceptor_tx) = do_negotiation(initiator, first_msg.unwrap(), acceptor).unwrap();
+		assert_eq!(initiator_tx, {
+			let mut tx = acceptor_tx.clone();
+			tx.holder_sends_tx_signatures_first = initiator_tx.holder_sends_tx_signatures_first;
+			tx.inputs.iter_mut().for_each(|input| input.added_by_holder = !input.added_by_holder);
+			tx.outputs.iter_mut().for_each(|output| output.added_by_holder = !output.added_by_holder);
+			tx
+		});
+		assert_eq!(initiator_tx.build_unsigned_tx().input.len(), 1);
+	}
+
+	#[test]
+	fn test_insufficient_fees() {
+		let keys = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		// The splice-out output leaves nothing to pay for the transaction's fees.
+		let splice_out = TxOut { value: 100_000, script_pubkey: p2wpkh_script(6) };
+		let (initiator, first_msg) = InteractiveTxConstructor::new(&&keys,
+			params(true, Some(shared_input(1_000_000)), 900_000, 0), Vec::new(), vec![splice_out]);
+		let (acceptor, _) = InteractiveTxConstructor::new(&&keys,
+			params(false, Some(shared_input(1_000_000)), 900_000, -100_000), Vec::new(), Vec::new());
+		assert_eq!(do_negotiation(initiator, first_msg.unwrap(), acceptor).unwrap_err(),
+			AbortReason::InsufficientFees);
+	}
+
+	#[test]
+	fn test_missing_and_unexpected_shared_contributions() {
+		let keys = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		// The initiator doesn't know about a shared input, but the acceptor expects one.
+		let (initiator, first_msg) = InteractiveTxConstructor::new(&&keys,
+			params(true, None, 1_000_000, 0), vec![wallet_input(1_100_000, 5)], Vec::new());
+		let (acceptor, _) = InteractiveTxConstructor::new(&&keys,
+			params(false, Some(shared_input(1_000_000)), 1_000_000, 0), Vec::new(), Vec::new());
+		assert_eq!(do_negotiation(initiator, first_msg.unwrap(), acceptor).unwrap_err(),
+			AbortReason::MissingSharedInput);
+
+		// The funding output's value doesn't match what the acceptor expects.
+		let (initiator, first_msg) = InteractiveTxConstructor::new(&&keys,
+			params(true, None, 1_000_000, 0), vec![wallet_input(1_100_000, 5)], Vec::new());
+		let (acceptor, _) = InteractiveTxConstructor::new(&&keys,
+			params(false, None, 999_999, 0), Vec::new(), Vec::new());
+		assert_eq!(do_negotiation(initiator, first_msg.unwrap(), acceptor).unwrap_err(),
+			AbortReason::MissingFundingOutput);
+
+		// Only the initiator may add the shared input.
+		let (mut acceptor, _) = InteractiveTxConstructor::new(&&keys,
+			params(true, Some(shared_input(1_000_000)), 1_000_000, 0), Vec::new(), Vec::new());
+		let shared = shared_input(1_000_000).outpoint;
+		assert_eq!(acceptor.handle_tx_add_input(&msgs::TxAddInput {
+			channel_id: ChannelId::from_bytes([7; 32]), serial_id: 1, prevtx: None,
+			prevtx_out: shared.vout, sequence: 0xfffffffd, shared_input_txid: Some(shared.txid),
+		}).unwrap_err(), AbortReason::InvalidSharedInput);
+	}
+
+	#[test]
+	fn test_invalid_counterparty_messages() {
+		let keys = TestKeysInterface::new(&[0; 32], Network::Testnet);
+		let channel_id = ChannelId::from_bytes([7; 32]);
+		let new_acceptor = || InteractiveTxConstructor::new(&&keys,
+			params(false, None, 1_000_000, 1_000_000), Vec::new(), Vec::new()).0;
+		let (txin, prevtx) = wallet_input(1_100_000, 5);
+		let add_input = msgs::TxAddInput {
+			channel_id, serial_id: 2, prevtx: Some(prevtx), prevtx_out: 0,
+			sequence: 0xfffffffd, shared_input_txid: None,
+		};
+
+		let mut acceptor = new_acceptor();
+		assert_eq!(acceptor.handle_tx_add_input(&msgs::TxAddInput { serial_id: 1, ..add_input.clone() }).unwrap_err(),
+			AbortReason::IncorrectSerialIdParity);
+		assert_eq!(acceptor.handle_tx_add_input(&msgs::TxAddInput { sequence: 0xfffffffe, ..add_input.clone() }).unwrap_err(),
+			AbortReason::IncorrectInputSequenceValue);
+		assert_eq!(acceptor.handle_tx_add_input(&msgs::TxAddInput { prevtx_out: 1, ..add_input.clone() }).unwrap_err(),
+			AbortReason::PrevTxOutInvalid);
+
+		let mut acceptor = new_acceptor();
+		assert!(matches!(acceptor.handle_tx_add_input(&add_input).unwrap(), InteractiveTxMessageSend::TxComplete(_)));
+		assert_eq!(acceptor.handle_tx_add_input(&add_input).unwrap_err(), AbortReason::DuplicateSerialId);
+		assert_eq!(acceptor.handle_tx_add_input(&msgs::TxAddInput { serial_id: 4, ..add_input.clone() }).unwrap_err(),
+			AbortReason::PrevTxOutInvalid);
+		assert_eq!(acceptor.handle_tx_remove_input(&msgs::TxRemoveInput { channel_id, serial_id: 6 }).unwrap_err(),
+			AbortReason::SerialIdUnknown);
+		assert!(acceptor.handle_tx_remove_input(&msgs::TxRemoveInput { channel_id, serial_id: 2 }).is_ok());
+		assert!(acceptor.inputs.values().all(|input| input.txin.previous_output != txin.previous_output));
+
+		let add_output = msgs::TxAddOutput { channel_id, serial_id: 8, sats: 1_000, script: p2wpkh_script(9) };
+		assert_eq!(acceptor.handle_tx_add_output(&msgs::TxAddOutput { sats: 100, ..add_output.clone() }).unwrap_err(),
+			AbortReason::BelowDustLimit);
+		assert_eq!(acceptor.handle_tx_add_output(&msgs::TxAddOutput { sats: TOTAL_BITCOIN_SUPPLY_SATOSHIS + 1, ..add_output.clone() }).unwrap_err(),
+			AbortReason::ExceededMaximumSatsAllowed);
+		let non_standard_script = Builder::new().push_opcode(opcodes::OP_TRUE).into_script();
+		assert_eq!(acceptor.handle_tx_add_output(&msgs::TxAddOutput { script: non_standard_script, ..add_output.clone() }).unwrap_err(),
+			AbortReason::InvalidOutputScript);
+		assert!(acceptor.handle_tx_add_output(&add_output).is_ok());
+		assert!(acceptor.handle_tx_remove_output(&msgs::TxRemoveOutput { channel_id, serial_id: 8 }).is_ok());
+
+		// Once the negotiation completes, no further messages are accepted.
+		let mut acceptor = new_acceptor();
+		acceptor.handle_tx_add_input(&add_input).unwrap();
+		acceptor.handle_tx_add_output(&msgs::TxAddOutput { serial_id: 10, sats: 1_000_000, script: funding_script(), ..add_output.clone() }).unwrap();
+		let (msg, tx) = acceptor.handle_tx_complete(&msgs::TxComplete { channel_id }).unwrap();
+		// Our last message was already a `tx_complete`, so there's nothing left to send.
+		assert!(msg.is_none());
+		assert!(tx.is_some());
+		assert_eq!(acceptor.handle_tx_complete(&msgs::TxComplete { channel_id }).unwrap_err(),
+			AbortReason::UnexpectedCounterpartyMessage);
+	}
+}
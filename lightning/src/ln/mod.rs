@@ -22,6 +22,7 @@ pub mod peer_handler;
 pub mod chan_utils;
 pub mod features;
 pub mod script;
+pub mod our_peer_storage;
 mod channel_id;
 
 #[cfg(fuzzing)]
@@ -37,8 +38,10 @@ pub(crate) mod channel;
 // Re-export ChannelId
 pub use channel_id::ChannelId;
 
+pub(crate) mod interactivetxs;
 pub(crate) mod onion_utils;
 mod outbound_payment;
+pub(crate) mod reputation;
 pub mod wire;
 
 pub use onion_utils::create_payment_onion;
@@ -79,6 +82,24 @@ mod shutdown_tests;
 #[cfg(all(test, async_signing))]
 #[allow(unused_mut)]
 mod async_signer_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod splicing_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod dual_funding_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod trampoline_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod quiescence_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod peer_storage_tests;
+#[cfg(test)]
+#[allow(unused_mut)]
+mod htlc_endorsement_tests;
 
 pub use self::peer_channel_encryptor::LN_MAX_MSG_LEN;
 
@@ -12,13 +12,14 @@
 use crate::sign::{ecdsa::EcdsaChannelSigner, SpendableOutputDescriptor};
 use crate::chain::channelmonitor::{ANTI_REORG_DELAY, LATENCY_GRACE_PERIOD_BLOCKS, Balance};
 use crate::chain::transaction::OutPoint;
-use crate::chain::chaininterface::{LowerBoundedFeeEstimator, compute_feerate_sat_per_1000_weight};
+use crate::chain::chaininterface::{LowerBoundedFeeEstimator, TRUC_CHILD_MAX_WEIGHT, compute_feerate_sat_per_1000_weight};
 use crate::events::bump_transaction::{BumpTransactionEvent, WalletSource};
 use crate::events::{Event, MessageSendEvent, MessageSendEventsProvider, ClosureReason, HTLCDestination};
-use crate::ln::channel;
+use crate::ln::{channel, chan_utils};
 use crate::ln::channelmanager::{BREAKDOWN_TIMEOUT, PaymentId, RecipientOnionFields};
-use crate::ln::msgs::ChannelMessageHandler;
+use crate::ln::msgs::{ChannelMessageHandler, ErrorAction};
 use crate::util::config::UserConfig;
+use crate::util::errors::APIError;
 use crate::crypto::utils::sign;
 use crate::util::ser::Writeable;
 use crate::util::scid_utils::block_from_scid;
@@ -2333,6 +2334,178 @@ fn test_yield_anchors_events() {
 	nodes[1].node.get_and_clear_pending_msg_events();
 }
 
+#[test]
+fn test_yield_anchor_zero_fee_commitments_events() {
+	// Tests that a channel using zero-fee commitments produces version 3 commitment and HTLC
+	// transactions with a single, keyless shared anchor output. Once the commitment transaction
+	// needs to be broadcast, the child anchor transaction must also be a version 3 transaction
+	// spending the shared anchor with an empty witness.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut zero_fee_config = UserConfig::default();
+	zero_fee_config.channel_handshake_config.announced_channel = true;
+	zero_fee_config.channel_handshake_config.negotiate_anchor_zero_fee_commitments = true;
+	zero_fee_config.manually_accept_inbound_channels = true;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(zero_fee_config), Some(zero_fee_config)]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	// The shared anchor output may be below the dust limit, so we can't use `check_spends` on its
+	// spending transactions.
+	let check_spends_shared_anchor = |tx: &Transaction, spends_txn: &[&Transaction]| {
+		do_check_spends(tx, |outpoint| spends_txn.iter().find(|spent_tx| spent_tx.txid() == outpoint.txid)
+			.and_then(|spent_tx| spent_tx.output.get(outpoint.vout as usize).cloned()));
+	};
+
+	let (_, _, _, funding_tx) = create_announced_chan_between_nodes_with_value(
+		&nodes, 0, 1, 1_000_000, 500_000_000
+	);
+	assert!(nodes[0].node.list_channels()[0].channel_type.as_ref().unwrap().supports_anchor_zero_fee_commitments());
+	assert_eq!(nodes[0].node.list_channels()[0].feerate_sat_per_1000_weight, Some(0));
+	route_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+
+	connect_blocks(&nodes[0], TEST_FINAL_CLTV + LATENCY_GRACE_PERIOD_BLOCKS + 1);
+	check_closed_broadcast!(&nodes[0], true);
+	check_added_monitors!(&nodes[0], 1);
+	check_closed_event!(&nodes[0], 1, ClosureReason::HolderForceClosed, [nodes[1].node.get_our_node_id()], 1_000_000);
+
+	let mut holder_events = nodes[0].chain_monitor.chain_monitor.get_and_clear_pending_events();
+	assert_eq!(holder_events.len(), 1);
+	let (commitment_tx, anchor_tx) = match holder_events.pop().unwrap() {
+		Event::BumpTransaction(event) => {
+			if let BumpTransactionEvent::ChannelClose { commitment_tx_fee_satoshis, anchor_descriptor, .. } = &event {
+				assert_eq!(*commitment_tx_fee_satoshis, 0);
+				assert!(anchor_descriptor.is_shared_anchor());
+			} else { panic!("Unexpected event"); }
+			let coinbase_tx = Transaction {
+				version: 2,
+				lock_time: LockTime::ZERO,
+				input: vec![TxIn { ..Default::default() }],
+				output: vec![TxOut { // UTXO to attach fees to `anchor_tx`
+					value: Amount::ONE_BTC.to_sat(),
+					script_pubkey: nodes[0].wallet_source.get_change_script().unwrap(),
+				}],
+			};
+			nodes[0].wallet_source.add_utxo(bitcoin::OutPoint { txid: coinbase_tx.txid(), vout: 0 }, coinbase_tx.output[0].value);
+			nodes[0].bump_tx_handler.handle_event(&event);
+			let mut txn = nodes[0].tx_broadcaster.unique_txn_broadcast();
+			assert_eq!(txn.len(), 2);
+			let anchor_tx = txn.pop().unwrap();
+			let commitment_tx = txn.pop().unwrap();
+			assert_eq!(commitment_tx.input.len(), 1);
+			assert_eq!(commitment_tx.input[0].previous_output.txid, funding_tx.txid());
+			check_spends_shared_anchor(&anchor_tx, &[&coinbase_tx, &commitment_tx]);
+			(commitment_tx, anchor_tx)
+		},
+		_ => panic!("Unexpected event"),
+	};
+
+	// The commitment transaction pays no fee and has a single shared anchor output instead of one
+	// anchor output per party.
+	assert_eq!(commitment_tx.version, 3);
+	assert_eq!(commitment_tx.output.len(), 4);
+	assert_eq!(commitment_tx.output.iter().map(|output| output.value).sum::<u64>(), 1_000_000);
+	let shared_anchor_script = chan_utils::get_shared_anchor_script_pubkey();
+	assert_eq!(commitment_tx.output.iter().filter(|output| output.script_pubkey == shared_anchor_script).count(), 1);
+
+	assert_eq!(anchor_tx.version, 3);
+	assert!(anchor_tx.weight().to_wu() <= TRUC_CHILD_MAX_WEIGHT);
+	let anchor_input = anchor_tx.input.iter()
+		.find(|input| input.previous_output.txid == commitment_tx.txid()).unwrap();
+	assert!(anchor_input.witness.is_empty());
+
+	mine_transactions(&nodes[0], &[&commitment_tx, &anchor_tx]);
+
+	let mut holder_events = nodes[0].chain_monitor.chain_monitor.get_and_clear_pending_events();
+	// Certain block `ConnectStyle`s cause an extra `ChannelClose` event to be emitted since the
+	// best block is updated before the confirmed transactions are notified.
+	if nodes[0].connect_style.borrow().updates_best_block_first() {
+		assert_eq!(holder_events.len(), 2);
+		if let Event::BumpTransaction(BumpTransactionEvent::ChannelClose { .. }) = holder_events.remove(0) {}
+		else { panic!("unexpected event"); }
+	} else {
+		assert_eq!(holder_events.len(), 1);
+	}
+	match holder_events.pop().unwrap() {
+		Event::BumpTransaction(event) => {
+			nodes[0].bump_tx_handler.handle_event(&event);
+			let mut txn = nodes[0].tx_broadcaster.unique_txn_broadcast();
+			assert_eq!(txn.len(), 1);
+			let htlc_tx = txn.pop().unwrap();
+			check_spends_shared_anchor(&htlc_tx, &[&commitment_tx, &anchor_tx]);
+			assert_eq!(htlc_tx.version, 3);
+		},
+		_ => panic!("Unexpected event"),
+	}
+
+	// Clear the remaining events as they're not relevant to what we're testing.
+	nodes[0].node.get_and_clear_pending_events();
+	nodes[1].node.get_and_clear_pending_events();
+	nodes[0].node.get_and_clear_pending_msg_events();
+	nodes[1].node.get_and_clear_pending_msg_events();
+}
+
+#[test]
+fn test_anchor_zero_fee_commitments_max_accepted_htlcs() {
+	// Tests that zero-fee commitment channels limit the number of HTLCs in each direction to 114,
+	// both when selecting our own limit and when checking the counterparty's.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let mut zero_fee_config = UserConfig::default();
+	zero_fee_config.channel_handshake_config.negotiate_anchor_zero_fee_commitments = true;
+	zero_fee_config.channel_handshake_config.our_max_accepted_htlcs = 483;
+	zero_fee_config.manually_accept_inbound_channels = true;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(zero_fee_config), Some(zero_fee_config)]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_a_id = nodes[0].node.get_our_node_id();
+	let node_b_id = nodes[1].node.get_our_node_id();
+
+	// An `open_channel` exceeding the limit is rejected.
+	nodes[0].node.create_channel(node_b_id, 1_000_000, 0, 42, None, None).unwrap();
+	let mut open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, node_b_id);
+	assert!(open_channel.channel_type.as_ref().unwrap().supports_anchor_zero_fee_commitments());
+	assert_eq!(open_channel.max_accepted_htlcs, chan_utils::MAX_ZERO_FEE_COMMITMENT_HTLCS);
+	open_channel.max_accepted_htlcs = chan_utils::MAX_ZERO_FEE_COMMITMENT_HTLCS + 1;
+	nodes[1].node.handle_open_channel(&node_a_id, &open_channel);
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	let temporary_channel_id = match &events[0] {
+		Event::OpenChannelRequest { temporary_channel_id, .. } => *temporary_channel_id,
+		_ => panic!("Unexpected event"),
+	};
+	match nodes[1].node.accept_inbound_channel(&temporary_channel_id, &node_a_id, 0) {
+		Err(APIError::ChannelUnavailable { err }) => assert!(err.contains("It must not be larger than 114")),
+		_ => panic!("Expected the channel to be rejected"),
+	}
+	nodes[1].node.get_and_clear_pending_msg_events();
+
+	// As is an `accept_channel` exceeding the limit.
+	nodes[0].node.create_channel(node_b_id, 1_000_000, 0, 42, None, None).unwrap();
+	let open_channel = get_event_msg!(nodes[0], MessageSendEvent::SendOpenChannel, node_b_id);
+	nodes[1].node.handle_open_channel(&node_a_id, &open_channel);
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::OpenChannelRequest { temporary_channel_id, .. } => {
+			nodes[1].node.accept_inbound_channel(temporary_channel_id, &node_a_id, 0).unwrap();
+		},
+		_ => panic!("Unexpected event"),
+	}
+	let mut accept_channel = get_event_msg!(nodes[1], MessageSendEvent::SendAcceptChannel, node_a_id);
+	assert_eq!(accept_channel.max_accepted_htlcs, chan_utils::MAX_ZERO_FEE_COMMITMENT_HTLCS);
+	accept_channel.max_accepted_htlcs = chan_utils::MAX_ZERO_FEE_COMMITMENT_HTLCS + 1;
+	nodes[0].node.handle_accept_channel(&node_b_id, &accept_channel);
+	let msg_events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(msg_events.len(), 1);
+	match &msg_events[0] {
+		MessageSendEvent::HandleError { action: ErrorAction::SendErrorMessage { msg }, .. } => {
+			assert!(msg.data.contains("It must not be larger than 114"));
+		},
+		_ => panic!("Unexpected event"),
+	}
+	nodes[0].node.get_and_clear_pending_events();
+	assert!(nodes[0].node.list_channels().iter()
+		.all(|channel| channel.channel_id != accept_channel.temporary_channel_id));
+}
+
 #[test]
 fn test_anchors_aggregated_revoked_htlc_tx() {
 	// Test that `ChannelMonitor`s can properly detect and claim funds from a counterparty claiming
@@ -29,6 +29,7 @@ use bitcoin::secp256k1::PublicKey;
 use bitcoin::secp256k1::ecdsa::Signature;
 use bitcoin::{secp256k1, Witness};
 use bitcoin::blockdata::script::ScriptBuf;
+use bitcoin::blockdata::transaction::Transaction;
 use bitcoin::hash_types::Txid;
 
 use crate::blinded_path::payment::{BlindedPaymentTlvs, ForwardTlvs, ReceiveTlvs};
@@ -55,7 +56,7 @@ use crate::io_extras::read_to_end;
 use crate::events::{EventsProvider, MessageSendEventsProvider};
 use crate::crypto::streams::ChaChaPolyReadAdapter;
 use crate::util::logger;
-use crate::util::ser::{LengthReadable, LengthReadableArgs, Readable, ReadableArgs, Writeable, Writer, WithoutLength, FixedLengthReader, HighZeroBytesDroppedBigSize, Hostname, TransactionU16LenLimited, BigSize};
+use crate::util::ser::{LengthRead, LengthReadable, LengthReadableArgs, Readable, ReadableArgs, Writeable, Writer, WithoutLength, FixedLengthReader, HighZeroBytesDroppedBigSize, Hostname, TransactionU16LenLimited, BigSize};
 use crate::util::base32;
 
 use crate::routing::gossip::{NodeAlias, NodeId};
@@ -63,6 +64,10 @@ use crate::routing::gossip::{NodeAlias, NodeId};
 /// 21 million * 10^8 * 1000
 pub(crate) const MAX_VALUE_MSAT: u64 = 21_000_000_0000_0000_000;
 
+/// The experimental onion payload TLV type used by the sender of an async payment to ask the
+/// forwarding node to hold the HTLC until the recipient releases it.
+const HOLD_HTLC_TLV_TYPE: u64 = 65536;
+
 #[cfg(taproot)]
 /// A partial signature that also contains the Musig2 nonce its signer used
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -441,6 +446,32 @@ pub struct ChannelReady {
 	pub short_channel_id_alias: Option<u64>,
 }
 
+/// A [`peer_storage`] message which may be sent to peers we have channels with, asking them to
+/// store the given blob on our behalf and return it via [`PeerStorageRetrieval`] upon
+/// reconnection.
+///
+/// [`peer_storage`]: https://github.com/lightning/bolts/pull/1110
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PeerStorage {
+	/// The data to be stored, which should be encrypted by the sender. May be at most
+	/// [`MAX_PEER_STORAGE_SIZE`] bytes.
+	pub data: Vec<u8>,
+}
+
+/// A [`peer_storage_retrieval`] message, returning the latest blob a peer stored with us via
+/// [`PeerStorage`].
+///
+/// [`peer_storage_retrieval`]: https://github.com/lightning/bolts/pull/1110
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PeerStorageRetrieval {
+	/// The data which was stored.
+	pub data: Vec<u8>,
+}
+
+/// The maximum size of the blob in a [`PeerStorage`] message, chosen such that the message always
+/// fits within the maximum Lightning message size.
+pub const MAX_PEER_STORAGE_SIZE: usize = 65531;
+
 /// An stfu (quiescence) message to be sent by or received from the stfu initiator.
 // TODO(splicing): Add spec link for `stfu`; still in draft, using from https://github.com/lightning/bolts/pull/863
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -507,11 +538,17 @@ pub struct TxAddInput {
 	pub serial_id: u64,
 	/// Serialized transaction that contains the output this input spends to verify that it is non
 	/// malleable.
-	pub prevtx: TransactionU16LenLimited,
+	///
+	/// This is `None` (and encoded as an empty transaction) if and only if this is the shared input
+	/// of a splice, in which case [`Self::shared_input_txid`] is set instead.
+	pub prevtx: Option<TransactionU16LenLimited>,
 	/// The index of the output being spent
 	pub prevtx_out: u32,
 	/// The sequence number of this input
 	pub sequence: u32,
+	/// The txid of the channel's current funding transaction, set only when this input spends the
+	/// channel's current funding output as part of a splice.
+	pub shared_input_txid: Option<Txid>,
 }
 
 /// A tx_add_output message for adding an output during interactive transaction construction.
@@ -574,6 +611,8 @@ pub struct TxSignatures {
 	pub tx_hash: Txid,
 	/// The list of witnesses
 	pub witnesses: Vec<Witness>,
+	/// The sender's signature for the shared (previous funding) input of a splice transaction.
+	pub shared_input_signature: Option<Signature>,
 }
 
 /// A tx_init_rbf message which initiates a replacement of the transaction after it's been
@@ -685,6 +724,12 @@ pub struct UpdateAddHTLC {
 	/// Provided if we are relaying or receiving a payment within a blinded path, to decrypt the onion
 	/// routing packet and the recipient-provided encrypted payload within.
 	pub blinding_point: Option<PublicKey>,
+	/// Whether the sender of this message endorses the HTLC, i.e. expects it to resolve quickly
+	/// and stakes its local reputation with us on that. Sent in the experimental
+	/// `update_add_tlvs` type 106823. `None` if the sender did not signal endorsement at all,
+	/// which is treated the same as `Some(false)`. Any signalled value other than 1 is read as
+	/// `Some(false)`.
+	pub endorsed: Option<bool>,
 }
 
  /// An onion message to be sent to or received from a peer.
@@ -1351,6 +1396,19 @@ pub struct ReplyShortChannelIdsEnd {
 	pub full_information: bool,
 }
 
+/// A single part of our reply to a [`QueryShortChannelIds`] message, as returned by
+/// [`RoutingMessageHandler::get_next_short_channel_ids_reply`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShortChannelIdsReply {
+	/// The [`ChannelAnnouncement`] for one of the queried `short_channel_id`s, along with the
+	/// latest [`ChannelUpdate`] in each direction, if any.
+	ChannelAnnouncement(ChannelAnnouncement, Option<ChannelUpdate>, Option<ChannelUpdate>),
+	/// The [`NodeAnnouncement`] for one of the endpoints of a queried channel.
+	NodeAnnouncement(NodeAnnouncement),
+	/// The [`ReplyShortChannelIdsEnd`] which completes the reply to the query.
+	End(ReplyShortChannelIdsEnd),
+}
+
 /// A [`gossip_timestamp_filter`] message is used by a node to request
 /// gossip relay for messages in the requested time range when the
 /// `gossip_queries` feature has been negotiated.
@@ -1471,6 +1529,12 @@ pub trait ChannelMessageHandler : MessageSendEventsProvider {
 	/// Handle an incoming `stfu` message from the given peer.
 	fn handle_stfu(&self, their_node_id: &PublicKey, msg: &Stfu);
 
+	// Peer storage
+	/// Handle an incoming `peer_storage` message from the given peer.
+	fn handle_peer_storage(&self, their_node_id: &PublicKey, msg: &PeerStorage);
+	/// Handle an incoming `peer_storage_retrieval` message from the given peer.
+	fn handle_peer_storage_retrieval(&self, their_node_id: &PublicKey, msg: &PeerStorageRetrieval);
+
 	// Splicing
 	/// Handle an incoming `splice` message from the given peer.
 	fn handle_splice(&self, their_node_id: &PublicKey, msg: &Splice);
@@ -1558,6 +1622,13 @@ pub trait ChannelMessageHandler : MessageSendEventsProvider {
 	/// If it's `None`, then no particular network chain hash compatibility will be enforced when
 	/// connecting to peers.
 	fn get_chain_hashes(&self) -> Option<Vec<ChainHash>>;
+
+	/// Returns whether we have any channels with the given peer which have not yet been closed.
+	///
+	/// Such peers are exempt from the limits configured in [`PeerLimits`].
+	///
+	/// [`PeerLimits`]: crate::ln::peer_handler::PeerLimits
+	fn peer_has_channels(&self, their_node_id: &PublicKey) -> bool;
 }
 
 /// A trait to describe an object which can receive routing messages.
@@ -1594,6 +1665,9 @@ pub trait RoutingMessageHandler : MessageSendEventsProvider {
 	/// with us. Implementors should be somewhat conservative about doing so, however, as other
 	/// message handlers may still wish to communicate with this peer.
 	fn peer_connected(&self, their_node_id: &PublicKey, init: &Init, inbound: bool) -> Result<(), ()>;
+	/// Indicates a connection to the peer failed/an existing connection was lost. Allows handlers to
+	/// drop any state kept for replying to the peer's gossip queries.
+	fn peer_disconnected(&self, their_node_id: &PublicKey);
 	/// Handles the reply of a query we initiated to learn about channels
 	/// for a given range of blocks. We can expect to receive one or more
 	/// replies to a single query.
@@ -1609,6 +1683,12 @@ pub trait RoutingMessageHandler : MessageSendEventsProvider {
 	/// Handles when a peer asks us to send routing gossip messages for a
 	/// list of `short_channel_id`s.
 	fn handle_query_short_channel_ids(&self, their_node_id: &PublicKey, msg: QueryShortChannelIds) -> Result<(), LightningError>;
+	/// Gets the next part of our reply to a [`QueryShortChannelIds`] previously handled via
+	/// [`Self::handle_query_short_channel_ids`] for the given peer.
+	///
+	/// This is polled each time the peer's outbound buffer has room for more gossip, until either
+	/// [`ShortChannelIdsReply::End`] or `None` is returned.
+	fn get_next_short_channel_ids_reply(&self, their_node_id: &PublicKey) -> Option<ShortChannelIdsReply>;
 
 	// Handler queueing status:
 	/// Indicates that there are a large number of [`ChannelAnnouncement`] (or other) messages
@@ -1689,7 +1769,7 @@ mod fuzzy_internal_msgs {
 	use crate::prelude::*;
 	use crate::ln::{PaymentPreimage, PaymentSecret};
 	use crate::ln::features::BlindedHopFeatures;
-	use super::FinalOnionHopData;
+	use super::{FinalOnionHopData, TrampolineOnionPacket};
 
 	// These types aren't intended to be pub, but are exposed for direct fuzzing (as we deserialize
 	// them from untrusted input):
@@ -1700,6 +1780,9 @@ mod fuzzy_internal_msgs {
 			/// The value, in msat, of the payment after this hop's fee is deducted.
 			amt_to_forward: u64,
 			outgoing_cltv_value: u32,
+			/// Set by the sender of an async payment to have us hold the HTLC until the often-offline
+			/// recipient releases it via a `ReleaseHeldHtlc` message carrying this secret.
+			hold_htlc: Option<[u8; 32]>,
 		},
 		Receive {
 			payment_data: Option<FinalOnionHopData>,
@@ -1715,6 +1798,7 @@ mod fuzzy_internal_msgs {
 			payment_constraints: PaymentConstraints,
 			features: BlindedHopFeatures,
 			intro_node_blinding_point: Option<PublicKey>,
+			hold_htlc: Option<[u8; 32]>,
 		},
 		BlindedReceive {
 			sender_intended_htlc_amt_msat: u64,
@@ -1723,7 +1807,23 @@ mod fuzzy_internal_msgs {
 			payment_secret: PaymentSecret,
 			payment_constraints: PaymentConstraints,
 			intro_node_blinding_point: Option<PublicKey>,
-		}
+			keysend_preimage: Option<PaymentPreimage>,
+		},
+		/// The outer onion payload for a trampoline node, carrying the trampoline onion.
+		TrampolineEntrypoint {
+			amt_to_forward: u64,
+			outgoing_cltv_value: u32,
+			multipath_trampoline_data: Option<FinalOnionHopData>,
+			trampoline_packet: TrampolineOnionPacket,
+		},
+		/// A payload within a trampoline onion instructing us to forward to the next trampoline
+		/// node.
+		TrampolineForward {
+			next_trampoline: PublicKey,
+			/// The value, in msat, of the payment the next trampoline node should receive.
+			amt_to_forward: u64,
+			outgoing_cltv_value: u32,
+		},
 	}
 
 	pub(crate) enum OutboundOnionPayload {
@@ -1732,6 +1832,9 @@ mod fuzzy_internal_msgs {
 			/// The value, in msat, of the payment after this hop's fee is deducted.
 			amt_to_forward: u64,
 			outgoing_cltv_value: u32,
+			/// Set by the sender of an async payment to have us hold the HTLC until the often-offline
+			/// recipient releases it via a `ReleaseHeldHtlc` message carrying this secret.
+			hold_htlc: Option<[u8; 32]>,
 		},
 		Receive {
 			payment_data: Option<FinalOnionHopData>,
@@ -1744,6 +1847,7 @@ mod fuzzy_internal_msgs {
 		BlindedForward {
 			encrypted_tlvs: Vec<u8>,
 			intro_node_blinding_point: Option<PublicKey>,
+			hold_htlc: Option<[u8; 32]>,
 		},
 		BlindedReceive {
 			sender_intended_htlc_amt_msat: u64,
@@ -1751,7 +1855,19 @@ mod fuzzy_internal_msgs {
 			cltv_expiry_height: u32,
 			encrypted_tlvs: Vec<u8>,
 			intro_node_blinding_point: Option<PublicKey>, // Set if the introduction node of the blinded path is the final node
-		}
+			keysend_preimage: Option<PaymentPreimage>,
+		},
+		TrampolineEntrypoint {
+			amt_to_forward: u64,
+			outgoing_cltv_value: u32,
+			multipath_trampoline_data: Option<FinalOnionHopData>,
+			trampoline_packet: TrampolineOnionPacket,
+		},
+		TrampolineForward {
+			next_trampoline: PublicKey,
+			amt_to_forward: u64,
+			outgoing_cltv_value: u32,
+		},
 	}
 
 	pub struct DecodedOnionErrorPacket {
@@ -1800,6 +1916,39 @@ impl fmt::Debug for OnionPacket {
 	}
 }
 
+/// BOLT 4 trampoline onion packet, carried within the onion payload of a trampoline node.
+///
+/// Unlike the outer [`OnionPacket`], the hop data is of variable length.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct TrampolineOnionPacket {
+	/// BOLT 4 version number.
+	pub version: u8,
+	/// A random sepc256k1 point, used to build the ECDH shared secret to decrypt hop_data.
+	pub public_key: PublicKey,
+	/// Encrypted payload for the next hop.
+	pub hop_data: Vec<u8>,
+	/// HMAC to verify the integrity of hop_data.
+	pub hmac: [u8; 32],
+}
+
+impl onion_utils::Packet for TrampolineOnionPacket {
+	type Data = Vec<u8>;
+	fn new(public_key: PublicKey, hop_data: Vec<u8>, hmac: [u8; 32]) -> Self {
+		Self {
+			version: 0,
+			public_key,
+			hop_data,
+			hmac,
+		}
+	}
+}
+
+impl fmt::Debug for TrampolineOnionPacket {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_fmt(format_args!("TrampolineOnionPacket version {} with hmac {:?}", self.version, &self.hmac[..]))
+	}
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub(crate) struct OnionErrorPacket {
 	// This really should be a constant size slice, but the spec lets these things be up to 128KB?
@@ -1901,6 +2050,14 @@ impl_writeable_msg!(Stfu, {
 	initiator,
 }, {});
 
+impl_writeable_msg!(PeerStorage, {
+	data,
+}, {});
+
+impl_writeable_msg!(PeerStorageRetrieval, {
+	data,
+}, {});
+
 impl_writeable_msg!(Splice, {
 	channel_id,
 	chain_hash,
@@ -1921,13 +2078,51 @@ impl_writeable_msg!(SpliceLocked, {
 	channel_id,
 }, {});
 
-impl_writeable_msg!(TxAddInput, {
-	channel_id,
-	serial_id,
-	prevtx,
-	prevtx_out,
-	sequence,
-}, {});
+impl Writeable for TxAddInput {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.channel_id.write(w)?;
+		self.serial_id.write(w)?;
+		match &self.prevtx {
+			Some(prevtx) => prevtx.write(w)?,
+			// The shared input of a splice is sent with an empty `prevtx`.
+			None => 0u16.write(w)?,
+		}
+		self.prevtx_out.write(w)?;
+		self.sequence.write(w)?;
+		encode_tlv_stream!(w, {
+			(0, self.shared_input_txid, option),
+		});
+		Ok(())
+	}
+}
+
+impl Readable for TxAddInput {
+	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let channel_id = Readable::read(r)?;
+		let serial_id = Readable::read(r)?;
+		let prevtx_len: u16 = Readable::read(r)?;
+		let prevtx = if prevtx_len == 0 {
+			None
+		} else {
+			let mut prevtx_reader = FixedLengthReader::new(&mut *r, prevtx_len as u64);
+			let prevtx: Transaction = Readable::read(&mut prevtx_reader)?;
+			if prevtx_reader.bytes_remain() {
+				return Err(DecodeError::BadLengthDescriptor);
+			}
+			Some(TransactionU16LenLimited::new(prevtx).map_err(|_| DecodeError::InvalidValue)?)
+		};
+		let prevtx_out = Readable::read(r)?;
+		let sequence = Readable::read(r)?;
+		let mut shared_input_txid = None;
+		decode_tlv_stream!(r, {
+			(0, shared_input_txid, option),
+		});
+		if prevtx.is_none() != shared_input_txid.is_some() {
+			return Err(DecodeError::InvalidValue);
+		}
+		Ok(Self { channel_id, serial_id, prevtx, prevtx_out, sequence, shared_input_txid })
+	}
+}
 
 impl_writeable_msg!(TxAddOutput, {
 	channel_id,
@@ -1954,7 +2149,9 @@ impl_writeable_msg!(TxSignatures, {
 	channel_id,
 	tx_hash,
 	witnesses,
-}, {});
+}, {
+	(0, shared_input_signature, option),
+});
 
 impl_writeable_msg!(TxInitRbf, {
 	channel_id,
@@ -2228,17 +2425,77 @@ impl Readable for OnionPacket {
 	}
 }
 
-impl_writeable_msg!(UpdateAddHTLC, {
-	channel_id,
-	htlc_id,
-	amount_msat,
-	payment_hash,
-	cltv_expiry,
-	onion_routing_packet,
-}, {
-	(0, blinding_point, option),
-	(65537, skimmed_fee_msat, option)
-});
+impl Writeable for TrampolineOnionPacket {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.version.write(w)?;
+		self.public_key.write(w)?;
+		w.write_all(&self.hop_data)?;
+		self.hmac.write(w)?;
+		Ok(())
+	}
+}
+
+impl LengthReadable for TrampolineOnionPacket {
+	fn read<R: LengthRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let version = Readable::read(r)?;
+		let public_key = Readable::read(r)?;
+
+		let hop_data_len = r.total_bytes().saturating_sub(66); // 1 (version) + 33 (pubkey) + 32 (HMAC) = 66
+		let mut rd = FixedLengthReader::new(&mut *r, hop_data_len);
+		let hop_data = WithoutLength::<Vec<u8>>::read(&mut rd)?.0;
+
+		let hmac = Readable::read(r)?;
+
+		Ok(TrampolineOnionPacket {
+			version,
+			public_key,
+			hop_data,
+			hmac,
+		})
+	}
+}
+
+impl Writeable for UpdateAddHTLC {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.channel_id.write(w)?;
+		self.htlc_id.write(w)?;
+		self.amount_msat.write(w)?;
+		self.payment_hash.write(w)?;
+		self.cltv_expiry.write(w)?;
+		self.onion_routing_packet.write(w)?;
+		encode_tlv_stream!(w, {
+			(0, self.blinding_point, option),
+			(65537, self.skimmed_fee_msat, option),
+			(106823, self.endorsed.map(|endorsed| endorsed as u8), option),
+		});
+		Ok(())
+	}
+}
+
+impl Readable for UpdateAddHTLC {
+	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let channel_id = Readable::read(r)?;
+		let htlc_id = Readable::read(r)?;
+		let amount_msat = Readable::read(r)?;
+		let payment_hash = Readable::read(r)?;
+		let cltv_expiry = Readable::read(r)?;
+		let onion_routing_packet = Readable::read(r)?;
+		let mut blinding_point = None;
+		let mut skimmed_fee_msat = None;
+		// The endorsement signal is a u8 on the wire, with any value other than 1 meaning the HTLC
+		// is unendorsed. Reading it as a bool would reject (and disconnect on) values above 1.
+		let mut endorsed: Option<u8> = None;
+		decode_tlv_stream!(r, {
+			(0, blinding_point, option),
+			(65537, skimmed_fee_msat, option),
+			(106823, endorsed, option),
+		});
+		Ok(Self {
+			channel_id, htlc_id, amount_msat, payment_hash, cltv_expiry, onion_routing_packet,
+			blinding_point, skimmed_fee_msat, endorsed: endorsed.map(|endorsed| endorsed == 1),
+		})
+	}
+}
 
 impl Readable for OnionMessage {
 	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
@@ -2282,11 +2539,12 @@ impl Readable for FinalOnionHopData {
 impl Writeable for OutboundOnionPayload {
 	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
 		match self {
-			Self::Forward { short_channel_id, amt_to_forward, outgoing_cltv_value } => {
+			Self::Forward { short_channel_id, amt_to_forward, outgoing_cltv_value, hold_htlc } => {
 				_encode_varint_length_prefixed_tlv!(w, {
 					(2, HighZeroBytesDroppedBigSize(*amt_to_forward), required),
 					(4, HighZeroBytesDroppedBigSize(*outgoing_cltv_value), required),
-					(6, short_channel_id, required)
+					(6, short_channel_id, required),
+					(HOLD_HTLC_TLV_TYPE, hold_htlc, option)
 				});
 			},
 			Self::Receive {
@@ -2306,22 +2564,42 @@ impl Writeable for OutboundOnionPayload {
 					(16, payment_metadata.as_ref().map(|m| WithoutLength(m)), option)
 				}, custom_tlvs.iter());
 			},
-			Self::BlindedForward { encrypted_tlvs, intro_node_blinding_point } => {
+			Self::BlindedForward { encrypted_tlvs, intro_node_blinding_point, hold_htlc } => {
 				_encode_varint_length_prefixed_tlv!(w, {
 					(10, *encrypted_tlvs, required_vec),
-					(12, intro_node_blinding_point, option)
+					(12, intro_node_blinding_point, option),
+					(HOLD_HTLC_TLV_TYPE, hold_htlc, option)
 				});
 			},
 			Self::BlindedReceive {
 				sender_intended_htlc_amt_msat, total_msat, cltv_expiry_height, encrypted_tlvs,
-				intro_node_blinding_point,
+				intro_node_blinding_point, keysend_preimage,
 			} => {
 				_encode_varint_length_prefixed_tlv!(w, {
 					(2, HighZeroBytesDroppedBigSize(*sender_intended_htlc_amt_msat), required),
 					(4, HighZeroBytesDroppedBigSize(*cltv_expiry_height), required),
 					(10, *encrypted_tlvs, required_vec),
 					(12, intro_node_blinding_point, option),
-					(18, HighZeroBytesDroppedBigSize(*total_msat), required)
+					(18, HighZeroBytesDroppedBigSize(*total_msat), required),
+					// See https://github.com/lightning/blips/blob/master/blip-0003.md
+					(5482373484, keysend_preimage, option)
+				});
+			},
+			Self::TrampolineEntrypoint {
+				amt_to_forward, outgoing_cltv_value, ref multipath_trampoline_data, ref trampoline_packet,
+			} => {
+				_encode_varint_length_prefixed_tlv!(w, {
+					(2, HighZeroBytesDroppedBigSize(*amt_to_forward), required),
+					(4, HighZeroBytesDroppedBigSize(*outgoing_cltv_value), required),
+					(8, multipath_trampoline_data, option),
+					(20, trampoline_packet, required)
+				});
+			},
+			Self::TrampolineForward { next_trampoline, amt_to_forward, outgoing_cltv_value } => {
+				_encode_varint_length_prefixed_tlv!(w, {
+					(2, HighZeroBytesDroppedBigSize(*amt_to_forward), required),
+					(4, HighZeroBytesDroppedBigSize(*outgoing_cltv_value), required),
+					(14, next_trampoline, required)
 				});
 			},
 		}
@@ -2342,6 +2620,9 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 		let mut payment_metadata: Option<WithoutLength<Vec<u8>>> = None;
 		let mut total_msat = None;
 		let mut keysend_preimage: Option<PaymentPreimage> = None;
+		let mut next_trampoline: Option<PublicKey> = None;
+		let mut trampoline_packet: Option<TrampolineOnionPacket> = None;
+		let mut hold_htlc: Option<[u8; 32]> = None;
 		let mut custom_tlvs = Vec::new();
 
 		let tlv_len = BigSize::read(r)?;
@@ -2353,8 +2634,11 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 			(8, payment_data, option),
 			(10, encrypted_tlvs_opt, option),
 			(12, intro_node_blinding_point, option),
+			(14, next_trampoline, option),
 			(16, payment_metadata, option),
 			(18, total_msat, (option, encoding: (u64, HighZeroBytesDroppedBigSize))),
+			(20, trampoline_packet, (option: LengthReadable)),
+			(HOLD_HTLC_TLV_TYPE, hold_htlc, option),
 			// See https://github.com/lightning/blips/blob/master/blip-0003.md
 			(5482373484, keysend_preimage, option)
 		}, |msg_type: u64, msg_reader: &mut FixedLengthReader<_>| -> Result<bool, DecodeError> {
@@ -2372,7 +2656,7 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 
 		if let Some(blinding_point) = intro_node_blinding_point.or(update_add_blinding_point) {
 			if short_id.is_some() || payment_data.is_some() || payment_metadata.is_some() ||
-				keysend_preimage.is_some()
+				next_trampoline.is_some() || trampoline_packet.is_some()
 			{
 				return Err(DecodeError::InvalidValue)
 			}
@@ -2386,7 +2670,9 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 				ChaChaPolyReadAdapter { readable: BlindedPaymentTlvs::Forward(ForwardTlvs {
 					short_channel_id, payment_relay, payment_constraints, features
 				})} => {
-					if amt.is_some() || cltv_value.is_some() || total_msat.is_some() {
+					if amt.is_some() || cltv_value.is_some() || total_msat.is_some() ||
+						keysend_preimage.is_some()
+					{
 						return Err(DecodeError::InvalidValue)
 					}
 					Ok(Self::BlindedForward {
@@ -2395,12 +2681,15 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 						payment_constraints,
 						features,
 						intro_node_blinding_point,
+						hold_htlc,
 					})
 				},
 				ChaChaPolyReadAdapter { readable: BlindedPaymentTlvs::Receive(ReceiveTlvs {
 					payment_secret, payment_constraints
 				})} => {
-					if total_msat.unwrap_or(0) > MAX_VALUE_MSAT { return Err(DecodeError::InvalidValue) }
+					if total_msat.unwrap_or(0) > MAX_VALUE_MSAT || hold_htlc.is_some() {
+						return Err(DecodeError::InvalidValue)
+					}
 					Ok(Self::BlindedReceive {
 						sender_intended_htlc_amt_msat: amt.ok_or(DecodeError::InvalidValue)?,
 						total_msat: total_msat.ok_or(DecodeError::InvalidValue)?,
@@ -2408,20 +2697,45 @@ impl<NS: Deref> ReadableArgs<(Option<PublicKey>, &NS)> for InboundOnionPayload w
 						payment_secret,
 						payment_constraints,
 						intro_node_blinding_point,
+						keysend_preimage,
 					})
 				},
 			}
 		} else if let Some(short_channel_id) = short_id {
 			if payment_data.is_some() || payment_metadata.is_some() || encrypted_tlvs_opt.is_some() ||
-				total_msat.is_some()
+				total_msat.is_some() || next_trampoline.is_some() || trampoline_packet.is_some()
 			{ return Err(DecodeError::InvalidValue) }
 			Ok(Self::Forward {
 				short_channel_id,
 				amt_to_forward: amt.ok_or(DecodeError::InvalidValue)?,
 				outgoing_cltv_value: cltv_value.ok_or(DecodeError::InvalidValue)?,
+				hold_htlc,
+			})
+		} else if let Some(next_trampoline) = next_trampoline {
+			if payment_data.is_some() || payment_metadata.is_some() || encrypted_tlvs_opt.is_some() ||
+				total_msat.is_some() || keysend_preimage.is_some() || trampoline_packet.is_some() ||
+				hold_htlc.is_some()
+			{ return Err(DecodeError::InvalidValue) }
+			Ok(Self::TrampolineForward {
+				next_trampoline,
+				amt_to_forward: amt.ok_or(DecodeError::InvalidValue)?,
+				outgoing_cltv_value: cltv_value.ok_or(DecodeError::InvalidValue)?,
+			})
+		} else if let Some(trampoline_packet) = trampoline_packet {
+			if payment_metadata.is_some() || encrypted_tlvs_opt.is_some() || total_msat.is_some() ||
+				keysend_preimage.is_some() || hold_htlc.is_some() || !custom_tlvs.is_empty()
+			{ return Err(DecodeError::InvalidValue) }
+			if payment_data.as_ref().map_or(false, |data| data.total_msat > MAX_VALUE_MSAT) {
+				return Err(DecodeError::InvalidValue)
+			}
+			Ok(Self::TrampolineEntrypoint {
+				amt_to_forward: amt.ok_or(DecodeError::InvalidValue)?,
+				outgoing_cltv_value: cltv_value.ok_or(DecodeError::InvalidValue)?,
+				multipath_trampoline_data: payment_data,
+				trampoline_packet,
 			})
 		} else {
-			if encrypted_tlvs_opt.is_some() || total_msat.is_some() {
+			if encrypted_tlvs_opt.is_some() || total_msat.is_some() || hold_htlc.is_some() {
 				return Err(DecodeError::InvalidValue)
 			}
 			if let Some(data) = &payment_data {
@@ -3525,6 +3839,16 @@ mod tests {
 		assert_eq!(encoded_value.as_hex().to_string(), "020202020202020202020202020202020202020202020202020202020202020201");
 	}
 
+	#[test]
+	fn encoding_peer_storage() {
+		let peer_storage = msgs::PeerStorage { data: vec![1, 2, 3] };
+		let encoded_value = peer_storage.encode();
+		assert_eq!(encoded_value.as_hex().to_string(), "0003010203");
+		let retrieval = msgs::PeerStorageRetrieval { data: vec![1, 2, 3] };
+		assert_eq!(retrieval.encode(), encoded_value);
+		assert_eq!(msgs::PeerStorageRetrieval::read(&mut &encoded_value[..]).unwrap(), retrieval);
+	}
+
 	#[test]
 	fn encoding_splice_ack() {
 		let secp_ctx = Secp256k1::new();
@@ -3553,7 +3877,7 @@ mod tests {
 		let tx_add_input = msgs::TxAddInput {
 			channel_id: ChannelId::from_bytes([2; 32]),
 			serial_id: 4886718345,
-			prevtx: TransactionU16LenLimited::new(Transaction {
+			prevtx: Some(TransactionU16LenLimited::new(Transaction {
 				version: 2,
 				lock_time: LockTime::ZERO,
 				input: vec![TxIn {
@@ -3574,13 +3898,42 @@ mod tests {
 						script_pubkey: Address::from_str("bc1qxmk834g5marzm227dgqvynd23y2nvt2ztwcw2z").unwrap().payload.script_pubkey(),
 					},
 				],
-			}).unwrap(),
+			}).unwrap()),
 			prevtx_out: 305419896,
 			sequence: 305419896,
+			shared_input_txid: None,
 		};
 		let encoded_value = tx_add_input.encode();
 		let target_value = <Vec<u8>>::from_hex("0202020202020202020202020202020202020202020202020202020202020202000000012345678900de02000000000101779ced6c148293f86b60cb222108553d22c89207326bb7b6b897e23e64ab5b300200000000fdffffff0236dbc1000000000016001417d29e4dd454bac3b1cde50d1926da80cfc5287b9cbd03000000000016001436ec78d514df462da95e6a00c24daa8915362d420247304402206af85b7dd67450ad12c979302fac49dfacbc6a8620f49c5da2b5721cf9565ca502207002b32fed9ce1bf095f57aeb10c36928ac60b12e723d97d2964a54640ceefa701210301ab7dc16488303549bfcdd80f6ae5ee4c20bf97ab5410bbd6b1bfa85dcd6944000000001234567812345678").unwrap();
 		assert_eq!(encoded_value, target_value);
+		assert_eq!(msgs::TxAddInput::read(&mut Cursor::new(&target_value)).unwrap(), tx_add_input);
+	}
+
+	#[test]
+	fn encoding_tx_add_shared_input() {
+		let tx_add_input = msgs::TxAddInput {
+			channel_id: ChannelId::from_bytes([2; 32]),
+			serial_id: 4886718345,
+			prevtx: None,
+			prevtx_out: 305419896,
+			sequence: 305419896,
+			shared_input_txid: Some(Txid::from_str("c2d4449afa8d26140898dd54d3390b057ba2a5afcf03ba29d7dc0d8b9ffe966e").unwrap()),
+		};
+		let encoded_value = tx_add_input.encode();
+		let mut target_value = <Vec<u8>>::from_hex("0202020202020202020202020202020202020202020202020202020202020202").unwrap(); // channel_id
+		target_value.append(&mut <Vec<u8>>::from_hex("0000000123456789").unwrap()); // serial_id
+		target_value.append(&mut <Vec<u8>>::from_hex("0000").unwrap()); // prevtx_len (u16)
+		target_value.append(&mut <Vec<u8>>::from_hex("12345678").unwrap()); // prevtx_out
+		target_value.append(&mut <Vec<u8>>::from_hex("12345678").unwrap()); // sequence
+		target_value.append(&mut <Vec<u8>>::from_hex("0020").unwrap()); // Type (shared_input_txid) and length
+		target_value.append(&mut <Vec<u8>>::from_hex("6e96fe9f8b0ddcd729ba03cfafa5a27b050b39d354dd980814268dfa9a44d4c2").unwrap());
+		assert_eq!(encoded_value, target_value);
+		assert_eq!(msgs::TxAddInput::read(&mut Cursor::new(&target_value)).unwrap(), tx_add_input);
+
+		// A shared input must not carry a `prevtx`, and a non-shared input must.
+		let mut no_txid_value = target_value.clone();
+		no_txid_value.truncate(target_value.len() - 34);
+		assert!(msgs::TxAddInput::read(&mut Cursor::new(&no_txid_value)).is_err());
 	}
 
 	#[test]
@@ -3641,6 +3994,7 @@ mod tests {
 					<Vec<u8>>::from_hex("3045022100ee00dbf4a862463e837d7c08509de814d620e4d9830fa84818713e0fa358f145022021c3c7060c4d53fe84fd165d60208451108a778c13b92ca4c6bad439236126cc01").unwrap(),
 					<Vec<u8>>::from_hex("028fbbf0b16f5ba5bcb5dd37cd4047ce6f726a21c06682f9ec2f52b057de1dbdb5").unwrap()]),
 			],
+			shared_input_signature: None,
 		};
 		let encoded_value = tx_signatures.encode();
 		let mut target_value = <Vec<u8>>::from_hex("0202020202020202020202020202020202020202020202020202020202020202").unwrap(); // channel_id
@@ -3807,12 +4161,53 @@ mod tests {
 			onion_routing_packet,
 			skimmed_fee_msat: None,
 			blinding_point: None,
+			endorsed: None,
 		};
 		let encoded_value = update_add_htlc.encode();
 		let target_value = <Vec<u8>>::from_hex("020202020202020202020202020202020202020202020202020202020202020200083a840000034d32144668701144760101010101010101010101010101010101010101010101010101010101010101000c89d4ff031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010202020202020202020202020202020202020202020202020202020202020202").unwrap();
 		assert_eq!(encoded_value, target_value);
 	}
 
+	#[test]
+	fn encoding_update_add_htlc_endorsed() {
+		let secp_ctx = Secp256k1::new();
+		let (_, pubkey_1) = get_keys_from!("0101010101010101010101010101010101010101010101010101010101010101", secp_ctx);
+		let mut update_add_htlc = msgs::UpdateAddHTLC {
+			channel_id: ChannelId::from_bytes([2; 32]),
+			htlc_id: 2316138423780173,
+			amount_msat: 3608586615801332854,
+			payment_hash: PaymentHash([1; 32]),
+			cltv_expiry: 821716,
+			onion_routing_packet: msgs::OnionPacket {
+				version: 255,
+				public_key: Ok(pubkey_1),
+				hop_data: [1; 20*65],
+				hmac: [2; 32]
+			},
+			skimmed_fee_msat: None,
+			blinding_point: None,
+			endorsed: None,
+		};
+		let unendorsed_len = update_add_htlc.encode().len();
+
+		// The endorsement signal is sent in the experimental TLV type 106823.
+		for endorsed in [false, true] {
+			update_add_htlc.endorsed = Some(endorsed);
+			let encoded_value = update_add_htlc.encode();
+			assert_eq!(encoded_value.len(), unendorsed_len + 7);
+			assert_eq!(&encoded_value[unendorsed_len..], &[0xfe, 0x00, 0x01, 0xa1, 0x47, 0x01, endorsed as u8]);
+			let decoded: msgs::UpdateAddHTLC = Readable::read(&mut Cursor::new(&encoded_value[..])).unwrap();
+			assert_eq!(decoded, update_add_htlc);
+		}
+
+		// Any value other than 1 is read as unendorsed rather than failing to decode.
+		update_add_htlc.endorsed = Some(true);
+		let mut encoded_value = update_add_htlc.encode();
+		*encoded_value.last_mut().unwrap() = 2;
+		let decoded: msgs::UpdateAddHTLC = Readable::read(&mut Cursor::new(&encoded_value[..])).unwrap();
+		assert_eq!(decoded.endorsed, Some(false));
+	}
+
 	#[test]
 	fn encoding_update_fulfill_htlc() {
 		let update_fulfill_htlc = msgs::UpdateFulfillHTLC {
@@ -3998,6 +4393,7 @@ mod tests {
 			short_channel_id: 0xdeadbeef1bad1dea,
 			amt_to_forward: 0x0badf00d01020304,
 			outgoing_cltv_value: 0xffffffff,
+			hold_htlc: None,
 		};
 		let encoded_value = outbound_msg.encode();
 		let target_value = <Vec<u8>>::from_hex("1a02080badf00d010203040404ffffffff0608deadbeef1bad1dea").unwrap();
@@ -4006,11 +4402,29 @@ mod tests {
 		let node_signer = test_utils::TestKeysInterface::new(&[42; 32], Network::Testnet);
 		let inbound_msg = ReadableArgs::read(&mut Cursor::new(&target_value[..]), (None, &&node_signer)).unwrap();
 		if let msgs::InboundOnionPayload::Forward {
-			short_channel_id, amt_to_forward, outgoing_cltv_value
+			short_channel_id, amt_to_forward, outgoing_cltv_value, hold_htlc
 		} = inbound_msg {
 			assert_eq!(short_channel_id, 0xdeadbeef1bad1dea);
 			assert_eq!(amt_to_forward, 0x0badf00d01020304);
 			assert_eq!(outgoing_cltv_value, 0xffffffff);
+			assert!(hold_htlc.is_none());
+		} else { panic!(); }
+	}
+
+	#[test]
+	fn encoding_held_nonfinal_onion_hop_data() {
+		let outbound_msg = msgs::OutboundOnionPayload::Forward {
+			short_channel_id: 0xdeadbeef1bad1dea,
+			amt_to_forward: 0x0badf00d01020304,
+			outgoing_cltv_value: 0xffffffff,
+			hold_htlc: Some([42; 32]),
+		};
+		let encoded_value = outbound_msg.encode();
+		let node_signer = test_utils::TestKeysInterface::new(&[42; 32], Network::Testnet);
+		let inbound_msg = ReadableArgs::read(&mut Cursor::new(&encoded_value[..]), (None, &&node_signer)).unwrap();
+		if let msgs::InboundOnionPayload::Forward { short_channel_id, hold_htlc, .. } = inbound_msg {
+			assert_eq!(short_channel_id, 0xdeadbeef1bad1dea);
+			assert_eq!(hold_htlc, Some([42; 32]));
 		} else { panic!(); }
 	}
 
@@ -4302,10 +4716,11 @@ mod tests {
 			short_channel_id: 0xdeadbeef1bad1dea,
 			amt_to_forward: 1000,
 			outgoing_cltv_value: 0xffffffff,
+			hold_htlc: None,
 		};
 		let mut encoded_payload = Vec::new();
 		let test_bytes = vec![42u8; 1000];
-		if let msgs::OutboundOnionPayload::Forward { short_channel_id, amt_to_forward, outgoing_cltv_value } = payload {
+		if let msgs::OutboundOnionPayload::Forward { short_channel_id, amt_to_forward, outgoing_cltv_value, .. } = payload {
 			_encode_varint_length_prefixed_tlv!(&mut encoded_payload, {
 				(1, test_bytes, required_vec),
 				(2, HighZeroBytesDroppedBigSize(amt_to_forward), required),
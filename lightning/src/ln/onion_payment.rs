@@ -18,6 +18,7 @@ use crate::ln::msgs;
 use crate::ln::onion_utils;
 use crate::ln::onion_utils::{HTLCFailReason, INVALID_ONION_BLINDING};
 use crate::sign::{NodeSigner, Recipient};
+use crate::util::config::ChannelConfig;
 use crate::util::logger::Logger;
 
 use crate::prelude::*;
@@ -73,12 +74,14 @@ pub(super) fn create_fwd_pending_htlc_info(
 	};
 
 	let (
-		short_channel_id, amt_to_forward, outgoing_cltv_value, intro_node_blinding_point
+		short_channel_id, amt_to_forward, outgoing_cltv_value, intro_node_blinding_point, hold_htlc
 	) = match hop_data {
-		msgs::InboundOnionPayload::Forward { short_channel_id, amt_to_forward, outgoing_cltv_value } =>
-			(short_channel_id, amt_to_forward, outgoing_cltv_value, None),
+		msgs::InboundOnionPayload::Forward {
+			short_channel_id, amt_to_forward, outgoing_cltv_value, hold_htlc
+		} => (short_channel_id, amt_to_forward, outgoing_cltv_value, None, hold_htlc),
 		msgs::InboundOnionPayload::BlindedForward {
 			short_channel_id, payment_relay, payment_constraints, intro_node_blinding_point, features,
+			hold_htlc,
 		} => {
 			let (amt_to_forward, outgoing_cltv_value) = check_blinded_forward(
 				msg.amount_msat, msg.cltv_expiry, &payment_relay, &payment_constraints, &features
@@ -91,7 +94,7 @@ pub(super) fn create_fwd_pending_htlc_info(
 					err_data: vec![0; 32],
 				}
 			})?;
-			(short_channel_id, amt_to_forward, outgoing_cltv_value, intro_node_blinding_point)
+			(short_channel_id, amt_to_forward, outgoing_cltv_value, intro_node_blinding_point, hold_htlc)
 		},
 		msgs::InboundOnionPayload::Receive { .. } | msgs::InboundOnionPayload::BlindedReceive { .. } =>
 			return Err(InboundHTLCErr {
@@ -99,6 +102,13 @@ pub(super) fn create_fwd_pending_htlc_info(
 				err_code: 0x4000 | 22,
 				err_data: Vec::new(),
 			}),
+		msgs::InboundOnionPayload::TrampolineEntrypoint { .. } |
+		msgs::InboundOnionPayload::TrampolineForward { .. } =>
+			return Err(InboundHTLCErr {
+				msg: "Trampoline OnionHopData provided for us as a non-trampoline intermediary node",
+				err_code: 0x4000 | 22,
+				err_data: Vec::new(),
+			}),
 	};
 
 	Ok(PendingHTLCInfo {
@@ -112,6 +122,70 @@ pub(super) fn create_fwd_pending_htlc_info(
 						.map(|_| BlindedFailure::FromIntroductionNode)
 						.unwrap_or(BlindedFailure::FromBlindedNode),
 				}),
+			incoming_trampoline_shared_secret: None,
+			hold_htlc,
+		},
+		payment_hash: msg.payment_hash,
+		incoming_shared_secret: shared_secret,
+		incoming_amt_msat: Some(msg.amount_msat),
+		outgoing_amt_msat: amt_to_forward,
+		outgoing_cltv_value,
+		skimmed_fee_msat: None,
+		incoming_endorsed: msg.endorsed == Some(true),
+	})
+}
+
+/// Returns the fee we charge for forwarding `outgoing_amt_msat` as a trampoline node.
+pub(super) fn trampoline_fee_msat(fee_policy: &ChannelConfig, outgoing_amt_msat: u64) -> u64 {
+	(fee_policy.forwarding_fee_base_msat as u64).saturating_add(
+		outgoing_amt_msat.saturating_mul(fee_policy.forwarding_fee_proportional_millionths as u64) / 1_000_000
+	)
+}
+
+/// Returns the error data for a `trampoline_fee_or_expiry_insufficient` failure, i.e. the fee and
+/// CLTV delta we require to forward a trampoline payment.
+pub(super) fn trampoline_fee_or_expiry_insufficient_data(fee_policy: &ChannelConfig) -> Vec<u8> {
+	let mut err_data = Vec::with_capacity(10);
+	err_data.extend_from_slice(&fee_policy.forwarding_fee_base_msat.to_be_bytes());
+	err_data.extend_from_slice(&fee_policy.forwarding_fee_proportional_millionths.to_be_bytes());
+	err_data.extend_from_slice(&fee_policy.cltv_expiry_delta.to_be_bytes());
+	err_data
+}
+
+/// Note that our fee and CLTV delta are only checked once we found a route to the next trampoline,
+/// as they depend on the outgoing channel's config.
+pub(super) fn create_trampoline_fwd_pending_htlc_info(
+	msg: &msgs::UpdateAddHTLC, hop_data: msgs::InboundOnionPayload, hop_hmac: [u8; 32],
+	new_packet_bytes: Vec<u8>, shared_secret: [u8; 32], trampoline_shared_secret: [u8; 32],
+	next_packet_pubkey: Result<PublicKey, secp256k1::Error>,
+) -> Result<PendingHTLCInfo, InboundHTLCErr> {
+	let (next_trampoline, amt_to_forward, outgoing_cltv_value) = match hop_data {
+		msgs::InboundOnionPayload::TrampolineForward {
+			next_trampoline, amt_to_forward, outgoing_cltv_value
+		} => (next_trampoline, amt_to_forward, outgoing_cltv_value),
+		_ => return Err(InboundHTLCErr {
+			msg: "Non-trampoline OnionHopData provided for us as a trampoline node",
+			err_code: 0x4000 | 22,
+			err_data: Vec::new(),
+		}),
+	};
+	let public_key = next_packet_pubkey.map_err(|_| InboundHTLCErr {
+		msg: "Invalid ephemeral pubkey in trampoline onion",
+		err_code: 0x4000 | 22,
+		err_data: Vec::new(),
+	})?;
+
+	Ok(PendingHTLCInfo {
+		routing: PendingHTLCRouting::TrampolineForward {
+			onion_packet: msgs::TrampolineOnionPacket {
+				version: 0,
+				public_key,
+				hop_data: new_packet_bytes,
+				hmac: hop_hmac,
+			},
+			node_id: next_trampoline,
+			incoming_cltv_expiry: msg.cltv_expiry,
+			incoming_trampoline_shared_secret: trampoline_shared_secret,
 		},
 		payment_hash: msg.payment_hash,
 		incoming_shared_secret: shared_secret,
@@ -119,6 +193,7 @@ pub(super) fn create_fwd_pending_htlc_info(
 		outgoing_amt_msat: amt_to_forward,
 		outgoing_cltv_value,
 		skimmed_fee_msat: None,
+		incoming_endorsed: msg.endorsed == Some(true),
 	})
 }
 
@@ -139,7 +214,7 @@ pub(super) fn create_recv_pending_htlc_info(
 			 cltv_expiry_height, payment_metadata, false),
 		msgs::InboundOnionPayload::BlindedReceive {
 			sender_intended_htlc_amt_msat, total_msat, cltv_expiry_height, payment_secret,
-			intro_node_blinding_point, payment_constraints, ..
+			intro_node_blinding_point, payment_constraints, keysend_preimage, ..
 		} => {
 			check_blinded_payment_constraints(
 				sender_intended_htlc_amt_msat, cltv_expiry, &payment_constraints
@@ -151,11 +226,19 @@ pub(super) fn create_recv_pending_htlc_info(
 						msg: "Amount or cltv_expiry violated blinded payment constraints",
 					}
 				})?;
-			let payment_data = msgs::FinalOnionHopData { payment_secret, total_msat };
-			(Some(payment_data), None, Vec::new(), sender_intended_htlc_amt_msat, cltv_expiry_height,
-			 None, intro_node_blinding_point.is_none())
+			// Blinded keysends (i.e., async payments to a static invoice) always carry a payment
+			// secret in the encrypted TLVs, so only treat them as MPP if they are actually split.
+			let payment_data = if keysend_preimage.is_some() && total_msat == sender_intended_htlc_amt_msat {
+				None
+			} else {
+				Some(msgs::FinalOnionHopData { payment_secret, total_msat })
+			};
+			(payment_data, keysend_preimage, Vec::new(), sender_intended_htlc_amt_msat,
+			 cltv_expiry_height, None, intro_node_blinding_point.is_none())
 		}
-		msgs::InboundOnionPayload::Forward { .. } => {
+		msgs::InboundOnionPayload::Forward { .. } |
+		msgs::InboundOnionPayload::TrampolineEntrypoint { .. } |
+		msgs::InboundOnionPayload::TrampolineForward { .. } => {
 			return Err(InboundHTLCErr {
 				err_code: 0x4000|22,
 				err_data: Vec::new(),
@@ -257,6 +340,7 @@ pub(super) fn create_recv_pending_htlc_info(
 		outgoing_amt_msat: onion_amt_msat,
 		outgoing_cltv_value: onion_cltv_expiry,
 		skimmed_fee_msat: counterparty_skimmed_fee_msat,
+		incoming_endorsed: false,
 	})
 }
 
@@ -324,7 +408,16 @@ where
 				received_data, shared_secret, msg.payment_hash, msg.amount_msat, msg.cltv_expiry,
 				None, allow_skimmed_fees, msg.skimmed_fee_msat, cur_height, accept_mpp_keysend,
 			)?
-		}
+		},
+		onion_utils::Hop::TrampolineForward {
+			next_trampoline_hop_data, next_trampoline_hop_hmac, new_trampoline_packet_bytes,
+			next_trampoline_packet_pubkey, trampoline_shared_secret,
+		} => {
+			create_trampoline_fwd_pending_htlc_info(
+				msg, next_trampoline_hop_data, next_trampoline_hop_hmac, new_trampoline_packet_bytes,
+				shared_secret, trampoline_shared_secret, next_trampoline_packet_pubkey,
+			)?
+		},
 	})
 }
 
@@ -418,7 +511,7 @@ where
 	let next_packet_details = match next_hop {
 		onion_utils::Hop::Forward {
 			next_hop_data: msgs::InboundOnionPayload::Forward {
-				short_channel_id, amt_to_forward, outgoing_cltv_value
+				short_channel_id, amt_to_forward, outgoing_cltv_value, ..
 			}, ..
 		} => {
 			let next_packet_pubkey = onion_utils::next_hop_pubkey(secp_ctx,
@@ -449,12 +542,68 @@ where
 				outgoing_cltv_value
 			}
 		},
+		onion_utils::Hop::Receive(msgs::InboundOnionPayload::TrampolineEntrypoint {
+			amt_to_forward, outgoing_cltv_value, ref multipath_trampoline_data, ref trampoline_packet,
+		}) => {
+			if msg.amount_msat < amt_to_forward {
+				return_err!("Upstream node sent less than the sender intended for the trampoline", 19,
+					&msg.amount_msat.to_be_bytes());
+			}
+			if msg.cltv_expiry < outgoing_cltv_value {
+				return_err!("Upstream node set CLTV to less than the CLTV set by the sender for the trampoline",
+					18, &msg.cltv_expiry.to_be_bytes());
+			}
+			if multipath_trampoline_data.as_ref().map_or(false, |data| data.total_msat != amt_to_forward) {
+				return_err!("Multi-part trampoline payments are not supported", 0x2000 | 25, &[0; 0]);
+			}
+			if trampoline_packet.version != 0 {
+				return_err!("Unknown trampoline onion packet version", 0x4000 | 22, &[0; 0]);
+			}
+			let trampoline_shared_secret = node_signer.ecdh(
+				Recipient::Node, &trampoline_packet.public_key, None
+			).unwrap().secret_bytes();
+			return match onion_utils::decode_next_trampoline_hop(
+				trampoline_shared_secret, &trampoline_packet.hop_data[..], trampoline_packet.hmac,
+				msg.payment_hash, node_signer
+			) {
+				Ok((next_trampoline_hop_data @ msgs::InboundOnionPayload::TrampolineForward { .. },
+					Some((next_trampoline_hop_hmac, new_trampoline_packet_bytes)))) =>
+				{
+					let next_trampoline_packet_pubkey = onion_utils::next_hop_pubkey(secp_ctx,
+						trampoline_packet.public_key, &trampoline_shared_secret);
+					Ok((onion_utils::Hop::TrampolineForward {
+						next_trampoline_hop_data, next_trampoline_hop_hmac, new_trampoline_packet_bytes,
+						next_trampoline_packet_pubkey, trampoline_shared_secret,
+					}, shared_secret, None))
+				},
+				Ok((received_data @ msgs::InboundOnionPayload::Receive { .. }, None)) =>
+					Ok((onion_utils::Hop::Receive(received_data), shared_secret, None)),
+				Ok(_) => {
+					return_err!("Invalid trampoline OnionHopData provided for us", 0x4000 | 22, &[0; 0]);
+				},
+				Err(onion_utils::OnionDecodeErr::Malformed { err_msg, .. }) |
+				Err(onion_utils::OnionDecodeErr::Relay { err_msg, .. }) => {
+					return_err!(err_msg, 0x4000 | 22, &[0; 0]);
+				},
+			};
+		},
+		onion_utils::Hop::Receive(msgs::InboundOnionPayload::TrampolineForward { .. }) => {
+			return_err!("Trampoline OnionHopData provided for us outside of a trampoline onion", 0x4000 | 22, &[0; 0]);
+		},
 		onion_utils::Hop::Receive { .. } => return Ok((next_hop, shared_secret, None)),
+		onion_utils::Hop::TrampolineForward { .. } => {
+			debug_assert!(false, "Trampoline forwards are only returned for trampoline onions");
+			return_err!("Trampoline onion provided for us outside of a trampoline entrypoint", 0x4000 | 22, &[0; 0]);
+		},
 		onion_utils::Hop::Forward { next_hop_data: msgs::InboundOnionPayload::Receive { .. }, .. } |
-			onion_utils::Hop::Forward { next_hop_data: msgs::InboundOnionPayload::BlindedReceive { .. }, .. } =>
+			onion_utils::Hop::Forward { next_hop_data: msgs::InboundOnionPayload::BlindedReceive { .. }, .. } |
+			onion_utils::Hop::Forward { next_hop_data: msgs::InboundOnionPayload::TrampolineEntrypoint { .. }, .. } =>
 		{
 			return_err!("Final Node OnionHopData provided for us as an intermediary node", 0x4000 | 22, &[0; 0]);
-		}
+		},
+		onion_utils::Hop::Forward { next_hop_data: msgs::InboundOnionPayload::TrampolineForward { .. }, .. } => {
+			return_err!("Trampoline OnionHopData provided for us outside of a trampoline onion", 0x4000 | 22, &[0; 0]);
+		},
 	};
 
 	Ok((next_hop, shared_secret, Some(next_packet_details)))
@@ -528,7 +677,7 @@ mod tests {
 		// Ensure the onion will not fit all the payloads by adding a large custom TLV.
 		recipient_onion.custom_tlvs.push((13377331, vec![0; 1156]));
 
-		let path = Path { hops, blinded_tail: None, };
+		let path = Path { hops, blinded_tail: None, trampoline_hops: vec![] };
 		let onion_keys = super::onion_utils::construct_onion_keys(&secp_ctx, &path, &session_priv).unwrap();
 		let (onion_payloads, ..) = super::onion_utils::build_onion_payloads(
 			&path, total_amt_msat, recipient_onion, cur_height + 1, &Some(keysend_preimage)
@@ -555,11 +704,12 @@ mod tests {
 		let path = Path {
 			hops: hops,
 			blinded_tail: None,
+			trampoline_hops: vec![],
 		};
 
 		let (onion, amount_msat, cltv_expiry) = create_payment_onion(
 			&secp_ctx, &path, &session_priv, total_amt_msat, recipient_onion, cur_height,
-			&payment_hash, &Some(preimage), prng_seed
+			&payment_hash, &Some(preimage), None, prng_seed
 		).unwrap();
 
 		let msg = make_update_add_msg(amount_msat, cltv_expiry, payment_hash, onion);
@@ -605,6 +755,7 @@ mod tests {
 			onion_routing_packet,
 			skimmed_fee_msat: None,
 			blinding_point: None,
+			endorsed: None,
 		}
 	}
 
@@ -12,7 +12,7 @@ use crate::ln::channelmanager::{HTLCSource, RecipientOnionFields};
 use crate::ln::msgs;
 use crate::ln::wire::Encode;
 use crate::routing::gossip::NetworkUpdate;
-use crate::routing::router::{BlindedTail, Path, RouteHop};
+use crate::routing::router::{BlindedTail, Path, RouteHop, TrampolineHop};
 use crate::sign::NodeSigner;
 use crate::crypto::chacha20::ChaCha20;
 use crate::crypto::streams::ChaChaReader;
@@ -110,21 +110,35 @@ pub(crate) fn next_hop_pubkey<T: secp256k1::Verification>(
 // can only fail if an intermediary hop has an invalid public key or session_priv is invalid
 #[inline]
 pub(super) fn construct_onion_keys_callback<T, FType>(
-	secp_ctx: &Secp256k1<T>, path: &Path, session_priv: &SecretKey, mut callback: FType
+	secp_ctx: &Secp256k1<T>, path: &Path, session_priv: &SecretKey, callback: FType
 ) -> Result<(), secp256k1::Error>
 where
 	T: secp256k1::Signing,
 	FType: FnMut(SharedSecret, [u8; 32], PublicKey, Option<&RouteHop>, usize)
 {
-	let mut blinded_priv = session_priv.clone();
-	let mut blinded_pub = PublicKey::from_secret_key(secp_ctx, &blinded_priv);
-
 	let unblinded_hops_iter = path.hops.iter().map(|h| (&h.pubkey, Some(h)));
 	let blinded_pks_iter = path.blinded_tail.as_ref()
 		.map(|t| t.hops.iter()).unwrap_or([].iter())
 		.skip(1) // Skip the intro node because it's included in the unblinded hops
 		.map(|h| (&h.blinded_node_id, None));
-	for (idx, (pubkey, route_hop_opt)) in unblinded_hops_iter.chain(blinded_pks_iter).enumerate() {
+	construct_onion_keys_generic_callback(
+		secp_ctx, unblinded_hops_iter.chain(blinded_pks_iter), session_priv, callback
+	)
+}
+
+#[inline]
+fn construct_onion_keys_generic_callback<'a, T, H, HopData, FType>(
+	secp_ctx: &Secp256k1<T>, hops: H, session_priv: &SecretKey, mut callback: FType
+) -> Result<(), secp256k1::Error>
+where
+	T: secp256k1::Signing,
+	H: Iterator<Item = (&'a PublicKey, Option<HopData>)>,
+	FType: FnMut(SharedSecret, [u8; 32], PublicKey, Option<HopData>, usize)
+{
+	let mut blinded_priv = session_priv.clone();
+	let mut blinded_pub = PublicKey::from_secret_key(secp_ctx, &blinded_priv);
+
+	for (idx, (pubkey, route_hop_opt)) in hops.enumerate() {
 		let shared_secret = SharedSecret::new(pubkey, &blinded_priv);
 
 		let mut sha = Sha256::engine();
@@ -166,8 +180,104 @@ pub(super) fn construct_onion_keys<T: secp256k1::Signing>(secp_ctx: &Secp256k1<T
 	Ok(res)
 }
 
+// can only fail if a trampoline hop has an invalid public key or session_priv is invalid
+fn construct_trampoline_onion_keys<T: secp256k1::Signing>(
+	secp_ctx: &Secp256k1<T>, trampoline_hops: &[TrampolineHop], session_priv: &SecretKey
+) -> Result<Vec<OnionKeys>, secp256k1::Error> {
+	let mut res = Vec::with_capacity(trampoline_hops.len());
+
+	let hops_iter = trampoline_hops.iter().map(|h| (&h.pubkey, Some(h)));
+	construct_onion_keys_generic_callback(secp_ctx, hops_iter, session_priv,
+		|shared_secret, _blinding_factor, ephemeral_pubkey, _, _|
+	{
+		let (rho, mu) = gen_rho_mu_from_shared_secret(shared_secret.as_ref());
+
+		res.push(OnionKeys {
+			#[cfg(test)]
+			shared_secret,
+			#[cfg(test)]
+			blinding_factor: _blinding_factor,
+			ephemeral_pubkey,
+			rho,
+			mu,
+		});
+	})?;
+
+	Ok(res)
+}
+
+/// Derives the session key for a path's trampoline onion from the session key of its outer onion,
+/// so that we don't have to track it separately.
+fn derive_trampoline_session_priv(session_priv: &SecretKey) -> SecretKey {
+	let mut sha = Sha256::engine();
+	sha.input(b"trampoline_session_priv");
+	sha.input(&session_priv.secret_bytes());
+	SecretKey::from_slice(&Sha256::from_engine(sha).to_byte_array())
+		.expect("SHA-256 output is a valid secret key with overwhelming probability")
+}
+
+/// Returns the trampoline hop data, as well as the value_msat and CLTV value the first trampoline
+/// hop should receive.
+fn build_trampoline_onion_payloads(
+	trampoline_hops: &[TrampolineHop], total_msat: u64, mut recipient_onion: RecipientOnionFields,
+	starting_htlc_offset: u32, keysend_preimage: &Option<PaymentPreimage>
+) -> Result<(Vec<msgs::OutboundOnionPayload>, u64, u32), APIError> {
+	let mut cur_value_msat = 0u64;
+	let mut cur_cltv = starting_htlc_offset;
+	let mut next_trampoline = None;
+	let mut res: Vec<msgs::OutboundOnionPayload> = Vec::with_capacity(trampoline_hops.len());
+
+	for hop in trampoline_hops.iter().rev() {
+		match next_trampoline {
+			None => {
+				cur_cltv += hop.cltv_expiry_delta;
+				res.push(msgs::OutboundOnionPayload::Receive {
+					payment_data: recipient_onion.payment_secret.take().map(|payment_secret| {
+						msgs::FinalOnionHopData { payment_secret, total_msat }
+					}),
+					payment_metadata: recipient_onion.payment_metadata.take(),
+					keysend_preimage: *keysend_preimage,
+					custom_tlvs: recipient_onion.custom_tlvs.clone(),
+					sender_intended_htlc_amt_msat: hop.fee_msat,
+					cltv_expiry_height: cur_cltv,
+				});
+			},
+			Some(next_trampoline) => {
+				res.insert(0, msgs::OutboundOnionPayload::TrampolineForward {
+					next_trampoline,
+					amt_to_forward: cur_value_msat,
+					outgoing_cltv_value: cur_cltv,
+				});
+				cur_cltv += hop.cltv_expiry_delta;
+			},
+		}
+		cur_value_msat += hop.fee_msat;
+		if cur_value_msat >= 21000000 * 100000000 * 1000 {
+			return Err(APIError::InvalidRoute{err: "Trampoline fees overflowed?".to_owned()});
+		}
+		if cur_cltv >= 500000000 {
+			return Err(APIError::InvalidRoute{err: "Trampoline CLTV overflowed?".to_owned()});
+		}
+		next_trampoline = Some(hop.pubkey);
+	}
+	Ok((res, cur_value_msat, cur_cltv))
+}
+
 /// returns the hop data, as well as the first-hop value_msat and CLTV value we should send.
-pub(super) fn build_onion_payloads(path: &Path, total_msat: u64, mut recipient_onion: RecipientOnionFields, starting_htlc_offset: u32, keysend_preimage: &Option<PaymentPreimage>) -> Result<(Vec<msgs::OutboundOnionPayload>, u64, u32), APIError> {
+pub(super) fn build_onion_payloads(path: &Path, total_msat: u64, recipient_onion: RecipientOnionFields, starting_htlc_offset: u32, keysend_preimage: &Option<PaymentPreimage>) -> Result<(Vec<msgs::OutboundOnionPayload>, u64, u32), APIError> {
+	build_onion_payloads_with_trampoline(
+		path, total_msat, recipient_onion, starting_htlc_offset, keysend_preimage, None
+	)
+}
+
+fn build_onion_payloads_with_trampoline(
+	path: &Path, total_msat: u64, mut recipient_onion: RecipientOnionFields,
+	starting_htlc_offset: u32, keysend_preimage: &Option<PaymentPreimage>,
+	mut trampoline_packet: Option<msgs::TrampolineOnionPacket>
+) -> Result<(Vec<msgs::OutboundOnionPayload>, u64, u32), APIError> {
+	if path.trampoline_hops.is_empty() != trampoline_packet.is_none() {
+		return Err(APIError::InvalidRoute{err: "Trampoline paths require a trampoline onion".to_owned()});
+	}
 	let mut cur_value_msat = 0u64;
 	let mut cur_cltv = starting_htlc_offset;
 	let mut last_short_channel_id = 0;
@@ -195,14 +305,25 @@ pub(super) fn build_onion_payloads(path: &Path, total_msat: u64, mut recipient_o
 							cltv_expiry_height: cur_cltv + excess_final_cltv_expiry_delta,
 							encrypted_tlvs: blinded_hop.encrypted_payload.clone(),
 							intro_node_blinding_point: blinding_point.take(),
+							keysend_preimage: *keysend_preimage,
 						});
 					} else {
 						res.push(msgs::OutboundOnionPayload::BlindedForward {
 							encrypted_tlvs: blinded_hop.encrypted_payload.clone(),
 							intro_node_blinding_point: blinding_point.take(),
+							hold_htlc: None,
 						});
 					}
 				}
+			} else if let Some(trampoline_packet) = trampoline_packet.take() {
+				let final_value_msat = path.final_value_msat();
+				cur_value_msat += final_value_msat;
+				res.push(msgs::OutboundOnionPayload::TrampolineEntrypoint {
+					amt_to_forward: final_value_msat + hop.fee_msat,
+					outgoing_cltv_value: cltv,
+					multipath_trampoline_data: None,
+					trampoline_packet,
+				});
 			} else {
 				res.push(msgs::OutboundOnionPayload::Receive {
 					payment_data: if let Some(secret) = recipient_onion.payment_secret.take() {
@@ -223,6 +344,7 @@ pub(super) fn build_onion_payloads(path: &Path, total_msat: u64, mut recipient_o
 				short_channel_id: last_short_channel_id,
 				amt_to_forward: value_msat,
 				outgoing_cltv_value: cltv,
+				hold_htlc: None,
 			});
 		}
 		cur_value_msat += hop.fee_msat;
@@ -242,6 +364,10 @@ pub(super) fn build_onion_payloads(path: &Path, total_msat: u64, mut recipient_o
 /// the hops can be of variable length.
 pub(crate) const ONION_DATA_LEN: usize = 20*65;
 
+/// Length of the trampoline onion data packet, which is carried within the outer onion's payload
+/// for the first trampoline hop and thus limits the space left for the hops leading up to it.
+pub(crate) const TRAMPOLINE_ONION_DATA_LEN: usize = 650;
+
 pub(super) const INVALID_ONION_BLINDING: u16 = 0x8000 | 0x4000 | 24;
 
 #[inline]
@@ -267,6 +393,19 @@ pub(super) fn construct_onion_packet(
 		payloads, onion_keys, FixedSizeOnionPacket(packet_data), Some(associated_data))
 }
 
+fn construct_trampoline_onion_packet(
+	payloads: Vec<msgs::OutboundOnionPayload>, onion_keys: Vec<OnionKeys>, prng_seed: [u8; 32],
+	associated_data: &PaymentHash
+) -> Result<msgs::TrampolineOnionPacket, ()> {
+	let mut packet_data = vec![0; TRAMPOLINE_ONION_DATA_LEN];
+
+	let mut chacha = ChaCha20::new(&prng_seed, &[0; 8]);
+	chacha.process_in_place(&mut packet_data);
+
+	construct_onion_packet_with_init_noise::<_, _>(
+		payloads, onion_keys, packet_data, Some(associated_data))
+}
+
 #[cfg(test)]
 /// Used in testing to write bogus `BogusOnionHopData` as well as `RawOnionHopData`, which is
 /// otherwise not representable in `msgs::OnionHopData`.
@@ -447,6 +586,13 @@ fn decrypt_onion_error_packet(
 	msgs::DecodedOnionErrorPacket::read(&mut Cursor::new(packet))
 }
 
+fn is_valid_error_packet_hmac(shared_secret: &[u8], packet: &msgs::DecodedOnionErrorPacket) -> bool {
+	let um = gen_um_from_shared_secret(shared_secret);
+	let mut hmac = HmacEngine::<Sha256>::new(&um);
+	hmac.input(&packet.encode()[32..]);
+	fixed_time_eq(&Hmac::from_engine(hmac).to_byte_array(), &packet.hmac)
+}
+
 /// Process failure we got back from upstream on a payment we sent (implying htlc_source is an
 /// OutboundRoute).
 #[inline]
@@ -500,9 +646,14 @@ pub(super) fn process_onion_failure<T: secp256k1::Signing, L: Deref>(
 		// The failing hop includes either the inbound channel to the recipient or the outbound channel
 		// from the current hop (i.e., the next hop's inbound channel).
 		let num_blinded_hops = path.blinded_tail.as_ref().map_or(0, |bt| bt.hops.len());
+		// Errors from the last hop of a trampoline path come from the first trampoline node, which
+		// has already handled any failures from the route it found beyond itself.
+		let is_from_trampoline_node =
+			route_hop_idx + 1 == path.hops.len() && !path.trampoline_hops.is_empty();
 		// For 1-hop blinded paths, the final `path.hops` entry is the recipient.
-		is_from_final_node = route_hop_idx + 1 == path.hops.len() && num_blinded_hops <= 1;
-		let failing_route_hop = if is_from_final_node { route_hop } else {
+		is_from_final_node = route_hop_idx + 1 == path.hops.len() && num_blinded_hops <= 1 &&
+			!is_from_trampoline_node;
+		let failing_route_hop = if is_from_final_node || is_from_trampoline_node { route_hop } else {
 			match path.hops.get(route_hop_idx + 1) {
 				Some(hop) => hop,
 				None => {
@@ -535,14 +686,21 @@ pub(super) fn process_onion_failure<T: secp256k1::Signing, L: Deref>(
 		htlc_msat = amt_to_forward;
 
 		let err_packet = match decrypt_onion_error_packet(&mut encrypted_packet, shared_secret) {
-			Ok(p) => p,
-			Err(_) => return
+			Ok(p) if is_valid_error_packet_hmac(shared_secret.as_ref(), &p) => p,
+			_ if is_from_trampoline_node => {
+				// Failures of the trampoline node are additionally encrypted with the shared secret of
+				// the trampoline onion we built for it.
+				let trampoline_session_priv = derive_trampoline_session_priv(session_priv);
+				let trampoline_shared_secret = SharedSecret::new(
+					&path.trampoline_hops[0].pubkey, &trampoline_session_priv
+				);
+				match decrypt_onion_error_packet(&mut encrypted_packet, trampoline_shared_secret) {
+					Ok(p) if is_valid_error_packet_hmac(trampoline_shared_secret.as_ref(), &p) => p,
+					_ => return
+				}
+			},
+			_ => return
 		};
-		let um = gen_um_from_shared_secret(shared_secret.as_ref());
-		let mut hmac = HmacEngine::<Sha256>::new(&um);
-		hmac.input(&err_packet.encode()[32..]);
-
-		if !fixed_time_eq(&Hmac::from_engine(hmac).to_byte_array(), &err_packet.hmac) { return }
 		let error_code_slice = match err_packet.failuremsg.get(0..2) {
 			Some(s) => s,
 			None => {
@@ -567,6 +725,20 @@ pub(super) fn process_onion_failure<T: secp256k1::Signing, L: Deref>(
 
 		let (debug_field, debug_field_size) = errors::get_onion_debug_field(error_code);
 
+		if is_from_trampoline_node {
+			// We can't attribute the failure to any channel or node beyond the trampoline node, and
+			// the trampoline node itself forwarded the payment just fine, so only give up on the
+			// payment if the trampoline node indicated it can never succeed.
+			res = Some(FailureLearnings {
+				network_update: None, short_channel_id: None,
+				payment_failed_permanently: error_code & PERM == PERM,
+				failed_within_blinded_path: false
+			});
+			let (description, title) = errors::get_onion_error_description(error_code);
+			log_info!(logger, "Onion Error[from trampoline {}: {}({:#x})] {}", route_hop.pubkey, title, error_code, description);
+			return;
+		}
+
 		// indicate that payment parameter has failed and no need to update Route object
 		let payment_failed = match error_code & 0xff {
 			15|16|17|18|19|23 => true,
@@ -831,6 +1003,9 @@ impl HTLCFailReason {
 		else if failure_code == 21 { debug_assert!(data.is_empty()) }
 		else if failure_code == 22 | PERM { debug_assert!(data.len() <= 11) }
 		else if failure_code == 23 { debug_assert!(data.is_empty()) }
+		else if failure_code == 25 | NODE { debug_assert!(data.is_empty()) }
+		else if failure_code == 26 | NODE { debug_assert_eq!(data.len(), 10) }
+		else if failure_code == 27 | PERM { debug_assert!(data.is_empty()) }
 		else if failure_code & BADONION != 0 {
 			// We set some bogus BADONION failure codes in test, so ignore unknown ones.
 		}
@@ -847,14 +1022,17 @@ impl HTLCFailReason {
 		Self(HTLCFailReasonRepr::LightningError { err: msg.reason.clone() })
 	}
 
-	pub(super) fn get_encrypted_failure_packet(&self, incoming_packet_shared_secret: &[u8; 32], phantom_shared_secret: &Option<[u8; 32]>)
+	/// Encrypts the failure to the sender. If an `inner_shared_secret` of a phantom or trampoline
+	/// onion is given, the failure is built and encrypted with it before being encrypted with the
+	/// outer onion's `incoming_packet_shared_secret`.
+	pub(super) fn get_encrypted_failure_packet(&self, incoming_packet_shared_secret: &[u8; 32], inner_shared_secret: &Option<[u8; 32]>)
 	-> msgs::OnionErrorPacket {
 		match self.0 {
 			HTLCFailReasonRepr::Reason { ref failure_code, ref data } => {
-				if let Some(phantom_ss) = phantom_shared_secret {
-					let phantom_packet = build_failure_packet(phantom_ss, *failure_code, &data[..]).encode();
-					let encrypted_phantom_packet = encrypt_failure_packet(phantom_ss, &phantom_packet);
-					encrypt_failure_packet(incoming_packet_shared_secret, &encrypted_phantom_packet.data[..])
+				if let Some(inner_ss) = inner_shared_secret {
+					let inner_packet = build_failure_packet(inner_ss, *failure_code, &data[..]).encode();
+					let encrypted_inner_packet = encrypt_failure_packet(inner_ss, &inner_packet);
+					encrypt_failure_packet(incoming_packet_shared_secret, &encrypted_inner_packet.data[..])
 				} else {
 					let packet = build_failure_packet(incoming_packet_shared_secret, *failure_code, &data[..]).encode();
 					encrypt_failure_packet(incoming_packet_shared_secret, &packet)
@@ -866,6 +1044,30 @@ impl HTLCFailReason {
 		}
 	}
 
+	/// Encrypts the failure of an HTLC we forwarded as a trampoline node to the sender, using the
+	/// shared secret of the trampoline onion we received as well as that of the outer onion.
+	///
+	/// Failures we received from downstream are encrypted to us rather than the sender, and those
+	/// of the route we found don't concern the sender, so both are replaced with a
+	/// `temporary_trampoline_failure`.
+	pub(super) fn get_encrypted_trampoline_failure_packet(
+		&self, incoming_packet_shared_secret: &[u8; 32], trampoline_shared_secret: &[u8; 32]
+	) -> msgs::OnionErrorPacket {
+		match self.0 {
+			// `invalid_onion_payload`, `temporary_trampoline_failure`,
+			// `trampoline_fee_or_expiry_insufficient` and `unknown_next_trampoline`.
+			HTLCFailReasonRepr::Reason { failure_code, .. }
+				if [0x4000 | 22, 0x2000 | 25, 0x2000 | 26, 0x4000 | 27].contains(&failure_code) =>
+			{
+				self.get_encrypted_failure_packet(incoming_packet_shared_secret, &Some(*trampoline_shared_secret))
+			},
+			HTLCFailReasonRepr::Reason { .. } | HTLCFailReasonRepr::LightningError { .. } => {
+				HTLCFailReason::from_failure_code(0x2000 | 25)
+					.get_encrypted_failure_packet(incoming_packet_shared_secret, &Some(*trampoline_shared_secret))
+			},
+		}
+	}
+
 	pub(super) fn decode_onion_failure<T: secp256k1::Signing, L: Deref>(
 		&self, secp_ctx: &Secp256k1<T>, logger: &L, htlc_source: &HTLCSource
 	) -> DecodedOnionFailure where L::Target: Logger {
@@ -929,6 +1131,20 @@ pub(crate) enum Hop {
 		/// Bytes of the onion packet we're forwarding.
 		new_packet_bytes: [u8; ONION_DATA_LEN],
 	},
+	/// This onion payload was for us as a trampoline node, and the payment needs to be forwarded
+	/// to the next trampoline node along a route we find ourselves.
+	TrampolineForward {
+		/// Trampoline onion payload data used in forwarding the payment.
+		next_trampoline_hop_data: msgs::InboundOnionPayload,
+		/// HMAC of the next trampoline hop's onion packet.
+		next_trampoline_hop_hmac: [u8; 32],
+		/// Bytes of the trampoline onion packet we're forwarding.
+		new_trampoline_packet_bytes: Vec<u8>,
+		/// Ephemeral public key of the trampoline onion packet we're forwarding.
+		next_trampoline_packet_pubkey: Result<PublicKey, secp256k1::Error>,
+		/// Shared secret of the trampoline onion we received, used to encrypt any failures.
+		trampoline_shared_secret: [u8; 32],
+	},
 }
 
 /// Error returned when we fail to decode the onion packet.
@@ -965,20 +1181,119 @@ pub(crate) fn decode_next_payment_hop<NS: Deref>(
 	}
 }
 
+/// Decodes the next hop of a trampoline onion, returning the next trampoline packet bytes if we
+/// are not its final recipient.
+pub(crate) fn decode_next_trampoline_hop<NS: Deref>(
+	shared_secret: [u8; 32], hop_data: &[u8], hmac_bytes: [u8; 32], payment_hash: PaymentHash,
+	node_signer: &NS,
+) -> Result<(msgs::InboundOnionPayload, Option<([u8; 32], Vec<u8>)>), OnionDecodeErr>
+where NS::Target: NodeSigner {
+	decode_next_hop(shared_secret, hop_data, hmac_bytes, Some(payment_hash), (None, node_signer))
+}
+
 /// Build a payment onion, returning the first hop msat and cltv values as well.
 /// `cur_block_height` should be set to the best known block height + 1.
+///
+/// If `hold_htlc_at_next_hop` is set, the first hop of the path is asked to hold the HTLC until it
+/// receives a `ReleaseHeldHtlc` onion message carrying the given secret.
 pub fn create_payment_onion<T: secp256k1::Signing>(
 	secp_ctx: &Secp256k1<T>, path: &Path, session_priv: &SecretKey, total_msat: u64,
 	recipient_onion: RecipientOnionFields, cur_block_height: u32, payment_hash: &PaymentHash,
-	keysend_preimage: &Option<PaymentPreimage>, prng_seed: [u8; 32]
+	keysend_preimage: &Option<PaymentPreimage>, hold_htlc_at_next_hop: Option<[u8; 32]>,
+	prng_seed: [u8; 32]
 ) -> Result<(msgs::OnionPacket, u64, u32), APIError> {
 	let onion_keys = construct_onion_keys(&secp_ctx, &path, &session_priv)
 		.map_err(|_| APIError::InvalidRoute{
 			err: "Pubkey along hop was maliciously selected".to_owned()
 		})?;
-	let (onion_payloads, htlc_msat, htlc_cltv) = build_onion_payloads(
-		&path, total_msat, recipient_onion, cur_block_height, keysend_preimage
+	let trampoline_packet = if path.trampoline_hops.is_empty() { None } else {
+		if path.blinded_tail.is_some() {
+			return Err(APIError::InvalidRoute{
+				err: "Trampoline paths cannot terminate in a blinded path".to_owned()
+			});
+		}
+		if path.hops.last().map(|hop| hop.pubkey) != path.trampoline_hops.first().map(|hop| hop.pubkey) {
+			return Err(APIError::InvalidRoute{
+				err: "The first trampoline hop must be the last hop of the path".to_owned()
+			});
+		}
+		let trampoline_session_priv = derive_trampoline_session_priv(session_priv);
+		let trampoline_onion_keys = construct_trampoline_onion_keys(
+			&secp_ctx, &path.trampoline_hops, &trampoline_session_priv
+		).map_err(|_| APIError::InvalidRoute{
+			err: "Pubkey along trampoline hop was maliciously selected".to_owned()
+		})?;
+		let (trampoline_payloads, trampoline_msat, trampoline_cltv) = build_trampoline_onion_payloads(
+			&path.trampoline_hops, total_msat, recipient_onion.clone(), cur_block_height,
+			keysend_preimage
+		)?;
+		let last_hop = path.hops.last().expect("Paths are never empty");
+		if trampoline_msat != path.final_value_msat() + last_hop.fee_msat ||
+			trampoline_cltv != cur_block_height + last_hop.cltv_expiry_delta
+		{
+			return Err(APIError::InvalidRoute{
+				err: "The fees or CLTV delta of the last hop do not match its trampoline hops".to_owned()
+			});
+		}
+		let trampoline_prng_seed = Sha256::hash(&prng_seed).to_byte_array();
+		Some(construct_trampoline_onion_packet(
+			trampoline_payloads, trampoline_onion_keys, trampoline_prng_seed, payment_hash
+		).map_err(|_| APIError::InvalidRoute{
+			err: "Trampoline route size too large considering trampoline onion data".to_owned()
+		})?)
+	};
+	let (mut onion_payloads, htlc_msat, htlc_cltv) = build_onion_payloads_with_trampoline(
+		&path, total_msat, recipient_onion, cur_block_height, keysend_preimage, trampoline_packet
+	)?;
+	if let Some(release_secret) = hold_htlc_at_next_hop {
+		match onion_payloads.first_mut() {
+			Some(msgs::OutboundOnionPayload::Forward { hold_htlc, .. }) |
+			Some(msgs::OutboundOnionPayload::BlindedForward { hold_htlc, .. }) => {
+				*hold_htlc = Some(release_secret);
+			},
+			_ => return Err(APIError::InvalidRoute{
+				err: "Only an intermediate hop can hold an HTLC".to_owned()
+			}),
+		}
+	}
+	let onion_packet = construct_onion_packet(onion_payloads, onion_keys, prng_seed, payment_hash)
+		.map_err(|_| APIError::InvalidRoute{
+			err: "Route size too large considering onion data".to_owned()
+		})?;
+	Ok((onion_packet, htlc_msat, htlc_cltv))
+}
+
+/// Build a payment onion for a path we found as a trampoline node, delivering the given trampoline
+/// onion to the last hop of the path (the next trampoline node or the recipient).
+pub(crate) fn create_trampoline_forward_onion<T: secp256k1::Signing>(
+	secp_ctx: &Secp256k1<T>, path: &Path, session_priv: &SecretKey,
+	trampoline_packet: msgs::TrampolineOnionPacket, cur_block_height: u32,
+	payment_hash: &PaymentHash, prng_seed: [u8; 32]
+) -> Result<(msgs::OnionPacket, u64, u32), APIError> {
+	if path.blinded_tail.is_some() || !path.trampoline_hops.is_empty() {
+		return Err(APIError::InvalidRoute{
+			err: "Routes between trampoline nodes must be unblinded, non-trampoline paths".to_owned()
+		});
+	}
+	let onion_keys = construct_onion_keys(&secp_ctx, &path, &session_priv)
+		.map_err(|_| APIError::InvalidRoute{
+			err: "Pubkey along hop was maliciously selected".to_owned()
+		})?;
+	let (mut onion_payloads, htlc_msat, htlc_cltv) = build_onion_payloads(
+		&path, path.final_value_msat(), RecipientOnionFields::spontaneous_empty(), cur_block_height,
+		&None
 	)?;
+	match onion_payloads.pop() {
+		Some(msgs::OutboundOnionPayload::Receive { sender_intended_htlc_amt_msat, cltv_expiry_height, .. }) => {
+			onion_payloads.push(msgs::OutboundOnionPayload::TrampolineEntrypoint {
+				amt_to_forward: sender_intended_htlc_amt_msat,
+				outgoing_cltv_value: cltv_expiry_height,
+				multipath_trampoline_data: None,
+				trampoline_packet,
+			});
+		},
+		_ => debug_assert!(false, "Unblinded paths always end in a Receive payload"),
+	}
 	let onion_packet = construct_onion_packet(onion_payloads, onion_keys, prng_seed, payment_hash)
 		.map_err(|_| APIError::InvalidRoute{
 			err: "Route size too large considering onion data".to_owned()
@@ -1120,7 +1435,7 @@ mod tests {
 						channel_features: ChannelFeatures::empty(), node_features: NodeFeatures::empty(),
 						short_channel_id: 0, fee_msat: 0, cltv_expiry_delta: 0, maybe_announced_channel: true, // We fill in the payloads manually instead of generating them from RouteHops.
 					},
-			], blinded_tail: None }],
+			], blinded_tail: None, trampoline_hops: vec![] }],
 			route_params: None,
 		};
 
@@ -1175,6 +1490,7 @@ mod tests {
 				short_channel_id: 1,
 				amt_to_forward: 15000,
 				outgoing_cltv_value: 1500,
+				hold_htlc: None,
 			}),
 			/*
 			The second payload is represented by raw hex as it contains custom type data. Content:
@@ -1198,11 +1514,13 @@ mod tests {
 				short_channel_id: 3,
 				amt_to_forward: 12500,
 				outgoing_cltv_value: 1250,
+				hold_htlc: None,
 			}),
 			RawOnionHopData::new(msgs::OutboundOnionPayload::Forward {
 				short_channel_id: 4,
 				amt_to_forward: 10000,
 				outgoing_cltv_value: 1000,
+				hold_htlc: None,
 			}),
 			/*
 			The fifth payload is represented by raw hex as it contains custom type data. Content:
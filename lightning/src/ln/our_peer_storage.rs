@@ -0,0 +1,165 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! The backup of our channel state which we hand to our peers via [`msgs::PeerStorage`] messages
+//! and which they return to us via [`msgs::PeerStorageRetrieval`] upon reconnection.
+//!
+//! The backup only contains a compact summary of each of our [`ChannelMonitor`]s, which is
+//! sufficient to detect that we've lost channel state and identify the affected channels, but not
+//! to recover any funds on its own.
+//!
+//! [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+
+use bitcoin::secp256k1::PublicKey;
+
+use crate::chain::transaction::OutPoint;
+use crate::crypto::chacha20poly1305rfc::ChaCha20Poly1305RFC;
+use crate::ln::msgs::{self, DecodeError};
+use crate::sign::PeerStorageKey;
+use crate::util::ser::{Readable, Writeable};
+
+use crate::prelude::*;
+use core::cmp;
+
+// Our `ChaCha20Poly1305RFC` implementation requires the first four bytes of the nonce to be zero,
+// so we only include the remaining eight in the backup.
+const NONCE_LEN: usize = 8;
+const TAG_LEN: usize = 16;
+
+fn nonce_from_bytes(bytes: &[u8]) -> [u8; 12] {
+	let mut nonce = [0; 12];
+	nonce[4..].copy_from_slice(&bytes[..NONCE_LEN]);
+	nonce
+}
+
+/// A summary of a single [`ChannelMonitor`], as included in [`OurPeerStorage`].
+///
+/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerStorageMonitorHolder {
+	/// The `node_id` of the counterparty of the channel, if known.
+	pub counterparty_node_id: Option<PublicKey>,
+	/// The outpoint of the channel's original funding transaction, identifying the channel.
+	pub funding_outpoint: OutPoint,
+	/// The identifier used to derive the channel's signer via [`SignerProvider::derive_channel_signer`].
+	///
+	/// [`SignerProvider::derive_channel_signer`]: crate::sign::SignerProvider::derive_channel_signer
+	pub channel_keys_id: [u8; 32],
+	/// The number of our latest commitment transaction. As commitment numbers count down, a lower
+	/// value indicates a more recent state.
+	pub latest_commitment_number: u64,
+}
+
+impl_writeable_tlv_based!(PeerStorageMonitorHolder, {
+	(0, funding_outpoint, required),
+	(1, counterparty_node_id, option),
+	(2, channel_keys_id, required),
+	(4, latest_commitment_number, required),
+});
+
+/// The decrypted contents of the backup we store with our peers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OurPeerStorage {
+	/// A summary of each of our [`ChannelMonitor`]s at the time the backup was created.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	pub monitors: Vec<PeerStorageMonitorHolder>,
+}
+
+impl_writeable_tlv_based!(OurPeerStorage, {
+	(0, monitors, required_vec),
+});
+
+impl OurPeerStorage {
+	/// Encrypts the backup with the given key, using `random_bytes` to derive the nonce.
+	///
+	/// The result is at most [`msgs::MAX_PEER_STORAGE_SIZE`] bytes, with any monitors which don't
+	/// fit being left out of the backup.
+	pub fn encrypt(&self, key: &PeerStorageKey, random_bytes: &[u8; 32]) -> Vec<u8> {
+		let mut plaintext = self.encode();
+		let mut monitor_count = self.monitors.len();
+		while plaintext.len() + NONCE_LEN + TAG_LEN > msgs::MAX_PEER_STORAGE_SIZE {
+			// Drop roughly as many monitors as needed to fit in one go, rather than one at a time.
+			let excess = plaintext.len() + NONCE_LEN + TAG_LEN - msgs::MAX_PEER_STORAGE_SIZE;
+			let avg_len = plaintext.len() / cmp::max(monitor_count, 1);
+			monitor_count -= cmp::min(cmp::max(excess / cmp::max(avg_len, 1), 1), monitor_count);
+			plaintext = OurPeerStorage { monitors: self.monitors[..monitor_count].to_vec() }.encode();
+		}
+
+		let mut res = vec![0; NONCE_LEN + plaintext.len() + TAG_LEN];
+		res[..NONCE_LEN].copy_from_slice(&random_bytes[..NONCE_LEN]);
+		let (ciphertext, tag) = res[NONCE_LEN..].split_at_mut(plaintext.len());
+		let mut chacha = ChaCha20Poly1305RFC::new(&key.0, &nonce_from_bytes(random_bytes), b"");
+		chacha.encrypt(&plaintext, ciphertext, tag);
+		res
+	}
+
+	/// Decrypts a backup previously created with [`Self::encrypt`] using the same key.
+	pub fn decrypt(key: &PeerStorageKey, data: &[u8]) -> Result<Self, DecodeError> {
+		if data.len() < NONCE_LEN + TAG_LEN {
+			return Err(DecodeError::InvalidValue);
+		}
+		let (nonce, rest) = data.split_at(NONCE_LEN);
+		let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+		let mut plaintext = vec![0; ciphertext.len()];
+		let mut chacha = ChaCha20Poly1305RFC::new(&key.0, &nonce_from_bytes(nonce), b"");
+		chacha.variable_time_decrypt(ciphertext, &mut plaintext, tag)
+			.map_err(|()| DecodeError::InvalidValue)?;
+		Readable::read(&mut &plaintext[..])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bitcoin::hashes::Hash;
+	use bitcoin::Txid;
+	use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+	use crate::chain::transaction::OutPoint;
+	use crate::ln::msgs::MAX_PEER_STORAGE_SIZE;
+	use crate::sign::PeerStorageKey;
+	use super::{OurPeerStorage, PeerStorageMonitorHolder};
+
+	fn monitor_holder(idx: u16) -> PeerStorageMonitorHolder {
+		let secp_ctx = Secp256k1::new();
+		let secret = SecretKey::from_slice(&[42; 32]).unwrap();
+		PeerStorageMonitorHolder {
+			counterparty_node_id: Some(PublicKey::from_secret_key(&secp_ctx, &secret)),
+			funding_outpoint: OutPoint { txid: Txid::from_byte_array([idx as u8; 32]), index: idx },
+			channel_keys_id: [idx as u8; 32],
+			latest_commitment_number: (1 << 48) - 1 - idx as u64,
+		}
+	}
+
+	#[test]
+	fn encrypt_decrypt_roundtrip() {
+		let key = PeerStorageKey([1; 32]);
+		let storage = OurPeerStorage { monitors: vec![monitor_holder(0), monitor_holder(1)] };
+		let encrypted = storage.encrypt(&key, &[2; 32]);
+		assert_eq!(OurPeerStorage::decrypt(&key, &encrypted).unwrap(), storage);
+
+		// Decryption fails with the wrong key or if the data was tampered with.
+		assert!(OurPeerStorage::decrypt(&PeerStorageKey([3; 32]), &encrypted).is_err());
+		let mut tampered = encrypted.clone();
+		*tampered.last_mut().unwrap() ^= 1;
+		assert!(OurPeerStorage::decrypt(&key, &tampered).is_err());
+		assert!(OurPeerStorage::decrypt(&key, &encrypted[..10]).is_err());
+	}
+
+	#[test]
+	fn encrypt_truncates_to_max_size() {
+		let key = PeerStorageKey([1; 32]);
+		let storage = OurPeerStorage { monitors: (0..1000).map(monitor_holder).collect() };
+		let encrypted = storage.encrypt(&key, &[2; 32]);
+		assert!(encrypted.len() <= MAX_PEER_STORAGE_SIZE);
+		let decrypted = OurPeerStorage::decrypt(&key, &encrypted).unwrap();
+		assert!(decrypted.monitors.len() < storage.monitors.len());
+		assert_eq!(decrypted.monitors[..], storage.monitors[..decrypted.monitors.len()]);
+	}
+}
@@ -18,7 +18,9 @@ use crate::events::{self, PaymentFailureReason};
 use crate::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
 use crate::ln::channelmanager::{ChannelDetails, EventCompletionAction, HTLCSource, PaymentId};
 use crate::ln::onion_utils::{DecodedOnionFailure, HTLCFailReason};
-use crate::offers::invoice::Bolt12Invoice;
+use crate::offers::invoice::{Bolt12Invoice, ExplicitSigningPubkey, InvoiceBuilder};
+use crate::offers::invoice_request::InvoiceRequest;
+use crate::offers::static_invoice::StaticInvoice;
 use crate::routing::router::{BlindedTail, InFlightHtlcs, Path, PaymentParameters, Route, RouteParameters, Router};
 use crate::util::errors::APIError;
 use crate::util::logger::Logger;
@@ -50,6 +52,9 @@ pub(crate) enum PendingOutboundPayment {
 		expiration: StaleExpiration,
 		retry_strategy: Retry,
 		max_total_routing_fee_msat: Option<u64>,
+		/// The request sent for the offer, used to correlate a [`StaticInvoice`] with this payment.
+		/// Only set for payments initiated on 0.0.122+ via an [`InvoiceRequest`].
+		invoice_request: Option<InvoiceRequest>,
 	},
 	InvoiceReceived {
 		payment_hash: PaymentHash,
@@ -58,6 +63,19 @@ pub(crate) enum PendingOutboundPayment {
 		// used anywhere.
 		max_total_routing_fee_msat: Option<u64>,
 	},
+	/// A [`StaticInvoice`] was received for an often-offline recipient. The payment is sent as a
+	/// keysend right away, asking our LSP to hold the HTLC until the recipient releases it with the
+	/// [`ReleaseHeldHtlc`] message matching `payment_release_secret`.
+	///
+	/// [`ReleaseHeldHtlc`]: crate::onion_message::async_payments::ReleaseHeldHtlc
+	StaticInvoiceReceived {
+		payment_hash: PaymentHash,
+		keysend_preimage: PaymentPreimage,
+		retry_strategy: Retry,
+		route_params: RouteParameters,
+		payment_release_secret: [u8; 32],
+		expiration: StaleExpiration,
+	},
 	Retryable {
 		retry_strategy: Option<Retry>,
 		attempts: PaymentAttempts,
@@ -137,6 +155,8 @@ impl PendingOutboundPayment {
 	fn is_awaiting_invoice(&self) -> bool {
 		match self {
 			PendingOutboundPayment::AwaitingInvoice { .. } => true,
+			// Held payments have no HTLCs in flight but must not be abandoned until released or stale.
+			PendingOutboundPayment::StaticInvoiceReceived { .. } => true,
 			_ => false,
 		}
 	}
@@ -164,6 +184,7 @@ impl PendingOutboundPayment {
 			PendingOutboundPayment::Legacy { .. } => None,
 			PendingOutboundPayment::AwaitingInvoice { .. } => None,
 			PendingOutboundPayment::InvoiceReceived { payment_hash, .. } => Some(*payment_hash),
+			PendingOutboundPayment::StaticInvoiceReceived { payment_hash, .. } => Some(*payment_hash),
 			PendingOutboundPayment::Retryable { payment_hash, .. } => Some(*payment_hash),
 			PendingOutboundPayment::Fulfilled { payment_hash, .. } => *payment_hash,
 			PendingOutboundPayment::Abandoned { payment_hash, .. } => Some(*payment_hash),
@@ -178,7 +199,8 @@ impl PendingOutboundPayment {
 				PendingOutboundPayment::Fulfilled { session_privs, .. } |
 				PendingOutboundPayment::Abandoned { session_privs, .. } => session_privs,
 			PendingOutboundPayment::AwaitingInvoice { .. } |
-				PendingOutboundPayment::InvoiceReceived { .. } => { debug_assert!(false); return; },
+				PendingOutboundPayment::InvoiceReceived { .. } |
+				PendingOutboundPayment::StaticInvoiceReceived { .. } => { debug_assert!(false); return; },
 		});
 		let payment_hash = self.payment_hash();
 		*self = PendingOutboundPayment::Fulfilled { session_privs, payment_hash, timer_ticks_without_htlcs: 0 };
@@ -199,6 +221,12 @@ impl PendingOutboundPayment {
 				payment_hash: *payment_hash,
 				reason: Some(reason)
 			};
+		} else if let PendingOutboundPayment::StaticInvoiceReceived { payment_hash, .. } = self {
+			*self = PendingOutboundPayment::Abandoned {
+				session_privs: HashSet::new(),
+				payment_hash: *payment_hash,
+				reason: Some(reason)
+			};
 		}
 	}
 
@@ -212,7 +240,8 @@ impl PendingOutboundPayment {
 					session_privs.remove(session_priv)
 				},
 			PendingOutboundPayment::AwaitingInvoice { .. } |
-				PendingOutboundPayment::InvoiceReceived { .. } => { debug_assert!(false); false },
+				PendingOutboundPayment::InvoiceReceived { .. } |
+				PendingOutboundPayment::StaticInvoiceReceived { .. } => { debug_assert!(false); false },
 		};
 		if remove_res {
 			if let PendingOutboundPayment::Retryable {
@@ -241,7 +270,8 @@ impl PendingOutboundPayment {
 					session_privs.insert(session_priv)
 				},
 			PendingOutboundPayment::AwaitingInvoice { .. } |
-				PendingOutboundPayment::InvoiceReceived { .. } => { debug_assert!(false); false },
+				PendingOutboundPayment::InvoiceReceived { .. } |
+				PendingOutboundPayment::StaticInvoiceReceived { .. } => { debug_assert!(false); false },
 			PendingOutboundPayment::Fulfilled { .. } => false,
 			PendingOutboundPayment::Abandoned { .. } => false,
 		};
@@ -274,6 +304,7 @@ impl PendingOutboundPayment {
 				},
 			PendingOutboundPayment::AwaitingInvoice { .. } => 0,
 			PendingOutboundPayment::InvoiceReceived { .. } => 0,
+			PendingOutboundPayment::StaticInvoiceReceived { .. } => 0,
 		}
 	}
 }
@@ -393,6 +424,25 @@ pub(crate) enum StaleExpiration {
 	AbsoluteTimeout(core::time::Duration),
 }
 
+impl StaleExpiration {
+	/// Returns whether the expiration has been reached, counting down any remaining timer ticks.
+	fn is_stale(&mut self, duration_since_epoch: Duration) -> bool {
+		match self {
+			StaleExpiration::AbsoluteTimeout(absolute_expiry) => {
+				*absolute_expiry <= duration_since_epoch
+			},
+			StaleExpiration::TimerTicks(timer_ticks_remaining) => {
+				if *timer_ticks_remaining > 0 {
+					*timer_ticks_remaining -= 1;
+					false
+				} else {
+					true
+				}
+			},
+		}
+	}
+}
+
 impl_writeable_tlv_based_enum!(StaleExpiration,
 	;
 	(0, TimerTicks),
@@ -500,6 +550,8 @@ pub(super) enum Bolt12PaymentError {
 	UnexpectedInvoice,
 	/// Payment for an invoice with the corresponding [`PaymentId`] was already initiated.
 	DuplicateInvoice,
+	/// The amount to pay could not be determined from the corresponding [`InvoiceRequest`].
+	InvalidAmount,
 }
 
 /// Indicates that we failed to send a payment probe. Further errors may be surfaced later via
@@ -664,6 +716,7 @@ pub(super) struct SendAlongPathArgs<'a> {
 	pub cur_height: u32,
 	pub payment_id: PaymentId,
 	pub keysend_preimage: &'a Option<PaymentPreimage>,
+	pub hold_htlc_at_next_hop: Option<[u8; 32]>,
 	pub session_priv_bytes: [u8; 32],
 }
 
@@ -711,7 +764,7 @@ impl OutboundPayments {
 		F: Fn(SendAlongPathArgs) -> Result<(), APIError>
 	{
 		let onion_session_privs = self.add_new_pending_payment(payment_hash, recipient_onion.clone(), payment_id, None, route, None, None, entropy_source, best_block_height)?;
-		self.pay_route_internal(route, payment_hash, recipient_onion, None, payment_id, None,
+		self.pay_route_internal(route, payment_hash, recipient_onion, None, None, payment_id, None,
 			onion_session_privs, node_signer, best_block_height, &send_payment_along_path)
 			.map_err(|e| { self.remove_outbound_if_all_failed(payment_id, &e); e })
 	}
@@ -756,7 +809,7 @@ impl OutboundPayments {
 		let onion_session_privs = self.add_new_pending_payment(payment_hash, recipient_onion.clone(),
 			payment_id, Some(preimage), &route, None, None, entropy_source, best_block_height)?;
 
-		match self.pay_route_internal(route, payment_hash, recipient_onion, Some(preimage),
+		match self.pay_route_internal(route, payment_hash, recipient_onion, Some(preimage), None,
 			payment_id, None, onion_session_privs, node_signer, best_block_height, &send_payment_along_path
 		) {
 			Ok(()) => Ok(payment_hash),
@@ -815,6 +868,84 @@ impl OutboundPayments {
 		Ok(())
 	}
 
+	/// Transitions the payment awaiting an invoice for the same offer as `invoice` to a held payment,
+	/// returning its id and the secret the recipient must present via [`ReleaseHeldHtlc`] to have our
+	/// LSP release it.
+	///
+	/// [`ReleaseHeldHtlc`]: crate::onion_message::async_payments::ReleaseHeldHtlc
+	pub(super) fn static_invoice_received<ES: Deref>(
+		&self, invoice: &StaticInvoice, entropy_source: &ES,
+	) -> Result<(PaymentId, [u8; 32]), Bolt12PaymentError> where ES::Target: EntropySource {
+		let mut outbounds = self.pending_outbound_payments.lock().unwrap();
+		let (payment_id, payment) = outbounds.iter_mut().find(|(_, payment)| match payment {
+			PendingOutboundPayment::AwaitingInvoice { invoice_request: Some(invreq), .. } =>
+				invoice.is_from_same_offer(invreq),
+			_ => false,
+		}).ok_or(Bolt12PaymentError::UnexpectedInvoice)?;
+
+		let (retry_strategy, route_params) = match payment {
+			PendingOutboundPayment::AwaitingInvoice {
+				retry_strategy, max_total_routing_fee_msat, invoice_request: Some(invreq), ..
+			} => {
+				let amount_msat = InvoiceBuilder::<ExplicitSigningPubkey>::amount_msats(invreq)
+					.map_err(|_| Bolt12PaymentError::InvalidAmount)?;
+				let pay_params = PaymentParameters::from_static_invoice(invoice);
+				let mut route_params = RouteParameters::from_payment_params_and_value(pay_params, amount_msat);
+				if let Some(max_fee_msat) = max_total_routing_fee_msat {
+					route_params.max_total_routing_fee_msat = Some(*max_fee_msat);
+				}
+				(*retry_strategy, route_params)
+			},
+			_ => return Err(Bolt12PaymentError::UnexpectedInvoice),
+		};
+
+		let keysend_preimage = PaymentPreimage(entropy_source.get_secure_random_bytes());
+		let payment_hash = PaymentHash(Sha256::hash(&keysend_preimage.0).to_byte_array());
+		let payment_release_secret = entropy_source.get_secure_random_bytes();
+		let expiration = StaleExpiration::AbsoluteTimeout(
+			invoice.created_at().saturating_add(invoice.relative_expiry())
+		);
+		*payment = PendingOutboundPayment::StaticInvoiceReceived {
+			payment_hash, keysend_preimage, retry_strategy, route_params, payment_release_secret,
+			expiration,
+		};
+
+		Ok((*payment_id, payment_release_secret))
+	}
+
+	/// Sends the held payment with the given id over `first_hops`, asking the first hop (our LSP) to
+	/// hold the HTLC until the recipient releases it.
+	pub(super) fn send_payment_for_static_invoice<R: Deref, ES: Deref, NS: Deref, IH, SP, L: Deref>(
+		&self, payment_id: PaymentId, router: &R, first_hops: Vec<ChannelDetails>,
+		inflight_htlcs: IH, entropy_source: &ES, node_signer: &NS, best_block_height: u32, logger: &L,
+		pending_events: &Mutex<VecDeque<(events::Event, Option<EventCompletionAction>)>>,
+		send_payment_along_path: SP,
+	) -> Result<(), Bolt12PaymentError>
+	where
+		R::Target: Router,
+		ES::Target: EntropySource,
+		NS::Target: NodeSigner,
+		L::Target: Logger,
+		IH: Fn() -> InFlightHtlcs,
+		SP: Fn(SendAlongPathArgs) -> Result<(), APIError>,
+	{
+		let (payment_hash, route_params) = match self.pending_outbound_payments.lock().unwrap()
+			.get(&payment_id)
+		{
+			Some(PendingOutboundPayment::StaticInvoiceReceived { payment_hash, route_params, .. }) =>
+				(*payment_hash, route_params.clone()),
+			_ => return Err(Bolt12PaymentError::UnexpectedInvoice),
+		};
+
+		self.find_route_and_send_payment(
+			payment_hash, payment_id, route_params, router, first_hops, &inflight_htlcs,
+			entropy_source, node_signer, best_block_height, logger, pending_events,
+			&send_payment_along_path
+		);
+
+		Ok(())
+	}
+
 	pub(super) fn check_retry_payments<R: Deref, ES: Deref, NS: Deref, SP, IH, FH, L: Deref>(
 		&self, router: &R, first_hops: FH, inflight_htlcs: IH, entropy_source: &ES, node_signer: &NS,
 		best_block_height: u32,
@@ -932,7 +1063,7 @@ impl OutboundPayments {
 				RetryableSendFailure::DuplicatePayment
 			})?;
 
-		let res = self.pay_route_internal(&route, payment_hash, recipient_onion, keysend_preimage, payment_id, None,
+		let res = self.pay_route_internal(&route, payment_hash, recipient_onion, keysend_preimage, None, payment_id, None,
 			onion_session_privs, node_signer, best_block_height, &send_payment_along_path);
 		log_info!(logger, "Sending payment with id {} and hash {} returned {:?}",
 			payment_id, payment_hash, res);
@@ -1006,7 +1137,7 @@ impl OutboundPayments {
 				}
 			}
 		}
-		let (total_msat, recipient_onion, keysend_preimage, onion_session_privs) = {
+		let (total_msat, recipient_onion, keysend_preimage, hold_htlc_at_next_hop, onion_session_privs) = {
 			let mut outbounds = self.pending_outbound_payments.lock().unwrap();
 			match outbounds.entry(payment_id) {
 				hash_map::Entry::Occupied(mut payment) => {
@@ -1048,7 +1179,7 @@ impl OutboundPayments {
 
 							payment.get_mut().increment_attempts();
 
-							(total_msat, recipient_onion, keysend_preimage, onion_session_privs)
+							(total_msat, recipient_onion, keysend_preimage, None, onion_session_privs)
 						},
 						PendingOutboundPayment::Legacy { .. } => {
 							log_error!(logger, "Unable to retry payments that were initially sent on LDK versions prior to 0.0.102");
@@ -1072,7 +1203,29 @@ impl OutboundPayments {
 								retry_strategy, payment_params, entropy_source, best_block_height
 							);
 							*payment.into_mut() = retryable_payment;
-							(total_amount, recipient_onion, None, onion_session_privs)
+							(total_amount, recipient_onion, None, None, onion_session_privs)
+						},
+						PendingOutboundPayment::StaticInvoiceReceived {
+							payment_hash, keysend_preimage, retry_strategy, payment_release_secret, ..
+						} => {
+							let total_amount = route_params.final_value_msat;
+							let recipient_onion = RecipientOnionFields {
+								payment_secret: None,
+								payment_metadata: None,
+								custom_tlvs: vec![],
+							};
+							let keysend_preimage = Some(*keysend_preimage);
+							let retry_strategy = Some(*retry_strategy);
+							let payment_params = Some(route_params.payment_params.clone());
+							let (retryable_payment, onion_session_privs) = self.create_pending_payment(
+								*payment_hash, recipient_onion.clone(), keysend_preimage, &route,
+								retry_strategy, payment_params, entropy_source, best_block_height
+							);
+							// Retries are not held, as the recipient only releases the HTLCs held under
+							// this secret once.
+							let hold_htlc_at_next_hop = Some(*payment_release_secret);
+							*payment.into_mut() = retryable_payment;
+							(total_amount, recipient_onion, keysend_preimage, hold_htlc_at_next_hop, onion_session_privs)
 						},
 						PendingOutboundPayment::Fulfilled { .. } => {
 							log_error!(logger, "Payment already completed");
@@ -1091,8 +1244,8 @@ impl OutboundPayments {
 			}
 		};
 		let res = self.pay_route_internal(&route, payment_hash, recipient_onion, keysend_preimage,
-			payment_id, Some(total_msat), onion_session_privs, node_signer, best_block_height,
-			&send_payment_along_path);
+			hold_htlc_at_next_hop, payment_id, Some(total_msat), onion_session_privs, node_signer,
+			best_block_height, &send_payment_along_path);
 		log_info!(logger, "Result retrying payment id {}: {:?}", &payment_id, res);
 		if let Err(e) = res {
 			self.handle_pay_route_err(e, payment_id, payment_hash, route, route_params, router, first_hops, inflight_htlcs, entropy_source, node_signer, best_block_height, logger, pending_events, send_payment_along_path);
@@ -1202,7 +1355,7 @@ impl OutboundPayments {
 			entropy_source, best_block_height)?;
 
 		match self.pay_route_internal(&route, payment_hash, RecipientOnionFields::spontaneous_empty(),
-			None, payment_id, None, onion_session_privs, node_signer, best_block_height, &send_payment_along_path
+			None, None, payment_id, None, onion_session_privs, node_signer, best_block_height, &send_payment_along_path
 		) {
 			Ok(()) => Ok((payment_hash, payment_id)),
 			Err(e) => {
@@ -1291,7 +1444,7 @@ impl OutboundPayments {
 
 	pub(super) fn add_new_awaiting_invoice(
 		&self, payment_id: PaymentId, expiration: StaleExpiration, retry_strategy: Retry,
-		max_total_routing_fee_msat: Option<u64>
+		max_total_routing_fee_msat: Option<u64>, invoice_request: Option<InvoiceRequest>
 	) -> Result<(), ()> {
 		let mut pending_outbounds = self.pending_outbound_payments.lock().unwrap();
 		match pending_outbounds.entry(payment_id) {
@@ -1301,6 +1454,7 @@ impl OutboundPayments {
 					expiration,
 					retry_strategy,
 					max_total_routing_fee_msat,
+					invoice_request,
 				});
 
 				Ok(())
@@ -1310,7 +1464,8 @@ impl OutboundPayments {
 
 	fn pay_route_internal<NS: Deref, F>(
 		&self, route: &Route, payment_hash: PaymentHash, recipient_onion: RecipientOnionFields,
-		keysend_preimage: Option<PaymentPreimage>, payment_id: PaymentId, recv_value_msat: Option<u64>,
+		keysend_preimage: Option<PaymentPreimage>, hold_htlc_at_next_hop: Option<[u8; 32]>,
+		payment_id: PaymentId, recv_value_msat: Option<u64>,
 		onion_session_privs: Vec<[u8; 32]>, node_signer: &NS, best_block_height: u32,
 		send_payment_along_path: &F
 	) -> Result<(), PaymentSendFailure>
@@ -1365,7 +1520,8 @@ impl OutboundPayments {
 		for (path, session_priv_bytes) in route.paths.iter().zip(onion_session_privs.into_iter()) {
 			let mut path_res = send_payment_along_path(SendAlongPathArgs {
 				path: &path, payment_hash: &payment_hash, recipient_onion: recipient_onion.clone(),
-				total_value, cur_height, payment_id, keysend_preimage: &keysend_preimage, session_priv_bytes
+				total_value, cur_height, payment_id, keysend_preimage: &keysend_preimage,
+				hold_htlc_at_next_hop, session_priv_bytes
 			});
 			match path_res {
 				Ok(_) => {},
@@ -1448,7 +1604,7 @@ impl OutboundPayments {
 		NS::Target: NodeSigner,
 		F: Fn(SendAlongPathArgs) -> Result<(), APIError>,
 	{
-		self.pay_route_internal(route, payment_hash, recipient_onion, keysend_preimage, payment_id,
+		self.pay_route_internal(route, payment_hash, recipient_onion, keysend_preimage, None, payment_id,
 			recv_value_msat, onion_session_privs, node_signer, best_block_height,
 			&send_payment_along_path)
 			.map_err(|e| { self.remove_outbound_if_all_failed(payment_id, &e); e })
@@ -1573,20 +1729,7 @@ impl OutboundPayments {
 				}
 			},
 			PendingOutboundPayment::AwaitingInvoice { expiration, .. } => {
-				let is_stale = match expiration {
-					StaleExpiration::AbsoluteTimeout(absolute_expiry) => {
-						*absolute_expiry <= duration_since_epoch
-					},
-					StaleExpiration::TimerTicks(timer_ticks_remaining) => {
-						if *timer_ticks_remaining > 0 {
-							*timer_ticks_remaining -= 1;
-							false
-						} else {
-							true
-						}
-					},
-				};
-				if is_stale {
+				if expiration.is_stale(duration_since_epoch) {
 					pending_events.push_back(
 						(events::Event::InvoiceRequestFailed { payment_id: *payment_id }, None)
 					);
@@ -1595,6 +1738,18 @@ impl OutboundPayments {
 					true
 				}
 			},
+			PendingOutboundPayment::StaticInvoiceReceived { payment_hash, expiration, .. } => {
+				if expiration.is_stale(duration_since_epoch) {
+					pending_events.push_back((events::Event::PaymentFailed {
+						payment_id: *payment_id,
+						payment_hash: *payment_hash,
+						reason: Some(PaymentFailureReason::PaymentExpired),
+					}, None));
+					false
+				} else {
+					true
+				}
+			},
 			_ => true,
 		});
 	}
@@ -1821,12 +1976,21 @@ impl_writeable_tlv_based_enum_upgradable!(PendingOutboundPayment,
 		(0, expiration, required),
 		(2, retry_strategy, required),
 		(4, max_total_routing_fee_msat, option),
+		(5, invoice_request, option),
 	},
 	(7, InvoiceReceived) => {
 		(0, payment_hash, required),
 		(2, retry_strategy, required),
 		(4, max_total_routing_fee_msat, option),
 	},
+	(9, StaticInvoiceReceived) => {
+		(0, payment_hash, required),
+		(2, keysend_preimage, required),
+		(4, retry_strategy, required),
+		(6, route_params, required),
+		(8, payment_release_secret, required),
+		(10, expiration, required),
+	},
 );
 
 #[cfg(test)]
@@ -1840,14 +2004,17 @@ mod tests {
 	use crate::ln::PaymentHash;
 	use crate::ln::channelmanager::{PaymentId, RecipientOnionFields};
 	use crate::ln::features::{ChannelFeatures, NodeFeatures};
+	use crate::ln::inbound_payment::ExpandedKey;
 	use crate::ln::msgs::{ErrorAction, LightningError};
 	use crate::ln::outbound_payment::{Bolt12PaymentError, OutboundPayments, Retry, RetryableSendFailure, StaleExpiration};
 	#[cfg(feature = "std")]
 	use crate::offers::invoice::DEFAULT_RELATIVE_EXPIRY;
 	use crate::offers::offer::OfferBuilder;
+	use crate::offers::static_invoice::StaticInvoiceBuilder;
 	use crate::offers::test_utils::*;
 	use crate::routing::gossip::NetworkGraph;
 	use crate::routing::router::{InFlightHtlcs, Path, PaymentParameters, Route, RouteHop, RouteParameters};
+	use crate::sign::KeyMaterial;
 	use crate::sync::{Arc, Mutex, RwLock};
 	use crate::util::errors::APIError;
 	use crate::util::test_utils;
@@ -1989,7 +2156,7 @@ mod tests {
 				fee_msat: 0,
 				cltv_expiry_delta: 0,
 				maybe_announced_channel: true,
-			}], blinded_tail: None }],
+			}], blinded_tail: None, trampoline_hops: vec![] }],
 			route_params: Some(route_params.clone()),
 		};
 		router.expect_find_route(route_params.clone(), Ok(route.clone()));
@@ -2058,7 +2225,7 @@ mod tests {
 		assert!(!outbound_payments.has_pending_payments());
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2084,14 +2251,14 @@ mod tests {
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_err()
 		);
 	}
@@ -2107,7 +2274,7 @@ mod tests {
 		assert!(!outbound_payments.has_pending_payments());
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2133,14 +2300,14 @@ mod tests {
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_err()
 		);
 	}
@@ -2155,7 +2322,7 @@ mod tests {
 		assert!(!outbound_payments.has_pending_payments());
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2188,7 +2355,7 @@ mod tests {
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), None
+				payment_id, expiration, Retry::Attempts(0), None, None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2250,7 +2417,7 @@ mod tests {
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
 				payment_id, expiration, Retry::Attempts(0),
-				Some(invoice.amount_msats() / 100 + 50_000)
+				Some(invoice.amount_msats() / 100 + 50_000), None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2309,7 +2476,7 @@ mod tests {
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
 				payment_id, expiration, Retry::Attempts(0),
-				Some(invoice.amount_msats() / 100 + 50_000)
+				Some(invoice.amount_msats() / 100 + 50_000), None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2387,6 +2554,7 @@ mod tests {
 							}
 						],
 						blinded_tail: None,
+						trampoline_hops: vec![],
 					}
 				],
 				route_params: Some(route_params),
@@ -2406,7 +2574,7 @@ mod tests {
 
 		assert!(
 			outbound_payments.add_new_awaiting_invoice(
-				payment_id, expiration, Retry::Attempts(0), Some(1234)
+				payment_id, expiration, Retry::Attempts(0), Some(1234), None
 			).is_ok()
 		);
 		assert!(outbound_payments.has_pending_payments());
@@ -2431,4 +2599,112 @@ mod tests {
 		assert!(outbound_payments.has_pending_payments());
 		assert!(pending_events.lock().unwrap().is_empty());
 	}
+
+	#[test]
+	fn sends_held_payment_for_static_invoice() {
+		let logger = test_utils::TestLogger::new();
+		let network_graph = Arc::new(NetworkGraph::new(Network::Testnet, &logger));
+		let scorer = RwLock::new(test_utils::TestScorer::new());
+		let router = test_utils::TestRouter::new(network_graph, &scorer);
+		let keys_manager = test_utils::TestKeysInterface::new(&[0; 32], Network::Testnet);
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+
+		let pending_events = Mutex::new(VecDeque::new());
+		let outbound_payments = OutboundPayments::new();
+		let payment_id = PaymentId([0; 32]);
+		let expiration = StaleExpiration::AbsoluteTimeout(Duration::from_secs(100));
+
+		let offer = OfferBuilder
+			::deriving_signing_pubkey("foo".into(), recipient_pubkey(), &expanded_key, &FixedEntropy {}, &secp_ctx)
+			.amount_msats(1000)
+			.path(payment_paths()[0].1.clone())
+			.build().unwrap();
+		let invoice_request = offer.request_invoice(vec![1; 32], payer_pubkey()).unwrap()
+			.build().unwrap()
+			.sign(payer_sign).unwrap();
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![payment_paths()[0].1.clone()], now(), &expanded_key,
+			&secp_ctx
+		).unwrap()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		assert_eq!(
+			outbound_payments.static_invoice_received(&invoice, &&keys_manager),
+			Err(Bolt12PaymentError::UnexpectedInvoice),
+		);
+
+		assert!(
+			outbound_payments.add_new_awaiting_invoice(
+				payment_id, expiration, Retry::Attempts(0), None, Some(invoice_request)
+			).is_ok()
+		);
+
+		let (held_payment_id, payment_release_secret) =
+			outbound_payments.static_invoice_received(&invoice, &&keys_manager).unwrap();
+		assert_eq!(held_payment_id, payment_id);
+		assert_eq!(
+			outbound_payments.static_invoice_received(&invoice, &&keys_manager),
+			Err(Bolt12PaymentError::UnexpectedInvoice),
+		);
+		assert_eq!(
+			outbound_payments.send_payment_for_static_invoice(
+				PaymentId([1; 32]), &&router, vec![], || InFlightHtlcs::new(), &&keys_manager,
+				&&keys_manager, 0, &&logger, &pending_events, |_| panic!()
+			),
+			Err(Bolt12PaymentError::UnexpectedInvoice),
+		);
+		assert!(outbound_payments.has_pending_payments());
+		assert!(pending_events.lock().unwrap().is_empty());
+
+		let route_params = RouteParameters::from_payment_params_and_value(
+			PaymentParameters::from_static_invoice(&invoice), 1000,
+		);
+		router.expect_find_route(
+			route_params.clone(),
+			Ok(Route {
+				paths: vec![
+					Path {
+						hops: vec![
+							RouteHop {
+								pubkey: recipient_pubkey(),
+								node_features: NodeFeatures::empty(),
+								short_channel_id: 42,
+								channel_features: ChannelFeatures::empty(),
+								fee_msat: 1000,
+								cltv_expiry_delta: 0,
+								maybe_announced_channel: true,
+							}
+						],
+						blinded_tail: None,
+						trampoline_hops: vec![],
+					}
+				],
+				route_params: Some(route_params),
+			})
+		);
+
+		// The HTLC is sent right away, asking the first hop to hold it until released.
+		assert_eq!(
+			outbound_payments.send_payment_for_static_invoice(
+				payment_id, &&router, vec![], || InFlightHtlcs::new(), &&keys_manager,
+				&&keys_manager, 0, &&logger, &pending_events, |args| {
+					assert_eq!(args.hold_htlc_at_next_hop, Some(payment_release_secret));
+					assert!(args.keysend_preimage.is_some());
+					Ok(())
+				}
+			),
+			Ok(()),
+		);
+		assert!(outbound_payments.has_pending_payments());
+		assert!(pending_events.lock().unwrap().is_empty());
+
+		assert_eq!(
+			outbound_payments.send_payment_for_static_invoice(
+				payment_id, &&router, vec![], || InFlightHtlcs::new(), &&keys_manager,
+				&&keys_manager, 0, &&logger, &pending_events, |_| panic!()
+			),
+			Err(Bolt12PaymentError::UnexpectedInvoice),
+		);
+	}
 }
@@ -24,10 +24,10 @@ use crate::ln::msgs::ChannelMessageHandler;
 use crate::ln::onion_utils;
 use crate::ln::outbound_payment::{IDEMPOTENCY_TIMEOUT_TICKS, Retry};
 use crate::routing::gossip::{EffectiveCapacity, RoutingFees};
-use crate::routing::router::{get_route, Path, PaymentParameters, Route, Router, RouteHint, RouteHintHop, RouteHop, RouteParameters, find_route};
+use crate::routing::router::{get_route, DefaultRouter, InFlightHtlcs, Path, PaymentParameters, Route, Router, RouteHint, RouteHintHop, RouteHop, RouteParameters, find_route};
 use crate::routing::scoring::ChannelUsage;
 use crate::util::config::UserConfig;
-use crate::util::test_utils;
+use crate::util::{metrics, test_utils};
 use crate::util::errors::APIError;
 use crate::util::ser::Writeable;
 use crate::util::string::UntrustedString;
@@ -2441,7 +2441,7 @@ fn auto_retry_partial_failure() {
 				fee_msat: amt_msat / 2,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[1].node.get_our_node_id(),
 				node_features: nodes[1].node.node_features(),
@@ -2450,7 +2450,7 @@ fn auto_retry_partial_failure() {
 				fee_msat: amt_msat / 2,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -2472,7 +2472,7 @@ fn auto_retry_partial_failure() {
 				fee_msat: amt_msat / 4,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[1].node.get_our_node_id(),
 				node_features: nodes[1].node.node_features(),
@@ -2481,7 +2481,7 @@ fn auto_retry_partial_failure() {
 				fee_msat: amt_msat / 4,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(retry_1_params.clone()),
 	};
@@ -2503,7 +2503,7 @@ fn auto_retry_partial_failure() {
 				fee_msat: amt_msat / 4,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(retry_2_params.clone()),
 	};
@@ -2648,7 +2648,7 @@ fn auto_retry_zero_attempts_send_error() {
 				fee_msat: amt_msat,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -2746,7 +2746,7 @@ fn retry_multi_path_single_failed_payment() {
 				fee_msat: 10_000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[1].node.get_our_node_id(),
 				node_features: nodes[1].node.node_features(),
@@ -2755,7 +2755,7 @@ fn retry_multi_path_single_failed_payment() {
 				fee_msat: 100_000_001, // Our default max-HTLC-value is 10% of the channel value, which this is one more than
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -2837,7 +2837,7 @@ fn immediate_retry_on_failure() {
 				fee_msat: 100_000_001, // Our default max-HTLC-value is 10% of the channel value, which this is one more than
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -2931,7 +2931,7 @@ fn no_extra_retries_on_back_to_back_fail() {
 				fee_msat: 100_000_000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[1].node.get_our_node_id(),
 				node_features: nodes[1].node.node_features(),
@@ -2948,7 +2948,7 @@ fn no_extra_retries_on_back_to_back_fail() {
 				fee_msat: 100_000_000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None }
+			}], blinded_tail: None, trampoline_hops: vec![] }
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -3136,7 +3136,7 @@ fn test_simple_partial_retry() {
 				fee_msat: 100_000_000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[1].node.get_our_node_id(),
 				node_features: nodes[1].node.node_features(),
@@ -3153,7 +3153,7 @@ fn test_simple_partial_retry() {
 				fee_msat: 100_000_000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None }
+			}], blinded_tail: None, trampoline_hops: vec![] }
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -3307,7 +3307,7 @@ fn test_threaded_payment_retries() {
 				fee_msat: amt_msat / 1000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None },
+			}], blinded_tail: None, trampoline_hops: vec![] },
 			Path { hops: vec![RouteHop {
 				pubkey: nodes[2].node.get_our_node_id(),
 				node_features: nodes[2].node.node_features(),
@@ -3324,7 +3324,7 @@ fn test_threaded_payment_retries() {
 				fee_msat: amt_msat - amt_msat / 1000,
 				cltv_expiry_delta: 100,
 				maybe_announced_channel: true,
-			}], blinded_tail: None }
+			}], blinded_tail: None, trampoline_hops: vec![] }
 		],
 		route_params: Some(route_params.clone()),
 	};
@@ -4238,7 +4238,7 @@ fn peel_payment_onion_custom_tlvs() {
 
 	let (onion_routing_packet, first_hop_msat, cltv_expiry) = onion_utils::create_payment_onion(
 		&secp_ctx, &route.paths[0], &session_priv, amt_msat, recipient_onion.clone(),
-		nodes[0].best_block_info().1, &payment_hash, &Some(keysend_preimage), prng_seed
+		nodes[0].best_block_info().1, &payment_hash, &Some(keysend_preimage), None, prng_seed
 	).unwrap();
 
 	let update_add = msgs::UpdateAddHTLC {
@@ -4250,6 +4250,7 @@ fn peel_payment_onion_custom_tlvs() {
 		skimmed_fee_msat: None,
 		onion_routing_packet,
 		blinding_point: None,
+		endorsed: None,
 	};
 	let peeled_onion = crate::ln::onion_payment::peel_payment_onion(
 		&update_add, &&chanmon_cfgs[1].keys_manager, &&chanmon_cfgs[1].logger, &secp_ctx,
@@ -4270,3 +4271,37 @@ fn peel_payment_onion_custom_tlvs() {
 		_ => panic!()
 	}
 }
+
+#[test]
+fn test_monitor_update_and_pathfinding_metrics() {
+	// Test that monitor updates are reported to the `ChainMonitor`'s `MetricsRecorder` and that
+	// pathfinding is reported to the `DefaultRouter`'s.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+	create_announced_chan_between_nodes(&nodes, 1, 2);
+
+	send_payment(&nodes[0], &[&nodes[1], &nodes[2]], 100_000);
+
+	let router_metrics = test_utils::TestMetricsRecorder::new();
+	let random_seed_bytes = chanmon_cfgs[0].keys_manager.get_secure_random_bytes();
+	let router = DefaultRouter::new(nodes[0].network_graph, nodes[0].logger, random_seed_bytes,
+		&chanmon_cfgs[0].scorer, (), &router_metrics);
+	let payment_params = PaymentParameters::from_node_id(nodes[2].node.get_our_node_id(), TEST_FINAL_CLTV);
+	for amount_msat in [100_000, 100_000_000_000] {
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params.clone(), amount_msat);
+		let _ = router.find_route(&nodes[0].node.get_our_node_id(), &route_params, None,
+			InFlightHtlcs::new());
+	}
+
+	let forwarder_metrics = &nodes[1].chain_monitor.metrics;
+	#[cfg(feature = "std")] {
+		assert_eq!(router_metrics.histogram_observations(metrics::FIND_ROUTE_DURATION, &[("result", "success")]), 1);
+		assert_eq!(router_metrics.histogram_observations(metrics::FIND_ROUTE_DURATION, &[("result", "failure")]), 1);
+		assert!(forwarder_metrics.histogram_observations(metrics::MONITOR_UPDATE_DURATION, &[("kind", "new_channel")]) >= 2);
+		assert!(forwarder_metrics.histogram_observations(metrics::MONITOR_UPDATE_DURATION, &[("kind", "update")]) > 0);
+	}
+	assert_eq!(forwarder_metrics.counter(metrics::MONITOR_UPDATES_IN_PROGRESS, &[("kind", "update")]), 0);
+}
@@ -33,11 +33,13 @@ use crate::ln::wire::{Encode, Type};
 #[cfg(not(c_bindings))]
 use crate::onion_message::messenger::{SimpleArcOnionMessenger, SimpleRefOnionMessenger};
 use crate::onion_message::messenger::{CustomOnionMessageHandler, PendingOnionMessage};
+use crate::onion_message::async_payments::{AsyncPaymentsMessageHandler, HeldHtlcAvailable, ReleaseHeldHtlc};
 use crate::onion_message::offers::{OffersMessage, OffersMessageHandler};
 use crate::onion_message::packet::OnionMessageContents;
 use crate::routing::gossip::{NodeId, NodeAlias};
 use crate::util::atomic_counter::AtomicCounter;
 use crate::util::logger::{Logger, WithContext};
+use crate::util::metrics::{self, IgnoringMetricsRecorder, MetricsRecorder};
 use crate::util::string::PrintableString;
 
 use crate::prelude::*;
@@ -112,10 +114,12 @@ impl RoutingMessageHandler for IgnoringMessageHandler {
 		Option<(msgs::ChannelAnnouncement, Option<msgs::ChannelUpdate>, Option<msgs::ChannelUpdate>)> { None }
 	fn get_next_node_announcement(&self, _starting_point: Option<&NodeId>) -> Option<msgs::NodeAnnouncement> { None }
 	fn peer_connected(&self, _their_node_id: &PublicKey, _init: &msgs::Init, _inbound: bool) -> Result<(), ()> { Ok(()) }
+	fn peer_disconnected(&self, _their_node_id: &PublicKey) {}
 	fn handle_reply_channel_range(&self, _their_node_id: &PublicKey, _msg: msgs::ReplyChannelRange) -> Result<(), LightningError> { Ok(()) }
 	fn handle_reply_short_channel_ids_end(&self, _their_node_id: &PublicKey, _msg: msgs::ReplyShortChannelIdsEnd) -> Result<(), LightningError> { Ok(()) }
 	fn handle_query_channel_range(&self, _their_node_id: &PublicKey, _msg: msgs::QueryChannelRange) -> Result<(), LightningError> { Ok(()) }
 	fn handle_query_short_channel_ids(&self, _their_node_id: &PublicKey, _msg: msgs::QueryShortChannelIds) -> Result<(), LightningError> { Ok(()) }
+	fn get_next_short_channel_ids_reply(&self, _their_node_id: &PublicKey) -> Option<msgs::ShortChannelIdsReply> { None }
 	fn provided_node_features(&self) -> NodeFeatures { NodeFeatures::empty() }
 	fn provided_init_features(&self, _their_node_id: &PublicKey) -> InitFeatures {
 		InitFeatures::empty()
@@ -136,6 +140,10 @@ impl OnionMessageHandler for IgnoringMessageHandler {
 impl OffersMessageHandler for IgnoringMessageHandler {
 	fn handle_message(&self, _msg: OffersMessage) -> Option<OffersMessage> { None }
 }
+impl AsyncPaymentsMessageHandler for IgnoringMessageHandler {
+	fn held_htlc_available(&self, _message: HeldHtlcAvailable) -> Option<ReleaseHeldHtlc> { None }
+	fn release_held_htlc(&self, _message: ReleaseHeldHtlc) {}
+}
 impl CustomOnionMessageHandler for IgnoringMessageHandler {
 	type CustomMessage = Infallible;
 	fn handle_custom_message(&self, _msg: Infallible) -> Option<Infallible> {
@@ -244,6 +252,8 @@ impl ChannelMessageHandler for ErroringMessageHandler {
 	fn handle_closing_signed(&self, their_node_id: &PublicKey, msg: &msgs::ClosingSigned) {
 		ErroringMessageHandler::push_error(self, their_node_id, msg.channel_id);
 	}
+	fn handle_peer_storage(&self, _their_node_id: &PublicKey, _msg: &msgs::PeerStorage) {}
+	fn handle_peer_storage_retrieval(&self, _their_node_id: &PublicKey, _msg: &msgs::PeerStorageRetrieval) {}
 	fn handle_stfu(&self, their_node_id: &PublicKey, msg: &msgs::Stfu) {
 		ErroringMessageHandler::push_error(&self, their_node_id, msg.channel_id);
 	}
@@ -306,6 +316,7 @@ impl ChannelMessageHandler for ErroringMessageHandler {
 		features.set_scid_privacy_optional();
 		features.set_zero_conf_optional();
 		features.set_route_blinding_optional();
+		features.set_quiescence_optional();
 		features
 	}
 
@@ -316,6 +327,8 @@ impl ChannelMessageHandler for ErroringMessageHandler {
 		None
 	}
 
+	fn peer_has_channels(&self, _their_node_id: &PublicKey) -> bool { false }
+
 	fn handle_open_channel_v2(&self, their_node_id: &PublicKey, msg: &msgs::OpenChannelV2) {
 		ErroringMessageHandler::push_error(self, their_node_id, msg.temporary_channel_id);
 	}
@@ -456,6 +469,171 @@ impl error::Error for PeerHandleError {
 	}
 }
 
+/// A token bucket limiting the rate at which a peer may send us a class of messages.
+///
+/// Each peer starts out with `burst` tokens, each message of the limited class consumes one
+/// token, and `refill_per_tick` tokens (up to `burst`) are added back on every call to
+/// [`PeerManager::timer_tick_occurred`]. Messages received while no tokens are left are dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+	/// The maximum number of tokens a peer may accumulate, i.e. the number of messages it may
+	/// send us in a burst.
+	pub burst: u32,
+	/// The number of tokens added back on each timer tick.
+	pub refill_per_tick: u32,
+}
+
+/// Per-peer resource limits enforced by a [`PeerManager`], see [`PeerManager::new_with_limits`].
+///
+/// Rate limits are measured in calls to [`PeerManager::timer_tick_occurred`], which are expected
+/// to happen roughly every ten seconds.
+///
+/// All limits are opt-in, the [`Default`] value imposes none. Peers with which we have funded
+/// channels, as reported by [`ChannelMessageHandler::peer_has_channels`], are never rate limited
+/// or banned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerLimits {
+	/// The rate limit applied to `channel_announcement`, `channel_update` and `node_announcement`
+	/// messages.
+	///
+	/// Note that a peer answering our initial gossip sync will send us a large burst of these.
+	///
+	/// Default value: `None`.
+	pub gossip_rate_limit: Option<RateLimit>,
+	/// The rate limit applied to `query_channel_range` and `query_short_channel_ids` messages.
+	/// Replies to our own queries are not limited.
+	///
+	/// Default value: `None`.
+	pub gossip_query_rate_limit: Option<RateLimit>,
+	/// The rate limit applied to `onion_message` messages.
+	///
+	/// Default value: `None`.
+	pub onion_message_rate_limit: Option<RateLimit>,
+	/// The rate limit applied to `ping` messages.
+	///
+	/// Default value: `None`.
+	pub ping_rate_limit: Option<RateLimit>,
+	/// The number of messages a peer may send us in excess of its rate limits within a single
+	/// timer tick before we disconnect it and temporarily ban it. Bans apply to the peer's node
+	/// id as well as the [`IpPrefix`] it connected from, if known.
+	///
+	/// Default value: 50.
+	pub max_rate_limit_violations_per_tick: u32,
+	/// The number of timer ticks a ban lasts for.
+	///
+	/// Default value: 60 (roughly ten minutes).
+	pub ban_duration_ticks: u32,
+	/// The maximum number of inbound connections, including those which have not yet completed
+	/// the handshake, we accept from a single [`IpPrefix`]. Connections without a known remote
+	/// address are not counted.
+	///
+	/// Note that inbound connections received via a Tor onion service will appear to come from
+	/// the local Tor daemon, so this should likely be left unset in that case. Limits on channels
+	/// which have not yet been funded are enforced separately by the `ChannelManager`.
+	///
+	/// Default value: `None`.
+	pub max_inbound_connections_per_ip_prefix: Option<usize>,
+}
+
+impl Default for PeerLimits {
+	fn default() -> Self {
+		Self {
+			gossip_rate_limit: None,
+			gossip_query_rate_limit: None,
+			onion_message_rate_limit: None,
+			ping_rate_limit: None,
+			max_rate_limit_violations_per_tick: 50,
+			ban_duration_ticks: 60,
+			max_inbound_connections_per_ip_prefix: None,
+		}
+	}
+}
+
+const LOOPBACK_IPV6: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+const IPV4_MAPPED_IPV6_PREFIX: [u8; 12] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff];
+
+/// The network an IP address belongs to, used to group connections for
+/// [`PeerLimits::max_inbound_connections_per_ip_prefix`] and bans.
+///
+/// IPv4 addresses are grouped by their /24 prefix, IPv6 addresses by their /48 prefix.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum IpPrefix {
+	/// The first three bytes of an IPv4 address.
+	V4([u8; 3]),
+	/// The first six bytes of an IPv6 address.
+	V6([u8; 6]),
+}
+
+impl IpPrefix {
+	/// Returns the prefix of the given address, if it is a non-loopback IPv4 or IPv6 address.
+	///
+	/// Loopback addresses have no prefix as connections proxied by a local daemon (e.g. Tor) all
+	/// appear to come from them, and thus are never counted or banned by prefix.
+	///
+	/// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`), as reported for IPv4 peers connecting to a
+	/// dual-stack listener, are treated as the IPv4 address they map.
+	pub fn from_socket_address(address: &SocketAddress) -> Option<Self> {
+		match address {
+			SocketAddress::TcpIpV4 { addr, .. } => Self::from_ipv4(addr),
+			SocketAddress::TcpIpV6 { addr, .. } if addr[..12] == IPV4_MAPPED_IPV6_PREFIX => {
+				Self::from_ipv4(&[addr[12], addr[13], addr[14], addr[15]])
+			},
+			SocketAddress::TcpIpV6 { addr, .. } if *addr == LOOPBACK_IPV6 => None,
+			SocketAddress::TcpIpV6 { addr, .. } => {
+				let mut prefix = [0; 6];
+				prefix.copy_from_slice(&addr[..6]);
+				Some(IpPrefix::V6(prefix))
+			},
+			_ => None,
+		}
+	}
+
+	fn from_ipv4(addr: &[u8; 4]) -> Option<Self> {
+		if addr[0] == 127 { None } else { Some(IpPrefix::V4([addr[0], addr[1], addr[2]])) }
+	}
+}
+
+/// A peer which has been temporarily banned for exceeding its [`PeerLimits`], see
+/// [`PeerManager::list_banned_peers`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum BannedPeer {
+	/// Connections with the given node id are refused once the handshake completes.
+	NodeId(PublicKey),
+	/// Inbound connections from the given IP prefix are refused.
+	IpPrefix(IpPrefix),
+}
+
+/// The classes of messages which may be limited by [`PeerLimits`].
+#[derive(Clone, Copy)]
+enum RateLimitedMessageClass {
+	Gossip = 0,
+	GossipQuery = 1,
+	OnionMessage = 2,
+	Ping = 3,
+}
+
+const RATE_LIMITED_MESSAGE_CLASSES: usize = 4;
+
+impl PeerLimits {
+	fn rate_limit(&self, class: RateLimitedMessageClass) -> Option<RateLimit> {
+		match class {
+			RateLimitedMessageClass::Gossip => self.gossip_rate_limit,
+			RateLimitedMessageClass::GossipQuery => self.gossip_query_rate_limit,
+			RateLimitedMessageClass::OnionMessage => self.onion_message_rate_limit,
+			RateLimitedMessageClass::Ping => self.ping_rate_limit,
+		}
+	}
+
+	fn initial_rate_limit_tokens(&self) -> [u32; RATE_LIMITED_MESSAGE_CLASSES] {
+		[
+			self.gossip_rate_limit.map_or(0, |limit| limit.burst),
+			self.gossip_query_rate_limit.map_or(0, |limit| limit.burst),
+			self.onion_message_rate_limit.map_or(0, |limit| limit.burst),
+			self.ping_rate_limit.map_or(0, |limit| limit.burst),
+		]
+	}
+}
+
 enum InitSyncTracker{
 	NoSyncRequested,
 	ChannelsSyncing(u64),
@@ -529,6 +707,9 @@ struct Peer {
 	pending_read_is_header: bool,
 
 	sync_status: InitSyncTracker,
+	/// Indicates the peer sent us a `query_short_channel_ids` which we haven't finished replying
+	/// to. Replies are pulled from the route handler as the outbound buffer drains.
+	awaiting_short_channel_ids_reply: bool,
 
 	msgs_sent_since_pong: usize,
 	awaiting_pong_timer_tick_intervals: i64,
@@ -542,6 +723,11 @@ struct Peer {
 	received_channel_announce_since_backlogged: bool,
 
 	inbound_connection: bool,
+
+	/// The tokens left in each of the peer's [`RateLimit`] buckets, indexed by
+	/// [`RateLimitedMessageClass`].
+	rate_limit_tokens: [u32; RATE_LIMITED_MESSAGE_CLASSES],
+	rate_limit_violations_since_timer_tick: u32,
 }
 
 impl Peer {
@@ -646,7 +832,8 @@ pub type SimpleArcPeerManager<SD, M, T, F, C, L> = PeerManager<
 	Arc<SimpleArcOnionMessenger<M, T, F, L>>,
 	Arc<L>,
 	IgnoringMessageHandler,
-	Arc<KeysManager>
+	Arc<KeysManager>,
+	IgnoringMetricsRecorder
 >;
 
 /// SimpleRefPeerManager is a type alias for a PeerManager reference, and is the reference
@@ -667,7 +854,8 @@ pub type SimpleRefPeerManager<
 	&'h SimpleRefOnionMessenger<'a, 'b, 'c, 'd, 'e, 'graph, 'logger, 'i, 'j, 'k, M, T, F, L>,
 	&'logger L,
 	IgnoringMessageHandler,
-	&'c KeysManager
+	&'c KeysManager,
+	IgnoringMetricsRecorder
 >;
 
 
@@ -692,20 +880,23 @@ pub trait APeerManager {
 	type CMH: Deref<Target=Self::CMHT>;
 	type NST: NodeSigner + ?Sized;
 	type NS: Deref<Target=Self::NST>;
+	type MRT: MetricsRecorder + ?Sized;
+	type MR: Deref<Target=Self::MRT>;
 	/// Gets a reference to the underlying [`PeerManager`].
-	fn as_ref(&self) -> &PeerManager<Self::Descriptor, Self::CM, Self::RM, Self::OM, Self::L, Self::CMH, Self::NS>;
+	fn as_ref(&self) -> &PeerManager<Self::Descriptor, Self::CM, Self::RM, Self::OM, Self::L, Self::CMH, Self::NS, Self::MR>;
 	/// Returns the peer manager's [`OnionMessageHandler`].
 	fn onion_message_handler(&self) -> &Self::OMT;
 }
 
-impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref>
-APeerManager for PeerManager<Descriptor, CM, RM, OM, L, CMH, NS> where
+impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref, MR: Deref>
+APeerManager for PeerManager<Descriptor, CM, RM, OM, L, CMH, NS, MR> where
 	CM::Target: ChannelMessageHandler,
 	RM::Target: RoutingMessageHandler,
 	OM::Target: OnionMessageHandler,
 	L::Target: Logger,
 	CMH::Target: CustomMessageHandler,
 	NS::Target: NodeSigner,
+	MR::Target: MetricsRecorder,
 {
 	type Descriptor = Descriptor;
 	type CMT = <CM as Deref>::Target;
@@ -720,7 +911,9 @@ APeerManager for PeerManager<Descriptor, CM, RM, OM, L, CMH, NS> where
 	type CMH = CMH;
 	type NST = <NS as Deref>::Target;
 	type NS = NS;
-	fn as_ref(&self) -> &PeerManager<Descriptor, CM, RM, OM, L, CMH, NS> { self }
+	type MRT = <MR as Deref>::Target;
+	type MR = MR;
+	fn as_ref(&self) -> &PeerManager<Descriptor, CM, RM, OM, L, CMH, NS, MR> { self }
 	fn onion_message_handler(&self) -> &Self::OMT {
 		self.message_handler.onion_message_handler.deref()
 	}
@@ -745,13 +938,14 @@ APeerManager for PeerManager<Descriptor, CM, RM, OM, L, CMH, NS> where
 /// you're using lightning-net-tokio.
 ///
 /// [`read_event`]: PeerManager::read_event
-pub struct PeerManager<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref> where
+pub struct PeerManager<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref, MR: Deref> where
 		CM::Target: ChannelMessageHandler,
 		RM::Target: RoutingMessageHandler,
 		OM::Target: OnionMessageHandler,
 		L::Target: Logger,
 		CMH::Target: CustomMessageHandler,
-		NS::Target: NodeSigner {
+		NS::Target: NodeSigner,
+		MR::Target: MetricsRecorder {
 	message_handler: MessageHandler<CM, RM, OM, CMH>,
 	/// Connection state for each connected peer - we have an outer read-write lock which is taken
 	/// as read while we're doing processing for a peer and taken write when a peer is being added
@@ -791,9 +985,17 @@ pub struct PeerManager<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: D
 	gossip_processing_backlogged: AtomicBool,
 	gossip_processing_backlog_lifted: AtomicBool,
 
+	limits: PeerLimits,
+	/// Peers which have been banned for exceeding their [`PeerLimits`], along with the number of
+	/// timer ticks left until the ban is lifted.
+	///
+	/// Locked *after* peers and any individual [`Peer`] lock.
+	banned_peers: Mutex<HashMap<BannedPeer, u32>>,
+
 	node_signer: NS,
 
 	logger: L,
+	metrics_recorder: MR,
 	secp_ctx: Secp256k1<secp256k1::SignOnly>
 }
 
@@ -822,7 +1024,7 @@ macro_rules! encode_msg {
 	}}
 }
 
-impl<Descriptor: SocketDescriptor, CM: Deref, OM: Deref, L: Deref, NS: Deref> PeerManager<Descriptor, CM, IgnoringMessageHandler, OM, L, IgnoringMessageHandler, NS> where
+impl<Descriptor: SocketDescriptor, CM: Deref, OM: Deref, L: Deref, NS: Deref> PeerManager<Descriptor, CM, IgnoringMessageHandler, OM, L, IgnoringMessageHandler, NS, IgnoringMetricsRecorder> where
 		CM::Target: ChannelMessageHandler,
 		OM::Target: OnionMessageHandler,
 		L::Target: Logger,
@@ -846,11 +1048,11 @@ impl<Descriptor: SocketDescriptor, CM: Deref, OM: Deref, L: Deref, NS: Deref> Pe
 			route_handler: IgnoringMessageHandler{},
 			onion_message_handler,
 			custom_message_handler: IgnoringMessageHandler{},
-		}, current_time, ephemeral_random_data, logger, node_signer)
+		}, current_time, ephemeral_random_data, logger, node_signer, IgnoringMetricsRecorder {})
 	}
 }
 
-impl<Descriptor: SocketDescriptor, RM: Deref, L: Deref, NS: Deref> PeerManager<Descriptor, ErroringMessageHandler, RM, IgnoringMessageHandler, L, IgnoringMessageHandler, NS> where
+impl<Descriptor: SocketDescriptor, RM: Deref, L: Deref, NS: Deref> PeerManager<Descriptor, ErroringMessageHandler, RM, IgnoringMessageHandler, L, IgnoringMessageHandler, NS, IgnoringMetricsRecorder> where
 		RM::Target: RoutingMessageHandler,
 		L::Target: Logger,
 		NS::Target: NodeSigner {
@@ -874,7 +1076,7 @@ impl<Descriptor: SocketDescriptor, RM: Deref, L: Deref, NS: Deref> PeerManager<D
 			route_handler: routing_message_handler,
 			onion_message_handler: IgnoringMessageHandler{},
 			custom_message_handler: IgnoringMessageHandler{},
-		}, current_time, ephemeral_random_data, logger, node_signer)
+		}, current_time, ephemeral_random_data, logger, node_signer, IgnoringMetricsRecorder {})
 	}
 }
 
@@ -919,13 +1121,14 @@ fn filter_addresses(ip_address: Option<SocketAddress>) -> Option<SocketAddress>
 	}
 }
 
-impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref> PeerManager<Descriptor, CM, RM, OM, L, CMH, NS> where
+impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CMH: Deref, NS: Deref, MR: Deref> PeerManager<Descriptor, CM, RM, OM, L, CMH, NS, MR> where
 		CM::Target: ChannelMessageHandler,
 		RM::Target: RoutingMessageHandler,
 		OM::Target: OnionMessageHandler,
 		L::Target: Logger,
 		CMH::Target: CustomMessageHandler,
-		NS::Target: NodeSigner
+		NS::Target: NodeSigner,
+		MR::Target: MetricsRecorder
 {
 	/// Constructs a new `PeerManager` with the given message handlers.
 	///
@@ -936,7 +1139,20 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 	/// incremented irregularly internally. In general it is best to simply use the current UNIX
 	/// timestamp, however if it is not available a persistent counter that increases once per
 	/// minute should suffice.
-	pub fn new(message_handler: MessageHandler<CM, RM, OM, CMH>, current_time: u32, ephemeral_random_data: &[u8; 32], logger: L, node_signer: NS) -> Self {
+	///
+	/// The number of messages sent and received, by message type, is reported to the given
+	/// [`MetricsRecorder`]. Use [`IgnoringMetricsRecorder`] if metrics are not needed.
+	///
+	/// The default [`PeerLimits`] are used, see [`Self::new_with_limits`] to configure them.
+	pub fn new(message_handler: MessageHandler<CM, RM, OM, CMH>, current_time: u32, ephemeral_random_data: &[u8; 32], logger: L, node_signer: NS, metrics_recorder: MR) -> Self {
+		Self::new_with_limits(message_handler, PeerLimits::default(), current_time, ephemeral_random_data, logger, node_signer, metrics_recorder)
+	}
+
+	/// Constructs a new `PeerManager` with the given message handlers, enforcing the given
+	/// per-peer [`PeerLimits`].
+	///
+	/// See [`Self::new`] for details on the remaining arguments.
+	pub fn new_with_limits(message_handler: MessageHandler<CM, RM, OM, CMH>, limits: PeerLimits, current_time: u32, ephemeral_random_data: &[u8; 32], logger: L, node_signer: NS, metrics_recorder: MR) -> Self {
 		let mut ephemeral_key_midstate = Sha256::engine();
 		ephemeral_key_midstate.input(ephemeral_random_data);
 
@@ -953,9 +1169,12 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 			peer_counter: AtomicCounter::new(),
 			gossip_processing_backlogged: AtomicBool::new(false),
 			gossip_processing_backlog_lifted: AtomicBool::new(false),
+			limits,
+			banned_peers: Mutex::new(HashMap::new()),
 			last_node_announcement_serial: AtomicU32::new(current_time),
 			logger,
 			node_signer,
+			metrics_recorder,
 			secp_ctx,
 		}
 	}
@@ -1038,6 +1257,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					pending_read_is_header: false,
 
 					sync_status: InitSyncTracker::NoSyncRequested,
+					awaiting_short_channel_ids_reply: false,
 
 					msgs_sent_since_pong: 0,
 					awaiting_pong_timer_tick_intervals: 0,
@@ -1046,6 +1266,9 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 
 					received_channel_announce_since_backlogged: false,
 					inbound_connection: false,
+
+					rate_limit_tokens: self.limits.initial_rate_limit_tokens(),
+					rate_limit_violations_since_timer_tick: 0,
 				}));
 				Ok(res)
 			}
@@ -1061,7 +1284,9 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 	///
 	/// May refuse the connection by returning an Err, but will never write bytes to the remote end
 	/// (outbound connector always speaks first). If an `Err` is returned here you must disconnect
-	/// the connection immediately.
+	/// the connection immediately. Connections are refused if the remote network address belongs
+	/// to a banned [`IpPrefix`] or the prefix has reached
+	/// [`PeerLimits::max_inbound_connections_per_ip_prefix`].
 	///
 	/// Panics if descriptor is duplicative with some other descriptor which has not yet been
 	/// [`socket_disconnected`].
@@ -1072,6 +1297,23 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 		let pending_read_buffer = [0; 50].to_vec(); // Noise act one is 50 bytes
 
 		let mut peers = self.peers.write().unwrap();
+		if let Some(ip_prefix) = remote_network_address.as_ref().and_then(IpPrefix::from_socket_address) {
+			if self.banned_peers.lock().unwrap().contains_key(&BannedPeer::IpPrefix(ip_prefix)) {
+				log_debug!(self.logger, "Refusing inbound connection from banned IP prefix {:?}", ip_prefix);
+				return Err(PeerHandleError {});
+			}
+			if let Some(max_connections) = self.limits.max_inbound_connections_per_ip_prefix {
+				let connections = peers.values().filter(|peer_mutex| {
+					let peer = peer_mutex.lock().unwrap();
+					peer.inbound_connection && peer.their_socket_address.as_ref()
+						.and_then(IpPrefix::from_socket_address) == Some(ip_prefix)
+				}).count();
+				if connections >= max_connections {
+					log_debug!(self.logger, "Refusing inbound connection as IP prefix {:?} already has {} connections", ip_prefix, connections);
+					return Err(PeerHandleError {});
+				}
+			}
+		}
 		match peers.entry(descriptor) {
 			hash_map::Entry::Occupied(_) => {
 				debug_assert!(false, "PeerManager driver duplicated descriptors!");
@@ -1094,6 +1336,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					pending_read_is_header: false,
 
 					sync_status: InitSyncTracker::NoSyncRequested,
+					awaiting_short_channel_ids_reply: false,
 
 					msgs_sent_since_pong: 0,
 					awaiting_pong_timer_tick_intervals: 0,
@@ -1102,6 +1345,9 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 
 					received_channel_announce_since_backlogged: false,
 					inbound_connection: true,
+
+					rate_limit_tokens: self.limits.initial_rate_limit_tokens(),
+					rate_limit_violations_since_timer_tick: 0,
 				}));
 				Ok(())
 			}
@@ -1136,6 +1382,29 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					peer.pending_outbound_buffer.push_back(peer.channel_encryptor.encrypt_buffer(msg));
 				}
 			}
+			if peer.awaiting_short_channel_ids_reply && peer.should_buffer_gossip_backfill() {
+				let their_node_id = peer.their_node_id.map(|(node_id, _)| node_id)
+					.expect("Queries are only handled after the handshake completes");
+				match self.message_handler.route_handler.get_next_short_channel_ids_reply(&their_node_id) {
+					Some(msgs::ShortChannelIdsReply::ChannelAnnouncement(announce, update_a_option, update_b_option)) => {
+						self.enqueue_message(peer, &announce);
+						if let Some(update_a) = update_a_option {
+							self.enqueue_message(peer, &update_a);
+						}
+						if let Some(update_b) = update_b_option {
+							self.enqueue_message(peer, &update_b);
+						}
+					},
+					Some(msgs::ShortChannelIdsReply::NodeAnnouncement(msg)) => {
+						self.enqueue_message(peer, &msg);
+					},
+					Some(msgs::ShortChannelIdsReply::End(msg)) => {
+						self.enqueue_message(peer, &msg);
+						peer.awaiting_short_channel_ids_reply = false;
+					},
+					None => peer.awaiting_short_channel_ids_reply = false,
+				}
+			}
 			if peer.should_buffer_gossip_backfill() {
 				match peer.sync_status {
 					InitSyncTracker::NoSyncRequested => {},
@@ -1270,6 +1539,11 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 		}
 	}
 
+	/// Increments the given per-message-type metrics counter.
+	fn record_message_metric(&self, name: &'static str, type_id: u16) {
+		self.metrics_recorder.increment_counter(name, &[("type", message_type_label(type_id))], 1);
+	}
+
 	/// Append a message to a peer's pending outbound/write buffer
 	fn enqueue_message<M: wire::Type>(&self, peer: &mut Peer, message: &M) {
 		let logger = WithContext::from(&self.logger, peer.their_node_id.map(|p| p.0), None);
@@ -1279,12 +1553,14 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 			log_trace!(logger, "Enqueueing message {:?} to {}", message, log_pubkey!(peer.their_node_id.unwrap().0))
 		}
 		peer.msgs_sent_since_pong += 1;
+		self.record_message_metric(metrics::PEER_MESSAGES_SENT, message.type_id());
 		peer.pending_outbound_buffer.push_back(peer.channel_encryptor.encrypt_message(message));
 	}
 
 	/// Append a message to a peer's pending outbound/write gossip broadcast buffer
-	fn enqueue_encoded_gossip_broadcast(&self, peer: &mut Peer, encoded_message: MessageBuf) {
+	fn enqueue_encoded_gossip_broadcast(&self, peer: &mut Peer, encoded_message: MessageBuf, type_id: u16) {
 		peer.msgs_sent_since_pong += 1;
+		self.record_message_metric(metrics::PEER_MESSAGES_SENT, type_id);
 		debug_assert!(peer.gossip_broadcast_buffer.len() <= OUTBOUND_BUFFER_LIMIT_DROP_GOSSIP);
 		peer.gossip_broadcast_buffer.push_back(encoded_message);
 	}
@@ -1302,6 +1578,8 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 				return Err(PeerHandleError { });
 			},
 			Some(peer_mutex) => {
+				peer_node_id = peer_mutex.lock().unwrap().their_node_id.clone();
+				let mut peer_has_channels = None;
 				let mut read_pos = 0;
 				while read_pos < data.len() {
 					macro_rules! try_potential_handleerror {
@@ -1355,6 +1633,14 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 						}}
 					}
 
+					// Look up whether the peer is exempt from rate limits before taking its lock, as
+					// the `ChannelMessageHandler` may take its own locks.
+					if peer_has_channels.is_none() {
+						if let Some((node_id, _)) = peer_node_id {
+							peer_has_channels = Some(self.message_handler.chan_handler.peer_has_channels(&node_id));
+						}
+					}
+
 					let mut peer_lock = peer_mutex.lock().unwrap();
 					let peer = &mut *peer_lock;
 					let mut msg_to_handle = None;
@@ -1378,6 +1664,11 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 						macro_rules! insert_node_id {
 							() => {
 								let logger = WithContext::from(&self.logger, peer.their_node_id.map(|p| p.0), None);
+								if self.banned_peers.lock().unwrap().contains_key(&BannedPeer::NodeId(peer.their_node_id.unwrap().0)) {
+									log_debug!(logger, "Refusing connection with banned peer {}", log_pubkey!(peer.their_node_id.unwrap().0));
+									peer.their_node_id = None; // Unset so that we don't generate a peer_disconnected event
+									return Err(PeerHandleError { })
+								}
 								match self.node_id_to_descriptor.lock().unwrap().entry(peer.their_node_id.unwrap().0) {
 									hash_map::Entry::Occupied(e) => {
 										log_trace!(logger, "Got second connection with {}, closing", log_pubkey!(peer.their_node_id.unwrap().0));
@@ -1510,7 +1801,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					pause_read = !self.peer_should_read(peer);
 
 					if let Some(message) = msg_to_handle {
-						match self.handle_message(&peer_mutex, peer_lock, message) {
+						match self.handle_message(&peer_mutex, peer_lock, peer_has_channels.unwrap_or(false), message) {
 							Err(handling_error) => match handling_error {
 								MessageHandlingError::PeerHandleError(e) => { return Err(e) },
 								MessageHandlingError::LightningError(e) => {
@@ -1534,17 +1825,85 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 		Ok(pause_read)
 	}
 
+	/// Consumes a token from the peer's [`RateLimit`] bucket for the given message's class, if any,
+	/// returning whether the message should be processed.
+	///
+	/// Peers with which we have funded channels, as indicated by `peer_has_channels`, are never
+	/// limited. Other peers which exceed their rate limits too many times since the last timer tick
+	/// are banned, in which case an `Err` is returned and the peer must be disconnected.
+	fn consume_rate_limit_token(
+		&self, peer: &mut Peer, peer_has_channels: bool,
+		message: &wire::Message<<<CMH as core::ops::Deref>::Target as wire::CustomMessageReader>::CustomMessage>
+	) -> Result<bool, PeerHandleError> {
+		let class = match message {
+			wire::Message::ChannelAnnouncement(_) | wire::Message::ChannelUpdate(_) |
+				wire::Message::NodeAnnouncement(_) => RateLimitedMessageClass::Gossip,
+			wire::Message::QueryChannelRange(_) | wire::Message::QueryShortChannelIds(_) =>
+				RateLimitedMessageClass::GossipQuery,
+			wire::Message::OnionMessage(_) => RateLimitedMessageClass::OnionMessage,
+			wire::Message::Ping(_) => RateLimitedMessageClass::Ping,
+			_ => return Ok(true),
+		};
+		if self.limits.rate_limit(class).is_none() {
+			return Ok(true);
+		}
+		let tokens = &mut peer.rate_limit_tokens[class as usize];
+		if *tokens > 0 {
+			*tokens -= 1;
+			return Ok(true);
+		}
+		if peer_has_channels {
+			return Ok(true);
+		}
+
+		peer.rate_limit_violations_since_timer_tick += 1;
+		if peer.rate_limit_violations_since_timer_tick > self.limits.max_rate_limit_violations_per_tick {
+			let mut banned_peers = self.banned_peers.lock().unwrap();
+			if let Some((node_id, _)) = peer.their_node_id {
+				log_info!(WithContext::from(&self.logger, Some(node_id), None),
+					"Banning peer {} for {} timer ticks after exceeding its rate limits", log_pubkey!(node_id),
+					self.limits.ban_duration_ticks);
+				banned_peers.insert(BannedPeer::NodeId(node_id), self.limits.ban_duration_ticks);
+			}
+			if peer.inbound_connection {
+				if let Some(ip_prefix) = peer.their_socket_address.as_ref().and_then(IpPrefix::from_socket_address) {
+					banned_peers.insert(BannedPeer::IpPrefix(ip_prefix), self.limits.ban_duration_ticks);
+				}
+			}
+			return Err(PeerHandleError { });
+		}
+		Ok(false)
+	}
+
+	/// Returns the peers currently banned for exceeding their [`PeerLimits`], along with the
+	/// number of timer ticks left until each ban is lifted.
+	pub fn list_banned_peers(&self) -> Vec<(BannedPeer, u32)> {
+		self.banned_peers.lock().unwrap().iter().map(|(peer, ticks)| (*peer, *ticks)).collect()
+	}
+
+	/// Lifts the ban on the given peer, returning whether it was banned.
+	pub fn unban_peer(&self, peer: &BannedPeer) -> bool {
+		self.banned_peers.lock().unwrap().remove(peer).is_some()
+	}
+
+	/// Lifts all bans on peers.
+	pub fn clear_banned_peers(&self) {
+		self.banned_peers.lock().unwrap().clear();
+	}
+
 	/// Process an incoming message and return a decision (ok, lightning error, peer handling error) regarding the next action with the peer
 	/// Returns the message back if it needs to be broadcasted to all other peers.
 	fn handle_message(
 		&self,
 		peer_mutex: &Mutex<Peer>,
 		mut peer_lock: MutexGuard<Peer>,
+		peer_has_channels: bool,
 		message: wire::Message<<<CMH as core::ops::Deref>::Target as wire::CustomMessageReader>::CustomMessage>
 	) -> Result<Option<wire::Message<<<CMH as core::ops::Deref>::Target as wire::CustomMessageReader>::CustomMessage>>, MessageHandlingError> {
 		let their_node_id = peer_lock.their_node_id.clone().expect("We know the peer's public key by the time we receive messages").0;
 		let logger = WithContext::from(&self.logger, Some(their_node_id), None);
 		peer_lock.received_message_since_timer_tick = true;
+		self.record_message_metric(metrics::PEER_MESSAGES_RECEIVED, message.type_id());
 
 		// Need an Init as first message
 		if let wire::Message::Init(msg) = message {
@@ -1609,6 +1968,11 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 			return Err(PeerHandleError { }.into());
 		}
 
+		if !self.consume_rate_limit_token(&mut *peer_lock, peer_has_channels, &message)? {
+			log_trace!(logger, "Dropping message of type {} from {} as it exceeded its rate limit", message.type_id(), log_pubkey!(their_node_id));
+			return Ok(None);
+		}
+
 		if let wire::Message::GossipTimestampFilter(_msg) = message {
 			// When supporting gossip messages, start initial gossip sync only after we receive
 			// a GossipTimestampFilter
@@ -1694,6 +2058,14 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 				self.message_handler.chan_handler.handle_stfu(&their_node_id, &msg);
 			}
 
+			// Peer storage messages:
+			wire::Message::PeerStorage(msg) => {
+				self.message_handler.chan_handler.handle_peer_storage(&their_node_id, &msg);
+			}
+			wire::Message::PeerStorageRetrieval(msg) => {
+				self.message_handler.chan_handler.handle_peer_storage_retrieval(&their_node_id, &msg);
+			}
+
 			// Splicing messages:
 			wire::Message::Splice(msg) => {
 				self.message_handler.chan_handler.handle_splice(&their_node_id, &msg);
@@ -1796,6 +2168,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 			},
 			wire::Message::QueryShortChannelIds(msg) => {
 				self.message_handler.route_handler.handle_query_short_channel_ids(&their_node_id, msg)?;
+				peer_mutex.lock().unwrap().awaiting_short_channel_ids_reply = true;
 			},
 			wire::Message::ReplyShortChannelIdsEnd(msg) => {
 				self.message_handler.route_handler.handle_reply_short_channel_ids_end(&their_node_id, msg)?;
@@ -1854,7 +2227,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					if except_node.is_some() && peer.their_node_id.as_ref().map(|(pk, _)| pk) == except_node {
 						continue;
 					}
-					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg));
+					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg), msg.type_id());
 				}
 			},
 			wire::Message::NodeAnnouncement(ref msg) => {
@@ -1882,7 +2255,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					if except_node.is_some() && peer.their_node_id.as_ref().map(|(pk, _)| pk) == except_node {
 						continue;
 					}
-					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg));
+					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg), msg.type_id());
 				}
 			},
 			wire::Message::ChannelUpdate(ref msg) => {
@@ -1905,7 +2278,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					if except_node.is_some() && peer.their_node_id.as_ref().map(|(pk, _)| pk) == except_node {
 						continue;
 					}
-					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg));
+					self.enqueue_encoded_gossip_broadcast(&mut *peer, MessageBuf::from_encoded(&encoded_msg), msg.type_id());
 				}
 			},
 			_ => debug_assert!(false, "We shouldn't attempt to forward anything but gossip messages"),
@@ -2025,6 +2398,16 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 									&msg.channel_id);
 							self.enqueue_message(&mut *get_peer_for_forwarding!(node_id), msg);
 						},
+						MessageSendEvent::SendPeerStorage { ref node_id, ref msg } => {
+							log_debug!(WithContext::from(&self.logger, Some(*node_id), None), "Handling SendPeerStorage event in peer_handler for node {}",
+									log_pubkey!(node_id));
+							self.enqueue_message(&mut *get_peer_for_forwarding!(node_id), msg);
+						},
+						MessageSendEvent::SendPeerStorageRetrieval { ref node_id, ref msg } => {
+							log_debug!(WithContext::from(&self.logger, Some(*node_id), None), "Handling SendPeerStorageRetrieval event in peer_handler for node {}",
+									log_pubkey!(node_id));
+							self.enqueue_message(&mut *get_peer_for_forwarding!(node_id), msg);
+						},
 						MessageSendEvent::SendStfu { ref node_id, ref msg} => {
 							let logger = WithContext::from(&self.logger, Some(*node_id), Some(msg.channel_id));
 							log_debug!(logger, "Handling SendStfu event in peer_handler for node {} for channel {}",
@@ -2333,6 +2716,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 		debug_assert!(peer.their_node_id.is_some());
 		if let Some((node_id, _)) = peer.their_node_id {
 			log_trace!(WithContext::from(&self.logger, Some(node_id), None), "Disconnecting peer with id {} due to {}", node_id, reason);
+			self.message_handler.route_handler.peer_disconnected(&node_id);
 			self.message_handler.chan_handler.peer_disconnected(&node_id);
 			self.message_handler.onion_message_handler.peer_disconnected(&node_id);
 		}
@@ -2355,6 +2739,7 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 					let removed = self.node_id_to_descriptor.lock().unwrap().remove(&node_id);
 					debug_assert!(removed.is_some(), "descriptor maps should be consistent");
 					if !peer.handshake_complete() { return; }
+					self.message_handler.route_handler.peer_disconnected(&node_id);
 					self.message_handler.chan_handler.peer_disconnected(&node_id);
 					self.message_handler.onion_message_handler.peer_disconnected(&node_id);
 				}
@@ -2427,6 +2812,16 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 				let mut peer = peer_mutex.lock().unwrap();
 				if flush_read_disabled { peer.received_channel_announce_since_backlogged = false; }
 
+				peer.rate_limit_violations_since_timer_tick = 0;
+				for class in [RateLimitedMessageClass::Gossip, RateLimitedMessageClass::GossipQuery,
+					RateLimitedMessageClass::OnionMessage, RateLimitedMessageClass::Ping]
+				{
+					if let Some(limit) = self.limits.rate_limit(class) {
+						let tokens = &mut peer.rate_limit_tokens[class as usize];
+						*tokens = cmp::min(tokens.saturating_add(limit.refill_per_tick), limit.burst);
+					}
+				}
+
 				if !peer.handshake_complete() {
 					// The peer needs to complete its handshake before we can exchange messages. We
 					// give peers one timer tick to complete handshake, reusing
@@ -2490,6 +2885,11 @@ impl<Descriptor: SocketDescriptor, CM: Deref, RM: Deref, OM: Deref, L: Deref, CM
 				}
 			}
 		}
+
+		self.banned_peers.lock().unwrap().retain(|_, ticks_remaining| {
+			*ticks_remaining = ticks_remaining.saturating_sub(1);
+			*ticks_remaining > 0
+		});
 	}
 
 	#[allow(dead_code)]
@@ -2575,6 +2975,61 @@ fn is_gossip_msg(type_id: u16) -> bool {
 	}
 }
 
+/// Maps a message type to the label used when recording [`MetricsRecorder`] counters, such that
+/// custom or unknown messages don't blow up the number of distinct labels.
+fn message_type_label(type_id: u16) -> &'static str {
+	match type_id {
+		msgs::Init::TYPE => "init",
+		msgs::ErrorMessage::TYPE => "error",
+		msgs::WarningMessage::TYPE => "warning",
+		msgs::Ping::TYPE => "ping",
+		msgs::Pong::TYPE => "pong",
+		msgs::PeerStorage::TYPE => "peer_storage",
+		msgs::PeerStorageRetrieval::TYPE => "peer_storage_retrieval",
+		msgs::OpenChannel::TYPE => "open_channel",
+		msgs::AcceptChannel::TYPE => "accept_channel",
+		msgs::FundingCreated::TYPE => "funding_created",
+		msgs::FundingSigned::TYPE => "funding_signed",
+		msgs::ChannelReady::TYPE => "channel_ready",
+		msgs::Stfu::TYPE => "stfu",
+		msgs::Splice::TYPE => "splice",
+		msgs::SpliceAck::TYPE => "splice_ack",
+		msgs::SpliceLocked::TYPE => "splice_locked",
+		msgs::Shutdown::TYPE => "shutdown",
+		msgs::ClosingSigned::TYPE => "closing_signed",
+		msgs::OpenChannelV2::TYPE => "open_channel2",
+		msgs::AcceptChannelV2::TYPE => "accept_channel2",
+		msgs::TxAddInput::TYPE => "tx_add_input",
+		msgs::TxAddOutput::TYPE => "tx_add_output",
+		msgs::TxRemoveInput::TYPE => "tx_remove_input",
+		msgs::TxRemoveOutput::TYPE => "tx_remove_output",
+		msgs::TxComplete::TYPE => "tx_complete",
+		msgs::TxSignatures::TYPE => "tx_signatures",
+		msgs::TxInitRbf::TYPE => "tx_init_rbf",
+		msgs::TxAckRbf::TYPE => "tx_ack_rbf",
+		msgs::TxAbort::TYPE => "tx_abort",
+		msgs::UpdateAddHTLC::TYPE => "update_add_htlc",
+		msgs::UpdateFulfillHTLC::TYPE => "update_fulfill_htlc",
+		msgs::UpdateFailHTLC::TYPE => "update_fail_htlc",
+		msgs::CommitmentSigned::TYPE => "commitment_signed",
+		msgs::RevokeAndACK::TYPE => "revoke_and_ack",
+		msgs::UpdateFee::TYPE => "update_fee",
+		msgs::UpdateFailMalformedHTLC::TYPE => "update_fail_malformed_htlc",
+		msgs::ChannelReestablish::TYPE => "channel_reestablish",
+		msgs::ChannelAnnouncement::TYPE => "channel_announcement",
+		msgs::NodeAnnouncement::TYPE => "node_announcement",
+		msgs::ChannelUpdate::TYPE => "channel_update",
+		msgs::AnnouncementSignatures::TYPE => "announcement_signatures",
+		msgs::QueryShortChannelIds::TYPE => "query_short_channel_ids",
+		msgs::ReplyShortChannelIdsEnd::TYPE => "reply_short_channel_ids_end",
+		msgs::QueryChannelRange::TYPE => "query_channel_range",
+		msgs::ReplyChannelRange::TYPE => "reply_channel_range",
+		msgs::GossipTimestampFilter::TYPE => "gossip_timestamp_filter",
+		msgs::OnionMessage::TYPE => "onion_message",
+		_ => "unknown",
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::sign::{NodeSigner, Recipient};
@@ -2584,9 +3039,10 @@ mod tests {
 	use crate::ln::features::{InitFeatures, NodeFeatures};
 	use crate::ln::peer_channel_encryptor::PeerChannelEncryptor;
 	use crate::ln::peer_handler::{CustomMessageHandler, PeerManager, MessageHandler, SocketDescriptor, IgnoringMessageHandler, filter_addresses};
+	use crate::ln::peer_handler::{BannedPeer, IpPrefix, PeerLimits, RateLimit, RateLimitedMessageClass};
 	use crate::ln::{msgs, wire};
 	use crate::ln::msgs::{LightningError, SocketAddress};
-	use crate::util::test_utils;
+	use crate::util::{metrics, test_utils};
 
 	use bitcoin::Network;
 	use bitcoin::blockdata::constants::ChainHash;
@@ -2630,6 +3086,7 @@ mod tests {
 		custom_handler: TestCustomMessageHandler,
 		logger: test_utils::TestLogger,
 		node_signer: test_utils::TestNodeSigner,
+		metrics: test_utils::TestMetricsRecorder,
 	}
 
 	struct TestCustomMessageHandler {
@@ -2673,6 +3130,7 @@ mod tests {
 					routing_handler: test_utils::TestRoutingMessageHandler::new(),
 					custom_handler: TestCustomMessageHandler { features },
 					node_signer: test_utils::TestNodeSigner::new(node_secret),
+					metrics: test_utils::TestMetricsRecorder::new(),
 				}
 			);
 		}
@@ -2696,6 +3154,7 @@ mod tests {
 					routing_handler: test_utils::TestRoutingMessageHandler::new(),
 					custom_handler: TestCustomMessageHandler { features },
 					node_signer: test_utils::TestNodeSigner::new(node_secret),
+					metrics: test_utils::TestMetricsRecorder::new(),
 				}
 			);
 		}
@@ -2716,6 +3175,7 @@ mod tests {
 					routing_handler: test_utils::TestRoutingMessageHandler::new(),
 					custom_handler: TestCustomMessageHandler { features },
 					node_signer: test_utils::TestNodeSigner::new(node_secret),
+					metrics: test_utils::TestMetricsRecorder::new(),
 				}
 			);
 		}
@@ -2723,7 +3183,7 @@ mod tests {
 		cfgs
 	}
 
-	fn create_network<'a>(peer_count: usize, cfgs: &'a Vec<PeerManagerCfg>) -> Vec<PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner>> {
+	fn create_network<'a>(peer_count: usize, cfgs: &'a Vec<PeerManagerCfg>) -> Vec<PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner, &'a test_utils::TestMetricsRecorder>> {
 		let mut peers = Vec::new();
 		for i in 0..peer_count {
 			let ephemeral_bytes = [i as u8; 32];
@@ -2731,14 +3191,18 @@ mod tests {
 				chan_handler: &cfgs[i].chan_handler, route_handler: &cfgs[i].routing_handler,
 				onion_message_handler: IgnoringMessageHandler {}, custom_message_handler: &cfgs[i].custom_handler
 			};
-			let peer = PeerManager::new(msg_handler, 0, &ephemeral_bytes, &cfgs[i].logger, &cfgs[i].node_signer);
+			let peer = PeerManager::new(msg_handler, 0, &ephemeral_bytes, &cfgs[i].logger, &cfgs[i].node_signer, &cfgs[i].metrics);
 			peers.push(peer);
 		}
 
 		peers
 	}
 
-	fn establish_connection<'a>(peer_a: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner>, peer_b: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner>) -> (FileDescriptor, FileDescriptor) {
+	fn establish_connection<'a>(peer_a: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner, &'a test_utils::TestMetricsRecorder>, peer_b: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner, &'a test_utils::TestMetricsRecorder>) -> (FileDescriptor, FileDescriptor) {
+		establish_connection_from(peer_a, peer_b, SocketAddress::TcpIpV4{addr: [127, 0, 0, 1], port: 1001})
+	}
+
+	fn establish_connection_from<'a>(peer_a: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner, &'a test_utils::TestMetricsRecorder>, peer_b: &PeerManager<FileDescriptor, &'a test_utils::TestChannelMessageHandler, &'a test_utils::TestRoutingMessageHandler, IgnoringMessageHandler, &'a test_utils::TestLogger, &'a TestCustomMessageHandler, &'a test_utils::TestNodeSigner, &'a test_utils::TestMetricsRecorder>, addr_b: SocketAddress) -> (FileDescriptor, FileDescriptor) {
 		let id_a = peer_a.node_signer.get_node_id(Recipient::Node).unwrap();
 		let mut fd_a = FileDescriptor {
 			fd: 1, outbound_data: Arc::new(Mutex::new(Vec::new())),
@@ -2750,7 +3214,6 @@ mod tests {
 			fd: 1, outbound_data: Arc::new(Mutex::new(Vec::new())),
 			disconnect: Arc::new(AtomicBool::new(false)),
 		};
-		let addr_b = SocketAddress::TcpIpV4{addr: [127, 0, 0, 1], port: 1001};
 		let initial_data = peer_b.new_outbound_connection(id_a, fd_b.clone(), Some(addr_a.clone())).unwrap();
 		peer_a.new_inbound_connection(fd_a.clone(), Some(addr_b.clone())).unwrap();
 		assert_eq!(peer_a.read_event(&mut fd_a, &initial_data).unwrap(), false);
@@ -2972,6 +3435,39 @@ mod tests {
 		assert_eq!(peers[1].read_event(&mut fd_b, &a_data).unwrap(), false);
 	}
 
+	#[test]
+	fn test_message_metrics() {
+		// Check that messages sent and received are counted, by type, in each peer's
+		// `MetricsRecorder`.
+		let cfgs = create_peermgr_cfgs(2);
+		let peers = create_network(2, &cfgs);
+		let (fd_a, mut fd_b) = establish_connection(&peers[0], &peers[1]);
+
+		// Only the `init` messages have been exchanged so far.
+		let init_type = "init";
+		for cfg in cfgs.iter() {
+			assert_eq!(cfg.metrics.counter(metrics::PEER_MESSAGES_SENT, &[("type", init_type)]), 1);
+			assert_eq!(cfg.metrics.counter(metrics::PEER_MESSAGES_RECEIVED, &[("type", init_type)]), 1);
+		}
+
+		let their_id = peers[1].node_signer.get_node_id(Recipient::Node).unwrap();
+		let msg = msgs::Shutdown { channel_id: ChannelId::from_bytes([42; 32]), scriptpubkey: bitcoin::ScriptBuf::new() };
+		cfgs[0].chan_handler.pending_events.lock().unwrap().push(events::MessageSendEvent::SendShutdown {
+			node_id: their_id, msg: msg.clone()
+		});
+		cfgs[1].chan_handler.expect_receive_msg(wire::Message::Shutdown(msg));
+		peers[0].process_events();
+
+		let a_data = fd_a.outbound_data.lock().unwrap().split_off(0);
+		assert_eq!(peers[1].read_event(&mut fd_b, &a_data).unwrap(), false);
+
+		let shutdown_type = "shutdown";
+		assert_eq!(cfgs[0].metrics.counter(metrics::PEER_MESSAGES_SENT, &[("type", shutdown_type)]), 1);
+		assert_eq!(cfgs[0].metrics.counter(metrics::PEER_MESSAGES_RECEIVED, &[("type", shutdown_type)]), 0);
+		assert_eq!(cfgs[1].metrics.counter(metrics::PEER_MESSAGES_SENT, &[("type", shutdown_type)]), 0);
+		assert_eq!(cfgs[1].metrics.counter(metrics::PEER_MESSAGES_RECEIVED, &[("type", shutdown_type)]), 1);
+	}
+
 	#[test]
 	fn test_non_init_first_msg() {
 		// Simple test of the first message received over a connection being something other than
@@ -3036,6 +3532,185 @@ mod tests {
 		assert_eq!(peers[0].peers.read().unwrap().len(), 0);
 	}
 
+	#[test]
+	fn test_rate_limit_ban() {
+		// Check that messages exceeding a peer's rate limit are dropped, and that a peer repeatedly
+		// exceeding it is disconnected and banned until the ban expires or is lifted.
+		let cfgs = create_peermgr_cfgs(2);
+		let mut peers = create_network(2, &cfgs);
+		peers[0].limits = PeerLimits {
+			gossip_query_rate_limit: Some(RateLimit { burst: 2, refill_per_tick: 1 }),
+			max_rate_limit_violations_per_tick: 1,
+			ban_duration_ticks: 2,
+			..Default::default()
+		};
+		let addr_b = SocketAddress::TcpIpV4 { addr: [10, 0, 0, 2], port: 1001 };
+		let (mut fd_a, fd_b) = establish_connection_from(&peers[0], &peers[1], addr_b);
+		let id_b = peers[1].node_signer.get_node_id(Recipient::Node).unwrap();
+
+		let send_queries = |count: usize| {
+			{
+				let peers_lock = peers[1].peers.read().unwrap();
+				let mut peer = peers_lock.get(&fd_b).unwrap().lock().unwrap();
+				for _ in 0..count {
+					peers[1].enqueue_message(&mut *peer, &msgs::QueryChannelRange {
+						chain_hash: ChainHash::using_genesis_block(Network::Testnet), first_blocknum: 0,
+						number_of_blocks: u32::max_value(),
+					});
+				}
+			}
+			peers[1].process_events();
+			fd_b.outbound_data.lock().unwrap().split_off(0)
+		};
+
+		// The first two queries consume the burst, the third is dropped.
+		assert_eq!(peers[0].read_event(&mut fd_a, &send_queries(3)).unwrap(), false);
+		{
+			let peers_lock = peers[0].peers.read().unwrap();
+			let peer = peers_lock.get(&fd_a).unwrap().lock().unwrap();
+			assert_eq!(peer.rate_limit_tokens[RateLimitedMessageClass::GossipQuery as usize], 0);
+			assert_eq!(peer.rate_limit_violations_since_timer_tick, 1);
+		}
+		assert!(peers[0].list_banned_peers().is_empty());
+
+		// Exceeding the rate limit again within the same tick gets the peer banned.
+		assert!(peers[0].read_event(&mut fd_a, &send_queries(1)).is_err());
+		assert_eq!(peers[0].peers.read().unwrap().len(), 0);
+		let mut banned_peers = peers[0].list_banned_peers();
+		banned_peers.sort_unstable_by_key(|(peer, _)| matches!(peer, BannedPeer::IpPrefix(_)));
+		assert_eq!(banned_peers, vec![
+			(BannedPeer::NodeId(id_b), 2), (BannedPeer::IpPrefix(IpPrefix::V4([10, 0, 0])), 2),
+		]);
+
+		// New inbound connections from the banned prefix are refused, as are connections with the
+		// banned node once the handshake completes.
+		let new_fd = |fd| FileDescriptor {
+			fd, outbound_data: Arc::new(Mutex::new(Vec::new())), disconnect: Arc::new(AtomicBool::new(false)),
+		};
+		let banned_addr = SocketAddress::TcpIpV4 { addr: [10, 0, 0, 3], port: 1000 };
+		assert!(peers[0].new_inbound_connection(new_fd(2), Some(banned_addr.clone())).is_err());
+
+		peers[1].socket_disconnected(&fd_b);
+		let id_a = peers[0].node_signer.get_node_id(Recipient::Node).unwrap();
+		let (mut fd_a, mut fd_b) = (new_fd(3), new_fd(3));
+		let initial_data = peers[1].new_outbound_connection(id_a, fd_b.clone(), None).unwrap();
+		peers[0].new_inbound_connection(fd_a.clone(), None).unwrap();
+		assert_eq!(peers[0].read_event(&mut fd_a, &initial_data).unwrap(), false);
+		peers[0].process_events();
+		let a_data = fd_a.outbound_data.lock().unwrap().split_off(0);
+		assert_eq!(peers[1].read_event(&mut fd_b, &a_data).unwrap(), false);
+		peers[1].process_events();
+		let b_data = fd_b.outbound_data.lock().unwrap().split_off(0);
+		assert!(peers[0].read_event(&mut fd_a, &b_data).is_err());
+		assert_eq!(peers[0].peers.read().unwrap().len(), 0);
+
+		peers[0].timer_tick_occurred();
+		assert_eq!(peers[0].list_banned_peers().len(), 2);
+		assert!(peers[0].unban_peer(&BannedPeer::NodeId(id_b)));
+		assert!(!peers[0].unban_peer(&BannedPeer::NodeId(id_b)));
+		assert_eq!(peers[0].list_banned_peers(), vec![(BannedPeer::IpPrefix(IpPrefix::V4([10, 0, 0])), 1)]);
+
+		peers[0].timer_tick_occurred();
+		assert!(peers[0].list_banned_peers().is_empty());
+		assert!(peers[0].new_inbound_connection(new_fd(4), Some(banned_addr)).is_ok());
+	}
+
+	#[test]
+	fn test_max_inbound_connections_per_ip_prefix() {
+		let cfgs = create_peermgr_cfgs(1);
+		let mut peers = create_network(1, &cfgs);
+		peers[0].limits.max_inbound_connections_per_ip_prefix = Some(1);
+		let new_fd = |fd| FileDescriptor {
+			fd, outbound_data: Arc::new(Mutex::new(Vec::new())), disconnect: Arc::new(AtomicBool::new(false)),
+		};
+
+		let addr = |addr| Some(SocketAddress::TcpIpV4 { addr, port: 9735 });
+		assert!(peers[0].new_inbound_connection(new_fd(1), addr([10, 0, 0, 1])).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(2), addr([10, 0, 0, 2])).is_err());
+		assert!(peers[0].new_inbound_connection(new_fd(3), addr([10, 0, 1, 1])).is_ok());
+		// Connections without a known address or from a loopback address aren't limited.
+		assert!(peers[0].new_inbound_connection(new_fd(4), None).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(5), None).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(9), addr([127, 0, 0, 1])).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(10), addr([127, 0, 0, 1])).is_ok());
+		let loopback_v6 = SocketAddress::TcpIpV6 { addr: super::LOOPBACK_IPV6, port: 9735 };
+		assert!(peers[0].new_inbound_connection(new_fd(11), Some(loopback_v6.clone())).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(12), Some(loopback_v6)).is_ok());
+
+		peers[0].socket_disconnected(&new_fd(1));
+		assert!(peers[0].new_inbound_connection(new_fd(6), addr([10, 0, 0, 2])).is_ok());
+
+		peers[0].clear_banned_peers();
+		peers[0].banned_peers.lock().unwrap().insert(BannedPeer::IpPrefix(IpPrefix::V4([10, 0, 2])), 1);
+		assert!(peers[0].new_inbound_connection(new_fd(7), addr([10, 0, 2, 1])).is_err());
+		peers[0].clear_banned_peers();
+		assert!(peers[0].new_inbound_connection(new_fd(8), addr([10, 0, 2, 1])).is_ok());
+	}
+
+	#[test]
+	fn test_ip_prefix_of_ipv4_mapped_ipv6_addresses() {
+		// IPv4 peers connecting to a dual-stack listener are reported with IPv4-mapped IPv6
+		// addresses, which must be grouped by their IPv4 prefix rather than all sharing ::ffff:0:0/48.
+		let mapped = |ipv4: [u8; 4]| {
+			let mut addr = [0; 16];
+			addr[10..12].copy_from_slice(&[0xff, 0xff]);
+			addr[12..].copy_from_slice(&ipv4);
+			SocketAddress::TcpIpV6 { addr, port: 9735 }
+		};
+		assert_eq!(IpPrefix::from_socket_address(&mapped([10, 0, 0, 1])), Some(IpPrefix::V4([10, 0, 0])));
+		assert_eq!(IpPrefix::from_socket_address(&mapped([10, 0, 1, 1])), Some(IpPrefix::V4([10, 0, 1])));
+		assert_eq!(IpPrefix::from_socket_address(&mapped([127, 0, 0, 1])), None);
+
+		let cfgs = create_peermgr_cfgs(1);
+		let mut peers = create_network(1, &cfgs);
+		peers[0].limits.max_inbound_connections_per_ip_prefix = Some(1);
+		let new_fd = |fd| FileDescriptor {
+			fd, outbound_data: Arc::new(Mutex::new(Vec::new())), disconnect: Arc::new(AtomicBool::new(false)),
+		};
+		assert!(peers[0].new_inbound_connection(new_fd(1), Some(mapped([10, 0, 0, 1]))).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(2), Some(mapped([10, 0, 1, 1]))).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(3), Some(mapped([10, 0, 0, 2]))).is_err());
+		assert!(peers[0].new_inbound_connection(new_fd(4), Some(SocketAddress::TcpIpV4 { addr: [10, 0, 0, 3], port: 9735 })).is_err());
+		assert!(peers[0].new_inbound_connection(new_fd(5), Some(mapped([127, 0, 0, 1]))).is_ok());
+		assert!(peers[0].new_inbound_connection(new_fd(6), Some(mapped([127, 0, 0, 1]))).is_ok());
+	}
+
+	#[test]
+	fn test_rate_limits_exempt_channel_peers() {
+		// Check that peers we have channels with are never rate limited or banned.
+		let cfgs = create_peermgr_cfgs(2);
+		let mut peers = create_network(2, &cfgs);
+		peers[0].limits = PeerLimits {
+			gossip_query_rate_limit: Some(RateLimit { burst: 1, refill_per_tick: 1 }),
+			max_rate_limit_violations_per_tick: 0,
+			..Default::default()
+		};
+		let id_b = peers[1].node_signer.get_node_id(Recipient::Node).unwrap();
+		cfgs[0].chan_handler.peers_with_channels.lock().unwrap().insert(id_b);
+		let addr_b = SocketAddress::TcpIpV4 { addr: [10, 0, 0, 2], port: 1001 };
+		let (mut fd_a, fd_b) = establish_connection_from(&peers[0], &peers[1], addr_b);
+
+		{
+			let peers_lock = peers[1].peers.read().unwrap();
+			let mut peer = peers_lock.get(&fd_b).unwrap().lock().unwrap();
+			for _ in 0..3 {
+				peers[1].enqueue_message(&mut *peer, &msgs::QueryChannelRange {
+					chain_hash: ChainHash::using_genesis_block(Network::Testnet), first_blocknum: 0,
+					number_of_blocks: u32::max_value(),
+				});
+			}
+		}
+		peers[1].process_events();
+		let b_data = fd_b.outbound_data.lock().unwrap().split_off(0);
+		assert_eq!(peers[0].read_event(&mut fd_a, &b_data).unwrap(), false);
+		{
+			let peers_lock = peers[0].peers.read().unwrap();
+			let peer = peers_lock.get(&fd_a).unwrap().lock().unwrap();
+			assert_eq!(peer.rate_limit_violations_since_timer_tick, 0);
+		}
+		assert!(peers[0].list_banned_peers().is_empty());
+	}
+
 	#[test]
 	fn test_do_attempt_write_data() {
 		// Create 2 peers with custom TestRoutingMessageHandlers and connect them.
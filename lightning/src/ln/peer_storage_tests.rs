@@ -0,0 +1,234 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of `option_provide_storage`, in which peers store a small backup blob for each other and
+//! return it upon reconnection.
+
+use crate::chain::Watch;
+use crate::events::{MessageSendEvent, MessageSendEventsProvider};
+use crate::ln::msgs::{self, ChannelMessageHandler};
+use crate::ln::our_peer_storage::OurPeerStorage;
+use crate::sign::NodeSigner;
+use crate::util::config::UserConfig;
+use crate::util::ser::Writeable;
+use crate::util::test_utils;
+use crate::prelude::*;
+
+use crate::ln::functional_test_utils::*;
+
+fn peer_storage_config() -> UserConfig {
+	let mut config = test_default_channel_config();
+	config.provide_peer_storage = true;
+	config
+}
+
+fn connect_and_get_events(node: &Node, counterparty: &Node) -> (Vec<MessageSendEvent>, Vec<MessageSendEvent>) {
+	let node_id = node.node.get_our_node_id();
+	let counterparty_id = counterparty.node.get_our_node_id();
+	let counterparty_init = msgs::Init {
+		features: counterparty.node.init_features(), networks: None, remote_network_address: None
+	};
+	node.node.peer_connected(&counterparty_id, &counterparty_init, true).unwrap();
+	let node_init = msgs::Init {
+		features: node.node.init_features(), networks: None, remote_network_address: None
+	};
+	counterparty.node.peer_connected(&node_id, &node_init, false).unwrap();
+	(node.node.get_and_clear_pending_msg_events(), counterparty.node.get_and_clear_pending_msg_events())
+}
+
+fn reconnect_and_get_events(node: &Node, counterparty: &Node) -> (Vec<MessageSendEvent>, Vec<MessageSendEvent>) {
+	node.node.peer_disconnected(&counterparty.node.get_our_node_id());
+	counterparty.node.peer_disconnected(&node.node.get_our_node_id());
+	connect_and_get_events(node, counterparty)
+}
+
+fn get_peer_storage(events: &[MessageSendEvent]) -> Option<msgs::PeerStorage> {
+	events.iter().find_map(|event|
+		if let MessageSendEvent::SendPeerStorage { msg, .. } = event { Some(msg.clone()) } else { None }
+	)
+}
+
+fn get_peer_storage_retrieval(events: &[MessageSendEvent]) -> Option<msgs::PeerStorageRetrieval> {
+	events.iter().find_map(|event|
+		if let MessageSendEvent::SendPeerStorageRetrieval { msg, .. } = event { Some(msg.clone()) } else { None }
+	)
+}
+
+fn expect_warning(node: &Node, expected: &str) {
+	let events = node.node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		MessageSendEvent::HandleError { action: msgs::ErrorAction::SendWarningMessage { msg, .. }, .. } => {
+			assert!(msg.data.contains(expected), "Unexpected warning {}", msg.data);
+		},
+		_ => panic!("Unexpected event {:?}", events[0]),
+	}
+}
+
+#[test]
+fn test_peer_storage_round_trip() {
+	// Both nodes hand each other a backup of their channel state, which is returned on reconnection
+	// and matches our current monitors.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(peer_storage_config()), Some(peer_storage_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+	assert!(nodes[0].node.init_features().supports_provide_storage());
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	// Our channel state changed, so the next timer tick hands the peer a new backup, but only once.
+	nodes[0].node.timer_tick_occurred();
+	let events = nodes[0].node.get_and_clear_pending_msg_events();
+	let our_storage = get_peer_storage(&events).unwrap();
+	nodes[0].node.timer_tick_occurred();
+	assert!(get_peer_storage(&nodes[0].node.get_and_clear_pending_msg_events()).is_none());
+
+	let decrypted = OurPeerStorage::decrypt(&nodes[0].keys_manager.get_peer_storage_key(), &our_storage.data).unwrap();
+	assert_eq!(decrypted.monitors, nodes[0].chain_monitor.get_peer_storage_snapshot());
+	assert_eq!(decrypted.monitors.len(), 1);
+	assert_eq!(decrypted.monitors[0].counterparty_node_id, Some(node_id_1));
+	assert_eq!(decrypted.monitors[0].latest_commitment_number, get_monitor!(nodes[0], chan_id).get_cur_holder_commitment_number());
+
+	nodes[1].node.handle_peer_storage(&node_id_0, &our_storage);
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+	// On reconnection nodes[1] returns our backup alongside its own, fresh one.
+	let (events_0, events_1) = reconnect_and_get_events(&nodes[0], &nodes[1]);
+	assert!(get_peer_storage_retrieval(&events_0).is_none());
+	assert!(get_peer_storage(&events_0).is_some());
+	let retrieval = get_peer_storage_retrieval(&events_1).unwrap();
+	assert_eq!(retrieval.data, our_storage.data);
+	assert!(get_peer_storage(&events_1).is_some());
+
+	nodes[0].node.handle_peer_storage_retrieval(&node_id_1, &retrieval);
+	nodes[0].logger.assert_log_contains("lightning::ln::channelmanager", "We may have lost channel state", 0);
+}
+
+#[test]
+fn test_peer_storage_checks_backup() {
+	// Backups of channels we have no matching ChannelMonitor for, or which we fail to decrypt, only
+	// result in a log entry.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(peer_storage_config()), Some(peer_storage_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_1 = nodes[1].node.get_our_node_id();
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let key = nodes[0].keys_manager.get_peer_storage_key();
+	let mut monitors = nodes[0].chain_monitor.get_peer_storage_snapshot();
+	monitors[0].channel_keys_id = [42; 32];
+	let data = OurPeerStorage { monitors: monitors.clone() }.encrypt(&key, &[42; 32]);
+	nodes[0].node.handle_peer_storage_retrieval(&node_id_1, &msgs::PeerStorageRetrieval { data });
+	nodes[0].logger.assert_log_contains("lightning::ln::channelmanager", "which doesn't match our ChannelMonitor", 1);
+
+	monitors[0].funding_outpoint.index += 1;
+	let data = OurPeerStorage { monitors }.encrypt(&key, &[42; 32]);
+	nodes[0].node.handle_peer_storage_retrieval(&node_id_1, &msgs::PeerStorageRetrieval { data });
+	nodes[0].logger.assert_log_contains("lightning::ln::channelmanager", "for which we have no ChannelMonitor", 1);
+
+	// Data we can't decrypt is ignored.
+	nodes[0].node.handle_peer_storage_retrieval(&node_id_1, &msgs::PeerStorageRetrieval { data: vec![42; 64] });
+	nodes[0].logger.assert_log_contains("lightning::ln::channelmanager", "which we failed to decrypt", 1);
+}
+
+#[test]
+#[should_panic(expected = "We have fallen behind")]
+fn test_peer_storage_detects_stale_state() {
+	// If the backup returned by our peer is more recent than our own monitors, we've lost channel
+	// state and must not continue operating.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(peer_storage_config()), Some(peer_storage_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_1 = nodes[1].node.get_our_node_id();
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+
+	let key = nodes[0].keys_manager.get_peer_storage_key();
+	let mut monitors = nodes[0].chain_monitor.get_peer_storage_snapshot();
+	monitors[0].latest_commitment_number -= 1;
+	let data = OurPeerStorage { monitors }.encrypt(&key, &[42; 32]);
+	nodes[0].node.handle_peer_storage_retrieval(&node_id_1, &msgs::PeerStorageRetrieval { data });
+}
+
+#[test]
+fn test_peer_storage_rejected() {
+	// We refuse to store backups for peers we have no funded channel with, backups which exceed the
+	// maximum size and any backups if we don't provide peer storage.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[Some(peer_storage_config()), Some(peer_storage_config()), None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let node_id_1 = nodes[1].node.get_our_node_id();
+	assert!(!nodes[2].node.init_features().supports_provide_storage());
+
+	nodes[1].node.handle_peer_storage(&node_id_0, &msgs::PeerStorage { data: vec![1; 32] });
+	expect_warning(&nodes[1], "no funded channels");
+
+	create_announced_chan_between_nodes(&nodes, 0, 1);
+	create_announced_chan_between_nodes(&nodes, 1, 2);
+
+	nodes[1].node.handle_peer_storage(&node_id_0, &msgs::PeerStorage { data: vec![1; msgs::MAX_PEER_STORAGE_SIZE + 1] });
+	expect_warning(&nodes[1], "exceeding the maximum");
+
+	nodes[2].node.handle_peer_storage(&node_id_1, &msgs::PeerStorage { data: vec![1; 32] });
+	expect_warning(&nodes[2], "not supported");
+
+	// Neither backup was stored, so nothing is returned on reconnection.
+	let (events_0, events_1) = reconnect_and_get_events(&nodes[0], &nodes[1]);
+	assert!(get_peer_storage_retrieval(&events_0).is_none());
+	assert!(get_peer_storage_retrieval(&events_1).is_none());
+	let (events_1, events_2) = reconnect_and_get_events(&nodes[1], &nodes[2]);
+	assert!(get_peer_storage_retrieval(&events_2).is_none());
+	// nodes[2] doesn't support peer storage, so we don't hand it our backup either.
+	assert!(get_peer_storage(&events_1).is_none());
+}
+
+#[test]
+fn test_peer_storage_persisted() {
+	// Backups stored with us are handed out for persistence and survive a reload of the
+	// ChannelManager once loaded again.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let persister;
+	let new_chain_monitor;
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(peer_storage_config()), Some(peer_storage_config())]);
+	let nodes_1_deserialized;
+	let mut nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let node_id_0 = nodes[0].node.get_our_node_id();
+	let chan_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	let data = vec![42; 128];
+	nodes[1].node.handle_peer_storage(&node_id_0, &msgs::PeerStorage { data: data.clone() });
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+	// Updates which failed to persist are handed out again.
+	let updates = nodes[1].node.take_pending_peer_storage_updates();
+	assert_eq!(updates, vec![(node_id_0, Some(data.clone()))]);
+	assert!(nodes[1].node.take_pending_peer_storage_updates().is_empty());
+	nodes[1].node.restore_pending_peer_storage_updates(updates);
+	assert_eq!(nodes[1].node.take_pending_peer_storage_updates(), vec![(node_id_0, Some(data.clone()))]);
+
+	let node_1_serialized = nodes[1].node.encode();
+	let chan_monitor_serialized = get_monitor!(nodes[1], chan_id).encode();
+	reload_node!(nodes[1], peer_storage_config(), &node_1_serialized, &[&chan_monitor_serialized], persister, new_chain_monitor, nodes_1_deserialized);
+
+	// The backup of a peer we no longer have channels with is dropped and removed from the
+	// persisted state.
+	let unknown_node_id = nodes[1].node.get_our_node_id();
+	nodes[1].node.load_peer_storage(vec![(node_id_0, data.clone()), (unknown_node_id, data.clone())]);
+	assert_eq!(nodes[1].node.take_pending_peer_storage_updates(), vec![(unknown_node_id, None)]);
+
+	nodes[0].node.peer_disconnected(&nodes[1].node.get_our_node_id());
+	let (_, events_1) = connect_and_get_events(&nodes[0], &nodes[1]);
+	assert_eq!(get_peer_storage_retrieval(&events_1).unwrap().data, data);
+}
@@ -0,0 +1,298 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of channel quiescence, in which both sides agree via `stfu` to stop proposing updates to
+//! a channel until the protocol which required it completes or the peers disconnect.
+
+use crate::events::{Event, MessageSendEvent, MessageSendEventsProvider};
+use crate::ln::ChannelId;
+use crate::ln::channel::{ChannelPhase, DISCONNECT_PEER_AWAITING_RESPONSE_TICKS};
+use crate::ln::channelmanager::{PaymentId, RecipientOnionFields};
+use crate::ln::msgs::{self, ChannelMessageHandler};
+use crate::prelude::*;
+
+use crate::ln::functional_test_utils::*;
+
+fn is_quiescent(node: &Node, counterparty: &Node, channel_id: ChannelId) -> bool {
+	let mut per_peer_state_lock;
+	let mut peer_state_lock;
+	match get_channel_ref!(node, counterparty, per_peer_state_lock, peer_state_lock, channel_id) {
+		ChannelPhase::Funded(chan) => chan.is_quiescent(),
+		_ => panic!("Unexpected channel phase"),
+	}
+}
+
+fn expect_channel_quiescent_event(node: &Node, channel_id: ChannelId, expected_initiator: bool) {
+	let events = node.node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 1);
+	match &events[0] {
+		Event::ChannelQuiescent { channel_id: quiescent_channel_id, is_initiator, .. } => {
+			assert_eq!(*quiescent_channel_id, channel_id);
+			assert_eq!(*is_initiator, expected_initiator);
+		},
+		_ => panic!("Unexpected event {:?}", events[0]),
+	}
+}
+
+fn reconnect_and_get_stfu(initiator: &Node, responder: &Node) -> Option<msgs::Stfu> {
+	let initiator_id = initiator.node.get_our_node_id();
+	let responder_id = responder.node.get_our_node_id();
+	initiator.node.peer_disconnected(&responder_id);
+	responder.node.peer_disconnected(&initiator_id);
+
+	let responder_init = msgs::Init {
+		features: responder.node.init_features(), networks: None, remote_network_address: None
+	};
+	initiator.node.peer_connected(&responder_id, &responder_init, true).unwrap();
+	let initiator_init = msgs::Init {
+		features: initiator.node.init_features(), networks: None, remote_network_address: None
+	};
+	responder.node.peer_connected(&initiator_id, &initiator_init, false).unwrap();
+	let initiator_reestablish = get_chan_reestablish_msgs!(initiator, responder);
+	let responder_reestablish = get_chan_reestablish_msgs!(responder, initiator);
+	responder.node.handle_channel_reestablish(&initiator_id, &initiator_reestablish[0]);
+	initiator.node.handle_channel_reestablish(&responder_id, &responder_reestablish[0]);
+	responder.node.get_and_clear_pending_msg_events();
+
+	initiator.node.get_and_clear_pending_msg_events().into_iter().find_map(|event|
+		if let MessageSendEvent::SendStfu { msg, .. } = event { Some(msg) } else { None }
+	)
+}
+
+fn expect_disconnect_event(node: &Node) {
+	let events = node.node.get_and_clear_pending_msg_events();
+	assert!(events.iter().any(|event| matches!(event, MessageSendEvent::HandleError {
+		action: msgs::ErrorAction::DisconnectPeerWithWarning { .. }, ..
+	})), "Expected a disconnect, got {:?}", events);
+}
+
+fn exchange_stfu(initiator: &Node, responder: &Node, channel_id: ChannelId) {
+	let initiator_id = initiator.node.get_our_node_id();
+	let responder_id = responder.node.get_our_node_id();
+
+	let stfu = get_event_msg!(initiator, MessageSendEvent::SendStfu, responder_id);
+	assert_eq!(stfu.initiator, 1);
+	responder.node.handle_stfu(&initiator_id, &stfu);
+	let stfu = get_event_msg!(responder, MessageSendEvent::SendStfu, initiator_id);
+	assert_eq!(stfu.initiator, 0);
+	initiator.node.handle_stfu(&responder_id, &stfu);
+
+	assert!(is_quiescent(initiator, responder, channel_id));
+	assert!(is_quiescent(responder, initiator, channel_id));
+	expect_channel_quiescent_event(initiator, channel_id, true);
+	expect_channel_quiescent_event(responder, channel_id, false);
+}
+
+#[test]
+fn test_quiescence_handshake() {
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	// Either side may initiate quiescence, with the responder replying with its own `stfu`.
+	nodes[1].node.propose_quiescence(&channel_id, &nodes[0].node.get_our_node_id()).unwrap();
+	exchange_stfu(&nodes[1], &nodes[0], channel_id);
+	assert!(nodes[1].node.propose_quiescence(&channel_id, &nodes[0].node.get_our_node_id()).is_err());
+	assert!(nodes[1].node.exit_quiescence(&nodes[0].node.get_our_node_id(), &channel_id).unwrap());
+	assert!(!nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &channel_id).unwrap());
+
+	// If both sides send `stfu` as the initiator at the same time, the channel funder wins.
+	nodes[0].node.propose_quiescence(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	nodes[1].node.propose_quiescence(&channel_id, &nodes[0].node.get_our_node_id()).unwrap();
+	let stfu_0 = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, nodes[1].node.get_our_node_id());
+	let stfu_1 = get_event_msg!(nodes[1], MessageSendEvent::SendStfu, nodes[0].node.get_our_node_id());
+	assert_eq!((stfu_0.initiator, stfu_1.initiator), (1, 1));
+	nodes[0].node.handle_stfu(&nodes[1].node.get_our_node_id(), &stfu_1);
+	nodes[1].node.handle_stfu(&nodes[0].node.get_our_node_id(), &stfu_0);
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+	assert!(is_quiescent(&nodes[0], &nodes[1], channel_id));
+	assert!(is_quiescent(&nodes[1], &nodes[0], channel_id));
+	expect_channel_quiescent_event(&nodes[0], channel_id, true);
+	expect_channel_quiescent_event(&nodes[1], channel_id, false);
+	assert!(nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &channel_id).unwrap());
+	assert!(!nodes[1].node.exit_quiescence(&nodes[0].node.get_our_node_id(), &channel_id).unwrap());
+
+	// Quiescence can only be terminated while quiescent.
+	assert!(nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &channel_id).is_err());
+
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
+
+#[test]
+fn test_quiescence_waits_for_pending_updates() {
+	// Quiescence may only be reached once all pending updates have been irrevocably committed, so
+	// `stfu` is only sent once the commitment dance completes.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	let (route, payment_hash, payment_preimage, payment_secret) = get_route_and_payment_hash!(nodes[0], nodes[1], 1_000_000);
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+
+	nodes[0].node.propose_quiescence(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	let payment_event = SendEvent::from_event(events.remove(0));
+	nodes[1].node.handle_update_add_htlc(&nodes[0].node.get_our_node_id(), &payment_event.msgs[0]);
+	nodes[1].node.handle_commitment_signed(&nodes[0].node.get_our_node_id(), &payment_event.commitment_msg);
+	check_added_monitors(&nodes[1], 1);
+	let (stfu, raa) = commitment_signed_dance!(nodes[1], nodes[0], (), false, true, true, true);
+	nodes[1].node.handle_revoke_and_ack(&nodes[0].node.get_our_node_id(), &raa);
+	check_added_monitors(&nodes[1], 1);
+
+	// Our `stfu` goes out alongside our final `revoke_and_ack`.
+	let stfu = match stfu {
+		Some(MessageSendEvent::SendStfu { msg, .. }) => msg,
+		_ => panic!("Unexpected event {:?}", stfu),
+	};
+	assert_eq!(stfu.initiator, 1);
+	nodes[1].node.handle_stfu(&nodes[0].node.get_our_node_id(), &stfu);
+	let stfu = get_event_msg!(nodes[1], MessageSendEvent::SendStfu, nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_stfu(&nodes[1].node.get_our_node_id(), &stfu);
+	assert!(is_quiescent(&nodes[0], &nodes[1], channel_id));
+	assert!(is_quiescent(&nodes[1], &nodes[0], channel_id));
+	expect_channel_quiescent_event(&nodes[0], channel_id, true);
+
+	// The recipient can't claim the payment while quiescent, but can once quiescence terminates.
+	let events = nodes[1].node.get_and_clear_pending_events();
+	assert_eq!(events.len(), 2);
+	assert!(matches!(events[0], Event::PendingHTLCsForwardable { .. }));
+	assert!(matches!(events[1], Event::ChannelQuiescent { is_initiator: false, .. }));
+	nodes[1].node.process_pending_htlc_forwards();
+	expect_payment_claimable!(nodes[1], payment_hash, payment_secret, 1_000_000);
+	nodes[1].node.claim_funds(payment_preimage);
+	check_added_monitors(&nodes[1], 1);
+	expect_payment_claimed!(nodes[1], payment_hash, 1_000_000);
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+	nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &channel_id).unwrap();
+	nodes[1].node.exit_quiescence(&nodes[0].node.get_our_node_id(), &channel_id).unwrap();
+	check_added_monitors(&nodes[1], 1);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_update_fulfill_htlc(&nodes[1].node.get_our_node_id(), &updates.update_fulfill_htlcs[0]);
+	commitment_signed_dance!(nodes[0], nodes[1], updates.commitment_signed, false);
+	expect_payment_sent!(nodes[0], payment_preimage);
+}
+
+#[test]
+fn test_quiescence_holds_new_updates() {
+	// Once we've sent `stfu`, any new updates we propose must wait in the holding cell until
+	// quiescence terminates.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	// Send the payment from the non-initiator, checking both sides hold off until the initiator
+	// is done with quiescence.
+	nodes[1].node.propose_quiescence(&channel_id, &nodes[0].node.get_our_node_id()).unwrap();
+	exchange_stfu(&nodes[1], &nodes[0], channel_id);
+
+	let (route, payment_hash, payment_preimage, payment_secret) = get_route_and_payment_hash!(nodes[0], nodes[1], 1_000_000);
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 0);
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+
+	nodes[1].node.exit_quiescence(&nodes[0].node.get_our_node_id(), &channel_id).unwrap();
+	nodes[0].node.exit_quiescence(&nodes[1].node.get_our_node_id(), &channel_id).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	let mut events = nodes[0].node.get_and_clear_pending_msg_events();
+	assert_eq!(events.len(), 1);
+	pass_along_path(&nodes[0], &[&nodes[1]], 1_000_000, payment_hash, Some(payment_secret), events.remove(0), true, None);
+	claim_payment(&nodes[0], &[&nodes[1]], payment_preimage);
+}
+
+#[test]
+fn test_quiescence_rejects_counterparty_updates() {
+	// Once quiescent, any update received from our counterparty is a protocol violation and results
+	// in a disconnection.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	nodes[0].node.propose_quiescence(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	exchange_stfu(&nodes[0], &nodes[1], channel_id);
+
+	let update_fee = msgs::UpdateFee { channel_id, feerate_per_kw: 1000 };
+	nodes[1].node.handle_update_fee(&nodes[0].node.get_our_node_id(), &update_fee);
+	expect_disconnect_event(&nodes[1]);
+}
+
+#[test]
+fn test_quiescence_timeout() {
+	// A counterparty which never responds to our `stfu`, or keeps the channel quiescent for too
+	// long, is disconnected.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	nodes[0].node.propose_quiescence(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	let stfu = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, nodes[1].node.get_our_node_id());
+	for _ in 0..DISCONNECT_PEER_AWAITING_RESPONSE_TICKS - 1 {
+		nodes[0].node.timer_tick_occurred();
+		assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+	}
+	nodes[0].node.timer_tick_occurred();
+	expect_disconnect_event(&nodes[0]);
+
+	// The responder gives the initiator a limited amount of time to make use of quiescence.
+	nodes[1].node.handle_stfu(&nodes[0].node.get_our_node_id(), &stfu);
+	let _ = get_event_msg!(nodes[1], MessageSendEvent::SendStfu, nodes[0].node.get_our_node_id());
+	expect_channel_quiescent_event(&nodes[1], channel_id, false);
+	for _ in 0..DISCONNECT_PEER_AWAITING_RESPONSE_TICKS - 1 {
+		nodes[1].node.timer_tick_occurred();
+		assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+	}
+	nodes[1].node.timer_tick_occurred();
+	expect_disconnect_event(&nodes[1]);
+}
+
+#[test]
+fn test_quiescence_retried_on_reconnect() {
+	// Quiescence terminates upon disconnection, but if we never made it to quiescence our request
+	// is retried once the channel has been reestablished.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let channel_id = create_announced_chan_between_nodes(&nodes, 0, 1).2;
+
+	nodes[0].node.propose_quiescence(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	let _ = get_event_msg!(nodes[0], MessageSendEvent::SendStfu, nodes[1].node.get_our_node_id());
+
+	let stfu = reconnect_and_get_stfu(&nodes[0], &nodes[1]).unwrap();
+	assert_eq!(stfu.initiator, 1);
+	nodes[1].node.handle_stfu(&nodes[0].node.get_our_node_id(), &stfu);
+	let stfu = get_event_msg!(nodes[1], MessageSendEvent::SendStfu, nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_stfu(&nodes[1].node.get_our_node_id(), &stfu);
+	assert!(is_quiescent(&nodes[0], &nodes[1], channel_id));
+	assert!(is_quiescent(&nodes[1], &nodes[0], channel_id));
+	expect_channel_quiescent_event(&nodes[0], channel_id, true);
+	expect_channel_quiescent_event(&nodes[1], channel_id, false);
+
+	// Once quiescent, disconnecting terminates quiescence without retrying it.
+	assert!(reconnect_and_get_stfu(&nodes[0], &nodes[1]).is_none());
+	assert!(!is_quiescent(&nodes[0], &nodes[1], channel_id));
+	assert!(!is_quiescent(&nodes[1], &nodes[0], channel_id));
+
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
@@ -0,0 +1,232 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Local reputation tracking, used to decide whether HTLCs we forward should be endorsed to the
+//! next hop.
+//!
+//! Each incoming channel builds up reputation with us by forwarding HTLCs which pay us fees and
+//! resolve quickly. Endorsed HTLCs which are held for long periods instead cost the incoming
+//! channel reputation, proportional to the fee they offered us. We only endorse an HTLC to the
+//! next hop if it was endorsed to us and the incoming channel's reputation covers the damage the
+//! HTLC, and any other endorsed HTLCs from the same channel still in flight, could do if held
+//! until they time out.
+//!
+//! All state is kept in memory only, a restart resets the reputation of all incoming channels.
+//!
+//! See [bLIP 4] for the endorsement signal itself.
+//!
+//! [bLIP 4]: https://github.com/lightning/blips/blob/master/blip-0004.md
+
+use crate::prelude::*;
+
+use core::cmp;
+
+/// HTLCs which resolve within this many [`ChannelManager::timer_tick_occurred`] calls are
+/// considered well-behaved. Endorsed HTLCs held for longer are penalized for each additional
+/// tick.
+///
+/// [`ChannelManager::timer_tick_occurred`]: crate::ln::channelmanager::ChannelManager::timer_tick_occurred
+const HTLC_RESOLUTION_THRESHOLD_TICKS: u64 = 1;
+
+/// The reputation of each incoming channel decays by this fraction every timer tick, so that
+/// fees earned roughly two weeks ago no longer carry much weight. The decay is rounded away from
+/// zero, so that even small reputations, and in particular small penalties, eventually expire.
+const REPUTATION_DECAY_TICKS: i64 = 60 * 24 * 14;
+
+/// Timer ticks happen roughly once per minute, thus we expect about ten of them per block.
+const TICKS_PER_BLOCK: u64 = 10;
+
+struct InFlightHTLC {
+	fee_msat: u64,
+	added_tick: u64,
+	endorsed: bool,
+	risk_msat: u64,
+}
+
+#[derive(Default)]
+struct IncomingChannelReputation {
+	reputation_msat: i64,
+	in_flight_risk_msat: u64,
+}
+
+/// Tracks the reputation of our incoming channels based on the HTLCs they forward through us.
+///
+/// Channels and HTLCs are identified by the incoming short channel id and HTLC id, which is all
+/// the information available once an HTLC is resolved.
+pub(crate) struct HTLCReputationTracker {
+	current_tick: u64,
+	channels: HashMap<u64, IncomingChannelReputation>,
+	in_flight_htlcs: HashMap<(u64, u64), InFlightHTLC>,
+}
+
+impl HTLCReputationTracker {
+	pub(crate) fn new() -> Self {
+		Self { current_tick: 0, channels: HashMap::new(), in_flight_htlcs: HashMap::new() }
+	}
+
+	/// The reputation an HTLC paying us `fee_msat` may cost its incoming channel if it is held
+	/// until it expires in `blocks_to_expiry` blocks.
+	fn htlc_risk_msat(fee_msat: u64, blocks_to_expiry: u32) -> u64 {
+		let max_hold_ticks = (blocks_to_expiry as u64).saturating_mul(TICKS_PER_BLOCK);
+		fee_msat.saturating_mul(max_hold_ticks.saturating_sub(HTLC_RESOLUTION_THRESHOLD_TICKS))
+	}
+
+	/// Returns whether the incoming channel has enough reputation for us to endorse an HTLC
+	/// paying us `fee_msat` and expiring in `blocks_to_expiry` blocks to the next hop.
+	pub(crate) fn should_endorse(&self, incoming_scid: u64, fee_msat: u64, blocks_to_expiry: u32) -> bool {
+		let channel = match self.channels.get(&incoming_scid) {
+			Some(channel) => channel,
+			None => return false,
+		};
+		let risk_msat = channel.in_flight_risk_msat
+			.saturating_add(Self::htlc_risk_msat(fee_msat, blocks_to_expiry));
+		channel.reputation_msat > 0 && channel.reputation_msat as u64 >= risk_msat
+	}
+
+	/// Records an HTLC we forwarded on behalf of the incoming channel `incoming_scid`.
+	pub(crate) fn htlc_forwarded(
+		&mut self, incoming_scid: u64, incoming_htlc_id: u64, fee_msat: u64, blocks_to_expiry: u32,
+		endorsed: bool,
+	) {
+		let risk_msat = if endorsed { Self::htlc_risk_msat(fee_msat, blocks_to_expiry) } else { 0 };
+		let channel = self.channels.entry(incoming_scid).or_default();
+		channel.in_flight_risk_msat = channel.in_flight_risk_msat.saturating_add(risk_msat);
+		let htlc = InFlightHTLC { fee_msat, added_tick: self.current_tick, endorsed, risk_msat };
+		self.in_flight_htlcs.insert((incoming_scid, incoming_htlc_id), htlc);
+	}
+
+	/// Updates the incoming channel's reputation once an HTLC we forwarded has been either claimed
+	/// (`settled`) or failed back.
+	///
+	/// HTLCs we did not record via [`Self::htlc_forwarded`] are ignored.
+	pub(crate) fn htlc_resolved(&mut self, incoming_scid: u64, incoming_htlc_id: u64, settled: bool) {
+		let htlc = match self.in_flight_htlcs.remove(&(incoming_scid, incoming_htlc_id)) {
+			Some(htlc) => htlc,
+			None => return,
+		};
+		let channel = self.channels.entry(incoming_scid).or_default();
+		channel.in_flight_risk_msat = channel.in_flight_risk_msat.saturating_sub(htlc.risk_msat);
+
+		let fee_msat = cmp::min(htlc.fee_msat, i64::MAX as u64) as i64;
+		if settled {
+			channel.reputation_msat = channel.reputation_msat.saturating_add(fee_msat);
+		}
+		let held_ticks = self.current_tick - htlc.added_tick;
+		if htlc.endorsed && held_ticks > HTLC_RESOLUTION_THRESHOLD_TICKS {
+			let penalty_msat = htlc.fee_msat
+				.saturating_mul(held_ticks - HTLC_RESOLUTION_THRESHOLD_TICKS);
+			let penalty_msat = cmp::min(penalty_msat, i64::MAX as u64) as i64;
+			channel.reputation_msat = channel.reputation_msat.saturating_sub(penalty_msat);
+		}
+	}
+
+	/// Advances our notion of time and decays the reputation of all incoming channels. Should be
+	/// called on each [`ChannelManager::timer_tick_occurred`].
+	///
+	/// [`ChannelManager::timer_tick_occurred`]: crate::ln::channelmanager::ChannelManager::timer_tick_occurred
+	pub(crate) fn timer_tick_occurred(&mut self) {
+		self.current_tick += 1;
+		self.channels.retain(|_, channel| {
+			let decay_msat = (channel.reputation_msat.unsigned_abs() + REPUTATION_DECAY_TICKS as u64 - 1)
+				/ REPUTATION_DECAY_TICKS as u64;
+			channel.reputation_msat -= channel.reputation_msat.signum() * decay_msat as i64;
+			channel.reputation_msat != 0 || channel.in_flight_risk_msat != 0
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{HTLCReputationTracker, HTLC_RESOLUTION_THRESHOLD_TICKS, REPUTATION_DECAY_TICKS};
+
+	#[test]
+	fn builds_reputation_from_fast_settles() {
+		let mut tracker = HTLCReputationTracker::new();
+		assert!(!tracker.should_endorse(42, 1_000, 1));
+
+		// An unendorsed HTLC which settles quickly earns its incoming channel the fee we were paid.
+		tracker.htlc_forwarded(42, 0, 100_000, 1, false);
+		tracker.htlc_resolved(42, 0, true);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, 100_000);
+		assert!(tracker.should_endorse(42, 1_000, 1));
+		// ...but not enough to cover an HTLC which may be held for much longer.
+		assert!(!tracker.should_endorse(42, 1_000, 144));
+		// Other channels are unaffected.
+		assert!(!tracker.should_endorse(43, 1_000, 1));
+
+		// Failed HTLCs don't earn any reputation.
+		tracker.htlc_forwarded(42, 1, 100_000, 1, false);
+		tracker.htlc_resolved(42, 1, false);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, 100_000);
+
+		// Unknown HTLCs are ignored.
+		tracker.htlc_resolved(42, 2, true);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, 100_000);
+	}
+
+	#[test]
+	fn penalizes_slow_endorsed_htlcs() {
+		let mut tracker = HTLCReputationTracker::new();
+		tracker.htlc_forwarded(42, 0, 100_000, 1, false);
+		tracker.htlc_resolved(42, 0, true);
+
+		// In-flight endorsed HTLCs count against the reputation available for new ones.
+		assert!(tracker.should_endorse(42, 10_000, 1));
+		tracker.htlc_forwarded(42, 1, 10_000, 1, true);
+		assert!(!tracker.should_endorse(42, 10_000, 1));
+
+		let slow_ticks = HTLC_RESOLUTION_THRESHOLD_TICKS + 5;
+		// Advance time without decaying reputation.
+		tracker.current_tick += slow_ticks;
+		tracker.htlc_resolved(42, 1, false);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, 100_000 - 5 * 10_000);
+		assert_eq!(tracker.channels.get(&42).unwrap().in_flight_risk_msat, 0);
+
+		// Slow unendorsed HTLCs are not penalized.
+		tracker.htlc_forwarded(42, 2, 10_000, 1, false);
+		tracker.current_tick += slow_ticks;
+		tracker.htlc_resolved(42, 2, false);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, 50_000);
+	}
+
+	#[test]
+	fn reputation_decays() {
+		let mut tracker = HTLCReputationTracker::new();
+		tracker.htlc_forwarded(42, 0, REPUTATION_DECAY_TICKS as u64 * 10, 1, false);
+		tracker.htlc_resolved(42, 0, true);
+		tracker.timer_tick_occurred();
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, REPUTATION_DECAY_TICKS * 10 - 10);
+
+		// Channels whose reputation fully decayed are forgotten.
+		tracker.htlc_forwarded(43, 0, 0, 1, false);
+		tracker.htlc_resolved(43, 0, true);
+		assert!(tracker.channels.contains_key(&43));
+		tracker.timer_tick_occurred();
+		assert!(!tracker.channels.contains_key(&43));
+	}
+
+	#[test]
+	fn small_penalties_decay() {
+		// Penalties smaller than REPUTATION_DECAY_TICKS still decay by at least a msat per tick.
+		let mut tracker = HTLCReputationTracker::new();
+		tracker.htlc_forwarded(42, 0, 1, 1, true);
+		tracker.current_tick += HTLC_RESOLUTION_THRESHOLD_TICKS + 100;
+		tracker.htlc_resolved(42, 0, false);
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, -100);
+
+		for _ in 0..99 {
+			tracker.timer_tick_occurred();
+		}
+		assert_eq!(tracker.channels.get(&42).unwrap().reputation_msat, -1);
+
+		// Once the penalty has fully decayed, the channel is forgotten.
+		tracker.timer_tick_occurred();
+		assert!(!tracker.channels.contains_key(&42));
+	}
+}
@@ -0,0 +1,365 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests of splicing funds into and out of live channels, covering the splice negotiation, the
+//! exchange of signatures for the new funding transaction and switching over to it once locked.
+
+use crate::chain::transaction::OutPoint;
+use crate::events::{ClosureReason, Event, MessageSendEvent, MessageSendEventsProvider};
+use crate::ln::channelmanager::SpliceContribution;
+use crate::ln::msgs::ChannelMessageHandler;
+use crate::util::config::UserConfig;
+use crate::util::errors::APIError;
+
+use bitcoin::{Sequence, Transaction, TxIn, TxOut, Witness};
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::blockdata::script::ScriptBuf;
+use bitcoin::blockdata::transaction::OutPoint as BitcoinOutPoint;
+use bitcoin::hash_types::WPubkeyHash;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+
+use crate::ln::functional_test_utils::*;
+
+fn splicing_config() -> UserConfig {
+	let mut config = test_default_channel_config();
+	config.enable_splicing = true;
+	config
+}
+
+/// Runs the splice negotiation started by `initiator` through to exchanging `commitment_signed`s,
+/// returning the unsigned splice transaction if `initiator` needs to sign its inputs.
+fn negotiate_splice<'a, 'b, 'c>(
+	initiator: &Node<'a, 'b, 'c>, acceptor: &Node<'a, 'b, 'c>,
+) -> Option<Transaction> {
+	let initiator_node_id = initiator.node.get_our_node_id();
+	let acceptor_node_id = acceptor.node.get_our_node_id();
+
+	let splice = get_event_msg!(initiator, MessageSendEvent::SendSplice, acceptor_node_id);
+	acceptor.node.handle_splice(&initiator_node_id, &splice);
+	let splice_ack = get_event_msg!(acceptor, MessageSendEvent::SendSpliceAck, initiator_node_id);
+	initiator.node.handle_splice_ack(&acceptor_node_id, &splice_ack);
+
+	let (mut initiator_commitment_signed, mut acceptor_commitment_signed) = (None, None);
+	while initiator_commitment_signed.is_none() || acceptor_commitment_signed.is_none() {
+		if let Some(msg) = handle_interactive_tx_msg_events(initiator, acceptor) {
+			initiator_commitment_signed = Some(msg);
+		}
+		if let Some(msg) = handle_interactive_tx_msg_events(acceptor, initiator) {
+			acceptor_commitment_signed = Some(msg);
+		}
+	}
+
+	let unsigned_transaction = initiator.node.get_and_clear_pending_events().pop().map(|event| match event {
+		Event::FundingTransactionReadyForSigning { unsigned_transaction, counterparty_node_id, .. } => {
+			assert_eq!(counterparty_node_id, acceptor_node_id);
+			unsigned_transaction
+		},
+		_ => panic!("Unexpected event {:?}", event),
+	});
+	assert!(acceptor.node.get_and_clear_pending_events().is_empty());
+
+	acceptor.node.handle_commitment_signed(&initiator_node_id, &initiator_commitment_signed.unwrap());
+	check_added_monitors(acceptor, 1);
+	initiator.node.handle_commitment_signed(&acceptor_node_id, &acceptor_commitment_signed.unwrap());
+	check_added_monitors(initiator, 1);
+	unsigned_transaction
+}
+
+/// Exchanges `tx_signatures` between the two nodes, returning the splice transaction they've both
+/// broadcast once both have generated an [`Event::SplicePending`] for it.
+fn exchange_splice_tx_signatures<'a, 'b, 'c>(node_a: &Node<'a, 'b, 'c>, node_b: &Node<'a, 'b, 'c>) -> Transaction {
+	let splice_tx = exchange_tx_signatures(node_a, node_b);
+	for node in [node_a, node_b] {
+		match get_event!(node, Event::SplicePending) {
+			Event::SplicePending { new_funding_txo, .. } => assert_eq!(new_funding_txo.txid, splice_tx.txid()),
+			_ => unreachable!(),
+		}
+	}
+	splice_tx
+}
+
+/// Confirms the splice transaction on both nodes and exchanges `splice_locked`.
+fn lock_splice<'a, 'b, 'c>(node_a: &Node<'a, 'b, 'c>, node_b: &Node<'a, 'b, 'c>, splice_tx: &Transaction) {
+	let node_a_id = node_a.node.get_our_node_id();
+	let node_b_id = node_b.node.get_our_node_id();
+	confirm_transaction(node_a, splice_tx);
+	confirm_transaction(node_b, splice_tx);
+
+	let get_splice_locked = |node: &Node| {
+		let mut splice_locked = None;
+		for event in node.node.get_and_clear_pending_msg_events() {
+			match event {
+				MessageSendEvent::SendSpliceLocked { msg, .. } => splice_locked = Some(msg),
+				MessageSendEvent::SendAnnouncementSignatures { .. } => {},
+				_ => panic!("Unexpected event {:?}", event),
+			}
+		}
+		splice_locked.unwrap()
+	};
+	let node_a_splice_locked = get_splice_locked(node_a);
+	let node_b_splice_locked = get_splice_locked(node_b);
+	node_b.node.handle_splice_locked(&node_a_id, &node_a_splice_locked);
+	node_a.node.handle_splice_locked(&node_b_id, &node_b_splice_locked);
+	// The ChannelMonitors learn that they may switch to the new funding output.
+	check_added_monitors(node_a, 1);
+	check_added_monitors(node_b, 1);
+
+	for node in [node_a, node_b] {
+		match get_event!(node, Event::SpliceLocked) {
+			Event::SpliceLocked { funding_txo, .. } => assert_eq!(funding_txo.txid, splice_tx.txid()),
+			_ => unreachable!(),
+		}
+	}
+
+	// The channel is announced again under its new short channel id.
+	let node_a_announcement_sigs = get_event_msg!(node_a, MessageSendEvent::SendAnnouncementSignatures, node_b_id);
+	let node_b_announcement_sigs = get_event_msg!(node_b, MessageSendEvent::SendAnnouncementSignatures, node_a_id);
+	node_b.node.handle_announcement_signatures(&node_a_id, &node_a_announcement_sigs);
+	node_a.node.handle_announcement_signatures(&node_b_id, &node_b_announcement_sigs);
+	for node in [node_a, node_b] {
+		let events = node.node.get_and_clear_pending_msg_events();
+		assert_eq!(events.len(), 1);
+		match &events[0] {
+			MessageSendEvent::BroadcastChannelAnnouncement { msg, .. } =>
+				assert_eq!(Some(msg.contents.short_channel_id), node.node.list_channels()[0].short_channel_id),
+			_ => panic!("Unexpected event {:?}", events[0]),
+		}
+	}
+}
+
+fn get_splice_funding_outpoint(splice_tx: &Transaction, channel_value_satoshis: u64) -> OutPoint {
+	let index = splice_tx.output.iter().position(|output| output.value == channel_value_satoshis).unwrap();
+	OutPoint { txid: splice_tx.txid(), index: index as u16 }
+}
+
+#[test]
+fn test_splice_out() {
+	// Splice funds out of a channel, checking that it keeps working once the splice is locked and
+	// that the ChannelMonitor switched over to the new funding output.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), Some(splicing_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, funding_tx) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+	send_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+
+	let splice_out_script = ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20]));
+	nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(), SpliceContribution::SpliceOut {
+		outputs: vec![TxOut { value: 20_000, script_pubkey: splice_out_script.clone() }],
+	}, 253, None).unwrap();
+	assert!(negotiate_splice(&nodes[0], &nodes[1]).is_none());
+
+	// No HTLCs may be added until the splice is locked.
+	assert_eq!(nodes[0].node.list_channels()[0].next_outbound_htlc_limit_msat, 0);
+	assert_eq!(nodes[1].node.list_channels()[0].next_outbound_htlc_limit_msat, 0);
+
+	let splice_tx = exchange_splice_tx_signatures(&nodes[0], &nodes[1]);
+	assert_eq!(splice_tx.input.len(), 1);
+	assert_eq!(splice_tx.input[0].previous_output, BitcoinOutPoint { txid: funding_tx.txid(), vout: 0 });
+	assert!(splice_tx.output.iter().any(|output| output.script_pubkey == splice_out_script && output.value == 20_000));
+	let fee = 100_000 - splice_tx.output.iter().map(|output| output.value).sum::<u64>();
+	let new_channel_value = 100_000 - 20_000 - fee;
+	let splice_funding_outpoint = get_splice_funding_outpoint(&splice_tx, new_channel_value);
+
+	lock_splice(&nodes[0], &nodes[1], &splice_tx);
+	for node in nodes.iter() {
+		let channel = &node.node.list_channels()[0];
+		assert_eq!(channel.channel_id, channel_id);
+		assert_eq!(channel.channel_value_satoshis, new_channel_value);
+		assert_eq!(channel.funding_txo, Some(splice_funding_outpoint));
+	}
+	assert_eq!(nodes[1].node.list_channels()[0].balance_msat, 10_000_000);
+
+	// Payments continue to work over the new funding output.
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+
+	// Commitment transactions now spend the new funding output.
+	nodes[0].node.force_close_broadcasting_latest_txn(&channel_id, &nodes[1].node.get_our_node_id()).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast!(nodes[0], true);
+	check_closed_event!(nodes[0], 1, ClosureReason::HolderForceClosed, [nodes[1].node.get_our_node_id()], new_channel_value);
+	let commitment_txn = nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert_eq!(commitment_txn.len(), 1);
+	check_spends!(commitment_txn[0], splice_tx);
+}
+
+#[test]
+fn test_revoked_commitment_after_splice_reorg() {
+	// The ChannelMonitor only switches to a splice's funding output once the splice is locked, so a
+	// revoked commitment transaction spending the previous funding output is still punished if the
+	// splice transaction confirms and is then reorged out.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), Some(splicing_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+	let revoked_txn = get_local_commitment_txn!(nodes[1], channel_id);
+	send_payment(&nodes[0], &[&nodes[1]], 10_000_000);
+
+	let splice_out_script = ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20]));
+	nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(), SpliceContribution::SpliceOut {
+		outputs: vec![TxOut { value: 20_000, script_pubkey: splice_out_script }],
+	}, 253, None).unwrap();
+	assert!(negotiate_splice(&nodes[0], &nodes[1]).is_none());
+	let splice_tx = exchange_splice_tx_signatures(&nodes[0], &nodes[1]);
+	nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().clear();
+
+	mine_transaction(&nodes[0], &splice_tx);
+	disconnect_blocks(&nodes[0], 1);
+
+	mine_transaction(&nodes[0], &revoked_txn[0]);
+	check_added_monitors(&nodes[0], 1);
+	check_closed_broadcast!(nodes[0], true);
+	check_closed_event!(nodes[0], 1, ClosureReason::CommitmentTxConfirmed, [nodes[1].node.get_our_node_id()], 100_000);
+	let node_txn = nodes[0].tx_broadcaster.txn_broadcasted.lock().unwrap().split_off(0);
+	assert!(node_txn.iter().any(|tx| tx.input.iter().any(|input| input.previous_output.txid == revoked_txn[0].txid())));
+}
+
+#[test]
+fn test_splice_in() {
+	// Splice funds into a channel from an input which needs to be signed by the user.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), Some(splicing_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+
+	let secp_ctx = Secp256k1::new();
+	let input_key = SecretKey::from_slice(&[42; 32]).unwrap();
+	let input_pubkey = bitcoin::PublicKey::new(input_key.public_key(&secp_ctx));
+	let prevtx = Transaction {
+		version: 2,
+		lock_time: LockTime::ZERO,
+		input: vec![],
+		output: vec![TxOut { value: 60_000, script_pubkey: ScriptBuf::new_v0_p2wpkh(&input_pubkey.wpubkey_hash().unwrap()) }],
+	};
+	let input = TxIn {
+		previous_output: BitcoinOutPoint { txid: prevtx.txid(), vout: 0 },
+		script_sig: ScriptBuf::new(),
+		sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+		witness: Witness::new(),
+	};
+	let change_script = ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20]));
+	nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(), SpliceContribution::SpliceIn {
+		value_satoshis: 50_000,
+		inputs: vec![(input, prevtx.clone())],
+		change_script: Some(change_script.clone()),
+	}, 253, None).unwrap();
+	let mut splice_tx = negotiate_splice(&nodes[0], &nodes[1]).unwrap();
+
+	// Sign our input and hand the transaction back.
+	let input_index = splice_tx.input.iter().position(|input| input.previous_output.txid == prevtx.txid()).unwrap();
+	let sighash = SighashCache::new(&splice_tx).segwit_signature_hash(input_index,
+		&ScriptBuf::new_p2pkh(&input_pubkey.pubkey_hash()), 60_000, EcdsaSighashType::All).unwrap();
+	let signature = secp_ctx.sign_ecdsa(&Message::from_slice(&sighash[..]).unwrap(), &input_key);
+	let mut signature = signature.serialize_der().to_vec();
+	signature.push(EcdsaSighashType::All as u8);
+	splice_tx.input[input_index].witness = Witness::from_slice(&[signature, input_pubkey.to_bytes()]);
+	nodes[0].node.funding_transaction_signed(&channel_id, &nodes[1].node.get_our_node_id(), splice_tx).unwrap();
+
+	let splice_tx = exchange_splice_tx_signatures(&nodes[0], &nodes[1]);
+	assert_eq!(splice_tx.input.len(), 2);
+	let change_value = splice_tx.output.iter().find(|output| output.script_pubkey == change_script).unwrap().value;
+	assert!(change_value < 10_000);
+	let splice_funding_outpoint = get_splice_funding_outpoint(&splice_tx, 150_000);
+
+	lock_splice(&nodes[0], &nodes[1], &splice_tx);
+	for node in nodes.iter() {
+		let channel = &node.node.list_channels()[0];
+		assert_eq!(channel.channel_value_satoshis, 150_000);
+		assert_eq!(channel.funding_txo, Some(splice_funding_outpoint));
+	}
+	send_payment(&nodes[0], &[&nodes[1]], 5_000_000);
+}
+
+#[test]
+fn test_splice_requires_peer_support() {
+	// We can't splice with a peer which hasn't signaled support for splicing.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), None]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+
+	let outputs = vec![TxOut { value: 20_000, script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20])) }];
+	match nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(),
+		SpliceContribution::SpliceOut { outputs }, 253, None)
+	{
+		Err(APIError::ChannelUnavailable { err }) => assert!(err.contains("does not support splicing")),
+		res => panic!("Unexpected result {:?}", res),
+	}
+	assert!(nodes[0].node.get_and_clear_pending_msg_events().is_empty());
+}
+
+#[test]
+fn test_splice_rejected() {
+	// A splice our counterparty won't accept is rejected with a tx_abort, failing the splice and
+	// leaving the channel usable.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), Some(splicing_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+
+	let outputs = vec![TxOut { value: 20_000, script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20])) }];
+	nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(),
+		SpliceContribution::SpliceOut { outputs }, 253, None).unwrap();
+	let mut splice = get_event_msg!(nodes[0], MessageSendEvent::SendSplice, nodes[1].node.get_our_node_id());
+	splice.funding_feerate_perkw = 252;
+	nodes[1].node.handle_splice(&nodes[0].node.get_our_node_id(), &splice);
+	let tx_abort = get_event_msg!(nodes[1], MessageSendEvent::SendTxAbort, nodes[0].node.get_our_node_id());
+	assert!(nodes[1].node.get_and_clear_pending_events().is_empty());
+
+	nodes[0].node.handle_tx_abort(&nodes[1].node.get_our_node_id(), &tx_abort);
+	let tx_abort_echo = get_event_msg!(nodes[0], MessageSendEvent::SendTxAbort, nodes[1].node.get_our_node_id());
+	match get_event!(nodes[0], Event::SpliceFailed) {
+		Event::SpliceFailed { channel_id: failed_channel_id, abandoned_funding_txo, .. } => {
+			assert_eq!(failed_channel_id, channel_id);
+			assert!(abandoned_funding_txo.is_none());
+		},
+		_ => unreachable!(),
+	}
+	// Receiving the echo doesn't trigger another tx_abort.
+	nodes[1].node.handle_tx_abort(&nodes[0].node.get_our_node_id(), &tx_abort_echo);
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
+
+#[test]
+fn test_splice_aborted_on_disconnect() {
+	// A splice which hasn't exchanged tx_signatures is aborted when our peer disconnects.
+	let chanmon_cfgs = create_chanmon_cfgs(2);
+	let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[Some(splicing_config()), Some(splicing_config())]);
+	let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+	let (_, _, channel_id, _) = create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 100_000, 0);
+
+	let outputs = vec![TxOut { value: 20_000, script_pubkey: ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::from_byte_array([42; 20])) }];
+	nodes[0].node.splice_channel(&channel_id, &nodes[1].node.get_our_node_id(),
+		SpliceContribution::SpliceOut { outputs }, 253, None).unwrap();
+	let splice = get_event_msg!(nodes[0], MessageSendEvent::SendSplice, nodes[1].node.get_our_node_id());
+	nodes[1].node.handle_splice(&nodes[0].node.get_our_node_id(), &splice);
+	get_event_msg!(nodes[1], MessageSendEvent::SendSpliceAck, nodes[0].node.get_our_node_id());
+
+	nodes[0].node.peer_disconnected(&nodes[1].node.get_our_node_id());
+	nodes[1].node.peer_disconnected(&nodes[0].node.get_our_node_id());
+	for node in nodes.iter() {
+		match get_event!(node, Event::SpliceFailed) {
+			Event::SpliceFailed { abandoned_funding_txo, .. } => assert!(abandoned_funding_txo.is_none()),
+			_ => unreachable!(),
+		}
+	}
+	let mut reconnect_args = ReconnectArgs::new(&nodes[0], &nodes[1]);
+	reconnect_args.send_channel_ready = (true, true);
+	reconnect_nodes(reconnect_args);
+	send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+}
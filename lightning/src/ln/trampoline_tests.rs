@@ -0,0 +1,182 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Tests for sending payments via trampoline nodes and forwarding them as one.
+
+use crate::events::{HTLCDestination, MessageSendEventsProvider};
+use crate::ln::channelmanager::{PaymentId, RecipientOnionFields};
+use crate::ln::functional_test_utils::*;
+use crate::ln::msgs::ChannelMessageHandler;
+use crate::prelude::*;
+use crate::routing::router::{InFlightHtlcs, PaymentParameters, Route, RouteParameters, Router, TrampolineRouter};
+
+fn trampoline_route<'a, 'b, 'c>(
+	nodes: &[Node<'a, 'b, 'c>], recipient: usize, amt_msat: u64
+) -> Route {
+	let router = TrampolineRouter::new(nodes[0].router, 1_000, 0, 72);
+	let route_params = RouteParameters::from_payment_params_and_value(
+		PaymentParameters::from_node_id(nodes[recipient].node.get_our_node_id(), TEST_FINAL_CLTV),
+		amt_msat
+	);
+	let first_hops = nodes[0].node.list_usable_channels();
+	router.find_route(
+		&nodes[0].node.get_our_node_id(), &route_params, Some(&first_hops.iter().collect::<Vec<_>>()),
+		InFlightHtlcs::new()
+	).unwrap()
+}
+
+#[test]
+fn trampoline_forward_success() {
+	// Route a payment from nodes[0] to nodes[2] with nodes[0] only knowing that nodes[1] can act as
+	// a trampoline, leaving nodes[1] to find the route on to the recipient.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let mut trampoline_cfg = test_default_channel_config();
+	trampoline_cfg.accept_trampoline_forwards = true;
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, Some(trampoline_cfg), None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0);
+
+	let amt_msat = 100_000;
+	let route = trampoline_route(&nodes, 2, amt_msat);
+	assert_eq!(route.paths.len(), 1);
+	assert_eq!(route.paths[0].hops.len(), 1);
+	assert_eq!(route.paths[0].trampoline_hops.len(), 2);
+	assert_eq!(route.paths[0].final_value_msat(), amt_msat);
+	assert_eq!(route.paths[0].fee_msat(), 1_000);
+
+	let (payment_preimage, payment_hash, payment_secret) = get_payment_preimage_hash!(nodes[2], Some(amt_msat));
+	send_along_route_with_secret(&nodes[0], route, &[&[&nodes[1], &nodes[2]]], amt_msat, payment_hash, payment_secret);
+	claim_payment(&nodes[0], &[&nodes[1], &nodes[2]], payment_preimage);
+}
+
+#[test]
+fn trampoline_forward_failure() {
+	// Failures from beyond the trampoline node are encrypted to it rather than to the sender, so it
+	// must fail the payment back with a `temporary_trampoline_failure` of its own.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let mut trampoline_cfg = test_default_channel_config();
+	trampoline_cfg.accept_trampoline_forwards = true;
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, Some(trampoline_cfg), None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	let chan_id_2 = create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0).2;
+
+	let amt_msat = 100_000;
+	let route = trampoline_route(&nodes, 2, amt_msat);
+	let (_, payment_hash, payment_secret) = get_payment_preimage_hash!(nodes[2], Some(amt_msat));
+	send_along_route_with_secret(&nodes[0], route, &[&[&nodes[1], &nodes[2]]], amt_msat, payment_hash, payment_secret);
+
+	nodes[2].node.fail_htlc_backwards(&payment_hash);
+	expect_pending_htlcs_forwardable_and_htlc_handling_failed!(nodes[2], vec![HTLCDestination::FailedPayment { payment_hash }]);
+	check_added_monitors(&nodes[2], 1);
+	let updates = get_htlc_update_msgs!(nodes[2], nodes[1].node.get_our_node_id());
+	nodes[1].node.handle_update_fail_htlc(&nodes[2].node.get_our_node_id(), &updates.update_fail_htlcs[0]);
+	commitment_signed_dance!(nodes[1], nodes[2], updates.commitment_signed, false);
+	expect_pending_htlcs_forwardable_and_htlc_handling_failed!(nodes[1],
+		vec![HTLCDestination::NextHopChannel { node_id: Some(nodes[2].node.get_our_node_id()), channel_id: chan_id_2 }]);
+	check_added_monitors(&nodes[1], 1);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_update_fail_htlc(&nodes[1].node.get_our_node_id(), &updates.update_fail_htlcs[0]);
+	commitment_signed_dance!(nodes[0], nodes[1], updates.commitment_signed, false);
+	expect_payment_failed_conditions(&nodes[0], payment_hash, false,
+		PaymentFailedConditions::new().expected_htlc_error_data(0x2000 | 25, &[]));
+}
+
+#[test]
+fn trampoline_forward_insufficient_fee() {
+	// The trampoline node's fee and CLTV delta are those of the outgoing channel of the route it
+	// found. If the sender didn't allot enough, the failure must be encrypted with the trampoline
+	// onion's shared secret so the sender can learn the required fee and CLTV delta from it.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let mut trampoline_cfg = test_default_channel_config();
+	trampoline_cfg.accept_trampoline_forwards = true;
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, Some(trampoline_cfg), None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	let chan_id_2 = create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0).2;
+
+	let mut outgoing_config = trampoline_cfg.channel_config;
+	outgoing_config.forwarding_fee_base_msat = 2_000;
+	nodes[1].node.update_channel_config(&nodes[2].node.get_our_node_id(), &[chan_id_2], &outgoing_config).unwrap();
+
+	let amt_msat = 100_000;
+	let route = trampoline_route(&nodes, 2, amt_msat);
+	let (_, payment_hash, payment_secret) = get_payment_preimage_hash!(nodes[2], Some(amt_msat));
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	let updates = get_htlc_update_msgs!(nodes[0], nodes[1].node.get_our_node_id());
+	nodes[1].node.handle_update_add_htlc(&nodes[0].node.get_our_node_id(), &updates.update_add_htlcs[0]);
+	commitment_signed_dance!(nodes[1], nodes[0], updates.commitment_signed, false);
+	expect_pending_htlcs_forwardable_and_htlc_handling_failed!(nodes[1],
+		vec![HTLCDestination::NextTrampoline { node_id: nodes[2].node.get_our_node_id() }]);
+	check_added_monitors(&nodes[1], 1);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_update_fail_htlc(&nodes[1].node.get_our_node_id(), &updates.update_fail_htlcs[0]);
+	commitment_signed_dance!(nodes[0], nodes[1], updates.commitment_signed, false);
+
+	let mut err_data = Vec::new();
+	err_data.extend_from_slice(&2_000u32.to_be_bytes());
+	err_data.extend_from_slice(&outgoing_config.forwarding_fee_proportional_millionths.to_be_bytes());
+	err_data.extend_from_slice(&outgoing_config.cltv_expiry_delta.to_be_bytes());
+	expect_payment_failed_conditions(&nodes[0], payment_hash, false,
+		PaymentFailedConditions::new().expected_htlc_error_data(0x2000 | 26, &err_data));
+}
+
+#[test]
+fn trampoline_forwards_rejected_by_default() {
+	// Nodes which don't accept trampoline forwards don't advertise support for them, but should
+	// still reject any trampoline onions they receive.
+	let chanmon_cfgs = create_chanmon_cfgs(3);
+	let node_cfgs = create_node_cfgs(3, &chanmon_cfgs);
+	let node_chanmgrs = create_node_chanmgrs(3, &node_cfgs, &[None, None, None]);
+	let nodes = create_network(3, &node_cfgs, &node_chanmgrs);
+	create_announced_chan_between_nodes_with_value(&nodes, 0, 1, 1_000_000, 0);
+	create_announced_chan_between_nodes_with_value(&nodes, 1, 2, 1_000_000, 0);
+
+	// Without any trampoline-capable peers, the `TrampolineRouter` falls back to regular routing.
+	let amt_msat = 100_000;
+	let route = trampoline_route(&nodes, 2, amt_msat);
+	assert!(route.paths[0].trampoline_hops.is_empty());
+	assert_eq!(route.paths[0].hops.len(), 2);
+
+	// Build the trampoline route by hand instead and check it is rejected.
+	let mut trampoline_cfg = test_default_channel_config();
+	trampoline_cfg.accept_trampoline_forwards = true;
+	let trampoline_features = crate::ln::channelmanager::provided_init_features(&trampoline_cfg);
+	let mut first_hops = nodes[0].node.list_usable_channels();
+	first_hops[0].counterparty.features = trampoline_features;
+	let router = TrampolineRouter::new(nodes[0].router, 1_000, 0, 72);
+	let route_params = RouteParameters::from_payment_params_and_value(
+		PaymentParameters::from_node_id(nodes[2].node.get_our_node_id(), TEST_FINAL_CLTV), amt_msat
+	);
+	let route = router.find_route(
+		&nodes[0].node.get_our_node_id(), &route_params, Some(&first_hops.iter().collect::<Vec<_>>()),
+		InFlightHtlcs::new()
+	).unwrap();
+	assert_eq!(route.paths[0].trampoline_hops.len(), 2);
+
+	let (_, payment_hash, payment_secret) = get_payment_preimage_hash!(nodes[2], Some(amt_msat));
+	nodes[0].node.send_payment_with_route(&route, payment_hash,
+		RecipientOnionFields::secret_only(payment_secret), PaymentId(payment_hash.0)).unwrap();
+	check_added_monitors(&nodes[0], 1);
+	let updates = get_htlc_update_msgs!(nodes[0], nodes[1].node.get_our_node_id());
+	nodes[1].node.handle_update_add_htlc(&nodes[0].node.get_our_node_id(), &updates.update_add_htlcs[0]);
+	commitment_signed_dance!(nodes[1], nodes[0], updates.commitment_signed, true, true);
+	let updates = get_htlc_update_msgs!(nodes[1], nodes[0].node.get_our_node_id());
+	nodes[0].node.handle_update_fail_htlc(&nodes[1].node.get_our_node_id(), &updates.update_fail_htlcs[0]);
+	commitment_signed_dance!(nodes[0], nodes[1], updates.commitment_signed, false);
+	expect_payment_failed_conditions(&nodes[0], payment_hash, true,
+		PaymentFailedConditions::new().expected_htlc_error_data(0x4000 | 22, &[]));
+	assert!(nodes[1].node.get_and_clear_pending_msg_events().is_empty());
+}
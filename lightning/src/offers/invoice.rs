@@ -247,8 +247,9 @@ impl<'a, S: SigningPubkeyStrategy> InvoiceBuilder<'a, S> {
 					amount_msats.checked_mul(invoice_request.quantity().unwrap_or(1))
 						.ok_or(Bolt12SemanticError::InvalidAmount)
 				},
-				Some(Amount::Currency { .. }) => Err(Bolt12SemanticError::UnsupportedCurrency),
-				None => Err(Bolt12SemanticError::MissingAmount),
+				// Requests for offers denominated in other currencies must set an amount, which
+				// is checked against an exchange rate before building an invoice.
+				Some(Amount::Currency { .. }) | None => Err(Bolt12SemanticError::MissingAmount),
 			},
 		}
 	}
@@ -1082,12 +1083,12 @@ tlv_stream!(InvoiceTlvStream, InvoiceTlvStreamRef, 160..240, {
 	(176, node_id: PublicKey),
 });
 
-type BlindedPathIter<'a> = core::iter::Map<
+pub(super) type BlindedPathIter<'a> = core::iter::Map<
 	core::slice::Iter<'a, (BlindedPayInfo, BlindedPath)>,
 	for<'r> fn(&'r (BlindedPayInfo, BlindedPath)) -> &'r BlindedPath,
 >;
 
-type BlindedPayInfoIter<'a> = core::iter::Map<
+pub(super) type BlindedPayInfoIter<'a> = core::iter::Map<
 	core::slice::Iter<'a, (BlindedPayInfo, BlindedPath)>,
 	for<'r> fn(&'r (BlindedPayInfo, BlindedPath)) -> &'r BlindedPayInfo,
 >;
@@ -23,5 +23,6 @@ pub mod parse;
 mod payer;
 pub mod refund;
 pub(crate) mod signer;
+pub mod static_invoice;
 #[cfg(test)]
 pub(crate) mod test_utils;
@@ -235,10 +235,10 @@ impl<'a, M: MetadataStrategy, T: secp256k1::Signing> OfferBuilder<'a, M, T> {
 		self.amount(Amount::Bitcoin { amount_msats })
 	}
 
-	/// Sets the [`Offer::amount`].
+	/// Sets the [`Offer::amount`], which may be denominated in a currency other than bitcoin.
 	///
 	/// Successive calls to this method will override the previous setting.
-	pub(super) fn amount(mut self, amount: Amount) -> Self {
+	pub fn amount(mut self, amount: Amount) -> Self {
 		self.offer.amount = Some(amount);
 		self
 	}
@@ -287,7 +287,11 @@ impl<'a, M: MetadataStrategy, T: secp256k1::Signing> OfferBuilder<'a, M, T> {
 					return Err(Bolt12SemanticError::InvalidAmount);
 				}
 			},
-			Some(Amount::Currency { .. }) => return Err(Bolt12SemanticError::UnsupportedCurrency),
+			Some(Amount::Currency { iso4217_code, .. }) => {
+				if !iso4217_code.iter().all(|c| c.is_ascii_uppercase()) {
+					return Err(Bolt12SemanticError::UnsupportedCurrency);
+				}
+			},
 			None => {},
 		}
 
@@ -640,7 +644,17 @@ impl OfferContents {
 		let offer_amount_msats = match self.amount {
 			None => 0,
 			Some(Amount::Bitcoin { amount_msats }) => amount_msats,
-			Some(Amount::Currency { .. }) => return Err(Bolt12SemanticError::UnsupportedCurrency),
+			Some(Amount::Currency { .. }) => {
+				// Without an exchange rate the payer must pick the amount, leaving it to the
+				// recipient to check it against their own conversion.
+				return match amount_msats {
+					None => Err(Bolt12SemanticError::MissingAmount),
+					Some(amount_msats) if amount_msats > MAX_VALUE_MSAT => {
+						Err(Bolt12SemanticError::InvalidAmount)
+					},
+					Some(_) => Ok(()),
+				};
+			},
 		};
 
 		if !self.expects_quantity() || quantity.is_some() {
@@ -781,6 +795,37 @@ pub enum Amount {
 /// An ISO 4712 three-letter currency code (e.g., USD).
 pub type CurrencyCode = [u8; 3];
 
+/// A source of exchange rates used to price an [`Offer`] whose [`Amount`] is denominated in a
+/// currency other than bitcoin.
+///
+/// [`ChannelManager`] consults it both when responding to an [`InvoiceRequest`] for such an offer
+/// and when choosing an amount to pay for one.
+///
+/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+pub trait CurrencyConversion {
+	/// Converts `amount`, given in the currency identified by `iso4217_code` and adjusted by its
+	/// ISO 4712 exponent (e.g., USD cents), into millisatoshis.
+	///
+	/// Returns `Err` if the currency is not supported or no exchange rate is currently available.
+	fn convert_to_msats(&self, iso4217_code: CurrencyCode, amount: u64) -> Result<u64, ()>;
+}
+
+/// A dummy [`CurrencyConversion`] which supports no currencies, limiting offers to those
+/// denominated in bitcoin.
+pub struct NoCurrencyConversion {}
+
+impl CurrencyConversion for NoCurrencyConversion {
+	fn convert_to_msats(&self, _iso4217_code: CurrencyCode, _amount: u64) -> Result<u64, ()> {
+		Err(())
+	}
+}
+
+impl Deref for NoCurrencyConversion {
+	type Target = NoCurrencyConversion;
+	fn deref(&self) -> &Self { self }
+}
+
 /// Quantity of items supported by an [`Offer`].
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Quantity {
@@ -1154,7 +1199,13 @@ mod tests {
 		assert_eq!(builder.offer.amount, Some(currency_amount.clone()));
 		assert_eq!(tlv_stream.amount, Some(10));
 		assert_eq!(tlv_stream.currency, Some(b"USD"));
-		match builder.build() {
+		let offer = builder.build().unwrap();
+		assert_eq!(offer.amount(), Some(&currency_amount));
+
+		match OfferBuilder::new("foo".into(), pubkey(42))
+			.amount(Amount::Currency { iso4217_code: *b"usd", amount: 10 })
+			.build()
+		{
 			Ok(_) => panic!("expected error"),
 			Err(e) => assert_eq!(e, Bolt12SemanticError::UnsupportedCurrency),
 		}
@@ -0,0 +1,671 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Data structures and encoding for static BOLT 12 invoices.
+//!
+//! A [`StaticInvoice`] is built for an [`Offer`] ahead of time and does not commit to a
+//! [`PaymentHash`]. It may be handed to an always-online node (e.g., an LSP) which then returns it
+//! in response to [`InvoiceRequest`]s while the recipient is offline. Payers use it to pay via a
+//! keysend over one of its payment paths once the recipient signals that it is online again (see
+//! [`onion_message::async_payments`]).
+//!
+//! [`PaymentHash`]: crate::ln::PaymentHash
+//! [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+//! [`onion_message::async_payments`]: crate::onion_message::async_payments
+
+use bitcoin::blockdata::constants::ChainHash;
+use bitcoin::secp256k1::{KeyPair, PublicKey, Secp256k1, self};
+use bitcoin::secp256k1::schnorr::Signature;
+use core::convert::{Infallible, TryFrom};
+use core::time::Duration;
+use crate::io;
+use crate::blinded_path::BlindedPath;
+use crate::ln::features::Bolt12InvoiceFeatures;
+use crate::ln::inbound_payment::ExpandedKey;
+use crate::ln::msgs::DecodeError;
+use crate::offers::invoice::{BlindedPathIter, BlindedPayInfo, BlindedPayInfoIter, DEFAULT_RELATIVE_EXPIRY};
+use crate::offers::invoice_request::InvoiceRequest;
+use crate::offers::merkle::{SignatureTlvStream, SignatureTlvStreamRef, TaggedHash, TlvStream, self};
+use crate::offers::offer::{OFFER_TYPES, Offer, OfferContents, OfferTlvStream};
+#[cfg(test)]
+use crate::offers::offer::OfferTlvStreamRef;
+use crate::offers::parse::{Bolt12ParseError, Bolt12SemanticError, ParsedMessage};
+use crate::util::ser::{HighZeroBytesDroppedBigSize, Iterable, SeekReadable, WithoutLength, Writeable, Writer};
+
+use crate::prelude::*;
+
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+/// Tag for the hash function used when signing a [`StaticInvoice`]'s merkle root.
+pub const SIGNATURE_TAG: &'static str = concat!("lightning", "static_invoice", "signature");
+
+/// Builds a [`StaticInvoice`] for an [`Offer`] whose signing pubkey was derived from its metadata.
+///
+/// This is not exported to bindings users as builder patterns don't map outside of move semantics.
+pub struct StaticInvoiceBuilder<'a> {
+	offer_bytes: &'a Vec<u8>,
+	invoice: InvoiceContents,
+	keys: KeyPair,
+}
+
+impl<'a> StaticInvoiceBuilder<'a> {
+	/// Initialize a [`StaticInvoiceBuilder`] for the given [`Offer`], deriving the signing keys from
+	/// its metadata using the given [`ExpandedKey`].
+	///
+	/// Errors if the offer was not built using [`OfferBuilder::deriving_signing_pubkey`] with the
+	/// same [`ExpandedKey`], or if no payment or message paths are given.
+	///
+	/// [`OfferBuilder::deriving_signing_pubkey`]: crate::offers::offer::OfferBuilder::deriving_signing_pubkey
+	pub fn for_offer_using_derived_keys<T: secp256k1::Signing>(
+		offer: &'a Offer, payment_paths: Vec<(BlindedPayInfo, BlindedPath)>,
+		message_paths: Vec<BlindedPath>, created_at: Duration, expanded_key: &ExpandedKey,
+		secp_ctx: &Secp256k1<T>
+	) -> Result<Self, Bolt12SemanticError> {
+		if payment_paths.is_empty() || message_paths.is_empty() {
+			return Err(Bolt12SemanticError::MissingPaths);
+		}
+
+		let keys = match offer.contents.verify(&offer.bytes, expanded_key, secp_ctx) {
+			Ok(Some(keys)) => keys,
+			_ => return Err(Bolt12SemanticError::InvalidMetadata),
+		};
+		if keys.public_key() != offer.signing_pubkey() {
+			return Err(Bolt12SemanticError::InvalidSigningPubkey);
+		}
+
+		let invoice = InvoiceContents {
+			offer: offer.contents.clone(),
+			payment_paths,
+			message_paths,
+			created_at,
+			relative_expiry: None,
+			features: Bolt12InvoiceFeatures::empty(),
+			signing_pubkey: keys.public_key(),
+		};
+
+		Ok(Self { offer_bytes: &offer.bytes, invoice, keys })
+	}
+
+	/// Sets the [`StaticInvoice::relative_expiry`] as seconds since [`StaticInvoice::created_at`].
+	/// Any expiry that has already passed is valid and can be checked for using
+	/// [`StaticInvoice::is_expired`].
+	///
+	/// Successive calls to this method will override the previous setting.
+	pub fn relative_expiry(mut self, relative_expiry_secs: u32) -> Self {
+		let relative_expiry = Duration::from_secs(relative_expiry_secs as u64);
+		self.invoice.relative_expiry = Some(relative_expiry);
+		self
+	}
+
+	/// Sets [`StaticInvoice::invoice_features`] to indicate MPP may be used. Otherwise, MPP is
+	/// disallowed.
+	pub fn allow_mpp(mut self) -> Self {
+		self.invoice.features.set_basic_mpp_optional();
+		self
+	}
+
+	/// Builds a signed [`StaticInvoice`] after checking for valid semantics.
+	pub fn build_and_sign<T: secp256k1::Signing>(
+		self, secp_ctx: &Secp256k1<T>
+	) -> Result<StaticInvoice, Bolt12SemanticError> {
+		#[cfg(feature = "std")] {
+			if self.invoice.is_offer_expired() {
+				return Err(Bolt12SemanticError::AlreadyExpired);
+			}
+		}
+
+		#[cfg(not(feature = "std"))] {
+			if self.invoice.is_offer_expired_no_std(self.invoice.created_at) {
+				return Err(Bolt12SemanticError::AlreadyExpired);
+			}
+		}
+
+		let StaticInvoiceBuilder { offer_bytes, invoice, keys } = self;
+
+		// Use the offer bytes instead of the offer TLV stream as the latter may have contained
+		// unknown TLV records, which are not stored in `OfferContents`.
+		let mut bytes = Vec::new();
+		WithoutLength(offer_bytes).write(&mut bytes).unwrap();
+		invoice.as_invoice_tlv_stream().write(&mut bytes).unwrap();
+
+		let tagged_hash = TaggedHash::new(SIGNATURE_TAG, &bytes);
+		let signature = merkle::sign_message::<_, Infallible, _>(
+			|message: &TaggedHash| Ok(secp_ctx.sign_schnorr_no_aux_rand(message.as_digest(), &keys)),
+			&tagged_hash, keys.public_key(),
+		).unwrap();
+
+		// Append the signature TLV record to the bytes.
+		let signature_tlv_stream = SignatureTlvStreamRef {
+			signature: Some(&signature),
+		};
+		signature_tlv_stream.write(&mut bytes).unwrap();
+
+		Ok(StaticInvoice { bytes, contents: invoice, signature })
+	}
+}
+
+/// A `StaticInvoice` is a reusable payment request corresponding to an [`Offer`].
+///
+/// Unlike a [`Bolt12Invoice`], it does not include a [`PaymentHash`] and thus may be served on the
+/// recipient's behalf by another node while the recipient is offline. Payers send a keysend over
+/// [`StaticInvoice::payment_paths`] that their LSP holds, notifying the recipient with a
+/// [`HeldHtlcAvailable`] onion message over one of [`StaticInvoice::message_paths`]. The LSP
+/// forwards the HTLC once the recipient comes online and replies with a [`ReleaseHeldHtlc`].
+///
+/// [`Bolt12Invoice`]: crate::offers::invoice::Bolt12Invoice
+/// [`PaymentHash`]: crate::ln::PaymentHash
+/// [`HeldHtlcAvailable`]: crate::onion_message::async_payments::HeldHtlcAvailable
+/// [`ReleaseHeldHtlc`]: crate::onion_message::async_payments::ReleaseHeldHtlc
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct StaticInvoice {
+	bytes: Vec<u8>,
+	contents: InvoiceContents,
+	signature: Signature,
+}
+
+/// The contents of a [`StaticInvoice`] for responding to an [`Offer`].
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+struct InvoiceContents {
+	offer: OfferContents,
+	payment_paths: Vec<(BlindedPayInfo, BlindedPath)>,
+	message_paths: Vec<BlindedPath>,
+	created_at: Duration,
+	relative_expiry: Option<Duration>,
+	features: Bolt12InvoiceFeatures,
+	signing_pubkey: PublicKey,
+}
+
+impl StaticInvoice {
+	offer_accessors!(self, self.contents.offer);
+
+	/// The chain that must be used when paying the invoice.
+	pub fn chain(&self) -> ChainHash {
+		self.contents.offer.chains().first().cloned()
+			.unwrap_or_else(|| self.contents.offer.implied_chain())
+	}
+
+	/// Paths to the recipient originating from publicly reachable nodes, including information
+	/// needed for routing payments across them.
+	///
+	/// Blinded paths provide recipient privacy by obfuscating its node id. Note, however, that this
+	/// privacy is lost if a public node id is used for [`StaticInvoice::signing_pubkey`].
+	pub fn payment_paths(&self) -> &[(BlindedPayInfo, BlindedPath)] {
+		&self.contents.payment_paths[..]
+	}
+
+	/// Paths to the recipient for sending [`HeldHtlcAvailable`] onion messages. Typically, these
+	/// terminate at the node serving the invoice on the recipient's behalf.
+	///
+	/// [`HeldHtlcAvailable`]: crate::onion_message::async_payments::HeldHtlcAvailable
+	pub fn message_paths(&self) -> &[BlindedPath] {
+		&self.contents.message_paths[..]
+	}
+
+	/// Duration since the Unix epoch when the invoice was created.
+	pub fn created_at(&self) -> Duration {
+		self.contents.created_at
+	}
+
+	/// Duration since [`StaticInvoice::created_at`] when the invoice has expired and therefore
+	/// should no longer be paid.
+	pub fn relative_expiry(&self) -> Duration {
+		self.contents.relative_expiry.unwrap_or(DEFAULT_RELATIVE_EXPIRY)
+	}
+
+	/// Whether the invoice has expired.
+	#[cfg(feature = "std")]
+	pub fn is_expired(&self) -> bool {
+		self.contents.is_expired()
+	}
+
+	/// Whether the invoice has expired given the current time as duration since the Unix epoch.
+	pub fn is_expired_no_std(&self, duration_since_epoch: Duration) -> bool {
+		self.contents.is_expired_no_std(duration_since_epoch)
+	}
+
+	/// Features pertaining to paying an invoice.
+	pub fn invoice_features(&self) -> &Bolt12InvoiceFeatures {
+		&self.contents.features
+	}
+
+	/// Signature of the invoice verified using [`StaticInvoice::signing_pubkey`].
+	pub fn signature(&self) -> Signature {
+		self.signature
+	}
+
+	/// Whether the invoice was built for the same [`Offer`] that the given [`InvoiceRequest`] was
+	/// requested for, including any unknown offer TLV records.
+	pub fn is_from_same_offer(&self, invoice_request: &InvoiceRequest) -> bool {
+		let invoice_offer_records = TlvStream::new(&self.bytes).range(OFFER_TYPES);
+		let invreq_offer_records = TlvStream::new(&invoice_request.bytes).range(OFFER_TYPES);
+		invoice_offer_records.map(|record| record.record_bytes)
+			.eq(invreq_offer_records.map(|record| record.record_bytes))
+	}
+
+	#[cfg(test)]
+	fn as_tlv_stream(&self) -> FullInvoiceTlvStreamRef {
+		let (offer_tlv_stream, invoice_tlv_stream) = self.contents.as_tlv_stream();
+		let signature_tlv_stream = SignatureTlvStreamRef {
+			signature: Some(&self.signature),
+		};
+		(offer_tlv_stream, invoice_tlv_stream, signature_tlv_stream)
+	}
+}
+
+impl InvoiceContents {
+	#[cfg(feature = "std")]
+	fn is_offer_expired(&self) -> bool {
+		self.offer.is_expired()
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn is_offer_expired_no_std(&self, duration_since_epoch: Duration) -> bool {
+		self.offer.is_expired_no_std(duration_since_epoch)
+	}
+
+	#[cfg(feature = "std")]
+	fn is_expired(&self) -> bool {
+		let absolute_expiry = self.created_at.checked_add(self.relative_expiry());
+		match absolute_expiry {
+			Some(seconds_from_epoch) => match SystemTime::UNIX_EPOCH.elapsed() {
+				Ok(elapsed) => elapsed > seconds_from_epoch,
+				Err(_) => false,
+			},
+			None => false,
+		}
+	}
+
+	fn is_expired_no_std(&self, duration_since_epoch: Duration) -> bool {
+		self.created_at.checked_add(self.relative_expiry())
+			.map(|absolute_expiry| duration_since_epoch > absolute_expiry)
+			.unwrap_or(false)
+	}
+
+	fn relative_expiry(&self) -> Duration {
+		self.relative_expiry.unwrap_or(DEFAULT_RELATIVE_EXPIRY)
+	}
+
+	fn as_invoice_tlv_stream(&self) -> InvoiceTlvStreamRef {
+		let features = {
+			if self.features == Bolt12InvoiceFeatures::empty() { None }
+			else { Some(&self.features) }
+		};
+
+		InvoiceTlvStreamRef {
+			paths: Some(Iterable(self.payment_paths.iter().map(|(_, path)| path))),
+			blindedpay: Some(Iterable(self.payment_paths.iter().map(|(payinfo, _)| payinfo))),
+			created_at: Some(self.created_at.as_secs()),
+			relative_expiry: self.relative_expiry.map(|duration| duration.as_secs() as u32),
+			features,
+			node_id: Some(&self.signing_pubkey),
+			message_paths: Some(&self.message_paths),
+		}
+	}
+
+	#[cfg(test)]
+	fn as_tlv_stream(&self) -> PartialInvoiceTlvStreamRef {
+		(self.offer.as_tlv_stream(), self.as_invoice_tlv_stream())
+	}
+}
+
+impl Writeable for StaticInvoice {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), io::Error> {
+		WithoutLength(&self.bytes).write(writer)
+	}
+}
+
+impl TryFrom<Vec<u8>> for StaticInvoice {
+	type Error = Bolt12ParseError;
+
+	fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+		let parsed_invoice = ParsedMessage::<FullInvoiceTlvStream>::try_from(bytes)?;
+		StaticInvoice::try_from(parsed_invoice)
+	}
+}
+
+/// Valid type range for static invoice TLV records.
+const INVOICE_TYPES: core::ops::Range<u64> = 160..240;
+
+tlv_stream!(InvoiceTlvStream, InvoiceTlvStreamRef, INVOICE_TYPES, {
+	(160, paths: (Vec<BlindedPath>, WithoutLength, Iterable<'a, BlindedPathIter<'a>, BlindedPath>)),
+	(162, blindedpay: (Vec<BlindedPayInfo>, WithoutLength, Iterable<'a, BlindedPayInfoIter<'a>, BlindedPayInfo>)),
+	(164, created_at: (u64, HighZeroBytesDroppedBigSize)),
+	(166, relative_expiry: (u32, HighZeroBytesDroppedBigSize)),
+	(174, features: (Bolt12InvoiceFeatures, WithoutLength)),
+	(176, node_id: PublicKey),
+	(238, message_paths: (Vec<BlindedPath>, WithoutLength)),
+});
+
+type FullInvoiceTlvStream = (OfferTlvStream, InvoiceTlvStream, SignatureTlvStream);
+
+#[cfg(test)]
+type FullInvoiceTlvStreamRef<'a> = (
+	OfferTlvStreamRef<'a>,
+	InvoiceTlvStreamRef<'a>,
+	SignatureTlvStreamRef<'a>,
+);
+
+impl SeekReadable for FullInvoiceTlvStream {
+	fn read<R: io::Read + io::Seek>(r: &mut R) -> Result<Self, DecodeError> {
+		let offer = SeekReadable::read(r)?;
+		let invoice = SeekReadable::read(r)?;
+		let signature = SeekReadable::read(r)?;
+
+		Ok((offer, invoice, signature))
+	}
+}
+
+type PartialInvoiceTlvStream = (OfferTlvStream, InvoiceTlvStream);
+
+#[cfg(test)]
+type PartialInvoiceTlvStreamRef<'a> = (OfferTlvStreamRef<'a>, InvoiceTlvStreamRef<'a>);
+
+impl TryFrom<ParsedMessage<FullInvoiceTlvStream>> for StaticInvoice {
+	type Error = Bolt12ParseError;
+
+	fn try_from(invoice: ParsedMessage<FullInvoiceTlvStream>) -> Result<Self, Self::Error> {
+		let ParsedMessage { bytes, tlv_stream } = invoice;
+		let (offer_tlv_stream, invoice_tlv_stream, SignatureTlvStream { signature }) = tlv_stream;
+		let contents = InvoiceContents::try_from((offer_tlv_stream, invoice_tlv_stream))?;
+
+		let signature = match signature {
+			None => return Err(Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::MissingSignature)),
+			Some(signature) => signature,
+		};
+		let tagged_hash = TaggedHash::new(SIGNATURE_TAG, &bytes);
+		merkle::verify_signature(&signature, &tagged_hash, contents.signing_pubkey)?;
+
+		Ok(StaticInvoice { bytes, contents, signature })
+	}
+}
+
+impl TryFrom<PartialInvoiceTlvStream> for InvoiceContents {
+	type Error = Bolt12SemanticError;
+
+	fn try_from(tlv_stream: PartialInvoiceTlvStream) -> Result<Self, Self::Error> {
+		let (
+			offer_tlv_stream,
+			InvoiceTlvStream {
+				paths, blindedpay, created_at, relative_expiry, features, node_id, message_paths,
+			},
+		) = tlv_stream;
+
+		let payment_paths = match (blindedpay, paths) {
+			(_, None) => return Err(Bolt12SemanticError::MissingPaths),
+			(None, _) => return Err(Bolt12SemanticError::InvalidPayInfo),
+			(_, Some(paths)) if paths.is_empty() => return Err(Bolt12SemanticError::MissingPaths),
+			(Some(blindedpay), Some(paths)) if paths.len() != blindedpay.len() => {
+				return Err(Bolt12SemanticError::InvalidPayInfo);
+			},
+			(Some(blindedpay), Some(paths)) => {
+				blindedpay.into_iter().zip(paths.into_iter()).collect::<Vec<_>>()
+			},
+		};
+
+		let message_paths = match message_paths {
+			Some(paths) if !paths.is_empty() => paths,
+			_ => return Err(Bolt12SemanticError::MissingPaths),
+		};
+
+		let created_at = match created_at {
+			None => return Err(Bolt12SemanticError::MissingCreationTime),
+			Some(timestamp) => Duration::from_secs(timestamp),
+		};
+
+		let relative_expiry = relative_expiry
+			.map(Into::<u64>::into)
+			.map(Duration::from_secs);
+
+		let features = features.unwrap_or_else(Bolt12InvoiceFeatures::empty);
+
+		let signing_pubkey = match node_id {
+			None => return Err(Bolt12SemanticError::MissingSigningPubkey),
+			Some(node_id) => node_id,
+		};
+
+		let offer = OfferContents::try_from(offer_tlv_stream)?;
+		if offer.signing_pubkey() != signing_pubkey {
+			return Err(Bolt12SemanticError::InvalidSigningPubkey);
+		}
+
+		Ok(InvoiceContents {
+			offer, payment_paths, message_paths, created_at, relative_expiry, features,
+			signing_pubkey,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{FullInvoiceTlvStreamRef, SIGNATURE_TAG, StaticInvoice, StaticInvoiceBuilder};
+
+	use bitcoin::blockdata::constants::ChainHash;
+	use bitcoin::network::constants::Network;
+	use bitcoin::secp256k1::{Secp256k1, self};
+	use core::convert::TryFrom;
+	use core::time::Duration;
+	use crate::blinded_path::{BlindedHop, BlindedPath};
+	use crate::ln::features::Bolt12InvoiceFeatures;
+	use crate::ln::inbound_payment::ExpandedKey;
+	use crate::offers::invoice::DEFAULT_RELATIVE_EXPIRY;
+	use crate::offers::merkle::{SignatureTlvStreamRef, TaggedHash, self};
+	use crate::offers::offer::{Offer, OfferBuilder};
+	use crate::offers::parse::{Bolt12ParseError, Bolt12SemanticError};
+	use crate::offers::test_utils::*;
+	use crate::sign::KeyMaterial;
+	use crate::util::ser::Writeable;
+
+	trait ToBytes {
+		fn to_bytes(&self) -> Vec<u8>;
+	}
+
+	impl<'a> ToBytes for FullInvoiceTlvStreamRef<'a> {
+		fn to_bytes(&self) -> Vec<u8> {
+			let mut buffer = Vec::new();
+			self.0.write(&mut buffer).unwrap();
+			self.1.write(&mut buffer).unwrap();
+			self.2.write(&mut buffer).unwrap();
+			buffer
+		}
+	}
+
+	fn blinded_path() -> BlindedPath {
+		BlindedPath {
+			introduction_node_id: pubkey(40),
+			blinding_point: pubkey(41),
+			blinded_hops: vec![
+				BlindedHop { blinded_node_id: pubkey(42), encrypted_payload: vec![0; 43] },
+				BlindedHop { blinded_node_id: pubkey(43), encrypted_payload: vec![0; 44] },
+			],
+		}
+	}
+
+	fn offer(expanded_key: &ExpandedKey) -> Offer {
+		let secp_ctx = Secp256k1::new();
+		OfferBuilder
+			::deriving_signing_pubkey("foo".into(), recipient_pubkey(), expanded_key, &FixedEntropy {}, &secp_ctx)
+			.path(blinded_path())
+			.build().unwrap()
+	}
+
+	#[test]
+	fn builds_static_invoice_with_defaults() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+		let now = now();
+		let offer = offer(&expanded_key);
+
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now, &expanded_key, &secp_ctx
+		).unwrap()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		let mut buffer = Vec::new();
+		invoice.write(&mut buffer).unwrap();
+
+		assert_eq!(invoice.bytes, buffer.as_slice());
+		assert_eq!(invoice.chain(), ChainHash::using_genesis_block(Network::Bitcoin));
+		assert_eq!(invoice.description(), offer.description());
+		assert_eq!(invoice.paths(), offer.paths());
+		assert_eq!(invoice.signing_pubkey(), offer.signing_pubkey());
+		assert_eq!(invoice.payment_paths(), payment_paths().as_slice());
+		assert_eq!(invoice.message_paths(), &[blinded_path()]);
+		assert_eq!(invoice.created_at(), now);
+		assert_eq!(invoice.relative_expiry(), DEFAULT_RELATIVE_EXPIRY);
+		#[cfg(feature = "std")]
+		assert!(!invoice.is_expired());
+		assert_eq!(invoice.invoice_features(), &Bolt12InvoiceFeatures::empty());
+
+		let message = TaggedHash::new(SIGNATURE_TAG, &invoice.bytes);
+		assert!(merkle::verify_signature(&invoice.signature, &message, offer.signing_pubkey()).is_ok());
+
+		if let Err(e) = StaticInvoice::try_from(buffer) {
+			panic!("error parsing invoice: {:?}", e);
+		}
+	}
+
+	#[test]
+	fn builds_static_invoice_with_relative_expiry_and_mpp() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+		let now = now();
+		let offer = offer(&expanded_key);
+
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now - Duration::from_secs(7200),
+			&expanded_key, &secp_ctx
+		).unwrap()
+			.relative_expiry(3600)
+			.allow_mpp()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		assert_eq!(invoice.relative_expiry(), Duration::from_secs(3600));
+		#[cfg(feature = "std")]
+		assert!(invoice.is_expired());
+		assert!(invoice.is_expired_no_std(now));
+
+		let mut features = Bolt12InvoiceFeatures::empty();
+		features.set_basic_mpp_optional();
+		assert_eq!(invoice.invoice_features(), &features);
+	}
+
+	#[test]
+	fn fails_building_static_invoice_without_derived_keys() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+
+		let non_derived_offer = OfferBuilder::new("foo".into(), recipient_pubkey()).build().unwrap();
+		match StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&non_derived_offer, payment_paths(), vec![blinded_path()], now(), &expanded_key, &secp_ctx
+		) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::InvalidMetadata),
+		}
+
+		let offer = offer(&expanded_key);
+		let other_expanded_key = ExpandedKey::new(&KeyMaterial([41; 32]));
+		match StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now(), &other_expanded_key, &secp_ctx
+		) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::InvalidMetadata),
+		}
+
+		match StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![], now(), &expanded_key, &secp_ctx
+		) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => assert_eq!(e, Bolt12SemanticError::MissingPaths),
+		}
+	}
+
+	#[test]
+	fn checks_invoice_is_from_same_offer() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+		let offer = offer(&expanded_key);
+
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now(), &expanded_key, &secp_ctx
+		).unwrap()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		let invoice_request = offer.request_invoice(vec![1; 32], payer_pubkey()).unwrap()
+			.amount_msats(1000).unwrap()
+			.build().unwrap()
+			.sign(payer_sign).unwrap();
+		assert!(invoice.is_from_same_offer(&invoice_request));
+
+		let other_offer = OfferBuilder::new("foo".into(), recipient_pubkey()).build().unwrap();
+		let invoice_request = other_offer.request_invoice(vec![1; 32], payer_pubkey()).unwrap()
+			.amount_msats(1000).unwrap()
+			.build().unwrap()
+			.sign(payer_sign).unwrap();
+		assert!(!invoice.is_from_same_offer(&invoice_request));
+	}
+
+	#[test]
+	fn fails_parsing_static_invoice_with_invalid_signature() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+		let offer = offer(&expanded_key);
+
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now(), &expanded_key, &secp_ctx
+		).unwrap()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		let mut buffer = Vec::new();
+		invoice.write(&mut buffer).unwrap();
+		let last_signature_byte = buffer.last_mut().unwrap();
+		*last_signature_byte = last_signature_byte.wrapping_add(1);
+
+		match StaticInvoice::try_from(buffer) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => {
+				assert_eq!(e, Bolt12ParseError::InvalidSignature(secp256k1::Error::InvalidSignature));
+			},
+		}
+
+		let mut tlv_stream = invoice.as_tlv_stream();
+		tlv_stream.2 = SignatureTlvStreamRef { signature: None };
+
+		match StaticInvoice::try_from(tlv_stream.to_bytes()) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => {
+				assert_eq!(e, Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::MissingSignature));
+			},
+		}
+	}
+
+	#[test]
+	fn fails_parsing_static_invoice_without_message_paths() {
+		let expanded_key = ExpandedKey::new(&KeyMaterial([42; 32]));
+		let secp_ctx = Secp256k1::new();
+		let offer = offer(&expanded_key);
+
+		let invoice = StaticInvoiceBuilder::for_offer_using_derived_keys(
+			&offer, payment_paths(), vec![blinded_path()], now(), &expanded_key, &secp_ctx
+		).unwrap()
+			.build_and_sign(&secp_ctx).unwrap();
+
+		let mut tlv_stream = invoice.as_tlv_stream();
+		tlv_stream.1.message_paths = None;
+
+		match StaticInvoice::try_from(tlv_stream.to_bytes()) {
+			Ok(_) => panic!("expected error"),
+			Err(e) => {
+				assert_eq!(e, Bolt12ParseError::InvalidSemantics(Bolt12SemanticError::MissingPaths));
+			},
+		}
+	}
+}
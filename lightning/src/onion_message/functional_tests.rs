@@ -16,6 +16,7 @@ use crate::ln::msgs::{self, DecodeError, OnionMessageHandler, SocketAddress};
 use crate::sign::{EntropySource, NodeSigner, Recipient};
 use crate::util::ser::{FixedLengthReader, LengthReadable, Writeable, Writer};
 use crate::util::test_utils;
+use super::async_payments::{AsyncPaymentsMessage, AsyncPaymentsMessageHandler, HeldHtlcAvailable, ReleaseHeldHtlc};
 use super::messenger::{CustomOnionMessageHandler, Destination, MessageRouter, OnionMessagePath, OnionMessenger, PendingOnionMessage, SendError};
 use super::offers::{OffersMessage, OffersMessageHandler};
 use super::packet::{OnionMessageContents, Packet};
@@ -39,8 +40,10 @@ struct MessengerNode {
 		Arc<test_utils::TestLogger>,
 		Arc<TestMessageRouter>,
 		Arc<TestOffersMessageHandler>,
+		Arc<TestAsyncPaymentsMessageHandler>,
 		Arc<TestCustomMessageHandler>
 	>,
+	async_payments_message_handler: Arc<TestAsyncPaymentsMessageHandler>,
 	custom_message_handler: Arc<TestCustomMessageHandler>,
 }
 
@@ -76,6 +79,20 @@ impl OffersMessageHandler for TestOffersMessageHandler {
 	}
 }
 
+struct TestAsyncPaymentsMessageHandler {
+	released_secrets: Mutex<Vec<[u8; 32]>>,
+}
+
+impl AsyncPaymentsMessageHandler for TestAsyncPaymentsMessageHandler {
+	fn held_htlc_available(&self, message: HeldHtlcAvailable) -> Option<ReleaseHeldHtlc> {
+		Some(ReleaseHeldHtlc { payment_release_secret: message.payment_release_secret })
+	}
+
+	fn release_held_htlc(&self, message: ReleaseHeldHtlc) {
+		self.released_secrets.lock().unwrap().push(message.payment_release_secret);
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
 enum TestCustomMessage {
 	Request,
@@ -181,14 +198,19 @@ fn create_nodes_using_secrets(secrets: Vec<SecretKey>) -> Vec<MessengerNode> {
 
 		let message_router = Arc::new(TestMessageRouter {});
 		let offers_message_handler = Arc::new(TestOffersMessageHandler {});
+		let async_payments_message_handler = Arc::new(TestAsyncPaymentsMessageHandler {
+			released_secrets: Mutex::new(Vec::new()),
+		});
 		let custom_message_handler = Arc::new(TestCustomMessageHandler::new());
 		nodes.push(MessengerNode {
 			node_id: node_signer.get_node_id(Recipient::Node).unwrap(),
 			entropy_source: entropy_source.clone(),
 			messenger: OnionMessenger::new(
 				entropy_source, node_signer, logger.clone(), message_router,
-				offers_message_handler, custom_message_handler.clone()
+				offers_message_handler, async_payments_message_handler.clone(),
+				custom_message_handler.clone()
 			),
+			async_payments_message_handler,
 			custom_message_handler,
 		});
 	}
@@ -422,6 +444,32 @@ fn reply_path() {
 	pass_along_path(&nodes);
 }
 
+#[test]
+fn releases_held_htlc_over_reply_path() {
+	let mut nodes = create_nodes(3);
+	let secp_ctx = Secp256k1::new();
+
+	let blinded_path = BlindedPath::new_for_message(&[nodes[1].node_id, nodes[2].node_id], &*nodes[2].entropy_source, &secp_ctx).unwrap();
+	let path = OnionMessagePath {
+		intermediate_nodes: vec![],
+		destination: Destination::BlindedPath(blinded_path),
+		first_node_addresses: None,
+	};
+	let reply_path = BlindedPath::new_for_message(&[nodes[1].node_id, nodes[0].node_id], &*nodes[0].entropy_source, &secp_ctx).unwrap();
+	let message = AsyncPaymentsMessage::HeldHtlcAvailable(HeldHtlcAvailable {
+		payment_release_secret: [42; 32],
+	});
+
+	nodes[0].messenger.send_onion_message_using_path(path, message, Some(reply_path)).unwrap();
+	pass_along_path(&nodes);
+	assert!(nodes[0].async_payments_message_handler.released_secrets.lock().unwrap().is_empty());
+
+	// The recipient replies with a `ReleaseHeldHtlc` carrying the same secret.
+	nodes.reverse();
+	pass_along_path(&nodes);
+	assert_eq!(*nodes[2].async_payments_message_handler.released_secrets.lock().unwrap(), vec![[42; 32]]);
+}
+
 #[test]
 fn invalid_custom_message_type() {
 	let nodes = create_nodes(2);
@@ -28,6 +28,7 @@ use crate::ln::onion_utils;
 use crate::routing::gossip::{NetworkGraph, NodeId};
 use super::packet::OnionMessageContents;
 use super::packet::ParsedOnionMessageContents;
+use super::async_payments::{AsyncPaymentsMessage, AsyncPaymentsMessageHandler};
 use super::offers::OffersMessageHandler;
 use super::packet::{BIG_PACKET_HOP_DATA_LEN, ForwardControlTlvs, Packet, Payload, ReceiveControlTlvs, SMALL_PACKET_HOP_DATA_LEN};
 use crate::util::logger::Logger;
@@ -56,6 +57,7 @@ pub(super) const MAX_TIMER_TICKS: usize = 2;
 /// messages to peers or delegating to the appropriate handler for the message type. Currently, the
 /// available handlers are:
 /// * [`OffersMessageHandler`], for responding to [`InvoiceRequest`]s and paying [`Bolt12Invoice`]s
+/// * [`AsyncPaymentsMessageHandler`], for holding and releasing HTLCs paying [`StaticInvoice`]s
 /// * [`CustomOnionMessageHandler`], for handling user-defined message types
 ///
 /// # Sending Messages
@@ -115,11 +117,12 @@ pub(super) const MAX_TIMER_TICKS: usize = 2;
 /// # let message_router = Arc::new(FakeMessageRouter {});
 /// # let custom_message_handler = IgnoringMessageHandler {};
 /// # let offers_message_handler = IgnoringMessageHandler {};
+/// # let async_payments_message_handler = IgnoringMessageHandler {};
 /// // Create the onion messenger. This must use the same `keys_manager` as is passed to your
 /// // ChannelManager.
 /// let onion_messenger = OnionMessenger::new(
 ///     &keys_manager, &keys_manager, logger, message_router, &offers_message_handler,
-///     &custom_message_handler
+///     &async_payments_message_handler, &custom_message_handler
 /// );
 
 /// # #[derive(Debug)]
@@ -156,13 +159,15 @@ pub(super) const MAX_TIMER_TICKS: usize = 2;
 ///
 /// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
 /// [`Bolt12Invoice`]: crate::offers::invoice::Bolt12Invoice
-pub struct OnionMessenger<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, CMH: Deref>
+/// [`StaticInvoice`]: crate::offers::static_invoice::StaticInvoice
+pub struct OnionMessenger<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, APH: Deref, CMH: Deref>
 where
 	ES::Target: EntropySource,
 	NS::Target: NodeSigner,
 	L::Target: Logger,
 	MR::Target: MessageRouter,
 	OMH::Target: OffersMessageHandler,
+	APH::Target: AsyncPaymentsMessageHandler,
 	CMH::Target: CustomOnionMessageHandler,
 {
 	entropy_source: ES,
@@ -172,6 +177,7 @@ where
 	secp_ctx: Secp256k1<secp256k1::All>,
 	message_router: MR,
 	offers_handler: OMH,
+	async_payments_handler: APH,
 	custom_handler: CMH,
 }
 
@@ -684,21 +690,22 @@ where
 	}
 }
 
-impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, CMH: Deref>
-OnionMessenger<ES, NS, L, MR, OMH, CMH>
+impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, APH: Deref, CMH: Deref>
+OnionMessenger<ES, NS, L, MR, OMH, APH, CMH>
 where
 	ES::Target: EntropySource,
 	NS::Target: NodeSigner,
 	L::Target: Logger,
 	MR::Target: MessageRouter,
 	OMH::Target: OffersMessageHandler,
+	APH::Target: AsyncPaymentsMessageHandler,
 	CMH::Target: CustomOnionMessageHandler,
 {
 	/// Constructs a new `OnionMessenger` to send, forward, and delegate received onion messages to
 	/// their respective handlers.
 	pub fn new(
 		entropy_source: ES, node_signer: NS, logger: L, message_router: MR, offers_handler: OMH,
-		custom_handler: CMH
+		async_payments_handler: APH, custom_handler: CMH
 	) -> Self {
 		let mut secp_ctx = Secp256k1::new();
 		secp_ctx.seeded_randomize(&entropy_source.get_secure_random_bytes());
@@ -710,6 +717,7 @@ where
 			logger,
 			message_router,
 			offers_handler,
+			async_payments_handler,
 			custom_handler,
 		}
 	}
@@ -867,14 +875,15 @@ fn outbound_buffer_full(peer_node_id: &PublicKey, buffer: &HashMap<PublicKey, On
 	false
 }
 
-impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, CMH: Deref> EventsProvider
-for OnionMessenger<ES, NS, L, MR, OMH, CMH>
+impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, APH: Deref, CMH: Deref> EventsProvider
+for OnionMessenger<ES, NS, L, MR, OMH, APH, CMH>
 where
 	ES::Target: EntropySource,
 	NS::Target: NodeSigner,
 	L::Target: Logger,
 	MR::Target: MessageRouter,
 	OMH::Target: OffersMessageHandler,
+	APH::Target: AsyncPaymentsMessageHandler,
 	CMH::Target: CustomOnionMessageHandler,
 {
 	fn process_pending_events<H: Deref>(&self, handler: H) where H::Target: EventHandler {
@@ -888,14 +897,15 @@ where
 	}
 }
 
-impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, CMH: Deref> OnionMessageHandler
-for OnionMessenger<ES, NS, L, MR, OMH, CMH>
+impl<ES: Deref, NS: Deref, L: Deref, MR: Deref, OMH: Deref, APH: Deref, CMH: Deref> OnionMessageHandler
+for OnionMessenger<ES, NS, L, MR, OMH, APH, CMH>
 where
 	ES::Target: EntropySource,
 	NS::Target: NodeSigner,
 	L::Target: Logger,
 	MR::Target: MessageRouter,
 	OMH::Target: OffersMessageHandler,
+	APH::Target: AsyncPaymentsMessageHandler,
 	CMH::Target: CustomOnionMessageHandler,
 {
 	fn handle_onion_message(&self, _peer_node_id: &PublicKey, msg: &OnionMessage) {
@@ -918,6 +928,19 @@ where
 							)
 						);
 					},
+					ParsedOnionMessageContents::AsyncPayments(AsyncPaymentsMessage::HeldHtlcAvailable(msg)) => {
+						let response = self.async_payments_handler.held_htlc_available(msg)
+							.map(|msg| AsyncPaymentsMessage::ReleaseHeldHtlc(msg));
+						self.handle_onion_message_response(
+							response, reply_path, format_args!(
+								"when responding to AsyncPayments onion message with path_id {:02x?}",
+								path_id
+							)
+						);
+					},
+					ParsedOnionMessageContents::AsyncPayments(AsyncPaymentsMessage::ReleaseHeldHtlc(msg)) => {
+						self.async_payments_handler.release_held_htlc(msg);
+					},
 					ParsedOnionMessageContents::Custom(msg) => {
 						let response = self.custom_handler.handle_custom_message(msg);
 						self.handle_onion_message_response(
@@ -1028,6 +1051,17 @@ where
 			);
 		}
 
+		// Enqueue any initiating `AsyncPaymentsMessage`s to send.
+		for message in self.async_payments_handler.release_pending_messages() {
+			#[cfg(not(c_bindings))]
+			let PendingOnionMessage { contents, destination, reply_path } = message;
+			#[cfg(c_bindings)]
+			let (contents, destination, reply_path) = message;
+			let _ = self.find_path_and_enqueue_onion_message(
+				contents, destination, reply_path, format_args!("when sending AsyncPaymentsMessage")
+			);
+		}
+
 		// Enqueue any initiating `CustomMessage`s to send.
 		for message in self.custom_handler.release_pending_custom_messages() {
 			#[cfg(not(c_bindings))]
@@ -1061,6 +1095,7 @@ pub type SimpleArcOnionMessenger<M, T, F, L> = OnionMessenger<
 	Arc<L>,
 	Arc<DefaultMessageRouter<Arc<NetworkGraph<Arc<L>>>, Arc<L>>>,
 	Arc<SimpleArcChannelManager<M, T, F, L>>,
+	Arc<SimpleArcChannelManager<M, T, F, L>>,
 	IgnoringMessageHandler
 >;
 
@@ -1080,6 +1115,7 @@ pub type SimpleRefOnionMessenger<
 	&'b L,
 	&'i DefaultMessageRouter<&'g NetworkGraph<&'b L>, &'b L>,
 	&'j SimpleRefChannelManager<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, M, T, F, L>,
+	&'j SimpleRefChannelManager<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, M, T, F, L>,
 	IgnoringMessageHandler
 >;
 
@@ -25,7 +25,7 @@ use crate::ln::ChannelId;
 use crate::ln::features::{ChannelFeatures, NodeFeatures, InitFeatures};
 use crate::ln::msgs::{DecodeError, ErrorAction, Init, LightningError, RoutingMessageHandler, SocketAddress, MAX_VALUE_MSAT};
 use crate::ln::msgs::{ChannelAnnouncement, ChannelUpdate, NodeAnnouncement, GossipTimestampFilter};
-use crate::ln::msgs::{QueryChannelRange, ReplyChannelRange, QueryShortChannelIds, ReplyShortChannelIdsEnd};
+use crate::ln::msgs::{QueryChannelRange, ReplyChannelRange, QueryShortChannelIds, ReplyShortChannelIdsEnd, ShortChannelIdsReply};
 use crate::ln::msgs;
 use crate::routing::utxo::{self, UtxoLookup, UtxoResolver};
 use crate::util::ser::{Readable, ReadableArgs, Writeable, Writer, MaybeReadable};
@@ -195,6 +195,133 @@ pub struct NetworkGraph<L: Deref> where L::Target: Logger {
 	removed_nodes: Mutex<HashMap<NodeId, Option<u64>>>,
 	/// Announcement messages which are awaiting an on-chain lookup to be processed.
 	pub(super) pending_checks: utxo::PendingChecks,
+	/// The channels and nodes which changed since the graph was last persisted.
+	///
+	/// Lock order: this is always taken last, after any of the above locks.
+	pending_changes: Mutex<PendingChanges>,
+}
+
+/// Tracks the changes to a [`NetworkGraph`] since it was last persisted.
+struct PendingChanges {
+	channels: HashSet<u64>,
+	nodes: HashSet<NodeId>,
+	last_rapid_gossip_sync_timestamp: Option<u32>,
+	/// The sequence number assigned to the next set of changes which are taken.
+	next_sequence: u64,
+	/// The `next_sequence` at the time the graph was last persisted in full, if ever.
+	full_persist_sequence: Option<u64>,
+}
+
+impl PendingChanges {
+	fn new(next_sequence: u64, full_persist_sequence: Option<u64>) -> Self {
+		Self { channels: HashSet::new(), nodes: HashSet::new(), last_rapid_gossip_sync_timestamp: None,
+			next_sequence, full_persist_sequence }
+	}
+
+	fn mark_channel(&mut self, short_channel_id: u64, channel: &ChannelInfo) {
+		self.channels.insert(short_channel_id);
+		self.nodes.insert(channel.node_one);
+		self.nodes.insert(channel.node_two);
+	}
+}
+
+/// The changes to a [`NetworkGraph`] since it was last persisted, as returned by
+/// [`NetworkGraph::take_pending_changes`].
+///
+/// These may be persisted instead of re-serializing the full graph and later be re-applied via
+/// [`NetworkGraph::apply_changes`]. Note that this is done automatically by the [`KVStore`]
+/// implementation of [`Persister`] and [`read_network_graph`].
+///
+/// [`KVStore`]: crate::util::persist::KVStore
+/// [`Persister`]: crate::util::persist::Persister
+/// [`read_network_graph`]: crate::util::persist::read_network_graph
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkGraphChanges {
+	sequence: u64,
+	deltas_since_full_persist: Option<u64>,
+	channels: Vec<(u64, Option<ChannelInfo>)>,
+	nodes: Vec<(NodeId, Option<NodeInfo>)>,
+	last_rapid_gossip_sync_timestamp: Option<u32>,
+}
+
+impl NetworkGraphChanges {
+	/// The sequence number of this set of changes. Changes need to be applied in order of their
+	/// sequence numbers.
+	pub fn sequence(&self) -> u64 {
+		self.sequence
+	}
+
+	/// The number of sets of changes taken since the graph was last persisted in full, including
+	/// this one, or `None` if it never was.
+	///
+	/// Persisters should regularly persist the full graph to bound the number of changes which
+	/// need to be applied when reading the graph.
+	pub fn deltas_since_full_persist(&self) -> Option<u64> {
+		self.deltas_since_full_persist
+	}
+
+	/// Returns whether nothing changed since the graph was last persisted.
+	pub fn is_empty(&self) -> bool {
+		self.channels.is_empty() && self.nodes.is_empty() && self.last_rapid_gossip_sync_timestamp.is_none()
+	}
+}
+
+impl Writeable for NetworkGraphChanges {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), io::Error> {
+		write_ver_prefix!(writer, SERIALIZATION_VERSION, MIN_SERIALIZATION_VERSION);
+
+		self.sequence.write(writer)?;
+		(self.channels.len() as u64).write(writer)?;
+		for (chan_id, chan_info) in self.channels.iter() {
+			chan_id.write(writer)?;
+			chan_info.write(writer)?;
+		}
+		(self.nodes.len() as u64).write(writer)?;
+		for (node_id, node_info) in self.nodes.iter() {
+			node_id.write(writer)?;
+			node_info.write(writer)?;
+		}
+
+		write_tlv_fields!(writer, {
+			(1, self.last_rapid_gossip_sync_timestamp, option),
+		});
+		Ok(())
+	}
+}
+
+impl Readable for NetworkGraphChanges {
+	fn read<R: io::Read>(reader: &mut R) -> Result<Self, DecodeError> {
+		let _ver = read_ver_prefix!(reader, SERIALIZATION_VERSION);
+
+		let sequence: u64 = Readable::read(reader)?;
+		let channels_count: u64 = Readable::read(reader)?;
+		let mut channels = Vec::with_capacity(cmp::min(channels_count as usize, 22500));
+		for _ in 0..channels_count {
+			let chan_id: u64 = Readable::read(reader)?;
+			let chan_info: Option<ChannelInfo> = Readable::read(reader)?;
+			channels.push((chan_id, chan_info));
+		}
+		let nodes_count: u64 = Readable::read(reader)?;
+		let mut nodes = Vec::with_capacity(cmp::min(nodes_count as usize, 103500));
+		for _ in 0..nodes_count {
+			let node_id: NodeId = Readable::read(reader)?;
+			let node_info: Option<NodeInfo> = Readable::read(reader)?;
+			nodes.push((node_id, node_info));
+		}
+
+		let mut last_rapid_gossip_sync_timestamp: Option<u32> = None;
+		read_tlv_fields!(reader, {
+			(1, last_rapid_gossip_sync_timestamp, option),
+		});
+
+		Ok(NetworkGraphChanges {
+			sequence,
+			deltas_since_full_persist: None,
+			channels,
+			nodes,
+			last_rapid_gossip_sync_timestamp,
+		})
+	}
 }
 
 /// A read-only view of [`NetworkGraph`].
@@ -262,9 +389,27 @@ where U::Target: UtxoLookup, L::Target: Logger
 	#[cfg(feature = "std")]
 	full_syncs_requested: AtomicUsize,
 	pending_events: Mutex<Vec<MessageSendEvent>>,
+	pending_short_channel_ids_queries: Mutex<HashMap<PublicKey, PendingShortChannelIdsQuery>>,
 	logger: L,
 }
 
+/// The maximum number of peers whose `query_short_channel_ids` we reply to at once. Queries from
+/// further peers are answered with a `reply_short_channel_ids_end` indicating we don't provide any
+/// information until one of the pending replies completes or its peer disconnects.
+const MAX_PENDING_SHORT_CHANNEL_IDS_QUERIES: usize = 128;
+
+/// A `query_short_channel_ids` from a peer which we're still replying to. Replies are generated
+/// lazily as the peer's outbound buffer drains to avoid flooding it on large queries.
+struct PendingShortChannelIdsQuery {
+	chain_hash: ChainHash,
+	full_information: bool,
+	short_channel_ids: VecDeque<u64>,
+	/// Nodes of already-replied channels whose `node_announcement` we still have to send.
+	pending_node_announcements: VecDeque<NodeId>,
+	/// Nodes whose `node_announcement` we've already queued, to avoid sending duplicates.
+	announced_nodes: HashSet<NodeId>,
+}
+
 impl<G: Deref<Target=NetworkGraph<L>>, U: Deref, L: Deref> P2PGossipSync<G, U, L>
 where U::Target: UtxoLookup, L::Target: Logger
 {
@@ -279,6 +424,7 @@ where U::Target: UtxoLookup, L::Target: Logger
 			full_syncs_requested: AtomicUsize::new(0),
 			utxo_lookup: RwLock::new(utxo_lookup),
 			pending_events: Mutex::new(vec![]),
+			pending_short_channel_ids_queries: Mutex::new(HashMap::new()),
 			logger,
 		}
 	}
@@ -519,6 +665,9 @@ where U::Target: UtxoLookup, L::Target: Logger
 	/// [`query_scid`]: msgs::QueryShortChannelIds
 	/// [`reply_scids_end`]: msgs::ReplyShortChannelIdsEnd
 	fn peer_connected(&self, their_node_id: &PublicKey, init_msg: &Init, _inbound: bool) -> Result<(), ()> {
+		// Any query left over from a previous connection will never be replied to.
+		self.pending_short_channel_ids_queries.lock().unwrap().remove(their_node_id);
+
 		// We will only perform a sync with peers that support gossip_queries.
 		if !init_msg.features.supports_gossip_queries() {
 			// Don't disconnect peers for not supporting gossip queries. We may wish to have
@@ -600,6 +749,10 @@ where U::Target: UtxoLookup, L::Target: Logger
 		Ok(())
 	}
 
+	fn peer_disconnected(&self, their_node_id: &PublicKey) {
+		self.pending_short_channel_ids_queries.lock().unwrap().remove(their_node_id);
+	}
+
 	fn handle_reply_channel_range(&self, _their_node_id: &PublicKey, _msg: ReplyChannelRange) -> Result<(), LightningError> {
 		// We don't make queries, so should never receive replies. If, in the future, the set
 		// reconciliation extensions to gossip queries become broadly supported, we should revert
@@ -718,12 +871,87 @@ where U::Target: UtxoLookup, L::Target: Logger
 		Ok(())
 	}
 
-	fn handle_query_short_channel_ids(&self, _their_node_id: &PublicKey, _msg: QueryShortChannelIds) -> Result<(), LightningError> {
-		// TODO
-		Err(LightningError {
-			err: String::from("Not implemented"),
-			action: ErrorAction::IgnoreError,
-		})
+	/// Processes a query from a peer for the gossip messages of specific channels. Rather than
+	/// enqueuing the full reply at once, the announcements and updates for each channel are handed
+	/// out via [`Self::get_next_short_channel_ids_reply`] as the peer's outbound buffer drains,
+	/// followed by a [`ReplyShortChannelIdsEnd`].
+	fn handle_query_short_channel_ids(&self, their_node_id: &PublicKey, msg: QueryShortChannelIds) -> Result<(), LightningError> {
+		log_debug!(self.logger, "Handling query_short_channel_ids peer={}, num_scids={}", log_pubkey!(their_node_id), msg.short_channel_ids.len());
+
+		let mut pending_queries = self.pending_short_channel_ids_queries.lock().unwrap();
+		if pending_queries.contains_key(their_node_id) {
+			return Err(LightningError {
+				err: String::from("query_short_channel_ids received before we finished replying to the previous one"),
+				action: ErrorAction::SendWarningMessage {
+					msg: msgs::WarningMessage {
+						channel_id: ChannelId::new_zero(),
+						data: String::from("Received query_short_channel_ids before reply_short_channel_ids_end"),
+					},
+					log_level: Level::Debug,
+				},
+			});
+		}
+
+		// Per spec, we must still reply to queries for chains we don't know about, though we
+		// indicate we don't have any information on them. Similarly, if we're already replying to
+		// too many other peers' queries, we reply immediately, indicating we aren't providing any
+		// information, rather than holding on to the requested SCIDs.
+		let too_many_pending = pending_queries.len() >= MAX_PENDING_SHORT_CHANNEL_IDS_QUERIES;
+		if too_many_pending {
+			log_debug!(self.logger, "Not replying to query_short_channel_ids from peer {} as too many queries are pending", log_pubkey!(their_node_id));
+		}
+		let full_information = msg.chain_hash == self.network_graph.chain_hash && !too_many_pending;
+		let short_channel_ids = if full_information { msg.short_channel_ids.into() } else { VecDeque::new() };
+		pending_queries.insert(*their_node_id, PendingShortChannelIdsQuery {
+			chain_hash: msg.chain_hash,
+			full_information,
+			short_channel_ids,
+			pending_node_announcements: VecDeque::new(),
+			announced_nodes: HashSet::new(),
+		});
+		Ok(())
+	}
+
+	fn get_next_short_channel_ids_reply(&self, their_node_id: &PublicKey) -> Option<ShortChannelIdsReply> {
+		let mut pending_queries = self.pending_short_channel_ids_queries.lock().unwrap();
+		let query = pending_queries.get_mut(their_node_id)?;
+
+		while let Some(node_id) = query.pending_node_announcements.pop_front() {
+			let nodes = self.network_graph.nodes.read().unwrap();
+			let announcement = nodes.get(&node_id)
+				.and_then(|node| node.announcement_info.as_ref())
+				.and_then(|info| info.announcement_message.clone());
+			if let Some(msg) = announcement {
+				return Some(ShortChannelIdsReply::NodeAnnouncement(msg));
+			}
+		}
+
+		while let Some(scid) = query.short_channel_ids.pop_front() {
+			let channels = self.network_graph.channels.read().unwrap();
+			let chan = match channels.get(&scid) {
+				Some(chan) => chan,
+				None => continue,
+			};
+			// Never reply with unannounced channels, even if the peer somehow knows the SCID.
+			let chan_announcement = match chan.announcement_message.as_ref() {
+				Some(msg) => msg.clone(),
+				None => continue,
+			};
+			for node_id in [chan.node_one, chan.node_two] {
+				if query.announced_nodes.insert(node_id) {
+					query.pending_node_announcements.push_back(node_id);
+				}
+			}
+			let one_to_two_update = chan.one_to_two.as_ref().and_then(|info| info.last_update_message.clone());
+			let two_to_one_update = chan.two_to_one.as_ref().and_then(|info| info.last_update_message.clone());
+			return Some(ShortChannelIdsReply::ChannelAnnouncement(chan_announcement, one_to_two_update, two_to_one_update));
+		}
+
+		let query = pending_queries.remove(their_node_id).unwrap();
+		Some(ShortChannelIdsReply::End(ReplyShortChannelIdsEnd {
+			chain_hash: query.chain_hash,
+			full_information: query.full_information,
+		}))
 	}
 
 	fn provided_node_features(&self) -> NodeFeatures {
@@ -1312,8 +1540,10 @@ impl<L: Deref> Writeable for NetworkGraph<L> where L::Target: Logger {
 		}
 
 		let last_rapid_gossip_sync_timestamp = self.get_last_rapid_gossip_sync_timestamp();
+		let next_change_sequence = self.pending_changes.lock().unwrap().next_sequence;
 		write_tlv_fields!(writer, {
 			(1, last_rapid_gossip_sync_timestamp, option),
+			(3, next_change_sequence, required),
 		});
 		Ok(())
 	}
@@ -1342,8 +1572,10 @@ impl<L: Deref> ReadableArgs<L> for NetworkGraph<L> where L::Target: Logger {
 		}
 
 		let mut last_rapid_gossip_sync_timestamp: Option<u32> = None;
+		let mut next_change_sequence: Option<u64> = None;
 		read_tlv_fields!(reader, {
 			(1, last_rapid_gossip_sync_timestamp, option),
+			(3, next_change_sequence, option),
 		});
 
 		Ok(NetworkGraph {
@@ -1356,6 +1588,8 @@ impl<L: Deref> ReadableArgs<L> for NetworkGraph<L> where L::Target: Logger {
 			removed_nodes: Mutex::new(HashMap::new()),
 			removed_channels: Mutex::new(HashMap::new()),
 			pending_checks: utxo::PendingChecks::new(),
+			pending_changes: Mutex::new(PendingChanges::new(
+				next_change_sequence.unwrap_or(0), next_change_sequence)),
 		})
 	}
 }
@@ -1401,6 +1635,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 			removed_channels: Mutex::new(HashMap::new()),
 			removed_nodes: Mutex::new(HashMap::new()),
 			pending_checks: utxo::PendingChecks::new(),
+			pending_changes: Mutex::new(PendingChanges::new(0, None)),
 		}
 	}
 
@@ -1424,6 +1659,114 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 	/// This should be done automatically by the rapid sync process after every sync completion.
 	pub fn set_last_rapid_gossip_sync_timestamp(&self, last_rapid_gossip_sync_timestamp: u32) {
 		self.last_rapid_gossip_sync_timestamp.lock().unwrap().replace(last_rapid_gossip_sync_timestamp);
+		self.pending_changes.lock().unwrap().last_rapid_gossip_sync_timestamp = Some(last_rapid_gossip_sync_timestamp);
+	}
+
+	/// Takes the changes to the graph since they were last taken, allowing them to be persisted
+	/// instead of re-serializing the full graph.
+	///
+	/// If persisting the changes fails, they must be handed back via
+	/// [`Self::restore_pending_changes`] to be included in the next set of changes. If the full graph
+	/// is persisted instead, [`Self::full_persist_completed`] should be called once done.
+	///
+	/// Note that users of the [`KVStore`] implementation of [`Persister`] do not need to call this.
+	///
+	/// [`KVStore`]: crate::util::persist::KVStore
+	/// [`Persister`]: crate::util::persist::Persister
+	pub fn take_pending_changes(&self) -> NetworkGraphChanges {
+		let (channel_ids, node_ids, last_rapid_gossip_sync_timestamp, sequence, deltas_since_full_persist) = {
+			let mut pending_changes = self.pending_changes.lock().unwrap();
+			let channel_ids = core::mem::take(&mut pending_changes.channels);
+			let node_ids = core::mem::take(&mut pending_changes.nodes);
+			let last_rapid_gossip_sync_timestamp = pending_changes.last_rapid_gossip_sync_timestamp.take();
+			let sequence = pending_changes.next_sequence;
+			if !channel_ids.is_empty() || !node_ids.is_empty() || last_rapid_gossip_sync_timestamp.is_some() {
+				pending_changes.next_sequence += 1;
+			}
+			let deltas_since_full_persist = pending_changes.full_persist_sequence
+				.map(|full_persist_sequence| pending_changes.next_sequence - full_persist_sequence);
+			(channel_ids, node_ids, last_rapid_gossip_sync_timestamp, sequence, deltas_since_full_persist)
+		};
+
+		// Any changes made from here on are marked pending again, and thus included in the next
+		// set of changes, even if we already pick them up below.
+		let channels = self.channels.read().unwrap();
+		let nodes = self.nodes.read().unwrap();
+		let mut changed_channels: Vec<_> = channel_ids.into_iter()
+			.map(|chan_id| (chan_id, channels.get(&chan_id).cloned()))
+			.collect();
+		changed_channels.sort_unstable_by_key(|(chan_id, _)| *chan_id);
+		let mut changed_nodes: Vec<_> = node_ids.into_iter()
+			.map(|node_id| (node_id, nodes.get(&node_id).cloned()))
+			.collect();
+		changed_nodes.sort_unstable_by_key(|(node_id, _)| *node_id);
+
+		NetworkGraphChanges {
+			sequence,
+			deltas_since_full_persist,
+			channels: changed_channels,
+			nodes: changed_nodes,
+			last_rapid_gossip_sync_timestamp,
+		}
+	}
+
+	/// Hands back changes previously returned by [`Self::take_pending_changes`] which could not be
+	/// persisted, marking them as pending again.
+	pub fn restore_pending_changes(&self, changes: NetworkGraphChanges) {
+		let mut pending_changes = self.pending_changes.lock().unwrap();
+		pending_changes.channels.extend(changes.channels.into_iter().map(|(chan_id, _)| chan_id));
+		pending_changes.nodes.extend(changes.nodes.into_iter().map(|(node_id, _)| node_id));
+		if pending_changes.last_rapid_gossip_sync_timestamp.is_none() {
+			pending_changes.last_rapid_gossip_sync_timestamp = changes.last_rapid_gossip_sync_timestamp;
+		}
+	}
+
+	/// Notes that the full graph was persisted after taking the given `changes` via
+	/// [`Self::take_pending_changes`], resetting [`NetworkGraphChanges::deltas_since_full_persist`].
+	pub fn full_persist_completed(&self, changes: &NetworkGraphChanges) {
+		let mut pending_changes = self.pending_changes.lock().unwrap();
+		let next_sequence = if changes.is_empty() { changes.sequence } else { changes.sequence + 1 };
+		pending_changes.full_persist_sequence = Some(next_sequence);
+	}
+
+	/// Ensures the next [`NetworkGraphChanges::deltas_since_full_persist`] is `None`, i.e., that the
+	/// graph is persisted in full next.
+	pub(crate) fn require_full_persist(&self) {
+		self.pending_changes.lock().unwrap().full_persist_sequence = None;
+	}
+
+	/// Applies changes previously returned by [`Self::take_pending_changes`] to a graph read from
+	/// an earlier full serialization.
+	///
+	/// Changes which predate the graph, i.e., whose [`NetworkGraphChanges::sequence`] is lower than
+	/// that of changes already applied or those taken before the graph was serialized, are ignored.
+	pub fn apply_changes(&self, changes: NetworkGraphChanges) {
+		let mut channels = self.channels.write().unwrap();
+		let mut nodes = self.nodes.write().unwrap();
+		let mut pending_changes = self.pending_changes.lock().unwrap();
+		if changes.sequence < pending_changes.next_sequence {
+			log_debug!(self.logger, "Ignoring stale network graph changes with sequence {}", changes.sequence);
+			return;
+		}
+
+		log_trace!(self.logger, "Applying network graph changes with sequence {} to {} channels and {} nodes",
+			changes.sequence, changes.channels.len(), changes.nodes.len());
+		for (chan_id, chan_info) in changes.channels {
+			match chan_info {
+				Some(chan_info) => { channels.insert(chan_id, chan_info); },
+				None => { channels.remove(&chan_id); },
+			}
+		}
+		for (node_id, node_info) in changes.nodes {
+			match node_info {
+				Some(node_info) => { nodes.insert(node_id, node_info); },
+				None => { nodes.remove(&node_id); },
+			}
+		}
+		if let Some(last_rapid_gossip_sync_timestamp) = changes.last_rapid_gossip_sync_timestamp {
+			self.last_rapid_gossip_sync_timestamp.lock().unwrap().replace(last_rapid_gossip_sync_timestamp);
+		}
+		pending_changes.next_sequence = changes.sequence + 1;
 	}
 
 	/// Clears the `NodeAnnouncementInfo` field for all nodes in the `NetworkGraph` for testing
@@ -1485,6 +1828,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 					alias: msg.alias,
 					announcement_message: if should_relay { full_msg.cloned() } else { None },
 				});
+				self.pending_changes.lock().unwrap().nodes.insert(msg.node_id);
 
 				Ok(())
 			}
@@ -1588,6 +1932,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 					//    get reorg'd out.
 					// c) it's unclear how to do so without exposing ourselves to massive DoS risk.
 					Self::remove_channel_in_nodes(&mut nodes, &entry.get(), short_channel_id);
+					self.pending_changes.lock().unwrap().mark_channel(short_channel_id, entry.get());
 					*entry.get_mut() = channel_info;
 				} else {
 					return Err(LightningError{err: "Already have knowledge of channel".to_owned(), action: ErrorAction::IgnoreDuplicateGossip});
@@ -1597,6 +1942,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 				entry.insert(channel_info);
 			}
 		};
+		self.pending_changes.lock().unwrap().mark_channel(short_channel_id, channels.get(&short_channel_id).unwrap());
 
 		for current_node_id in [node_id_a, node_id_b].iter() {
 			match nodes.entry(current_node_id.clone()) {
@@ -1726,6 +2072,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 			let mut nodes = self.nodes.write().unwrap();
 			self.removed_channels.lock().unwrap().insert(short_channel_id, current_time_unix);
 			Self::remove_channel_in_nodes(&mut nodes, &chan, short_channel_id);
+			self.pending_changes.lock().unwrap().mark_channel(short_channel_id, &chan);
 		}
 	}
 
@@ -1742,10 +2089,13 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 		let mut nodes = self.nodes.write().unwrap();
 		let mut removed_channels = self.removed_channels.lock().unwrap();
 		let mut removed_nodes = self.removed_nodes.lock().unwrap();
+		let mut pending_changes = self.pending_changes.lock().unwrap();
 
 		if let Some(node) = nodes.remove(&node_id) {
+			pending_changes.nodes.insert(node_id);
 			for scid in node.channels.iter() {
 				if let Some(chan_info) = channels.remove(scid) {
+					pending_changes.mark_channel(*scid, &chan_info);
 					let other_node_id = if node_id == chan_info.node_one { chan_info.node_two } else { chan_info.node_one };
 					if let IndexedMapEntry::Occupied(mut other_node_entry) = nodes.entry(other_node_id) {
 						other_node_entry.get_mut().channels.retain(|chan_id| {
@@ -1811,11 +2161,13 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 				log_gossip!(self.logger, "Removing directional update one_to_two (0) for channel {} due to its timestamp {} being below {}",
 					scid, info.one_to_two.as_ref().unwrap().last_update, min_time_unix);
 				info.one_to_two = None;
+				self.pending_changes.lock().unwrap().channels.insert(*scid);
 			}
 			if info.two_to_one.is_some() && info.two_to_one.as_ref().unwrap().last_update < min_time_unix {
 				log_gossip!(self.logger, "Removing directional update two_to_one (1) for channel {} due to its timestamp {} being below {}",
 					scid, info.two_to_one.as_ref().unwrap().last_update, min_time_unix);
 				info.two_to_one = None;
+				self.pending_changes.lock().unwrap().channels.insert(*scid);
 			}
 			if info.one_to_two.is_none() || info.two_to_one.is_none() {
 				// We check the announcement_received_time here to ensure we don't drop
@@ -1835,6 +2187,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 				let info = channels.remove(&scid).expect("We just accessed this scid, it should be present");
 				Self::remove_channel_in_nodes(&mut nodes, &info, scid);
 				self.removed_channels.lock().unwrap().insert(scid, Some(current_time_unix));
+				self.pending_changes.lock().unwrap().mark_channel(scid, &info);
 			}
 		}
 
@@ -1993,6 +2346,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 					}
 					if !only_verify {
 						channel.two_to_one = get_new_channel_info!();
+						self.pending_changes.lock().unwrap().channels.insert(msg.short_channel_id);
 					}
 				} else {
 					check_update_latest!(channel.one_to_two);
@@ -2004,6 +2358,7 @@ impl<L: Deref> NetworkGraph<L> where L::Target: Logger {
 					}
 					if !only_verify {
 						channel.one_to_two = get_new_channel_info!();
+						self.pending_changes.lock().unwrap().channels.insert(msg.short_channel_id);
 					}
 				}
 			}
@@ -2090,14 +2445,15 @@ pub(crate) mod tests {
 	use crate::routing::utxo::{UtxoLookupError, UtxoResult};
 	use crate::ln::msgs::{RoutingMessageHandler, UnsignedNodeAnnouncement, NodeAnnouncement,
 		UnsignedChannelAnnouncement, ChannelAnnouncement, UnsignedChannelUpdate, ChannelUpdate,
-		ReplyChannelRange, QueryChannelRange, QueryShortChannelIds, MAX_VALUE_MSAT};
+		ReplyChannelRange, QueryChannelRange, QueryShortChannelIds, ReplyShortChannelIdsEnd, ShortChannelIdsReply,
+		MAX_VALUE_MSAT};
 	use crate::util::config::UserConfig;
 	use crate::util::test_utils;
 	use crate::util::ser::{ReadableArgs, Readable, Writeable};
 	use crate::util::scid_utils::scid_from_parts;
 
 	use crate::routing::gossip::REMOVED_ENTRIES_TRACKING_AGE_LIMIT_SECS;
-	use super::STALE_CHANNEL_UPDATE_AGE_LIMIT_SECS;
+	use super::{STALE_CHANNEL_UPDATE_AGE_LIMIT_SECS, MAX_PENDING_SHORT_CHANNEL_IDS_QUERIES};
 
 	use bitcoin::hashes::sha256d::Hash as Sha256dHash;
 	use bitcoin::hashes::Hash;
@@ -3285,18 +3641,99 @@ pub(crate) mod tests {
 
 	#[test]
 	fn handling_query_short_channel_ids() {
+		use crate::ln::features::InitFeatures;
+		use crate::ln::msgs::Init;
+
 		let network_graph = create_network_graph();
 		let (secp_ctx, gossip_sync) = create_gossip_sync(&network_graph);
-		let node_privkey = &SecretKey::from_slice(&[41; 32]).unwrap();
-		let node_id = PublicKey::from_secret_key(&secp_ctx, node_privkey);
+		let node_1_privkey = &SecretKey::from_slice(&[42; 32]).unwrap();
+		let node_2_privkey = &SecretKey::from_slice(&[41; 32]).unwrap();
+		let node_id = PublicKey::from_secret_key(&secp_ctx, node_2_privkey);
 
 		let chain_hash = ChainHash::using_genesis_block(Network::Testnet);
+		let announced_scid = scid_from_parts(1000, 0, 0).unwrap();
+		let second_scid = scid_from_parts(1001, 0, 0).unwrap();
+		let unknown_scid = scid_from_parts(1002, 0, 0).unwrap();
+
+		let mut announcements = Vec::new();
+		for scid in [announced_scid, second_scid] {
+			let announcement = get_signed_channel_announcement(|unsigned_announcement| {
+				unsigned_announcement.short_channel_id = scid;
+			}, node_1_privkey, node_2_privkey, &secp_ctx);
+			gossip_sync.handle_channel_announcement(&announcement).unwrap();
+			announcements.push(announcement);
+		}
+		let update = get_signed_channel_update(|unsigned_channel_update| {
+			unsigned_channel_update.short_channel_id = announced_scid;
+		}, node_1_privkey, &secp_ctx);
+		gossip_sync.handle_channel_update(&update).unwrap();
+		let node_announcement = get_signed_node_announcement(|_| {}, node_1_privkey, &secp_ctx);
+		gossip_sync.handle_node_announcement(&node_announcement).unwrap();
 
-		let result = gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
+		// Nothing to reply with until we're queried.
+		assert!(gossip_sync.get_next_short_channel_ids_reply(&node_id).is_none());
+
+		gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
 			chain_hash,
-			short_channel_ids: vec![0x0003e8_000000_0000],
-		});
-		assert!(result.is_err());
+			short_channel_ids: vec![unknown_scid, announced_scid, second_scid],
+		}).unwrap();
+
+		// A second query before we've finished replying to the first is rejected.
+		assert!(gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
+			chain_hash,
+			short_channel_ids: vec![announced_scid],
+		}).is_err());
+
+		// Unknown channels are skipped, and each node announcement is sent only once, right after
+		// the first channel which references it.
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::ChannelAnnouncement(announcements[0].clone(), Some(update), None)));
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::NodeAnnouncement(node_announcement)));
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::ChannelAnnouncement(announcements[1].clone(), None, None)));
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::End(ReplyShortChannelIdsEnd { chain_hash, full_information: true })));
+		assert!(gossip_sync.get_next_short_channel_ids_reply(&node_id).is_none());
+
+		// Queries for another chain are replied to with an empty reply.
+		let other_chain_hash = ChainHash::using_genesis_block(Network::Bitcoin);
+		gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
+			chain_hash: other_chain_hash,
+			short_channel_ids: vec![announced_scid],
+		}).unwrap();
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::End(ReplyShortChannelIdsEnd { chain_hash: other_chain_hash, full_information: false })));
+
+		// A pending query is dropped if the peer reconnects.
+		gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
+			chain_hash,
+			short_channel_ids: vec![announced_scid],
+		}).unwrap();
+		let init_msg = Init { features: InitFeatures::empty(), networks: None, remote_network_address: None };
+		gossip_sync.peer_connected(&node_id, &init_msg, true).unwrap();
+		assert!(gossip_sync.get_next_short_channel_ids_reply(&node_id).is_none());
+
+		// ...or disconnects.
+		gossip_sync.handle_query_short_channel_ids(&node_id, QueryShortChannelIds {
+			chain_hash,
+			short_channel_ids: vec![announced_scid],
+		}).unwrap();
+		gossip_sync.peer_disconnected(&node_id);
+		assert!(gossip_sync.get_next_short_channel_ids_reply(&node_id).is_none());
+
+		// We only reply to a bounded number of peers' queries at once.
+		let query = QueryShortChannelIds { chain_hash, short_channel_ids: vec![announced_scid] };
+		for i in 0..MAX_PENDING_SHORT_CHANNEL_IDS_QUERIES {
+			let privkey = SecretKey::from_slice(&[i as u8 + 100; 32]).unwrap();
+			let peer_id = PublicKey::from_secret_key(&secp_ctx, &privkey);
+			gossip_sync.handle_query_short_channel_ids(&peer_id, query.clone()).unwrap();
+		}
+		// Beyond that, we still end the query, though without providing any information.
+		gossip_sync.handle_query_short_channel_ids(&node_id, query.clone()).unwrap();
+		assert_eq!(gossip_sync.get_next_short_channel_ids_reply(&node_id),
+			Some(ShortChannelIdsReply::End(ReplyShortChannelIdsEnd { chain_hash, full_information: false })));
+		assert!(gossip_sync.get_next_short_channel_ids_reply(&node_id).is_none());
 	}
 
 	#[test]
@@ -20,12 +20,14 @@ use crate::ln::channelmanager::{ChannelDetails, PaymentId};
 use crate::ln::features::{BlindedHopFeatures, Bolt11InvoiceFeatures, Bolt12InvoiceFeatures, ChannelFeatures, NodeFeatures};
 use crate::ln::msgs::{DecodeError, ErrorAction, LightningError, MAX_VALUE_MSAT};
 use crate::offers::invoice::{BlindedPayInfo, Bolt12Invoice};
+use crate::offers::static_invoice::StaticInvoice;
 use crate::onion_message::messenger::{DefaultMessageRouter, Destination, MessageRouter, OnionMessagePath};
-use crate::routing::gossip::{DirectedChannelInfo, EffectiveCapacity, ReadOnlyNetworkGraph, NetworkGraph, NodeId, RoutingFees};
-use crate::routing::scoring::{ChannelUsage, LockableScore, ScoreLookUp};
+use crate::routing::gossip::{ChannelInfo, DirectedChannelInfo, EffectiveCapacity, ReadOnlyNetworkGraph, NetworkGraph, NodeId, RoutingFees};
+use crate::routing::scoring::{self, ChannelUsage, LockableScore, ProbabilisticScorer, ProbabilisticScoringFeeParameters, ScoreLookUp};
 use crate::sign::EntropySource;
 use crate::util::ser::{Writeable, Readable, ReadableArgs, Writer};
 use crate::util::logger::{Level, Logger};
+use crate::util::metrics::{self, IgnoringMetricsRecorder, LatencyTimer, MetricsRecorder};
 use crate::crypto::chacha20::ChaCha20;
 
 use crate::io;
@@ -36,9 +38,10 @@ use core::{cmp, fmt};
 use core::ops::Deref;
 
 /// A [`Router`] implemented using [`find_route`].
-pub struct DefaultRouter<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>> where
+pub struct DefaultRouter<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>, MR: Deref> where
 	L::Target: Logger,
 	S::Target: for <'a> LockableScore<'a, ScoreLookUp = Sc>,
+	MR::Target: MetricsRecorder,
 {
 	network_graph: G,
 	logger: L,
@@ -46,23 +49,35 @@ pub struct DefaultRouter<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S
 	scorer: S,
 	score_params: SP,
 	message_router: DefaultMessageRouter<G, L>,
+	metrics_recorder: MR,
 }
 
-impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>> DefaultRouter<G, L, S, SP, Sc> where
+impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>, MR: Deref> DefaultRouter<G, L, S, SP, Sc, MR> where
 	L::Target: Logger,
 	S::Target: for <'a> LockableScore<'a, ScoreLookUp = Sc>,
+	MR::Target: MetricsRecorder,
 {
 	/// Creates a new router.
-	pub fn new(network_graph: G, logger: L, random_seed_bytes: [u8; 32], scorer: S, score_params: SP) -> Self {
+	///
+	/// The time spent finding routes is reported to the given [`MetricsRecorder`], see
+	/// [`metrics::FIND_ROUTE_DURATION`]. Use [`IgnoringMetricsRecorder`] if metrics are not needed.
+	pub fn new(network_graph: G, logger: L, random_seed_bytes: [u8; 32], scorer: S, score_params: SP, metrics_recorder: MR) -> Self {
 		let random_seed_bytes = Mutex::new(random_seed_bytes);
 		let message_router = DefaultMessageRouter::new(network_graph.clone());
-		Self { network_graph, logger, random_seed_bytes, scorer, score_params, message_router }
+		Self { network_graph, logger, random_seed_bytes, scorer, score_params, message_router, metrics_recorder }
+	}
+
+	fn next_random_seed_bytes(&self) -> [u8; 32] {
+		let mut locked_random_seed_bytes = self.random_seed_bytes.lock().unwrap();
+		*locked_random_seed_bytes = Sha256::hash(&*locked_random_seed_bytes).to_byte_array();
+		*locked_random_seed_bytes
 	}
 }
 
-impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>> Router for DefaultRouter<G, L, S, SP, Sc> where
+impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>, MR: Deref> Router for DefaultRouter<G, L, S, SP, Sc, MR> where
 	L::Target: Logger,
 	S::Target: for <'a> LockableScore<'a, ScoreLookUp = Sc>,
+	MR::Target: MetricsRecorder,
 {
 	fn find_route(
 		&self,
@@ -71,17 +86,17 @@ impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized,
 		first_hops: Option<&[&ChannelDetails]>,
 		inflight_htlcs: InFlightHtlcs
 	) -> Result<Route, LightningError> {
-		let random_seed_bytes = {
-			let mut locked_random_seed_bytes = self.random_seed_bytes.lock().unwrap();
-			*locked_random_seed_bytes = Sha256::hash(&*locked_random_seed_bytes).to_byte_array();
-			*locked_random_seed_bytes
-		};
-		find_route(
+		let random_seed_bytes = self.next_random_seed_bytes();
+		let timer = LatencyTimer::start();
+		let route_res = find_route(
 			payer, params, &self.network_graph, first_hops, &*self.logger,
 			&ScorerAccountingForInFlightHtlcs::new(self.scorer.read_lock(), &inflight_htlcs),
 			&self.score_params,
 			&random_seed_bytes
-		)
+		);
+		let result = if route_res.is_ok() { "success" } else { "failure" };
+		timer.record(&*self.metrics_recorder, metrics::FIND_ROUTE_DURATION, &[("result", result)]);
+		route_res
 	}
 
 	fn create_blinded_payment_paths<
@@ -159,9 +174,10 @@ impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized,
 	}
 }
 
-impl< G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>> MessageRouter for DefaultRouter<G, L, S, SP, Sc> where
+impl< G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized, Sc: ScoreLookUp<ScoreParams = SP>, MR: Deref> MessageRouter for DefaultRouter<G, L, S, SP, Sc, MR> where
 	L::Target: Logger,
 	S::Target: for <'a> LockableScore<'a, ScoreLookUp = Sc>,
+	MR::Target: MetricsRecorder,
 {
 	fn find_path(
 		&self, sender: PublicKey, peers: Vec<PublicKey>, destination: Destination
@@ -179,6 +195,235 @@ impl< G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, SP: Sized,
 	}
 }
 
+/// A [`Router`] implemented using [`find_min_cost_flow_route`].
+///
+/// Payments which [`find_min_cost_flow_route`] cannot route, e.g., because they are made to blinded
+/// paths or don't support multi-path payments, are routed using [`find_route`] instead, as done by
+/// [`DefaultRouter`].
+pub struct MinCostFlowRouter<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, MR: Deref> where
+	L::Target: Logger,
+	S::Target: for <'a> LockableScore<'a, ScoreLookUp = ProbabilisticScorer<G, L>>,
+	MR::Target: MetricsRecorder,
+{
+	router: DefaultRouter<G, L, S, ProbabilisticScoringFeeParameters, ProbabilisticScorer<G, L>, MR>,
+}
+
+impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, MR: Deref> MinCostFlowRouter<G, L, S, MR> where
+	L::Target: Logger,
+	S::Target: for <'a> LockableScore<'a, ScoreLookUp = ProbabilisticScorer<G, L>>,
+	MR::Target: MetricsRecorder,
+{
+	/// Creates a new router.
+	///
+	/// The time spent finding routes is reported to the given [`MetricsRecorder`], see
+	/// [`metrics::FIND_ROUTE_DURATION`]. Use [`IgnoringMetricsRecorder`] if metrics are not needed.
+	pub fn new(
+		network_graph: G, logger: L, random_seed_bytes: [u8; 32], scorer: S,
+		score_params: ProbabilisticScoringFeeParameters, metrics_recorder: MR
+	) -> Self {
+		Self { router: DefaultRouter::new(network_graph, logger, random_seed_bytes, scorer, score_params, metrics_recorder) }
+	}
+}
+
+impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, MR: Deref> Router for MinCostFlowRouter<G, L, S, MR> where
+	L::Target: Logger,
+	S::Target: for <'a> LockableScore<'a, ScoreLookUp = ProbabilisticScorer<G, L>>,
+	MR::Target: MetricsRecorder,
+{
+	fn find_route(
+		&self, payer: &PublicKey, params: &RouteParameters,
+		first_hops: Option<&[&ChannelDetails]>, inflight_htlcs: InFlightHtlcs
+	) -> Result<Route, LightningError> {
+		let random_seed_bytes = self.router.next_random_seed_bytes();
+		let timer = LatencyTimer::start();
+		let route_res = find_min_cost_flow_route(
+			payer, params, &self.router.network_graph, first_hops, &*self.router.logger,
+			&*self.router.scorer.read_lock(), &self.router.score_params, &inflight_htlcs,
+			&random_seed_bytes
+		);
+		match route_res {
+			Ok(route) => {
+				timer.record(&*self.router.metrics_recorder, metrics::FIND_ROUTE_DURATION, &[("result", "success")]);
+				Ok(route)
+			},
+			Err(e) => {
+				// The fallback records its own latency and result.
+				log_debug!(self.router.logger, "Falling back to find_route as no min-cost flow route was found: {}", e.err);
+				self.router.find_route(payer, params, first_hops, inflight_htlcs)
+			},
+		}
+	}
+
+	fn create_blinded_payment_paths<
+		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
+	>(
+		&self, recipient: PublicKey, first_hops: Vec<ChannelDetails>, tlvs: ReceiveTlvs,
+		amount_msats: u64, entropy_source: &ES, secp_ctx: &Secp256k1<T>
+	) -> Result<Vec<(BlindedPayInfo, BlindedPath)>, ()> {
+		self.router.create_blinded_payment_paths(
+			recipient, first_hops, tlvs, amount_msats, entropy_source, secp_ctx
+		)
+	}
+}
+
+impl<G: Deref<Target = NetworkGraph<L>> + Clone, L: Deref, S: Deref, MR: Deref> MessageRouter for MinCostFlowRouter<G, L, S, MR> where
+	L::Target: Logger,
+	S::Target: for <'a> LockableScore<'a, ScoreLookUp = ProbabilisticScorer<G, L>>,
+	MR::Target: MetricsRecorder,
+{
+	fn find_path(
+		&self, sender: PublicKey, peers: Vec<PublicKey>, destination: Destination
+	) -> Result<OnionMessagePath, ()> {
+		self.router.find_path(sender, peers, destination)
+	}
+
+	fn create_blinded_paths<
+		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
+	>(
+		&self, recipient: PublicKey, peers: Vec<PublicKey>, entropy_source: &ES,
+		secp_ctx: &Secp256k1<T>
+	) -> Result<Vec<BlindedPath>, ()> {
+		self.router.create_blinded_paths(recipient, peers, entropy_source, secp_ctx)
+	}
+}
+
+/// A [`Router`] which delegates path-finding to a trampoline node, for nodes which cannot afford to
+/// sync and store the full [`NetworkGraph`].
+///
+/// Payments which can be routed without a trampoline node are handed to the wrapped router. This is
+/// the case for payments to a direct peer, payments into blinded paths, and payments for which none
+/// of our peers support trampoline routing. Otherwise, the payment is sent over our channel to a
+/// trampoline-capable peer, which then finds a route to the recipient itself.
+///
+/// The trampoline node is paid `fee_base_msat` plus `fee_proportional_millionths` of the payment
+/// amount and is given `cltv_expiry_delta` blocks, out of which it must pay for its own fee and
+/// CLTV delta as well as those of the route it finds.
+pub struct TrampolineRouter<R: Deref> where R::Target: Router {
+	router: R,
+	fee_base_msat: u32,
+	fee_proportional_millionths: u32,
+	cltv_expiry_delta: u16,
+}
+
+impl<R: Deref> TrampolineRouter<R> where R::Target: Router {
+	/// Creates a new router wrapping the given `router`, paying trampoline nodes the given fees and
+	/// CLTV expiry delta.
+	pub fn new(
+		router: R, fee_base_msat: u32, fee_proportional_millionths: u32, cltv_expiry_delta: u16
+	) -> Self {
+		Self { router, fee_base_msat, fee_proportional_millionths, cltv_expiry_delta }
+	}
+
+	fn trampoline_fee_msat(&self, amount_msat: u64) -> u64 {
+		(self.fee_base_msat as u64).saturating_add(
+			amount_msat.saturating_mul(self.fee_proportional_millionths as u64) / 1_000_000
+		)
+	}
+}
+
+impl<R: Deref> Router for TrampolineRouter<R> where R::Target: Router {
+	fn find_route(
+		&self, payer: &PublicKey, params: &RouteParameters,
+		first_hops: Option<&[&ChannelDetails]>, inflight_htlcs: InFlightHtlcs
+	) -> Result<Route, LightningError> {
+		let (payee, final_cltv_expiry_delta) = match params.payment_params.payee {
+			Payee::Clear { node_id, final_cltv_expiry_delta, .. } => (node_id, final_cltv_expiry_delta),
+			Payee::Blinded { .. } => return self.router.find_route(payer, params, first_hops, inflight_htlcs),
+		};
+		let first_hops = match first_hops {
+			Some(first_hops) if !first_hops.iter().any(|hop| hop.counterparty.node_id == payee) => first_hops,
+			_ => return self.router.find_route(payer, params, first_hops, inflight_htlcs),
+		};
+
+		let fee_msat = self.trampoline_fee_msat(params.final_value_msat);
+		let amount_msat = params.final_value_msat.saturating_add(fee_msat);
+		let first_hop = first_hops.iter()
+			.filter(|details| details.is_usable)
+			.filter(|details| details.counterparty.features.supports_trampoline_routing())
+			.filter(|details| details.get_outbound_payment_scid().is_some())
+			.filter(|details| amount_msat <= details.next_outbound_htlc_limit_msat)
+			.filter(|details| amount_msat >= details.next_outbound_htlc_minimum_msat)
+			.max_by_key(|details| details.next_outbound_htlc_limit_msat);
+		let first_hop = match first_hop {
+			Some(first_hop) => first_hop,
+			None => return self.router.find_route(payer, params, Some(first_hops), inflight_htlcs),
+		};
+
+		if params.max_total_routing_fee_msat.map_or(false, |max_fee_msat| fee_msat > max_fee_msat) {
+			return Err(LightningError {
+				err: "Trampoline fee exceeds the maximum total routing fee".to_owned(),
+				action: ErrorAction::IgnoreError,
+			});
+		}
+		let cltv_expiry_delta = (self.cltv_expiry_delta as u32).saturating_add(final_cltv_expiry_delta);
+		if cltv_expiry_delta > params.payment_params.max_total_cltv_expiry_delta {
+			return Err(LightningError {
+				err: "Trampoline CLTV expiry delta exceeds the maximum total CLTV expiry delta".to_owned(),
+				action: ErrorAction::IgnoreError,
+			});
+		}
+
+		let trampoline_node_id = first_hop.counterparty.node_id;
+		let node_features: NodeFeatures = first_hop.counterparty.features.to_context();
+		let path = Path {
+			hops: vec![RouteHop {
+				pubkey: trampoline_node_id,
+				node_features: node_features.clone(),
+				short_channel_id: first_hop.get_outbound_payment_scid().unwrap(),
+				channel_features: first_hop.counterparty.features.to_context(),
+				fee_msat,
+				cltv_expiry_delta,
+				maybe_announced_channel: first_hop.is_public,
+			}],
+			blinded_tail: None,
+			trampoline_hops: vec![
+				TrampolineHop {
+					pubkey: trampoline_node_id,
+					node_features,
+					fee_msat,
+					cltv_expiry_delta: self.cltv_expiry_delta as u32,
+				},
+				TrampolineHop {
+					pubkey: payee,
+					node_features: params.payment_params.payee.node_features()
+						.unwrap_or_else(NodeFeatures::empty),
+					fee_msat: params.final_value_msat,
+					cltv_expiry_delta: final_cltv_expiry_delta,
+				},
+			],
+		};
+		Ok(Route { paths: vec![path], route_params: Some(params.clone()) })
+	}
+
+	fn create_blinded_payment_paths<
+		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
+	>(
+		&self, recipient: PublicKey, first_hops: Vec<ChannelDetails>, tlvs: ReceiveTlvs,
+		amount_msats: u64, entropy_source: &ES, secp_ctx: &Secp256k1<T>
+	) -> Result<Vec<(BlindedPayInfo, BlindedPath)>, ()> {
+		self.router.create_blinded_payment_paths(
+			recipient, first_hops, tlvs, amount_msats, entropy_source, secp_ctx
+		)
+	}
+}
+
+impl<R: Deref> MessageRouter for TrampolineRouter<R> where R::Target: Router {
+	fn find_path(
+		&self, sender: PublicKey, peers: Vec<PublicKey>, destination: Destination
+	) -> Result<OnionMessagePath, ()> {
+		self.router.find_path(sender, peers, destination)
+	}
+
+	fn create_blinded_paths<
+		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
+	>(
+		&self, recipient: PublicKey, peers: Vec<PublicKey>, entropy_source: &ES,
+		secp_ctx: &Secp256k1<T>
+	) -> Result<Vec<BlindedPath>, ()> {
+		self.router.create_blinded_paths(recipient, peers, entropy_source, secp_ctx)
+	}
+}
+
 /// A trait defining behavior for routing a payment.
 pub trait Router: MessageRouter {
 	/// Finds a [`Route`] for a payment between the given `payer` and a payee.
@@ -413,6 +658,31 @@ impl_writeable_tlv_based!(BlindedTail, {
 	(6, final_value_msat, required),
 });
 
+/// A trampoline hop in a [`Path`], i.e. a node which will compute the route to the next
+/// trampoline hop (or the recipient) on our behalf.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct TrampolineHop {
+	/// The node_id of the node at this hop.
+	pub pubkey: PublicKey,
+	/// The node_announcement features of the node at this hop.
+	pub node_features: NodeFeatures,
+	/// The fee this trampoline node is paid for routing the payment to the next trampoline hop.
+	/// If this is the last hop in [`Path::trampoline_hops`], this is the full value of this
+	/// [`Path`]'s part of the payment.
+	pub fee_msat: u64,
+	/// The CLTV delta this trampoline node requires for routing the payment to the next trampoline
+	/// hop. If this is the last hop in [`Path::trampoline_hops`], this is the CLTV delta expected
+	/// at the destination.
+	pub cltv_expiry_delta: u32,
+}
+
+impl_writeable_tlv_based!(TrampolineHop, {
+	(0, pubkey, required),
+	(2, node_features, required),
+	(4, fee_msat, required),
+	(6, cltv_expiry_delta, required),
+});
+
 /// A path in a [`Route`] to the payment recipient. Must always be at least length one.
 /// If no [`Path::blinded_tail`] is present, then [`Path::hops`] length may be up to 19.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -421,23 +691,30 @@ pub struct Path {
 	pub hops: Vec<RouteHop>,
 	/// The blinded path at which this path terminates, if we're sending to one, and its metadata.
 	pub blinded_tail: Option<BlindedTail>,
+	/// The trampoline hops this [`Path`] is routed through, if any, ending with the recipient.
+	///
+	/// If non-empty, the first trampoline hop must be the last hop in [`Path::hops`], whose
+	/// [`RouteHop::fee_msat`] and [`RouteHop::cltv_expiry_delta`] are then the total fee and CLTV
+	/// delta of all trampoline hops. Cannot be combined with a [`Path::blinded_tail`].
+	pub trampoline_hops: Vec<TrampolineHop>,
 }
 
 impl Path {
 	/// Gets the fees for a given path, excluding any excess paid to the recipient.
 	pub fn fee_msat(&self) -> u64 {
-		match &self.blinded_tail {
-			Some(_) => self.hops.iter().map(|hop| hop.fee_msat).sum::<u64>(),
-			None => {
-				// Do not count last hop of each path since that's the full value of the payment
-				self.hops.split_last().map_or(0,
-					|(_, path_prefix)| path_prefix.iter().map(|hop| hop.fee_msat).sum())
-			}
+		if self.blinded_tail.is_some() || !self.trampoline_hops.is_empty() {
+			return self.hops.iter().map(|hop| hop.fee_msat).sum::<u64>();
 		}
+		// Do not count last hop of each path since that's the full value of the payment
+		self.hops.split_last().map_or(0,
+			|(_, path_prefix)| path_prefix.iter().map(|hop| hop.fee_msat).sum())
 	}
 
 	/// Gets the total amount paid on this [`Path`], excluding the fees.
 	pub fn final_value_msat(&self) -> u64 {
+		if let Some(trampoline_hop) = self.trampoline_hops.last() {
+			return trampoline_hop.fee_msat;
+		}
 		match &self.blinded_tail {
 			Some(blinded_tail) => blinded_tail.final_value_msat,
 			None => self.hops.last().map_or(0, |hop| hop.fee_msat)
@@ -446,6 +723,9 @@ impl Path {
 
 	/// Gets the final hop's CLTV expiry delta.
 	pub fn final_cltv_expiry_delta(&self) -> Option<u32> {
+		if let Some(trampoline_hop) = self.trampoline_hops.last() {
+			return Some(trampoline_hop.cltv_expiry_delta);
+		}
 		match &self.blinded_tail {
 			Some(_) => None,
 			None => self.hops.last().map(|hop| hop.cltv_expiry_delta)
@@ -523,12 +803,16 @@ impl Writeable for Route {
 				blinded_tails.push(Some(blinded_tail));
 			} else if !blinded_tails.is_empty() { blinded_tails.push(None); }
 		}
+		let trampoline_hops = if self.paths.iter().any(|path| !path.trampoline_hops.is_empty()) {
+			self.paths.iter().map(|path| &path.trampoline_hops).collect()
+		} else { Vec::new() };
 		write_tlv_fields!(writer, {
 			// For compatibility with LDK versions prior to 0.0.117, we take the individual
 			// RouteParameters' fields and reconstruct them on read.
 			(1, self.route_params.as_ref().map(|p| &p.payment_params), option),
 			(2, blinded_tails, optional_vec),
 			(3, self.route_params.as_ref().map(|p| p.final_value_msat), option),
+			(4, trampoline_hops, optional_vec),
 			(5, self.route_params.as_ref().and_then(|p| p.max_total_routing_fee_msat), option),
 		});
 		Ok(())
@@ -551,12 +835,13 @@ impl Readable for Route {
 			if hops.is_empty() { return Err(DecodeError::InvalidValue); }
 			min_final_cltv_expiry_delta =
 				cmp::min(min_final_cltv_expiry_delta, hops.last().unwrap().cltv_expiry_delta);
-			paths.push(Path { hops, blinded_tail: None });
+			paths.push(Path { hops, blinded_tail: None, trampoline_hops: vec![] });
 		}
 		_init_and_read_len_prefixed_tlv_fields!(reader, {
 			(1, payment_params, (option: ReadableArgs, min_final_cltv_expiry_delta)),
 			(2, blinded_tails, optional_vec),
 			(3, final_value_msat, option),
+			(4, trampoline_hops, optional_vec),
 			(5, max_total_routing_fee_msat, option)
 		});
 		let blinded_tails = blinded_tails.unwrap_or(Vec::new());
@@ -566,6 +851,13 @@ impl Readable for Route {
 				path.blinded_tail = blinded_tail_opt;
 			}
 		}
+		let trampoline_hops: Vec<Vec<TrampolineHop>> = trampoline_hops.unwrap_or(Vec::new());
+		if trampoline_hops.len() != 0 {
+			if trampoline_hops.len() != paths.len() { return Err(DecodeError::InvalidValue) }
+			for (path, path_trampoline_hops) in paths.iter_mut().zip(trampoline_hops.into_iter()) {
+				path.trampoline_hops = path_trampoline_hops;
+			}
+		}
 
 		// If we previously wrote the corresponding fields, reconstruct RouteParameters.
 		let route_params = match (payment_params, final_value_msat) {
@@ -654,6 +946,8 @@ pub const DEFAULT_MAX_PATH_COUNT: u8 = 10;
 
 const DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF: u8 = 2;
 
+const DEFAULT_MAX_CHANNEL_SHARE_PERCENT: u8 = 100;
+
 // The median hop CLTV expiry delta currently seen in the network.
 const MEDIAN_HOP_CLTV_EXPIRY_DELTA: u32 = 40;
 
@@ -711,8 +1005,46 @@ pub struct PaymentParameters {
 	/// payment was previously attempted over and which caused the payment to fail. Future attempts
 	/// for the same payment shouldn't be relayed through any of these blinded paths.
 	pub previously_failed_blinded_path_idxs: Vec<u64>,
+
+	/// The degree to which the paths of a multi-path payment must not share channels or nodes, so
+	/// that a single failing channel or node doesn't cause multiple paths to fail.
+	///
+	/// Note that requiring disjoint paths may cause routing to fail if there aren't enough disjoint
+	/// paths to the payee, e.g., if they only have few channels.
+	///
+	/// Default value: [`PathDisjointness::None`]
+	pub path_disjointness: PathDisjointness,
+
+	/// The maximum share of the payment amount, in percent, which may be sent over any single
+	/// channel across all paths of a multi-path payment. Channels from us to our counterparties are
+	/// exempt from this limit.
+	///
+	/// Lowering this spreads a payment over more channels, limiting the amount which fails if a
+	/// single channel fails. Note that values below `100 / max_path_count` will cause routing to
+	/// fail, as will values which can't be met given the payee's channels.
+	///
+	/// Default value: 100
+	pub max_channel_share_percent: u8,
 }
 
+/// The degree to which the paths of a multi-path payment must not share hops, see
+/// [`PaymentParameters::path_disjointness`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum PathDisjointness {
+	/// Paths may share any channels and nodes.
+	None,
+	/// Paths must not share any channels, though they may pass through the same nodes.
+	Channels,
+	/// Paths must not share any channels or intermediary nodes.
+	Nodes,
+}
+
+impl_writeable_tlv_based_enum!(PathDisjointness,
+	(0, None) => {},
+	(2, Channels) => {},
+	(4, Nodes) => {}, ;
+);
+
 impl Writeable for PaymentParameters {
 	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), io::Error> {
 		let mut clear_hints = &vec![];
@@ -733,6 +1065,8 @@ impl Writeable for PaymentParameters {
 			(8, *blinded_hints, optional_vec),
 			(9, self.payee.final_cltv_expiry_delta(), option),
 			(11, self.previously_failed_blinded_path_idxs, required_vec),
+			(13, self.path_disjointness, required),
+			(15, self.max_channel_share_percent, required),
 		});
 		Ok(())
 	}
@@ -752,6 +1086,8 @@ impl ReadableArgs<u32> for PaymentParameters {
 			(8, blinded_route_hints, optional_vec),
 			(9, final_cltv_expiry_delta, (default_value, default_final_cltv_expiry_delta)),
 			(11, previously_failed_blinded_path_idxs, optional_vec),
+			(13, path_disjointness, (default_value, PathDisjointness::None)),
+			(15, max_channel_share_percent, (default_value, DEFAULT_MAX_CHANNEL_SHARE_PERCENT)),
 		});
 		let blinded_route_hints = blinded_route_hints.unwrap_or(vec![]);
 		let payee = if blinded_route_hints.len() != 0 {
@@ -776,6 +1112,8 @@ impl ReadableArgs<u32> for PaymentParameters {
 			expiry_time,
 			previously_failed_channels: previously_failed_channels.unwrap_or(Vec::new()),
 			previously_failed_blinded_path_idxs: previously_failed_blinded_path_idxs.unwrap_or(Vec::new()),
+			path_disjointness: _init_tlv_based_struct_field!(path_disjointness, (default_value, unused)),
+			max_channel_share_percent: _init_tlv_based_struct_field!(max_channel_share_percent, (default_value, unused)),
 		})
 	}
 }
@@ -795,6 +1133,8 @@ impl PaymentParameters {
 			max_channel_saturation_power_of_half: DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF,
 			previously_failed_channels: Vec::new(),
 			previously_failed_blinded_path_idxs: Vec::new(),
+			path_disjointness: PathDisjointness::None,
+			max_channel_share_percent: DEFAULT_MAX_CHANNEL_SHARE_PERCENT,
 		}
 	}
 
@@ -824,6 +1164,15 @@ impl PaymentParameters {
 			.with_expiry_time(invoice.created_at().as_secs().saturating_add(invoice.relative_expiry().as_secs()))
 	}
 
+	/// Creates parameters for paying to a blinded payee from the provided [`StaticInvoice`]. Sets
+	/// [`Payee::Blinded::route_hints`], [`Payee::Blinded::features`], and
+	/// [`PaymentParameters::expiry_time`].
+	pub fn from_static_invoice(invoice: &StaticInvoice) -> Self {
+		Self::blinded(invoice.payment_paths().to_vec())
+			.with_bolt12_features(invoice.invoice_features().clone()).unwrap()
+			.with_expiry_time(invoice.created_at().as_secs().saturating_add(invoice.relative_expiry().as_secs()))
+	}
+
 	/// Creates parameters for paying to a blinded payee from the provided blinded route hints.
 	pub fn blinded(blinded_route_hints: Vec<(BlindedPayInfo, BlindedPath)>) -> Self {
 		Self {
@@ -834,6 +1183,8 @@ impl PaymentParameters {
 			max_channel_saturation_power_of_half: DEFAULT_MAX_CHANNEL_SATURATION_POW_HALF,
 			previously_failed_channels: Vec::new(),
 			previously_failed_blinded_path_idxs: Vec::new(),
+			path_disjointness: PathDisjointness::None,
+			max_channel_share_percent: DEFAULT_MAX_CHANNEL_SHARE_PERCENT,
 		}
 	}
 
@@ -910,6 +1261,22 @@ impl PaymentParameters {
 		Self { max_channel_saturation_power_of_half, ..self }
 	}
 
+	/// Includes a requirement for the paths of a multi-path payment to not share channels or nodes.
+	/// See [`PaymentParameters::path_disjointness`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_path_disjointness(self, path_disjointness: PathDisjointness) -> Self {
+		Self { path_disjointness, ..self }
+	}
+
+	/// Includes a limit for the maximum share of the payment amount that can be sent over any single
+	/// channel, in percent. See [`PaymentParameters::max_channel_share_percent`].
+	///
+	/// This is not exported to bindings users since bindings don't support move semantics
+	pub fn with_max_channel_share_percent(self, max_channel_share_percent: u8) -> Self {
+		Self { max_channel_share_percent, ..self }
+	}
+
 	pub(crate) fn insert_previously_failed_blinded_path(&mut self, failed_blinded_tail: &BlindedTail) {
 		let mut found_blinded_tail = false;
 		for (idx, (_, path)) in self.payee.blinded_route_hints().iter().enumerate() {
@@ -1808,11 +2175,623 @@ where L::Target: Logger, GL::Target: Logger {
 	Ok(route)
 }
 
+/// A candidate [`Route`] for a payment, as returned by [`find_route_candidates`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteCandidate {
+	/// The candidate route.
+	pub route: Route,
+	/// The total cost of the route, i.e., the fees paid plus the penalties assigned by the scorer,
+	/// in msat.
+	pub cost_msat: u64,
+}
+
+/// Finds up to `max_candidates` alternative routes from us (payer) to the given target node
+/// (payee), ranked by their cost, i.e., the fees paid plus the penalties assigned by the scorer.
+///
+/// This allows to pick a route ourselves rather than relying on the single route chosen by
+/// [`find_route`], which is always the first candidate returned. Alternatives are found by
+/// excluding the channels used by the candidates found so far in turn, similar to Yen's
+/// algorithm, thus each candidate differs from the others by at least one channel.
+///
+/// See [`find_route`] for details on the parameters. Note that this is significantly more
+/// expensive than [`find_route`], as it runs a path search for each candidate considered.
+pub fn find_route_candidates<L: Deref, GL: Deref, S: ScoreLookUp>(
+	our_node_pubkey: &PublicKey, route_params: &RouteParameters,
+	network_graph: &NetworkGraph<GL>, first_hops: Option<&[&ChannelDetails]>, logger: L,
+	scorer: &S, score_params: &S::ScoreParams, random_seed_bytes: &[u8; 32], max_candidates: usize,
+) -> Result<Vec<RouteCandidate>, LightningError>
+where L::Target: Logger, GL::Target: Logger {
+	let graph_lock = network_graph.read_only();
+	let (route, cost_msat) = get_route_with_cost(our_node_pubkey, route_params, &graph_lock,
+		first_hops, &*logger, scorer, score_params, random_seed_bytes)?;
+	// Each candidate along with the channels excluded to find it.
+	let mut candidates = vec![(RouteCandidate { route, cost_msat }, Vec::new())];
+
+	let mut tried_exclusions: HashSet<Vec<u64>> = HashSet::new();
+	let max_attempts = max_candidates.saturating_mul(MAX_PATH_LENGTH_ESTIMATE as usize);
+	let mut attempts = 0;
+	let mut candidate_idx = 0;
+	'candidates: while candidate_idx < candidates.len() {
+		let (candidate, excluded_scids) = &candidates[candidate_idx];
+		let candidate_scids: Vec<u64> = candidate.route.paths.iter()
+			.flat_map(|path| path.hops.iter().map(|hop| hop.short_channel_id))
+			.collect();
+		let excluded_scids = excluded_scids.clone();
+		candidate_idx += 1;
+
+		for scid in candidate_scids {
+			if candidates.len() >= max_candidates || attempts >= max_attempts { break 'candidates; }
+
+			let mut alternative_excluded_scids = excluded_scids.clone();
+			alternative_excluded_scids.push(scid);
+			alternative_excluded_scids.sort_unstable();
+			alternative_excluded_scids.dedup();
+			if !tried_exclusions.insert(alternative_excluded_scids.clone()) { continue; }
+			attempts += 1;
+
+			let mut alternative_route_params = route_params.clone();
+			alternative_route_params.payment_params.previously_failed_channels
+				.extend_from_slice(&alternative_excluded_scids);
+			let (mut route, cost_msat) = match get_route_with_cost(our_node_pubkey,
+				&alternative_route_params, &graph_lock, first_hops, &*logger, scorer, score_params,
+				random_seed_bytes)
+			{
+				Ok(res) => res,
+				Err(_) => continue,
+			};
+			if candidates.iter().any(|(candidate, _)| candidate.route.paths == route.paths) {
+				continue;
+			}
+			route.route_params = Some(route_params.clone());
+			candidates.push((RouteCandidate { route, cost_msat }, alternative_excluded_scids));
+		}
+	}
+	log_debug!(logger, "Found {} route candidates after {} alternative path searches", candidates.len(), attempts);
+
+	let mut candidates: Vec<RouteCandidate> = candidates.into_iter()
+		.map(|(candidate, _)| candidate)
+		.collect();
+	candidates.sort_by_key(|candidate| candidate.cost_msat);
+	candidates.truncate(max_candidates);
+	for candidate in candidates.iter_mut() {
+		add_random_cltv_offset(&mut candidate.route, &route_params.payment_params, &graph_lock,
+			random_seed_bytes);
+	}
+	Ok(candidates)
+}
+
+/// The number of units the payment is split into when finding a min-cost flow, i.e., the
+/// granularity at which it may be split over paths.
+const MIN_COST_FLOW_UNITS: u64 = 100;
+
+/// The number of linear segments used to approximate the liquidity penalty of each channel when
+/// finding a min-cost flow.
+const MIN_COST_FLOW_SEGMENTS: u64 = 5;
+
+/// The number of hops by which paths considered when finding a min-cost flow may be longer than
+/// the shortest path to the payee.
+const MIN_COST_FLOW_EXTRA_HOPS: u8 = 2;
+
+/// Where a [`FlowHop`] was learned from.
+enum FlowHopSource<'a> {
+	FirstHop(&'a ChannelDetails),
+	Graph(&'a ChannelInfo),
+	RouteHint,
+}
+
+/// A channel considered when finding a min-cost flow, directed towards the payee.
+struct FlowHop<'a> {
+	source: NodeId,
+	target: NodeId,
+	short_channel_id: u64,
+	hop_source: FlowHopSource<'a>,
+	fees: RoutingFees,
+	cltv_expiry_delta: u16,
+	htlc_minimum_msat: u64,
+	/// The maximum amount we're willing to send over the channel, across all paths.
+	max_msat: u64,
+	/// The bounds on the liquidity available in the channel, used to compute its penalty.
+	min_liquidity_msat: u64,
+	max_liquidity_msat: u64,
+	/// Any penalty which is incurred regardless of the amount sent over the channel.
+	fixed_penalty_msat: u64,
+}
+
+/// An arc in the residual graph used to find a min-cost flow. Arcs are stored in pairs, with the
+/// arc at an even index carrying flow and the following arc at an odd index allowing to undo it.
+struct FlowArc {
+	target: usize,
+	residual_units: u64,
+	cost_per_unit: i64,
+}
+
+/// Finds a route from us (payer) to the given target node (payee) by solving a min-cost flow
+/// problem over the given [`NetworkGraph`], splitting the payment over multiple paths as needed.
+///
+/// Unlike [`find_route`], which adds one path at a time until the payment amount is reached, this
+/// considers how to split the whole amount at once. The cost of sending an amount over a channel
+/// is the fee charged for it plus a penalty for the probability of it failing, as estimated from
+/// the liquidity bounds learned by the [`ProbabilisticScorer`], assuming the liquidity to be
+/// uniformly distributed within them. As this penalty is convex in the amount sent, a split
+/// minimizing the overall cost, and thus maximizing the probability of success, can be found
+/// efficiently by approximating it piecewise linearly. Base fees and penalties, which are not
+/// convex, are charged as if they were proportional to the share of the payment sent over the
+/// channel.
+///
+/// Payments to blinded paths, payments which don't support multi-path payments and payments
+/// requiring disjoint paths are not supported and will fail, as will payments for which the flow
+/// found cannot be turned into paths adhering to the [`PaymentParameters`]' limits, e.g., due to
+/// the fees added along the paths. [`MinCostFlowRouter`] falls back to [`find_route`] in these
+/// cases.
+///
+/// See [`find_route`] for details on the remaining parameters.
+pub fn find_min_cost_flow_route<L: Deref, GL: Deref, G: Deref<Target = NetworkGraph<SL>>, SL: Deref>(
+	our_node_pubkey: &PublicKey, route_params: &RouteParameters,
+	network_graph: &NetworkGraph<GL>, first_hops: Option<&[&ChannelDetails]>, logger: L,
+	scorer: &ProbabilisticScorer<G, SL>, score_params: &ProbabilisticScoringFeeParameters,
+	inflight_htlcs: &InFlightHtlcs, random_seed_bytes: &[u8; 32]
+) -> Result<Route, LightningError>
+where L::Target: Logger, GL::Target: Logger, SL::Target: Logger {
+	let graph_lock = network_graph.read_only();
+	let mut route = get_min_cost_flow_route(our_node_pubkey, route_params, &graph_lock, first_hops,
+		&*logger, scorer, score_params, inflight_htlcs)?;
+	add_random_cltv_offset(&mut route, &route_params.payment_params, &graph_lock, random_seed_bytes);
+	Ok(route)
+}
+
+fn get_min_cost_flow_route<L: Deref, G: Deref<Target = NetworkGraph<SL>>, SL: Deref>(
+	our_node_pubkey: &PublicKey, route_params: &RouteParameters, network_graph: &ReadOnlyNetworkGraph,
+	first_hops: Option<&[&ChannelDetails]>, logger: L, scorer: &ProbabilisticScorer<G, SL>,
+	score_params: &ProbabilisticScoringFeeParameters, inflight_htlcs: &InFlightHtlcs
+) -> Result<Route, LightningError>
+where L::Target: Logger, SL::Target: Logger {
+	let payment_params = &route_params.payment_params;
+	let final_value_msat = route_params.final_value_msat;
+	let (payee_pubkey, route_hints, final_cltv_expiry_delta) = match &payment_params.payee {
+		Payee::Clear { node_id, route_hints, final_cltv_expiry_delta, .. } =>
+			(*node_id, route_hints, *final_cltv_expiry_delta),
+		Payee::Blinded { .. } => return Err(LightningError{err: "Cannot find a min-cost flow route to blinded paths".to_owned(), action: ErrorAction::IgnoreError}),
+	};
+	if !payment_params.payee.supports_basic_mpp() || payment_params.max_path_count <= 1 {
+		return Err(LightningError{err: "Cannot find a min-cost flow route for a payment which doesn't support multi-path payments".to_owned(), action: ErrorAction::IgnoreError});
+	}
+	if payment_params.path_disjointness != PathDisjointness::None {
+		return Err(LightningError{err: "Cannot find a min-cost flow route with disjoint paths".to_owned(), action: ErrorAction::IgnoreError});
+	}
+	if payee_pubkey == *our_node_pubkey {
+		return Err(LightningError{err: "Cannot generate a route to ourselves".to_owned(), action: ErrorAction::IgnoreError});
+	}
+	if final_value_msat > MAX_VALUE_MSAT {
+		return Err(LightningError{err: "Cannot generate a route of more value than all existing satoshis".to_owned(), action: ErrorAction::IgnoreError});
+	}
+	if final_value_msat == 0 {
+		return Err(LightningError{err: "Cannot send a payment of 0 msat".to_owned(), action: ErrorAction::IgnoreError});
+	}
+
+	let our_node_id = NodeId::from_pubkey(our_node_pubkey);
+	let payee_node_id = NodeId::from_pubkey(&payee_pubkey);
+	let network_nodes = network_graph.nodes();
+	let max_channel_share_msat = if payment_params.max_channel_share_percent < 100 {
+		(final_value_msat as u128 * payment_params.max_channel_share_percent as u128 / 100) as u64
+	} else {
+		u64::max_value()
+	};
+	let is_banned = |node_id: &NodeId| {
+		score_params.manual_node_penalties.get(node_id) == Some(&u64::max_value())
+	};
+
+	// Collect the channels we may use, starting with our own, followed by public channels and
+	// those in route hints.
+	let mut hops: Vec<FlowHop> = Vec::new();
+	if let Some(first_hops) = first_hops {
+		for details in first_hops.iter() {
+			let short_channel_id = match details.get_outbound_payment_scid() {
+				Some(short_channel_id) => short_channel_id,
+				None => continue,
+			};
+			let liquidity_msat = details.next_outbound_htlc_limit_msat;
+			hops.push(FlowHop {
+				source: our_node_id,
+				target: NodeId::from_pubkey(&details.counterparty.node_id),
+				short_channel_id,
+				hop_source: FlowHopSource::FirstHop(details),
+				fees: RoutingFees { base_msat: 0, proportional_millionths: 0 },
+				cltv_expiry_delta: 0,
+				htlc_minimum_msat: details.next_outbound_htlc_minimum_msat,
+				max_msat: liquidity_msat,
+				min_liquidity_msat: liquidity_msat,
+				max_liquidity_msat: liquidity_msat,
+				fixed_penalty_msat: 0,
+			});
+		}
+	}
+	for (short_channel_id, channel) in network_graph.channels().unordered_iter() {
+		if channel.features.requires_unknown_bits() ||
+			payment_params.previously_failed_channels.contains(short_channel_id)
+		{
+			continue;
+		}
+		for source in [&channel.node_one, &channel.node_two] {
+			if first_hops.is_some() && *source == our_node_id {
+				continue;
+			}
+			let (directed_channel, target) = match channel.as_directed_from(source) {
+				Some(directed_channel) => directed_channel,
+				None => continue,
+			};
+			let update = directed_channel.direction();
+			let target_requires_unknown_bits = network_nodes.get(target)
+				.and_then(|node| node.announcement_info.as_ref())
+				.map_or(false, |info| info.features.requires_unknown_bits());
+			if !update.enabled || target_requires_unknown_bits || is_banned(target) {
+				continue;
+			}
+			let effective_capacity = directed_channel.effective_capacity();
+			let capacity_msat = effective_capacity.as_msat();
+			let (min_liquidity_msat, max_liquidity_msat) = scorer
+				.liquidity_bounds_msat(*short_channel_id, source, target, capacity_msat)
+				.unwrap_or((0, capacity_msat));
+			let inflight_htlc_msat = inflight_htlcs
+				.used_liquidity_msat(source, target, *short_channel_id)
+				.unwrap_or(0);
+			let max_liquidity_msat = max_liquidity_msat.saturating_sub(inflight_htlc_msat);
+			let fees = if *source == our_node_id {
+				RoutingFees { base_msat: 0, proportional_millionths: 0 }
+			} else {
+				update.fees
+			};
+			hops.push(FlowHop {
+				source: *source,
+				target: *target,
+				short_channel_id: *short_channel_id,
+				hop_source: FlowHopSource::Graph(channel),
+				fees,
+				cltv_expiry_delta: update.cltv_expiry_delta,
+				htlc_minimum_msat: update.htlc_minimum_msat,
+				max_msat: cmp::min(max_liquidity_msat, cmp::min(max_channel_share_msat,
+					max_htlc_from_capacity(effective_capacity, payment_params.max_channel_saturation_power_of_half))),
+				min_liquidity_msat: min_liquidity_msat.saturating_sub(inflight_htlc_msat),
+				max_liquidity_msat,
+				fixed_penalty_msat: score_params.base_penalty_msat.saturating_add(
+					score_params.manual_node_penalties.get(target).copied().unwrap_or(0)),
+			});
+		}
+	}
+	for route_hint in route_hints.iter() {
+		for (idx, hint_hop) in route_hint.0.iter().enumerate() {
+			let source = NodeId::from_pubkey(&hint_hop.src_node_id);
+			let target = route_hint.0.get(idx + 1)
+				.map_or(payee_node_id, |next_hop| NodeId::from_pubkey(&next_hop.src_node_id));
+			if source == payee_node_id || (first_hops.is_some() && source == our_node_id) ||
+				payment_params.previously_failed_channels.contains(&hint_hop.short_channel_id)
+			{
+				continue;
+			}
+			let max_msat = hint_hop.htlc_maximum_msat.unwrap_or(MAX_VALUE_MSAT);
+			hops.push(FlowHop {
+				source,
+				target,
+				short_channel_id: hint_hop.short_channel_id,
+				hop_source: FlowHopSource::RouteHint,
+				fees: hint_hop.fees,
+				cltv_expiry_delta: hint_hop.cltv_expiry_delta,
+				htlc_minimum_msat: hint_hop.htlc_minimum_msat.unwrap_or(0),
+				max_msat: cmp::min(max_msat, max_channel_share_msat),
+				min_liquidity_msat: max_msat,
+				max_liquidity_msat: max_msat,
+				fixed_penalty_msat: score_params.base_penalty_msat,
+			});
+		}
+	}
+
+	// Index the nodes and prune the channels to those on paths at most `MIN_COST_FLOW_EXTRA_HOPS`
+	// longer than the shortest path to the payee, keeping the flow problem small.
+	let mut node_indices: HashMap<NodeId, usize> = HashMap::new();
+	let mut node_ids = Vec::new();
+	let mut node_index = |node_id: NodeId| *node_indices.entry(node_id).or_insert_with(|| {
+		node_ids.push(node_id);
+		node_ids.len() - 1
+	});
+	let source_idx = node_index(our_node_id);
+	let sink_idx = node_index(payee_node_id);
+	let hop_nodes: Vec<(usize, usize)> = hops.iter()
+		.map(|hop| (node_index(hop.source), node_index(hop.target)))
+		.collect();
+	let node_count = node_ids.len();
+	let mut outbound_hops = vec![Vec::new(); node_count];
+	let mut inbound_hops = vec![Vec::new(); node_count];
+	for (hop_idx, (source, target)) in hop_nodes.iter().enumerate() {
+		if hops[hop_idx].htlc_minimum_msat <= final_value_msat && source != target {
+			outbound_hops[*source].push(hop_idx);
+			inbound_hops[*target].push(hop_idx);
+		}
+	}
+	let hops_from_source = hop_distances(source_idx, &outbound_hops, |hop_idx| hop_nodes[hop_idx].1);
+	let hops_to_sink = hop_distances(sink_idx, &inbound_hops, |hop_idx| hop_nodes[hop_idx].0);
+	let max_path_length = match hops_from_source[sink_idx] {
+		Some(shortest_path_length) => cmp::min(
+			shortest_path_length.saturating_add(MIN_COST_FLOW_EXTRA_HOPS), MAX_PATH_LENGTH_ESTIMATE),
+		None => return Err(LightningError{err: "Failed to find a path to the given destination".to_owned(), action: ErrorAction::IgnoreError}),
+	};
+
+	// Build the residual graph, splitting the payment into units and adding an arc for each
+	// linear segment of each channel's cost.
+	let unit_msat = (final_value_msat + MIN_COST_FLOW_UNITS - 1) / MIN_COST_FLOW_UNITS;
+	let demand_units = (final_value_msat + unit_msat - 1) / unit_msat;
+	let mut arcs: Vec<FlowArc> = Vec::new();
+	let mut arc_hops: Vec<usize> = Vec::new();
+	let mut node_arcs = vec![Vec::new(); node_count];
+	for (hop_idx, hop) in hops.iter().enumerate() {
+		let (source, target) = hop_nodes[hop_idx];
+		let is_on_short_path = match (hops_from_source[source], hops_to_sink[target]) {
+			(Some(from_source), Some(to_sink)) =>
+				from_source as u16 + 1 + to_sink as u16 <= max_path_length as u16,
+			_ => false,
+		};
+		let capacity_units = cmp::min(hop.max_msat / unit_msat, demand_units);
+		if !is_on_short_path || capacity_units == 0 || !outbound_hops[source].contains(&hop_idx) {
+			continue;
+		}
+
+		let is_public = matches!(hop.hop_source, FlowHopSource::Graph(_));
+		let cost_msat = |units: u64| -> u64 {
+			let amount_msat = units * unit_msat;
+			let fee_msat = compute_fees_saturating(amount_msat, RoutingFees {
+				base_msat: 0, proportional_millionths: hop.fees.proportional_millionths,
+			});
+			let fixed_msat = (hop.fees.base_msat as u64).saturating_add(hop.fixed_penalty_msat);
+			let amortized_fixed_msat = (fixed_msat as u128 * amount_msat as u128
+				/ final_value_msat as u128) as u64;
+			let base_amount_penalty_msat = score_params.base_penalty_amount_multiplier_msat
+				.saturating_mul(amount_msat) / scoring::BASE_AMOUNT_PENALTY_DIVISOR;
+			let liquidity_penalty_msat = if is_public {
+				scoring::uncertainty_penalty_msat(amount_msat, hop.min_liquidity_msat,
+					hop.max_liquidity_msat, score_params)
+			} else { 0 };
+			fee_msat.saturating_add(amortized_fixed_msat).saturating_add(base_amount_penalty_msat)
+				.saturating_add(liquidity_penalty_msat)
+		};
+
+		// The amount up to the lower liquidity bound can be sent without any liquidity penalty,
+		// while the remaining capacity is split into equally-sized segments.
+		let certain_units = cmp::min(hop.min_liquidity_msat / unit_msat, capacity_units);
+		let uncertain_units = capacity_units - certain_units;
+		let segment_units = cmp::max((uncertain_units + MIN_COST_FLOW_SEGMENTS - 1) / MIN_COST_FLOW_SEGMENTS, 1);
+		let mut breakpoints = vec![0];
+		if certain_units > 0 { breakpoints.push(certain_units); }
+		let mut breakpoint = certain_units;
+		while breakpoint < capacity_units {
+			breakpoint = cmp::min(breakpoint + segment_units, capacity_units);
+			breakpoints.push(breakpoint);
+		}
+
+		let mut last_cost_per_unit = 0;
+		for segment in breakpoints.windows(2) {
+			let (start_units, end_units) = (segment[0], segment[1]);
+			let segment_units = end_units - start_units;
+			let segment_cost_msat = cost_msat(end_units).saturating_sub(cost_msat(start_units));
+			// Round up and ensure costs are non-decreasing, keeping the approximation convex.
+			let cost_per_unit = cmp::max(last_cost_per_unit, segment_cost_msat / segment_units +
+				if segment_cost_msat % segment_units != 0 { 1 } else { 0 });
+			let cost_per_unit = cmp::min(cost_per_unit, i64::max_value() as u64 / MAX_PATH_LENGTH_ESTIMATE as u64 / 2);
+			last_cost_per_unit = cost_per_unit;
+			node_arcs[source].push(arcs.len());
+			arcs.push(FlowArc { target, residual_units: segment_units, cost_per_unit: cost_per_unit as i64 });
+			node_arcs[target].push(arcs.len());
+			arcs.push(FlowArc { target: source, residual_units: 0, cost_per_unit: -(cost_per_unit as i64) });
+			arc_hops.push(hop_idx);
+		}
+	}
+	log_trace!(logger, "Finding a min-cost flow of {} units of {} msat over {} arcs between {} nodes",
+		demand_units, unit_msat, arcs.len(), node_count);
+
+	// Find the min-cost flow by successively augmenting the cheapest path in the residual graph.
+	// Node potentials keep the reduced arc costs non-negative, allowing us to use Dijkstra.
+	let mut potentials = vec![0i64; node_count];
+	let mut remaining_units = demand_units;
+	while remaining_units > 0 {
+		let mut distances = vec![i64::max_value(); node_count];
+		let mut previous_arcs = vec![usize::max_value(); node_count];
+		let mut heap = BinaryHeap::new();
+		distances[source_idx] = 0;
+		heap.push(cmp::Reverse((0i64, source_idx)));
+		while let Some(cmp::Reverse((distance, node))) = heap.pop() {
+			if distance > distances[node] { continue; }
+			if node == sink_idx { break; }
+			for arc_idx in node_arcs[node].iter() {
+				let arc = &arcs[*arc_idx];
+				if arc.residual_units == 0 { continue; }
+				let next_distance = distance + arc.cost_per_unit + potentials[node] - potentials[arc.target];
+				if next_distance < distances[arc.target] {
+					distances[arc.target] = next_distance;
+					previous_arcs[arc.target] = *arc_idx;
+					heap.push(cmp::Reverse((next_distance, arc.target)));
+				}
+			}
+		}
+		let sink_distance = distances[sink_idx];
+		if sink_distance == i64::max_value() {
+			return Err(LightningError{err: "Failed to find a sufficient route to the given destination".to_owned(), action: ErrorAction::IgnoreError});
+		}
+		for (potential, distance) in potentials.iter_mut().zip(distances.iter()) {
+			*potential += cmp::min(*distance, sink_distance);
+		}
+
+		let mut augment_units = remaining_units;
+		let mut node = sink_idx;
+		while node != source_idx {
+			let arc_idx = previous_arcs[node];
+			augment_units = cmp::min(augment_units, arcs[arc_idx].residual_units);
+			node = arcs[arc_idx ^ 1].target;
+		}
+		let mut node = sink_idx;
+		while node != source_idx {
+			let arc_idx = previous_arcs[node];
+			arcs[arc_idx].residual_units -= augment_units;
+			arcs[arc_idx ^ 1].residual_units += augment_units;
+			node = arcs[arc_idx ^ 1].target;
+		}
+		remaining_units -= augment_units;
+	}
+
+	// Decompose the flow into paths, cancelling any cycles we come across.
+	let mut hop_flow_units = vec![0u64; hops.len()];
+	for (pair_idx, hop_idx) in arc_hops.iter().enumerate() {
+		hop_flow_units[*hop_idx] += arcs[pair_idx * 2 + 1].residual_units;
+	}
+	let mut flow_paths: Vec<(Vec<usize>, u64)> = Vec::new();
+	'decompose: loop {
+		let mut path_hops: Vec<usize> = Vec::new();
+		let mut path_nodes = vec![source_idx];
+		let mut node = source_idx;
+		while node != sink_idx {
+			let hop_idx = match outbound_hops[node].iter().find(|hop_idx| hop_flow_units[**hop_idx] > 0) {
+				Some(hop_idx) => *hop_idx,
+				None if node == source_idx => break 'decompose,
+				None => {
+					debug_assert!(false, "Flow should be conserved at intermediate nodes");
+					return Err(LightningError{err: "Failed to decompose the min-cost flow into paths".to_owned(), action: ErrorAction::IgnoreError});
+				},
+			};
+			path_hops.push(hop_idx);
+			node = hop_nodes[hop_idx].1;
+			if let Some(cycle_start) = path_nodes.iter().position(|path_node| *path_node == node) {
+				let cycle_units = path_hops[cycle_start..].iter()
+					.map(|hop_idx| hop_flow_units[*hop_idx]).min().unwrap_or(0);
+				for hop_idx in path_hops[cycle_start..].iter() {
+					hop_flow_units[*hop_idx] -= cycle_units;
+				}
+				path_hops.truncate(cycle_start);
+				path_nodes.truncate(cycle_start + 1);
+			} else {
+				path_nodes.push(node);
+			}
+		}
+		let path_units = path_hops.iter().map(|hop_idx| hop_flow_units[*hop_idx]).min().unwrap_or(0);
+		for hop_idx in path_hops.iter() {
+			hop_flow_units[*hop_idx] -= path_units;
+		}
+		flow_paths.push((path_hops, path_units));
+	}
+	if flow_paths.len() > payment_params.max_path_count as usize {
+		return Err(LightningError{err: format!("Min-cost flow required {} paths, more than the maximum of {}",
+			flow_paths.len(), payment_params.max_path_count), action: ErrorAction::IgnoreError});
+	}
+
+	// Assign each path its share of the payment, taking the excess of the last unit from the
+	// largest path, and build the route, adding fees backwards from the payee.
+	flow_paths.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+	let excess_msat = demand_units * unit_msat - final_value_msat;
+	let mut hop_amounts_msat = vec![0u64; hops.len()];
+	let mut paths = Vec::with_capacity(flow_paths.len());
+	for (path_idx, (path_hops, path_units)) in flow_paths.iter().enumerate() {
+		let path_value_msat = path_units * unit_msat - if path_idx == 0 { excess_msat } else { 0 };
+		let mut route_hops = Vec::with_capacity(path_hops.len());
+		let mut amount_msat = path_value_msat;
+		let mut fee_msat = path_value_msat;
+		let mut cltv_expiry_delta = final_cltv_expiry_delta;
+		let mut total_cltv_expiry_delta = 0u32;
+		for hop_idx in path_hops.iter().rev() {
+			let hop = &hops[*hop_idx];
+			if amount_msat < hop.htlc_minimum_msat {
+				return Err(LightningError{err: format!("Min-cost flow path sends less than the htlc_minimum_msat of channel {}",
+					hop.short_channel_id), action: ErrorAction::IgnoreError});
+			}
+			hop_amounts_msat[*hop_idx] = hop_amounts_msat[*hop_idx].saturating_add(amount_msat);
+			if hop_amounts_msat[*hop_idx] > hop.max_msat {
+				return Err(LightningError{err: format!("Min-cost flow paths exceed the available liquidity of channel {} after fees",
+					hop.short_channel_id), action: ErrorAction::IgnoreError});
+			}
+			let node_features = if hop.target == payee_node_id && payment_params.payee.node_features().is_some() {
+				payment_params.payee.node_features().unwrap()
+			} else if let FlowHopSource::FirstHop(details) = hop.hop_source {
+				details.counterparty.features.to_context()
+			} else {
+				network_nodes.get(&hop.target)
+					.and_then(|node| node.announcement_info.as_ref())
+					.map_or_else(default_node_features, |info| info.features.clone())
+			};
+			let (channel_features, maybe_announced_channel) = match hop.hop_source {
+				FlowHopSource::FirstHop(details) =>
+					(details.counterparty.features.to_context(), details.is_public),
+				FlowHopSource::Graph(channel) => (channel.features.clone(), true),
+				FlowHopSource::RouteHint => (ChannelFeatures::empty(), network_nodes.get(&hop.target)
+					.map_or(false, |node| node.channels.iter().any(|scid| network_graph.channel(*scid)
+						.map_or(false, |c| c.as_directed_from(&hop.source).is_some())))),
+			};
+			route_hops.push(RouteHop {
+				pubkey: PublicKey::from_slice(hop.target.as_slice()).map_err(|_| LightningError{err: format!("Public key {:?} is invalid", &hop.target), action: ErrorAction::IgnoreAndLog(Level::Trace)})?,
+				node_features,
+				short_channel_id: hop.short_channel_id,
+				channel_features,
+				fee_msat,
+				cltv_expiry_delta,
+				maybe_announced_channel,
+			});
+			total_cltv_expiry_delta = total_cltv_expiry_delta.saturating_add(cltv_expiry_delta);
+			fee_msat = compute_fees_saturating(amount_msat, hop.fees);
+			amount_msat = amount_msat.saturating_add(fee_msat);
+			cltv_expiry_delta = hop.cltv_expiry_delta as u32;
+		}
+		if total_cltv_expiry_delta > payment_params.max_total_cltv_expiry_delta {
+			return Err(LightningError{err: "Min-cost flow path exceeds the maximum total CLTV expiry delta".to_owned(), action: ErrorAction::IgnoreError});
+		}
+		route_hops.reverse();
+		paths.push(Path { hops: route_hops, blinded_tail: None, trampoline_hops: vec![] });
+	}
+
+	let route = Route { paths, route_params: Some(route_params.clone()) };
+	if let Some(max_total_routing_fee_msat) = route_params.max_total_routing_fee_msat {
+		if route.get_total_fees() > max_total_routing_fee_msat {
+			return Err(LightningError{err: format!("Failed to find route that adheres to the maximum total fee limit of {}msat",
+				max_total_routing_fee_msat), action: ErrorAction::IgnoreError});
+		}
+	}
+
+	log_info!(logger, "Got min-cost flow route: {}", log_route!(route));
+	Ok(route)
+}
+
+/// Computes the number of hops from `start` to every node via breadth-first search, following
+/// the hops in `node_hops` to the node given by `next_node`.
+fn hop_distances<F: Fn(usize) -> usize>(
+	start: usize, node_hops: &[Vec<usize>], next_node: F
+) -> Vec<Option<u8>> {
+	let mut distances = vec![None; node_hops.len()];
+	let mut queue = VecDeque::new();
+	distances[start] = Some(0u8);
+	queue.push_back(start);
+	while let Some(node) = queue.pop_front() {
+		let distance = distances[node].unwrap_or(0);
+		if distance >= MAX_PATH_LENGTH_ESTIMATE { continue; }
+		for hop_idx in node_hops[node].iter() {
+			let next = next_node(*hop_idx);
+			if distances[next].is_none() {
+				distances[next] = Some(distance + 1);
+				queue.push_back(next);
+			}
+		}
+	}
+	distances
+}
+
 pub(crate) fn get_route<L: Deref, S: ScoreLookUp>(
 	our_node_pubkey: &PublicKey, route_params: &RouteParameters, network_graph: &ReadOnlyNetworkGraph,
 	first_hops: Option<&[&ChannelDetails]>, logger: L, scorer: &S, score_params: &S::ScoreParams,
-	_random_seed_bytes: &[u8; 32]
+	random_seed_bytes: &[u8; 32]
 ) -> Result<Route, LightningError>
+where L::Target: Logger {
+	get_route_with_cost(our_node_pubkey, route_params, network_graph, first_hops, logger, scorer,
+		score_params, random_seed_bytes).map(|(route, _)| route)
+}
+
+/// Finds a route as [`get_route`] does, additionally returning its total cost, i.e., the fees paid
+/// plus the penalties assigned by the scorer.
+fn get_route_with_cost<L: Deref, S: ScoreLookUp>(
+	our_node_pubkey: &PublicKey, route_params: &RouteParameters, network_graph: &ReadOnlyNetworkGraph,
+	first_hops: Option<&[&ChannelDetails]>, logger: L, scorer: &S, score_params: &S::ScoreParams,
+	_random_seed_bytes: &[u8; 32]
+) -> Result<(Route, u64), LightningError>
 where L::Target: Logger {
 
 	let payment_params = &route_params.payment_params;
@@ -2049,6 +3028,16 @@ where L::Target: Logger {
 	// when we want to stop looking for new paths.
 	let mut already_collected_value_msat = 0;
 
+	// The intermediary nodes of the paths collected so far, which may not be used by further paths
+	// if we're required to find node-disjoint paths.
+	let mut excluded_nodes: HashSet<NodeId> = HashSet::new();
+
+	// The maximum value we may send over any single channel across all paths, not counting the fees
+	// for subsequent hops, if limited via `PaymentParameters::max_channel_share_percent`.
+	let max_channel_share_msat = if allow_mpp && payment_params.max_channel_share_percent < 100 {
+		Some((final_value_msat as u128 * payment_params.max_channel_share_percent as u128 / 100) as u64)
+	} else { None };
+
 	for (_, channels) in first_hop_targets.iter_mut() {
 		sort_first_hop_channels(channels, &used_liquidities, recommended_value_msat,
 			our_node_pubkey);
@@ -2082,10 +3071,18 @@ where L::Target: Logger {
 			// - for regular channels at channel announcement (TODO)
 			// - for first and last hops early in get_route
 			let src_node_id = $candidate.source();
-			if Some(src_node_id) != $candidate.target() {
+			let uses_excluded_node = excluded_nodes.contains(&src_node_id) ||
+				$candidate.target().map_or(false, |target| excluded_nodes.contains(&target));
+			if Some(src_node_id) != $candidate.target() && !uses_excluded_node {
 				let scid_opt = $candidate.short_channel_id();
 				let effective_capacity = $candidate.effective_capacity();
-				let htlc_maximum_msat = max_htlc_from_capacity(effective_capacity, channel_saturation_pow_half);
+				let mut htlc_maximum_msat = max_htlc_from_capacity(effective_capacity, channel_saturation_pow_half);
+				if let Some(max_channel_share_msat) = max_channel_share_msat {
+					if !matches!($candidate, CandidateRouteHop::FirstHop(_)) {
+						htlc_maximum_msat = cmp::min(htlc_maximum_msat,
+							max_channel_share_msat.saturating_add($next_hops_fee_msat));
+					}
+				}
 
 				// It is tricky to subtract $next_hops_fee_msat from available liquidity here.
 				// It may be misleading because we might later choose to reduce the value transferred
@@ -2802,6 +3799,25 @@ where L::Target: Logger {
 					}
 					debug_assert!(*used_liquidity_msat <= hop_max_msat);
 				}
+				if allow_mpp && payment_params.path_disjointness != PathDisjointness::None {
+					// Exclude all hops of this path from further paths, and, if requested, any
+					// nodes along it other than us and the payee.
+					for (hop, _) in payment_path.hops.iter() {
+						if let Some(scid) = hop.candidate.short_channel_id() {
+							*used_liquidities.entry(CandidateHopId::Clear((scid, false))).or_default() = u64::max_value();
+							*used_liquidities.entry(CandidateHopId::Clear((scid, true))).or_default() = u64::max_value();
+						} else {
+							*used_liquidities.entry(hop.candidate.id()).or_default() = u64::max_value();
+						}
+						if payment_params.path_disjointness == PathDisjointness::Nodes {
+							let node_id = hop.candidate.source();
+							if node_id != our_node_id {
+								excluded_nodes.insert(node_id);
+							}
+						}
+					}
+					prevented_redundant_path_selection = true;
+				}
 				if !prevented_redundant_path_selection {
 					// If we weren't capped by hitting a liquidity limit on a channel in the path,
 					// we'll probably end up picking the same path again on the next iteration.
@@ -2975,6 +3991,9 @@ where L::Target: Logger {
 		}
 	}
 
+	let route_cost_msat = selected_route.iter()
+		.fold(0u64, |cost_msat, path| cost_msat.saturating_add(path.get_cost_msat()));
+
 	let mut paths = Vec::new();
 	for payment_path in selected_route {
 		let mut hops = Vec::with_capacity(payment_path.hops.len());
@@ -3027,7 +4046,7 @@ where L::Target: Logger {
 			core::mem::replace(&mut hop.cltv_expiry_delta, prev_cltv_expiry_delta)
 		});
 
-		paths.push(Path { hops, blinded_tail });
+		paths.push(Path { hops, blinded_tail, trampoline_hops: vec![] });
 	}
 	// Make sure we would never create a route with more paths than we allow.
 	debug_assert!(paths.len() <= payment_params.max_path_count.into());
@@ -3049,7 +4068,7 @@ where L::Target: Logger {
 	}
 
 	log_info!(logger, "Got route: {}", log_route!(route));
-	Ok(route)
+	Ok((route, route_cost_msat))
 }
 
 // When an adversarial intermediary node observes a payment, it may be able to infer its
@@ -3215,10 +4234,11 @@ mod tests {
 	use crate::blinded_path::{BlindedHop, BlindedPath};
 	use crate::routing::gossip::{NetworkGraph, P2PGossipSync, NodeId, EffectiveCapacity};
 	use crate::routing::utxo::UtxoResult;
-	use crate::routing::router::{get_route, build_route_from_hops_internal, add_random_cltv_offset, default_node_features,
-		BlindedTail, InFlightHtlcs, Path, PaymentParameters, Route, RouteHint, RouteHintHop, RouteHop, RoutingFees,
-		DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA, MAX_PATH_LENGTH_ESTIMATE, RouteParameters, CandidateRouteHop, PublicHopCandidate};
-	use crate::routing::scoring::{ChannelUsage, FixedPenaltyScorer, ScoreLookUp, ProbabilisticScorer, ProbabilisticScoringFeeParameters, ProbabilisticScoringDecayParameters};
+	use crate::routing::router::{get_route, find_route_candidates, find_min_cost_flow_route, build_route_from_hops_internal, add_random_cltv_offset, default_node_features,
+		BlindedTail, InFlightHtlcs, Path, PaymentParameters, Route, RouteHint, RouteHintHop, RouteHop, RoutingFees, TrampolineHop,
+		DEFAULT_MAX_TOTAL_CLTV_EXPIRY_DELTA, MAX_PATH_LENGTH_ESTIMATE, RouteParameters, CandidateRouteHop, PublicHopCandidate,
+		PathDisjointness, MinCostFlowRouter, Router};
+	use crate::routing::scoring::{ChannelUsage, FixedPenaltyScorer, ScoreLookUp, ScoreUpdate, ProbabilisticScorer, ProbabilisticScoringFeeParameters, ProbabilisticScoringDecayParameters};
 	use crate::routing::test_utils::{add_channel, add_or_update_node, build_graph, build_line_graph, id_to_feature_flags, get_nodes, update_channel};
 	use crate::chain::transaction::OutPoint;
 	use crate::sign::EntropySource;
@@ -3228,6 +4248,7 @@ mod tests {
 	use crate::ln::channelmanager;
 	use crate::offers::invoice::BlindedPayInfo;
 	use crate::util::config::UserConfig;
+	use crate::util::metrics::IgnoringMetricsRecorder;
 	use crate::util::test_utils as ln_test_utils;
 	use crate::crypto::chacha20::ChaCha20;
 	use crate::util::ser::{Readable, Writeable};
@@ -3246,7 +4267,9 @@ mod tests {
 
 	use crate::io::Cursor;
 	use crate::prelude::*;
-	use crate::sync::Arc;
+	use crate::sync::{Arc, Mutex};
+
+	use core::time::Duration;
 
 	use core::convert::TryInto;
 
@@ -6604,7 +7627,7 @@ mod tests {
 					channel_features: ChannelFeatures::empty(), node_features: NodeFeatures::empty(),
 					short_channel_id: 0, fee_msat: 225, cltv_expiry_delta: 0, maybe_announced_channel: true,
 				},
-			], blinded_tail: None }],
+			], blinded_tail: None, trampoline_hops: vec![] }],
 			route_params: None,
 		};
 
@@ -6626,7 +7649,7 @@ mod tests {
 					channel_features: ChannelFeatures::empty(), node_features: NodeFeatures::empty(),
 					short_channel_id: 0, fee_msat: 150, cltv_expiry_delta: 0, maybe_announced_channel: true,
 				},
-			], blinded_tail: None }, Path { hops: vec![
+			], blinded_tail: None, trampoline_hops: vec![] }, Path { hops: vec![
 				RouteHop {
 					pubkey: PublicKey::from_slice(&<Vec<u8>>::from_hex("02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619").unwrap()[..]).unwrap(),
 					channel_features: ChannelFeatures::empty(), node_features: NodeFeatures::empty(),
@@ -6637,7 +7660,7 @@ mod tests {
 					channel_features: ChannelFeatures::empty(), node_features: NodeFeatures::empty(),
 					short_channel_id: 0, fee_msat: 150, cltv_expiry_delta: 0, maybe_announced_channel: true,
 				},
-			], blinded_tail: None }],
+			], blinded_tail: None, trampoline_hops: vec![] }],
 			route_params: None,
 		};
 
@@ -6936,6 +7959,237 @@ mod tests {
 			(route.paths[1].hops[1].short_channel_id == 4 && route.paths[0].hops[1].short_channel_id == 13));
 	}
 
+	fn build_equivalent_paths_graph() -> (
+		Arc<NetworkGraph<Arc<ln_test_utils::TestLogger>>>, Arc<ln_test_utils::TestLogger>, PublicKey, Vec<PublicKey>
+	) {
+		build_equivalent_paths_graph_with_first_hop_max(250_000_000)
+	}
+
+	/// Like [`build_equivalent_paths_graph`], but with our channel to node 0 (scid 1) limited to
+	/// `first_hop_htlc_maximum_msat` rather than the second hops' 250_000_000.
+	fn build_equivalent_paths_graph_with_first_hop_max(first_hop_htlc_maximum_msat: u64) -> (
+		Arc<NetworkGraph<Arc<ln_test_utils::TestLogger>>>, Arc<ln_test_utils::TestLogger>, PublicKey, Vec<PublicKey>
+	) {
+		let (secp_ctx, network_graph, gossip_sync, _, logger) = build_graph();
+		let (our_privkey, our_id, privkeys, nodes) = get_nodes(&secp_ctx);
+
+		// Give us three equivalent paths to node2: us -> node 0 -> node2,
+		// us -> node 7 -> node 2 and us -> node 1 -> node 2.
+		update_channel(&gossip_sync, &secp_ctx, &our_privkey, UnsignedChannelUpdate {
+			chain_hash: ChainHash::using_genesis_block(Network::Testnet),
+			short_channel_id: 1,
+			timestamp: 2,
+			flags: 0,
+			cltv_expiry_delta: 0,
+			htlc_minimum_msat: 0,
+			htlc_maximum_msat: first_hop_htlc_maximum_msat,
+			fee_base_msat: 0,
+			fee_proportional_millionths: 0,
+			excess_data: Vec::new()
+		});
+		for (privkey_idx, scid) in [(0, 3), (1, 4), (7, 13)] {
+			update_channel(&gossip_sync, &secp_ctx, &privkeys[privkey_idx], UnsignedChannelUpdate {
+				chain_hash: ChainHash::using_genesis_block(Network::Testnet),
+				short_channel_id: scid,
+				timestamp: 2,
+				flags: 0,
+				cltv_expiry_delta: ((scid as u16) << 4) | 1,
+				htlc_minimum_msat: 0,
+				htlc_maximum_msat: 250_000_000,
+				fee_base_msat: 0,
+				fee_proportional_millionths: 0,
+				excess_data: Vec::new()
+			});
+		}
+		(network_graph, logger, our_id, nodes)
+	}
+
+	#[test]
+	fn respects_max_channel_share() {
+		let (network_graph, logger, our_id, nodes) = build_equivalent_paths_graph();
+		let scorer = ln_test_utils::TestScorer::new();
+		let config = UserConfig::default();
+		let keys_manager = ln_test_utils::TestKeysInterface::new(&[0u8; 32], Network::Testnet);
+		let random_seed_bytes = keys_manager.get_secure_random_bytes();
+
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+			.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+			.unwrap()
+			.with_max_channel_share_percent(40);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100_000_000);
+		let route = get_route(&our_id, &route_params, &network_graph.read_only(), None,
+			Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes).unwrap();
+		assert_eq!(route.get_total_amount(), 100_000_000);
+		assert!(route.paths.len() >= 3);
+
+		let mut amount_per_channel: HashMap<u64, u64> = HashMap::new();
+		for path in route.paths.iter() {
+			for hop in path.hops.iter() {
+				*amount_per_channel.entry(hop.short_channel_id).or_insert(0) += path.final_value_msat();
+			}
+		}
+		assert!(amount_per_channel.values().all(|amount| *amount <= 40_000_000));
+
+		// If the remaining channels can't carry the payment within the limit, we fail.
+		let payment_params = route_params.payment_params.clone().with_max_channel_share_percent(30);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100_000_000);
+		assert!(get_route(&our_id, &route_params, &network_graph.read_only(), None,
+			Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes).is_err());
+	}
+
+	#[test]
+	fn respects_path_disjointness() {
+		let (network_graph, logger, our_id, nodes) = build_equivalent_paths_graph();
+		let scorer = ln_test_utils::TestScorer::new();
+		let config = UserConfig::default();
+		let keys_manager = ln_test_utils::TestKeysInterface::new(&[0u8; 32], Network::Testnet);
+		let random_seed_bytes = keys_manager.get_secure_random_bytes();
+
+		for disjointness in [PathDisjointness::Channels, PathDisjointness::Nodes] {
+			let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+				.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+				.unwrap()
+				.with_path_disjointness(disjointness)
+				.with_max_channel_share_percent(50);
+			let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100_000_000);
+			let route = get_route(&our_id, &route_params, &network_graph.read_only(), None,
+				Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes).unwrap();
+			assert_eq!(route.get_total_amount(), 100_000_000);
+			assert!(route.paths.len() >= 2);
+
+			let mut seen_channels = HashSet::new();
+			let mut seen_nodes = HashSet::new();
+			for path in route.paths.iter() {
+				for hop in path.hops.iter() {
+					assert!(seen_channels.insert(hop.short_channel_id));
+				}
+				for hop in path.hops.iter().take(path.hops.len() - 1) {
+					assert!(seen_nodes.insert(hop.pubkey));
+				}
+			}
+		}
+
+		// With only three disjoint paths, each limited to a quarter of the amount, we fail.
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+			.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+			.unwrap()
+			.with_path_disjointness(PathDisjointness::Nodes)
+			.with_max_channel_share_percent(25);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 100_000_000);
+		assert!(get_route(&our_id, &route_params, &network_graph.read_only(), None,
+			Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes).is_err());
+	}
+
+	#[test]
+	fn finds_ranked_route_candidates() {
+		let (network_graph, logger, our_id, nodes) = build_equivalent_paths_graph();
+		let scorer = ln_test_utils::TestScorer::new();
+		let config = UserConfig::default();
+		let keys_manager = ln_test_utils::TestKeysInterface::new(&[0u8; 32], Network::Testnet);
+		let random_seed_bytes = keys_manager.get_secure_random_bytes();
+
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+			.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+			.unwrap();
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 10_000);
+		let candidates = find_route_candidates(&our_id, &route_params, &network_graph, None,
+			Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes, 3).unwrap();
+		assert_eq!(candidates.len(), 3);
+		for window in candidates.windows(2) {
+			assert!(window[0].cost_msat <= window[1].cost_msat);
+			assert_ne!(window[0].route.paths, window[1].route.paths);
+		}
+		for candidate in candidates.iter() {
+			assert_eq!(candidate.route.get_total_amount(), 10_000);
+			assert_eq!(candidate.route.route_params, Some(route_params.clone()));
+		}
+
+		let route = get_route(&our_id, &route_params, &network_graph.read_only(), None,
+			Arc::clone(&logger), &scorer, &Default::default(), &random_seed_bytes).unwrap();
+		let route_scids = route.paths[0].hops.iter().map(|hop| hop.short_channel_id).collect::<Vec<_>>();
+		let best_scids = candidates[0].route.paths[0].hops.iter().map(|hop| hop.short_channel_id).collect::<Vec<_>>();
+		assert_eq!(route_scids, best_scids);
+	}
+
+	#[test]
+	fn finds_min_cost_flow_route() {
+		// Lift the limit on our own channel to node 0 so that the flow is only shaped by the
+		// second hops' capacities and the scorer's liquidity bounds.
+		let (network_graph, logger, our_id, nodes) =
+			build_equivalent_paths_graph_with_first_hop_max(MAX_VALUE_MSAT);
+		let decay_params = ProbabilisticScoringDecayParameters::default();
+		let mut scorer = ProbabilisticScorer::new(decay_params, &*network_graph, Arc::clone(&logger));
+		let score_params = ProbabilisticScoringFeeParameters::default();
+		let config = UserConfig::default();
+		let keys_manager = ln_test_utils::TestKeysInterface::new(&[0u8; 32], Network::Testnet);
+		let random_seed_bytes = keys_manager.get_secure_random_bytes();
+
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+			.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+			.unwrap();
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 120_000_000);
+		let route = find_min_cost_flow_route(&our_id, &route_params, &network_graph, None,
+			Arc::clone(&logger), &scorer, &score_params, &InFlightHtlcs::new(), &random_seed_bytes).unwrap();
+		assert_eq!(route.get_total_amount(), 120_000_000);
+		assert_eq!(route.route_params, Some(route_params.clone()));
+
+		// The three paths are equivalent, so the payment is split evenly over them.
+		assert_eq!(route.paths.len(), 3);
+		for path in route.paths.iter() {
+			assert_eq!(path.hops.len(), 2);
+			assert_eq!(path.hops[1].pubkey, nodes[2]);
+			assert!(path.final_value_msat() > 38_000_000 && path.final_value_msat() < 42_000_000);
+		}
+
+		// Once we learn that channel 13 can't carry 30,000 sats, we send less over it.
+		let failed_path = route.paths.iter().find(|path| path.hops[1].short_channel_id == 13).unwrap();
+		let mut failed_path = failed_path.clone();
+		failed_path.hops[1].fee_msat = 30_000_000;
+		scorer.payment_path_failed(&failed_path, 13, Duration::ZERO);
+		let route = find_min_cost_flow_route(&our_id, &route_params, &network_graph, None,
+			Arc::clone(&logger), &scorer, &score_params, &InFlightHtlcs::new(), &random_seed_bytes).unwrap();
+		assert_eq!(route.get_total_amount(), 120_000_000);
+		for path in route.paths.iter() {
+			if path.hops[1].short_channel_id == 13 {
+				assert!(path.final_value_msat() < 30_000_000);
+			} else {
+				assert!(path.final_value_msat() > 40_000_000);
+			}
+		}
+
+		// We can't send more than the liquidity available after saturation limits.
+		let route_params = RouteParameters::from_payment_params_and_value(
+			route_params.payment_params.clone(), 200_000_000);
+		assert!(find_min_cost_flow_route(&our_id, &route_params, &network_graph, None,
+			Arc::clone(&logger), &scorer, &score_params, &InFlightHtlcs::new(), &random_seed_bytes).is_err());
+	}
+
+	#[test]
+	fn min_cost_flow_router_falls_back_to_find_route() {
+		let (network_graph, logger, our_id, nodes) =
+			build_equivalent_paths_graph_with_first_hop_max(MAX_VALUE_MSAT);
+		let scorer = Mutex::new(ProbabilisticScorer::new(
+			ProbabilisticScoringDecayParameters::default(), Arc::clone(&network_graph), Arc::clone(&logger)));
+		let router = MinCostFlowRouter::new(Arc::clone(&network_graph), Arc::clone(&logger), [42; 32],
+			&scorer, ProbabilisticScoringFeeParameters::default(), IgnoringMetricsRecorder {});
+		let config = UserConfig::default();
+
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42)
+			.with_bolt11_features(channelmanager::provided_bolt11_invoice_features(&config))
+			.unwrap();
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 120_000_000);
+		let route = router.find_route(&our_id, &route_params, None, InFlightHtlcs::new()).unwrap();
+		assert_eq!(route.paths.len(), 3);
+		assert_eq!(route.get_total_amount(), 120_000_000);
+
+		// Without multi-path payment support, we fall back to `find_route`.
+		let payment_params = PaymentParameters::from_node_id(nodes[2], 42);
+		let route_params = RouteParameters::from_payment_params_and_value(payment_params, 10_000);
+		let route = router.find_route(&our_id, &route_params, None, InFlightHtlcs::new()).unwrap();
+		assert_eq!(route.paths.len(), 1);
+		assert_eq!(route.get_total_amount(), 10_000);
+	}
+
 	#[cfg(not(feature = "no-std"))]
 	pub(super) fn random_init_seed() -> u64 {
 		// Because the default HashMap in std pulls OS randomness, we can use it as a (bad) RNG.
@@ -7254,7 +8508,7 @@ mod tests {
 				blinding_point: blinded_path_1.blinding_point,
 				excess_final_cltv_expiry_delta: 40,
 				final_value_msat: 100,
-			})}, Path {
+			}), trampoline_hops: vec![] }, Path {
 			hops: vec![RouteHop {
 				pubkey: ln_test_utils::pubkey(51),
 				node_features: NodeFeatures::empty(),
@@ -7263,7 +8517,7 @@ mod tests {
 				fee_msat: 100,
 				cltv_expiry_delta: 0,
 				maybe_announced_channel: true,
-			}], blinded_tail: None }],
+			}], blinded_tail: None, trampoline_hops: vec![] }],
 			route_params: None,
 		};
 		let encoded_route = route.encode();
@@ -7284,6 +8538,46 @@ mod tests {
 		assert_eq!(decoded_route.paths[1].blinded_tail, route.paths[1].blinded_tail);
 	}
 
+	#[test]
+	fn trampoline_route_ser() {
+		// (De)serialize a Route with a trampoline path and a regular path.
+		let route_hop = |pubkey, short_channel_id, fee_msat| RouteHop {
+			pubkey, node_features: NodeFeatures::empty(), short_channel_id,
+			channel_features: ChannelFeatures::empty(), fee_msat, cltv_expiry_delta: 72,
+			maybe_announced_channel: true,
+		};
+		let mut route = Route { paths: vec![Path {
+			hops: vec![route_hop(ln_test_utils::pubkey(50), 42, 1_000)],
+			blinded_tail: None,
+			trampoline_hops: vec![TrampolineHop {
+				pubkey: ln_test_utils::pubkey(50),
+				node_features: NodeFeatures::empty(),
+				fee_msat: 1_000,
+				cltv_expiry_delta: 576,
+			}, TrampolineHop {
+				pubkey: ln_test_utils::pubkey(51),
+				node_features: NodeFeatures::empty(),
+				fee_msat: 100_000,
+				cltv_expiry_delta: 40,
+			}],
+		}, Path {
+			hops: vec![route_hop(ln_test_utils::pubkey(51), 43, 100)],
+			blinded_tail: None,
+			trampoline_hops: vec![],
+		}], route_params: None };
+		let encoded_route = route.encode();
+		let decoded_route: Route = Readable::read(&mut Cursor::new(&encoded_route[..])).unwrap();
+		assert_eq!(decoded_route, route);
+		assert_eq!(decoded_route.paths[0].final_value_msat(), 100_000);
+		assert_eq!(decoded_route.paths[0].final_cltv_expiry_delta(), Some(40));
+
+		// Routes without any trampoline paths don't write the trampoline hops at all.
+		route.paths.remove(0);
+		let encoded_route = route.encode();
+		let decoded_route: Route = Readable::read(&mut Cursor::new(&encoded_route[..])).unwrap();
+		assert_eq!(decoded_route, route);
+	}
+
 	#[test]
 	fn blinded_path_inflight_processing() {
 		// Ensure we'll score the channel that's inbound to a blinded path's introduction node, and
@@ -7319,6 +8613,7 @@ mod tests {
 				excess_final_cltv_expiry_delta: 0,
 				final_value_msat: 200,
 			}),
+			trampoline_hops: vec![],
 		};
 		inflight_htlcs.process_path(&path, ln_test_utils::pubkey(44));
 		assert_eq!(*inflight_htlcs.0.get(&(42, true)).unwrap(), 301);
@@ -7362,6 +8657,7 @@ mod tests {
 				excess_final_cltv_expiry_delta: 0,
 				final_value_msat: 200,
 			}),
+			trampoline_hops: vec![],
 		}], route_params: None};
 
 		let payment_params = PaymentParameters::from_node_id(ln_test_utils::pubkey(47), 18);
@@ -8613,6 +9909,26 @@ pub mod benches {
 			"generate_large_mpp_routes_with_nonlinear_probabilistic_scorer");
 	}
 
+	pub fn generate_mpp_routes_with_min_cost_flow(bench: &mut Criterion) {
+		let logger = TestLogger::new();
+		let network_graph = bench_utils::read_network_graph(&logger).unwrap();
+		let params = ProbabilisticScoringFeeParameters::default();
+		let scorer = ProbabilisticScorer::new(ProbabilisticScoringDecayParameters::default(), &network_graph, &logger);
+		generate_min_cost_flow_routes(bench, &network_graph, scorer, &params,
+			channelmanager::provided_bolt11_invoice_features(&UserConfig::default()), 0,
+			"generate_mpp_routes_with_min_cost_flow");
+	}
+
+	pub fn generate_large_mpp_routes_with_min_cost_flow(bench: &mut Criterion) {
+		let logger = TestLogger::new();
+		let network_graph = bench_utils::read_network_graph(&logger).unwrap();
+		let params = ProbabilisticScoringFeeParameters::default();
+		let scorer = ProbabilisticScorer::new(ProbabilisticScoringDecayParameters::default(), &network_graph, &logger);
+		generate_min_cost_flow_routes(bench, &network_graph, scorer, &params,
+			channelmanager::provided_bolt11_invoice_features(&UserConfig::default()), 100_000_000,
+			"generate_large_mpp_routes_with_min_cost_flow");
+	}
+
 	fn generate_routes<S: ScoreLookUp + ScoreUpdate>(
 		bench: &mut Criterion, graph: &NetworkGraph<&TestLogger>, mut scorer: S,
 		score_params: &S::ScoreParams, features: Bolt11InvoiceFeatures, starting_amount: u64,
@@ -8635,4 +9951,38 @@ pub mod benches {
 			idx += 1;
 		}));
 	}
+
+	/// Benchmarks [`find_min_cost_flow_route`] over the same (source, destination) pairs used by
+	/// [`generate_routes`], allowing to compare it against [`get_route`].
+	///
+	/// Pairs which [`find_min_cost_flow_route`] fails to route, and which [`MinCostFlowRouter`] would
+	/// thus route using [`find_route`] instead, are dropped before benchmarking.
+	fn generate_min_cost_flow_routes<'a>(
+		bench: &mut Criterion, graph: &'a NetworkGraph<&'a TestLogger>,
+		mut scorer: ProbabilisticScorer<&'a NetworkGraph<&'a TestLogger>, &'a TestLogger>,
+		score_params: &ProbabilisticScoringFeeParameters, features: Bolt11InvoiceFeatures,
+		starting_amount: u64, bench_name: &'static str,
+	) {
+		let payer = bench_utils::payer_pubkey();
+		let keys_manager = KeysManager::new(&[0u8; 32], 42, 42);
+		let random_seed_bytes = keys_manager.get_secure_random_bytes();
+		let inflight_htlcs = InFlightHtlcs::new();
+
+		let mut route_endpoints = bench_utils::generate_test_routes(graph, &mut scorer, score_params, features, 0xdeadbeef, starting_amount, 50);
+		route_endpoints.retain(|(first_hop, params, amt)| {
+			let route_params = RouteParameters::from_payment_params_and_value(params.clone(), *amt);
+			find_min_cost_flow_route(&payer, &route_params, graph, Some(&[first_hop]),
+				&DummyLogger{}, &scorer, score_params, &inflight_htlcs, &random_seed_bytes).is_ok()
+		});
+		assert!(!route_endpoints.is_empty());
+
+		let mut idx = 0;
+		bench.bench_function(bench_name, |b| b.iter(|| {
+			let (first_hop, params, amt) = &route_endpoints[idx % route_endpoints.len()];
+			let route_params = RouteParameters::from_payment_params_and_value(params.clone(), *amt);
+			assert!(find_min_cost_flow_route(&payer, &route_params, graph, Some(&[first_hop]),
+				&DummyLogger{}, &scorer, score_params, &inflight_htlcs, &random_seed_bytes).is_ok());
+			idx += 1;
+		}));
+	}
 }
@@ -900,6 +900,20 @@ impl<G: Deref<Target = NetworkGraph<L>>, L: Deref> ProbabilisticScorer<G, L> whe
 		None
 	}
 
+	/// Returns the estimated minimum and maximum liquidity available for sending a payment over the
+	/// channel with `scid` from `source` to `target`, given the channel's `capacity_msat`.
+	///
+	/// Unlike [`Self::estimated_channel_liquidity_range`], this doesn't look the channel up in the
+	/// network graph, and thus may be called while holding a lock on it.
+	pub(super) fn liquidity_bounds_msat(
+		&self, scid: u64, source: &NodeId, target: &NodeId, capacity_msat: u64
+	) -> Option<(u64, u64)> {
+		self.channel_liquidities.get(&scid).map(|liq| {
+			let dir_liq = liq.as_directed(source, target, capacity_msat);
+			(dir_liq.min_liquidity_msat(), dir_liq.max_liquidity_msat())
+		})
+	}
+
 	/// Query the historical estimated minimum and maximum liquidity available for sending a
 	/// payment over the channel with `scid` towards the given `target` node.
 	///
@@ -975,6 +989,59 @@ impl<G: Deref<Target = NetworkGraph<L>>, L: Deref> ProbabilisticScorer<G, L> whe
 		}
 		None
 	}
+
+	/// Exports what we've learned about the liquidity of each channel as a [`ScorerSnapshot`].
+	///
+	/// Unlike the [`Writeable`] encoding of the scorer, the snapshot can be inspected, merged with
+	/// snapshots from other nodes via [`ScorerSnapshot::merge`], and imported into another scorer
+	/// via [`Self::import_snapshot`].
+	pub fn export_snapshot(&self) -> ScorerSnapshot {
+		let mut channels = self.channel_liquidities.iter()
+			.map(|(scid, liquidity)| liquidity.to_snapshot(*scid))
+			.collect::<Vec<_>>();
+		channels.sort_unstable_by_key(|channel| channel.short_channel_id);
+		ScorerSnapshot { channels }
+	}
+
+	/// Imports the channel liquidity information from the given [`ScorerSnapshot`], replacing
+	/// anything we've learned about the channels included in it.
+	///
+	/// To combine the snapshot with what we've learned ourselves instead, merge it with the
+	/// result of [`Self::export_snapshot`] before importing it.
+	///
+	/// Channels which aren't in the network graph, or whose liquidity offsets add up to more than
+	/// the channel's effective capacity, are skipped. Thus, the network graph should be synced
+	/// before importing a snapshot.
+	pub fn import_snapshot(&mut self, snapshot: &ScorerSnapshot) {
+		let graph = self.network_graph.read_only();
+		let mut imported_channels = 0;
+		for channel in snapshot.channels.iter() {
+			let capacity_msat = graph.channels().get(&channel.short_channel_id).and_then(|chan| {
+				[&chan.node_one, &chan.node_two].iter()
+					.filter_map(|source| chan.as_directed_from(source))
+					.map(|(directed_info, _)| directed_info.effective_capacity().as_msat())
+					.max()
+			});
+			let offsets_msat = channel.min_liquidity_offset_msat
+				.checked_add(channel.max_liquidity_offset_msat);
+			match (capacity_msat, offsets_msat) {
+				(None, _) => log_debug!(self.logger,
+					"Skipping liquidity information for channel {} as it is not in the network graph",
+					channel.short_channel_id),
+				(Some(capacity_msat), Some(offsets_msat)) if offsets_msat <= capacity_msat => {
+					self.channel_liquidities.insert(
+						channel.short_channel_id, ChannelLiquidity::from_snapshot(channel)
+					);
+					imported_channels += 1;
+				},
+				(Some(capacity_msat), _) => log_debug!(self.logger,
+					"Skipping liquidity information for channel {} as its offsets exceed its capacity of {} msat",
+					channel.short_channel_id, capacity_msat),
+			}
+		}
+		log_debug!(self.logger, "Imported liquidity information for {} of {} channels",
+			imported_channels, snapshot.channels.len());
+	}
 }
 
 impl ChannelLiquidity {
@@ -1055,6 +1122,59 @@ impl ChannelLiquidity {
 			0
 		}
 	}
+
+	/// Decays the liquidity bounds and their history from when they were last updated until
+	/// `duration_since_epoch`.
+	fn decay(&mut self, duration_since_epoch: Duration, decay_params: ProbabilisticScoringDecayParameters) {
+		self.min_liquidity_offset_msat =
+			self.decayed_offset(self.min_liquidity_offset_msat, duration_since_epoch, decay_params);
+		self.max_liquidity_offset_msat =
+			self.decayed_offset(self.max_liquidity_offset_msat, duration_since_epoch, decay_params);
+		self.last_updated = duration_since_epoch;
+
+		let elapsed_time =
+			duration_since_epoch.saturating_sub(self.offset_history_last_updated);
+		if elapsed_time > decay_params.historical_no_updates_half_life {
+			let half_life = decay_params.historical_no_updates_half_life.as_secs_f64();
+			if half_life != 0.0 {
+				let divisor = powf64(2048.0, elapsed_time.as_secs_f64() / half_life) as u64;
+				for bucket in self.min_liquidity_offset_history.buckets.iter_mut() {
+					*bucket = ((*bucket as u64) * 1024 / divisor) as u16;
+				}
+				for bucket in self.max_liquidity_offset_history.buckets.iter_mut() {
+					*bucket = ((*bucket as u64) * 1024 / divisor) as u16;
+				}
+				self.offset_history_last_updated = duration_since_epoch;
+			}
+		}
+	}
+
+	fn from_snapshot(channel: &ChannelLiquiditySnapshot) -> Self {
+		Self {
+			min_liquidity_offset_msat: channel.min_liquidity_offset_msat,
+			max_liquidity_offset_msat: channel.max_liquidity_offset_msat,
+			min_liquidity_offset_history: HistoricalBucketRangeTracker {
+				buckets: channel.min_liquidity_offset_history,
+			},
+			max_liquidity_offset_history: HistoricalBucketRangeTracker {
+				buckets: channel.max_liquidity_offset_history,
+			},
+			last_updated: channel.last_updated,
+			offset_history_last_updated: channel.offset_history_last_updated,
+		}
+	}
+
+	fn to_snapshot(&self, short_channel_id: u64) -> ChannelLiquiditySnapshot {
+		ChannelLiquiditySnapshot {
+			short_channel_id,
+			min_liquidity_offset_msat: self.min_liquidity_offset_msat,
+			max_liquidity_offset_msat: self.max_liquidity_offset_msat,
+			min_liquidity_offset_history: self.min_liquidity_offset_history.buckets,
+			max_liquidity_offset_history: self.max_liquidity_offset_history.buckets,
+			last_updated: self.last_updated,
+			offset_history_last_updated: self.offset_history_last_updated,
+		}
+	}
 }
 
 /// Bounds `-log10` to avoid excessive liquidity penalties for payments with low success
@@ -1067,7 +1187,35 @@ const PRECISION_LOWER_BOUND_DENOMINATOR: u64 = approx::LOWER_BITS_BOUND;
 
 /// The divisor used when computing the amount penalty.
 const AMOUNT_PENALTY_DIVISOR: u64 = 1 << 20;
-const BASE_AMOUNT_PENALTY_DIVISOR: u64 = 1 << 30;
+pub(super) const BASE_AMOUNT_PENALTY_DIVISOR: u64 = 1 << 30;
+
+/// Computes the liquidity penalty for sending `amount_msat` over a channel whose liquidity is
+/// known to lie between `min_liquidity_msat` and `max_liquidity_msat`, assuming it to be uniformly
+/// distributed within these bounds.
+///
+/// Unlike the penalty applied by [`ProbabilisticScorer`], the success probability isn't bounded
+/// from below, keeping the penalty convex in `amount_msat` up to `max_liquidity_msat`, as needed
+/// for min-cost flow routing. Returns `u64::max_value()` for amounts above `max_liquidity_msat`.
+pub(super) fn uncertainty_penalty_msat(
+	amount_msat: u64, min_liquidity_msat: u64, max_liquidity_msat: u64,
+	score_params: &ProbabilisticScoringFeeParameters,
+) -> u64 {
+	if amount_msat <= min_liquidity_msat {
+		return 0;
+	} else if amount_msat > max_liquidity_msat {
+		return u64::max_value();
+	}
+	let numerator = max_liquidity_msat - amount_msat + 1;
+	let denominator = max_liquidity_msat - min_liquidity_msat + 1;
+	let negative_log10_times_2048 = approx::negative_log10_times_2048(numerator, denominator);
+	let liquidity_penalty_msat = negative_log10_times_2048
+		.saturating_mul(score_params.liquidity_penalty_multiplier_msat) / 2048;
+	let amount_penalty_msat = (negative_log10_times_2048 as u128
+		* score_params.liquidity_penalty_amount_multiplier_msat as u128
+		* amount_msat as u128 / 2048 / AMOUNT_PENALTY_DIVISOR as u128)
+		.try_into().unwrap_or(u64::max_value());
+	liquidity_penalty_msat.saturating_add(amount_penalty_msat)
+}
 
 /// Raises three `f64`s to the 3rd power, without `powi` because it requires `std` (dunno why).
 #[inline(always)]
@@ -1449,27 +1597,7 @@ impl<G: Deref<Target = NetworkGraph<L>>, L: Deref> ScoreUpdate for Probabilistic
 	fn time_passed(&mut self, duration_since_epoch: Duration) {
 		let decay_params = self.decay_params;
 		self.channel_liquidities.retain(|_scid, liquidity| {
-			liquidity.min_liquidity_offset_msat =
-				liquidity.decayed_offset(liquidity.min_liquidity_offset_msat, duration_since_epoch, decay_params);
-			liquidity.max_liquidity_offset_msat =
-				liquidity.decayed_offset(liquidity.max_liquidity_offset_msat, duration_since_epoch, decay_params);
-			liquidity.last_updated = duration_since_epoch;
-
-			let elapsed_time =
-				duration_since_epoch.saturating_sub(liquidity.offset_history_last_updated);
-			if elapsed_time > decay_params.historical_no_updates_half_life {
-				let half_life = decay_params.historical_no_updates_half_life.as_secs_f64();
-				if half_life != 0.0 {
-					let divisor = powf64(2048.0, elapsed_time.as_secs_f64() / half_life) as u64;
-					for bucket in liquidity.min_liquidity_offset_history.buckets.iter_mut() {
-						*bucket = ((*bucket as u64) * 1024 / divisor) as u16;
-					}
-					for bucket in liquidity.max_liquidity_offset_history.buckets.iter_mut() {
-						*bucket = ((*bucket as u64) * 1024 / divisor) as u16;
-					}
-					liquidity.offset_history_last_updated = duration_since_epoch;
-				}
-			}
+			liquidity.decay(duration_since_epoch, decay_params);
 			liquidity.min_liquidity_offset_msat != 0 || liquidity.max_liquidity_offset_msat != 0 ||
 				liquidity.min_liquidity_offset_history.buckets != [0; 32] ||
 				liquidity.max_liquidity_offset_history.buckets != [0; 32]
@@ -2056,6 +2184,165 @@ mod bucketed_history {
 }
 use bucketed_history::{LegacyHistoricalBucketRangeTracker, HistoricalBucketRangeTracker, HistoricalMinMaxBuckets};
 
+/// A portable snapshot of the channel liquidity information learned by a [`ProbabilisticScorer`].
+///
+/// Snapshots are created via [`ProbabilisticScorer::export_snapshot`] and may be imported into
+/// another scorer via [`ProbabilisticScorer::import_snapshot`], e.g., to bootstrap a new node with
+/// what other nodes have learned. Snapshots exported by several nodes may be combined using
+/// [`ScorerSnapshot::merge`].
+///
+/// The serialization of a snapshot is versioned and, unlike that of the [`ProbabilisticScorer`]
+/// itself, its contents are public, allowing them to be converted to other formats.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScorerSnapshot {
+	/// The liquidity information for each channel, ordered by short channel id.
+	pub channels: Vec<ChannelLiquiditySnapshot>,
+}
+
+/// The liquidity information learned about a channel, as part of a [`ScorerSnapshot`].
+///
+/// All amounts are given in the direction from the channel's `node_one` to its `node_two`, i.e.,
+/// from the lesser to the greater [`NodeId`], and relative to the channel's capacity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelLiquiditySnapshot {
+	/// The short channel id of the channel.
+	pub short_channel_id: u64,
+	/// The lower bound of the channel's liquidity, as an offset from zero.
+	pub min_liquidity_offset_msat: u64,
+	/// The upper bound of the channel's liquidity, as an offset from the channel's capacity.
+	pub max_liquidity_offset_msat: u64,
+	/// The history of the lower liquidity bound, in 32 buckets as described in
+	/// [`ProbabilisticScorer::historical_estimated_channel_liquidity_probabilities`], though
+	/// relative to the offset from zero.
+	pub min_liquidity_offset_history: [u16; 32],
+	/// The history of the upper liquidity bound, in 32 buckets as described in
+	/// [`ProbabilisticScorer::historical_estimated_channel_liquidity_probabilities`], though
+	/// relative to the offset from the channel's capacity.
+	pub max_liquidity_offset_history: [u16; 32],
+	/// The time either liquidity bound was last updated, as a duration since the UNIX epoch.
+	pub last_updated: Duration,
+	/// The time the liquidity history was last updated, as a duration since the UNIX epoch.
+	pub offset_history_last_updated: Duration,
+}
+
+impl ScorerSnapshot {
+	/// Merges the given snapshots, e.g., as exported by different nodes, into a single snapshot.
+	///
+	/// Each snapshot is given a weight reflecting how much we trust it, relative to the others. For
+	/// each channel, the liquidity bounds and their histories are first decayed to
+	/// `duration_since_epoch` using the given `decay_params`, as done on
+	/// [`ScoreUpdate::time_passed`], such that information learned at different times is
+	/// comparable. They are then averaged across the snapshots including the channel, weighted by
+	/// their trust. Snapshots with a weight of zero are ignored.
+	pub fn merge(
+		snapshots: &[(&ScorerSnapshot, u32)], decay_params: ProbabilisticScoringDecayParameters,
+		duration_since_epoch: Duration,
+	) -> ScorerSnapshot {
+		struct MergedChannel {
+			total_weight: u64,
+			min_liquidity_offset_msat: u128,
+			max_liquidity_offset_msat: u128,
+			min_liquidity_offset_history: [u64; 32],
+			max_liquidity_offset_history: [u64; 32],
+			last_updated: Duration,
+			offset_history_last_updated: Duration,
+		}
+
+		let mut merged_channels: HashMap<u64, MergedChannel> = HashMap::new();
+		for (snapshot, weight) in snapshots.iter().filter(|(_, weight)| *weight > 0) {
+			let weight = *weight as u64;
+			for channel in snapshot.channels.iter() {
+				let mut liquidity = ChannelLiquidity::from_snapshot(channel);
+				liquidity.decay(duration_since_epoch, decay_params);
+				let channel = liquidity.to_snapshot(channel.short_channel_id);
+				let merged = merged_channels.entry(channel.short_channel_id).or_insert(MergedChannel {
+					total_weight: 0,
+					min_liquidity_offset_msat: 0,
+					max_liquidity_offset_msat: 0,
+					min_liquidity_offset_history: [0; 32],
+					max_liquidity_offset_history: [0; 32],
+					last_updated: Duration::ZERO,
+					offset_history_last_updated: Duration::ZERO,
+				});
+				merged.total_weight += weight;
+				merged.min_liquidity_offset_msat += channel.min_liquidity_offset_msat as u128 * weight as u128;
+				merged.max_liquidity_offset_msat += channel.max_liquidity_offset_msat as u128 * weight as u128;
+				for (merged_bucket, bucket) in merged.min_liquidity_offset_history.iter_mut()
+					.zip(channel.min_liquidity_offset_history.iter())
+				{
+					*merged_bucket += *bucket as u64 * weight;
+				}
+				for (merged_bucket, bucket) in merged.max_liquidity_offset_history.iter_mut()
+					.zip(channel.max_liquidity_offset_history.iter())
+				{
+					*merged_bucket += *bucket as u64 * weight;
+				}
+				merged.last_updated = cmp::max(merged.last_updated, channel.last_updated);
+				merged.offset_history_last_updated =
+					cmp::max(merged.offset_history_last_updated, channel.offset_history_last_updated);
+			}
+		}
+
+		let mut channels = merged_channels.into_iter()
+			.map(|(short_channel_id, merged)| {
+				let total_weight = merged.total_weight;
+				let average_buckets = |buckets: [u64; 32]| {
+					let mut averaged = [0u16; 32];
+					for (averaged_bucket, bucket) in averaged.iter_mut().zip(buckets.iter()) {
+						*averaged_bucket = (*bucket / total_weight) as u16;
+					}
+					averaged
+				};
+				ChannelLiquiditySnapshot {
+					short_channel_id,
+					min_liquidity_offset_msat: (merged.min_liquidity_offset_msat / total_weight as u128) as u64,
+					max_liquidity_offset_msat: (merged.max_liquidity_offset_msat / total_weight as u128) as u64,
+					min_liquidity_offset_history: average_buckets(merged.min_liquidity_offset_history),
+					max_liquidity_offset_history: average_buckets(merged.max_liquidity_offset_history),
+					last_updated: merged.last_updated,
+					offset_history_last_updated: merged.offset_history_last_updated,
+				}
+			})
+			.collect::<Vec<_>>();
+		channels.sort_unstable_by_key(|channel| channel.short_channel_id);
+		ScorerSnapshot { channels }
+	}
+}
+
+const SNAPSHOT_SERIALIZATION_VERSION: u8 = 1;
+const MIN_SNAPSHOT_SERIALIZATION_VERSION: u8 = 1;
+
+impl Writeable for ScorerSnapshot {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		write_ver_prefix!(w, SNAPSHOT_SERIALIZATION_VERSION, MIN_SNAPSHOT_SERIALIZATION_VERSION);
+		write_tlv_fields!(w, {
+			(0, self.channels, required_vec),
+		});
+		Ok(())
+	}
+}
+
+impl Readable for ScorerSnapshot {
+	fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let _ver = read_ver_prefix!(r, SNAPSHOT_SERIALIZATION_VERSION);
+		let mut channels = Vec::new();
+		read_tlv_fields!(r, {
+			(0, channels, required_vec),
+		});
+		Ok(Self { channels })
+	}
+}
+
+impl_writeable_tlv_based!(ChannelLiquiditySnapshot, {
+	(0, short_channel_id, required),
+	(2, min_liquidity_offset_msat, required),
+	(4, max_liquidity_offset_msat, required),
+	(6, min_liquidity_offset_history, required),
+	(8, max_liquidity_offset_history, required),
+	(10, last_updated, required),
+	(12, offset_history_last_updated, required),
+});
+
 impl<G: Deref<Target = NetworkGraph<L>>, L: Deref> Writeable for ProbabilisticScorer<G, L> where L::Target: Logger {
 	#[inline]
 	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
@@ -2152,7 +2439,7 @@ impl Readable for ChannelLiquidity {
 
 #[cfg(test)]
 mod tests {
-	use super::{ChannelLiquidity, HistoricalBucketRangeTracker, ProbabilisticScoringFeeParameters, ProbabilisticScoringDecayParameters, ProbabilisticScorer};
+	use super::{ChannelLiquidity, ChannelLiquiditySnapshot, HistoricalBucketRangeTracker, ProbabilisticScoringFeeParameters, ProbabilisticScoringDecayParameters, ProbabilisticScorer, ScorerSnapshot};
 	use crate::blinded_path::{BlindedHop, BlindedPath};
 	use crate::util::config::UserConfig;
 
@@ -2161,7 +2448,7 @@ mod tests {
 	use crate::routing::gossip::{EffectiveCapacity, NetworkGraph, NodeId};
 	use crate::routing::router::{BlindedTail, Path, RouteHop, CandidateRouteHop, PublicHopCandidate};
 	use crate::routing::scoring::{ChannelUsage, ScoreLookUp, ScoreUpdate};
-	use crate::util::ser::{ReadableArgs, Writeable};
+	use crate::util::ser::{Readable, ReadableArgs, Writeable};
 	use crate::util::test_utils::{self, TestLogger};
 
 	use bitcoin::blockdata::constants::ChainHash;
@@ -2308,7 +2595,7 @@ mod tests {
 				path_hop(source_pubkey(), 41, 1),
 				path_hop(target_pubkey(), 42, 2),
 				path_hop(recipient_pubkey(), 43, amount_msat),
-			], blinded_tail: None,
+			], blinded_tail: None, trampoline_hops: vec![],
 		}
 	}
 
@@ -2789,7 +3076,7 @@ mod tests {
 		});
 		assert_eq!(scorer.channel_penalty_msat(&candidate, usage, &params), 128);
 
-		scorer.payment_path_failed(&Path { hops: path, blinded_tail: None }, 43, Duration::ZERO);
+		scorer.payment_path_failed(&Path { hops: path, blinded_tail: None, trampoline_hops: vec![] }, 43, Duration::ZERO);
 
 		let channel = network_graph.read_only().channel(42).unwrap().to_owned();
 		let (info, _) = channel.as_directed_from(&node_a).unwrap();
@@ -3105,6 +3392,82 @@ mod tests {
 		do_decays_persisted_liquidity_bounds(true);
 	}
 
+	#[test]
+	fn exports_and_imports_snapshots() {
+		let logger = TestLogger::new();
+		let network_graph = network_graph(&logger);
+		let decay_params = ProbabilisticScoringDecayParameters::default();
+		let mut scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		let target = target_node_id();
+
+		scorer.payment_path_failed(&payment_path_for_amount(500), 42, Duration::ZERO);
+		scorer.payment_path_successful(&payment_path_for_amount(200), Duration::from_secs(1));
+		let snapshot = scorer.export_snapshot();
+		assert_eq!(snapshot.channels.len(), 2);
+		assert!(snapshot.channels.windows(2).all(|channels| channels[0].short_channel_id < channels[1].short_channel_id));
+
+		let mut serialized_snapshot = Vec::new();
+		snapshot.write(&mut serialized_snapshot).unwrap();
+		let deserialized_snapshot = ScorerSnapshot::read(&mut io::Cursor::new(&serialized_snapshot)).unwrap();
+		assert_eq!(deserialized_snapshot, snapshot);
+
+		let mut imported_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		assert_eq!(imported_scorer.estimated_channel_liquidity_range(42, &target), None);
+		imported_scorer.import_snapshot(&deserialized_snapshot);
+		assert_eq!(imported_scorer.export_snapshot(), snapshot);
+		assert_eq!(imported_scorer.estimated_channel_liquidity_range(42, &target),
+			scorer.estimated_channel_liquidity_range(42, &target));
+		assert_eq!(imported_scorer.historical_estimated_channel_liquidity_probabilities(42, &target),
+			scorer.historical_estimated_channel_liquidity_probabilities(42, &target));
+
+		// Channels which aren't in the network graph or whose offsets exceed their capacity are
+		// skipped.
+		let mut invalid_snapshot = snapshot.clone();
+		invalid_snapshot.channels[0].min_liquidity_offset_msat = 600;
+		invalid_snapshot.channels[0].max_liquidity_offset_msat = 600;
+		invalid_snapshot.channels[1].short_channel_id = 44;
+		let mut imported_scorer = ProbabilisticScorer::new(decay_params, &network_graph, &logger);
+		imported_scorer.import_snapshot(&invalid_snapshot);
+		assert_eq!(imported_scorer.export_snapshot(), ScorerSnapshot::default());
+	}
+
+	#[test]
+	fn merges_snapshots() {
+		let channel = |short_channel_id, min_liquidity_offset_msat, max_liquidity_offset_msat, bucket, last_updated, offset_history_last_updated| {
+			let mut min_liquidity_offset_history = [0; 32];
+			min_liquidity_offset_history[0] = bucket;
+			ChannelLiquiditySnapshot {
+				short_channel_id,
+				min_liquidity_offset_msat,
+				max_liquidity_offset_msat,
+				min_liquidity_offset_history,
+				max_liquidity_offset_history: [0; 32],
+				last_updated: Duration::from_secs(last_updated),
+				offset_history_last_updated: Duration::from_secs(offset_history_last_updated),
+			}
+		};
+		let trusted = ScorerSnapshot { channels: vec![channel(41, 100, 300, 64, 10, 10), channel(42, 0, 0, 0, 10, 10)] };
+		let untrusted = ScorerSnapshot { channels: vec![channel(42, 400, 800, 32, 20, 20), channel(43, 50, 50, 32, 30, 30)] };
+		let ignored = ScorerSnapshot { channels: vec![channel(41, 1_000, 1_000, 0, 40, 40), channel(44, 0, 0, 0, 40, 40)] };
+		let decay_params = ProbabilisticScoringDecayParameters {
+			liquidity_offset_half_life: Duration::from_secs(10),
+			..ProbabilisticScoringDecayParameters::default()
+		};
+
+		let merged = ScorerSnapshot::merge(
+			&[(&trusted, 3), (&untrusted, 1), (&ignored, 0)], decay_params, Duration::from_secs(30));
+		assert_eq!(merged.channels, vec![
+			// Channels only included in a single snapshot are only decayed.
+			channel(41, 25, 75, 64, 30, 10),
+			// Otherwise, they are decayed to a common time before being averaged by weight.
+			channel(42, 50, 100, 8, 30, 20),
+			channel(43, 50, 50, 32, 30, 30),
+		]);
+
+		assert_eq!(ScorerSnapshot::merge(&[(&trusted, 1)], decay_params, Duration::from_secs(10)), trusted);
+		assert_eq!(ScorerSnapshot::merge(&[], decay_params, Duration::from_secs(10)), ScorerSnapshot::default());
+	}
+
 	#[test]
 	fn scores_realistic_payments() {
 		// Shows the scores of "realistic" sends of 100k sats over channels of 1-10m sats (with a
@@ -3486,7 +3849,7 @@ mod tests {
 			path_hop(source_pubkey(), 42, 1),
 			path_hop(sender_pubkey(), 41, 0),
 		];
-		scorer.payment_path_failed(&Path { hops: path, blinded_tail: None }, 42, Duration::from_secs(10 * (16 + 60 * 60)));
+		scorer.payment_path_failed(&Path { hops: path, blinded_tail: None, trampoline_hops: vec![] }, 42, Duration::from_secs(10 * (16 + 60 * 60)));
 	}
 
 	#[test]
@@ -3713,7 +4076,8 @@ pub mod benches {
 					cltv_expiry_delta: 42,
 					maybe_announced_channel: true,
 				}],
-				blinded_tail: None
+				blinded_tail: None,
+				trampoline_hops: vec![]
 			};
 			seed = seed.overflowing_mul(6364136223846793005).0.overflowing_add(1).0;
 			if seed % 1 == 0 {
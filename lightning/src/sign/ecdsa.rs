@@ -139,6 +139,23 @@ pub trait EcdsaChannelSigner: ChannelSigner {
 	/// chosen to forgo their output as dust.
 	fn sign_closing_transaction(&self, closing_tx: &ClosingTransaction,
 		secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()>;
+	/// Creates a signature for the counterparty's commitment transaction spending the funding
+	/// output of a splice which has yet to lock in, paying `channel_value_satoshis` to the channel.
+	///
+	/// The commitment transaction has the same commitment number as the counterparty's current
+	/// commitment transaction, but spends the new funding output. As splices are only negotiated
+	/// while no HTLCs are pending, it never contains HTLC outputs.
+	///
+	/// Note that if signing fails or is rejected, the splice will be aborted.
+	fn sign_splice_counterparty_commitment(&self, commitment_tx: &CommitmentTransaction,
+		channel_value_satoshis: u64, secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()>;
+	/// Creates a signature for the input of a splice transaction which spends the channel's
+	/// current funding output.
+	///
+	/// A validating signer should check that the splice transaction pays the expected amount back
+	/// to the channel, as no other channel state is provided here.
+	fn sign_splice_shared_input(&self, splice_tx: &Transaction, input_index: usize,
+		secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()>;
 	/// Computes the signature for a commitment transaction's anchor output used as an
 	/// input within `anchor_tx`, which spends the commitment transaction, at index `input`.
 	fn sign_holder_anchor_input(
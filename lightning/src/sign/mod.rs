@@ -81,6 +81,15 @@ pub mod taproot;
 #[derive(Hash, Copy, Clone, PartialEq, Eq, Debug)]
 pub struct KeyMaterial(pub [u8; 32]);
 
+/// A secret key used to encrypt the backup of our channel state which we hand to our peers via
+/// [`msgs::PeerStorage`] messages.
+///
+/// This is not exported to bindings users as we just use `[u8; 32]` directly
+///
+/// [`msgs::PeerStorage`]: crate::ln::msgs::PeerStorage
+#[derive(Hash, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PeerStorageKey(pub [u8; 32]);
+
 /// Information about a spendable output to a P2WSH script.
 ///
 /// See [`SpendableOutputDescriptor::DelayedPaymentOutput`] for more details on how to spend this.
@@ -296,6 +305,15 @@ impl_writeable_tlv_based_enum!(SpendableOutputDescriptor,
 );
 
 impl SpendableOutputDescriptor {
+	/// Returns the outpoint of the spendable output.
+	pub fn outpoint(&self) -> OutPoint {
+		match self {
+			Self::StaticOutput { outpoint, .. } => *outpoint,
+			Self::StaticPaymentOutput(descriptor) => descriptor.outpoint,
+			Self::DelayedPaymentOutput(descriptor) => descriptor.outpoint,
+		}
+	}
+
 	/// Turns this into a [`bitcoin::psbt::Input`] which can be used to create a
 	/// [`PartiallySignedTransaction`] which spends the given descriptor.
 	///
@@ -627,6 +645,14 @@ pub trait ChannelSigner {
 	///
 	/// channel_parameters.is_populated() MUST be true.
 	fn provide_channel_parameters(&mut self, channel_parameters: &ChannelTransactionParameters);
+
+	/// Updates the funding outpoint and channel value once a splice has replaced the channel's
+	/// funding output.
+	///
+	/// This is called after [`Self::provide_channel_parameters`], once for each splice, after which
+	/// all holder commitment and closing transactions spend the new funding output. The funding
+	/// public keys, and thus the funding script, are unchanged by a splice.
+	fn provide_renegotiated_funding(&mut self, funding_outpoint: OutPoint, channel_value_satoshis: u64);
 }
 
 /// Specifies the recipient of an invoice.
@@ -666,6 +692,14 @@ pub trait NodeSigner {
 	/// [phantom node payments]: PhantomKeysManager
 	fn get_inbound_payment_key_material(&self) -> KeyMaterial;
 
+	/// Get the secret key used to encrypt the backup of our channel state which is stored by our
+	/// peers.
+	///
+	/// This method must return the same value each time it is called, including across restarts
+	/// and on a new device restored from the same seed, as the backup can otherwise not be
+	/// decrypted.
+	fn get_peer_storage_key(&self) -> PeerStorageKey;
+
 	/// Get node id based on the provided [`Recipient`].
 	///
 	/// This method must return the same value each time it is called with a given [`Recipient`]
@@ -795,6 +829,36 @@ pub trait SignerProvider {
 	fn get_shutdown_scriptpubkey(&self) -> Result<ShutdownScript, ()>;
 }
 
+/// A trait that describes a wallet capable of creating a spending [`Transaction`] from a set of
+/// [`SpendableOutputDescriptor`]s.
+pub trait OutputSpender {
+	/// Creates a [`Transaction`] which spends the given descriptors to the given outputs, plus an
+	/// output to the given change destination (if sufficient change value remains). The
+	/// transaction will have a feerate, at least, of the given value.
+	///
+	/// The `locktime` argument is used to set the transaction's locktime. If `None`, the
+	/// transaction will have a locktime of 0. It it recommended to set this to the current block
+	/// height to avoid fee sniping, unless you have some specific reason to use a different
+	/// locktime.
+	///
+	/// Returns `Err(())` if the output value is greater than the input value minus required fee,
+	/// if a descriptor was duplicated, or if an output descriptor `script_pubkey`
+	/// does not match the one we can spend.
+	fn spend_spendable_outputs<C: Signing>(&self, descriptors: &[&SpendableOutputDescriptor], outputs: Vec<TxOut>, change_destination_script: ScriptBuf, feerate_sat_per_1000_weight: u32, locktime: Option<LockTime>, secp_ctx: &Secp256k1<C>) -> Result<Transaction, ()>;
+}
+
+/// A trait that describes a wallet capable of returning a script to which funds swept from
+/// [`SpendableOutputDescriptor`]s may be sent, e.g., by the
+/// [`OutputSweeper`](crate::util::sweep::OutputSweeper).
+pub trait ChangeDestinationSource {
+	/// Returns a script pubkey which can be used as a change destination for
+	/// [`OutputSpender::spend_spendable_outputs`].
+	///
+	/// This method should return a different value each time it is called, to avoid linking
+	/// on-chain funds controlled to the same user.
+	fn get_change_destination_script(&self) -> Result<ScriptBuf, ()>;
+}
+
 /// A simple implementation of [`WriteableEcdsaChannelSigner`] that just keeps the private keys in memory.
 ///
 /// This implementation performs no policy checks and is insufficient by itself as
@@ -1107,6 +1171,12 @@ impl ChannelSigner for InMemorySigner {
 		assert!(channel_parameters.is_populated(), "Channel parameters must be fully populated");
 		self.channel_parameters = Some(channel_parameters.clone());
 	}
+
+	fn provide_renegotiated_funding(&mut self, funding_outpoint: OutPoint, channel_value_satoshis: u64) {
+		let channel_parameters = self.channel_parameters.as_mut().expect(MISSING_PARAMS_ERR);
+		channel_parameters.funding_outpoint = Some(funding_outpoint);
+		self.channel_value_satoshis = channel_value_satoshis;
+	}
 }
 
 const MISSING_PARAMS_ERR: &'static str = "ChannelSigner::provide_channel_parameters must be called before signing operations";
@@ -1236,6 +1306,31 @@ impl EcdsaChannelSigner for InMemorySigner {
 		Ok(closing_tx.trust().sign(&self.funding_key, &channel_funding_redeemscript, self.channel_value_satoshis, secp_ctx))
 	}
 
+	fn sign_splice_counterparty_commitment(&self, commitment_tx: &CommitmentTransaction, channel_value_satoshis: u64, secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()> {
+		let funding_pubkey = PublicKey::from_secret_key(secp_ctx, &self.funding_key);
+		let counterparty_keys = self.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let channel_funding_redeemscript = make_funding_redeemscript(&funding_pubkey, &counterparty_keys.funding_pubkey);
+		if !commitment_tx.htlcs().is_empty() {
+			return Err(());
+		}
+		let trusted_tx = commitment_tx.trust();
+		Ok(trusted_tx.built_transaction().sign_counterparty_commitment(&self.funding_key, &channel_funding_redeemscript, channel_value_satoshis, secp_ctx))
+	}
+
+	fn sign_splice_shared_input(&self, splice_tx: &Transaction, input_index: usize, secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()> {
+		let funding_outpoint = self.funding_outpoint().expect(MISSING_PARAMS_ERR).into_bitcoin_outpoint();
+		if splice_tx.input.get(input_index).map(|input| input.previous_output) != Some(funding_outpoint) {
+			return Err(());
+		}
+		let funding_pubkey = PublicKey::from_secret_key(secp_ctx, &self.funding_key);
+		let counterparty_keys = self.counterparty_pubkeys().expect(MISSING_PARAMS_ERR);
+		let channel_funding_redeemscript = make_funding_redeemscript(&funding_pubkey, &counterparty_keys.funding_pubkey);
+		let sighash = sighash::SighashCache::new(splice_tx).segwit_signature_hash(
+			input_index, &channel_funding_redeemscript, self.channel_value_satoshis, EcdsaSighashType::All,
+		).map_err(|_| ())?;
+		Ok(sign_with_aux_rand(secp_ctx, &hash_to_message!(&sighash[..]), &self.funding_key, &self))
+	}
+
 	fn sign_holder_anchor_input(
 		&self, anchor_tx: &Transaction, input: usize, secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<Signature, ()> {
@@ -1363,6 +1458,7 @@ impl<ES: Deref> ReadableArgs<ES> for InMemorySigner where ES::Target: EntropySou
 /// Unilateral closes may use seed/1'.
 /// Cooperative closes may use seed/2'.
 /// The two close keys may be needed to claim on-chain funds!
+/// The backup of our channel state stored by our peers is encrypted with seed/6'.
 ///
 /// This struct cannot be used for nodes that wish to support receiving phantom payments;
 /// [`PhantomKeysManager`] must be used instead.
@@ -1374,6 +1470,7 @@ pub struct KeysManager {
 	node_secret: SecretKey,
 	node_id: PublicKey,
 	inbound_payment_key: KeyMaterial,
+	peer_storage_key: PeerStorageKey,
 	destination_script: ScriptBuf,
 	shutdown_pubkey: PublicKey,
 	channel_master_key: ExtendedPrivKey,
@@ -1429,6 +1526,7 @@ impl KeysManager {
 				let inbound_payment_key: SecretKey = master_key.ckd_priv(&secp_ctx, ChildNumber::from_hardened_idx(5).unwrap()).expect("Your RNG is busted").private_key;
 				let mut inbound_pmt_key_bytes = [0; 32];
 				inbound_pmt_key_bytes.copy_from_slice(&inbound_payment_key[..]);
+				let peer_storage_key: SecretKey = master_key.ckd_priv(&secp_ctx, ChildNumber::from_hardened_idx(6).unwrap()).expect("Your RNG is busted").private_key;
 
 				let mut rand_bytes_engine = Sha256::engine();
 				rand_bytes_engine.input(&starting_time_secs.to_be_bytes());
@@ -1442,6 +1540,7 @@ impl KeysManager {
 					node_secret,
 					node_id,
 					inbound_payment_key: KeyMaterial(inbound_pmt_key_bytes),
+					peer_storage_key: PeerStorageKey(peer_storage_key.secret_bytes()),
 
 					destination_script,
 					shutdown_pubkey,
@@ -1661,6 +1760,10 @@ impl NodeSigner for KeysManager {
 		self.inbound_payment_key.clone()
 	}
 
+	fn get_peer_storage_key(&self) -> PeerStorageKey {
+		self.peer_storage_key
+	}
+
 	fn sign_invoice(&self, hrp_bytes: &[u8], invoice_data: &[u5], recipient: Recipient) -> Result<RecoverableSignature, ()> {
 		let preimage = construct_invoice_preimage(&hrp_bytes, &invoice_data);
 		let secret = match recipient {
@@ -1732,6 +1835,12 @@ impl SignerProvider for KeysManager {
 	}
 }
 
+impl OutputSpender for KeysManager {
+	fn spend_spendable_outputs<C: Signing>(&self, descriptors: &[&SpendableOutputDescriptor], outputs: Vec<TxOut>, change_destination_script: ScriptBuf, feerate_sat_per_1000_weight: u32, locktime: Option<LockTime>, secp_ctx: &Secp256k1<C>) -> Result<Transaction, ()> {
+		KeysManager::spend_spendable_outputs(self, descriptors, outputs, change_destination_script, feerate_sat_per_1000_weight, locktime, secp_ctx)
+	}
+}
+
 /// Similar to [`KeysManager`], but allows the node using this struct to receive phantom node
 /// payments.
 ///
@@ -1789,6 +1898,10 @@ impl NodeSigner for PhantomKeysManager {
 		self.inbound_payment_key.clone()
 	}
 
+	fn get_peer_storage_key(&self) -> PeerStorageKey {
+		self.inner.get_peer_storage_key()
+	}
+
 	fn sign_invoice(&self, hrp_bytes: &[u8], invoice_data: &[u5], recipient: Recipient) -> Result<RecoverableSignature, ()> {
 		let preimage = construct_invoice_preimage(&hrp_bytes, &invoice_data);
 		let secret = match recipient {
@@ -1841,6 +1954,12 @@ impl SignerProvider for PhantomKeysManager {
 	}
 }
 
+impl OutputSpender for PhantomKeysManager {
+	fn spend_spendable_outputs<C: Signing>(&self, descriptors: &[&SpendableOutputDescriptor], outputs: Vec<TxOut>, change_destination_script: ScriptBuf, feerate_sat_per_1000_weight: u32, locktime: Option<LockTime>, secp_ctx: &Secp256k1<C>) -> Result<Transaction, ()> {
+		self.inner.spend_spendable_outputs(descriptors, outputs, change_destination_script, feerate_sat_per_1000_weight, locktime, secp_ctx)
+	}
+}
+
 impl PhantomKeysManager {
 	/// Constructs a [`PhantomKeysManager`] given a 32-byte seed and an additional `cross_node_seed`
 	/// that is shared across all nodes that intend to participate in [phantom node payments]
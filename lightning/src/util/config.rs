@@ -178,6 +178,28 @@ pub struct ChannelHandshakeConfig {
 	/// [`DecodeError::InvalidValue`]: crate::ln::msgs::DecodeError::InvalidValue
 	/// [`SIGHASH_SINGLE + update_fee Considered Harmful`]: https://lists.linuxfoundation.org/pipermail/lightning-dev/2020-September/002796.html
 	pub negotiate_anchors_zero_fee_htlc_tx: bool,
+	/// If set, we attempt to negotiate the `zero_fee_commitments` option for all future channels.
+	///
+	/// Channels of this type build on [`Self::negotiate_anchors_zero_fee_htlc_tx`] but use version
+	/// 3 (TRUC) commitment transactions which pay no fee themselves and include a single shared
+	/// ephemeral anchor output. Their fee is paid entirely by a CPFP child at broadcast time, so a
+	/// reserve of onchain funds is strictly required, and broadcasting them relies on package
+	/// relay support of the [`BroadcasterInterface`]'s backend, i.e. Bitcoin Core v28 or later.
+	///
+	/// As this option builds on `anchors_zero_fee_htlc_tx`, setting it also implies
+	/// [`Self::negotiate_anchors_zero_fee_htlc_tx`]. As with anchor channels, inbound channels of
+	/// this type must be manually accepted with [`ChannelManager::accept_inbound_channel`].
+	///
+	/// Note that setting this to true does *not* prevent us from opening channels with
+	/// counterparties that do not support the `zero_fee_commitments` option; we will simply fall
+	/// back to an `anchors_zero_fee_htlc_tx` channel, if negotiated, or a `static_remote_key`
+	/// channel otherwise.
+	///
+	/// Default value: false.
+	///
+	/// [`BroadcasterInterface`]: crate::chain::chaininterface::BroadcasterInterface
+	/// [`ChannelManager::accept_inbound_channel`]: crate::ln::channelmanager::ChannelManager::accept_inbound_channel
+	pub negotiate_anchor_zero_fee_commitments: bool,
 
 	/// The maximum number of HTLCs in-flight from our counterparty towards us at the same time.
 	///
@@ -190,6 +212,8 @@ pub struct ChannelHandshakeConfig {
 	/// Default value: 50
 	/// Maximum value: 483, any values larger will be treated as 483.
 	///                     This is the BOLT #2 spec limit on `max_accepted_htlcs`.
+	///                     On channels negotiating [`Self::negotiate_anchor_zero_fee_commitments`],
+	///                     any values larger than 114 will be treated as 114.
 	pub our_max_accepted_htlcs: u16,
 }
 
@@ -205,6 +229,7 @@ impl Default for ChannelHandshakeConfig {
 			commit_upfront_shutdown_pubkey: true,
 			their_channel_reserve_proportional_millionths: 10_000,
 			negotiate_anchors_zero_fee_htlc_tx: false,
+			negotiate_anchor_zero_fee_commitments: false,
 			our_max_accepted_htlcs: 50,
 		}
 	}
@@ -486,6 +511,28 @@ pub struct ChannelConfig {
 	/// [`PaymentClaimable::counterparty_skimmed_fee_msat`]: crate::events::Event::PaymentClaimable::counterparty_skimmed_fee_msat
 	//  TODO: link to bLIP when it's merged
 	pub accept_underpaying_htlcs: bool,
+	/// The percentage of our counterparty's HTLC slots on this channel which HTLCs we forward
+	/// over it without endorsing them may occupy.
+	///
+	/// We only endorse an HTLC to the next hop if our counterparty endorsed it to us and the
+	/// incoming channel has built up sufficient local reputation with us. Reserving the remaining
+	/// slots for endorsed HTLCs ensures peers which forward us HTLCs that are held for long
+	/// periods cannot use up all of our channels' HTLC slots, i.e. jam them. Forwards which would
+	/// exceed this share are failed back with a `temporary_channel_failure`.
+	///
+	/// Values above 100 are treated as 100, which allows unendorsed HTLCs to use all slots, i.e.
+	/// endorsement signals are only recorded but not acted upon.
+	///
+	/// Default value: 100.
+	pub unendorsed_htlc_slot_share_percentage: u8,
+	/// The percentage of this channel's maximum in-flight HTLC value which HTLCs we forward over
+	/// it without endorsing them may occupy, protecting our liquidity against jamming in the same
+	/// way [`Self::unendorsed_htlc_slot_share_percentage`] protects our HTLC slots.
+	///
+	/// Values above 100 are treated as 100, which allows unendorsed HTLCs to use all liquidity.
+	///
+	/// Default value: 100.
+	pub unendorsed_htlc_liquidity_share_percentage: u8,
 }
 
 impl ChannelConfig {
@@ -506,6 +553,12 @@ impl ChannelConfig {
 		if let Some(force_close_avoidance_max_fee_satoshis) = update.force_close_avoidance_max_fee_satoshis {
 			self.force_close_avoidance_max_fee_satoshis = force_close_avoidance_max_fee_satoshis;
 		}
+		if let Some(unendorsed_htlc_slot_share_percentage) = update.unendorsed_htlc_slot_share_percentage {
+			self.unendorsed_htlc_slot_share_percentage = unendorsed_htlc_slot_share_percentage;
+		}
+		if let Some(unendorsed_htlc_liquidity_share_percentage) = update.unendorsed_htlc_liquidity_share_percentage {
+			self.unendorsed_htlc_liquidity_share_percentage = unendorsed_htlc_liquidity_share_percentage;
+		}
 	}
 }
 
@@ -519,6 +572,8 @@ impl Default for ChannelConfig {
 			max_dust_htlc_exposure: MaxDustHTLCExposure::FeeRateMultiplier(5000),
 			force_close_avoidance_max_fee_satoshis: 1000,
 			accept_underpaying_htlcs: false,
+			unendorsed_htlc_slot_share_percentage: 100,
+			unendorsed_htlc_liquidity_share_percentage: 100,
 		}
 	}
 }
@@ -535,7 +590,9 @@ impl crate::util::ser::Writeable for ChannelConfig {
 			(2, self.forwarding_fee_base_msat, required),
 			(3, self.max_dust_htlc_exposure, required),
 			(4, self.cltv_expiry_delta, required),
+			(5, self.unendorsed_htlc_slot_share_percentage, (default_value, 100)),
 			(6, max_dust_htlc_exposure_msat_fixed_limit, required),
+			(7, self.unendorsed_htlc_liquidity_share_percentage, (default_value, 100)),
 			// ChannelConfig serialized this field with a required type of 8 prior to the introduction of
 			// LegacyChannelConfig. To make sure that serialization is not compatible with this one, we use
 			// the next required type of 10, which if seen by the old serialization will always fail.
@@ -554,14 +611,18 @@ impl crate::util::ser::Readable for ChannelConfig {
 		let mut max_dust_htlc_exposure_msat = None;
 		let mut max_dust_htlc_exposure_enum = None;
 		let mut force_close_avoidance_max_fee_satoshis = 1000;
+		let mut unendorsed_htlc_slot_share_percentage = 100;
+		let mut unendorsed_htlc_liquidity_share_percentage = 100;
 		read_tlv_fields!(reader, {
 			(0, forwarding_fee_proportional_millionths, required),
 			(1, accept_underpaying_htlcs, (default_value, false)),
 			(2, forwarding_fee_base_msat, required),
 			(3, max_dust_htlc_exposure_enum, option),
 			(4, cltv_expiry_delta, required),
+			(5, unendorsed_htlc_slot_share_percentage, (default_value, 100u8)),
 			// Has always been written, but became optionally read in 0.0.116
 			(6, max_dust_htlc_exposure_msat, option),
+			(7, unendorsed_htlc_liquidity_share_percentage, (default_value, 100u8)),
 			(10, force_close_avoidance_max_fee_satoshis, required),
 		});
 		let max_dust_htlc_fixed_limit = max_dust_htlc_exposure_msat.unwrap_or(5_000_000);
@@ -574,6 +635,8 @@ impl crate::util::ser::Readable for ChannelConfig {
 			cltv_expiry_delta,
 			max_dust_htlc_exposure: max_dust_htlc_exposure_msat,
 			force_close_avoidance_max_fee_satoshis,
+			unendorsed_htlc_slot_share_percentage,
+			unendorsed_htlc_liquidity_share_percentage,
 		})
 	}
 }
@@ -586,6 +649,8 @@ pub struct ChannelConfigUpdate {
 	pub cltv_expiry_delta: Option<u16>,
 	pub max_dust_htlc_exposure_msat: Option<MaxDustHTLCExposure>,
 	pub force_close_avoidance_max_fee_satoshis: Option<u64>,
+	pub unendorsed_htlc_slot_share_percentage: Option<u8>,
+	pub unendorsed_htlc_liquidity_share_percentage: Option<u8>,
 }
 
 impl Default for ChannelConfigUpdate {
@@ -596,6 +661,8 @@ impl Default for ChannelConfigUpdate {
 			cltv_expiry_delta: None,
 			max_dust_htlc_exposure_msat: None,
 			force_close_avoidance_max_fee_satoshis: None,
+			unendorsed_htlc_slot_share_percentage: None,
+			unendorsed_htlc_liquidity_share_percentage: None,
 		}
 	}
 }
@@ -608,6 +675,8 @@ impl From<ChannelConfig> for ChannelConfigUpdate {
 			cltv_expiry_delta: Some(config.cltv_expiry_delta),
 			max_dust_htlc_exposure_msat: Some(config.max_dust_htlc_exposure),
 			force_close_avoidance_max_fee_satoshis: Some(config.force_close_avoidance_max_fee_satoshis),
+			unendorsed_htlc_slot_share_percentage: Some(config.unendorsed_htlc_slot_share_percentage),
+			unendorsed_htlc_liquidity_share_percentage: Some(config.unendorsed_htlc_liquidity_share_percentage),
 		}
 	}
 }
@@ -651,7 +720,9 @@ impl crate::util::ser::Writeable for LegacyChannelConfig {
 			(4, self.announced_channel, required),
 			(5, self.options.max_dust_htlc_exposure, required),
 			(6, self.commit_upfront_shutdown_pubkey, required),
+			(7, self.options.unendorsed_htlc_slot_share_percentage, (default_value, 100)),
 			(8, self.options.forwarding_fee_base_msat, required),
+			(9, self.options.unendorsed_htlc_liquidity_share_percentage, (default_value, 100)),
 		});
 		Ok(())
 	}
@@ -667,6 +738,8 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 		let mut commit_upfront_shutdown_pubkey = false;
 		let mut forwarding_fee_base_msat = 0;
 		let mut max_dust_htlc_exposure_enum = None;
+		let mut unendorsed_htlc_slot_share_percentage = 100;
+		let mut unendorsed_htlc_liquidity_share_percentage = 100;
 		read_tlv_fields!(reader, {
 			(0, forwarding_fee_proportional_millionths, required),
 			// Has always been written, but became optionally read in 0.0.116
@@ -676,7 +749,9 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 			(4, announced_channel, required),
 			(5, max_dust_htlc_exposure_enum, option),
 			(6, commit_upfront_shutdown_pubkey, required),
+			(7, unendorsed_htlc_slot_share_percentage, (default_value, 100u8)),
 			(8, forwarding_fee_base_msat, required),
+			(9, unendorsed_htlc_liquidity_share_percentage, (default_value, 100u8)),
 		});
 		let max_dust_htlc_exposure_msat_fixed_limit =
 			max_dust_htlc_exposure_msat_fixed_limit.unwrap_or(5_000_000);
@@ -690,6 +765,8 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 				force_close_avoidance_max_fee_satoshis,
 				forwarding_fee_base_msat,
 				accept_underpaying_htlcs: false,
+				unendorsed_htlc_slot_share_percentage,
+				unendorsed_htlc_liquidity_share_percentage,
 			},
 			announced_channel,
 			commit_upfront_shutdown_pubkey,
@@ -697,6 +774,43 @@ impl crate::util::ser::Readable for LegacyChannelConfig {
 	}
 }
 
+/// Bounds applied to amounts converted with a [`CurrencyConversion`] when paying or being paid for
+/// an [`Offer`] denominated in a currency other than bitcoin.
+///
+/// Payer and payee are unlikely to source their exchange rates identically or at the same time,
+/// so some slack is needed to keep such payments from failing whenever rates move slightly.
+///
+/// [`CurrencyConversion`]: crate::offers::offer::CurrencyConversion
+/// [`Offer`]: crate::offers::offer::Offer
+#[derive(Copy, Clone, Debug)]
+pub struct CurrencyConversionTolerance {
+	/// The proportion, in parts per million, by which the amount of an [`InvoiceRequest`] for one
+	/// of our offers may fall short of our own conversion of the offer's amount before we refuse
+	/// to respond with an invoice.
+	///
+	/// Default value: 10_000 (1%)
+	///
+	/// [`InvoiceRequest`]: crate::offers::invoice_request::InvoiceRequest
+	pub max_underpayment_ppm: u32,
+	/// The proportion, in parts per million, we add on top of our own conversion of an offer's
+	/// amount when paying it via [`ChannelManager::pay_for_offer`] without an explicit amount.
+	/// This leaves room for the recipient's exchange rate to be less favorable than ours.
+	///
+	/// Default value: 5_000 (0.5%)
+	///
+	/// [`ChannelManager::pay_for_offer`]: crate::ln::channelmanager::ChannelManager::pay_for_offer
+	pub overpayment_ppm: u32,
+}
+
+impl Default for CurrencyConversionTolerance {
+	fn default() -> Self {
+		CurrencyConversionTolerance {
+			max_underpayment_ppm: 10_000,
+			overpayment_ppm: 5_000,
+		}
+	}
+}
+
 /// Top-level config which holds ChannelHandshakeLimits and ChannelConfig.
 ///
 /// Default::default() provides sane defaults for most configurations
@@ -765,6 +879,64 @@ pub struct UserConfig {
 	///
 	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
 	pub accept_mpp_keysend: bool,
+	/// If this is set to true, we will signal support for splicing to our peers and accept their
+	/// requests to splice funds into or out of our channels with them. Splices initiated via
+	/// [`ChannelManager::splice_channel`] additionally require the counterparty to signal support.
+	///
+	/// Note that while a splice is pending, no HTLCs may be added to the channel until the new
+	/// funding transaction has been signed by both parties and has confirmed.
+	///
+	/// Default value: false.
+	///
+	/// [`ChannelManager::splice_channel`]: crate::ln::channelmanager::ChannelManager::splice_channel
+	pub enable_splicing: bool,
+	/// If this is set to true, we will signal support for dual-funded channels to our peers and
+	/// accept their requests to open dual-funded channels with us. Dual-funded channels opened
+	/// via [`ChannelManager::create_dual_funded_channel`] additionally require the counterparty to
+	/// signal support.
+	///
+	/// Note that inbound dual-funded channels are subject to the same acceptance rules as V1
+	/// channels, i.e. [`Self::accept_inbound_channels`] and
+	/// [`Self::manually_accept_inbound_channels`] apply.
+	///
+	/// Default value: false.
+	///
+	/// [`ChannelManager::create_dual_funded_channel`]: crate::ln::channelmanager::ChannelManager::create_dual_funded_channel
+	pub enable_dual_funded_channels: bool,
+	/// If this is set to true, we will signal support for trampoline routing to our peers and
+	/// forward trampoline payments we receive, computing the route to the next trampoline node or
+	/// the recipient on the sender's behalf using our [`Router`].
+	///
+	/// Trampoline forwards are charged the fee and CLTV delta the sender allotted to us in the
+	/// trampoline onion, and fail with `trampoline_fee_or_expiry_insufficient` if that does not
+	/// cover the route we find.
+	///
+	/// Default value: false.
+	///
+	/// [`Router`]: crate::routing::router::Router
+	pub accept_trampoline_forwards: bool,
+	/// If this is set to true, we will signal support for `option_provide_storage` to our peers,
+	/// store a small backup blob for each peer we have a funded channel with and return it to them
+	/// upon reconnection. Additionally, we will hand peers which signal support an encrypted
+	/// summary of our [`ChannelMonitor`]s, allowing us to detect that we lost channel state if we
+	/// ever restart from an outdated backup.
+	///
+	/// Backups stored with us by our peers are persisted separately from the [`ChannelManager`],
+	/// see [`ChannelManager::take_pending_peer_storage_updates`] and
+	/// [`ChannelManager::load_peer_storage`].
+	///
+	/// Default value: false.
+	///
+	/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+	/// [`ChannelManager`]: crate::ln::channelmanager::ChannelManager
+	/// [`ChannelManager::take_pending_peer_storage_updates`]: crate::ln::channelmanager::ChannelManager::take_pending_peer_storage_updates
+	/// [`ChannelManager::load_peer_storage`]: crate::ln::channelmanager::ChannelManager::load_peer_storage
+	pub provide_peer_storage: bool,
+	/// Bounds applied to amounts converted from offers denominated in a currency other than
+	/// bitcoin.
+	///
+	/// Default value: [`CurrencyConversionTolerance::default`]
+	pub currency_conversion_tolerance: CurrencyConversionTolerance,
 }
 
 impl Default for UserConfig {
@@ -778,6 +950,11 @@ impl Default for UserConfig {
 			manually_accept_inbound_channels: false,
 			accept_intercept_htlcs: false,
 			accept_mpp_keysend: false,
+			enable_splicing: false,
+			enable_dual_funded_channels: false,
+			accept_trampoline_forwards: false,
+			provide_peer_storage: false,
+			currency_conversion_tolerance: CurrencyConversionTolerance::default(),
 		}
 	}
 }
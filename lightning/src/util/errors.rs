@@ -139,6 +139,9 @@ pub(crate) fn get_onion_error_description(error_code: u16) -> (&'static str, &'s
 		_c if _c == 21 => ("Node indicated the CLTV expiry in the HTLC is too far in the future", "expiry_too_far"),
 		_c if _c == PERM|22 => ("Node indicated that the decrypted onion per-hop payload was not understood by it or is incomplete", "invalid_onion_payload"),
 		_c if _c == 23 => ("The final node indicated the complete amount of the multi-part payment was not received within a reasonable time", "mpp_timeout"),
+		_c if _c == NODE|25 => ("The trampoline node indicated a temporary failure while routing to the next trampoline", "temporary_trampoline_failure"),
+		_c if _c == NODE|26 => ("The trampoline node indicated the fee or CLTV budget was insufficient to route to the next trampoline", "trampoline_fee_or_expiry_insufficient"),
+		_c if _c == PERM|27 => ("The trampoline node indicated the next trampoline node is unknown", "unknown_next_trampoline"),
 		_ => ("Unknown", ""),
 	}
 }
@@ -0,0 +1,122 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! Hooks allowing to collect metrics about the operation of LDK's core types.
+//!
+//! Metrics are reported to the [`MetricsRecorder`] the respective object was constructed with.
+//! Pass an [`IgnoringMetricsRecorder`] if no metrics should be recorded.
+//!
+//! Metric names are given as the constants of this module, following the Prometheus naming
+//! conventions. Latencies are reported in seconds and are only measured with the `std` feature
+//! enabled.
+
+use core::ops::Deref;
+
+/// Counter of HTLCs we successfully forwarded, i.e., for which we claimed the inbound HTLC after
+/// the outbound one was claimed.
+///
+/// Recorded by the `lightning-background-processor` for each [`Event::PaymentForwarded`].
+///
+/// [`Event::PaymentForwarded`]: crate::events::Event::PaymentForwarded
+pub const HTLCS_FORWARDED: &str = "ldk_htlcs_forwarded_total";
+
+/// Counter of inbound HTLCs we failed back, labeled by `reason`.
+///
+/// The `reason` is one of `next_hop_channel`, `unknown_next_hop`, `invalid_forward`,
+/// `failed_payment` or `next_trampoline`, matching the [`HTLCDestination`] given in the
+/// corresponding [`Event::HTLCHandlingFailed`]. Recorded by the `lightning-background-processor`
+/// for each such event.
+///
+/// [`HTLCDestination`]: crate::events::HTLCDestination
+/// [`Event::HTLCHandlingFailed`]: crate::events::Event::HTLCHandlingFailed
+pub const HTLCS_FAILED: &str = "ldk_htlcs_failed_total";
+
+/// Histogram of the time spent finding routes in [`DefaultRouter::find_route`] and
+/// [`MinCostFlowRouter::find_route`], labeled by `result` being either `success` or `failure`.
+///
+/// [`DefaultRouter::find_route`]: crate::routing::router::DefaultRouter#method.find_route
+/// [`MinCostFlowRouter::find_route`]: crate::routing::router::MinCostFlowRouter#method.find_route
+pub const FIND_ROUTE_DURATION: &str = "ldk_find_route_duration_seconds";
+
+/// Histogram of the time spent persisting a [`ChannelMonitor`] or [`ChannelMonitorUpdate`] via
+/// [`Persist`], labeled by `kind` being one of `new_channel`, `update` or `chain_sync`.
+///
+/// [`ChannelMonitor`]: crate::chain::channelmonitor::ChannelMonitor
+/// [`ChannelMonitorUpdate`]: crate::chain::channelmonitor::ChannelMonitorUpdate
+/// [`Persist`]: crate::chain::chainmonitor::Persist
+pub const MONITOR_UPDATE_DURATION: &str = "ldk_monitor_update_duration_seconds";
+
+/// Counter of [`Persist`] calls which returned [`ChannelMonitorUpdateStatus::InProgress`], labeled
+/// by `kind` as for [`MONITOR_UPDATE_DURATION`].
+///
+/// [`Persist`]: crate::chain::chainmonitor::Persist
+/// [`ChannelMonitorUpdateStatus::InProgress`]: crate::chain::ChannelMonitorUpdateStatus::InProgress
+pub const MONITOR_UPDATES_IN_PROGRESS: &str = "ldk_monitor_updates_in_progress_total";
+
+/// Counter of messages received from peers, labeled by the BOLT message `type` name, e.g.
+/// `update_add_htlc`, or `unknown` for custom and unknown messages.
+pub const PEER_MESSAGES_RECEIVED: &str = "ldk_peer_messages_received_total";
+
+/// Counter of messages sent to peers, labeled by the BOLT message `type` name as for
+/// [`PEER_MESSAGES_RECEIVED`].
+pub const PEER_MESSAGES_SENT: &str = "ldk_peer_messages_sent_total";
+
+/// Histogram of the time spent persisting objects in the `lightning-background-processor`,
+/// labeled by `object` being one of `channel_manager`, `network_graph` or `scorer`.
+pub const PERSISTENCE_DURATION: &str = "ldk_persistence_duration_seconds";
+
+/// A trait receiving metrics about the operation of LDK's core types.
+///
+/// See the [module-level documentation](self) for the metrics which are reported.
+pub trait MetricsRecorder {
+	/// Increments the counter `name` with the given `labels` by `value`.
+	fn increment_counter(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64);
+
+	/// Records an observation of `value` in the histogram `name` with the given `labels`.
+	fn record_histogram(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64);
+}
+
+/// A dummy [`MetricsRecorder`] which discards all metrics.
+pub struct IgnoringMetricsRecorder {}
+
+impl MetricsRecorder for IgnoringMetricsRecorder {
+	fn increment_counter(&self, _name: &'static str, _labels: &[(&'static str, &str)], _value: u64) {}
+	fn record_histogram(&self, _name: &'static str, _labels: &[(&'static str, &str)], _value: f64) {}
+}
+
+impl Deref for IgnoringMetricsRecorder {
+	type Target = IgnoringMetricsRecorder;
+	fn deref(&self) -> &Self { self }
+}
+
+/// Measures the time elapsed since it was started, to be recorded in a latency histogram.
+pub(crate) struct LatencyTimer {
+	#[cfg(feature = "std")]
+	start: std::time::Instant,
+}
+
+impl LatencyTimer {
+	pub(crate) fn start() -> Self {
+		Self {
+			#[cfg(feature = "std")]
+			start: std::time::Instant::now(),
+		}
+	}
+
+	/// Records the time elapsed since [`LatencyTimer::start`] in the histogram `name`. Does
+	/// nothing without the `std` feature.
+	pub(crate) fn record<R: MetricsRecorder + ?Sized>(
+		self, recorder: &R, name: &'static str, labels: &[(&'static str, &str)]
+	) {
+		#[cfg(feature = "std")]
+		recorder.record_histogram(name, labels, self.start.elapsed().as_secs_f64());
+		#[cfg(not(feature = "std"))]
+		let _ = (recorder, name, labels);
+	}
+}
@@ -41,7 +41,9 @@ pub(crate) mod macro_logger;
 
 // These have to come after macro_logger to build
 pub mod logger;
+pub mod metrics;
 pub mod config;
+pub mod sweep;
 
 #[cfg(any(test, feature = "_test_utils"))]
 pub mod test_utils;
@@ -6,13 +6,19 @@
 
 //! This module contains a simple key-value store trait [`KVStore`] that
 //! allows one to implement the persistence for [`ChannelManager`], [`NetworkGraph`],
-//! and [`ChannelMonitor`] all in one place.
+//! and [`ChannelMonitor`] all in one place, as well as its asynchronous counterpart
+//! [`AsyncKVStore`].
 
 use core::cmp;
 use core::convert::{TryFrom, TryInto};
+use core::future::Future;
+#[cfg(feature = "std")]
+use core::mem;
 use core::ops::Deref;
+use core::pin::Pin;
 use core::str::FromStr;
 use bitcoin::{BlockHash, Txid};
+use bitcoin::secp256k1::PublicKey;
 
 use crate::{io, log_error};
 use crate::alloc::string::ToString;
@@ -21,15 +27,20 @@ use crate::prelude::*;
 use crate::chain;
 use crate::chain::chaininterface::{BroadcasterInterface, FeeEstimator};
 use crate::chain::chainmonitor::{Persist, MonitorUpdateId};
+#[cfg(feature = "std")]
+use crate::chain::chainmonitor::PersistCompletionNotifier;
 use crate::sign::{EntropySource, NodeSigner, ecdsa::WriteableEcdsaChannelSigner, SignerProvider};
 use crate::chain::transaction::OutPoint;
 use crate::chain::channelmonitor::{ChannelMonitor, ChannelMonitorUpdate, CLOSED_CHANNEL_UPDATE_ID};
 use crate::ln::channelmanager::ChannelManager;
+use crate::offers::offer::CurrencyConversion;
 use crate::routing::router::Router;
-use crate::routing::gossip::NetworkGraph;
+use crate::routing::gossip::{NetworkGraph, NetworkGraphChanges};
 use crate::routing::scoring::WriteableScore;
 use crate::util::logger::Logger;
 use crate::util::ser::{Readable, ReadableArgs, Writeable};
+#[cfg(feature = "std")]
+use crate::sync::{Arc, Mutex};
 
 /// The alphabet of characters allowed for namespaces and keys.
 pub const KVSTORE_NAMESPACE_KEY_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-";
@@ -56,7 +67,26 @@ pub const NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE: &str = "";
 /// The secondary namespace under which the [`NetworkGraph`] will be persisted.
 pub const NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
 /// The key under which the [`NetworkGraph`] will be persisted.
-pub const NETWORK_GRAPH_PERSISTENCE_KEY: &str = "network_graph";
+///
+/// As graphs persisted under this key may have [`NetworkGraphChanges`] persisted on top of them,
+/// they need to be read via [`read_network_graph`].
+pub const NETWORK_GRAPH_PERSISTENCE_KEY: &str = "network_graph_v2";
+/// The key under which the [`NetworkGraph`] was persisted in full by versions of LDK prior to
+/// persisting [`NetworkGraphChanges`].
+///
+/// A graph found under this key is read by [`read_network_graph`] in place of the one under
+/// [`NETWORK_GRAPH_PERSISTENCE_KEY`] and removed once the graph is next persisted in full.
+pub const LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY: &str = "network_graph";
+
+/// The primary namespace under which changes to the [`NetworkGraph`] since it was last persisted
+/// in full will be persisted, keyed by their [`NetworkGraphChanges::sequence`].
+pub const NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE: &str = "network_graph_changes";
+/// The secondary namespace under which changes to the [`NetworkGraph`] will be persisted.
+pub const NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+
+/// The number of sets of [`NetworkGraphChanges`] after which the [`KVStore`] implementation of
+/// [`Persister`] compacts them by persisting the full [`NetworkGraph`] again.
+pub const MAX_NETWORK_GRAPH_CHANGES: u64 = 100;
 
 /// The primary namespace under which the [`WriteableScore`] will be persisted.
 pub const SCORER_PERSISTENCE_PRIMARY_NAMESPACE: &str = "";
@@ -65,6 +95,68 @@ pub const SCORER_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
 /// The key under which the [`WriteableScore`] will be persisted.
 pub const SCORER_PERSISTENCE_KEY: &str = "scorer";
 
+/// The primary namespace under which the backups our peers handed us via [`PeerStorage`] messages
+/// will be persisted, keyed by the hex-encoded `node_id` of the peer.
+///
+/// [`PeerStorage`]: crate::ln::msgs::PeerStorage
+pub const PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE: &str = "peer_storage";
+/// The secondary namespace under which the backups our peers handed us via [`PeerStorage`]
+/// messages will be persisted.
+///
+/// [`PeerStorage`]: crate::ln::msgs::PeerStorage
+pub const PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+
+/// The primary namespace under which [`OutputSweeper`] state will be persisted.
+///
+/// [`OutputSweeper`]: crate::util::sweep::OutputSweeper
+pub const OUTPUT_SWEEPER_PERSISTENCE_PRIMARY_NAMESPACE: &str = "";
+/// The secondary namespace under which [`OutputSweeper`] state will be persisted.
+///
+/// [`OutputSweeper`]: crate::util::sweep::OutputSweeper
+pub const OUTPUT_SWEEPER_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+/// The key under which [`OutputSweeper`] state will be persisted.
+///
+/// [`OutputSweeper`]: crate::util::sweep::OutputSweeper
+pub const OUTPUT_SWEEPER_PERSISTENCE_KEY: &str = "output_sweeper";
+
+/// The primary namespace under which [`WatchtowerClient`] state will be persisted.
+///
+/// [`WatchtowerClient`]: crate::chain::watchtower::WatchtowerClient
+pub const WATCHTOWER_CLIENT_PERSISTENCE_PRIMARY_NAMESPACE: &str = "";
+/// The secondary namespace under which [`WatchtowerClient`] state will be persisted.
+///
+/// [`WatchtowerClient`]: crate::chain::watchtower::WatchtowerClient
+pub const WATCHTOWER_CLIENT_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+/// The key under which [`WatchtowerClient`] state will be persisted.
+///
+/// [`WatchtowerClient`]: crate::chain::watchtower::WatchtowerClient
+pub const WATCHTOWER_CLIENT_PERSISTENCE_KEY: &str = "watchtower_client";
+
+/// The primary namespace under which [`Watchtower`] state will be persisted.
+///
+/// [`Watchtower`]: crate::chain::watchtower::Watchtower
+pub const WATCHTOWER_PERSISTENCE_PRIMARY_NAMESPACE: &str = "";
+/// The secondary namespace under which [`Watchtower`] state will be persisted.
+///
+/// [`Watchtower`]: crate::chain::watchtower::Watchtower
+pub const WATCHTOWER_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+/// The key under which [`Watchtower`] state will be persisted.
+///
+/// [`Watchtower`]: crate::chain::watchtower::Watchtower
+pub const WATCHTOWER_PERSISTENCE_KEY: &str = "watchtower";
+
+/// The primary namespace under which the appointments held by a [`Watchtower`] will be persisted,
+/// keyed by the hex-encoded [`Locator`].
+///
+/// [`Watchtower`]: crate::chain::watchtower::Watchtower
+/// [`Locator`]: crate::chain::watchtower::Locator
+pub const WATCHTOWER_APPOINTMENTS_PERSISTENCE_PRIMARY_NAMESPACE: &str = "watchtower_appointments";
+/// The secondary namespace under which the appointments held by a [`Watchtower`] will be
+/// persisted.
+///
+/// [`Watchtower`]: crate::chain::watchtower::Watchtower
+pub const WATCHTOWER_APPOINTMENTS_PERSISTENCE_SECONDARY_NAMESPACE: &str = "";
+
 /// A sentinel value to be prepended to monitors persisted by the [`MonitorUpdatingPersister`].
 ///
 /// This serves to prevent someone from accidentally loading such monitors (which may need
@@ -130,8 +222,76 @@ pub trait KVStore {
 	fn list(&self, primary_namespace: &str, secondary_namespace: &str) -> Result<Vec<String>, io::Error>;
 }
 
+/// The result of an [`AsyncKVStore`] operation, which resolves once the operation has completed.
+pub type AsyncKVStoreResult<T> = Pin<Box<dyn Future<Output = Result<T, io::Error>> + Send + 'static>>;
+
+/// An asynchronous variant of [`KVStore`], allowing storage backends such as remote databases to
+/// be used without blocking the calling thread.
+///
+/// The same requirements on namespaces and keys as for [`KVStore`] apply.
+///
+/// The returned futures must not borrow from `self` or the arguments, allowing them to be driven
+/// to completion on a background task. While they may be polled in any order, implementations
+/// **must** ensure that writes and removals of the same key take effect in the order in which the
+/// respective methods were *called*, rather than the order in which their futures are polled.
+pub trait AsyncKVStore {
+	/// Returns the data stored for the given `primary_namespace`, `secondary_namespace`, and
+	/// `key`.
+	///
+	/// See [`KVStore::read`] for more details.
+	fn read(&self, primary_namespace: &str, secondary_namespace: &str, key: &str) -> AsyncKVStoreResult<Vec<u8>>;
+	/// Persists the given data under the given `key`.
+	///
+	/// See [`KVStore::write`] for more details.
+	fn write(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>) -> AsyncKVStoreResult<()>;
+	/// Removes any data that had previously been persisted under the given `key`.
+	///
+	/// See [`KVStore::remove`] for more details.
+	fn remove(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool) -> AsyncKVStoreResult<()>;
+	/// Returns a list of keys that are stored under the given `secondary_namespace` in
+	/// `primary_namespace`.
+	///
+	/// See [`KVStore::list`] for more details.
+	fn list(&self, primary_namespace: &str, secondary_namespace: &str) -> AsyncKVStoreResult<Vec<String>>;
+}
+
+/// Wraps a synchronous [`KVStore`] to provide an [`AsyncKVStore`] whose operations complete
+/// immediately.
+///
+/// This is mostly useful for testing or for gradually migrating to asynchronous persistence, as
+/// each operation still blocks the calling thread.
+pub struct KVStoreSyncWrapper<K: Deref>(pub K) where K::Target: KVStore;
+
+impl<K: Deref> AsyncKVStore for KVStoreSyncWrapper<K> where K::Target: KVStore {
+	fn read(&self, primary_namespace: &str, secondary_namespace: &str, key: &str) -> AsyncKVStoreResult<Vec<u8>> {
+		let res = self.0.read(primary_namespace, secondary_namespace, key);
+		Box::pin(async move { res })
+	}
+
+	fn write(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: Vec<u8>) -> AsyncKVStoreResult<()> {
+		let res = self.0.write(primary_namespace, secondary_namespace, key, &buf);
+		Box::pin(async move { res })
+	}
+
+	fn remove(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool) -> AsyncKVStoreResult<()> {
+		let res = self.0.remove(primary_namespace, secondary_namespace, key, lazy);
+		Box::pin(async move { res })
+	}
+
+	fn list(&self, primary_namespace: &str, secondary_namespace: &str) -> AsyncKVStoreResult<Vec<String>> {
+		let res = self.0.list(primary_namespace, secondary_namespace);
+		Box::pin(async move { res })
+	}
+}
+
+/// Spawns futures to be driven to completion in the background, e.g. via `tokio::spawn`.
+pub trait FutureSpawner {
+	/// Spawns the given future as a background task.
+	fn spawn<F: Future<Output = ()> + Send + 'static>(&self, future: F);
+}
+
 /// Trait that handles persisting a [`ChannelManager`], [`NetworkGraph`], and [`WriteableScore`] to disk.
-pub trait Persister<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, S: WriteableScore<'a>>
+pub trait Persister<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref, S: WriteableScore<'a>>
 	where M::Target: 'static + chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 		T::Target: 'static + BroadcasterInterface,
 		ES::Target: 'static + EntropySource,
@@ -140,9 +300,10 @@ pub trait Persister<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F:
 		F::Target: 'static + FeeEstimator,
 		R::Target: 'static + Router,
 		L::Target: 'static + Logger,
+		CC::Target: 'static + CurrencyConversion,
 {
 	/// Persist the given ['ChannelManager'] to disk, returning an error if persistence failed.
-	fn persist_manager(&self, channel_manager: &ChannelManager<M, T, ES, NS, SP, F, R, L>) -> Result<(), io::Error>;
+	fn persist_manager(&self, channel_manager: &ChannelManager<M, T, ES, NS, SP, F, R, L, CC>) -> Result<(), io::Error>;
 
 	/// Persist the given [`NetworkGraph`] to disk, returning an error if persistence failed.
 	fn persist_graph(&self, network_graph: &NetworkGraph<L>) -> Result<(), io::Error>;
@@ -152,7 +313,7 @@ pub trait Persister<'a, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F:
 }
 
 
-impl<'a, A: KVStore, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, S: WriteableScore<'a>> Persister<'a, M, T, ES, NS, SP, F, R, L, S> for A
+impl<'a, A: KVStore, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Deref, R: Deref, L: Deref, CC: Deref, S: WriteableScore<'a>> Persister<'a, M, T, ES, NS, SP, F, R, L, CC, S> for A
 	where M::Target: 'static + chain::Watch<<SP::Target as SignerProvider>::EcdsaSigner>,
 		T::Target: 'static + BroadcasterInterface,
 		ES::Target: 'static + EntropySource,
@@ -161,21 +322,36 @@ impl<'a, A: KVStore, M: Deref, T: Deref, ES: Deref, NS: Deref, SP: Deref, F: Der
 		F::Target: 'static + FeeEstimator,
 		R::Target: 'static + Router,
 		L::Target: 'static + Logger,
+		CC::Target: 'static + CurrencyConversion,
 {
 	/// Persist the given [`ChannelManager`] to disk, returning an error if persistence failed.
-	fn persist_manager(&self, channel_manager: &ChannelManager<M, T, ES, NS, SP, F, R, L>) -> Result<(), io::Error> {
+	///
+	/// The backups our peers handed us are persisted separately from the [`ChannelManager`] under
+	/// [`PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE`], and thus must be read via
+	/// [`read_peer_storage`] and loaded into the deserialized [`ChannelManager`] via
+	/// [`ChannelManager::load_peer_storage`].
+	fn persist_manager(&self, channel_manager: &ChannelManager<M, T, ES, NS, SP, F, R, L, CC>) -> Result<(), io::Error> {
 		self.write(CHANNEL_MANAGER_PERSISTENCE_PRIMARY_NAMESPACE,
 			CHANNEL_MANAGER_PERSISTENCE_SECONDARY_NAMESPACE,
 			CHANNEL_MANAGER_PERSISTENCE_KEY,
-			&channel_manager.encode())
+			&channel_manager.encode())?;
+
+		let updates = channel_manager.take_pending_peer_storage_updates();
+		let res = persist_peer_storage_updates(self, &updates);
+		if res.is_err() {
+			channel_manager.restore_pending_peer_storage_updates(updates);
+		}
+		res
 	}
 
 	/// Persist the given [`NetworkGraph`] to disk, returning an error if persistence failed.
+	///
+	/// Rather than re-serializing the full graph every time, only the [`NetworkGraphChanges`] since
+	/// it was last persisted are appended, with the full graph being persisted again every
+	/// [`MAX_NETWORK_GRAPH_CHANGES`] sets of changes. Thus, the graph must be read via
+	/// [`read_network_graph`].
 	fn persist_graph(&self, network_graph: &NetworkGraph<L>) -> Result<(), io::Error> {
-		self.write(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
-			NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE,
-			NETWORK_GRAPH_PERSISTENCE_KEY,
-			&network_graph.encode())
+		persist_network_graph(self, network_graph)
 	}
 
 	/// Persist the given [`WriteableScore`] to disk, returning an error if persistence failed.
@@ -273,6 +449,161 @@ where
 	Ok(res)
 }
 
+/// Persists the given updates taken via [`ChannelManager::take_pending_peer_storage_updates`] to
+/// the `kv_store`. See [`Persister::persist_manager`] for details.
+fn persist_peer_storage_updates<K: KVStore + ?Sized>(
+	kv_store: &K, updates: &[(PublicKey, Option<Vec<u8>>)],
+) -> Result<(), io::Error> {
+	for (node_id, peer_storage) in updates.iter() {
+		match peer_storage {
+			Some(data) => kv_store.write(PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE,
+				PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE, &node_id.to_string(), data)?,
+			None => kv_store.remove(PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE,
+				PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE, &node_id.to_string(), false)?,
+		}
+	}
+	Ok(())
+}
+
+/// Read the backups our peers handed us via [`PeerStorage`] messages, as previously persisted via
+/// the [`KVStore`] implementation of [`Persister`], to be loaded via
+/// [`ChannelManager::load_peer_storage`].
+///
+/// [`PeerStorage`]: crate::ln::msgs::PeerStorage
+pub fn read_peer_storage<K: Deref>(kv_store: K) -> Result<Vec<(PublicKey, Vec<u8>)>, io::Error>
+where
+	K::Target: KVStore,
+{
+	let mut res = Vec::new();
+	for stored_key in kv_store.list(
+		PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE, PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE)?
+	{
+		let node_id = PublicKey::from_str(&stored_key).map_err(|_| {
+			io::Error::new(io::ErrorKind::InvalidData, "Invalid node id in stored key")
+		})?;
+		let data = kv_store.read(PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE,
+			PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE, &stored_key)?;
+		res.push((node_id, data));
+	}
+	Ok(res)
+}
+
+/// Persists the changes to the given [`NetworkGraph`], or the full graph if due, to the
+/// `kv_store`. See [`Persister::persist_graph`] for details.
+fn persist_network_graph<K: KVStore + ?Sized, L: Deref>(
+	kv_store: &K, network_graph: &NetworkGraph<L>,
+) -> Result<(), io::Error> where L::Target: Logger {
+	let changes = network_graph.take_pending_changes();
+	let persist_full_graph = changes.deltas_since_full_persist()
+		.map_or(true, |deltas| deltas >= MAX_NETWORK_GRAPH_CHANGES);
+	if persist_full_graph {
+		if let Err(e) = kv_store.write(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE,
+			NETWORK_GRAPH_PERSISTENCE_KEY,
+			&network_graph.encode())
+		{
+			network_graph.restore_pending_changes(changes);
+			return Err(e);
+		}
+		network_graph.full_persist_completed(&changes);
+
+		// `read_network_graph` prefers any graph under the legacy key, which is only written by
+		// versions prior to persisting changes, so it has to go now that we persisted a newer one.
+		// If that fails we merely start from an older graph on restart, so we don't fail here.
+		let _ = kv_store.remove(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE, LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY, false);
+
+		// Any changes left over are ignored by `read_network_graph` as they predate the full
+		// graph, so we don't fail if we can't clean them up yet.
+		if let Ok(stored_keys) = kv_store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE)
+		{
+			for stored_key in stored_keys {
+				let _ = kv_store.remove(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+					NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE, &stored_key, true);
+			}
+		}
+		Ok(())
+	} else if changes.is_empty() {
+		Ok(())
+	} else {
+		let res = kv_store.write(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE,
+			&changes.sequence().to_string(),
+			&changes.encode());
+		if res.is_err() {
+			network_graph.restore_pending_changes(changes);
+		}
+		res
+	}
+}
+
+/// Read a [`NetworkGraph`] previously persisted via the [`KVStore`] implementation of
+/// [`Persister`], applying any [`NetworkGraphChanges`] persisted since the full graph.
+///
+/// If a graph persisted by a version of LDK prior to persisting [`NetworkGraphChanges`] is found
+/// under [`LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY`], it is read instead and any persisted changes are
+/// removed, as they were persisted on top of a different graph.
+pub fn read_network_graph<K: Deref, L: Deref>(
+	kv_store: K, logger: L,
+) -> Result<NetworkGraph<L>, io::Error>
+where
+	K::Target: KVStore,
+	L::Target: Logger,
+{
+	match kv_store.read(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
+		NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE, LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY)
+	{
+		Ok(legacy_graph) => {
+			let network_graph = NetworkGraph::read(&mut io::Cursor::new(legacy_graph), logger)
+				.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to read NetworkGraph"))?;
+			// Any changes were persisted on top of a graph we no longer read and would otherwise be
+			// applied on top of the one persisted next, so we have to remove them now.
+			for stored_key in kv_store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+				NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE)?
+			{
+				kv_store.remove(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+					NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE, &stored_key, false)?;
+			}
+			network_graph.require_full_persist();
+			return Ok(network_graph);
+		},
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+		Err(e) => return Err(e),
+	}
+
+	let network_graph = NetworkGraph::read(&mut io::Cursor::new(kv_store.read(
+		NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE, NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE,
+		NETWORK_GRAPH_PERSISTENCE_KEY)?), logger)
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to read NetworkGraph"))?;
+
+	let mut sequences = Vec::new();
+	for stored_key in kv_store.list(
+		NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE, NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE)?
+	{
+		let sequence: u64 = stored_key.parse().map_err(|_| {
+			io::Error::new(io::ErrorKind::InvalidData, "Invalid network graph changes sequence in stored key")
+		})?;
+		sequences.push((sequence, stored_key));
+	}
+	sequences.sort_unstable();
+
+	for (sequence, stored_key) in sequences {
+		let changes = NetworkGraphChanges::read(&mut io::Cursor::new(kv_store.read(
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE, &stored_key)?))
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to read NetworkGraphChanges"))?;
+		if changes.sequence() != sequence {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"NetworkGraphChanges were stored under the wrong key",
+			));
+		}
+		network_graph.apply_changes(changes);
+	}
+	Ok(network_graph)
+}
+
 /// Implements [`Persist`] in a way that writes and reads both [`ChannelMonitor`]s and
 /// [`ChannelMonitorUpdate`]s.
 ///
@@ -496,47 +827,12 @@ where
 	fn read_monitor(
 		&self, monitor_name: &MonitorName,
 	) -> Result<(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>), io::Error> {
-		let outpoint: OutPoint = monitor_name.try_into()?;
-		let mut monitor_cursor = io::Cursor::new(self.kv_store.read(
+		let monitor_bytes = self.kv_store.read(
 			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
 			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
 			monitor_name.as_str(),
-		)?);
-		// Discard the sentinel bytes if found.
-		if monitor_cursor.get_ref().starts_with(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL) {
-			monitor_cursor.set_position(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL.len() as u64);
-		}
-		match <(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>)>::read(
-			&mut monitor_cursor,
-			(&*self.entropy_source, &*self.signer_provider),
-		) {
-			Ok((blockhash, channel_monitor)) => {
-				if channel_monitor.get_funding_txo().0.txid != outpoint.txid
-					|| channel_monitor.get_funding_txo().0.index != outpoint.index
-				{
-					log_error!(
-						self.logger,
-						"ChannelMonitor {} was stored under the wrong key!",
-						monitor_name.as_str()
-					);
-					Err(io::Error::new(
-						io::ErrorKind::InvalidData,
-						"ChannelMonitor was stored under the wrong key",
-					))
-				} else {
-					Ok((blockhash, channel_monitor))
-				}
-			}
-			Err(e) => {
-				log_error!(
-					self.logger,
-					"Failed to read ChannelMonitor {}, reason: {}",
-					monitor_name.as_str(),
-					e,
-				);
-				Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to read ChannelMonitor"))
-			}
-		}
+		)?;
+		read_monitor_from_bytes(monitor_bytes, monitor_name, &self.entropy_source, &self.signer_provider, &self.logger)
 	}
 
 	/// Read a channel monitor update.
@@ -548,17 +844,7 @@ where
 			monitor_name.as_str(),
 			update_name.as_str(),
 		)?;
-		ChannelMonitorUpdate::read(&mut io::Cursor::new(update_bytes)).map_err(|e| {
-			log_error!(
-				self.logger,
-				"Failed to read ChannelMonitorUpdate {}/{}/{}, reason: {}",
-				CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
-				monitor_name.as_str(),
-				update_name.as_str(),
-				e,
-			);
-			io::Error::new(io::ErrorKind::InvalidData, "Failed to read ChannelMonitorUpdate")
-		})
+		read_monitor_update_from_bytes(update_bytes, monitor_name, update_name, &self.logger)
 	}
 
 	/// Cleans up stale updates for all monitors.
@@ -612,11 +898,7 @@ where
 		// Determine the proper key for this monitor
 		let monitor_name = MonitorName::from(funding_txo);
 		// Serialize and write the new monitor
-		let mut monitor_bytes = Vec::with_capacity(
-			MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL.len() + monitor.serialized_length(),
-		);
-		monitor_bytes.extend_from_slice(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL);
-		monitor.write(&mut monitor_bytes).unwrap();
+		let monitor_bytes = encode_monitor_with_sentinel(monitor);
 		match self.kv_store.write(
 			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
 			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
@@ -752,6 +1034,441 @@ where
 	}
 }
 
+/// Implements [`Persist`] on top of an [`AsyncKVStore`] in the same way as
+/// [`MonitorUpdatingPersister`] does on top of a [`KVStore`], storing [`ChannelMonitor`]s and
+/// [`ChannelMonitorUpdate`]s in exactly the same layout.
+///
+/// Rather than blocking until each write completes, this returns
+/// [`ChannelMonitorUpdateStatus::InProgress`] and drives the write to completion on a background
+/// task via the given [`FutureSpawner`]. Once a write completes the [`ChainMonitor`] is woken and
+/// handed the result through [`Persist::get_and_clear_completed_updates`], at which point the
+/// affected channel is unblocked.
+///
+/// If a write fails, it is reported as [`ChannelMonitorUpdateStatus::UnrecoverableError`], as
+/// [`MonitorUpdatingPersister`] would have returned for a failed synchronous write, causing the
+/// node to shut down. Any updates which did not complete will be replayed on startup.
+///
+/// See the [`MonitorUpdatingPersister`] documentation for details on the storage layout, reading
+/// monitors and pruning stale updates.
+///
+/// [`ChannelMonitorUpdateStatus::InProgress`]: chain::ChannelMonitorUpdateStatus::InProgress
+/// [`ChannelMonitorUpdateStatus::UnrecoverableError`]: chain::ChannelMonitorUpdateStatus::UnrecoverableError
+/// [`ChainMonitor`]: crate::chain::chainmonitor::ChainMonitor
+#[cfg(feature = "std")]
+pub struct MonitorUpdatingPersisterAsync<K: Deref, S: Deref, L: Deref, ES: Deref, SP: Deref>
+where
+	K::Target: AsyncKVStore,
+	S::Target: FutureSpawner,
+	L::Target: Logger,
+	ES::Target: EntropySource + Sized,
+	SP::Target: SignerProvider + Sized,
+{
+	kv_store: K,
+	future_spawner: S,
+	logger: L,
+	maximum_pending_updates: u64,
+	entropy_source: ES,
+	signer_provider: SP,
+	state: Arc<AsyncPersistState>,
+}
+
+/// The state of a [`MonitorUpdatingPersisterAsync`] shared with its background writes.
+#[cfg(feature = "std")]
+struct AsyncPersistState {
+	completed_updates: Mutex<Vec<(OutPoint, MonitorUpdateId, Result<(), io::Error>)>>,
+	/// The latest update IDs of the full [`ChannelMonitor`] writes still in flight for each
+	/// monitor, in the order they were started.
+	in_flight_monitor_writes: Mutex<HashMap<OutPoint, Vec<u64>>>,
+	completion_notifier: Mutex<Option<PersistCompletionNotifier>>,
+}
+
+#[cfg(feature = "std")]
+impl AsyncPersistState {
+	fn update_completed(&self, funding_txo: OutPoint, update_id: MonitorUpdateId, res: Result<(), io::Error>) {
+		self.completed_updates.lock().unwrap().push((funding_txo, update_id, res));
+		if let Some(notifier) = self.completion_notifier.lock().unwrap().as_ref() {
+			notifier.notify();
+		}
+	}
+
+	fn monitor_write_started(&self, funding_txo: OutPoint, latest_update_id: u64) {
+		self.in_flight_monitor_writes.lock().unwrap().entry(funding_txo).or_insert_with(Vec::new)
+			.push(latest_update_id);
+	}
+
+	/// Records that the full write of the monitor at `latest_update_id` finished, returning which
+	/// of the given stale updates may now be removed.
+	///
+	/// Stale updates are only removed once no write of an older copy of the monitor is still in
+	/// flight, ensuring we never remove updates which the monitor on disk may still require, even
+	/// if the older write were to land last.
+	fn monitor_write_completed(
+		&self, funding_txo: OutPoint, latest_update_id: u64, written: bool, cleanup: StaleUpdateCleanup,
+	) -> StaleUpdateCleanup {
+		let mut in_flight_monitor_writes = self.in_flight_monitor_writes.lock().unwrap();
+		let in_flight = match in_flight_monitor_writes.get_mut(&funding_txo) {
+			Some(in_flight) => in_flight,
+			None => {
+				debug_assert!(false, "Monitor writes are always tracked when started");
+				return StaleUpdateCleanup::None;
+			},
+		};
+		if let Some(pos) = in_flight.iter().position(|update_id| *update_id == latest_update_id) {
+			in_flight.remove(pos);
+		}
+		let older_write_in_flight = in_flight.iter().any(|update_id| *update_id < latest_update_id);
+		if in_flight.is_empty() {
+			in_flight_monitor_writes.remove(&funding_txo);
+		}
+		// Any updates we leave behind are never applied to the newer monitor, and can be cleaned
+		// up later.
+		if written && !older_write_in_flight { cleanup } else { StaleUpdateCleanup::None }
+	}
+}
+
+/// The stale [`ChannelMonitorUpdate`]s to remove once a full [`ChannelMonitor`] has been written.
+#[cfg(feature = "std")]
+enum StaleUpdateCleanup {
+	None,
+	/// The updates with IDs in the given inclusive range.
+	Range(u64, u64),
+	/// All updates stored for the monitor, used once the channel has been closed.
+	All,
+}
+
+#[cfg(feature = "std")]
+impl<K: Deref, S: Deref, L: Deref, ES: Deref, SP: Deref> MonitorUpdatingPersisterAsync<K, S, L, ES, SP>
+where
+	K::Target: AsyncKVStore,
+	S::Target: FutureSpawner,
+	L::Target: Logger,
+	ES::Target: EntropySource + Sized,
+	SP::Target: SignerProvider + Sized,
+{
+	/// Constructs a new [`MonitorUpdatingPersisterAsync`].
+	///
+	/// See [`MonitorUpdatingPersister::new`] for details on `maximum_pending_updates`.
+	pub fn new(
+		kv_store: K, future_spawner: S, logger: L, maximum_pending_updates: u64, entropy_source: ES,
+		signer_provider: SP,
+	) -> Self {
+		MonitorUpdatingPersisterAsync {
+			kv_store,
+			future_spawner,
+			logger,
+			maximum_pending_updates,
+			entropy_source,
+			signer_provider,
+			state: Arc::new(AsyncPersistState {
+				completed_updates: Mutex::new(Vec::new()),
+				in_flight_monitor_writes: Mutex::new(HashMap::new()),
+				completion_notifier: Mutex::new(None),
+			}),
+		}
+	}
+
+	/// Reads all stored channel monitors, along with any stored updates for them.
+	///
+	/// See [`MonitorUpdatingPersister::read_all_channel_monitors_with_updates`] for more details.
+	pub async fn read_all_channel_monitors_with_updates<B: Deref, F: Deref>(
+		&self, broadcaster: &B, fee_estimator: &F,
+	) -> Result<Vec<(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>)>, io::Error>
+	where
+		B::Target: BroadcasterInterface,
+		F::Target: FeeEstimator,
+	{
+		let monitor_list = self.kv_store.list(
+			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
+			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+		).await?;
+		let mut res = Vec::with_capacity(monitor_list.len());
+		for monitor_key in monitor_list {
+			res.push(self.read_channel_monitor_with_updates(
+				broadcaster,
+				fee_estimator,
+				monitor_key,
+			).await?)
+		}
+		Ok(res)
+	}
+
+	/// Read a single channel monitor, along with any stored updates for it.
+	///
+	/// See [`MonitorUpdatingPersister::read_channel_monitor_with_updates`] for more details.
+	pub async fn read_channel_monitor_with_updates<B: Deref, F: Deref>(
+		&self, broadcaster: &B, fee_estimator: &F, monitor_key: String,
+	) -> Result<(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>), io::Error>
+	where
+		B::Target: BroadcasterInterface,
+		F::Target: FeeEstimator,
+	{
+		let monitor_name = MonitorName::new(monitor_key)?;
+		let monitor_bytes = self.kv_store.read(
+			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
+			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+			monitor_name.as_str(),
+		).await?;
+		let (block_hash, monitor) = read_monitor_from_bytes(
+			monitor_bytes, &monitor_name, &self.entropy_source, &self.signer_provider, &self.logger)?;
+		let mut current_update_id = monitor.get_latest_update_id();
+		loop {
+			current_update_id = match current_update_id.checked_add(1) {
+				Some(next_update_id) => next_update_id,
+				None => break,
+			};
+			let update_name = UpdateName::from(current_update_id);
+			let update_bytes = match self.kv_store.read(
+				CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
+				monitor_name.as_str(),
+				update_name.as_str(),
+			).await {
+				Ok(update_bytes) => update_bytes,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => {
+					// We can't find any more updates, so we are done.
+					break;
+				}
+				Err(err) => return Err(err),
+			};
+			let update = read_monitor_update_from_bytes(update_bytes, &monitor_name, &update_name, &self.logger)?;
+
+			monitor.update_monitor(&update, broadcaster, fee_estimator, &self.logger)
+				.map_err(|e| {
+					log_error!(
+						self.logger,
+						"Monitor update failed. monitor: {} update: {} reason: {:?}",
+						monitor_name.as_str(),
+						update_name.as_str(),
+						e
+					);
+					io::Error::new(io::ErrorKind::Other, "Monitor update failed")
+				})?;
+		}
+		Ok((block_hash, monitor))
+	}
+}
+
+#[cfg(feature = "std")]
+impl<K: Deref + Clone + Send + 'static, S: Deref, L: Deref, ES: Deref, SP: Deref> MonitorUpdatingPersisterAsync<K, S, L, ES, SP>
+where
+	K::Target: AsyncKVStore,
+	S::Target: FutureSpawner,
+	L::Target: Logger,
+	ES::Target: EntropySource + Sized,
+	SP::Target: SignerProvider + Sized,
+{
+	/// Spawns a write of the full `monitor`, removing the given stale updates once it completes.
+	fn spawn_monitor_write<ChannelSigner: WriteableEcdsaChannelSigner>(
+		&self, funding_txo: OutPoint, monitor: &ChannelMonitor<ChannelSigner>,
+		monitor_update_call_id: MonitorUpdateId, cleanup: StaleUpdateCleanup,
+	) {
+		let monitor_name = MonitorName::from(funding_txo);
+		let latest_update_id = monitor.get_latest_update_id();
+		self.state.monitor_write_started(funding_txo, latest_update_id);
+		let write_future = self.kv_store.write(
+			CHANNEL_MONITOR_PERSISTENCE_PRIMARY_NAMESPACE,
+			CHANNEL_MONITOR_PERSISTENCE_SECONDARY_NAMESPACE,
+			monitor_name.as_str(),
+			encode_monitor_with_sentinel(monitor),
+		);
+		let kv_store = self.kv_store.clone();
+		let state = Arc::clone(&self.state);
+		self.future_spawner.spawn(async move {
+			let res = write_future.await;
+			let cleanup = state.monitor_write_completed(funding_txo, latest_update_id, res.is_ok(), cleanup);
+			state.update_completed(funding_txo, monitor_update_call_id, res);
+			// Stale updates are never applied to the newer monitor, so failing to remove any of
+			// them is harmless, and they can be cleaned up later via `cleanup_stale_updates`.
+			let stale_updates: Vec<String> = match cleanup {
+				StaleUpdateCleanup::None => return,
+				StaleUpdateCleanup::Range(start, end) => {
+					(start..=end).map(|update_id| UpdateName::from(update_id).1).collect()
+				},
+				StaleUpdateCleanup::All => {
+					let list_future = kv_store.list(
+						CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE, monitor_name.as_str());
+					list_future.await.unwrap_or_default()
+				},
+			};
+			for update_name in stale_updates {
+				let remove_future = kv_store.remove(
+					CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
+					monitor_name.as_str(),
+					&update_name,
+					true,
+				);
+				let _ = remove_future.await;
+			}
+		});
+	}
+}
+
+#[cfg(feature = "std")]
+impl<ChannelSigner: WriteableEcdsaChannelSigner, K: Deref + Clone + Send + 'static, S: Deref, L: Deref, ES: Deref, SP: Deref>
+	Persist<ChannelSigner> for MonitorUpdatingPersisterAsync<K, S, L, ES, SP>
+where
+	K::Target: AsyncKVStore,
+	S::Target: FutureSpawner,
+	L::Target: Logger,
+	ES::Target: EntropySource + Sized,
+	SP::Target: SignerProvider + Sized,
+{
+	/// Persists a new channel by writing the entire monitor in the background.
+	fn persist_new_channel(
+		&self, funding_txo: OutPoint, monitor: &ChannelMonitor<ChannelSigner>,
+		monitor_update_call_id: MonitorUpdateId,
+	) -> chain::ChannelMonitorUpdateStatus {
+		self.spawn_monitor_write(funding_txo, monitor, monitor_update_call_id, StaleUpdateCleanup::None);
+		chain::ChannelMonitorUpdateStatus::InProgress
+	}
+
+	/// Persists a channel update in the background, writing only the update if possible.
+	///
+	/// The full monitor is written in the same cases as for
+	/// [`MonitorUpdatingPersister::update_persisted_channel`].
+	fn update_persisted_channel(
+		&self, funding_txo: OutPoint, update: Option<&ChannelMonitorUpdate>,
+		monitor: &ChannelMonitor<ChannelSigner>, monitor_update_call_id: MonitorUpdateId,
+	) -> chain::ChannelMonitorUpdateStatus {
+		match update {
+			Some(update) if update.update_id != CLOSED_CHANNEL_UPDATE_ID
+				&& update.update_id % self.maximum_pending_updates != 0 =>
+			{
+				let monitor_name = MonitorName::from(funding_txo);
+				let update_name = UpdateName::from(update.update_id);
+				let write_future = self.kv_store.write(
+					CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
+					monitor_name.as_str(),
+					update_name.as_str(),
+					update.encode(),
+				);
+				let state = Arc::clone(&self.state);
+				self.future_spawner.spawn(async move {
+					let res = write_future.await;
+					state.update_completed(funding_txo, monitor_update_call_id, res);
+				});
+			},
+			Some(_) => {
+				// We could write this update, but it meets criteria of our design that calls for a
+				// full monitor write.
+				let cleanup = if monitor.get_latest_update_id() == CLOSED_CHANNEL_UPDATE_ID {
+					StaleUpdateCleanup::All
+				} else {
+					let end = monitor.get_latest_update_id();
+					StaleUpdateCleanup::Range(end.saturating_sub(self.maximum_pending_updates), end)
+				};
+				self.spawn_monitor_write(funding_txo, monitor, monitor_update_call_id, cleanup);
+			},
+			None => {
+				// There is no update given, so we must persist a new monitor.
+				self.spawn_monitor_write(funding_txo, monitor, monitor_update_call_id, StaleUpdateCleanup::None);
+			},
+		}
+		chain::ChannelMonitorUpdateStatus::InProgress
+	}
+
+	fn get_and_clear_completed_updates(&self) -> Vec<(OutPoint, MonitorUpdateId, chain::ChannelMonitorUpdateStatus)> {
+		let completed_updates = mem::take(&mut *self.state.completed_updates.lock().unwrap());
+		completed_updates.into_iter().map(|(funding_txo, update_id, res)| match res {
+			Ok(()) => (funding_txo, update_id, chain::ChannelMonitorUpdateStatus::Completed),
+			Err(e) => {
+				log_error!(
+					self.logger,
+					"Failed to persist ChannelMonitor {} in the background, reason: {}",
+					MonitorName::from(funding_txo).as_str(),
+					e
+				);
+				(funding_txo, update_id, chain::ChannelMonitorUpdateStatus::UnrecoverableError)
+			},
+		}).collect()
+	}
+
+	fn register_completion_notifier(&self, notifier: PersistCompletionNotifier) {
+		*self.state.completion_notifier.lock().unwrap() = Some(notifier);
+	}
+}
+
+/// Serializes a [`ChannelMonitor`] prefixed with [`MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL`].
+fn encode_monitor_with_sentinel<ChannelSigner: WriteableEcdsaChannelSigner>(
+	monitor: &ChannelMonitor<ChannelSigner>,
+) -> Vec<u8> {
+	let mut monitor_bytes = Vec::with_capacity(
+		MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL.len() + monitor.serialized_length(),
+	);
+	monitor_bytes.extend_from_slice(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL);
+	monitor.write(&mut monitor_bytes).unwrap();
+	monitor_bytes
+}
+
+/// Deserializes a [`ChannelMonitor`] as written by [`encode_monitor_with_sentinel`], checking
+/// that it was stored under the expected name.
+fn read_monitor_from_bytes<ES: Deref, SP: Deref, L: Deref>(
+	monitor_bytes: Vec<u8>, monitor_name: &MonitorName, entropy_source: &ES, signer_provider: &SP,
+	logger: &L,
+) -> Result<(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>), io::Error>
+where
+	ES::Target: EntropySource + Sized,
+	SP::Target: SignerProvider + Sized,
+	L::Target: Logger,
+{
+	let outpoint: OutPoint = monitor_name.try_into()?;
+	let mut monitor_cursor = io::Cursor::new(monitor_bytes);
+	// Discard the sentinel bytes if found.
+	if monitor_cursor.get_ref().starts_with(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL) {
+		monitor_cursor.set_position(MONITOR_UPDATING_PERSISTER_PREPEND_SENTINEL.len() as u64);
+	}
+	match <(BlockHash, ChannelMonitor<<SP::Target as SignerProvider>::EcdsaSigner>)>::read(
+		&mut monitor_cursor,
+		(&**entropy_source, &**signer_provider),
+	) {
+		Ok((blockhash, channel_monitor)) => {
+			if channel_monitor.get_funding_txo().0.txid != outpoint.txid
+				|| channel_monitor.get_funding_txo().0.index != outpoint.index
+			{
+				log_error!(
+					logger,
+					"ChannelMonitor {} was stored under the wrong key!",
+					monitor_name.as_str()
+				);
+				Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"ChannelMonitor was stored under the wrong key",
+				))
+			} else {
+				Ok((blockhash, channel_monitor))
+			}
+		}
+		Err(e) => {
+			log_error!(
+				logger,
+				"Failed to read ChannelMonitor {}, reason: {}",
+				monitor_name.as_str(),
+				e,
+			);
+			Err(io::Error::new(io::ErrorKind::InvalidData, "Failed to read ChannelMonitor"))
+		}
+	}
+}
+
+/// Deserializes a [`ChannelMonitorUpdate`] stored under the given names.
+fn read_monitor_update_from_bytes<L: Deref>(
+	update_bytes: Vec<u8>, monitor_name: &MonitorName, update_name: &UpdateName, logger: &L,
+) -> Result<ChannelMonitorUpdate, io::Error>
+where
+	L::Target: Logger,
+{
+	ChannelMonitorUpdate::read(&mut io::Cursor::new(update_bytes)).map_err(|e| {
+		log_error!(
+			logger,
+			"Failed to read ChannelMonitorUpdate {}/{}/{}, reason: {}",
+			CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE,
+			monitor_name.as_str(),
+			update_name.as_str(),
+			e,
+		);
+		io::Error::new(io::ErrorKind::InvalidData, "Failed to read ChannelMonitorUpdate")
+	})
+}
+
 /// A struct representing a name for a monitor.
 #[derive(Debug)]
 struct MonitorName(String);
@@ -843,8 +1560,10 @@ mod tests {
 	use crate::chain::ChannelMonitorUpdateStatus;
 	use crate::events::{ClosureReason, MessageSendEventsProvider};
 	use crate::ln::functional_test_utils::*;
+	use crate::routing::gossip::NodeId;
 	use crate::util::test_utils::{self, TestLogger, TestStore};
-	use crate::{check_added_monitors, check_closed_broadcast};
+	use crate::util::metrics::IgnoringMetricsRecorder;
+	use crate::{check_added_monitors, check_closed_broadcast, get_monitor};
 
 	const EXPECTED_UPDATES_PER_PAYMENT: u64 = 5;
 
@@ -883,6 +1602,156 @@ mod tests {
 		assert!(MonitorName::new("deadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeef_21".to_string()).is_err());
 	}
 
+	fn channel_update(short_channel_id: u64, flags: u8, timestamp: u32) -> crate::ln::msgs::UnsignedChannelUpdate {
+		crate::ln::msgs::UnsignedChannelUpdate {
+			chain_hash: bitcoin::blockdata::constants::ChainHash::using_genesis_block(bitcoin::Network::Testnet),
+			short_channel_id,
+			timestamp,
+			flags,
+			cltv_expiry_delta: 144,
+			htlc_minimum_msat: 1000,
+			htlc_maximum_msat: 1_000_000,
+			fee_base_msat: timestamp,
+			fee_proportional_millionths: 100,
+			excess_data: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn persists_peer_storage() {
+		let store = TestStore::new(false);
+		let secp_ctx = bitcoin::secp256k1::Secp256k1::new();
+		let node_ids: Vec<_> = (1..=2u8).map(|i| PublicKey::from_secret_key(
+			&secp_ctx, &bitcoin::secp256k1::SecretKey::from_slice(&[i; 32]).unwrap())).collect();
+
+		persist_peer_storage_updates(&store, &[(node_ids[0], Some(vec![1; 32])), (node_ids[1], Some(vec![2; 64]))]).unwrap();
+		let mut peer_storage = read_peer_storage(&store).unwrap();
+		peer_storage.sort_unstable();
+		assert_eq!(peer_storage, vec![(node_ids[0], vec![1; 32]), (node_ids[1], vec![2; 64])]);
+
+		// Backups we no longer store are removed, and removing a missing backup succeeds.
+		persist_peer_storage_updates(&store, &[(node_ids[0], None), (node_ids[0], None)]).unwrap();
+		assert_eq!(read_peer_storage(&store).unwrap(), vec![(node_ids[1], vec![2; 64])]);
+
+		// Keys which aren't a valid node id are rejected.
+		store.write(PEER_STORAGE_PERSISTENCE_PRIMARY_NAMESPACE,
+			PEER_STORAGE_PERSISTENCE_SECONDARY_NAMESPACE, "invalid", &[0; 32]).unwrap();
+		assert_eq!(read_peer_storage(&store).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+		// Failures to persist are surfaced.
+		let read_only_store = TestStore::new(true);
+		assert!(persist_peer_storage_updates(&read_only_store, &[(node_ids[0], Some(vec![1; 32]))]).is_err());
+	}
+
+	#[test]
+	fn persists_network_graph_changes() {
+		let logger = TestLogger::new();
+		let store = TestStore::new(false);
+		let secp_ctx = bitcoin::secp256k1::Secp256k1::new();
+		let node_ids: Vec<_> = (1..=4u8).map(|i| bitcoin::secp256k1::PublicKey::from_secret_key(
+			&secp_ctx, &bitcoin::secp256k1::SecretKey::from_slice(&[i; 32]).unwrap())).collect();
+		let features = crate::ln::features::ChannelFeatures::empty();
+
+		let network_graph = NetworkGraph::new(bitcoin::Network::Testnet, &logger);
+		network_graph.add_channel_from_partial_announcement(1, 0, features.clone(), node_ids[0], node_ids[1]).unwrap();
+		network_graph.update_channel_unsigned(&channel_update(1, 0, 100)).unwrap();
+
+		// The first time, the full graph is persisted.
+		persist_network_graph(&store, &network_graph).unwrap();
+		assert!(store.read(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE, NETWORK_GRAPH_PERSISTENCE_KEY).is_ok());
+		assert!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().is_empty());
+
+		// Nothing is written if nothing changed.
+		persist_network_graph(&store, &network_graph).unwrap();
+		assert!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().is_empty());
+
+		// Afterwards, only the changes are persisted.
+		network_graph.update_channel_unsigned(&channel_update(1, 1, 101)).unwrap();
+		network_graph.add_channel_from_partial_announcement(2, 0, features.clone(), node_ids[1], node_ids[2]).unwrap();
+		persist_network_graph(&store, &network_graph).unwrap();
+		network_graph.channel_failed_permanent(1);
+		network_graph.add_channel_from_partial_announcement(3, 0, features.clone(), node_ids[2], node_ids[3]).unwrap();
+		network_graph.set_last_rapid_gossip_sync_timestamp(42);
+		persist_network_graph(&store, &network_graph).unwrap();
+		assert_eq!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().len(), 2);
+
+		let read_graph = read_network_graph(&store, &logger).unwrap();
+		assert!(read_graph == network_graph);
+		assert_eq!(read_graph.get_last_rapid_gossip_sync_timestamp(), Some(42));
+		assert!(read_graph.read_only().channel(1).is_none());
+		assert!(read_graph.read_only().node(&NodeId::from_pubkey(&node_ids[0])).is_none());
+
+		// Changes to the read graph are appended to the existing ones until they're compacted.
+		for timestamp in 102..102 + MAX_NETWORK_GRAPH_CHANGES as u32 - 3 {
+			read_graph.update_channel_unsigned(&channel_update(2, 0, timestamp)).unwrap();
+			persist_network_graph(&store, &read_graph).unwrap();
+		}
+		assert_eq!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().len() as u64, MAX_NETWORK_GRAPH_CHANGES - 1);
+		read_graph.update_channel_unsigned(&channel_update(3, 0, 1000)).unwrap();
+		persist_network_graph(&store, &read_graph).unwrap();
+		assert!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().is_empty());
+		assert!(read_network_graph(&store, &logger).unwrap() == read_graph);
+	}
+
+	#[test]
+	fn restores_network_graph_changes_on_write_failure() {
+		let logger = TestLogger::new();
+		let store = TestStore::new(false);
+		let read_only_store = TestStore::new(true);
+		let secp_ctx = bitcoin::secp256k1::Secp256k1::new();
+		let node_ids: Vec<_> = (1..=2u8).map(|i| bitcoin::secp256k1::PublicKey::from_secret_key(
+			&secp_ctx, &bitcoin::secp256k1::SecretKey::from_slice(&[i; 32]).unwrap())).collect();
+
+		let network_graph = NetworkGraph::new(bitcoin::Network::Testnet, &logger);
+		persist_network_graph(&store, &network_graph).unwrap();
+
+		network_graph.add_channel_from_partial_announcement(
+			1, 0, crate::ln::features::ChannelFeatures::empty(), node_ids[0], node_ids[1]).unwrap();
+		assert!(persist_network_graph(&read_only_store, &network_graph).is_err());
+
+		// The changes which failed to be persisted are included in the next attempt.
+		persist_network_graph(&store, &network_graph).unwrap();
+		assert!(read_network_graph(&store, &logger).unwrap() == network_graph);
+	}
+
+	#[test]
+	fn migrates_legacy_network_graph() {
+		let logger = TestLogger::new();
+		let store = TestStore::new(false);
+		let secp_ctx = bitcoin::secp256k1::Secp256k1::new();
+		let node_ids: Vec<_> = (1..=2u8).map(|i| bitcoin::secp256k1::PublicKey::from_secret_key(
+			&secp_ctx, &bitcoin::secp256k1::SecretKey::from_slice(&[i; 32]).unwrap())).collect();
+		let features = crate::ln::features::ChannelFeatures::empty();
+
+		let network_graph = NetworkGraph::new(bitcoin::Network::Testnet, &logger);
+		persist_network_graph(&store, &network_graph).unwrap();
+		network_graph.add_channel_from_partial_announcement(1, 0, features.clone(), node_ids[0], node_ids[1]).unwrap();
+		persist_network_graph(&store, &network_graph).unwrap();
+
+		// A graph written by a version which doesn't persist changes, e.g. after a downgrade, is
+		// read in place of ours and any changes persisted on top of ours are dropped.
+		let legacy_graph = NetworkGraph::new(bitcoin::Network::Testnet, &logger);
+		legacy_graph.add_channel_from_partial_announcement(2, 0, features.clone(), node_ids[0], node_ids[1]).unwrap();
+		store.write(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE, NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE,
+			LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY, &legacy_graph.encode()).unwrap();
+		let read_graph = read_network_graph(&store, &logger).unwrap();
+		assert!(read_graph == legacy_graph);
+		assert!(store.list(NETWORK_GRAPH_CHANGES_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_CHANGES_PERSISTENCE_SECONDARY_NAMESPACE).unwrap().is_empty());
+
+		// Once persisted in full again, the legacy graph is removed.
+		persist_network_graph(&store, &read_graph).unwrap();
+		assert!(store.read(NETWORK_GRAPH_PERSISTENCE_PRIMARY_NAMESPACE,
+			NETWORK_GRAPH_PERSISTENCE_SECONDARY_NAMESPACE, LEGACY_NETWORK_GRAPH_PERSISTENCE_KEY).is_err());
+		assert!(read_network_graph(&store, &logger).unwrap() == legacy_graph);
+	}
+
 	// Exercise the `MonitorUpdatingPersister` with real channels and payments.
 	#[test]
 	fn persister_with_real_monitors() {
@@ -1185,4 +2054,170 @@ mod tests {
 			.read(CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE, monitor_name.as_str(), UpdateName::from(u64::MAX - 1).as_str())
 			.is_err());
 	}
+
+	/// Queues spawned futures until they're explicitly driven via [`Self::poll_futures`].
+	#[cfg(feature = "std")]
+	struct TestFutureSpawner {
+		futures: Mutex<Vec<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+	}
+
+	#[cfg(feature = "std")]
+	impl TestFutureSpawner {
+		fn new() -> Self {
+			Self { futures: Mutex::new(Vec::new()) }
+		}
+
+		fn poll_futures(&self) {
+			let futures = mem::take(&mut *self.futures.lock().unwrap());
+			for mut future in futures {
+				if poll_once(future.as_mut()).is_pending() {
+					self.futures.lock().unwrap().push(future);
+				}
+			}
+		}
+	}
+
+	#[cfg(feature = "std")]
+	impl FutureSpawner for TestFutureSpawner {
+		fn spawn<F: Future<Output = ()> + Send + 'static>(&self, future: F) {
+			self.futures.lock().unwrap().push(Box::pin(future));
+		}
+	}
+
+	#[cfg(feature = "std")]
+	fn poll_once<F: Future + ?Sized>(future: Pin<&mut F>) -> core::task::Poll<F::Output> {
+		use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+		const NOOP_WAKER_V_TABLE: RawWakerVTable = RawWakerVTable::new(|_| RawWaker::new(core::ptr::null(), &NOOP_WAKER_V_TABLE), |_| {}, |_| {}, |_| {});
+		let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &NOOP_WAKER_V_TABLE)) };
+		future.poll(&mut Context::from_waker(&waker))
+	}
+
+	// Exercise the `MonitorUpdatingPersisterAsync` with a real monitor and the updates from real
+	// payments, checking that updates only complete once their writes have been driven.
+	#[test]
+	#[cfg(feature = "std")]
+	fn async_persister_with_real_monitors() {
+		use crate::chain::Watch;
+		use crate::chain::chainmonitor::ChainMonitor;
+		use crate::chain::channelmonitor::MonitorEvent;
+		use crate::util::test_channel_signer::TestChannelSigner;
+
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let (_, _, channel_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+		let funding_txo = OutPoint { txid: funding_tx.txid(), index: 0 };
+
+		// Take a copy of node 0's monitor as of the channel opening, replaying the updates from a
+		// few payments on top of it below.
+		let monitor = <(BlockHash, ChannelMonitor<TestChannelSigner>)>::read(
+			&mut io::Cursor::new(&get_monitor!(nodes[0], channel_id).encode()),
+			(&chanmon_cfgs[0].keys_manager, &chanmon_cfgs[0].keys_manager),
+		).unwrap().1;
+		let initial_update_id = monitor.get_latest_update_id();
+		for _ in 0..3 {
+			send_payment(&nodes[0], &[&nodes[1]], 1_000_000);
+		}
+		let updates: Vec<ChannelMonitorUpdate> = nodes[0].chain_monitor.monitor_updates.lock().unwrap()
+			.get(&channel_id).unwrap().iter()
+			.filter(|update| update.update_id > initial_update_id)
+			.cloned().collect();
+		assert_eq!(updates.len() as u64, 3 * EXPECTED_UPDATES_PER_PAYMENT);
+		let latest_update_id = updates.last().unwrap().update_id;
+
+		let maximum_pending_updates = 4;
+		let kv_store = Arc::new(KVStoreSyncWrapper(Arc::new(TestStore::new(false))));
+		let future_spawner = TestFutureSpawner::new();
+		let logger = TestLogger::new();
+		let persister = MonitorUpdatingPersisterAsync::new(
+			Arc::clone(&kv_store), &future_spawner, &logger, maximum_pending_updates,
+			&chanmon_cfgs[0].keys_manager, &chanmon_cfgs[0].keys_manager,
+		);
+		let chain_monitor = ChainMonitor::new(
+			None::<&test_utils::TestChainSource>, &chanmon_cfgs[0].tx_broadcaster, &logger,
+			&chanmon_cfgs[0].fee_estimator, &persister, IgnoringMetricsRecorder {},
+		);
+
+		// The new monitor isn't completed until its write has been driven.
+		assert_eq!(chain_monitor.watch_channel(funding_txo, monitor), Ok(ChannelMonitorUpdateStatus::InProgress));
+		assert!(chain_monitor.release_pending_monitor_events().is_empty());
+		future_spawner.poll_futures();
+		// Completing the write wakes the `ChainMonitor`.
+		assert!(chain_monitor.get_update_future().poll_is_complete());
+		let monitor_events = chain_monitor.release_pending_monitor_events();
+		assert_eq!(monitor_events.len(), 1);
+		match &monitor_events[0].1[..] {
+			[MonitorEvent::Completed { monitor_update_id, .. }] => assert_eq!(*monitor_update_id, initial_update_id),
+			_ => panic!("Unexpected monitor events"),
+		}
+
+		// Nor are any of the updates, and once their writes are driven only a single completion
+		// event is generated for the latest update.
+		for update in updates.iter() {
+			assert_eq!(chain_monitor.update_channel(funding_txo, update), ChannelMonitorUpdateStatus::InProgress);
+		}
+		assert!(chain_monitor.release_pending_monitor_events().is_empty());
+		future_spawner.poll_futures();
+		let monitor_events = chain_monitor.release_pending_monitor_events();
+		assert_eq!(monitor_events.len(), 1);
+		match &monitor_events[0].1[..] {
+			[MonitorEvent::Completed { monitor_update_id, .. }] => assert_eq!(*monitor_update_id, latest_update_id),
+			_ => panic!("Unexpected monitor events"),
+		}
+
+		// Reading the monitor back applies the updates stored since the last full monitor write,
+		// with all older updates having been cleaned up.
+		let persisted_monitors = poll_once(Box::pin(persister.read_all_channel_monitors_with_updates(
+			&&chanmon_cfgs[0].tx_broadcaster, &&chanmon_cfgs[0].fee_estimator,
+		)).as_mut());
+		let persisted_monitors = match persisted_monitors {
+			core::task::Poll::Ready(res) => res.unwrap(),
+			core::task::Poll::Pending => panic!("Reads from a synchronous store should complete immediately"),
+		};
+		assert_eq!(persisted_monitors.len(), 1);
+		assert_eq!(persisted_monitors[0].1.get_latest_update_id(), latest_update_id);
+		let stored_updates = kv_store.0.list(
+			CHANNEL_MONITOR_UPDATE_PERSISTENCE_PRIMARY_NAMESPACE, MonitorName::from(funding_txo).as_str()).unwrap();
+		assert_eq!(stored_updates.len() as u64, latest_update_id % maximum_pending_updates);
+	}
+
+	// A failed background write is surfaced as an unrecoverable error once the `ChainMonitor`
+	// learns of it.
+	#[test]
+	#[cfg(feature = "std")]
+	#[should_panic(expected = "ChannelMonitor[Update] persistence failed unrecoverably")]
+	fn async_persister_failed_write() {
+		use crate::chain::Watch;
+		use crate::chain::chainmonitor::ChainMonitor;
+
+		let chanmon_cfgs = create_chanmon_cfgs(2);
+		let node_cfgs = create_node_cfgs(2, &chanmon_cfgs);
+		let node_chanmgrs = create_node_chanmgrs(2, &node_cfgs, &[None, None]);
+		let nodes = create_network(2, &node_cfgs, &node_chanmgrs);
+		let (_, _, channel_id, funding_tx) = create_announced_chan_between_nodes(&nodes, 0, 1);
+		let funding_txo = OutPoint { txid: funding_tx.txid(), index: 0 };
+		let monitor = <(BlockHash, ChannelMonitor<crate::util::test_channel_signer::TestChannelSigner>)>::read(
+			&mut io::Cursor::new(&get_monitor!(nodes[0], channel_id).encode()),
+			(&chanmon_cfgs[0].keys_manager, &chanmon_cfgs[0].keys_manager),
+		).unwrap().1;
+
+		// A read-only store fails all writes.
+		let kv_store = Arc::new(KVStoreSyncWrapper(Arc::new(TestStore::new(true))));
+		let future_spawner = TestFutureSpawner::new();
+		let logger = TestLogger::new();
+		let persister = MonitorUpdatingPersisterAsync::new(
+			kv_store, &future_spawner, &logger, 4,
+			&chanmon_cfgs[0].keys_manager, &chanmon_cfgs[0].keys_manager,
+		);
+		let chain_monitor = ChainMonitor::new(
+			None::<&test_utils::TestChainSource>, &chanmon_cfgs[0].tx_broadcaster, &logger,
+			&chanmon_cfgs[0].fee_estimator, &persister, IgnoringMetricsRecorder {},
+		);
+
+		assert_eq!(chain_monitor.watch_channel(funding_txo, monitor), Ok(ChannelMonitorUpdateStatus::InProgress));
+		future_spawner.poll_futures();
+		assert!(chain_monitor.get_update_future().poll_is_complete());
+		chain_monitor.release_pending_monitor_events();
+	}
 }
@@ -321,6 +321,12 @@ impl<A, T: ReadableArgs<A>> ReadableArgs<A> for RequiredWrapper<T> {
 		Ok(Self(Some(ReadableArgs::read(reader, args)?)))
 	}
 }
+impl<T: LengthReadable> LengthReadable for RequiredWrapper<T> {
+	#[inline]
+	fn read<R: LengthRead>(reader: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self(Some(LengthReadable::read(reader)?)))
+	}
+}
 /// When handling `default_values`, we want to map the default-value T directly
 /// to a `RequiredWrapper<T>` in a way that works for `field: T = t;` as
 /// well. Thus, we assume `Into<T> for T` does nothing and use that.
@@ -850,6 +856,7 @@ impl_for_vec!(crate::ln::channelmanager::MonitorUpdateCompletionAction);
 impl_for_vec!((A, B), A, B);
 impl_writeable_for_vec!(&crate::routing::router::BlindedTail);
 impl_readable_for_vec!(crate::routing::router::BlindedTail);
+impl_for_vec!(crate::routing::router::TrampolineHop);
 
 impl Writeable for Vec<Witness> {
 	#[inline]
@@ -1407,6 +1414,11 @@ impl TransactionU16LenLimited {
 	pub fn into_transaction(self) -> Transaction {
 		self.0
 	}
+
+	/// Returns a reference to the contained `Transaction`.
+	pub fn as_transaction(&self) -> &Transaction {
+		&self.0
+	}
 }
 
 impl Writeable for TransactionU16LenLimited {
@@ -44,6 +44,9 @@ macro_rules! _encode_tlv {
 		BigSize($field.serialized_length() as u64).write($stream)?;
 		$field.write($stream)?;
 	};
+	($stream: expr, $type: expr, $field: expr, (required: $trait: ident $(, $read_arg: expr)?)) => {
+		$crate::_encode_tlv!($stream, $type, $field, required);
+	};
 	($stream: expr, $type: expr, $field: expr, required_vec) => {
 		$crate::_encode_tlv!($stream, $type, $crate::util::ser::WithoutLength(&$field), required);
 	};
@@ -211,6 +214,9 @@ macro_rules! _get_varint_length_prefixed_tlv_length {
 			$crate::_get_varint_length_prefixed_tlv_length!($len, $type, $field, required_vec);
 		}
 	};
+	($len: expr, $type: expr, $field: expr, (required: $trait: ident $(, $read_arg: expr)?)) => {
+		$crate::_get_varint_length_prefixed_tlv_length!($len, $type, $field, required);
+	};
 	($len: expr, $type: expr, $field: expr, (option: $trait: ident $(, $read_arg: expr)?)) => {
 		$crate::_get_varint_length_prefixed_tlv_length!($len, $type, $field, option);
 	};
@@ -760,6 +766,9 @@ macro_rules! _init_tlv_based_struct_field {
 	($field: ident, required) => {
 		$field.0.unwrap()
 	};
+	($field: ident, (required: $trait: ident $(, $read_arg: expr)?)) => {
+		$crate::_init_tlv_based_struct_field!($field, required)
+	};
 	($field: ident, required_vec) => {
 		$field
 	};
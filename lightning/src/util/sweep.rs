@@ -0,0 +1,790 @@
+// This file is Copyright its original authors, visible in version control
+// history.
+//
+// This file is licensed under the Apache License, Version 2.0 <LICENSE-APACHE
+// or http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// You may not use this file except in accordance with one or both of these
+// licenses.
+
+//! This module contains an [`OutputSweeper`] utility that keeps track of
+//! [`SpendableOutputDescriptor`]s, i.e., persists them in a given [`KVStore`] and regularly retries
+//! sweeping them.
+
+use crate::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use crate::chain::channelmonitor::ANTI_REORG_DELAY;
+use crate::chain::transaction::TransactionData;
+use crate::chain::{BestBlock, Confirm, Filter, Listen, WatchedOutput};
+use crate::io;
+use crate::ln::ChannelId;
+use crate::ln::msgs::DecodeError;
+use crate::sign::{ChangeDestinationSource, OutputSpender, SpendableOutputDescriptor};
+use crate::sync::Mutex;
+use crate::util::logger::Logger;
+use crate::util::persist::{
+	KVStore, OUTPUT_SWEEPER_PERSISTENCE_KEY, OUTPUT_SWEEPER_PERSISTENCE_PRIMARY_NAMESPACE,
+	OUTPUT_SWEEPER_PERSISTENCE_SECONDARY_NAMESPACE,
+};
+use crate::util::ser::{Readable, ReadableArgs, Writeable};
+
+use bitcoin::blockdata::block::Header;
+use bitcoin::blockdata::locktime::absolute::LockTime;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{BlockHash, Transaction, Txid};
+
+use crate::prelude::*;
+use core::cmp;
+use core::ops::Deref;
+
+/// The number of blocks we wait for a spending transaction to confirm before we regenerate it with
+/// a bumped feerate, replacing the previous one.
+pub const FEE_BUMP_INTERVAL_BLOCKS: u32 = 6;
+
+/// The minimum feerate increase (in satoshis per 1000 weight units) we apply when replacing a
+/// previously broadcast spending transaction, matching Bitcoin Core's default incremental relay
+/// fee.
+const MIN_FEERATE_BUMP_SAT_PER_1000_WEIGHT: u32 = 253;
+
+/// The state of a spendable output currently tracked by an [`OutputSweeper`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedSpendableOutput {
+	/// The tracked output descriptor.
+	pub descriptor: SpendableOutputDescriptor,
+	/// The channel this output belongs to.
+	///
+	/// Will be `None` if no `channel_id` was given to [`OutputSweeper::track_spendable_outputs`]
+	pub channel_id: Option<ChannelId>,
+	/// The current status of the output spend.
+	pub status: OutputSpendStatus,
+}
+
+impl TrackedSpendableOutput {
+	fn is_spent_in(&self, tx: &Transaction) -> bool {
+		let prev_outpoint = self.descriptor.outpoint().into_bitcoin_outpoint();
+		tx.input.iter().any(|input| input.previous_output == prev_outpoint)
+	}
+
+	fn is_pending_spend(&self, cur_height: u32) -> bool {
+		match self.status {
+			OutputSpendStatus::PendingInitialBroadcast { delayed_until_height } => {
+				delayed_until_height.map_or(true, |height| cur_height >= height)
+			},
+			OutputSpendStatus::PendingFirstConfirmation { .. } => true,
+			OutputSpendStatus::PendingThresholdConfirmations { .. } => false,
+		}
+	}
+}
+
+impl_writeable_tlv_based!(TrackedSpendableOutput, {
+	(0, descriptor, required),
+	(2, channel_id, option),
+	(4, status, required),
+});
+
+/// The current status of the output spend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputSpendStatus {
+	/// The output is tracked but an initial spending transaction hasn't been generated and
+	/// broadcasted yet.
+	PendingInitialBroadcast {
+		/// The height at which we will first generate and broadcast a spending transaction.
+		delayed_until_height: Option<u32>,
+	},
+	/// A transaction spending the output has been broadcasted but is pending its first confirmation
+	/// on-chain.
+	PendingFirstConfirmation {
+		/// The height at which we broadcasted the latest spending transaction.
+		///
+		/// If the transaction hasn't confirmed [`FEE_BUMP_INTERVAL_BLOCKS`] after this height, we
+		/// will replace it with one paying a higher feerate.
+		latest_broadcast_height: u32,
+		/// The latest spending transaction that we have broadcasted.
+		latest_spending_tx: Transaction,
+		/// The feerate (in satoshis per 1000 weight units) of the latest spending transaction.
+		latest_feerate_sat_per_1000_weight: u32,
+	},
+	/// A transaction spending the output has been confirmed on-chain but will be tracked until it
+	/// reaches [`ANTI_REORG_DELAY`] confirmations.
+	PendingThresholdConfirmations {
+		/// The transaction that spent the output and has been confirmed on-chain.
+		spending_tx: Transaction,
+		/// The height at which the spending transaction was confirmed.
+		confirmation_height: u32,
+		/// The hash of the block in which the spending transaction was confirmed.
+		confirmation_hash: BlockHash,
+		/// The feerate (in satoshis per 1000 weight units) of the latest spending transaction we
+		/// broadcasted prior to confirmation, if any.
+		///
+		/// If the spend is reorged out, any replacement has to pay a higher feerate.
+		latest_feerate_sat_per_1000_weight: Option<u32>,
+	},
+}
+
+impl_writeable_tlv_based_enum!(OutputSpendStatus,
+	(0, PendingInitialBroadcast) => {
+		(0, delayed_until_height, option),
+	},
+	(2, PendingFirstConfirmation) => {
+		(0, latest_broadcast_height, required),
+		(2, latest_spending_tx, required),
+		(4, latest_feerate_sat_per_1000_weight, required),
+	},
+	(4, PendingThresholdConfirmations) => {
+		(0, spending_tx, required),
+		(2, confirmation_height, required),
+		(4, confirmation_hash, required),
+		(6, latest_feerate_sat_per_1000_weight, option),
+	};
+);
+
+/// A utility that keeps track of [`SpendableOutputDescriptor`]s, persists them in a given
+/// [`KVStore`] and regularly retries sweeping them until they are irrevocably confirmed.
+///
+/// Users should call [`Self::track_spendable_outputs`] for any [`SpendableOutputDescriptor`]s
+/// received via [`Event::SpendableOutputs`].
+///
+/// All unconfirmed outputs are swept together in a single batch transaction paying the
+/// [`ConfirmationTarget::OutputSpendingFee`] feerate. If that transaction hasn't confirmed after
+/// [`FEE_BUMP_INTERVAL_BLOCKS`], it is replaced by one paying a higher feerate. Once a spending
+/// transaction has reached [`ANTI_REORG_DELAY`] confirmations, the output is no longer tracked.
+///
+/// The [`OutputSweeper`] needs to be notified of chain data via either the [`Listen`] or the
+/// [`Confirm`] interface. If a [`Filter`] is given, the tracked outputs will be registered with
+/// it so that their spends are included in the provided chain data.
+///
+/// [`Event::SpendableOutputs`]: crate::events::Event::SpendableOutputs
+pub struct OutputSweeper<B: Deref, D: Deref, E: Deref, F: Deref, K: Deref, L: Deref, O: Deref>
+where
+	B::Target: BroadcasterInterface,
+	D::Target: ChangeDestinationSource,
+	E::Target: FeeEstimator,
+	F::Target: Filter,
+	K::Target: KVStore,
+	L::Target: Logger,
+	O::Target: OutputSpender,
+{
+	sweeper_state: Mutex<SweeperState>,
+	broadcaster: B,
+	fee_estimator: E,
+	chain_data_source: Option<F>,
+	output_spender: O,
+	change_destination_source: D,
+	kv_store: K,
+	logger: L,
+	secp_ctx: Secp256k1<bitcoin::secp256k1::All>,
+}
+
+impl<B: Deref, D: Deref, E: Deref, F: Deref, K: Deref, L: Deref, O: Deref>
+	OutputSweeper<B, D, E, F, K, L, O>
+where
+	B::Target: BroadcasterInterface,
+	D::Target: ChangeDestinationSource,
+	E::Target: FeeEstimator,
+	F::Target: Filter,
+	K::Target: KVStore,
+	L::Target: Logger,
+	O::Target: OutputSpender,
+{
+	/// Constructs a new [`OutputSweeper`].
+	///
+	/// If chain data is provided via the [`Confirm`] interface or via filtered blocks, users also
+	/// need to register their [`Filter`] implementation via the given `chain_data_source`.
+	pub fn new(
+		best_block: BestBlock, broadcaster: B, fee_estimator: E, chain_data_source: Option<F>,
+		output_spender: O, change_destination_source: D, kv_store: K, logger: L,
+	) -> Self {
+		let outputs = Vec::new();
+		let sweeper_state = Mutex::new(SweeperState { outputs, best_block });
+		Self {
+			sweeper_state,
+			broadcaster,
+			fee_estimator,
+			chain_data_source,
+			output_spender,
+			change_destination_source,
+			kv_store,
+			logger,
+			secp_ctx: Secp256k1::new(),
+		}
+	}
+
+	/// Tells the sweeper to track the given outputs descriptors.
+	///
+	/// Usually, this should be called based on the values emitted by the
+	/// [`Event::SpendableOutputs`].
+	///
+	/// The given `exclude_static_outputs` flag controls whether the sweeper will filter out
+	/// [`SpendableOutputDescriptor::StaticOutput`]s, which may be handled directly by the on-chain
+	/// wallet implementation.
+	///
+	/// If `delay_until_height` is set, we will delay the spending until the respective block
+	/// height is reached. This can be used to batch spends, e.g., to reduce on-chain fees.
+	///
+	/// Returns `Err` on persistence failure, in which case the call may be safely retried.
+	///
+	/// [`Event::SpendableOutputs`]: crate::events::Event::SpendableOutputs
+	pub fn track_spendable_outputs(
+		&self, output_descriptors: Vec<SpendableOutputDescriptor>, channel_id: Option<ChannelId>,
+		exclude_static_outputs: bool, delay_until_height: Option<u32>,
+	) -> Result<(), ()> {
+		let mut relevant_descriptors = output_descriptors
+			.into_iter()
+			.filter(|desc| {
+				!(exclude_static_outputs &&
+					matches!(desc, SpendableOutputDescriptor::StaticOutput { .. }))
+			})
+			.peekable();
+
+		if relevant_descriptors.peek().is_none() {
+			return Ok(());
+		}
+
+		{
+			let mut state_lock = self.sweeper_state.lock().unwrap();
+			for descriptor in relevant_descriptors {
+				if state_lock.outputs.iter().any(|o| o.descriptor == descriptor) {
+					continue;
+				}
+
+				self.watch_output(&descriptor);
+				let output_info = TrackedSpendableOutput {
+					descriptor,
+					channel_id,
+					status: OutputSpendStatus::PendingInitialBroadcast {
+						delayed_until_height: delay_until_height,
+					},
+				};
+				state_lock.outputs.push(output_info);
+			}
+			self.persist_state(&state_lock).map_err(|e| {
+				log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+			})?;
+		}
+
+		self.rebroadcast_if_necessary();
+		Ok(())
+	}
+
+	/// Returns a list of the currently tracked spendable outputs.
+	pub fn tracked_spendable_outputs(&self) -> Vec<TrackedSpendableOutput> {
+		self.sweeper_state.lock().unwrap().outputs.clone()
+	}
+
+	/// Gets the latest best block which was connected either via the [`Listen`] or
+	/// [`Confirm`] interfaces.
+	pub fn current_best_block(&self) -> BestBlock {
+		self.sweeper_state.lock().unwrap().best_block
+	}
+
+	fn rebroadcast_if_necessary(&self) {
+		let mut state_lock = self.sweeper_state.lock().unwrap();
+		let cur_height = state_lock.best_block.height();
+
+		// We (re-)generate the batch spend if we have outputs which were never spent, or if the
+		// last spend hasn't confirmed in time and needs a fee bump.
+		let needs_respend = state_lock.outputs.iter().any(|o| match o.status {
+			OutputSpendStatus::PendingInitialBroadcast { .. } => o.is_pending_spend(cur_height),
+			OutputSpendStatus::PendingFirstConfirmation { latest_broadcast_height, .. } => {
+				cur_height >= latest_broadcast_height.saturating_add(FEE_BUMP_INTERVAL_BLOCKS)
+			},
+			OutputSpendStatus::PendingThresholdConfirmations { .. } => false,
+		});
+
+		if !needs_respend {
+			let mut pending_txn: Vec<&Transaction> = Vec::new();
+			for output in state_lock.outputs.iter() {
+				if let OutputSpendStatus::PendingFirstConfirmation { ref latest_spending_tx, .. } = output.status {
+					if !pending_txn.iter().any(|tx| tx.txid() == latest_spending_tx.txid()) {
+						pending_txn.push(latest_spending_tx);
+					}
+				}
+			}
+			if !pending_txn.is_empty() {
+				self.broadcaster.broadcast_transactions(&pending_txn);
+			}
+			return;
+		}
+
+		// Any replacement needs to pay more than all the transactions it conflicts with.
+		let mut feerate_sat_per_1000_weight =
+			self.fee_estimator.get_est_sat_per_1000_weight(ConfirmationTarget::OutputSpendingFee);
+		for output in state_lock.outputs.iter() {
+			if let OutputSpendStatus::PendingFirstConfirmation { latest_feerate_sat_per_1000_weight, .. } = output.status {
+				let bumped_feerate = latest_feerate_sat_per_1000_weight.saturating_add(cmp::max(
+					latest_feerate_sat_per_1000_weight / 4, MIN_FEERATE_BUMP_SAT_PER_1000_WEIGHT));
+				feerate_sat_per_1000_weight = cmp::max(feerate_sat_per_1000_weight, bumped_feerate);
+			}
+		}
+
+		let change_destination_script = match self.change_destination_source.get_change_destination_script() {
+			Ok(script) => script,
+			Err(()) => {
+				log_error!(self.logger, "Failed to retrieve a change destination script for sweeping spendable outputs");
+				return;
+			},
+		};
+
+		let respend_descriptors: Vec<&SpendableOutputDescriptor> = state_lock.outputs.iter()
+			.filter(|o| o.is_pending_spend(cur_height))
+			.map(|o| &o.descriptor)
+			.collect();
+		let locktime = LockTime::from_height(cur_height).unwrap_or(LockTime::ZERO);
+		let spending_tx = match self.output_spender.spend_spendable_outputs(
+			&respend_descriptors, Vec::new(), change_destination_script,
+			feerate_sat_per_1000_weight, Some(locktime), &self.secp_ctx,
+		) {
+			Ok(tx) => tx,
+			Err(()) => {
+				log_error!(self.logger, "Failed to spend {} tracked spendable outputs", respend_descriptors.len());
+				return;
+			},
+		};
+
+		log_info!(self.logger, "Sweeping {} spendable outputs in transaction {} at a feerate of {} sat/kW",
+			respend_descriptors.len(), spending_tx.txid(), feerate_sat_per_1000_weight);
+
+		for output in state_lock.outputs.iter_mut() {
+			if output.is_pending_spend(cur_height) {
+				output.status = OutputSpendStatus::PendingFirstConfirmation {
+					latest_broadcast_height: cur_height,
+					latest_spending_tx: spending_tx.clone(),
+					latest_feerate_sat_per_1000_weight: feerate_sat_per_1000_weight,
+				};
+			}
+		}
+
+		// Persist before broadcasting so that we never lose track of a spend we handed out.
+		if let Err(e) = self.persist_state(&state_lock) {
+			log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+			return;
+		}
+
+		self.broadcaster.broadcast_transactions(&[&spending_tx]);
+	}
+
+	fn prune_confirmed_outputs(&self, sweeper_state: &mut SweeperState) {
+		let cur_height = sweeper_state.best_block.height();
+
+		// Prune all outputs that have sufficient depth by now.
+		sweeper_state.outputs.retain(|o| {
+			if let OutputSpendStatus::PendingThresholdConfirmations { confirmation_height, ref spending_tx, .. } = o.status {
+				if cur_height >= confirmation_height + ANTI_REORG_DELAY - 1 {
+					log_debug!(self.logger, "Pruning swept output as sufficiently confirmed via spend in transaction {:?}. Pruned descriptor: {:?}",
+						spending_tx.txid(), o.descriptor
+					);
+					return false;
+				}
+			}
+			true
+		});
+	}
+
+	fn transactions_confirmed_internal(
+		&self, sweeper_state: &mut SweeperState, header: &Header, txdata: &TransactionData,
+		height: u32,
+	) {
+		let confirmation_hash = header.block_hash();
+		for (_, tx) in txdata {
+			for output in sweeper_state.outputs.iter_mut() {
+				if let OutputSpendStatus::PendingThresholdConfirmations { .. } = output.status {
+					continue;
+				}
+				if output.is_spent_in(tx) {
+					let latest_feerate_sat_per_1000_weight = match output.status {
+						OutputSpendStatus::PendingFirstConfirmation { latest_feerate_sat_per_1000_weight, .. } =>
+							Some(latest_feerate_sat_per_1000_weight),
+						_ => None,
+					};
+					output.status = OutputSpendStatus::PendingThresholdConfirmations {
+						spending_tx: (*tx).clone(),
+						confirmation_height: height,
+						confirmation_hash,
+						latest_feerate_sat_per_1000_weight,
+					};
+				}
+			}
+		}
+	}
+
+	fn unconfirm_outputs(&self, sweeper_state: &mut SweeperState, unconfirmed: impl Fn(&Transaction, &BlockHash) -> bool) {
+		let cur_height = sweeper_state.best_block.height();
+		for output in sweeper_state.outputs.iter_mut() {
+			if let OutputSpendStatus::PendingThresholdConfirmations {
+				ref spending_tx, ref confirmation_hash, latest_feerate_sat_per_1000_weight, ..
+			} = output.status {
+				if unconfirmed(spending_tx, confirmation_hash) {
+					// The spend was reorged out, so generate a fresh one on the next block. As the
+					// reorged out spend may be back in the mempool, the fresh one has to replace it,
+					// so we treat it as a spend which is due for a fee bump if it was ours.
+					output.status = match latest_feerate_sat_per_1000_weight {
+						Some(latest_feerate_sat_per_1000_weight) => OutputSpendStatus::PendingFirstConfirmation {
+							latest_broadcast_height: cur_height.saturating_sub(FEE_BUMP_INTERVAL_BLOCKS),
+							latest_spending_tx: spending_tx.clone(),
+							latest_feerate_sat_per_1000_weight,
+						},
+						None => OutputSpendStatus::PendingInitialBroadcast { delayed_until_height: None },
+					};
+				}
+			}
+		}
+	}
+
+	fn best_block_updated_internal(
+		&self, sweeper_state: &mut SweeperState, header: &Header, height: u32,
+	) {
+		sweeper_state.best_block = BestBlock::new(header.block_hash(), height);
+		self.prune_confirmed_outputs(sweeper_state);
+	}
+
+	fn watch_output(&self, descriptor: &SpendableOutputDescriptor) {
+		if let Some(filter) = self.chain_data_source.as_ref() {
+			let outpoint = descriptor.outpoint();
+			let script_pubkey = match descriptor {
+				SpendableOutputDescriptor::StaticOutput { ref output, .. } => output.script_pubkey.clone(),
+				SpendableOutputDescriptor::DelayedPaymentOutput(ref descriptor) => descriptor.output.script_pubkey.clone(),
+				SpendableOutputDescriptor::StaticPaymentOutput(ref descriptor) => descriptor.output.script_pubkey.clone(),
+			};
+			filter.register_output(WatchedOutput { block_hash: None, outpoint, script_pubkey });
+		}
+	}
+
+	fn persist_state(&self, sweeper_state: &SweeperState) -> Result<(), io::Error> {
+		self.kv_store.write(
+			OUTPUT_SWEEPER_PERSISTENCE_PRIMARY_NAMESPACE,
+			OUTPUT_SWEEPER_PERSISTENCE_SECONDARY_NAMESPACE,
+			OUTPUT_SWEEPER_PERSISTENCE_KEY,
+			&sweeper_state.encode(),
+		)
+	}
+}
+
+impl<B: Deref, D: Deref, E: Deref, F: Deref, K: Deref, L: Deref, O: Deref> Listen
+	for OutputSweeper<B, D, E, F, K, L, O>
+where
+	B::Target: BroadcasterInterface,
+	D::Target: ChangeDestinationSource,
+	E::Target: FeeEstimator,
+	F::Target: Filter,
+	K::Target: KVStore,
+	L::Target: Logger,
+	O::Target: OutputSpender,
+{
+	fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
+		{
+			let mut state_lock = self.sweeper_state.lock().unwrap();
+			assert_eq!(state_lock.best_block.block_hash(), header.prev_blockhash,
+				"Blocks must be connected in chain-order - the connected header must build on the last connected header");
+			assert_eq!(state_lock.best_block.height(), height - 1,
+				"Blocks must be connected in chain-order - the connected block height must be one greater than the previous height");
+
+			self.transactions_confirmed_internal(&mut state_lock, header, txdata, height);
+			self.best_block_updated_internal(&mut state_lock, header, height);
+			self.persist_state(&state_lock).unwrap_or_else(|e| {
+				log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+			});
+		}
+
+		self.rebroadcast_if_necessary();
+	}
+
+	fn block_disconnected(&self, header: &Header, height: u32) {
+		let mut state_lock = self.sweeper_state.lock().unwrap();
+
+		let new_height = height - 1;
+		let block_hash = header.block_hash();
+
+		assert_eq!(state_lock.best_block.block_hash(), block_hash,
+			"Blocks must be disconnected in chain-order - the disconnected header must be the last connected header");
+		assert_eq!(state_lock.best_block.height(), height,
+			"Blocks must be disconnected in chain-order - the disconnected block must have the correct height");
+		state_lock.best_block = BestBlock::new(header.prev_blockhash, new_height);
+
+		self.unconfirm_outputs(&mut state_lock, |_, confirmation_hash| *confirmation_hash == block_hash);
+
+		self.persist_state(&state_lock).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+		});
+	}
+}
+
+impl<B: Deref, D: Deref, E: Deref, F: Deref, K: Deref, L: Deref, O: Deref> Confirm
+	for OutputSweeper<B, D, E, F, K, L, O>
+where
+	B::Target: BroadcasterInterface,
+	D::Target: ChangeDestinationSource,
+	E::Target: FeeEstimator,
+	F::Target: Filter,
+	K::Target: KVStore,
+	L::Target: Logger,
+	O::Target: OutputSpender,
+{
+	fn transactions_confirmed(&self, header: &Header, txdata: &TransactionData, height: u32) {
+		let mut state_lock = self.sweeper_state.lock().unwrap();
+		self.transactions_confirmed_internal(&mut state_lock, header, txdata, height);
+		self.persist_state(&state_lock).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+		});
+	}
+
+	fn transaction_unconfirmed(&self, txid: &Txid) {
+		let mut state_lock = self.sweeper_state.lock().unwrap();
+		self.unconfirm_outputs(&mut state_lock, |spending_tx, _| spending_tx.txid() == *txid);
+		self.persist_state(&state_lock).unwrap_or_else(|e| {
+			log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+		});
+	}
+
+	fn best_block_updated(&self, header: &Header, height: u32) {
+		{
+			let mut state_lock = self.sweeper_state.lock().unwrap();
+			self.best_block_updated_internal(&mut state_lock, header, height);
+			self.persist_state(&state_lock).unwrap_or_else(|e| {
+				log_error!(self.logger, "Error persisting OutputSweeper: {:?}", e);
+			});
+		}
+
+		self.rebroadcast_if_necessary();
+	}
+
+	fn get_relevant_txids(&self) -> Vec<(Txid, u32, Option<BlockHash>)> {
+		let state_lock = self.sweeper_state.lock().unwrap();
+		let mut relevant_txids = Vec::new();
+		for output in state_lock.outputs.iter() {
+			if let OutputSpendStatus::PendingThresholdConfirmations { ref spending_tx, confirmation_height, confirmation_hash } = output.status {
+				let txid = spending_tx.txid();
+				if !relevant_txids.iter().any(|(id, _, _)| *id == txid) {
+					relevant_txids.push((txid, confirmation_height, Some(confirmation_hash)));
+				}
+			}
+		}
+		relevant_txids
+	}
+}
+
+#[derive(Debug, Clone)]
+struct SweeperState {
+	outputs: Vec<TrackedSpendableOutput>,
+	best_block: BestBlock,
+}
+
+impl_writeable_tlv_based!(SweeperState, {
+	(0, outputs, required_vec),
+	(2, best_block, required),
+});
+
+impl<B: Deref, D: Deref, E: Deref, F: Deref, K: Deref, L: Deref, O: Deref>
+	ReadableArgs<(B, E, Option<F>, O, D, K, L)> for OutputSweeper<B, D, E, F, K, L, O>
+where
+	B::Target: BroadcasterInterface,
+	D::Target: ChangeDestinationSource,
+	E::Target: FeeEstimator,
+	F::Target: Filter,
+	K::Target: KVStore,
+	L::Target: Logger,
+	O::Target: OutputSpender,
+{
+	#[inline]
+	fn read<R: io::Read>(
+		reader: &mut R, args: (B, E, Option<F>, O, D, K, L),
+	) -> Result<Self, DecodeError> {
+		let (
+			broadcaster,
+			fee_estimator,
+			chain_data_source,
+			output_spender,
+			change_destination_source,
+			kv_store,
+			logger,
+		) = args;
+		let state = SweeperState::read(reader)?;
+
+		let sweeper = Self {
+			sweeper_state: Mutex::new(SweeperState { outputs: Vec::new(), best_block: state.best_block }),
+			broadcaster,
+			fee_estimator,
+			chain_data_source,
+			output_spender,
+			change_destination_source,
+			kv_store,
+			logger,
+			secp_ctx: Secp256k1::new(),
+		};
+
+		// Make sure the chain source learns about the spends of any outputs we're still tracking.
+		for output in state.outputs.iter() {
+			sweeper.watch_output(&output.descriptor);
+		}
+		*sweeper.sweeper_state.lock().unwrap() = state;
+
+		Ok(sweeper)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::chain::chaininterface::BroadcasterInterface;
+	use crate::chain::transaction::OutPoint;
+	use crate::ln::functional_test_utils::create_dummy_block;
+	use crate::sign::{KeysManager, SignerProvider};
+	use crate::util::test_utils::{TestChainSource, TestFeeEstimator, TestLogger, TestStore};
+
+	use bitcoin::blockdata::block::Block;
+	use bitcoin::blockdata::constants::genesis_block;
+	use bitcoin::blockdata::script::ScriptBuf;
+	use bitcoin::blockdata::transaction::TxOut;
+	use bitcoin::hashes::Hash;
+	use bitcoin::network::constants::Network;
+
+	struct TestChangeDestination(ScriptBuf);
+
+	impl ChangeDestinationSource for TestChangeDestination {
+		fn get_change_destination_script(&self) -> Result<ScriptBuf, ()> {
+			Ok(self.0.clone())
+		}
+	}
+
+	struct TestSweepBroadcaster(Mutex<Vec<Transaction>>);
+
+	impl BroadcasterInterface for TestSweepBroadcaster {
+		fn broadcast_transactions(&self, txs: &[&Transaction]) {
+			self.0.lock().unwrap().extend(txs.iter().map(|tx| (*tx).clone()));
+		}
+	}
+
+	impl TestSweepBroadcaster {
+		fn txn_broadcast(&self) -> Vec<Transaction> {
+			self.0.lock().unwrap().split_off(0)
+		}
+	}
+
+	fn static_output(keys_manager: &KeysManager, idx: u16, value: u64) -> SpendableOutputDescriptor {
+		let script_pubkey = keys_manager.get_shutdown_scriptpubkey().unwrap().into_inner();
+		SpendableOutputDescriptor::StaticOutput {
+			outpoint: OutPoint { txid: Txid::from_slice(&[42; 32]).unwrap(), index: idx },
+			output: TxOut { value, script_pubkey },
+			channel_keys_id: None,
+		}
+	}
+
+	#[test]
+	fn sweeps_bumps_and_prunes_outputs() {
+		let keys_manager = KeysManager::new(&[42; 32], 42, 42);
+		let broadcaster = TestSweepBroadcaster(Mutex::new(Vec::new()));
+		let fee_estimator = TestFeeEstimator { sat_per_kw: Mutex::new(253) };
+		let chain_source = TestChainSource::new(Network::Testnet);
+		let change_destination = TestChangeDestination(ScriptBuf::new_v0_p2wpkh(&bitcoin::WPubkeyHash::all_zeros()));
+		let kv_store = TestStore::new(false);
+		let logger = TestLogger::new();
+
+		let mut blocks: Vec<Block> = vec![genesis_block(Network::Testnet)];
+		let sweeper = OutputSweeper::new(BestBlock::from_network(Network::Testnet), &broadcaster,
+			&fee_estimator, Some(&chain_source), &keys_manager, &change_destination, &kv_store, &logger);
+
+		let connect_block = |blocks: &mut Vec<Block>, txdata: Vec<Transaction>| {
+			let block = create_dummy_block(blocks.last().unwrap().block_hash(), 42, txdata);
+			sweeper.block_connected(&block, blocks.len() as u32);
+			blocks.push(block);
+		};
+
+		// Outputs delayed until a later height are only swept once we get there.
+		let first_output = static_output(&keys_manager, 0, 100_000);
+		sweeper.track_spendable_outputs(vec![first_output.clone()], None, false, Some(2)).unwrap();
+		assert!(broadcaster.txn_broadcast().is_empty());
+		assert_eq!(chain_source.watched_outputs.lock().unwrap().len(), 1);
+
+		// Static outputs may be excluded entirely, and outputs are only tracked once.
+		sweeper.track_spendable_outputs(vec![static_output(&keys_manager, 1, 50_000)], None, true, None).unwrap();
+		sweeper.track_spendable_outputs(vec![first_output.clone()], None, false, None).unwrap();
+		assert_eq!(sweeper.tracked_spendable_outputs().len(), 1);
+
+		connect_block(&mut blocks, Vec::new());
+		assert!(broadcaster.txn_broadcast().is_empty());
+
+		// Both pending outputs are batched into the same spend once the delay has passed.
+		let second_output = static_output(&keys_manager, 2, 50_000);
+		sweeper.track_spendable_outputs(vec![second_output.clone()], None, false, None).unwrap();
+		let first_spend = {
+			let txn = broadcaster.txn_broadcast();
+			assert_eq!(txn.len(), 1);
+			assert_eq!(txn[0].input.len(), 1);
+			assert_eq!(txn[0].input[0].previous_output, second_output.outpoint().into_bitcoin_outpoint());
+			txn[0].clone()
+		};
+		connect_block(&mut blocks, Vec::new());
+		let batch_spend = {
+			let txn = broadcaster.txn_broadcast();
+			assert_eq!(txn.len(), 1);
+			assert_eq!(txn[0].input.len(), 2);
+			assert_ne!(txn[0].txid(), first_spend.txid());
+			txn[0].clone()
+		};
+
+		// While unconfirmed, the spend is rebroadcast on every block until it is due for a fee bump.
+		for _ in 1..FEE_BUMP_INTERVAL_BLOCKS {
+			connect_block(&mut blocks, Vec::new());
+			assert_eq!(broadcaster.txn_broadcast(), vec![batch_spend.clone()]);
+		}
+		connect_block(&mut blocks, Vec::new());
+		let bumped_spend = {
+			let txn = broadcaster.txn_broadcast();
+			assert_eq!(txn.len(), 1);
+			assert_ne!(txn[0].txid(), batch_spend.txid());
+			assert_eq!(txn[0].input.len(), 2);
+			let input_value = 150_000;
+			let batch_fee = input_value - batch_spend.output.iter().map(|o| o.value).sum::<u64>();
+			let bumped_fee = input_value - txn[0].output.iter().map(|o| o.value).sum::<u64>();
+			assert!(bumped_fee > batch_fee);
+			txn[0].clone()
+		};
+
+		// The original batch confirms after all, which is just as good.
+		connect_block(&mut blocks, vec![batch_spend.clone()]);
+		let confirmation_height = blocks.len() as u32 - 1;
+		assert!(broadcaster.txn_broadcast().is_empty());
+		assert_eq!(sweeper.get_relevant_txids(), vec![(batch_spend.txid(), confirmation_height, Some(blocks.last().unwrap().block_hash()))]);
+
+		// A reorg unconfirms the spend, so we generate a fresh one on the next block, paying a
+		// higher feerate than any spend we broadcasted before so that it can replace the reorged
+		// out one.
+		let disconnected = blocks.pop().unwrap();
+		sweeper.block_disconnected(&disconnected.header, confirmation_height);
+		assert!(sweeper.tracked_spendable_outputs().iter().all(|o| matches!(o.status,
+			OutputSpendStatus::PendingFirstConfirmation { ref latest_spending_tx, .. } if *latest_spending_tx == batch_spend)));
+		connect_block(&mut blocks, Vec::new());
+		let respend = {
+			let txn = broadcaster.txn_broadcast();
+			assert_eq!(txn.len(), 1);
+			assert_eq!(txn[0].input.len(), 2);
+			let input_value = 150_000;
+			let bumped_fee = input_value - bumped_spend.output.iter().map(|o| o.value).sum::<u64>();
+			let respend_fee = input_value - txn[0].output.iter().map(|o| o.value).sum::<u64>();
+			assert!(respend_fee > bumped_fee);
+			txn[0].clone()
+		};
+		assert_ne!(respend.txid(), bumped_spend.txid());
+
+		// Once the spend reaches ANTI_REORG_DELAY confirmations we stop tracking the outputs.
+		connect_block(&mut blocks, vec![respend.clone()]);
+		for _ in 0..ANTI_REORG_DELAY - 2 {
+			connect_block(&mut blocks, Vec::new());
+			assert_eq!(sweeper.tracked_spendable_outputs().len(), 2);
+		}
+
+		// The persisted state is read back as-is.
+		let persisted = kv_store.read(OUTPUT_SWEEPER_PERSISTENCE_PRIMARY_NAMESPACE,
+			OUTPUT_SWEEPER_PERSISTENCE_SECONDARY_NAMESPACE, OUTPUT_SWEEPER_PERSISTENCE_KEY).unwrap();
+		let reloaded_chain_source = TestChainSource::new(Network::Testnet);
+		let reloaded: OutputSweeper<_, _, _, _, _, _, _> = OutputSweeper::read(&mut &persisted[..],
+			(&broadcaster, &fee_estimator, Some(&reloaded_chain_source), &keys_manager,
+			&change_destination, &kv_store, &logger)).unwrap();
+		assert_eq!(reloaded.tracked_spendable_outputs(), sweeper.tracked_spendable_outputs());
+		assert_eq!(reloaded.current_best_block(), sweeper.current_best_block());
+		assert_eq!(reloaded_chain_source.watched_outputs.lock().unwrap().len(), 2);
+
+		connect_block(&mut blocks, Vec::new());
+		assert!(sweeper.tracked_spendable_outputs().is_empty());
+		assert!(broadcaster.txn_broadcast().is_empty());
+	}
+}
@@ -7,6 +7,7 @@
 // You may not use this file except in accordance with one or both of these
 // licenses.
 
+use crate::chain::transaction::OutPoint;
 use crate::ln::channel::{ANCHOR_OUTPUT_VALUE_SATOSHI, MIN_CHAN_DUST_LIMIT_SATOSHIS};
 use crate::ln::chan_utils::{HTLCOutputInCommitment, ChannelPublicKeys, HolderCommitmentTransaction, CommitmentTransaction, ChannelTransactionParameters, TrustedCommitmentTransaction, ClosingTransaction};
 use crate::ln::channel_keys::{HtlcKey};
@@ -163,6 +164,10 @@ impl ChannelSigner for TestChannelSigner {
 	fn provide_channel_parameters(&mut self, channel_parameters: &ChannelTransactionParameters) {
 		self.inner.provide_channel_parameters(channel_parameters)
 	}
+
+	fn provide_renegotiated_funding(&mut self, funding_outpoint: OutPoint, channel_value_satoshis: u64) {
+		self.inner.provide_renegotiated_funding(funding_outpoint, channel_value_satoshis)
+	}
 }
 
 impl EcdsaChannelSigner for TestChannelSigner {
@@ -263,6 +268,23 @@ impl EcdsaChannelSigner for TestChannelSigner {
 		Ok(self.inner.sign_closing_transaction(closing_tx, secp_ctx).unwrap())
 	}
 
+	fn sign_splice_counterparty_commitment(&self, commitment_tx: &CommitmentTransaction, channel_value_satoshis: u64, secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()> {
+		if !*self.available.lock().unwrap() {
+			return Err(());
+		}
+		let state = self.state.lock().unwrap();
+		// The splice's commitment transaction replaces the counterparty's current one.
+		assert_eq!(commitment_tx.commitment_number(), state.last_counterparty_commitment);
+		self.inner.sign_splice_counterparty_commitment(commitment_tx, channel_value_satoshis, secp_ctx)
+	}
+
+	fn sign_splice_shared_input(&self, splice_tx: &Transaction, input_index: usize, secp_ctx: &Secp256k1<secp256k1::All>) -> Result<Signature, ()> {
+		if !*self.available.lock().unwrap() {
+			return Err(());
+		}
+		self.inner.sign_splice_shared_input(splice_tx, input_index, secp_ctx)
+	}
+
 	fn sign_holder_anchor_input(
 		&self, anchor_tx: &Transaction, input: usize, secp_ctx: &Secp256k1<secp256k1::All>,
 	) -> Result<Signature, ()> {
@@ -29,9 +29,11 @@ use crate::ln::chan_utils::CommitmentTransaction;
 use crate::ln::features::{ChannelFeatures, InitFeatures, NodeFeatures};
 use crate::ln::{msgs, wire};
 use crate::ln::msgs::LightningError;
+use crate::ln::our_peer_storage::PeerStorageMonitorHolder;
 use crate::ln::script::ShutdownScript;
 use crate::offers::invoice::{BlindedPayInfo, UnsignedBolt12Invoice};
 use crate::offers::invoice_request::UnsignedInvoiceRequest;
+use crate::offers::offer::{CurrencyCode, CurrencyConversion};
 use crate::onion_message::messenger::{Destination, MessageRouter, OnionMessagePath};
 use crate::routing::gossip::{EffectiveCapacity, NetworkGraph, NodeId, RoutingFees};
 use crate::routing::utxo::{UtxoLookup, UtxoLookupError, UtxoResult};
@@ -41,6 +43,7 @@ use crate::sync::RwLock;
 use crate::util::config::UserConfig;
 use crate::util::test_channel_signer::{TestChannelSigner, EnforcementState};
 use crate::util::logger::{Logger, Level, Record};
+use crate::util::metrics::MetricsRecorder;
 use crate::util::ser::{Readable, ReadableArgs, Writer, Writeable};
 use crate::util::persist::KVStore;
 
@@ -196,10 +199,11 @@ impl<'a> Router for TestRouter<'a> {
 	fn create_blinded_payment_paths<
 		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
 	>(
-		&self, _recipient: PublicKey, _first_hops: Vec<ChannelDetails>, _tlvs: ReceiveTlvs,
-		_amount_msats: u64, _entropy_source: &ES, _secp_ctx: &Secp256k1<T>
+		&self, recipient: PublicKey, _first_hops: Vec<ChannelDetails>, tlvs: ReceiveTlvs,
+		_amount_msats: u64, entropy_source: &ES, secp_ctx: &Secp256k1<T>
 	) -> Result<Vec<(BlindedPayInfo, BlindedPath)>, ()> {
-		unreachable!()
+		BlindedPath::one_hop_for_payment(recipient, tlvs, entropy_source, secp_ctx)
+			.map(|path| vec![path])
 	}
 }
 
@@ -213,10 +217,11 @@ impl<'a> MessageRouter for TestRouter<'a> {
 	fn create_blinded_paths<
 		ES: EntropySource + ?Sized, T: secp256k1::Signing + secp256k1::Verification
 	>(
-		&self, _recipient: PublicKey, _peers: Vec<PublicKey>, _entropy_source: &ES,
-		_secp_ctx: &Secp256k1<T>
+		&self, recipient: PublicKey, _peers: Vec<PublicKey>, entropy_source: &ES,
+		secp_ctx: &Secp256k1<T>
 	) -> Result<Vec<BlindedPath>, ()> {
-		unreachable!()
+		BlindedPath::one_hop_for_message(recipient, entropy_source, secp_ctx)
+			.map(|path| vec![path])
 	}
 }
 
@@ -264,7 +269,7 @@ pub struct TestChainMonitor<'a> {
 	pub added_monitors: Mutex<Vec<(OutPoint, channelmonitor::ChannelMonitor<TestChannelSigner>)>>,
 	pub monitor_updates: Mutex<HashMap<ChannelId, Vec<channelmonitor::ChannelMonitorUpdate>>>,
 	pub latest_monitor_update_id: Mutex<HashMap<ChannelId, (OutPoint, u64, MonitorUpdateId)>>,
-	pub chain_monitor: chainmonitor::ChainMonitor<TestChannelSigner, &'a TestChainSource, &'a dyn chaininterface::BroadcasterInterface, &'a TestFeeEstimator, &'a TestLogger, &'a dyn chainmonitor::Persist<TestChannelSigner>>,
+	pub chain_monitor: chainmonitor::ChainMonitor<TestChannelSigner, &'a TestChainSource, &'a dyn chaininterface::BroadcasterInterface, &'a TestFeeEstimator, &'a TestLogger, &'a dyn chainmonitor::Persist<TestChannelSigner>, Arc<TestMetricsRecorder>>,
 	pub keys_manager: &'a TestKeysInterface,
 	/// If this is set to Some(), the next update_channel call (not watch_channel) must be a
 	/// ChannelForceClosed event for the given channel_id with should_broadcast set to the given
@@ -273,17 +278,20 @@ pub struct TestChainMonitor<'a> {
 	/// If this is set to Some(), the next round trip serialization check will not hold after an
 	/// update_channel call (not watch_channel) for the given channel_id.
 	pub expect_monitor_round_trip_fail: Mutex<Option<ChannelId>>,
+	pub metrics: Arc<TestMetricsRecorder>,
 }
 impl<'a> TestChainMonitor<'a> {
 	pub fn new(chain_source: Option<&'a TestChainSource>, broadcaster: &'a dyn chaininterface::BroadcasterInterface, logger: &'a TestLogger, fee_estimator: &'a TestFeeEstimator, persister: &'a dyn chainmonitor::Persist<TestChannelSigner>, keys_manager: &'a TestKeysInterface) -> Self {
+		let metrics = Arc::new(TestMetricsRecorder::new());
 		Self {
 			added_monitors: Mutex::new(Vec::new()),
 			monitor_updates: Mutex::new(HashMap::new()),
 			latest_monitor_update_id: Mutex::new(HashMap::new()),
-			chain_monitor: chainmonitor::ChainMonitor::new(chain_source, broadcaster, logger, fee_estimator, persister),
+			chain_monitor: chainmonitor::ChainMonitor::new(chain_source, broadcaster, logger, fee_estimator, persister, Arc::clone(&metrics)),
 			keys_manager,
 			expect_channel_force_closed: Mutex::new(None),
 			expect_monitor_round_trip_fail: Mutex::new(None),
+			metrics,
 		}
 	}
 
@@ -347,6 +355,10 @@ impl<'a> chain::Watch<TestChannelSigner> for TestChainMonitor<'a> {
 	fn release_pending_monitor_events(&self) -> Vec<(OutPoint, Vec<MonitorEvent>, Option<PublicKey>)> {
 		return self.chain_monitor.release_pending_monitor_events();
 	}
+
+	fn get_peer_storage_snapshot(&self) -> Vec<PeerStorageMonitorHolder> {
+		self.chain_monitor.get_peer_storage_snapshot()
+	}
 }
 
 struct JusticeTxData {
@@ -643,6 +655,7 @@ pub struct TestChannelMessageHandler {
 	pub pending_events: Mutex<Vec<events::MessageSendEvent>>,
 	expected_recv_msgs: Mutex<Option<Vec<wire::Message<()>>>>,
 	connected_peers: Mutex<HashSet<PublicKey>>,
+	pub peers_with_channels: Mutex<HashSet<PublicKey>>,
 	pub message_fetch_counter: AtomicUsize,
 	chain_hash: ChainHash,
 }
@@ -653,6 +666,7 @@ impl TestChannelMessageHandler {
 			pending_events: Mutex::new(Vec::new()),
 			expected_recv_msgs: Mutex::new(None),
 			connected_peers: Mutex::new(HashSet::new()),
+			peers_with_channels: Mutex::new(HashSet::new()),
 			message_fetch_counter: AtomicUsize::new(0),
 			chain_hash,
 		}
@@ -709,6 +723,12 @@ impl msgs::ChannelMessageHandler for TestChannelMessageHandler {
 	fn handle_closing_signed(&self, _their_node_id: &PublicKey, msg: &msgs::ClosingSigned) {
 		self.received_msg(wire::Message::ClosingSigned(msg.clone()));
 	}
+	fn handle_peer_storage(&self, _their_node_id: &PublicKey, msg: &msgs::PeerStorage) {
+		self.received_msg(wire::Message::PeerStorage(msg.clone()));
+	}
+	fn handle_peer_storage_retrieval(&self, _their_node_id: &PublicKey, msg: &msgs::PeerStorageRetrieval) {
+		self.received_msg(wire::Message::PeerStorageRetrieval(msg.clone()));
+	}
 	fn handle_stfu(&self, _their_node_id: &PublicKey, msg: &msgs::Stfu) {
 		self.received_msg(wire::Message::Stfu(msg.clone()));
 	}
@@ -774,6 +794,10 @@ impl msgs::ChannelMessageHandler for TestChannelMessageHandler {
 		Some(vec![self.chain_hash])
 	}
 
+	fn peer_has_channels(&self, their_node_id: &PublicKey) -> bool {
+		self.peers_with_channels.lock().unwrap().contains(their_node_id)
+	}
+
 	fn handle_open_channel_v2(&self, _their_node_id: &PublicKey, msg: &msgs::OpenChannelV2) {
 		self.received_msg(wire::Message::OpenChannelV2(msg.clone()));
 	}
@@ -949,6 +973,8 @@ impl msgs::RoutingMessageHandler for TestRoutingMessageHandler {
 		Ok(())
 	}
 
+	fn peer_disconnected(&self, _their_node_id: &PublicKey) {}
+
 	fn handle_reply_channel_range(&self, _their_node_id: &PublicKey, _msg: msgs::ReplyChannelRange) -> Result<(), msgs::LightningError> {
 		Ok(())
 	}
@@ -965,6 +991,10 @@ impl msgs::RoutingMessageHandler for TestRoutingMessageHandler {
 		Ok(())
 	}
 
+	fn get_next_short_channel_ids_reply(&self, _their_node_id: &PublicKey) -> Option<msgs::ShortChannelIdsReply> {
+		None
+	}
+
 	fn provided_node_features(&self) -> NodeFeatures {
 		let mut features = NodeFeatures::empty();
 		features.set_gossip_queries_optional();
@@ -989,6 +1019,67 @@ impl events::MessageSendEventsProvider for TestRoutingMessageHandler {
 	}
 }
 
+type TestMetricKey = (&'static str, Vec<(&'static str, String)>);
+
+pub struct TestMetricsRecorder {
+	counters: Mutex<HashMap<TestMetricKey, u64>>,
+	histogram_observations: Mutex<HashMap<TestMetricKey, usize>>,
+}
+
+impl TestMetricsRecorder {
+	pub fn new() -> Self {
+		Self { counters: Mutex::new(HashMap::new()), histogram_observations: Mutex::new(HashMap::new()) }
+	}
+
+	fn key(name: &'static str, labels: &[(&'static str, &str)]) -> TestMetricKey {
+		(name, labels.iter().map(|(k, v)| (*k, v.to_string())).collect())
+	}
+
+	/// Returns the current value of the counter `name` with the given `labels`.
+	pub fn counter(&self, name: &'static str, labels: &[(&'static str, &str)]) -> u64 {
+		*self.counters.lock().unwrap().get(&Self::key(name, labels)).unwrap_or(&0)
+	}
+
+	/// Returns the number of observations recorded in the histogram `name` with the given `labels`.
+	pub fn histogram_observations(&self, name: &'static str, labels: &[(&'static str, &str)]) -> usize {
+		*self.histogram_observations.lock().unwrap().get(&Self::key(name, labels)).unwrap_or(&0)
+	}
+}
+
+impl MetricsRecorder for TestMetricsRecorder {
+	fn increment_counter(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64) {
+		*self.counters.lock().unwrap().entry(Self::key(name, labels)).or_insert(0) += value;
+	}
+
+	fn record_histogram(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+		assert!(value >= 0.0);
+		*self.histogram_observations.lock().unwrap().entry(Self::key(name, labels)).or_insert(0) += 1;
+	}
+}
+
+/// A [`CurrencyConversion`] with exchange rates, in millisatoshis per unit of each currency, set
+/// by the test. Currencies without a rate are unsupported.
+pub struct TestCurrencyConversion {
+	rates: Mutex<HashMap<CurrencyCode, u64>>,
+}
+
+impl TestCurrencyConversion {
+	pub fn new() -> Self {
+		Self { rates: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn set_rate(&self, iso4217_code: CurrencyCode, msats_per_unit: u64) {
+		self.rates.lock().unwrap().insert(iso4217_code, msats_per_unit);
+	}
+}
+
+impl CurrencyConversion for TestCurrencyConversion {
+	fn convert_to_msats(&self, iso4217_code: CurrencyCode, amount: u64) -> Result<u64, ()> {
+		let rate = *self.rates.lock().unwrap().get(&iso4217_code).ok_or(())?;
+		amount.checked_mul(rate).ok_or(())
+	}
+}
+
 pub struct TestLogger {
 	level: Level,
 	pub(crate) id: String,
@@ -1078,6 +1169,10 @@ impl NodeSigner for TestNodeSigner {
 		unreachable!()
 	}
 
+	fn get_peer_storage_key(&self) -> crate::sign::PeerStorageKey {
+		unreachable!()
+	}
+
 	fn get_node_id(&self, recipient: Recipient) -> Result<PublicKey, ()> {
 		let node_secret = match recipient {
 			Recipient::Node => Ok(&self.node_secret),
@@ -1149,6 +1244,10 @@ impl NodeSigner for TestKeysInterface {
 		self.backing.get_inbound_payment_key_material()
 	}
 
+	fn get_peer_storage_key(&self) -> sign::PeerStorageKey {
+		self.backing.get_peer_storage_key()
+	}
+
 	fn sign_invoice(&self, hrp_bytes: &[u8], invoice_data: &[u5], recipient: Recipient) -> Result<RecoverableSignature, ()> {
 		self.backing.sign_invoice(hrp_bytes, invoice_data, recipient)
 	}